@@ -0,0 +1,655 @@
+//! `probing agentd`, one collector for the probes of a host.
+//!
+//! Every probe serves its own unix socket, so a host running many training
+//! processes runs as many servers. `agentd` attaches to several processes,
+//! injecting the probe where it is missing, and serves all of them on one
+//! HTTP port:
+//!
+//! - `POST /query` runs SQL across the processes. The tables of process
+//!   `<pid>` are the catalog `pid<pid>`, e.g. `pid1234.python.backtrace`, and
+//!   `agentd.targets` lists the processes and their state;
+//! - `/pid/<pid>/<path>` forwards any other API call to the socket of `<pid>`.
+//!
+//! ```bash
+//! $ probing agentd --pid 1234,1235
+//! $ probing -t 127.0.0.1:9700 query "SELECT pid, status FROM agentd.targets"
+//! $ probing -t 127.0.0.1:9700 query "SELECT * FROM pid1234.python.backtrace"
+//! ```
+//!
+//! Without `--pid`, every probe registered on the host is followed, including
+//! those started later. The tables of a process are fetched from its probe
+//! when a query reads them.
+//!
+//! Listening on anything but a loopback address requires `--token` (or
+//! `PROBING_AUTH_TOKEN`): every request must then carry
+//! `Authorization: Bearer <token>`. Only the managed processes are reachable
+//! through `/pid/<pid>/<path>`.
+
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Args;
+use datafusion::catalog::{CatalogProvider, SchemaProvider};
+use datafusion::datasource::{MemTable, TableProvider};
+use datafusion::error::DataFusionError;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use once_cell::sync::Lazy;
+use probing_client::{Client, Endpoint};
+use probing_core::core::{
+    dataframe_to_record_batch, CustomTable, DataType, Engine, Field, Int64Array, RecordBatch,
+    Schema, SchemaRef, StringArray, TablePluginHelper,
+};
+use probing_proto::prelude::{Ele, ErrorCode, Message, Query, QueryDataFormat, QueryError};
+
+use super::ctrl::ProbeEndpoint;
+use super::inject::InjectCommand;
+
+/// Serve the probes of several local processes on one HTTP port
+#[derive(Args, Debug, Clone)]
+pub struct AgentdCommand {
+    /// Address to serve on
+    #[arg(long, default_value = "127.0.0.1:9700")]
+    listen: String,
+
+    /// Processes to attach to, injecting the probe where needed; every
+    /// probe registered on the host when empty
+    #[arg(short, long, value_delimiter = ',')]
+    pid: Vec<i32>,
+
+    /// Seconds between two refreshes of the processes and their tables
+    #[arg(long, default_value_t = 5)]
+    interval: u64,
+
+    /// Bearer token required from clients, mandatory unless listening on a
+    /// loopback address
+    #[arg(long, env = "PROBING_AUTH_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+}
+
+/// State of a process, as served by `agentd.targets`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TargetState {
+    pub pid: i32,
+    /// `ok`, `unreachable` or `gone`
+    pub status: &'static str,
+    pub cmd: String,
+    /// Tables the probe serves
+    pub tables: usize,
+    pub error: Option<String>,
+    /// Microseconds since the epoch
+    pub updated: i64,
+}
+
+static TARGETS: Lazy<RwLock<BTreeMap<i32, TargetState>>> = Lazy::new(Default::default);
+
+/// Catalog holding the tables of process `pid`
+pub fn catalog_name(pid: i32) -> String {
+    format!("pid{pid}")
+}
+
+/// `agentd.targets`: the processes served by agentd
+#[derive(Default, Debug)]
+pub struct TargetsTable {}
+
+impl CustomTable for TargetsTable {
+    fn name() -> &'static str {
+        "targets"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("pid", DataType::Int64, false),
+            Field::new("catalog", DataType::Utf8, false),
+            Field::new("status", DataType::Utf8, false),
+            Field::new("cmd", DataType::Utf8, false),
+            Field::new("tables", DataType::Int64, false),
+            Field::new("error", DataType::Utf8, true),
+            Field::new("updated", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let targets = TARGETS.read().unwrap();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(
+                    targets.values().map(|t| t.pid as i64),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    targets.values().map(|t| catalog_name(t.pid)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    targets.values().map(|t| t.status),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    targets.values().map(|t| &t.cmd),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    targets.values().map(|t| t.tables as i64),
+                )),
+                Arc::new(StringArray::from_iter(
+                    targets.values().map(|t| t.error.as_deref()),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    targets.values().map(|t| t.updated),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build agentd.targets: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type TargetsPlugin = TablePluginHelper<TargetsTable>;
+
+/// Tables of one process, by namespace, as listed at the last refresh
+#[derive(Debug)]
+struct RemoteCatalog {
+    client: Client,
+    schemas: RwLock<BTreeMap<String, Vec<String>>>,
+}
+
+impl CatalogProvider for RemoteCatalog {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema_names(&self) -> Vec<String> {
+        self.schemas.read().unwrap().keys().cloned().collect()
+    }
+
+    fn schema(&self, name: &str) -> Option<Arc<dyn SchemaProvider>> {
+        let tables = self.schemas.read().unwrap().get(name)?.clone();
+        Some(Arc::new(RemoteSchema {
+            client: self.client.clone(),
+            name: name.to_string(),
+            tables,
+        }))
+    }
+}
+
+/// A namespace of a process, whose tables are fetched when read
+#[derive(Debug)]
+struct RemoteSchema {
+    client: Client,
+    name: String,
+    tables: Vec<String>,
+}
+
+#[async_trait]
+impl SchemaProvider for RemoteSchema {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.tables.clone()
+    }
+
+    async fn table(&self, name: &str) -> datafusion::error::Result<Option<Arc<dyn TableProvider>>> {
+        if !self.table_exist(name) {
+            return Ok(None);
+        }
+        let expr = format!("SELECT * FROM {}.{}", quote(&self.name), quote(name));
+        let df = self
+            .client
+            .query(Query::new(expr))
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let batch = dataframe_to_record_batch(&df)?;
+        let table = MemTable::try_new(batch.schema(), vec![vec![batch]])?;
+        Ok(Some(Arc::new(table)))
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        self.tables.iter().any(|t| t == name)
+    }
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Collector serving the probes of the host
+struct Agent {
+    engine: Engine,
+    /// Processes given with `--pid`, empty to follow the registered probes
+    pids: Vec<i32>,
+    /// Bearer token expected from clients
+    token: Option<String>,
+    catalogs: Mutex<HashMap<i32, Arc<RemoteCatalog>>>,
+}
+
+impl Agent {
+    /// Attach to new processes and list the tables of every process
+    async fn refresh(&self) {
+        let pids = if self.pids.is_empty() {
+            match probing_client::registered() {
+                Ok(registered) => registered
+                    .into_iter()
+                    .map(|r| r.pid)
+                    .filter(|pid| *pid != std::process::id() as i32)
+                    .collect(),
+                Err(e) => {
+                    log::warn!("failed to list the probes of the host: {e}");
+                    return;
+                }
+            }
+        } else {
+            self.pids.clone()
+        };
+
+        let mut states = BTreeMap::new();
+        for &pid in &pids {
+            states.insert(pid, self.refresh_target(pid).await);
+        }
+
+        let mut targets = TARGETS.write().unwrap();
+        for (pid, state) in targets.iter_mut() {
+            if !states.contains_key(pid) && state.status != "gone" {
+                log::info!("process {pid} is gone");
+                state.status = "gone";
+                state.tables = 0;
+            }
+        }
+        targets.extend(states);
+        // queries against processes that cannot be reached fail at planning
+        let catalogs = self.catalogs.lock().unwrap();
+        for (pid, state) in targets.iter() {
+            if let (Some(catalog), false) = (catalogs.get(pid), state.status == "ok") {
+                catalog.schemas.write().unwrap().clear();
+            }
+        }
+    }
+
+    async fn refresh_target(&self, pid: i32) -> TargetState {
+        let mut state = TargetState {
+            pid,
+            status: "ok",
+            cmd: std::fs::read_to_string(format!("/proc/{pid}/cmdline"))
+                .map(|cmd| cmd.replace('\0', " ").trim().to_string())
+                .unwrap_or_default(),
+            updated: chrono::Utc::now().timestamp_micros(),
+            ..Default::default()
+        };
+        if !std::path::Path::new(&format!("/proc/{pid}")).exists() {
+            state.status = "gone";
+            return state;
+        }
+        match self.list_tables(pid).await {
+            Ok(schemas) => {
+                state.tables = schemas.values().map(Vec::len).sum();
+                *self.catalog(pid).schemas.write().unwrap() = schemas;
+            }
+            Err(e) => {
+                state.status = "unreachable";
+                state.error = Some(format!("{e:#}"));
+            }
+        }
+        state
+    }
+
+    async fn list_tables(&self, pid: i32) -> Result<BTreeMap<String, Vec<String>>> {
+        let inject = InjectCommand::default();
+        if !self.pids.is_empty() && !inject.check_library(pid, "libprobing.so")? {
+            inject.run(ProbeEndpoint::Local { pid }).await?;
+        }
+        let df = Client::new(Endpoint::Local { pid })
+            .query(Query::new(
+                "SELECT table_schema, table_name FROM information_schema.tables \
+                 WHERE table_schema != 'information_schema'"
+                    .to_string(),
+            ))
+            .await?;
+        let mut schemas = BTreeMap::<String, Vec<String>>::new();
+        for row in df.iter() {
+            if let [Ele::Text(schema), Ele::Text(table)] = row.as_slice() {
+                schemas
+                    .entry(schema.clone())
+                    .or_default()
+                    .push(table.clone());
+            }
+        }
+        Ok(schemas)
+    }
+
+    /// Catalog of `pid`, registered with the engine on first use
+    fn catalog(&self, pid: i32) -> Arc<RemoteCatalog> {
+        self.catalogs
+            .lock()
+            .unwrap()
+            .entry(pid)
+            .or_insert_with(|| {
+                let catalog = Arc::new(RemoteCatalog {
+                    client: Client::new(Endpoint::Local { pid }),
+                    schemas: Default::default(),
+                });
+                self.engine
+                    .context
+                    .register_catalog(catalog_name(pid), catalog.clone());
+                catalog
+            })
+            .clone()
+    }
+
+    async fn query(&self, body: &[u8]) -> Message<QueryDataFormat> {
+        let request = match serde_json::from_slice::<Message<Query>>(body) {
+            Ok(request) => request.payload,
+            Err(e) => {
+                return Message::new(QueryDataFormat::Error(QueryError::new(
+                    ErrorCode::ParseError,
+                    format!("Invalid request format: {e}"),
+                )))
+            }
+        };
+        let reply = match self.engine.async_query(request.expr.as_str()).await {
+            Ok(Some(df)) => QueryDataFormat::DataFrame(df),
+            Ok(None) => QueryDataFormat::Nil,
+            Err(e) => {
+                log::error!("Error executing query '{}': {e}", request.expr);
+                QueryDataFormat::Error(self.engine.query_error(&e))
+            }
+        };
+        Message::new(reply)
+    }
+
+    /// Whether `pid` is one of the processes served: those given with
+    /// `--pid`, or the registered probes that are not gone
+    fn manages(&self, pid: i32) -> bool {
+        if !self.pids.is_empty() {
+            return self.pids.contains(&pid);
+        }
+        TARGETS
+            .read()
+            .unwrap()
+            .get(&pid)
+            .is_some_and(|t| t.status != "gone")
+    }
+
+    async fn handle(&self, req: Request<Incoming>) -> Response<Full<Bytes>> {
+        if !authorized(self.token.as_deref(), req.headers()) {
+            return reply(
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid token".to_string(),
+            );
+        }
+        let method = req.method().clone();
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_default();
+        let body = match req.into_body().collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(e) => return reply(StatusCode::BAD_REQUEST, e.to_string()),
+        };
+
+        if method == Method::POST && path == "/query" {
+            return match serde_json::to_string(&self.query(&body).await) {
+                Ok(json) => reply(StatusCode::OK, json),
+                Err(e) => reply(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            };
+        }
+        if path == "/apis/targets" {
+            let targets = TARGETS
+                .read()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            let targets = targets
+                .iter()
+                .map(|t| {
+                    serde_json::json!({
+                        "pid": t.pid,
+                        "catalog": catalog_name(t.pid),
+                        "status": t.status,
+                        "cmd": t.cmd,
+                        "tables": t.tables,
+                        "error": t.error,
+                    })
+                })
+                .collect::<Vec<_>>();
+            return reply(StatusCode::OK, serde_json::Value::from(targets).to_string());
+        }
+        if let Some((pid, rest)) = forwarded(&path) {
+            if !self.manages(pid) {
+                return reply(
+                    StatusCode::FORBIDDEN,
+                    format!("process {pid} is not served"),
+                );
+            }
+            return forward(pid, &rest, &method, body).await;
+        }
+        reply(StatusCode::NOT_FOUND, format!("no route for {path}"))
+    }
+}
+
+/// Whether the request carries `Authorization: Bearer <token>`, always when
+/// no token is required
+fn authorized(token: Option<&str>, headers: &hyper::HeaderMap) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| given == token)
+}
+
+/// Pid and path of a request forwarded with `/pid/<pid>/<path>`
+fn forwarded(path: &str) -> Option<(i32, String)> {
+    let rest = path.strip_prefix("/pid/")?;
+    let (pid, rest) = match rest.find(['/', '?']) {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, ""),
+    };
+    let rest = match rest.strip_prefix('?') {
+        Some(query) => format!("/?{query}"),
+        None if rest.is_empty() => "/".to_string(),
+        None => rest.to_string(),
+    };
+    Some((pid.parse().ok()?, rest))
+}
+
+async fn forward(pid: i32, path: &str, method: &Method, body: Bytes) -> Response<Full<Bytes>> {
+    let body = (method == Method::POST).then(|| String::from_utf8_lossy(&body).into_owned());
+    let response = match Client::new(Endpoint::Local { pid }).send(path, body).await {
+        Ok(response) => response,
+        Err(e) => return reply(StatusCode::BAD_GATEWAY, e.to_string()),
+    };
+    let status = response.status();
+    let content_type = response.headers().get(hyper::header::CONTENT_TYPE).cloned();
+    let body = match response.collect().await {
+        Ok(body) => body.to_bytes(),
+        Err(e) => return reply(StatusCode::BAD_GATEWAY, e.to_string()),
+    };
+    let mut response = Response::new(Full::new(body));
+    *response.status_mut() = status;
+    if let Some(content_type) = content_type {
+        response
+            .headers_mut()
+            .insert(hyper::header::CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+fn reply(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response
+}
+
+impl AgentdCommand {
+    pub async fn run(&self) -> Result<()> {
+        let engine = Engine::builder()
+            .with_default_namespace("agentd")
+            .with_plugin(TargetsPlugin::create("agentd", TargetsTable::name()))
+            .build()
+            .await?;
+        let agent = Arc::new(Agent {
+            engine,
+            pids: self.pid.clone(),
+            token: self.token.clone().filter(|token| !token.is_empty()),
+            catalogs: Default::default(),
+        });
+
+        let listener = tokio::net::TcpListener::bind(&self.listen).await?;
+        let addr = listener.local_addr()?;
+        if agent.token.is_none() && !addr.ip().is_loopback() {
+            anyhow::bail!("--token (or PROBING_AUTH_TOKEN) is required to listen on {addr}");
+        }
+        eprintln!("probing agentd is available on {addr}");
+
+        let refresher = agent.clone();
+        let interval = Duration::from_secs(self.interval.max(1));
+        tokio::spawn(async move {
+            loop {
+                refresher.refresh().await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let agent = agent.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let agent = agent.clone();
+                    async move { Ok::<_, Infallible>(agent.handle(req).await) }
+                });
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    log::debug!("connection from {peer} closed: {e}");
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forwarded_paths() {
+        assert_eq!(
+            forwarded("/pid/12/apis/overview"),
+            Some((12, "/apis/overview".to_string()))
+        );
+        assert_eq!(
+            forwarded("/pid/12?limit=1"),
+            Some((12, "/?limit=1".to_string()))
+        );
+        assert_eq!(forwarded("/pid/12"), Some((12, "/".to_string())));
+        assert_eq!(forwarded("/pid/abc/query"), None);
+        assert_eq!(forwarded("/query"), None);
+    }
+
+    #[test]
+    fn test_authorized() {
+        let mut headers = hyper::HeaderMap::new();
+        assert!(authorized(None, &headers));
+        assert!(!authorized(Some("secret"), &headers));
+        headers.insert(
+            hyper::header::AUTHORIZATION,
+            "Bearer other".parse().unwrap(),
+        );
+        assert!(!authorized(Some("secret"), &headers));
+        headers.insert(
+            hyper::header::AUTHORIZATION,
+            "Bearer secret".parse().unwrap(),
+        );
+        assert!(authorized(Some("secret"), &headers));
+    }
+
+    #[tokio::test]
+    async fn test_manages() {
+        let engine = Engine::builder().build().await.unwrap();
+        let agent = Agent {
+            engine,
+            pids: vec![11, 12],
+            token: None,
+            catalogs: Default::default(),
+        };
+        assert!(agent.manages(11));
+        assert!(!agent.manages(13));
+
+        TARGETS.write().unwrap().extend([
+            (
+                21,
+                TargetState {
+                    pid: 21,
+                    status: "ok",
+                    ..Default::default()
+                },
+            ),
+            (
+                22,
+                TargetState {
+                    pid: 22,
+                    status: "gone",
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let agent = Agent {
+            pids: vec![],
+            ..agent
+        };
+        assert!(agent.manages(21));
+        assert!(!agent.manages(22));
+        assert!(!agent.manages(23));
+    }
+
+    #[tokio::test]
+    async fn test_targets_table() {
+        TARGETS.write().unwrap().insert(
+            7,
+            TargetState {
+                pid: 7,
+                status: "unreachable",
+                error: Some("connection refused".to_string()),
+                ..Default::default()
+            },
+        );
+        let engine = Engine::builder()
+            .with_default_namespace("agentd")
+            .with_plugin(TargetsPlugin::create("agentd", TargetsTable::name()))
+            .build()
+            .await
+            .unwrap();
+        let df = engine
+            .async_query("SELECT catalog, status FROM agentd.targets WHERE pid = 7")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            df.iter().next().unwrap(),
+            vec![
+                Ele::Text("pid7".to_string()),
+                Ele::Text("unreachable".to_string())
+            ]
+        );
+    }
+}
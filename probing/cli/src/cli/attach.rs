@@ -0,0 +1,247 @@
+//! `probing attach`, from a pid to the web UI in one command.
+//!
+//! Attaching injects the probe unless the process already loaded it, waits
+//! for its endpoint, applies a named config profile (see `probing.profile`)
+//! and `-D` settings, checks that the probe answers and prints the URL of the
+//! web UI.
+//!
+//! A process without a TCP server is only reachable through the unix socket
+//! of its probe. `--forward` serves that socket on a port of the loopback
+//! interface until the command is interrupted, so the browser can reach it.
+
+use std::io::Write as _;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use probing_proto::prelude::{Process, Query};
+
+use super::ctrl::{self, ProbeEndpoint};
+
+/// Interval between two attempts to reach the probe endpoint
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Args, Debug)]
+pub struct AttachCommand {
+    /// Config profile applied once the probe is up, e.g. `dataloader-debug`
+    #[arg(short, long)]
+    profile: Option<String>,
+
+    /// Setting as `key=value`, applied after the profile
+    #[arg(short = 'D', long = "define")]
+    settings: Vec<String>,
+
+    /// Seconds to wait for the probe endpoint
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+
+    /// Serve the probe of a local target on this loopback port until
+    /// interrupted; without a value a free port is picked
+    #[arg(long, num_args = 0..=1, default_missing_value = "0")]
+    forward: Option<u16>,
+}
+
+impl AttachCommand {
+    pub async fn run(&self, ctrl: ProbeEndpoint) -> Result<()> {
+        // malformed settings are reported before touching the process
+        let settings = self.settings()?;
+        let local = match ctrl {
+            ProbeEndpoint::Ptrace { pid } | ProbeEndpoint::Local { pid } => Some(pid),
+            _ => None,
+        };
+        if self.forward.is_some() && local.is_none() {
+            anyhow::bail!("--forward takes a local target");
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(pid) = local {
+            let inject = super::inject::InjectCommand::default();
+            if inject.check_library(pid, "libprobing.so")? {
+                println!("probe already loaded in {pid}");
+            } else {
+                // the probe is checked below, once the settings are applied
+                inject.without_verify().run(ctrl.clone()).await?;
+            }
+        }
+
+        wait_ready(&ctrl, Duration::from_secs(self.timeout)).await?;
+
+        if !settings.is_empty() {
+            ctrl.query(Query::new(settings_query(&settings))).await?;
+            println!("applied {} settings", settings.len());
+        }
+
+        let process = ctrl.health().await?;
+        println!(
+            "probe is healthy: pid {} running {}",
+            process.pid, process.cmd
+        );
+
+        if let (Some(port), Some(pid)) = (self.forward, local) {
+            return forward(port, pid).await;
+        }
+        let address = ctrl.config().await?.store.remove("server.address");
+        match ui_url(&ctrl, address.as_deref()) {
+            Some(url) => println!("web UI: {url}"),
+            None => {
+                println!("the probe has no TCP server, attach with --forward to open the web UI")
+            }
+        }
+        Ok(())
+    }
+
+    /// The profile followed by the settings given with `-D`
+    fn settings(&self) -> Result<Vec<(String, String)>> {
+        let mut settings = vec![];
+        if let Some(profile) = &self.profile {
+            settings.push(("probing.profile".to_string(), profile.clone()));
+        }
+        for setting in &self.settings {
+            let Some((key, value)) = setting.split_once('=') else {
+                anyhow::bail!("setting `{setting}` is not `key=value`");
+            };
+            settings.push((key.trim().to_string(), value.trim().to_string()));
+        }
+        Ok(settings)
+    }
+}
+
+impl ProbeEndpoint {
+    /// Check that the probe serves requests and its engine runs queries
+    pub async fn health(&self) -> Result<Process> {
+        let process = self
+            .client()?
+            .overview()
+            .await
+            .map_err(|e| anyhow::anyhow!("probe is unhealthy: {e}"))?;
+        self.query(Query::new("SELECT 1".to_string()))
+            .await
+            .context("probe is unhealthy, its query engine fails")?;
+        Ok(process)
+    }
+}
+
+/// `set` statements applying `settings`, values quoted as strings
+fn settings_query(settings: &[(String, String)]) -> String {
+    settings
+        .iter()
+        .map(|(key, value)| format!("set {key}='{}';", value.replace('\'', "''")))
+        .collect()
+}
+
+/// URL of the web UI served at `address`, the TCP address of the probe
+fn ui_url(ctrl: &ProbeEndpoint, address: Option<&str>) -> Option<String> {
+    let address = match ctrl {
+        ProbeEndpoint::Remote { addr } => addr.as_str(),
+        _ => address.filter(|a| !a.is_empty())?,
+    };
+    // a server listening on every interface is reached on the loopback one
+    let address = match address.rsplit_once(':') {
+        Some(("0.0.0.0", port)) => format!("127.0.0.1:{port}"),
+        Some(("[::]", port)) => format!("[::1]:{port}"),
+        _ => address.to_string(),
+    };
+    Some(format!("http://{address}/"))
+}
+
+/// Whether a connection to the probe endpoint can be opened
+async fn reachable(ctrl: &ProbeEndpoint) -> bool {
+    match ctrl {
+        ProbeEndpoint::Ptrace { pid } | ProbeEndpoint::Local { pid } => {
+            tokio::net::UnixStream::connect(ctrl::socket_path(*pid))
+                .await
+                .is_ok()
+        }
+        ProbeEndpoint::Remote { addr } => tokio::net::TcpStream::connect(addr).await.is_ok(),
+        ProbeEndpoint::Launch { .. } => false,
+    }
+}
+
+/// Poll until a connection to the probe endpoint opens, for up to `timeout`
+pub(crate) async fn wait_ready(ctrl: &ProbeEndpoint, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+    while !reachable(ctrl).await {
+        if start.elapsed() >= timeout {
+            anyhow::bail!(
+                "probe of {} is not reachable after {}s",
+                String::from(ctrl.clone()),
+                timeout.as_secs()
+            );
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// Serve the unix socket of the probe of `pid` on a loopback port
+async fn forward(port: u16, pid: i32) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("failed to listen on port {port}"))?;
+    println!(
+        "web UI: http://{}/ (forwarded to {pid}, interrupt to stop)",
+        listener.local_addr()?
+    );
+    std::io::stdout().flush()?;
+
+    loop {
+        let (mut client, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            match tokio::net::UnixStream::connect(ctrl::socket_path(pid)).await {
+                Ok(mut probe) => {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut probe).await;
+                }
+                Err(e) => log::warn!("failed to reach the probe of {pid}: {e}"),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_query() {
+        let cmd = AttachCommand {
+            profile: Some("dataloader-debug".into()),
+            settings: vec!["probing.privacy.redact_patterns = it's".into()],
+            timeout: 30,
+            forward: None,
+        };
+        assert_eq!(
+            settings_query(&cmd.settings().unwrap()),
+            "set probing.profile='dataloader-debug';set probing.privacy.redact_patterns='it''s';"
+        );
+
+        let cmd = AttachCommand {
+            profile: None,
+            settings: vec!["probing.x".into()],
+            timeout: 30,
+            forward: None,
+        };
+        assert!(cmd.settings().is_err());
+    }
+
+    #[test]
+    fn test_ui_url() {
+        let local = ProbeEndpoint::Local { pid: 1 };
+        assert_eq!(
+            ui_url(&local, Some("0.0.0.0:9700")).as_deref(),
+            Some("http://127.0.0.1:9700/")
+        );
+        assert_eq!(
+            ui_url(&local, Some("10.0.0.2:9700")).as_deref(),
+            Some("http://10.0.0.2:9700/")
+        );
+        assert_eq!(ui_url(&local, None), None);
+
+        let remote = ProbeEndpoint::Remote {
+            addr: "node1:9700".into(),
+        };
+        assert_eq!(
+            ui_url(&remote, Some("0.0.0.0:9700")).as_deref(),
+            Some("http://node1:9700/")
+        );
+    }
+}
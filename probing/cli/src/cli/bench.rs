@@ -0,0 +1,467 @@
+//! `probing bench`, micro-benchmarks of the probe's overhead on a target.
+//!
+//! Sampling settings trade visibility against the time the probe takes from
+//! the workload. A benchmark measures both sides with a fixed suite of
+//! operations, so runs with different settings can be compared:
+//!
+//! - `query` runs a fixed set of SQL queries against the probe;
+//! - `stack` captures the call stacks of the main thread;
+//! - `tracing` creates spans in the target, with `tracer.sample_every` set for
+//!   the duration of the run.
+//!
+//! The target is first observed idle for `--duration` seconds, then the
+//! operations run back to back for as long again. The report gives the
+//! latencies of the operations and how much the target's CPU use and, when
+//! it records `python.trainer_steps`, its step time changed between the two
+//! windows.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use probing_client::Client;
+use probing_proto::prelude::{DataFrame, Ele, Query};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::ctrl::ProbeEndpoint;
+
+/// Queries of the `query` suite, run in turn
+pub const QUERIES: &[&str] = &[
+    "SELECT 1",
+    "SELECT name, value FROM information_schema.df_settings",
+    "SELECT count(*) FROM python.threads",
+];
+
+/// Spans created by one operation of the `tracing` suite
+pub const SPANS_PER_OP: usize = 100;
+
+/// Option enabled by the `tracing` suite while it runs
+const TRACER_OPTION: &str = "probing.tracer.sample_every";
+
+/// CPU seconds used by the target so far
+const CPU_TIME: &str = "import os; print(sum(os.times()[:2]))";
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Suite {
+    /// Call tracing and spans created in the target
+    Tracing,
+    /// SQL queries against the probe
+    #[default]
+    Query,
+    /// Call stack captures
+    Stack,
+}
+
+impl Suite {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Suite::Tracing => "tracing",
+            Suite::Query => "query",
+            Suite::Stack => "stack",
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct BenchCommand {
+    /// Operations to measure
+    #[arg(long, value_enum, default_value = "query")]
+    suite: Suite,
+
+    /// Seconds of each window, idle and instrumented
+    #[arg(long, default_value_t = 5.0)]
+    duration: f64,
+
+    /// `tracer.sample_every` of the tracing suite
+    #[arg(long, default_value_t = 100)]
+    sample_every: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// Latency distribution of the operations, in milliseconds
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Latency {
+    pub min: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl Latency {
+    pub fn from_samples(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(|a, b| a.total_cmp(b));
+        let quantile = |q: f64| {
+            let rank = (q * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+        Self {
+            min: samples[0],
+            p50: quantile(0.50),
+            p95: quantile(0.95),
+            p99: quantile(0.99),
+            max: samples[samples.len() - 1],
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+        }
+    }
+}
+
+/// What the target did during a window
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Window {
+    pub seconds: f64,
+    /// CPU used by the target, in percent of one core
+    pub cpu_percent: f64,
+    /// Mean duration of the trainer steps completed, in seconds
+    pub step_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub suite: Suite,
+    pub target: String,
+    pub duration: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_every: Option<u64>,
+    pub operations: usize,
+    pub errors: usize,
+    /// Latency of an operation; of a single span for the tracing suite
+    pub latency_ms: Latency,
+    pub baseline: Window,
+    pub instrumented: Window,
+}
+
+impl BenchReport {
+    /// Change of the target's CPU use, in percentage points
+    pub fn cpu_delta(&self) -> f64 {
+        self.instrumented.cpu_percent - self.baseline.cpu_percent
+    }
+
+    /// Relative change of the step time, in percent
+    pub fn step_delta(&self) -> Option<f64> {
+        let baseline = self.baseline.step_seconds.filter(|s| *s > 0.0)?;
+        let instrumented = self.instrumented.step_seconds?;
+        Some((instrumented / baseline - 1.0) * 100.0)
+    }
+}
+
+impl BenchCommand {
+    pub async fn run(&self, ctrl: ProbeEndpoint) -> Result<()> {
+        if !self.duration.is_finite() || self.duration <= 0.0 {
+            anyhow::bail!("--duration must be positive");
+        }
+        let client = ctrl.client()?;
+        let window = Duration::from_secs_f64(self.duration);
+
+        eprintln!(
+            "observing {} idle for {:.1}s...",
+            String::from(ctrl.clone()),
+            self.duration
+        );
+        let (baseline, ()) = observe(&client, async {
+            tokio::time::sleep(window).await;
+            Ok(())
+        })
+        .await?;
+
+        eprintln!(
+            "running the {} suite for {:.1}s...",
+            self.suite.as_str(),
+            self.duration
+        );
+        let (instrumented, samples) = match self.suite {
+            Suite::Query => {
+                let mut next = 0;
+                run_suite(&client, window, || {
+                    let query = QUERIES[next % QUERIES.len()];
+                    next += 1;
+                    timed(client.query(Query::new(query.to_string())))
+                })
+                .await?
+            }
+            Suite::Stack => run_suite(&client, window, || timed(client.callstack(None))).await?,
+            Suite::Tracing => {
+                let previous = setting(&client, TRACER_OPTION).await;
+                client
+                    .set(&[(TRACER_OPTION.to_string(), self.sample_every.to_string())])
+                    .await?;
+                let result =
+                    run_suite(&client, window, || create_spans(&client, SPANS_PER_OP)).await;
+                let restored = client
+                    .set(&[(TRACER_OPTION.to_string(), previous.unwrap_or_default())])
+                    .await;
+                if let Err(e) = restored {
+                    eprintln!("failed to restore {TRACER_OPTION}: {e}");
+                }
+                result?
+            }
+        };
+
+        let errors = samples.iter().filter(|sample| sample.is_none()).count();
+        let samples = samples.into_iter().flatten().collect::<Vec<_>>();
+        let report = BenchReport {
+            suite: self.suite,
+            target: String::from(ctrl),
+            duration: self.duration,
+            sample_every: (self.suite == Suite::Tracing).then_some(self.sample_every),
+            operations: samples.len(),
+            errors,
+            latency_ms: Latency::from_samples(samples),
+            baseline,
+            instrumented,
+        };
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            println!("{}", format_report(&report));
+        }
+        Ok(())
+    }
+}
+
+/// Milliseconds taken by `op`, `None` when it failed
+async fn timed<T, E>(op: impl std::future::Future<Output = Result<T, E>>) -> Option<f64> {
+    let start = Instant::now();
+    op.await.ok()?;
+    Some(start.elapsed().as_secs_f64() * 1e3)
+}
+
+/// Run `op` back to back for `window`, observing the target meanwhile
+async fn run_suite<F, Fut>(
+    client: &Client,
+    window: Duration,
+    mut op: F,
+) -> Result<(Window, Vec<Option<f64>>)>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<f64>>,
+{
+    let mut samples = vec![];
+    let (observed, ()) = observe(client, async {
+        let start = Instant::now();
+        while start.elapsed() < window {
+            samples.push(op().await);
+        }
+        Ok(())
+    })
+    .await?;
+    Ok((observed, samples))
+}
+
+/// Observe the target while `work` runs
+async fn observe<T>(
+    client: &Client,
+    work: impl std::future::Future<Output = Result<T>>,
+) -> Result<(Window, T)> {
+    let last_step = last_step(client).await;
+    let cpu = cpu_seconds(client).await?;
+    let start = Instant::now();
+    let output = work.await?;
+    let seconds = start.elapsed().as_secs_f64();
+    let used = cpu_seconds(client).await? - cpu;
+    let step_seconds = match last_step {
+        Some(step) => step_seconds(client, step).await,
+        None => None,
+    };
+    let observed = Window {
+        seconds,
+        cpu_percent: used / seconds * 100.0,
+        step_seconds,
+    };
+    Ok((observed, output))
+}
+
+/// Evaluate `code` in the target and return what it printed
+async fn eval(client: &Client, code: &str) -> Result<String> {
+    let reply = client
+        .request("/apis/pythonext/eval", Some(code.to_string()))
+        .await?;
+    let reply: Value = serde_json::from_slice(&reply).context("unexpected eval reply")?;
+    if let Some(traceback) = reply["traceback"].as_array().filter(|t| !t.is_empty()) {
+        let last = traceback.last().and_then(Value::as_str).unwrap_or_default();
+        anyhow::bail!("eval failed: {}", last.trim());
+    }
+    Ok(reply["output"]
+        .as_str()
+        .unwrap_or_default()
+        .trim()
+        .to_string())
+}
+
+async fn cpu_seconds(client: &Client) -> Result<f64> {
+    let output = eval(client, CPU_TIME).await?;
+    output
+        .parse()
+        .with_context(|| format!("unexpected CPU time `{output}`"))
+}
+
+/// Milliseconds one span takes in the target, over `count` spans
+async fn create_spans(client: &Client, count: usize) -> Option<f64> {
+    let code = format!(
+        "import time, probing\n\
+         start = time.perf_counter_ns()\n\
+         for _ in range({count}):\n    \
+             with probing.span('probing.bench'):\n        pass\n\
+         print((time.perf_counter_ns() - start) / {count} / 1e6)"
+    );
+    eval(client, &code).await.ok()?.parse().ok()
+}
+
+fn number(value: &Ele) -> Option<f64> {
+    match value {
+        Ele::I32(x) => Some(*x as f64),
+        Ele::I64(x) => Some(*x as f64),
+        Ele::F32(x) => Some(*x as f64),
+        Ele::F64(x) => Some(*x),
+        _ => None,
+    }
+}
+
+fn first_number(df: &DataFrame) -> Option<f64> {
+    df.iter().next()?.first().and_then(number)
+}
+
+/// Last trainer step recorded, `None` without `python.trainer_steps`
+async fn last_step(client: &Client) -> Option<f64> {
+    let query = "SELECT coalesce(max(step), -1) FROM python.trainer_steps";
+    let df = client.query(Query::new(query.to_string())).await.ok()?;
+    first_number(&df)
+}
+
+/// Mean duration of the steps after `step`
+async fn step_seconds(client: &Client, step: f64) -> Option<f64> {
+    let query = format!("SELECT avg(duration) FROM python.trainer_steps WHERE step > {step}");
+    let df = client.query(Query::new(query)).await.ok()?;
+    first_number(&df)
+}
+
+async fn setting(client: &Client, name: &str) -> Option<String> {
+    let query = format!(
+        "SELECT value FROM information_schema.df_settings WHERE name = '{}'",
+        name.replace('\'', "''")
+    );
+    let df = client.query(Query::new(query)).await.ok()?;
+    match df.iter().next()?.first()? {
+        Ele::Nil => None,
+        value => Some(value.to_string()),
+    }
+}
+
+pub fn format_report(report: &BenchReport) -> String {
+    let BenchReport {
+        suite,
+        target,
+        duration,
+        sample_every,
+        operations,
+        errors,
+        latency_ms: latency,
+        baseline,
+        instrumented,
+    } = report;
+    let mut out = format!("probing bench: suite={} target={target}", suite.as_str());
+    if let Some(sample_every) = sample_every {
+        out.push_str(&format!(" sample_every={sample_every}"));
+    }
+    let rate = *operations as f64 / instrumented.seconds.max(f64::EPSILON);
+    out.push_str(&format!(
+        "\n  window:       {duration:.1}s\n  operations:   {operations} ({rate:.1}/s)"
+    ));
+    if *errors > 0 {
+        out.push_str(&format!(", {errors} failed"));
+    }
+    let unit = match suite {
+        Suite::Tracing => "ms per span",
+        _ => "ms",
+    };
+    out.push_str(&format!(
+        "\n  latency:      min {:.3}  p50 {:.3}  p95 {:.3}  p99 {:.3}  max {:.3} {unit}",
+        latency.min, latency.p50, latency.p95, latency.p99, latency.max
+    ));
+    out.push_str(&format!(
+        "\n  target cpu:   baseline {:.1}%  instrumented {:.1}%  delta {:+.1}%",
+        baseline.cpu_percent,
+        instrumented.cpu_percent,
+        report.cpu_delta()
+    ));
+    match (baseline.step_seconds, instrumented.step_seconds) {
+        (Some(before), Some(after)) => {
+            out.push_str(&format!(
+                "\n  step time:    baseline {before:.3}s  instrumented {after:.3}s"
+            ));
+            if let Some(delta) = report.step_delta() {
+                out.push_str(&format!("  delta {delta:+.1}%"));
+            }
+        }
+        _ => out.push_str("\n  step time:    no trainer steps recorded"),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency() {
+        let latency = Latency::from_samples((1..=100).rev().map(|x| x as f64).collect());
+        assert_eq!(latency.min, 1.0);
+        assert_eq!(latency.p50, 50.0);
+        assert_eq!(latency.p95, 95.0);
+        assert_eq!(latency.p99, 99.0);
+        assert_eq!(latency.max, 100.0);
+        assert_eq!(latency.mean, 50.5);
+        assert_eq!(Latency::from_samples(vec![]), Latency::default());
+        assert_eq!(Latency::from_samples(vec![2.0]).p99, 2.0);
+    }
+
+    #[test]
+    fn test_format_report() {
+        let mut report = BenchReport {
+            suite: Suite::Tracing,
+            target: "1234".to_string(),
+            duration: 5.0,
+            sample_every: Some(100),
+            operations: 50,
+            errors: 0,
+            latency_ms: Latency::from_samples(vec![0.01, 0.02]),
+            baseline: Window {
+                seconds: 5.0,
+                cpu_percent: 95.0,
+                step_seconds: Some(0.5),
+            },
+            instrumented: Window {
+                seconds: 5.0,
+                cpu_percent: 98.5,
+                step_seconds: Some(0.51),
+            },
+        };
+        let text = format_report(&report);
+        assert!(text.starts_with("probing bench: suite=tracing target=1234 sample_every=100"));
+        assert!(text.contains("operations:   50 (10.0/s)\n"));
+        assert!(text.contains("ms per span"));
+        assert!(text.contains("delta +3.5%"));
+        assert!(text.ends_with("instrumented 0.510s  delta +2.0%"));
+
+        report.suite = Suite::Query;
+        report.sample_every = None;
+        report.errors = 2;
+        report.instrumented.step_seconds = None;
+        let text = format_report(&report);
+        assert!(text.contains("(10.0/s), 2 failed"));
+        assert!(text.ends_with("no trainer steps recorded"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["suite"], "query");
+        assert!(json.get("sample_every").is_none());
+    }
+}
@@ -0,0 +1,545 @@
+//! `probing check`, SQL assertions against a target for CI gating.
+//!
+//! A rules file lists queries with the condition their result must meet:
+//!
+//! ```yaml
+//! name: training
+//! setup:
+//!   - CREATE EXTERNAL TABLE IF NOT EXISTS archive.steps STORED AS PARQUET LOCATION '/runs/42/'
+//! rules:
+//!   - name: p99-step-time
+//!     query: SELECT approx_percentile_cont(duration, 0.99) FROM archive.steps
+//!     max: 0.5
+//!   - name: no-nan-loss
+//!     query: SELECT step FROM metrics WHERE isnan(loss)
+//!     empty: true
+//!     severity: warning
+//! ```
+//!
+//! The `setup` statements run first, e.g. to register recorded data. A rule
+//! checks the first column of every row against `min` and `max`, requires no
+//! rows with `empty`, and otherwise requires every first value to be true.
+//! The report is printed as text, JUnit XML or SARIF.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use probing_proto::prelude::{DataFrame, Ele, Query};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::ctrl::ProbeEndpoint;
+
+/// Rows of a result quoted in a failure message
+const MAX_QUOTED_ROWS: usize = 5;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// One line per rule
+    #[default]
+    Text,
+    /// JUnit XML, for the test report of CI systems
+    Junit,
+    /// SARIF 2.1.0, for code scanning dashboards
+    Sarif,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckCommand {
+    /// Rules file (YAML)
+    #[arg(long)]
+    rules: PathBuf,
+
+    /// Report format
+    #[arg(long, value_enum, default_value = "text")]
+    format: ReportFormat,
+
+    /// Write the report to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// A violation fails the check
+    #[default]
+    Error,
+    /// A violation is reported only
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RuleSet {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Statements run before the rules
+    #[serde(default)]
+    pub setup: Vec<String>,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub query: String,
+    /// Lower bound of the first column
+    #[serde(default)]
+    pub min: Option<f64>,
+    /// Upper bound of the first column
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// The query must return no rows
+    #[serde(default)]
+    pub empty: bool,
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+impl RuleSet {
+    pub fn parse(text: &str) -> Result<Self> {
+        let rules: RuleSet = serde_yaml::from_str(text)?;
+        for rule in &rules.rules {
+            if rule.empty && (rule.min.is_some() || rule.max.is_some()) {
+                anyhow::bail!("rule {}: `empty` excludes `min` and `max`", rule.name);
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    Passed,
+    /// The result does not meet the rule
+    Failed(String),
+    /// The rule could not be evaluated, e.g. the query is invalid
+    Error(String),
+}
+
+#[derive(Debug)]
+pub struct Outcome<'a> {
+    pub rule: &'a Rule,
+    pub verdict: Verdict,
+    pub elapsed: Duration,
+}
+
+impl Outcome<'_> {
+    /// Whether the outcome fails the check, warnings do not
+    fn fails(&self) -> bool {
+        match self.verdict {
+            Verdict::Passed => false,
+            Verdict::Failed(_) => self.rule.severity == Severity::Error,
+            Verdict::Error(_) => true,
+        }
+    }
+}
+
+fn number(value: &Ele) -> Option<f64> {
+    match value {
+        Ele::I32(x) => Some(*x as f64),
+        Ele::I64(x) => Some(*x as f64),
+        Ele::F32(x) => Some(*x as f64),
+        Ele::F64(x) => Some(*x),
+        Ele::DataTime(x) => Some(*x as f64),
+        Ele::BOOL(x) => Some(*x as u8 as f64),
+        Ele::Nil | Ele::Text(_) | Ele::Url(_) => None,
+    }
+}
+
+fn quote_rows(rows: &[Vec<Ele>]) -> String {
+    let mut quoted = rows
+        .iter()
+        .take(MAX_QUOTED_ROWS)
+        .map(|row| {
+            let row = row.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            format!("({})", row.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    if rows.len() > MAX_QUOTED_ROWS {
+        let _ = write!(quoted, " and {} more", rows.len() - MAX_QUOTED_ROWS);
+    }
+    quoted
+}
+
+/// Evaluate `rule` against the result of its query
+pub fn evaluate(rule: &Rule, df: &DataFrame) -> Verdict {
+    let rows = df.iter().collect::<Vec<_>>();
+    if rule.empty {
+        return match rows.len() {
+            0 => Verdict::Passed,
+            n => Verdict::Failed(format!("{n} row(s), expected none: {}", quote_rows(&rows))),
+        };
+    }
+    if rows.is_empty() {
+        return Verdict::Failed("no rows".to_string());
+    }
+
+    let mut violations = vec![];
+    for row in rows {
+        let Some(first) = row.first() else {
+            return Verdict::Error("the query returns no column".to_string());
+        };
+        let ok = if rule.min.is_some() || rule.max.is_some() {
+            let Some(value) = number(first) else {
+                return Verdict::Error(format!("`{first}` is not a number"));
+            };
+            rule.min.is_none_or(|min| value >= min) && rule.max.is_none_or(|max| value <= max)
+        } else {
+            number(first).is_some_and(|value| value != 0.0)
+        };
+        if !ok {
+            violations.push(row);
+        }
+    }
+    if violations.is_empty() {
+        return Verdict::Passed;
+    }
+    let expected = match (rule.min, rule.max) {
+        (Some(min), Some(max)) => format!("outside [{min}, {max}]"),
+        (Some(min), None) => format!("below {min}"),
+        (None, Some(max)) => format!("above {max}"),
+        (None, None) => "not true".to_string(),
+    };
+    Verdict::Failed(format!("{expected}: {}", quote_rows(&violations)))
+}
+
+impl CheckCommand {
+    pub async fn run(&self, ctrl: ProbeEndpoint) -> Result<()> {
+        let text = std::fs::read_to_string(&self.rules)
+            .with_context(|| format!("cannot read {}", self.rules.display()))?;
+        let rules = RuleSet::parse(&text)
+            .with_context(|| format!("invalid rules file {}", self.rules.display()))?;
+
+        for statement in &rules.setup {
+            ctrl.query(Query::new(statement.clone()))
+                .await
+                .with_context(|| format!("setup statement failed: {statement}"))?;
+        }
+
+        let mut outcomes = vec![];
+        for rule in &rules.rules {
+            let start = Instant::now();
+            let verdict = match ctrl.query(Query::new(rule.query.clone())).await {
+                Ok(df) => evaluate(rule, &df),
+                Err(err) => Verdict::Error(err.to_string()),
+            };
+            outcomes.push(Outcome {
+                rule,
+                verdict,
+                elapsed: start.elapsed(),
+            });
+        }
+
+        let suite = rules.name.as_deref().unwrap_or("probing");
+        let target = String::from(ctrl);
+        let report = match self.format {
+            ReportFormat::Text => text_report(&outcomes),
+            ReportFormat::Junit => junit_report(suite, &target, &outcomes),
+            ReportFormat::Sarif => {
+                let report = sarif_report(&self.rules, &text, &outcomes);
+                format!("{}\n", serde_json::to_string_pretty(&report)?)
+            }
+        };
+        match &self.output {
+            Some(path) => std::fs::write(path, report)
+                .with_context(|| format!("cannot write {}", path.display()))?,
+            None => print!("{report}"),
+        }
+
+        let failed = outcomes.iter().filter(|o| o.fails()).count();
+        if failed > 0 {
+            anyhow::bail!("{failed} of {} rule(s) failed", outcomes.len());
+        }
+        Ok(())
+    }
+}
+
+fn text_report(outcomes: &[Outcome]) -> String {
+    let mut out = String::new();
+    for outcome in outcomes {
+        let (status, message) = match &outcome.verdict {
+            Verdict::Passed => ("pass", ""),
+            Verdict::Failed(msg) if outcome.rule.severity == Severity::Warning => ("warn", &**msg),
+            Verdict::Failed(msg) => ("fail", &**msg),
+            Verdict::Error(msg) => ("error", &**msg),
+        };
+        let _ = writeln!(out, "[{status:>5}] {} {message}", outcome.rule.name);
+    }
+    out
+}
+
+/// Escape `s` for XML text and attribute values
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\t' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A JUnit XML report with one test case per rule.
+///
+/// Violated warnings pass, with the violation in `system-out`.
+pub fn junit_report(suite: &str, target: &str, outcomes: &[Outcome]) -> String {
+    let count = |f: fn(&Outcome) -> bool| outcomes.iter().filter(|o| f(o)).count();
+    let failures = count(|o| matches!(o.verdict, Verdict::Failed(_)) && o.fails());
+    let errors = count(|o| matches!(o.verdict, Verdict::Error(_)));
+    let time = outcomes
+        .iter()
+        .map(|o| o.elapsed.as_secs_f64())
+        .sum::<f64>();
+    let suite = xml_escape(suite);
+
+    let mut out = String::new();
+    let _ = writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        out,
+        r#"<testsuites name="probing" tests="{}" failures="{failures}" errors="{errors}" time="{time:.3}">"#,
+        outcomes.len()
+    );
+    let _ = writeln!(
+        out,
+        r#"  <testsuite name="{suite}" tests="{}" failures="{failures}" errors="{errors}" skipped="0" time="{time:.3}" hostname="{}">"#,
+        outcomes.len(),
+        xml_escape(target)
+    );
+    for outcome in outcomes {
+        let _ = write!(
+            out,
+            r#"    <testcase name="{}" classname="{suite}" time="{:.3}""#,
+            xml_escape(&outcome.rule.name),
+            outcome.elapsed.as_secs_f64()
+        );
+        let query = xml_escape(&outcome.rule.query);
+        match &outcome.verdict {
+            Verdict::Passed => {
+                let _ = writeln!(out, "/>");
+                continue;
+            }
+            Verdict::Failed(msg) if !outcome.fails() => {
+                let _ = writeln!(out, ">");
+                let _ = writeln!(
+                    out,
+                    "      <system-out>warning: {}</system-out>",
+                    xml_escape(msg)
+                );
+            }
+            Verdict::Failed(msg) => {
+                let _ = writeln!(out, ">");
+                let _ = writeln!(
+                    out,
+                    r#"      <failure message="{}" type="assertion">{query}</failure>"#,
+                    xml_escape(msg)
+                );
+            }
+            Verdict::Error(msg) => {
+                let _ = writeln!(out, ">");
+                let _ = writeln!(
+                    out,
+                    r#"      <error message="{}" type="query">{query}</error>"#,
+                    xml_escape(msg)
+                );
+            }
+        }
+        let _ = writeln!(out, "    </testcase>");
+    }
+    let _ = writeln!(out, "  </testsuite>");
+    let _ = writeln!(out, "</testsuites>");
+    out
+}
+
+/// Line of the rules file defining `rule`, 1 if it is not found
+fn rule_line(text: &str, rule: &str) -> usize {
+    text.lines()
+        .position(|line| {
+            let line = line.trim_start().trim_start_matches("- ").trim_start();
+            line.strip_prefix("name:")
+                .map(|name| name.trim().trim_matches(['"', '\'']) == rule)
+                .unwrap_or(false)
+        })
+        .map_or(1, |i| i + 1)
+}
+
+/// A SARIF 2.1.0 log with one result per rule, located in the rules file
+pub fn sarif_report(path: &Path, text: &str, outcomes: &[Outcome]) -> Value {
+    let uri = path.to_string_lossy();
+    let rules = outcomes
+        .iter()
+        .map(|o| {
+            let description = o.rule.description.as_deref().unwrap_or(&o.rule.name);
+            json!({
+                "id": o.rule.name,
+                "shortDescription": { "text": description },
+                "fullDescription": { "text": o.rule.query },
+                "defaultConfiguration": { "level": o.rule.severity.as_str() },
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = outcomes
+        .iter()
+        .enumerate()
+        .map(|(index, o)| {
+            let (kind, level, message) = match &o.verdict {
+                Verdict::Passed => ("pass", "none", "passed".to_string()),
+                Verdict::Failed(msg) => ("fail", o.rule.severity.as_str(), msg.clone()),
+                Verdict::Error(msg) => ("fail", "error", format!("query failed: {msg}")),
+            };
+            json!({
+                "ruleId": o.rule.name,
+                "ruleIndex": index,
+                "kind": kind,
+                "level": level,
+                "message": { "text": message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                        "region": { "startLine": rule_line(text, &o.rule.name) },
+                    }
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "probing",
+                    "informationUri": "https://github.com/reiase/probing",
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use probing_proto::prelude::Seq;
+
+    const RULES: &str = r#"
+name: training
+setup:
+  - CREATE SCHEMA archive
+rules:
+  - name: p99-step-time
+    query: SELECT p99 FROM steps
+    max: 0.5
+  - name: "no-nan-loss"
+    query: SELECT step FROM metrics WHERE isnan(loss)
+    empty: true
+    severity: warning
+  - name: converged
+    query: SELECT loss < 1 FROM metrics
+"#;
+
+    fn frame(values: Vec<f64>) -> DataFrame {
+        DataFrame::new(vec!["v".into()], vec![Seq::SeqF64(values)])
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let rules = RuleSet::parse(RULES).unwrap();
+        assert_eq!(rules.setup.len(), 1);
+        let [p99, nan, converged] = &rules.rules[..] else {
+            panic!("expected three rules");
+        };
+        assert_eq!(nan.severity, Severity::Warning);
+
+        assert_eq!(evaluate(p99, &frame(vec![0.2, 0.5])), Verdict::Passed);
+        assert_eq!(
+            evaluate(p99, &frame(vec![0.2, 0.7])),
+            Verdict::Failed("above 0.5: (0.7)".to_string())
+        );
+        assert_eq!(
+            evaluate(p99, &frame(vec![])),
+            Verdict::Failed("no rows".into())
+        );
+        let text = DataFrame::new(vec!["v".into()], vec![Seq::SeqText(vec!["x".into()])]);
+        assert!(matches!(evaluate(p99, &text), Verdict::Error(_)));
+
+        assert_eq!(evaluate(nan, &frame(vec![])), Verdict::Passed);
+        assert!(matches!(
+            evaluate(nan, &frame(vec![3.0])),
+            Verdict::Failed(_)
+        ));
+
+        let flags = DataFrame::new(vec!["ok".into()], vec![Seq::SeqBOOL(vec![true, false])]);
+        assert!(matches!(evaluate(converged, &flags), Verdict::Failed(_)));
+
+        assert!(
+            RuleSet::parse("rules:\n  - name: x\n    query: q\n    empty: true\n    max: 1\n")
+                .is_err()
+        );
+        assert!(RuleSet::parse("rules:\n  - name: x\n    query: q\n    maximum: 1\n").is_err());
+    }
+
+    #[test]
+    fn test_reports() {
+        let rules = RuleSet::parse(RULES).unwrap();
+        let verdicts = [
+            Verdict::Failed("above 0.5: (0.7)".into()),
+            Verdict::Failed("1 row(s), expected none: (3)".into()),
+            Verdict::Error("table <metrics> not found".into()),
+        ];
+        let outcomes = rules
+            .rules
+            .iter()
+            .zip(verdicts)
+            .map(|(rule, verdict)| Outcome {
+                rule,
+                verdict,
+                elapsed: Duration::from_millis(5),
+            })
+            .collect::<Vec<_>>();
+
+        let junit = junit_report("training", "1234", &outcomes);
+        assert!(junit.contains(r#"tests="3" failures="1" errors="1""#));
+        assert!(junit.contains(r#"<failure message="above 0.5: (0.7)" type="assertion">"#));
+        assert!(junit.contains("<system-out>warning: 1 row(s)"));
+        assert!(junit.contains(r#"message="table &lt;metrics&gt; not found""#));
+
+        let sarif = sarif_report(Path::new("rules.yaml"), RULES, &outcomes);
+        let results = &sarif["runs"][0]["results"];
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[1]["level"], "warning");
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            9
+        );
+        assert_eq!(results[2]["kind"], "fail");
+        assert_eq!(
+            sarif["runs"][0]["tool"]["driver"]["rules"][2]["id"],
+            "converged"
+        );
+    }
+}
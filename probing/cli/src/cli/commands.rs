@@ -1,6 +1,12 @@
 use clap::{Args, Subcommand};
 
+use super::attach::AttachCommand;
+use super::bench::BenchCommand;
+use super::check::CheckCommand;
+use super::config::ConfigCommand;
+use super::doctor::DoctorCommand;
 use super::store::StoreCommand;
+use super::verify::VerifyCommand;
 
 #[derive(Args, Default, Debug)]
 pub struct Settings {
@@ -109,6 +115,15 @@ pub enum Commands {
     #[command(visible_aliases = ["in", "i"])]
     Inject(super::inject::InjectCommand),
 
+    /// Inject if needed, apply a config profile and print the web UI URL
+    ///
+    /// ```bash
+    /// $ probing -t 1234 attach --profile dataloader-debug
+    /// $ probing -t 1234 attach --forward 9700
+    /// ```
+    #[command(visible_aliases = ["at"])]
+    Attach(AttachCommand),
+
     /// List all processes with injected probes
     #[command(visible_aliases = ["ls", "l"])]
     List {
@@ -117,6 +132,13 @@ pub enum Commands {
 
         #[arg(short, long, help = "Show processes as a tree structure")]
         tree: bool,
+
+        #[arg(
+            long,
+            help = "Scan the probe sockets instead of reading the discovery files, \
+                    to find probes started by older versions"
+        )]
+        scan: bool,
     },
 
     /// Display or modify the configuration
@@ -126,21 +148,65 @@ pub enum Commands {
         options: Settings,
 
         setting: Option<String>,
+
+        #[command(subcommand)]
+        action: Option<ConfigCommand>,
     },
 
     /// Show the backtrace of the target process or thread
     #[command(visible_aliases = ["bt", "b"])]
     Backtrace { tid: Option<i32> },
 
+    /// Stop the Python threads of the target process at a safe point
+    Pause {
+        /// Resume automatically after this many seconds
+        #[arg(long)]
+        duration: Option<f64>,
+
+        /// Only report whether the process is paused
+        #[arg(long)]
+        status: bool,
+    },
+
+    /// Resume a process stopped by `pause`
+    Resume,
+
     /// Get RDMA flow of the target process or thread
     #[command(visible_aliases = ["rd"])]
     Rdma { hca_name: Option<String> },
 
     /// Evaluate Python code in the target process
+    ///
+    /// With `--job` the code runs in a thread of its own and its output is
+    /// followed until it ends, so long running code is not cut off by the
+    /// request timeout.
+    ///
+    /// ```bash
+    /// $ probing -t 1234 eval "print(1)"
+    /// $ probing -t 1234 eval --job "import time; time.sleep(60)"
+    /// $ probing -t 1234 eval --jobs
+    /// $ probing -t 1234 eval --cancel 3
+    /// ```
     #[command(visible_aliases = ["e"])]
     Eval {
-        #[arg()]
-        code: String,
+        #[arg(required_unless_present_any = ["jobs", "cancel"])]
+        code: Option<String>,
+
+        /// Run the code as a background job and follow its output
+        #[arg(long)]
+        job: bool,
+
+        /// REPL session whose namespace the job runs in
+        #[arg(short, long, requires = "job")]
+        session: Option<String>,
+
+        /// List the eval jobs of the target
+        #[arg(long, conflicts_with_all = ["code", "job", "cancel"])]
+        jobs: bool,
+
+        /// Cancel a running eval job
+        #[arg(long, conflicts_with_all = ["code", "job"])]
+        cancel: Option<u64>,
     },
 
     /// Query data from the target process
@@ -148,11 +214,140 @@ pub enum Commands {
     Query {
         #[arg()]
         query: String,
+
+        /// Run against a read-only snapshot of the tables instead of the live data
+        #[arg(long)]
+        snapshot: bool,
+
+        /// Fetch and print the result in pages of this many rows
+        #[arg(long)]
+        page_size: Option<usize>,
+
+        /// Session whose temporary tables (`CREATE TEMP TABLE`) the query sees
+        #[arg(long, conflicts_with = "snapshot")]
+        session: Option<String>,
+
+        /// Comma-separated columns of the result to print
+        #[arg(long, value_delimiter = ',')]
+        columns: Option<Vec<String>>,
+
+        /// Fraction of the rows to print, drawn at random, e.g. 0.01
+        #[arg(long)]
+        sample: Option<f64>,
+
+        /// Cut text cells to this many characters
+        #[arg(long)]
+        max_cell_chars: Option<usize>,
+
+        /// Run the query even if the query guard refuses it as too heavy
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Run the SQL assertions of a rules file and report them as text, JUnit or SARIF
+    ///
+    /// ```bash
+    /// $ probing -t 1234 check --rules rules.yaml --format junit -o report.xml
+    /// ```
+    Check(CheckCommand),
+
+    /// Measure the latency of probe operations and the overhead they add to the target
+    ///
+    /// The target is observed idle, then while the suite runs for as long;
+    /// attach the report when tuning sampling settings.
+    ///
+    /// ```bash
+    /// $ probing -t 1234 bench --suite tracing --sample-every 10
+    /// $ probing -t 1234 bench --suite query --duration 10 --json
+    /// ```
+    Bench(BenchCommand),
+
+    /// Save a self-contained HTML report of the target to share
+    ///
+    /// The page embeds a flamegraph, a waterfall of the latest spans, key
+    /// tables and the configuration.
+    ///
+    /// ```bash
+    /// $ probing -t 1234 report --out report.html
+    /// ```
+    Report {
+        /// File to write the report to
+        #[arg(short, long, default_value = "report.html")]
+        out: std::path::PathBuf,
+
+        /// Latest spans drawn in the waterfall
+        #[arg(long, default_value_t = 200)]
+        spans: usize,
+
+        /// Rows of each table
+        #[arg(long, default_value_t = 20)]
+        rows: usize,
+    },
+
+    /// Follow agent notifications (config changes, profiler state, alerts, ...)
+    #[command(visible_aliases = ["ev"])]
+    Events {
+        /// Print the raw JSON payload of each event
+        #[arg(long)]
+        raw: bool,
     },
 
     /// Interactive Python REPL session
+    ///
+    /// Named sessions keep their namespace after the connection drops and are
+    /// picked up again by attaching with the same name.
+    ///
+    /// ```bash
+    /// $ probing -t 1234 repl --session oom
+    /// $ probing -t 1234 repl --list
+    /// ```
     #[command(visible_aliases = ["r"])]
-    Repl,
+    Repl {
+        /// Session to attach to, created on first use
+        #[arg(short, long)]
+        session: Option<String>,
+
+        /// List the REPL sessions of the target
+        #[arg(long, conflicts_with_all = ["session", "close"])]
+        list: bool,
+
+        /// Close a session and release its namespace
+        #[arg(long, conflicts_with = "session")]
+        close: Option<String>,
+    },
+
+    /// Wait for the probe of a process to answer and report its version and features
+    ///
+    /// ```bash
+    /// $ probing verify 1234
+    /// $ probing -t node1:9700 verify --timeout 5
+    /// ```
+    #[command(visible_aliases = ["vf"])]
+    Verify(VerifyCommand),
+
+    /// Check the environment for what keeps probing from injecting or serving
+    ///
+    /// With a target pid, the process is checked as well.
+    ///
+    /// ```bash
+    /// $ probing doctor
+    /// $ probing -t 1234 doctor --json
+    /// ```
+    #[command(visible_aliases = ["dr"])]
+    Doctor(DoctorCommand),
+
+    /// Serve the probes of several local processes on one HTTP port
+    ///
+    /// The tables of process `<pid>` are queried as `pid<pid>.<namespace>.<table>`,
+    /// other APIs are forwarded from `/pid/<pid>/...`.
+    ///
+    /// ```bash
+    /// $ probing agentd --pid 1234,1235
+    /// $ probing -t 127.0.0.1:9700 query "SELECT * FROM agentd.targets"
+    /// ```
+    #[cfg(target_os = "linux")]
+    #[command()]
+    Agentd(super::agentd::AgentdCommand),
 
     /// Launch new Python process
     #[command()]
@@ -171,3 +366,72 @@ pub enum Commands {
     #[command(subcommand = false, hide = true)]
     Store(StoreCommand),
 }
+
+impl Commands {
+    /// Whether the command only reads from the target, so that a request
+    /// failing half way may be sent again
+    ///
+    /// `eval`, `SET` and other statements, configuration writes, `pause` and
+    /// the like are not: the probe may have applied them already.
+    pub fn is_idempotent(&self) -> bool {
+        match self {
+            Commands::List { .. }
+            | Commands::Backtrace { .. }
+            | Commands::Rdma { .. }
+            | Commands::Check(..)
+            | Commands::Report { .. }
+            | Commands::Verify(..) => true,
+            Commands::Config {
+                options,
+                setting: None,
+                action,
+            } => {
+                options.to_cfg().is_none()
+                    && matches!(
+                        action,
+                        None | Some(
+                            ConfigCommand::Dump { .. }
+                                | ConfigCommand::Schema { .. }
+                                | ConfigCommand::Watch { .. }
+                                | ConfigCommand::Schedule {
+                                    assignment: None,
+                                    cancel: None,
+                                    ..
+                                }
+                        )
+                    )
+            }
+            Commands::Pause { status, .. } => *status,
+            Commands::Eval { jobs, .. } => *jobs,
+            Commands::Repl { list, .. } => *list,
+            Commands::Query { query, .. } => is_read_only_query(query),
+            _ => false,
+        }
+    }
+}
+
+/// Whether the SQL only reads, judged by its first keyword
+fn is_read_only_query(query: &str) -> bool {
+    let keyword = query
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .find(|word| !word.is_empty())
+        .unwrap_or_default();
+    ["select", "with", "show", "describe", "explain", "values"]
+        .iter()
+        .any(|read| keyword.eq_ignore_ascii_case(read))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_only_query() {
+        assert!(is_read_only_query("SELECT * FROM python.backtrace"));
+        assert!(is_read_only_query("\n with t as (select 1) select 1"));
+        assert!(is_read_only_query("(SELECT 1) UNION (SELECT 2)"));
+        assert!(!is_read_only_query("SET probing.torch.profiling=on"));
+        assert!(!is_read_only_query("CREATE TEMP TABLE t AS SELECT 1"));
+        assert!(!is_read_only_query(""));
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use clap::{Subcommand, ValueEnum};
+
+use probing_proto::prelude::{
+    ConfigDump, OptionSchema, ScheduleState, ScheduleTrigger, ScheduledChange,
+};
+
+use super::ctrl::ProbeEndpoint;
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Dump all extension options and config store entries, sorted by key
+    ///
+    /// The output is stable, so dumps of two ranks can be compared with `diff`.
+    Dump {
+        /// Output format
+        #[arg(long, value_enum, default_value = "toml")]
+        format: DumpFormat,
+    },
+
+    /// List the extension options with their type, default, allowed values and help
+    Schema {
+        /// Only list the options whose key contains this text
+        filter: Option<String>,
+
+        /// Print the schemas as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Schedule a change at a time or step, or list the scheduled changes
+    ///
+    /// The previous value is restored at `--until` or `--until-step`. Steps
+    /// count the optimizer steps done, as reported by the probe.
+    Schedule {
+        /// `key=value` to write, e.g. `probing.torch.profiling=on`
+        #[arg(requires = "trigger")]
+        assignment: Option<String>,
+
+        /// Write at this time: `+10m`, `2026-10-17 14:00` (local) or RFC 3339
+        #[arg(long, group = "trigger")]
+        at: Option<String>,
+
+        /// Write once the training reaches this step
+        #[arg(long, group = "trigger")]
+        at_step: Option<i64>,
+
+        /// Restore the previous value at this time
+        #[arg(long, conflicts_with = "until_step")]
+        until: Option<String>,
+
+        /// Restore the previous value once the training reaches this step
+        #[arg(long)]
+        until_step: Option<i64>,
+
+        /// Cancel the scheduled change with this id
+        #[arg(long, conflicts_with = "assignment")]
+        cancel: Option<u64>,
+
+        /// Print the changes as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Poll the configuration and print every change
+    Watch {
+        /// Polling interval in seconds
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DumpFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigCommand {
+    pub async fn run(&self, ctrl: ProbeEndpoint) -> Result<()> {
+        match self {
+            ConfigCommand::Dump { format } => {
+                let dump = ctrl.config().await?;
+                match format {
+                    DumpFormat::Toml => print!("{}", to_toml(&dump)),
+                    DumpFormat::Json => println!("{}", serde_json::to_string_pretty(&dump)?),
+                }
+                Ok(())
+            }
+            ConfigCommand::Schema { filter, json } => {
+                let mut schemas = ctrl.client()?.config_schema().await?;
+                if let Some(filter) = filter {
+                    schemas.retain(|schema| schema.key.contains(filter.as_str()));
+                }
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&schemas)?);
+                } else {
+                    print!("{}", format_schemas(&schemas));
+                }
+                Ok(())
+            }
+            ConfigCommand::Schedule {
+                assignment,
+                at,
+                at_step,
+                until,
+                until_step,
+                cancel,
+                json,
+            } => {
+                let client = ctrl.client()?;
+                let changes = if let Some(id) = cancel {
+                    vec![client.cancel_config_schedule(*id).await?]
+                } else if let Some(assignment) = assignment {
+                    let (key, value) = assignment
+                        .split_once('=')
+                        .with_context(|| format!("expected key=value, got `{assignment}`"))?;
+                    let now = Local::now();
+                    let trigger = |time: &Option<String>, step: &Option<i64>| -> Result<_> {
+                        Ok(match (time, step) {
+                            (Some(time), _) => Some(ScheduleTrigger::Time(parse_time(time, now)?)),
+                            (None, Some(step)) => Some(ScheduleTrigger::Step(*step)),
+                            (None, None) => None,
+                        })
+                    };
+                    let change = ScheduledChange {
+                        id: 0,
+                        key: key.trim().to_string(),
+                        value: value.trim().to_string(),
+                        at: trigger(at, at_step)?.context("--at or --at-step is required")?,
+                        until: trigger(until, until_step)?,
+                        state: ScheduleState::Pending,
+                        previous: None,
+                        error: None,
+                    };
+                    vec![client.schedule_config(&change).await?]
+                } else {
+                    client.config_schedule().await?
+                };
+                if *json {
+                    println!("{}", serde_json::to_string_pretty(&changes)?);
+                } else {
+                    print!("{}", format_schedule(&changes));
+                }
+                Ok(())
+            }
+            ConfigCommand::Watch { interval } => {
+                let interval = Duration::from_secs((*interval).max(1));
+                let mut last = ctrl.config().await?;
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let current = ctrl.config().await?;
+                    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+                    for change in last.diff(&current) {
+                        println!("{now} {change}");
+                    }
+                    std::io::stdout().flush()?;
+                    last = current;
+                }
+            }
+        }
+    }
+}
+
+impl ProbeEndpoint {
+    /// Fetch the configuration dump of the target process
+    pub async fn config(&self) -> Result<ConfigDump> {
+        Ok(self.client()?.config().await?)
+    }
+}
+
+/// One line per option, `probing.<key>  <type> = <default>`, followed by the
+/// allowed values, aliases and indented help
+fn format_schemas(schemas: &[OptionSchema]) -> String {
+    let mut out = String::new();
+    for schema in schemas {
+        let _ = write!(out, "probing.{}  {}", schema.key, schema.kind.as_str());
+        if let Some(default) = &schema.default {
+            let _ = write!(out, " = {default}");
+        }
+        if !schema.allowed.is_empty() {
+            let _ = write!(out, "  [{}]", schema.allowed.join("|"));
+        }
+        if schema.requires_restart {
+            out.push_str("  (restart)");
+        }
+        out.push('\n');
+        if !schema.aliases.is_empty() {
+            let _ = writeln!(out, "    aliases: {}", schema.aliases.join(", "));
+        }
+        for line in schema.help.lines().filter(|line| !line.is_empty()) {
+            let _ = writeln!(out, "    {line}");
+        }
+    }
+    out
+}
+
+/// Nanoseconds since the epoch of `+<n><s|m|h|d>` after `now`, of an RFC 3339
+/// time, or of a local `YYYY-MM-DD HH:MM[:SS]`
+fn parse_time(text: &str, now: DateTime<Local>) -> Result<i64> {
+    let text = text.trim();
+    let time = if let Some(offset) = text.strip_prefix('+') {
+        let split = offset
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(offset.len());
+        let (value, unit) = offset.split_at(split);
+        let value: i64 = value
+            .parse()
+            .with_context(|| format!("invalid offset `{text}`"))?;
+        let seconds = match unit {
+            "" | "s" => value,
+            "m" => value * 60,
+            "h" => value * 3600,
+            "d" => value * 86400,
+            _ => bail!("offset `{text}` has unknown unit `{unit}`"),
+        };
+        now + chrono::Duration::seconds(seconds)
+    } else if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        time.with_timezone(&Local)
+    } else {
+        let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+            .with_context(|| format!("invalid time `{text}`, expected +10m or 2026-10-17 14:00"))?;
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .with_context(|| format!("`{text}` does not exist in the local time zone"))?
+    };
+    time.timestamp_nanos_opt()
+        .with_context(|| format!("time `{text}` is out of range"))
+}
+
+fn format_trigger(trigger: &ScheduleTrigger) -> String {
+    match trigger {
+        ScheduleTrigger::Step(step) => format!("step {step}"),
+        ScheduleTrigger::Time(nanos) => Local
+            .timestamp_nanos(*nanos)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+    }
+}
+
+/// One line per change, `#<id> <key>=<value>  at <trigger>  until <trigger>
+/// <state>`, followed by the previous value and the error
+fn format_schedule(changes: &[ScheduledChange]) -> String {
+    if changes.is_empty() {
+        return "no scheduled changes\n".to_string();
+    }
+    let mut out = String::new();
+    for change in changes {
+        let _ = write!(
+            out,
+            "#{} {}={}  at {}",
+            change.id,
+            change.key,
+            change.value,
+            format_trigger(&change.at)
+        );
+        if let Some(until) = &change.until {
+            let _ = write!(out, "  until {}", format_trigger(until));
+        }
+        let state = serde_json::to_value(change.state).unwrap_or_default();
+        let _ = writeln!(out, "  {}", state.as_str().unwrap_or_default());
+        if let Some(previous) = &change.previous {
+            let _ = writeln!(out, "    previous: {previous}");
+        }
+        if let Some(error) = &change.error {
+            let _ = writeln!(out, "    error: {error}");
+        }
+    }
+    out
+}
+
+/// Render a dump as TOML with one `[options]` and one `[store]` table
+fn to_toml(dump: &ConfigDump) -> String {
+    let mut out = String::new();
+    write_table(&mut out, "options", &dump.options);
+    out.push('\n');
+    write_table(&mut out, "store", &dump.store);
+    out
+}
+
+fn write_table(out: &mut String, name: &str, entries: &BTreeMap<String, String>) {
+    let _ = writeln!(out, "[{name}]");
+    for (key, value) in entries {
+        let _ = writeln!(out, "{} = {}", quote(key), quote(value));
+    }
+}
+
+/// Quote a string as a TOML basic string
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04X}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_schemas() {
+        let schemas = vec![OptionSchema {
+            key: "server.log_level".to_string(),
+            aliases: vec!["server.loglevel".to_string()],
+            kind: probing_proto::prelude::OptionKind::String,
+            default: Some("info".to_string()),
+            allowed: vec!["debug".to_string(), "info".to_string()],
+            requires_restart: true,
+            help: "Log level".to_string(),
+        }];
+        assert_eq!(
+            format_schemas(&schemas),
+            "probing.server.log_level  string = info  [debug|info]  (restart)\n    \
+             aliases: server.loglevel\n    Log level\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = Local.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let nanos = |time: DateTime<Local>| time.timestamp_nanos_opt().unwrap();
+        assert_eq!(
+            parse_time("+10m", now).unwrap(),
+            nanos(now) + 600_000_000_000
+        );
+        assert_eq!(
+            parse_time("2026-10-17 14:30", now).unwrap(),
+            nanos(Local.with_ymd_and_hms(2026, 10, 17, 14, 30, 0).unwrap())
+        );
+        assert_eq!(
+            parse_time("2026-10-17T12:00:00Z", now).unwrap(),
+            1_792_238_400_000_000_000
+        );
+        assert!(parse_time("+10w", now).is_err());
+        assert!(parse_time("tomorrow", now).is_err());
+    }
+
+    #[test]
+    fn test_format_schedule() {
+        let change = ScheduledChange {
+            id: 3,
+            key: "probing.torch.profiling".to_string(),
+            value: "on".to_string(),
+            at: ScheduleTrigger::Step(1000),
+            until: Some(ScheduleTrigger::Step(1011)),
+            state: ScheduleState::Applied,
+            previous: Some("off".to_string()),
+            error: None,
+        };
+        assert_eq!(
+            format_schedule(&[change]),
+            "#3 probing.torch.profiling=on  at step 1000  until step 1011  applied\n    \
+             previous: off\n"
+        );
+        assert_eq!(format_schedule(&[]), "no scheduled changes\n");
+    }
+
+    #[test]
+    fn test_to_toml() {
+        let mut dump = ConfigDump::default();
+        dump.options
+            .insert("probing.taskstats.interval".into(), "10".into());
+        dump.store.insert("a\"b".into(), "line\nbreak".into());
+
+        assert_eq!(
+            to_toml(&dump),
+            "[options]\n\"probing.taskstats.interval\" = \"10\"\n\n[store]\n\"a\\\"b\" = \"line\\nbreak\"\n"
+        );
+    }
+}
@@ -1,20 +1,70 @@
 use anyhow::Result;
 use serde_json::Value;
 use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
-use http_body_util::{BodyExt, Full};
-use hyper_util::rt::TokioIo;
+use http_body_util::BodyExt;
 
-use probing_proto::{prelude::*, protocol::process::CallFrame};
+use probing_client::{Client, Endpoint};
+use probing_proto::prelude::*;
 
 use crate::table::render_dataframe;
 
+/// Default of `--timeout`, in seconds
+pub const DEFAULT_TIMEOUT_SECS: f64 = 10.0;
+
+/// Interval between polls of the output of an eval job
+const EVAL_JOB_POLL: Duration = Duration::from_millis(200);
+
+/// Milliseconds clients wait for each reply, `0` for no limit
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new((DEFAULT_TIMEOUT_SECS * 1000.0) as u64);
+
+/// Wait at most `secs` seconds for each reply of the probes, forever if `0`
+pub fn set_timeout(secs: f64) -> Result<()> {
+    let timeout = Duration::try_from_secs_f64(secs)
+        .map_err(|_| anyhow::anyhow!("invalid timeout {secs}, expected seconds >= 0"))?;
+    TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+    Ok(())
+}
+
+fn timeout() -> Option<Duration> {
+    match TIMEOUT_MS.load(Ordering::Relaxed) {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
 pub async fn query(ctrl: ProbeEndpoint, query: Query) -> Result<()> {
-    let reply = ctrl.query(query).await?;
-    render_dataframe(&reply);
+    let mut reply = ctrl.query_data(query).await?;
+    // render paginated results page by page as they arrive
+    while let QueryDataFormat::Page(page) = reply {
+        render_dataframe(&page.df);
+        eprintln!(
+            "rows {}-{} of {}",
+            page.offset + 1,
+            page.offset + page.df.len(),
+            page.total
+        );
+        let Some(cursor) = page.next_cursor else {
+            return Ok(());
+        };
+        reply = ctrl.query_data(Query::next_page(cursor)).await?;
+    }
+    render_dataframe(&into_dataframe(reply)?);
     Ok(())
 }
 
+fn into_dataframe(reply: QueryDataFormat) -> Result<DataFrame> {
+    match reply {
+        QueryDataFormat::Error(err) => Err(anyhow::anyhow!("error: {}", err)),
+        QueryDataFormat::Nil => Ok(Default::default()),
+        QueryDataFormat::DataFrame(df) => Ok(df),
+        QueryDataFormat::Page(page) => Ok(page.df),
+        QueryDataFormat::TimeSeries(_) => Err(anyhow::anyhow!("error: unexpected time series")),
+    }
+}
+
 #[derive(Clone)]
 pub enum ProbeEndpoint {
     Ptrace { pid: i32 },
@@ -56,27 +106,67 @@ impl From<ProbeEndpoint> for String {
 }
 
 impl ProbeEndpoint {
-    async fn send_request(&self, url: &str, body: &str) -> Result<String> {
-        // Await request directly
-        let bytes = request(self.clone(), url, Some(body.to_string())).await?;
-        Ok(String::from_utf8(bytes)?)
+    /// Client of the probe API, authenticated with `PROBING_AUTH_TOKEN` and
+    /// waiting `--timeout` for replies
+    pub fn client(&self) -> Result<Client> {
+        let endpoint = match self {
+            ProbeEndpoint::Ptrace { pid } | ProbeEndpoint::Local { pid } => {
+                Endpoint::Local { pid: *pid }
+            }
+            ProbeEndpoint::Remote { addr } => Endpoint::Remote { addr: addr.clone() },
+            ProbeEndpoint::Launch { cmd } => {
+                anyhow::bail!("`{cmd}` is not running, it has no probe to send requests to")
+            }
+        };
+        Ok(Client::new(endpoint)
+            .with_token_from_env()
+            .with_timeout(timeout()))
     }
 
     pub async fn backtrace(&self, tid: Option<i32>) -> Result<()> {
-        let mut url = "/apis/pythonext/callstack".to_string();
-        if let Some(tid) = tid {
-            url = format!("/apis/pythonext/callstack?tid={tid}");
+        for f in self.client()?.callstack(tid).await? {
+            println!("{f}")
         }
-        let reply = request(self.clone(), &url, None).await?;
-        match serde_json::from_slice::<Vec<CallFrame>>(&reply) {
-            Ok(msg) => {
-                for f in msg {
-                    println!("{f}")
-                }
-                Ok(())
-            }
-            Err(err) => Err(anyhow::anyhow!("error: {}", err)),
+        Ok(())
+    }
+
+    pub async fn pause(&self, duration: Option<f64>) -> Result<()> {
+        let url = match duration {
+            Some(secs) => format!("/apis/pythonext/pause?duration={secs}"),
+            None => "/apis/pythonext/pause".to_string(),
+        };
+        self.pause_state(&url).await
+    }
+
+    pub async fn resume(&self) -> Result<()> {
+        self.pause_state("/apis/pythonext/resume").await
+    }
+
+    pub async fn paused(&self) -> Result<()> {
+        self.pause_state("/apis/pythonext/paused").await
+    }
+
+    async fn pause_state(&self, url: &str) -> Result<()> {
+        let state = self.client()?.pause_state(url).await?;
+        println!("{}", format_pause_state(&state));
+        Ok(())
+    }
+
+    pub async fn repl_sessions(&self) -> Result<()> {
+        let sessions = self.client()?.repl_sessions().await?;
+        if sessions.is_empty() {
+            println!("no REPL sessions");
         }
+        for session in sessions {
+            println!("{}", format_repl_session(&session));
+        }
+        Ok(())
+    }
+
+    pub async fn close_repl_session(&self, name: &str) -> Result<()> {
+        let session = self.client()?.close_repl_session(name).await?;
+        println!("closed {}", format_repl_session(&session));
+        Ok(())
     }
 
     pub async fn rdma(&self, hca_name: String) -> Result<()> {
@@ -87,6 +177,40 @@ impl ProbeEndpoint {
         Ok(())
     }
 
+    /// Fail unless the probe was built with `feature`, needed by `command`
+    pub async fn require(&self, feature: &str, command: &str) -> Result<()> {
+        if !self.client()?.capabilities().await?.has(feature) {
+            anyhow::bail!(
+                "{command} needs a probe built with the `{feature}` feature, \
+                 this one is a minimal build"
+            );
+        }
+        Ok(())
+    }
+
+    /// Warn when the probe speaks a protocol this CLI does not support;
+    /// unreachable probes are left to the command to report
+    pub async fn check_version(&self) {
+        let Ok(client) = self.client() else {
+            return;
+        };
+        if let Ok(Some(build)) = client.version().await {
+            if let Some(reason) = build.incompatibility(&ProtocolVersion::current()) {
+                eprintln!("warning: {reason}, upgrade one of them if commands fail");
+            }
+        }
+    }
+
+    pub async fn report(&self, out: &std::path::Path, spans: usize, rows: usize) -> Result<()> {
+        self.require(FEATURE_ANALYTICS, "report").await?;
+        let url = format!("/apis/report?spans={spans}&rows={rows}");
+        let page = self.client()?.get(&url).await?;
+        std::fs::write(out, &page)
+            .map_err(|e| anyhow::anyhow!("failed to write {}: {e}", out.display()))?;
+        println!("report written to {}", out.display());
+        Ok(())
+    }
+
     pub async fn eval(&self, code: String) -> Result<()> {
         let reply = request(self.clone(), "/apis/pythonext/eval", Some(code)).await?;
         let reply_str = String::from_utf8(reply)?;
@@ -133,78 +257,169 @@ impl ProbeEndpoint {
         Ok(())
     }
 
-    pub async fn query(&self, q: Query) -> Result<DataFrame> {
-        let request = Message::new(q);
-        let q_str = serde_json::to_string(&request)?;
-        let reply_str = self.send_request("/query", &q_str).await?; // Renamed reply variable
-        let reply = serde_json::from_str::<Message<QueryDataFormat>>(&reply_str)?.payload;
-
-        match reply {
-            QueryDataFormat::Error(err) => Err(anyhow::anyhow!("error: {}", err)),
-            QueryDataFormat::Nil => Ok(Default::default()),
-            QueryDataFormat::DataFrame(df) => Ok(df),
-            QueryDataFormat::TimeSeries(_) => todo!(),
+    /// Run `code` as an eval job and print its output as it comes
+    pub async fn eval_job(&self, code: &str, session: Option<&str>) -> Result<()> {
+        let client = self.client()?;
+        let id = client.start_eval_job(code, session).await?.id;
+        eprintln!("eval job {id} started, cancel it with `probing eval --cancel {id}`");
+        let mut offset = 0;
+        loop {
+            let reply = client.eval_job(id, offset).await?;
+            print!("{}", reply.output);
+            std::io::stdout().flush()?;
+            offset = reply.offset;
+            match reply.job.state {
+                EvalJobState::Running => tokio::time::sleep(EVAL_JOB_POLL).await,
+                EvalJobState::Done => return Ok(()),
+                state => anyhow::bail!("eval job {id} {state}"),
+            }
         }
     }
-}
 
-pub async fn request(ctrl: ProbeEndpoint, url: &str, body: Option<String>) -> Result<Vec<u8>> {
-    use hyper::body::Bytes;
-    use hyper::client::conn;
-    use hyper::Request;
-
-    let mut sender = match ctrl {
-        ProbeEndpoint::Ptrace { pid } | ProbeEndpoint::Local { pid } => {
-            eprintln!("sending ctrl commands via unix socket...");
-            #[cfg(target_os = "linux")]
-            let path = format!("\0probing-{}", pid);
-            #[cfg(not(target_os = "linux"))]
-            let path = {
-                let temp_dir = std::env::temp_dir();
-                let file_path = temp_dir.join(format!("probing-{}.sock", pid));
-                file_path.to_string_lossy().to_string()
-            };
-            let stream = tokio::net::UnixStream::connect(path).await?;
-            let io = TokioIo::new(stream);
+    pub async fn eval_jobs(&self) -> Result<()> {
+        let jobs = self.client()?.eval_jobs().await?;
+        if jobs.is_empty() {
+            println!("no eval jobs");
+        }
+        for job in jobs {
+            println!("{}", format_eval_job(&job));
+        }
+        Ok(())
+    }
 
-            let (sender, connection) = conn::http1::handshake(io).await?;
-            tokio::spawn(async move {
-                if let Err(e) = connection.await {
-                    eprintln!("Connection error: {e}");
-                }
-            });
-            sender
+    pub async fn cancel_eval_job(&self, id: u64) -> Result<()> {
+        let job = self.client()?.cancel_eval_job(id).await?;
+        println!("cancelling {}", format_eval_job(&job));
+        Ok(())
+    }
+
+    /// Follow the `/events` server-sent-events stream and print each notification
+    pub async fn events(&self, raw: bool) -> Result<()> {
+        let mut res = self.client()?.send("/events", None).await?;
+        if !res.status().is_success() {
+            return Err(anyhow::anyhow!("error: server returned {}", res.status()));
         }
-        ProbeEndpoint::Remote { addr } => {
-            eprintln!("sending ctrl commands via tcp socket...");
-            let stream = tokio::net::TcpStream::connect(addr).await?;
-            let io = TokioIo::new(stream);
-
-            let (sender, connection) = conn::http1::handshake(io).await?;
-            tokio::spawn(async move {
-                if let Err(err) = connection.await {
-                    eprintln!("Connection error: {err}");
+
+        let mut buffer = String::new();
+        while let Some(frame) = res.frame().await {
+            let Ok(data) = frame?.into_data() else {
+                continue;
+            };
+            buffer.push_str(&String::from_utf8_lossy(&data));
+
+            // SSE events are separated by a blank line
+            while let Some(pos) = buffer.find("\n\n") {
+                let block: String = buffer.drain(..pos + 2).collect();
+                if let Some(line) = format_sse_block(&block, raw) {
+                    println!("{line}");
+                    std::io::stdout().flush()?;
                 }
-            });
-            sender
+            }
         }
-        _ => todo!(),
+        Ok(())
+    }
+
+    /// Run a query, fetching and joining all pages of a paginated result
+    pub async fn query(&self, q: Query) -> Result<DataFrame> {
+        Ok(self.client()?.query(q).await?)
+    }
+
+    pub async fn query_data(&self, q: Query) -> Result<QueryDataFormat> {
+        Ok(self.client()?.query_data(q).await?)
+    }
+}
+
+pub async fn request(ctrl: ProbeEndpoint, url: &str, body: Option<String>) -> Result<Vec<u8>> {
+    Ok(ctrl.client()?.request(url, body).await?)
+}
+
+pub use probing_client::socket_path;
+
+fn format_pause_state(state: &PauseState) -> String {
+    let time = |micros: u64| {
+        chrono::DateTime::from_timestamp_micros(micros as i64)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%H:%M:%S%.3f")
+                    .to_string()
+            })
+            .unwrap_or_default()
+    };
+    let mut line = match (state.paused, state.requested) {
+        (true, _) => "paused".to_string(),
+        (false, true) => "pausing, waiting for a safe point".to_string(),
+        (false, false) => return "running".to_string(),
+    };
+    if let Some(since) = state.since {
+        line.push_str(&format!(" since {}", time(since)));
+    }
+    if let Some(until) = state.until {
+        line.push_str(&format!(", resumes at {}", time(until)));
+    }
+    line
+}
+
+fn format_repl_session(session: &ReplSession) -> String {
+    let idle = chrono::Utc::now().timestamp_micros() - session.last_active;
+    let attached = match session.attached {
+        0 => "detached".to_string(),
+        n => format!("{n} attached"),
     };
-    let request = if let Some(body) = body {
-        Request::builder()
-            .method("POST")
-            .uri(url)
-            .body(Full::<Bytes>::from(body))
-            .map_err(|e| anyhow::anyhow!("Failed to build POST request: {e}"))?
-    } else {
-        Request::builder()
-            .method("GET")
-            .uri(url)
-            .body(Full::<Bytes>::default())
-            .map_err(|e| anyhow::anyhow!("Failed to build GET request: {e}"))?
+    format!(
+        "{}: {attached}, {} commands, idle {}s",
+        session.name,
+        session.commands,
+        idle.max(0) / 1_000_000
+    )
+}
+
+fn format_eval_job(job: &EvalJob) -> String {
+    let end = job
+        .finished
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_micros());
+    let session = match &job.session {
+        Some(session) => format!(" in session {session}"),
+        None => String::new(),
     };
+    format!(
+        "{}: {}{session}, {} bytes of output, {}s",
+        job.id,
+        job.state,
+        job.output_bytes,
+        (end - job.started).max(0) / 1_000_000
+    )
+}
 
-    let res = sender.send_request(request).await?;
+/// Render one SSE event block, returning `None` for keep-alive comments
+fn format_sse_block(block: &str, raw: bool) -> Option<String> {
+    let mut name = "message";
+    let mut data = vec![];
+    for line in block.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = value.trim();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.trim_start());
+        }
+    }
+    if data.is_empty() {
+        return None;
+    }
+    let data = data.join("\n");
+    if raw {
+        return Some(data);
+    }
 
-    Ok(res.collect().await.map(|x| x.to_bytes().to_vec())?)
+    match serde_json::from_str::<Message<AgentEvent>>(&data) {
+        Ok(msg) => {
+            let time = chrono::DateTime::from_timestamp_micros(msg.timestamp as i64)
+                .map(|t| {
+                    t.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M:%S%.3f")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            Some(format!("{time} {}", msg.payload))
+        }
+        Err(_) => Some(format!("[{name}] {data}")),
+    }
 }
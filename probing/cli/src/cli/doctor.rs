@@ -0,0 +1,491 @@
+//! `probing doctor`, a diagnosis of the environment probing runs in.
+//!
+//! Injecting into a process needs `ptrace`, which the kernel, the container
+//! runtime and other debuggers can all take away. Each check reports what it
+//! found and, when something is off, the command that fixes it.
+
+use std::net::TcpListener;
+use std::path::Path;
+
+use anyhow::Result;
+use clap::Args;
+use serde_json::{json, Value};
+
+/// `CAP_SYS_PTRACE` in the capability sets of `/proc/<pid>/status`
+const CAP_SYS_PTRACE: u32 = 19;
+
+/// Oldest glibc the manylinux2014 wheels are built against
+const MIN_GLIBC: (u32, u32) = (2, 17);
+
+/// Oldest Python the `abi3-py37` extension loads into
+const MIN_PYTHON: (u32, u32) = (3, 7);
+
+/// Tools that attach with `ptrace` or hook the interpreter themselves, and
+/// what they do to an injection
+const PROFILERS: &[(&str, &str)] = &[
+    ("py-spy", "attaches with ptrace"),
+    ("austin", "reads the interpreter memory"),
+    ("gdb", "attaches with ptrace"),
+    ("strace", "attaches with ptrace"),
+    ("ltrace", "attaches with ptrace"),
+    ("perf", "samples the process"),
+    ("nsys", "injects its own library"),
+    ("ncu", "injects its own library"),
+    ("scalene", "replaces the allocator and signal handlers"),
+    ("memray", "replaces the allocator"),
+    ("viztracer", "installs its own trace function"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Ok,
+    Skipped,
+    Warn,
+    Fail,
+}
+
+impl Level {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Level::Ok => "ok",
+            Level::Skipped => "skip",
+            Level::Warn => "warn",
+            Level::Fail => "fail",
+        }
+    }
+}
+
+/// Outcome of one check, with the remediation when it is not `Ok`
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub level: Level,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, level: Level, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            level,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "check": self.name,
+            "level": self.level.as_str(),
+            "detail": self.detail,
+            "fix": self.fix,
+        })
+    }
+}
+
+#[derive(Args, Debug, Default)]
+pub struct DoctorCommand {
+    /// Port the probe server is configured to listen on, `RANDOM` or a number
+    #[arg(long, env = "PROBING_PORT")]
+    port: Option<String>,
+
+    /// Print the checks as one JSON object, e.g. to attach to a bug report
+    #[arg(long)]
+    json: bool,
+}
+
+impl DoctorCommand {
+    /// Run the checks, and those of the target process `pid` if any
+    pub fn run(&self, pid: Option<i32>) -> Result<()> {
+        let checks = self.checks(pid);
+        if self.json {
+            let checks = checks.iter().map(Check::to_json).collect::<Vec<_>>();
+            println!("{}", json!({ "pid": pid, "checks": checks }));
+        } else {
+            for check in &checks {
+                println!(
+                    "[{:>4}] {:<14} {}",
+                    check.level.as_str(),
+                    check.name,
+                    check.detail
+                );
+                if let Some(fix) = &check.fix {
+                    println!("{:22}fix: {fix}", "");
+                }
+            }
+        }
+
+        let failed = checks.iter().filter(|c| c.level == Level::Fail).count();
+        if failed > 0 {
+            anyhow::bail!("{failed} check(s) failed, injection is not going to work");
+        }
+        Ok(())
+    }
+
+    fn checks(&self, pid: Option<i32>) -> Vec<Check> {
+        let status = read("/proc/self/status").unwrap_or_default();
+        let cap_sys_ptrace = has_capability(&status, CAP_SYS_PTRACE);
+        let container = in_container();
+
+        let mut checks = vec![
+            ptrace_scope(
+                read("/proc/sys/kernel/yama/ptrace_scope").as_deref(),
+                cap_sys_ptrace,
+            ),
+            capabilities(cap_sys_ptrace, container),
+            seccomp(status_field(&status, "Seccomp").as_deref(), container),
+            port(self.port.as_deref()),
+            glibc(glibc_version().as_deref()),
+            python(pid),
+        ];
+        if let Some(pid) = pid {
+            let status = read(format!("/proc/{pid}/status"));
+            let tracer = status
+                .as_deref()
+                .and_then(|s| status_field(s, "TracerPid"))
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            checks.push(target(pid, status.is_some(), tracer, &comm(tracer)));
+        }
+        checks.push(profilers(&processes()));
+        checks
+    }
+}
+
+fn read(path: impl AsRef<Path>) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+fn comm(pid: i32) -> String {
+    read(format!("/proc/{pid}/comm"))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Value of `field` in a `/proc/<pid>/status` file
+fn status_field(status: &str, field: &str) -> Option<String> {
+    status.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key == field).then(|| value.trim().to_string())
+    })
+}
+
+fn has_capability(status: &str, cap: u32) -> bool {
+    status_field(status, "CapEff")
+        .and_then(|caps| u64::from_str_radix(&caps, 16).ok())
+        .is_some_and(|caps| caps & (1 << cap) != 0)
+}
+
+fn in_container() -> bool {
+    Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+        || std::env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+        || read("/proc/1/cgroup").is_some_and(|cgroup| {
+            ["docker", "kubepods", "containerd", "lxc", "podman"]
+                .iter()
+                .any(|runtime| cgroup.contains(runtime))
+        })
+}
+
+fn ptrace_scope(scope: Option<&str>, cap_sys_ptrace: bool) -> Check {
+    const NAME: &str = "ptrace_scope";
+    const FIX: &str = "echo 0 | sudo tee /proc/sys/kernel/yama/ptrace_scope";
+    let Some(scope) = scope.map(str::trim) else {
+        return Check::new(NAME, Level::Ok, "yama is not enabled");
+    };
+    match scope {
+        "0" => Check::new(
+            NAME,
+            Level::Ok,
+            "0, processes of the same user can be traced",
+        ),
+        "1" | "2" if cap_sys_ptrace => Check::new(
+            NAME,
+            Level::Ok,
+            format!("{scope}, allowed by CAP_SYS_PTRACE"),
+        ),
+        "1" => Check::new(
+            NAME,
+            Level::Warn,
+            "1, only child processes can be traced, `probing launch` works but not injection",
+        )
+        .fix(format!("{FIX}, or run probing with sudo")),
+        "2" => Check::new(NAME, Level::Fail, "2, only CAP_SYS_PTRACE can trace")
+            .fix(format!("{FIX}, or run probing with sudo")),
+        "3" => Check::new(NAME, Level::Fail, "3, ptrace is disabled until reboot")
+            .fix("set kernel.yama.ptrace_scope=0 in /etc/sysctl.d and reboot"),
+        other => Check::new(NAME, Level::Warn, format!("unknown value {other}")),
+    }
+}
+
+fn capabilities(cap_sys_ptrace: bool, container: bool) -> Check {
+    const NAME: &str = "capabilities";
+    match (cap_sys_ptrace, container) {
+        (true, _) => Check::new(NAME, Level::Ok, "CAP_SYS_PTRACE is effective"),
+        (false, false) => Check::new(
+            NAME,
+            Level::Ok,
+            "no CAP_SYS_PTRACE, processes of the same user can still be traced",
+        ),
+        (false, true) => Check::new(
+            NAME,
+            Level::Warn,
+            "running in a container without CAP_SYS_PTRACE",
+        )
+        .fix(
+            "docker run --cap-add=SYS_PTRACE, or add SYS_PTRACE to \
+             securityContext.capabilities.add of the pod",
+        ),
+    }
+}
+
+fn seccomp(mode: Option<&str>, container: bool) -> Check {
+    const NAME: &str = "seccomp";
+    match mode {
+        Some("2") if container => Check::new(
+            NAME,
+            Level::Warn,
+            "filtered, the default profile of older container runtimes blocks ptrace",
+        )
+        .fix("docker run --security-opt seccomp=unconfined, if injection fails with EPERM"),
+        Some("2") => Check::new(NAME, Level::Warn, "filtered, ptrace may be blocked"),
+        Some(_) => Check::new(NAME, Level::Ok, "no syscall filter"),
+        None => Check::new(NAME, Level::Skipped, "unknown"),
+    }
+}
+
+fn port(port: Option<&str>) -> Check {
+    const NAME: &str = "port";
+    let port = match port.map(str::trim) {
+        None => {
+            return Check::new(
+                NAME,
+                Level::Ok,
+                "PROBING_PORT is not set, only the local socket is served",
+            )
+        }
+        Some(p) if p.eq_ignore_ascii_case("random") => {
+            return Check::new(NAME, Level::Ok, "a random port is picked at startup")
+        }
+        Some(p) => p,
+    };
+    let Ok(port) = port.parse::<u16>() else {
+        return Check::new(NAME, Level::Fail, format!("`{port}` is not a port number"))
+            .fix("set PROBING_PORT to a number or to RANDOM");
+    };
+    match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(_) => Check::new(NAME, Level::Ok, format!("{port} is free")),
+        Err(err) => {
+            Check::new(NAME, Level::Fail, format!("cannot listen on {port}: {err}")).fix(format!(
+            "stop the process using it (ss -ltnp 'sport = :{port}'), or set PROBING_PORT=RANDOM"
+        ))
+        }
+    }
+}
+
+/// `major.minor` of a version string, e.g. `2.35` or `Python 3.10.12`
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let version = version.trim().rsplit(' ').next()?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts
+        .next()?
+        .trim_end_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .ok()?;
+    Some((major, minor))
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn glibc_version() -> Option<String> {
+    // SAFETY: returns a pointer to a static NUL terminated string
+    let version = unsafe { std::ffi::CStr::from_ptr(libc::gnu_get_libc_version()) };
+    version.to_str().ok().map(str::to_string)
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn glibc_version() -> Option<String> {
+    None
+}
+
+fn glibc(version: Option<&str>) -> Check {
+    const NAME: &str = "glibc";
+    let Some(version) = version else {
+        return Check::new(NAME, Level::Skipped, "not linked against glibc");
+    };
+    match parse_version(version) {
+        Some(v) if v >= MIN_GLIBC => Check::new(NAME, Level::Ok, version),
+        Some(_) => Check::new(
+            NAME,
+            Level::Fail,
+            format!("{version} is older than {}.{}", MIN_GLIBC.0, MIN_GLIBC.1),
+        )
+        .fix("use a distribution with a newer glibc, or build probing from source"),
+        None => Check::new(NAME, Level::Warn, format!("unknown version {version}")),
+    }
+}
+
+/// Python version of the target process, or of `python3` on the `PATH`
+fn python_version(pid: Option<i32>) -> Option<(String, String)> {
+    if let Some(pid) = pid {
+        let maps = read(format!("/proc/{pid}/maps"))?;
+        let exe = std::fs::read_link(format!("/proc/{pid}/exe")).ok();
+        let version = maps
+            .lines()
+            .filter_map(|line| line.rsplit('/').next())
+            .chain(exe.iter().filter_map(|exe| exe.file_name()?.to_str()))
+            .find_map(|name| {
+                let name = name.strip_prefix("libpython").unwrap_or(name);
+                let name = name.strip_prefix("python")?;
+                let version = name.split(".so").next()?;
+                parse_version(version).map(|(major, minor)| format!("{major}.{minor}"))
+            })?;
+        return Some((version, format!("process {pid}")));
+    }
+    let output = std::process::Command::new("python3")
+        .arg("--version")
+        .output()
+        .ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Some((version, "python3".to_string()))
+}
+
+fn python(pid: Option<i32>) -> Check {
+    const NAME: &str = "python";
+    let Some((version, source)) = python_version(pid) else {
+        return match pid {
+            Some(pid) => Check::new(
+                NAME,
+                Level::Warn,
+                format!("process {pid} is not running Python"),
+            )
+            .fix("only Python processes can be probed, check the pid"),
+            None => Check::new(NAME, Level::Skipped, "python3 is not on the PATH"),
+        };
+    };
+    match parse_version(&version) {
+        Some(v) if v >= MIN_PYTHON => Check::new(NAME, Level::Ok, format!("{version} ({source})")),
+        Some(_) => Check::new(
+            NAME,
+            Level::Fail,
+            format!(
+                "{version} ({source}) is older than {}.{}",
+                MIN_PYTHON.0, MIN_PYTHON.1
+            ),
+        )
+        .fix("upgrade Python"),
+        None => Check::new(
+            NAME,
+            Level::Warn,
+            format!("unknown version {version} ({source})"),
+        ),
+    }
+}
+
+fn target(pid: i32, exists: bool, tracer: i32, tracer_comm: &str) -> Check {
+    const NAME: &str = "target";
+    if !exists {
+        return Check::new(NAME, Level::Fail, format!("process {pid} does not exist"))
+            .fix("check the pid, or run doctor inside the container of the process");
+    }
+    if tracer != 0 {
+        return Check::new(
+            NAME,
+            Level::Fail,
+            format!("process {pid} is already traced by {tracer} ({tracer_comm})"),
+        )
+        .fix(format!(
+            "detach {tracer_comm} first, a process has a single tracer"
+        ));
+    }
+    Check::new(NAME, Level::Ok, format!("process {pid} is not traced"))
+}
+
+/// `(pid, comm)` of the running processes
+fn processes() -> Vec<(i32, String)> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+        .map(|pid| (pid, comm(pid)))
+        .collect()
+}
+
+fn profilers(processes: &[(i32, String)]) -> Check {
+    const NAME: &str = "profilers";
+    let found = processes
+        .iter()
+        .filter_map(|(pid, comm)| {
+            let (name, effect) = PROFILERS.iter().find(|(name, _)| comm == name)?;
+            Some(format!("{name} ({pid}) {effect}"))
+        })
+        .collect::<Vec<_>>();
+    if found.is_empty() {
+        return Check::new(NAME, Level::Ok, "no conflicting profiler is running");
+    }
+    Check::new(NAME, Level::Warn, found.join("; "))
+        .fix("stop them while probing the same process, or results may be skewed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ptrace_checks() {
+        assert_eq!(ptrace_scope(None, false).level, Level::Ok);
+        assert_eq!(ptrace_scope(Some("0\n"), false).level, Level::Ok);
+        assert_eq!(ptrace_scope(Some("1\n"), false).level, Level::Warn);
+        assert_eq!(ptrace_scope(Some("2\n"), true).level, Level::Ok);
+        assert_eq!(ptrace_scope(Some("2\n"), false).level, Level::Fail);
+        assert_eq!(ptrace_scope(Some("3\n"), true).level, Level::Fail);
+
+        let status = "Name:\tpython\nTracerPid:\t0\nCapEff:\t00000000a80425fb\n";
+        assert_eq!(status_field(status, "TracerPid").as_deref(), Some("0"));
+        assert!(!has_capability(status, CAP_SYS_PTRACE));
+        assert!(has_capability(
+            "CapEff:\t0000000000080000\n",
+            CAP_SYS_PTRACE
+        ));
+        assert!(capabilities(false, true).fix.is_some());
+
+        assert_eq!(target(1, true, 0, "").level, Level::Ok);
+        let traced = target(1, true, 42, "py-spy");
+        assert_eq!(traced.level, Level::Fail);
+        assert!(traced.detail.contains("py-spy"));
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(parse_version("2.35"), Some((2, 35)));
+        assert_eq!(parse_version("Python 3.10.12\n"), Some((3, 10)));
+        assert_eq!(parse_version("3.12t"), Some((3, 12)));
+        assert_eq!(parse_version("unknown"), None);
+        assert_eq!(glibc(Some("2.17")).level, Level::Ok);
+        assert_eq!(glibc(Some("2.12")).level, Level::Fail);
+        assert_eq!(glibc(None).level, Level::Skipped);
+    }
+
+    #[test]
+    fn test_port_and_profilers() {
+        assert_eq!(port(None).level, Level::Ok);
+        assert_eq!(port(Some("random")).level, Level::Ok);
+        assert_eq!(port(Some("http")).level, Level::Fail);
+        let listener = TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let used = listener.local_addr().unwrap().port().to_string();
+        assert_eq!(port(Some(&used)).level, Level::Fail);
+
+        let processes = [(10, "bash".to_string()), (11, "py-spy".to_string())];
+        let check = profilers(&processes);
+        assert_eq!(check.level, Level::Warn);
+        assert!(check.detail.starts_with("py-spy (11)"));
+        assert_eq!(profilers(&processes[..1]).level, Level::Ok);
+    }
+}
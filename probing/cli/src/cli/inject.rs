@@ -1,3 +1,4 @@
+use crate::cli::job::{find_workers, local_processes, JobSpec};
 use crate::inject::{InjectionTrait, Injector, LibcAddrs, Process};
 use anyhow::{anyhow, Context, Error, Result};
 use clap::Args;
@@ -6,6 +7,7 @@ use std::os::unix::ffi::OsStringExt;
 
 use super::ctrl;
 use super::ctrl::ProbeEndpoint;
+use super::verify;
 
 /// The x64 shellcode that will be injected into the tracee.
 const SHELLCODE: [u8; 6] = [
@@ -358,14 +360,46 @@ impl<'a> InjectionTrait for Injection<'a> {
 }
 
 /// Inject into the target process
-#[derive(Args, Default, Debug)]
+///
+/// Once the library is loaded, the probe socket is polled until the probe
+/// answers, and its version and features are printed, see `probing verify`.
+///
+/// ```bash
+/// $ probing -t 1234 inject -D probing.torch.profiling=on
+/// $ probing inject --job node0:29500 -D probing.torch.profiling=on
+/// ```
+#[derive(Args, Debug)]
 pub struct InjectCommand {
     #[arg(short = 'D', long = "define", num_args = 1..)]
     settings: Vec<String>,
+
+    /// Inject into every local worker of the torchrun job with this master
+    /// address, `<host>:<port>`, `<host>` or `:<port>`, instead of a target
+    #[arg(long, value_name = "MASTER_ADDR")]
+    pub job: Option<String>,
+
+    /// Return once the library is loaded, without waiting for the probe to answer
+    #[arg(long)]
+    no_verify: bool,
+
+    /// Seconds to wait for the probe to answer after injecting
+    #[arg(long, default_value_t = verify::DEFAULT_TIMEOUT_SECS)]
+    verify_timeout: u64,
+}
+
+impl Default for InjectCommand {
+    fn default() -> Self {
+        Self {
+            settings: vec![],
+            job: None,
+            no_verify: false,
+            verify_timeout: verify::DEFAULT_TIMEOUT_SECS,
+        }
+    }
 }
 
 impl InjectCommand {
-    fn check_library(&self, pid: i32, lib_name: &str) -> Result<bool> {
+    pub(crate) fn check_library(&self, pid: i32, lib_name: &str) -> Result<bool> {
         Ok(procfs::process::Process::new(pid)?.maps()?.iter().any(|m| {
             matches!(
                 &m.pathname,
@@ -408,12 +442,46 @@ impl InjectCommand {
             .map_err(|e| anyhow!("Failed to inject probing: {}\n\t{}", e, e.root_cause()))
     }
 
+    /// The same injection, returning once the library is loaded
+    pub fn without_verify(self) -> Self {
+        Self {
+            no_verify: true,
+            ..self
+        }
+    }
+
+    /// Local workers of the `--job`, as targets named after their rank
+    pub fn job_targets(&self) -> Result<Vec<(String, ProbeEndpoint)>> {
+        let Some(job) = &self.job else {
+            return Ok(vec![]);
+        };
+        let spec = job.parse::<JobSpec>()?;
+        let workers = find_workers(&local_processes()?, &spec);
+        if workers.is_empty() {
+            return Err(anyhow!("no local worker of job {job} found"));
+        }
+        Ok(workers
+            .into_iter()
+            .map(|w| (w.name(), ProbeEndpoint::Local { pid: w.pid }))
+            .collect())
+    }
+
     pub async fn run(&self, ctrl: ProbeEndpoint) -> Result<()> {
         match ctrl {
             ProbeEndpoint::Ptrace { pid } | ProbeEndpoint::Local { pid } => {
                 if !self.check_library(pid, "libprobing.so")? {
+                    println!("checking that {pid} runs Python");
                     self.wait_for_library(pid, "python")?;
-                    self.inject(pid)
+                    self.inject(pid)?;
+                    if self.no_verify {
+                        return Ok(());
+                    }
+                    let timeout = std::time::Duration::from_secs(self.verify_timeout);
+                    verify::verify(&ProbeEndpoint::Local { pid }, timeout)
+                        .await
+                        .map_err(|e| {
+                            anyhow!("injected into {pid}, but the probe does not answer: {e}")
+                        })
                 } else {
                     let settings = self.build_settings();
                     let query: Vec<String> = settings
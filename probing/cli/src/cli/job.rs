@@ -0,0 +1,292 @@
+//! Local workers of a running `torchrun` job.
+//!
+//! `probing inject --job <master addr>` probes every rank of a job on this
+//! host at once. A job is named by the rendezvous address of its master,
+//! `host:port`, `host` or `:port`. A process is a worker of the job when it
+//! has the `RANK` or `LOCAL_RANK` set by the launcher, and either its
+//! `MASTER_ADDR`/`MASTER_PORT` match, or one of its ancestors is a launcher
+//! started with a matching `--master_addr`, `--master_port` or
+//! `--rdzv_endpoint`. Processes forked by a worker, such as data loader
+//! workers, inherit its environment and are left out.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+/// Master address of a job; unset parts match any value
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JobSpec {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl std::str::FromStr for JobSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (host, port) = match s.trim().rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow!("invalid job `{s}`: bad port `{port}`"))?;
+                (host, Some(port))
+            }
+            None => (s.trim(), None),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() && port.is_none() {
+            return Err(anyhow!(
+                "invalid job `{s}`, expected <master addr>[:<port>] or :<port>"
+            ));
+        }
+        Ok(JobSpec {
+            host: (!host.is_empty()).then(|| host.to_string()),
+            port,
+        })
+    }
+}
+
+impl JobSpec {
+    fn matches(&self, host: Option<&str>, port: Option<&str>) -> bool {
+        let host_ok = match (&self.host, host) {
+            (None, _) => true,
+            (Some(want), Some(host)) => same_host(want, host),
+            (Some(_), None) => false,
+        };
+        let port_ok = match (self.port, port) {
+            (None, _) => true,
+            (Some(want), Some(port)) => port.trim().parse() == Ok(want),
+            (Some(_), None) => false,
+        };
+        host_ok && port_ok
+    }
+
+    /// Whether a launcher command line sets the master address of the job
+    fn matches_launcher(&self, cmdline: &[String]) -> bool {
+        let is_launcher = cmdline.iter().take(3).any(|arg| {
+            arg.ends_with("torchrun")
+                || arg == "torch.distributed.run"
+                || arg == "torch.distributed.launch"
+        });
+        if !is_launcher {
+            return false;
+        }
+        let (host, port) = launcher_master(cmdline);
+        (host.is_some() || port.is_some()) && self.matches(host.as_deref(), port.as_deref())
+    }
+}
+
+fn same_host(a: &str, b: &str) -> bool {
+    const LOCAL: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+    a.eq_ignore_ascii_case(b) || (LOCAL.contains(&a) && LOCAL.contains(&b))
+}
+
+/// `--master_addr`, `--master_port` and `--rdzv_endpoint` of a launcher,
+/// spelled with `_` or `-`, as one or two arguments
+fn launcher_master(cmdline: &[String]) -> (Option<String>, Option<String>) {
+    let mut host = None;
+    let mut port = None;
+    let mut args = cmdline.iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            continue;
+        };
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name.replace('-', "_"), Some(value.to_string())),
+            None => (flag.replace('-', "_"), None),
+        };
+        if !matches!(
+            name.as_str(),
+            "master_addr" | "master_port" | "rdzv_endpoint"
+        ) {
+            continue;
+        }
+        let Some(value) = value.or_else(|| args.next().cloned()) else {
+            break;
+        };
+        match name.as_str() {
+            "master_addr" => host = Some(value),
+            "master_port" => port = Some(value),
+            _ => match value.rsplit_once(':') {
+                Some((h, p)) => {
+                    host = Some(h.to_string());
+                    port = Some(p.to_string());
+                }
+                None => host = Some(value),
+            },
+        }
+    }
+    (host, port)
+}
+
+/// What discovery needs to know about a process
+#[derive(Debug, Default, Clone)]
+pub struct ProcEntry {
+    pub pid: i32,
+    pub ppid: i32,
+    pub cmdline: Vec<String>,
+    pub env: HashMap<String, String>,
+}
+
+/// A rank of the job running on this host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Worker {
+    pub pid: i32,
+    pub rank: Option<i64>,
+    pub local_rank: Option<i64>,
+}
+
+impl Worker {
+    pub fn name(&self) -> String {
+        match (self.rank, self.local_rank) {
+            (Some(rank), _) => format!("rank {rank} (pid {})", self.pid),
+            (None, Some(local)) => format!("local rank {local} (pid {})", self.pid),
+            _ => format!("pid {}", self.pid),
+        }
+    }
+}
+
+fn rank_env(entry: &ProcEntry, name: &str) -> Option<i64> {
+    entry.env.get(name).and_then(|v| v.trim().parse().ok())
+}
+
+/// Workers of `job` among `procs`, ordered by rank
+pub fn find_workers(procs: &[ProcEntry], job: &JobSpec) -> Vec<Worker> {
+    let by_pid = procs.iter().map(|p| (p.pid, p)).collect::<HashMap<_, _>>();
+    let ancestors = |entry: &ProcEntry| {
+        let mut chain = vec![];
+        let mut ppid = entry.ppid;
+        while let Some(parent) = by_pid.get(&ppid) {
+            // guard against pid reuse loops
+            if chain.len() > by_pid.len() || parent.pid == entry.pid {
+                break;
+            }
+            chain.push(*parent);
+            ppid = parent.ppid;
+        }
+        chain
+    };
+    let is_worker = |entry: &ProcEntry| {
+        let ranked = entry.env.contains_key("RANK") || entry.env.contains_key("LOCAL_RANK");
+        ranked
+            && (job.matches(
+                entry.env.get("MASTER_ADDR").map(String::as_str),
+                entry.env.get("MASTER_PORT").map(String::as_str),
+            ) || ancestors(entry)
+                .iter()
+                .any(|parent| job.matches_launcher(&parent.cmdline)))
+    };
+
+    let mut workers = procs
+        .iter()
+        .filter(|entry| is_worker(entry))
+        // children of a worker share its rank, probe the worker itself
+        .filter(|entry| !ancestors(entry).iter().any(|parent| is_worker(parent)))
+        .map(|entry| Worker {
+            pid: entry.pid,
+            rank: rank_env(entry, "RANK"),
+            local_rank: rank_env(entry, "LOCAL_RANK"),
+        })
+        .collect::<Vec<_>>();
+    workers.sort_by_key(|w| (w.rank, w.local_rank, w.pid));
+    workers
+}
+
+/// Read the processes of this host; the environment of those that cannot be
+/// read, e.g. of other users, is left empty
+pub fn local_processes() -> Result<Vec<ProcEntry>> {
+    let mut procs = vec![];
+    for process in procfs::process::all_processes()?.filter_map(|p| p.ok()) {
+        let Ok(stat) = process.stat() else {
+            continue;
+        };
+        let env = process
+            .environ()
+            .map(|env| {
+                env.into_iter()
+                    .map(|(k, v)| {
+                        (
+                            k.to_string_lossy().into_owned(),
+                            v.to_string_lossy().into_owned(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        procs.push(ProcEntry {
+            pid: process.pid(),
+            ppid: stat.ppid,
+            cmdline: process.cmdline().unwrap_or_default(),
+            env,
+        });
+    }
+    Ok(procs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pid: i32, ppid: i32, cmdline: &str, env: &[(&str, &str)]) -> ProcEntry {
+        ProcEntry {
+            pid,
+            ppid,
+            cmdline: cmdline.split_whitespace().map(str::to_string).collect(),
+            env: env
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_job() {
+        let job: JobSpec = "node0:29500".parse().unwrap();
+        assert_eq!(job.host.as_deref(), Some("node0"));
+        assert_eq!(job.port, Some(29500));
+        assert_eq!(":29500".parse::<JobSpec>().unwrap().host, None);
+        assert_eq!("node0".parse::<JobSpec>().unwrap().port, None);
+        assert!("node0:abc".parse::<JobSpec>().is_err());
+        assert!("".parse::<JobSpec>().is_err());
+    }
+
+    #[test]
+    fn test_find_workers() {
+        let rank = |r: &'static str| {
+            vec![
+                ("RANK", r),
+                ("LOCAL_RANK", r),
+                ("MASTER_ADDR", "node0"),
+                ("MASTER_PORT", "29500"),
+            ]
+        };
+        let procs = vec![
+            entry(
+                10,
+                1,
+                "/usr/bin/python /usr/bin/torchrun --nproc-per-node 2 --rdzv-endpoint node0:29500 train.py",
+                &[],
+            ),
+            entry(12, 10, "python train.py", &rank("1")),
+            entry(11, 10, "python train.py", &rank("0")),
+            // data loader worker of rank 0
+            entry(13, 11, "python train.py", &rank("0")),
+            // worker of another job
+            entry(20, 1, "python other.py", &[("RANK", "0"), ("MASTER_PORT", "29600")]),
+            // no master address of its own, found through the launcher
+            entry(14, 10, "python train.py", &[("LOCAL_RANK", "2")]),
+        ];
+
+        let job = "node0:29500".parse().unwrap();
+        let pids = find_workers(&procs, &job)
+            .iter()
+            .map(|w| w.pid)
+            .collect::<Vec<_>>();
+        assert_eq!(pids, [14, 11, 12]);
+
+        let job = ":29600".parse().unwrap();
+        let workers = find_workers(&procs, &job);
+        assert_eq!(workers.len(), 1);
+        assert_eq!(workers[0].name(), "rank 0 (pid 20)");
+    }
+}
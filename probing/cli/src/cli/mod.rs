@@ -1,16 +1,28 @@
 use anyhow::Result;
 use clap::Parser;
-use probing_proto::prelude::Query;
+use probing_proto::prelude::{Query, QueryOptions};
 
+#[cfg(target_os = "linux")]
+pub mod agentd;
+pub mod attach;
+pub mod bench;
+pub mod check;
 pub mod commands;
+pub mod config;
 pub mod ctrl;
+pub mod doctor;
 pub mod repl;
 
 pub mod store;
+pub mod targets;
+pub mod verify;
 
 #[cfg(target_os = "linux")]
 pub mod inject;
 
+#[cfg(target_os = "linux")]
+pub mod job;
+
 #[cfg(target_os = "linux")]
 pub mod process_monitor;
 
@@ -22,6 +34,7 @@ mod ptree;
 use crate::cli::ctrl::ProbeEndpoint;
 use commands::Commands;
 use once_cell::sync::Lazy;
+use targets::{SummaryFormat, TargetsFailed};
 
 fn get_build_info() -> String {
     let mut info = "0.2.1".to_string();
@@ -47,9 +60,28 @@ pub struct Cli {
     #[arg(short, long, global = true)]
     verbose: bool,
 
-    /// target process, PID (e.g., 1234) for local process, and <ip>:<port> for remote process
+    /// target process, PID (e.g., 1234) for local process, and <ip>:<port> for remote process;
+    /// repeat the flag or separate targets with commas to run the command against each of them
     #[arg(short, long)]
-    target: Option<String>,
+    target: Vec<String>,
+
+    /// Stop at the first target that fails and skip the remaining ones
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Number of retries, with exponential backoff, when a target cannot be reached;
+    /// commands changing the target are only retried if the connection was refused
+    #[arg(long, default_value_t = 2)]
+    retries: u32,
+
+    /// Seconds to wait for each reply of the probe, which cancels the work of
+    /// requests given up on; 0 waits forever
+    #[arg(long, default_value_t = ctrl::DEFAULT_TIMEOUT_SECS)]
+    timeout: f64,
+
+    /// Print a summary of the per-target results; defaults to `text` for multiple targets
+    #[arg(long, value_enum)]
+    summary: Option<SummaryFormat>,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -57,16 +89,22 @@ pub struct Cli {
 
 impl Cli {
     pub async fn run(&mut self) -> Result<()> {
+        ctrl::set_timeout(self.timeout)?;
+
         // Handle external commands first to avoid target requirement
         if let Some(Commands::External(args)) = &self.command {
-            std::env::set_var("PROBING_ENDPOINT", self.target.clone().unwrap_or_default());
+            std::env::set_var("PROBING_ENDPOINT", self.target.join(","));
             return handle_external_command(args);
         }
 
         // Handle commands that don't need a target
         match &self.command {
-            Some(Commands::List { verbose, tree }) => {
-                return self.handle_list_command(*verbose, *tree).await;
+            Some(Commands::List {
+                verbose,
+                tree,
+                scan,
+            }) => {
+                return self.handle_list_command(*verbose, *tree, *scan).await;
             }
             #[cfg(target_os = "linux")]
             Some(Commands::Launch { recursive, args }) => {
@@ -75,17 +113,107 @@ impl Cli {
             Some(Commands::Store(cmd)) => {
                 return cmd.run().await;
             }
+            #[cfg(target_os = "linux")]
+            Some(Commands::Agentd(cmd)) => {
+                return cmd.run().await;
+            }
+            #[cfg(target_os = "linux")]
+            Some(Commands::Inject(cmd)) if cmd.job.is_some() => {
+                if !self.target.is_empty() {
+                    anyhow::bail!("inject --job finds its targets, drop -t");
+                }
+                let targets = cmd.job_targets()?;
+                return self.run_targets(&targets).await;
+            }
+            Some(Commands::Verify(cmd)) if cmd.pid.is_some() => {
+                if !self.target.is_empty() {
+                    anyhow::bail!("verify takes either a pid or -t, not both");
+                }
+                let pid = cmd.pid.unwrap_or_default();
+                return cmd.run(ProbeEndpoint::Local { pid }).await;
+            }
+            Some(Commands::Doctor(cmd)) => {
+                let pid = match targets::parse_targets(&self.target)?.as_slice() {
+                    [] => None,
+                    [(_, ProbeEndpoint::Local { pid })] => Some(*pid),
+                    _ => anyhow::bail!("doctor takes a single local target"),
+                };
+                return cmd.run(pid);
+            }
             _ => {}
         }
 
         // For other commands, we need a target
-        let target = self.target.clone().unwrap_or("0".to_string());
-        let ctrl: ProbeEndpoint = target.as_str().try_into()?;
-        self.execute_command(ctrl).await
+        let mut targets = targets::parse_targets(&self.target)?;
+        if targets.is_empty() {
+            targets.push(("0".to_string(), ProbeEndpoint::Local { pid: 0 }));
+        }
+
+        if targets.len() == 1 && self.summary.is_none() {
+            let (result, _) = targets::run_with_retry(
+                &targets[0].1,
+                self.retries,
+                self.is_idempotent(),
+                |ctrl| self.execute_command(ctrl),
+            )
+            .await;
+            return result;
+        }
+        self.run_targets(&targets).await
     }
 
-    async fn handle_list_command(&self, verbose: bool, tree: bool) -> Result<()> {
-        match ptree::collect_probe_processes().await {
+    fn is_idempotent(&self) -> bool {
+        self.command.as_ref().is_some_and(Commands::is_idempotent)
+    }
+
+    /// Run the command against every target and summarize the outcomes
+    async fn run_targets(&self, targets: &[(String, ProbeEndpoint)]) -> Result<()> {
+        if matches!(
+            self.command,
+            Some(
+                Commands::Repl {
+                    list: false,
+                    close: None,
+                    ..
+                } | Commands::Events { .. }
+                    | Commands::Attach(..)
+            )
+        ) {
+            anyhow::bail!("interactive commands take a single target");
+        }
+        if matches!(&self.command, Some(Commands::Check(cmd)) if cmd.output.is_some()) {
+            anyhow::bail!("check --output takes a single target");
+        }
+        if matches!(&self.command, Some(Commands::Report { .. })) {
+            anyhow::bail!("report takes a single target");
+        }
+        if matches!(&self.command, Some(Commands::Bench(..))) {
+            anyhow::bail!("bench takes a single target");
+        }
+
+        let summary = targets::run_all(
+            targets,
+            self.fail_fast,
+            self.retries,
+            self.is_idempotent(),
+            |ctrl| self.execute_command(ctrl),
+        )
+        .await;
+        summary.print(self.summary.unwrap_or_default());
+
+        match summary.exit_code() {
+            targets::EXIT_OK => Ok(()),
+            code => Err(TargetsFailed {
+                code,
+                failed: summary.outcomes.len() - summary.succeeded(),
+                total: summary.outcomes.len(),
+            }
+            .into()),
+        }
+    }
+
+    async fn handle_list_command(&self, verbose: bool, tree: bool, scan: bool) -> Result<()> {
+        match ptree::collect_probe_processes(scan).await {
             Ok(processes) => {
                 if processes.is_empty() {
                     println!("No processes with injected probes found.");
@@ -118,10 +246,28 @@ impl Cli {
             return Ok(());
         }
         let command = self.command.as_ref().unwrap();
+        // commands loading the probe check it once it answers
+        let loads_probe = match command {
+            #[cfg(target_os = "linux")]
+            Commands::Inject(_) => true,
+            Commands::Attach(_) | Commands::Verify(_) => true,
+            _ => false,
+        };
+        if !loads_probe {
+            ctrl.check_version().await;
+        }
         match command {
             #[cfg(target_os = "linux")]
             Commands::Inject(cmd) => cmd.run(ctrl).await,
-            Commands::Config { options, setting } => {
+            Commands::Attach(cmd) => cmd.run(ctrl).await,
+            Commands::Config {
+                options,
+                setting,
+                action,
+            } => {
+                if let Some(action) = action {
+                    return action.run(ctrl).await;
+                }
                 let options_cfg = options.to_cfg();
 
                 let query_expr = match (setting, options_cfg) {
@@ -159,17 +305,69 @@ impl Cli {
                 .await
             }
             Commands::Backtrace { tid } => ctrl.backtrace(*tid).await,
+            Commands::Pause { status: true, .. } => ctrl.paused().await,
+            Commands::Pause { duration, .. } => ctrl.pause(*duration).await,
+            Commands::Resume => ctrl.resume().await,
             Commands::Rdma { hca_name } => {
                 let hca_name = hca_name.clone().unwrap_or_default();
                 ctrl.rdma(hca_name).await
             }
-            Commands::Eval { code } => ctrl.eval(code.clone()).await,
-            Commands::Query { query } => ctrl::query(ctrl, Query::new(query.clone())).await,
-            Commands::Repl => repl::start_repl(ctrl).await,
+            Commands::Eval { jobs: true, .. } => ctrl.eval_jobs().await,
+            Commands::Eval {
+                cancel: Some(id), ..
+            } => ctrl.cancel_eval_job(*id).await,
+            Commands::Eval {
+                code: Some(code),
+                job: true,
+                session,
+                ..
+            } => ctrl.eval_job(code, session.as_deref()).await,
+            Commands::Eval { code, .. } => ctrl.eval(code.clone().unwrap_or_default()).await,
+            Commands::Query {
+                query,
+                snapshot,
+                page_size,
+                session,
+                columns,
+                sample,
+                max_cell_chars,
+                force,
+            } => {
+                let mut request = Query::new(query.clone());
+                let opts = QueryOptions {
+                    snapshot: *snapshot,
+                    page_size: *page_size,
+                    session: session.clone(),
+                    columns: columns.clone(),
+                    sample_rate: *sample,
+                    max_cell_chars: *max_cell_chars,
+                    force: *force,
+                    ..Default::default()
+                };
+                if opts != QueryOptions::default() {
+                    request.opts = Some(opts);
+                }
+                ctrl::query(ctrl, request).await
+            }
+            Commands::Check(cmd) => cmd.run(ctrl).await,
+            Commands::Bench(cmd) => cmd.run(ctrl).await,
+            Commands::Verify(cmd) => cmd.run(ctrl).await,
+            Commands::Report { out, spans, rows } => ctrl.report(out, *spans, *rows).await,
+            Commands::Events { raw } => ctrl.events(*raw).await,
+            Commands::Repl { list: true, .. } => ctrl.repl_sessions().await,
+            Commands::Repl {
+                close: Some(name), ..
+            } => ctrl.close_repl_session(name).await,
+            Commands::Repl { session, .. } => repl::start_repl(ctrl, session.as_deref()).await,
             // These commands are handled in run() method and don't need a target
+            #[cfg(target_os = "linux")]
+            Commands::Agentd(..) => {
+                unreachable!("These commands should be handled in run() method")
+            }
             Commands::Launch { .. }
             | Commands::List { .. }
             | Commands::Store(..)
+            | Commands::Doctor(..)
             | Commands::External(..) => {
                 unreachable!("These commands should be handled in run() method")
             }
@@ -7,6 +7,8 @@ use std::fs::File;
 #[cfg(target_os = "linux")]
 use std::io::{BufRead, BufReader};
 
+use probing_proto::prelude::Registration;
+
 use crate::cli::ctrl::{self, ProbeEndpoint};
 
 #[derive(Debug, Default, Clone)]
@@ -16,15 +18,25 @@ pub struct ProcessInfo {
     pub cmd: String,
     pub socket_name: Option<String>,
     pub remote_addr: Option<String>,
+    pub token_hint: Option<String>,
     pub children: Vec<ProcessInfo>,
 }
 
-/// Collect information about processes with injected probes
-pub async fn collect_probe_processes() -> Result<Vec<ProcessInfo>> {
+/// Collect information about processes with injected probes, from their
+/// discovery files, or by scanning the probe sockets and asking every probe
+/// for its address with `scan`
+pub async fn collect_probe_processes(scan: bool) -> Result<Vec<ProcessInfo>> {
+    if !scan {
+        return Ok(probing_client::registered()?
+            .into_iter()
+            .map(registered_process)
+            .collect());
+    }
+
     let mut processes = Vec::new();
     let mut tasks = Vec::new();
 
-    for (pid_val, socket_name_val) in find_probe_sockets()? {
+    for (pid_val, socket_name_val) in probing_client::discover()? {
         tasks.push(tokio::spawn(async move {
             let res = get_process_info(pid_val, Some(socket_name_val.clone())).await;
             (pid_val, socket_name_val, res) // Return pid and socket_name along with the result
@@ -55,78 +67,17 @@ pub async fn collect_probe_processes() -> Result<Vec<ProcessInfo>> {
     Ok(processes)
 }
 
-/// Find all probe-related sockets.
-#[cfg(target_os = "linux")]
-fn find_probe_sockets() -> Result<Vec<(i32, String)>, std::io::Error> {
-    let mut result = Vec::new();
-
-    // Read /proc/net/unix for abstract sockets
-    let file = File::open("/proc/net/unix")?;
-    let reader = BufReader::new(file);
-
-    // Skip header
-    let mut lines = reader.lines();
-    let _ = lines.next();
-
-    // Process socket entries
-    for line in lines {
-        let line = line?;
-        let fields: Vec<&str> = line.split_whitespace().collect();
-
-        // Check if we have enough fields and it's an abstract socket
-        if fields.len() >= 8 {
-            let socket_name_full = fields[7]; // e.g., @probing-12345
-
-            // Check for the new naming convention: @probing-<pid>
-            if let Some(pid_str) = socket_name_full.strip_prefix("@probing-") {
-                if let Ok(pid) = pid_str.parse::<i32>() {
-                    result.push((pid, socket_name_full.to_string()));
-                } else {
-                    log::warn!(
-                        "Failed to parse PID from socket name: {}. Expected format @probing-<pid>.",
-                        socket_name_full
-                    );
-                }
-            }
-        }
-    }
-
-    Ok(result)
-}
-
-#[cfg(target_os = "macos")]
-fn find_probe_sockets() -> Result<Vec<(i32, String)>, std::io::Error> {
-    let mut result = Vec::new();
-    let temp_dir = std::env::temp_dir();
-
-    for entry in std::fs::read_dir(temp_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
-            if file_name.starts_with("probing-") && file_name.ends_with(".sock") {
-                // Extract PID from "probing-<pid>.sock"
-                if let Some(pid_str) = file_name
-                    .strip_prefix("probing-")
-                    .and_then(|s| s.strip_suffix(".sock"))
-                {
-                    if let Ok(pid) = pid_str.parse::<i32>() {
-                        result.push((pid, path.to_string_lossy().to_string()));
-                    } else {
-                        log::warn!("Failed to parse PID from socket file: {}", file_name);
-                    }
-                }
-            }
-        }
+fn registered_process(registration: Registration) -> ProcessInfo {
+    let pid = registration.pid;
+    ProcessInfo {
+        pid,
+        ppid: read_parent_pid(pid).unwrap_or(0),
+        cmd: read_process_cmdline(pid).unwrap_or_else(|_| String::from("[cmd error]")),
+        socket_name: Some(registration.socket),
+        remote_addr: registration.address,
+        token_hint: registration.token_hint,
+        children: Vec::new(),
     }
-
-    Ok(result)
-}
-
-#[cfg(not(any(target_os = "linux", target_os = "macos")))]
-fn find_probe_sockets() -> Result<Vec<(i32, String)>, std::io::Error> {
-    log::warn!("find_probe_sockets is not implemented for this OS. Returning empty list.");
-    Ok(Vec::new())
 }
 
 /// Get information about a process
@@ -154,6 +105,7 @@ pub async fn get_process_info(pid: i32, socket_name: Option<String>) -> Result<P
         cmd,
         socket_name,
         remote_addr,
+        token_hint: None,
         children: Vec::new(), // Initialize children
     })
 }
@@ -398,8 +350,12 @@ pub fn format_process(info: &ProcessInfo, verbose: bool) -> String {
     if verbose {
         let local = info.socket_name.as_deref().unwrap_or("-");
         let remote = info.remote_addr.as_deref().unwrap_or("-");
+        let token = match &info.token_hint {
+            Some(hint) => format!(", token: {hint}"),
+            None => String::new(),
+        };
         format!(
-            "{} (local: {local}, remote: {remote}): {}",
+            "{} (local: {local}, remote: {remote}{token}): {}",
             info.pid, info.cmd
         )
     } else {
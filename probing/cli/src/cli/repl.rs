@@ -13,15 +13,28 @@ use tokio_tungstenite::tungstenite::Error as WsError;
 use tokio_tungstenite::{client_async, connect_async};
 use tokio_tungstenite::{tungstenite::Message as WsMessage, WebSocketStream as WsStream};
 
+use probing_proto::prelude::ReplCompression;
+use probing_proto::protocol::repl::PARTIAL_STATUS;
+
 use super::ctrl::ProbeEndpoint;
 
-pub async fn start_repl(ctrl: ProbeEndpoint) -> Result<()> {
+/// REPL 通道地址，请求压缩大帧并流式接收长输出
+const WS_PATH: &str = "/ws?compress=deflate&stream=true";
+
+pub async fn start_repl(ctrl: ProbeEndpoint, session: Option<&str>) -> Result<()> {
+    let path = match session {
+        Some(session) => format!("{WS_PATH}&session={session}"),
+        None => WS_PATH.to_string(),
+    };
     println!("Connecting to REPL server...");
     println!("Type 'exit' or press Ctrl+D to exit");
     println!();
 
     // 连接 WebSocket
-    let mut ws = connect_websocket(&ctrl).await?;
+    let mut ws = connect_websocket(&ctrl, &path).await?;
+    if let Some(session) = session {
+        println!("Attached to session {session}, it is kept after exiting");
+    }
 
     // 创建 Reedline 实例（使用 Arc<Mutex> 以便在异步环境中共享）
     let line_editor = Arc::new(Mutex::new(Reedline::create()));
@@ -66,63 +79,9 @@ pub async fn start_repl(ctrl: ProbeEndpoint) -> Result<()> {
                     break;
                 }
 
-                // 等待并接收服务器响应（这会阻塞直到响应到达）
-                match ws.read.as_mut().next().await {
-                    Some(Ok(WsMessage::Text(response))) => {
-                        // 解析 JSON 响应
-                        match serde_json::from_str::<Value>(&response) {
-                            Ok(json) => {
-                                // 显示输出
-                                if let Some(output) = json.get("output").and_then(|v| v.as_str()) {
-                                    if !output.is_empty() {
-                                        print!("{}", output);
-                                        // 如果输出不以换行结尾，添加换行
-                                        if !output.ends_with('\n') {
-                                            print!("\n");
-                                        }
-                                    }
-                                }
-
-                                // 显示错误堆栈
-                                if let Some(traceback) =
-                                    json.get("traceback").and_then(|v| v.as_array())
-                                {
-                                    if !traceback.is_empty() {
-                                        for line in traceback {
-                                            if let Some(line_str) = line.as_str() {
-                                                eprintln!("{}", line_str);
-                                            }
-                                        }
-                                    }
-                                }
-
-                                // 刷新输出
-                                std::io::stdout().flush().unwrap();
-                                std::io::stderr().flush().unwrap();
-                            }
-                            Err(_) => {
-                                // 如果不是 JSON，直接显示原始响应
-                                print!("{}", response);
-                                if !response.ends_with('\n') {
-                                    print!("\n");
-                                }
-                                std::io::stdout().flush().unwrap();
-                            }
-                        }
-                    }
-                    Some(Ok(WsMessage::Close(_))) => {
-                        println!("\nConnection closed");
-                        break;
-                    }
-                    Some(Err(e)) => {
-                        eprintln!("\nReceive error: {}", e);
-                        break;
-                    }
-                    None => {
-                        println!("\nConnection disconnected");
-                        break;
-                    }
-                    _ => {}
+                // 等待并接收服务器响应（这会阻塞直到最后一帧到达）
+                if !receive_response(&mut ws).await {
+                    break;
                 }
             }
             Ok(Signal::CtrlC) => {
@@ -143,16 +102,98 @@ pub async fn start_repl(ctrl: ProbeEndpoint) -> Result<()> {
     Ok(())
 }
 
-async fn connect_websocket(ctrl: &ProbeEndpoint) -> Result<WsConnection> {
+/// 接收一条命令的所有响应帧，`partial` 帧到达即输出；连接断开时返回 false
+async fn receive_response(ws: &mut WsConnection) -> bool {
+    let mut pending_newline = false;
+    loop {
+        let response = match ws.read.as_mut().next().await {
+            Some(Ok(WsMessage::Text(response))) => response.to_string(),
+            // 二进制帧为压缩后的 JSON
+            Some(Ok(WsMessage::Binary(data))) => {
+                match ReplCompression::Deflate
+                    .decode(&data)
+                    .map(String::from_utf8)
+                {
+                    Ok(Ok(response)) => response,
+                    _ => {
+                        eprintln!("\nFailed to decode compressed response");
+                        return false;
+                    }
+                }
+            }
+            Some(Ok(WsMessage::Close(_))) => {
+                println!("\nConnection closed");
+                return false;
+            }
+            Some(Err(e)) => {
+                eprintln!("\nReceive error: {}", e);
+                return false;
+            }
+            None => {
+                println!("\nConnection disconnected");
+                return false;
+            }
+            _ => continue,
+        };
+
+        // 解析 JSON 响应
+        let json = match serde_json::from_str::<Value>(&response) {
+            Ok(json) => json,
+            Err(_) => {
+                // 如果不是 JSON，直接显示原始响应
+                print!("{}", response);
+                if !response.ends_with('\n') {
+                    println!();
+                }
+                std::io::stdout().flush().unwrap();
+                return true;
+            }
+        };
+
+        // 显示输出
+        if let Some(output) = json.get("output").and_then(|v| v.as_str()) {
+            if !output.is_empty() {
+                print!("{}", output);
+                pending_newline = !output.ends_with('\n');
+            }
+        }
+        std::io::stdout().flush().unwrap();
+
+        // 长输出被拆成多帧，继续接收后续帧
+        if json.get("status").and_then(|v| v.as_str()) == Some(PARTIAL_STATUS) {
+            continue;
+        }
+        // 如果输出不以换行结尾，添加换行
+        if pending_newline {
+            println!();
+        }
+
+        // 显示错误堆栈
+        if let Some(traceback) = json.get("traceback").and_then(|v| v.as_array()) {
+            for line in traceback {
+                if let Some(line_str) = line.as_str() {
+                    eprintln!("{}", line_str);
+                }
+            }
+        }
+
+        // 刷新输出
+        std::io::stdout().flush().unwrap();
+        std::io::stderr().flush().unwrap();
+        return true;
+    }
+}
+
+async fn connect_websocket(ctrl: &ProbeEndpoint, path: &str) -> Result<WsConnection> {
     match ctrl {
-        ProbeEndpoint::Local { pid } => connect_unix_websocket(*pid).await,
-        ProbeEndpoint::Remote { addr } => connect_tcp_websocket(addr).await,
+        ProbeEndpoint::Local { pid } => connect_unix_websocket(*pid, path).await,
+        ProbeEndpoint::Remote { addr } => connect_tcp_websocket(addr, path).await,
         _ => anyhow::bail!("Unsupported endpoint type for REPL"),
     }
 }
 
-async fn connect_tcp_websocket(addr: &str) -> Result<WsConnection> {
-    let url = format!("ws://{}/ws", addr);
+async fn connect_tcp_websocket(addr: &str, path: &str) -> Result<WsConnection> {
+    let url = format!("ws://{}{}", addr, path);
     let (ws_stream, _) = connect_async(&url)
         .await
         .map_err(|e| anyhow::anyhow!("WebSocket connection failed: {}", e))?;
@@ -160,7 +201,7 @@ async fn connect_tcp_websocket(addr: &str) -> Result<WsConnection> {
     Ok(boxed_connection(ws_stream))
 }
 
-async fn connect_unix_websocket(pid: i32) -> Result<WsConnection> {
+async fn connect_unix_websocket(pid: i32, ws_path: &str) -> Result<WsConnection> {
     #[cfg(target_os = "linux")]
     let path = format!("\0probing-{}", pid);
     #[cfg(not(target_os = "linux"))]
@@ -180,7 +221,7 @@ async fn connect_unix_websocket(pid: i32) -> Result<WsConnection> {
         }
     };
 
-    let (ws_stream, _) = client_async("ws://localhost/ws", stream)
+    let (ws_stream, _) = client_async(format!("ws://localhost{}", ws_path), stream)
         .await
         .map_err(|e| anyhow::anyhow!("WebSocket connection failed: {}", e))?;
 
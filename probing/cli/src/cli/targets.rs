@@ -0,0 +1,376 @@
+//! Running one command against several targets.
+//!
+//! Targets are handled one after another. Each target gets a few retries with
+//! exponential backoff when the probe cannot be reached, the outcome of every
+//! target is recorded, and the run ends with a summary and an exit code that
+//! tells a full success from a partial or complete failure.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use super::ctrl::ProbeEndpoint;
+
+/// Every target succeeded
+pub const EXIT_OK: i32 = 0;
+/// No target succeeded
+pub const EXIT_FAILED: i32 = 1;
+/// Some targets failed, or were skipped after a failure with `--fail-fast`
+pub const EXIT_PARTIAL: i32 = 2;
+
+const BACKOFF_BASE: Duration = Duration::from_millis(200);
+const BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SummaryFormat {
+    /// Human readable lines on stderr
+    #[default]
+    Text,
+    /// One JSON object on stdout
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+/// Result of running the command against one target
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub target: String,
+    pub status: Status,
+    pub attempts: u32,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Summary {
+    fn count(&self, status: Status) -> usize {
+        self.outcomes.iter().filter(|o| o.status == status).count()
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.count(Status::Ok)
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        let ok = self.count(Status::Ok);
+        if ok == self.outcomes.len() {
+            EXIT_OK
+        } else if ok == 0 && self.count(Status::Failed) > 0 {
+            EXIT_FAILED
+        } else {
+            EXIT_PARTIAL
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let targets = self
+            .outcomes
+            .iter()
+            .map(|o| {
+                json!({
+                    "target": o.target,
+                    "status": o.status.as_str(),
+                    "attempts": o.attempts,
+                    "elapsed_ms": o.elapsed.as_millis() as u64,
+                    "error": o.error,
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "total": self.outcomes.len(),
+            "succeeded": self.count(Status::Ok),
+            "failed": self.count(Status::Failed),
+            "skipped": self.count(Status::Skipped),
+            "exit_code": self.exit_code(),
+            "targets": targets,
+        })
+    }
+
+    pub fn print(&self, format: SummaryFormat) {
+        match format {
+            SummaryFormat::Json => println!("{}", self.to_json()),
+            SummaryFormat::Text => {
+                eprintln!(
+                    "summary: {} succeeded, {} failed, {} skipped",
+                    self.count(Status::Ok),
+                    self.count(Status::Failed),
+                    self.count(Status::Skipped)
+                );
+                for o in &self.outcomes {
+                    match &o.error {
+                        Some(err) => eprintln!("  {:<8} {}: {err}", o.status.as_str(), o.target),
+                        None => eprintln!("  {:<8} {}", o.status.as_str(), o.target),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Error returned when a multi-target run did not fully succeed; the binary
+/// exits with [`Self::code`]
+#[derive(Debug)]
+pub struct TargetsFailed {
+    pub code: i32,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl std::fmt::Display for TargetsFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} targets did not succeed",
+            self.failed, self.total
+        )
+    }
+}
+
+impl std::error::Error for TargetsFailed {}
+
+/// Split `-t` values into endpoints, accepting repeated flags and
+/// comma-separated lists
+pub fn parse_targets(specs: &[String]) -> Result<Vec<(String, ProbeEndpoint)>> {
+    let mut targets = vec![];
+    for spec in specs.iter().flat_map(|s| s.split(',')) {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            continue;
+        }
+        let endpoint = ProbeEndpoint::try_from(spec)
+            .map_err(|e| anyhow::anyhow!("invalid target `{spec}`: {e}"))?;
+        targets.push((spec.to_string(), endpoint));
+    }
+    Ok(targets)
+}
+
+/// Whether the error means the probe could not be reached, so that trying
+/// again later may succeed
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                err.kind(),
+                ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::NotConnected
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+            );
+        }
+        if let Some(err) = cause.downcast_ref::<hyper::Error>() {
+            return err.is_closed() || err.is_incomplete_message() || err.is_timeout();
+        }
+        false
+    })
+}
+
+/// Whether the error means the request never reached the probe, so that even
+/// a command changing the target may be sent again
+pub fn is_refused(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::ConnectionRefused)
+    })
+}
+
+/// Delay before retry number `attempt`, starting at 1
+pub fn backoff(attempt: u32) -> Duration {
+    BACKOFF_BASE
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(BACKOFF_MAX)
+}
+
+/// Run `f` against one target, retrying transient errors up to `retries` times
+///
+/// Unless the command is `idempotent`, only requests refused before reaching
+/// the probe are retried: a reply lost on the way back may follow a change the
+/// probe has already made.
+pub async fn run_with_retry<F, Fut>(
+    endpoint: &ProbeEndpoint,
+    retries: u32,
+    idempotent: bool,
+    mut f: F,
+) -> (Result<()>, u32)
+where
+    F: FnMut(ProbeEndpoint) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let retryable: fn(&anyhow::Error) -> bool = if idempotent { is_transient } else { is_refused };
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match f(endpoint.clone()).await {
+            Err(err) if attempt <= retries && retryable(&err) => {
+                let delay = backoff(attempt);
+                log::warn!("{err}, retrying in {delay:?} ({attempt}/{retries})");
+                tokio::time::sleep(delay).await;
+            }
+            result => return (result, attempt),
+        }
+    }
+}
+
+/// Run `f` against every target in order and record the outcomes
+pub async fn run_all<F, Fut>(
+    targets: &[(String, ProbeEndpoint)],
+    fail_fast: bool,
+    retries: u32,
+    idempotent: bool,
+    mut f: F,
+) -> Summary
+where
+    F: FnMut(ProbeEndpoint) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut summary = Summary::default();
+    let mut failed = false;
+    for (name, endpoint) in targets {
+        if failed && fail_fast {
+            summary.outcomes.push(Outcome {
+                target: name.clone(),
+                status: Status::Skipped,
+                attempts: 0,
+                elapsed: Duration::ZERO,
+                error: None,
+            });
+            continue;
+        }
+
+        println!("==> {name} <==");
+        let start = Instant::now();
+        let (result, attempts) = run_with_retry(endpoint, retries, idempotent, &mut f).await;
+        let error = result.err().map(|e| format!("{e:#}"));
+        if let Some(err) = &error {
+            eprintln!("{name}: {err}");
+            failed = true;
+        }
+        summary.outcomes.push(Outcome {
+            target: name.clone(),
+            status: if error.is_some() {
+                Status::Failed
+            } else {
+                Status::Ok
+            },
+            attempts,
+            elapsed: start.elapsed(),
+            error,
+        });
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refused() -> anyhow::Error {
+        std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into()
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        let targets = parse_targets(&["1,2".into(), " 10.0.0.1:9700 ".into()]).unwrap();
+        let names = targets.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["1", "2", "10.0.0.1:9700"]);
+        assert!(parse_targets(&["1,abc".into()]).is_err());
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(is_transient(&refused().context("connecting")));
+        assert!(!is_transient(&anyhow::anyhow!("error: bad query")));
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert!(is_transient(&reset.into()));
+        let reset = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+        assert!(!is_refused(&reset.into()));
+        assert!(is_refused(&refused()));
+        assert_eq!(backoff(1), BACKOFF_BASE);
+        assert_eq!(backoff(2), BACKOFF_BASE * 2);
+        assert_eq!(backoff(30), BACKOFF_MAX);
+    }
+
+    #[tokio::test]
+    async fn test_retry_non_idempotent() {
+        let endpoint = ProbeEndpoint::Local { pid: 1 };
+        let reset =
+            || anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+
+        // the probe may have applied the change before the connection dropped
+        let (result, attempts) =
+            run_with_retry(&endpoint, 2, false, |_| async { Err(reset()) }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        let (_, attempts) = run_with_retry(&endpoint, 2, false, |_| async { Err(refused()) }).await;
+        assert_eq!(attempts, 3);
+        let (_, attempts) = run_with_retry(&endpoint, 2, true, |_| async { Err(reset()) }).await;
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_all() {
+        let targets = parse_targets(&["1,2,3".into()]).unwrap();
+        let mut calls = vec![];
+        let summary = run_all(&targets, false, 2, true, |endpoint| {
+            let target = String::from(endpoint);
+            calls.push(target.clone());
+            async move {
+                match target.as_str() {
+                    "1" => Ok(()),
+                    "2" => Err(refused()),
+                    _ => Err(anyhow::anyhow!("error: bad query")),
+                }
+            }
+        })
+        .await;
+
+        // the refused target is retried twice, the other failure is not
+        assert_eq!(calls, ["1", "2", "2", "2", "3"]);
+        let attempts = summary
+            .outcomes
+            .iter()
+            .map(|o| o.attempts)
+            .collect::<Vec<_>>();
+        assert_eq!(attempts, [1, 3, 1]);
+        assert_eq!(summary.exit_code(), EXIT_PARTIAL);
+        assert_eq!(summary.to_json()["failed"], 2);
+
+        let summary = run_all(&targets, true, 0, true, |_| async { Err(refused()) }).await;
+        let status = summary
+            .outcomes
+            .iter()
+            .map(|o| o.status)
+            .collect::<Vec<_>>();
+        assert_eq!(status, [Status::Failed, Status::Skipped, Status::Skipped]);
+        assert_eq!(summary.exit_code(), EXIT_FAILED);
+    }
+}
@@ -0,0 +1,173 @@
+//! `probing verify`, check that the probe of a process answers.
+//!
+//! A failed injection is silent: the library may not load, or load without
+//! starting its server, and the mistake only shows up as a connection error
+//! of a later command. Verifying polls the probe socket until the probe
+//! answers its API and runs queries, then reports its version, build and the
+//! optional features it was built with. `inject` runs the same check after
+//! loading the library.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use clap::Args;
+use probing_proto::prelude::{BuildInfo, Capabilities, Process, ProtocolVersion};
+
+use super::attach::wait_ready;
+use super::ctrl::ProbeEndpoint;
+
+/// Seconds to wait for a probe to answer by default
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Check that the probe of a process answers
+///
+/// ```bash
+/// $ probing verify 1234
+/// $ probing -t node1:9700 verify
+/// ```
+#[derive(Args, Debug)]
+pub struct VerifyCommand {
+    /// Local process to verify, instead of the target
+    pub pid: Option<i32>,
+
+    /// Seconds to wait for the probe to answer
+    #[arg(long, default_value_t = DEFAULT_TIMEOUT_SECS)]
+    timeout: u64,
+}
+
+impl VerifyCommand {
+    pub async fn run(&self, ctrl: ProbeEndpoint) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        if let ProbeEndpoint::Ptrace { pid } | ProbeEndpoint::Local { pid } = ctrl {
+            let inject = super::inject::InjectCommand::default();
+            if !inject.check_library(pid, "libprobing.so")? {
+                anyhow::bail!(
+                    "libprobing.so is not loaded in {pid}, inject it with `probing -t {pid} inject`"
+                );
+            }
+        }
+        verify(&ctrl, Duration::from_secs(self.timeout)).await
+    }
+}
+
+/// What a probe reported once it answered
+#[derive(Debug)]
+struct Verified {
+    elapsed: Duration,
+    capabilities: Capabilities,
+    build: Option<BuildInfo>,
+    process: Process,
+}
+
+/// Wait up to `timeout` for the probe to answer and print what it reports
+pub async fn verify(ctrl: &ProbeEndpoint, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+    println!(
+        "waiting for the probe of {} to answer...",
+        String::from(ctrl.clone())
+    );
+    wait_ready(ctrl, timeout).await?;
+    let process = ctrl.health().await?;
+    let client = ctrl.client()?;
+    let capabilities = client.capabilities().await?;
+    let build = client.version().await?;
+    let verified = Verified {
+        elapsed: start.elapsed(),
+        capabilities,
+        build,
+        process,
+    };
+    println!("{}", format_verified(&verified));
+    Ok(())
+}
+
+fn format_verified(verified: &Verified) -> String {
+    let Verified {
+        elapsed,
+        capabilities,
+        build,
+        process,
+    } = verified;
+    let version = match capabilities.version.as_str() {
+        "" => "unknown, older than the capabilities API".to_string(),
+        version if version != env!("CARGO_PKG_VERSION") => format!(
+            "{version} (this CLI is {}, upgrade one of them if commands fail)",
+            env!("CARGO_PKG_VERSION")
+        ),
+        version => version.to_string(),
+    };
+    let features = if capabilities.features.is_empty() {
+        "none, minimal build".to_string()
+    } else {
+        capabilities.features.join(", ")
+    };
+    let mut report = format!(
+        "probe ready after {:.1}s\n  pid:      {}\n  command:  {}\n  version:  {version}",
+        elapsed.as_secs_f64(),
+        process.pid,
+        process.cmd,
+    );
+    if let Some(build) = build {
+        let commit = match build.git_hash.as_str() {
+            "" => "unknown",
+            hash => hash,
+        };
+        report.push_str(&format!(
+            "\n  commit:   {commit}\n  protocol: {}\n  python:   {}",
+            build.protocol, build.python_abi
+        ));
+        if let Some(reason) = build.incompatibility(&ProtocolVersion::current()) {
+            report.push_str(&format!(" ({reason})"));
+        }
+    }
+    report.push_str(&format!("\n  features: {features}"));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_verified() {
+        let mut verified = Verified {
+            elapsed: Duration::from_millis(1300),
+            capabilities: Capabilities {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                features: vec!["analytics".to_string(), "wasm".to_string()],
+            },
+            build: None,
+            process: Process {
+                pid: 1234,
+                cmd: "python train.py".to_string(),
+                ..Default::default()
+            },
+        };
+        let report = format_verified(&verified);
+        assert!(report.starts_with("probe ready after 1.3s"));
+        assert!(report.contains("pid:      1234"));
+        assert!(report.contains(&format!("version:  {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(report.ends_with("features: analytics, wasm"));
+
+        verified.capabilities = Capabilities {
+            version: "0.0.1".to_string(),
+            features: vec![],
+        };
+        let report = format_verified(&verified);
+        assert!(report.contains("version:  0.0.1 (this CLI is"));
+        assert!(report.ends_with("features: none, minimal build"));
+
+        verified.build = Some(BuildInfo {
+            git_hash: "0123456789ab".to_string(),
+            protocol: ProtocolVersion {
+                major: ProtocolVersion::current().major + 1,
+                ..ProtocolVersion::current()
+            },
+            python_abi: "cp312".to_string(),
+            ..Default::default()
+        });
+        let report = format_verified(&verified);
+        assert!(report.contains("commit:   0123456789ab\n"));
+        assert!(report.contains("python:   cp312 (probe "));
+    }
+}
@@ -1,8 +1,16 @@
 use anyhow::Result;
+use probing_cli::cli::targets::TargetsFailed;
 use probing_cli::cli_main;
 
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
     // cli_main already uses #[tokio::main], so it handles async execution internally
-    cli_main(args)
+    match cli_main(args) {
+        // the per-target summary has been printed already
+        Err(err) => match err.downcast_ref::<TargetsFailed>() {
+            Some(failed) => std::process::exit(failed.code),
+            None => Err(err),
+        },
+        ok => ok,
+    }
 }
@@ -0,0 +1,21 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use probing_core::{probe_event, probe_span};
+
+fn criterion_benchmark(c: &mut Criterion) {
+    c.bench_function("probe_span", |b| {
+        b.iter(|| {
+            let span = probe_span!("bench.span", rows = 1i64);
+            black_box(span);
+        })
+    });
+    c.bench_function("probe_event", |b| {
+        b.iter(|| probe_event!("bench.event", hit = true))
+    });
+    probing_core::trace::flush();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
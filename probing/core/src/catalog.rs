@@ -0,0 +1,207 @@
+//! Changes of the tables that can be queried.
+//!
+//! Tables come and go while the process runs: Python code creates external
+//! tables, extensions enable plugins and recorders register their table on
+//! first use. Each such change is published on the [event bus](crate::events)
+//! as an [`EventKind::CatalogChanged`] event and kept in `catalog.changes`,
+//! so that a UI refreshes its table list when told to instead of polling
+//! `SHOW TABLES`. `seq` numbers the changes, a client that remembers the last
+//! one it saw reads what it missed with
+//!
+//! ```sql
+//! SELECT * FROM catalog.changes WHERE seq > 41
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use probing_proto::prelude::{AgentEvent, EventKind};
+
+use crate::core::{time, CustomTable, TablePluginHelper};
+
+/// Changes kept for `catalog.changes`
+pub const MAX_CHANGES: usize = 1024;
+
+/// What happened to a table or a namespace
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Registered,
+    Unregistered,
+}
+
+impl Change {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Change::Registered => "registered",
+            Change::Unregistered => "unregistered",
+        }
+    }
+}
+
+/// One row of `catalog.changes`
+#[derive(Clone, Debug)]
+pub struct CatalogChange {
+    /// Number of the change, starting at 1
+    pub seq: i64,
+    /// Microseconds since the unix epoch
+    pub time: i64,
+    pub change: Change,
+    /// `table` or `namespace`
+    pub kind: &'static str,
+    /// `namespace.table` of a table, name of a namespace
+    pub name: String,
+    /// Component that made the change, e.g. `python` or `engine`
+    pub source: String,
+}
+
+static SEQ: AtomicI64 = AtomicI64::new(0);
+
+static CHANGES: LazyLock<Mutex<VecDeque<CatalogChange>>> = LazyLock::new(Default::default);
+
+/// A table `namespace.table` can now be queried
+pub fn table_registered(source: &str, table: &str) {
+    record(Change::Registered, "table", source, table);
+}
+
+/// A table `namespace.table` is gone
+pub fn table_unregistered(source: &str, table: &str) {
+    record(Change::Unregistered, "table", source, table);
+}
+
+/// The tables of `namespace` can now be queried
+pub fn namespace_registered(source: &str, namespace: &str) {
+    record(Change::Registered, "namespace", source, namespace);
+}
+
+fn record(change: Change, kind: &'static str, source: &str, name: &str) {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as i64;
+    let seq = {
+        let mut changes = CHANGES.lock().unwrap();
+        // numbered under the lock, so that the rows are in order
+        let seq = SEQ.fetch_add(1, Ordering::Relaxed) + 1;
+        if changes.len() >= MAX_CHANGES {
+            changes.pop_front();
+        }
+        changes.push_back(CatalogChange {
+            seq,
+            time,
+            change,
+            kind,
+            name: name.to_string(),
+            source: source.to_string(),
+        });
+        seq
+    };
+    crate::events::publish(
+        AgentEvent::new(
+            EventKind::CatalogChanged,
+            source,
+            format!("{kind} {name} {}", change.as_str()),
+        )
+        .with_detail("change", change.as_str())
+        .with_detail("kind", kind)
+        .with_detail("name", name)
+        .with_detail("seq", seq.to_string()),
+    );
+}
+
+/// The changes kept, oldest first
+pub fn changes() -> Vec<CatalogChange> {
+    CHANGES.lock().unwrap().iter().cloned().collect()
+}
+
+/// `catalog.changes`, the last [`MAX_CHANGES`] changes
+#[derive(Default, Debug)]
+pub struct ChangesTable {}
+
+impl CustomTable for ChangesTable {
+    fn name() -> &'static str {
+        "changes"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("seq", DataType::Int64, false),
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("change", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("source", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let changes = changes();
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(changes.iter().map(|c| c.seq))),
+                Arc::new(time::timestamp_us_array(changes.iter().map(|c| c.time))),
+                Arc::new(StringArray::from_iter_values(
+                    changes.iter().map(|c| c.change.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    changes.iter().map(|c| c.kind),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    changes.iter().map(|c| c.name.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    changes.iter().map(|c| c.source.as_str()),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build catalog changes batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type ChangesPlugin = TablePluginHelper<ChangesTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_changes_are_published() {
+        let mut rx = crate::events::subscribe();
+        table_registered("catalog_test", "python.loss");
+        table_unregistered("catalog_test", "python.loss");
+
+        let event = loop {
+            let event = rx.recv().await.unwrap();
+            if event.source == "catalog_test" {
+                break event;
+            }
+        };
+        assert_eq!(event.kind, EventKind::CatalogChanged);
+        assert!(event
+            .details
+            .contains(&("name".to_string(), "python.loss".to_string())));
+
+        let changes: Vec<_> = changes()
+            .into_iter()
+            .filter(|c| c.source == "catalog_test")
+            .collect();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].change, Change::Registered);
+        assert_eq!(changes[1].change, Change::Unregistered);
+        assert!(changes[0].seq < changes[1].seq);
+
+        let batch = &ChangesTable::data()[0];
+        assert_eq!(batch.num_columns(), 6);
+        assert!(batch.num_rows() >= 2);
+    }
+}
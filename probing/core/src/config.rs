@@ -1,10 +1,11 @@
 use std::collections::BTreeMap;
 
 use once_cell::sync::Lazy;
-use probing_proto::prelude::{Ele, EleExt};
+use probing_proto::prelude::{ConfigDump, Ele, EleExt};
 use tokio::sync::RwLock;
 
 use crate::core::{EngineError, EngineExtensionManager};
+use crate::events;
 use crate::ENGINE;
 
 /// Global configuration key-value store.
@@ -59,6 +60,67 @@ pub async fn is_empty() -> bool {
     CONFIG_STORE.read().await.is_empty()
 }
 
+/// Value served in place of a secret option.
+pub const MASKED: &str = "******";
+
+/// Parts of the keys whose values are never served: tokens, passwords and
+/// the remote tables, whose urls may carry credentials.
+const SECRET_KEYS: &[&str] = &["token", "password", "secret", "federation.tables"];
+
+/// Whether the value of `key` is a secret, regardless of `privacy.redact`.
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEYS.iter().any(|secret| key.contains(secret))
+}
+
+/// Replace the values of the secret keys of `dump` with [`MASKED`].
+pub fn mask(dump: &mut ConfigDump) {
+    for (key, value) in dump.options.iter_mut().chain(dump.store.iter_mut()) {
+        if is_secret_key(key) {
+            *value = MASKED.to_string();
+        }
+    }
+}
+
+/// Sorted snapshot of all extension options and configuration entries, with
+/// the secrets masked.
+pub async fn dump() -> ConfigDump {
+    let mut dump = snapshot().await;
+    mask(&mut dump);
+    dump
+}
+
+/// Sorted snapshot of all extension options and configuration entries.
+pub(crate) async fn snapshot() -> ConfigDump {
+    let eem = {
+        let engine = ENGINE.read().await;
+        let state = engine.context.state();
+        state
+            .config()
+            .options()
+            .extensions
+            .get::<EngineExtensionManager>()
+            .cloned()
+    };
+
+    let mut dump = ConfigDump::default();
+    if let Some(eem) = eem {
+        for option in eem.options().await {
+            if let Some(value) = option.value {
+                dump.options
+                    .insert(format!("probing.{}", option.key), value);
+            }
+        }
+    }
+    dump.store = CONFIG_STORE
+        .read()
+        .await
+        .iter()
+        .map(|(key, value)| (key.clone(), value.to_string_lossy()))
+        .collect();
+    dump
+}
+
 /// Set a configuration option through the engine extension system.
 ///
 /// If the key starts with "probing", it will attempt to update the engine's
@@ -109,6 +171,7 @@ pub async fn write(key: &str, value: &str) -> Result<(), EngineError> {
 
     // For non-"probing" keys or unsupported "probing" keys, write to the store.
     set(key, value).await;
+    events::config_changed("config", key, value, None);
     Ok(())
 }
 
@@ -174,6 +237,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_mask_secrets() {
+        let mut dump = ConfigDump::default();
+        for key in [
+            "probing.server.auth_token",
+            "probing.federation.tables",
+            "db.Password",
+            "probing.torch.profiling",
+        ] {
+            dump.options.insert(key.to_string(), "value".to_string());
+        }
+        dump.store
+            .insert("PROBING_AUTH_TOKEN".to_string(), "value".to_string());
+        mask(&mut dump);
+
+        assert_eq!(dump.options["probing.server.auth_token"], MASKED);
+        assert_eq!(dump.options["probing.federation.tables"], MASKED);
+        assert_eq!(dump.options["db.Password"], MASKED);
+        assert_eq!(dump.options["probing.torch.profiling"], "value");
+        assert_eq!(dump.store["PROBING_AUTH_TOKEN"], MASKED);
+    }
+
     #[tokio::test]
     async fn test_config_set_syncs_to_config_store() {
         setup_test().await;
@@ -246,6 +331,27 @@ mod tests {
         teardown_test().await;
     }
 
+    #[tokio::test]
+    async fn test_dump_is_sorted_and_includes_extension_options() {
+        let builder = create_engine().with_extension(TestExtension::default(), "test", None);
+        initialize_engine(builder)
+            .await
+            .expect("Failed to initialize engine");
+
+        set("dump.b", 2i64).await;
+        set("dump.a", "x").await;
+        let dump = dump().await;
+
+        assert!(dump.options.contains_key("probing.option"));
+        let keys: Vec<_> = dump
+            .store
+            .keys()
+            .filter(|k| k.starts_with("dump."))
+            .collect();
+        assert_eq!(keys, vec!["dump.a", "dump.b"]);
+        assert_eq!(dump.store["dump.b"], "2");
+    }
+
     #[tokio::test]
     async fn test_config_set_engine_not_initialized() {
         setup_test().await;
@@ -3,9 +3,13 @@
 //! This module provides unified conversion functions from Arrow arrays to Seq,
 //! replacing hardcoded type conversion logic throughout the codebase.
 
+use std::sync::Arc;
+
 use arrow::array::ArrayRef;
 use arrow::array::*;
-use probing_proto::prelude::Seq;
+use arrow::datatypes::{Field, Schema};
+use arrow::error::ArrowError;
+use probing_proto::prelude::{DataFrame, Seq};
 
 /// Convert Arrow ArrayRef to Seq
 ///
@@ -41,3 +45,63 @@ pub fn arrow_array_to_seq(array: &ArrayRef) -> Seq {
         Seq::Nil
     }
 }
+
+/// Convert a Seq to an Arrow array, the inverse of [`arrow_array_to_seq`]
+///
+/// `SeqDateTime` values, microseconds since the epoch, become microsecond
+/// timestamps; `Nil` becomes a column of `len` nulls.
+pub fn seq_to_arrow_array(seq: &Seq, len: usize) -> ArrayRef {
+    match seq {
+        Seq::SeqBOOL(vec) => Arc::new(BooleanArray::from(vec.clone())),
+        Seq::SeqI32(vec) => Arc::new(Int32Array::from(vec.clone())),
+        Seq::SeqI64(vec) => Arc::new(Int64Array::from(vec.clone())),
+        Seq::SeqF32(vec) => Arc::new(Float32Array::from(vec.clone())),
+        Seq::SeqF64(vec) => Arc::new(Float64Array::from(vec.clone())),
+        Seq::SeqText(vec) => Arc::new(StringArray::from_iter_values(vec)),
+        Seq::SeqDateTime(vec) => Arc::new(TimestampMicrosecondArray::from_iter_values(
+            vec.iter().map(|x| *x as i64),
+        )),
+        Seq::Nil => Arc::new(NullArray::new(len)),
+    }
+}
+
+/// Convert a DataFrame, e.g. the reply of another probe, to a RecordBatch
+pub fn dataframe_to_record_batch(df: &DataFrame) -> Result<RecordBatch, ArrowError> {
+    let len = df.cols.iter().map(Seq::len).max().unwrap_or(0);
+    let columns = df
+        .cols
+        .iter()
+        .map(|col| seq_to_arrow_array(col, len))
+        .collect::<Vec<_>>();
+    let fields = df
+        .names
+        .iter()
+        .zip(&columns)
+        .map(|(name, col)| Field::new(name, col.data_type().clone(), true))
+        .collect::<Vec<_>>();
+    let schema = Arc::new(Schema::new(fields));
+    if columns.is_empty() {
+        return Ok(RecordBatch::new_empty(schema));
+    }
+    RecordBatch::try_new(schema, columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataframe_round_trip() {
+        let df = DataFrame::new(
+            vec!["pid".to_string(), "name".to_string()],
+            vec![
+                Seq::SeqI64(vec![1, 2]),
+                Seq::SeqText(vec!["a".to_string(), "b".to_string()]),
+            ],
+        );
+        let batch = dataframe_to_record_batch(&df).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema().field(1).name(), "name");
+        assert_eq!(arrow_array_to_seq(batch.column(0)), df.cols[0]);
+    }
+}
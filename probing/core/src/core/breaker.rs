@@ -0,0 +1,321 @@
+//! Circuit breakers of the extensions.
+//!
+//! Extensions run inside the traced process, a panic in one of them must not
+//! take the agent, or the training job, down with it. Every `call`, `set` and
+//! `get` of an extension goes through [`run`] or [`run_async`], which catch
+//! panics and turn them into errors. An extension that panics or fails with an
+//! internal error [`max_failures`] times in a row is disabled: its calls fail
+//! right away with [`EngineError::ExtensionDisabled`] until it is reset with
+//!
+//! ```sql
+//! SET probing.extensions.reset = 'torch';
+//! ```
+//!
+//! The state of the breakers is served as `extensions.status`.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use futures::FutureExt;
+
+use super::error::EngineError;
+use super::{CustomTable, TablePluginHelper};
+use crate::journal::{self, EntryKind};
+
+/// Consecutive failures after which an extension is disabled
+pub const DEFAULT_MAX_FAILURES: u32 = 5;
+
+/// Environment variable overriding [`DEFAULT_MAX_FAILURES`], 0 never disables
+pub const MAX_FAILURES_ENV: &str = "PROBING_EXTENSION_MAX_FAILURES";
+
+/// Option re-enabling the extension named by its value
+pub const RESET_KEY: &str = "extensions.reset";
+
+/// Calls and failures of an extension
+#[derive(Clone, Debug, Default)]
+pub struct Health {
+    pub calls: u64,
+    pub failures: u64,
+    pub panics: u64,
+    /// Failures since the last successful call
+    pub consecutive: u32,
+    pub disabled: bool,
+    pub last_error: Option<String>,
+    /// Microseconds since the unix epoch
+    pub last_failure: Option<i64>,
+}
+
+impl Health {
+    /// `ok`, `failing` or `disabled`
+    pub fn state(&self) -> &'static str {
+        if self.disabled {
+            "disabled"
+        } else if self.consecutive > 0 {
+            "failing"
+        } else {
+            "ok"
+        }
+    }
+}
+
+static HEALTH: LazyLock<Mutex<BTreeMap<String, Health>>> = LazyLock::new(Default::default);
+
+/// Consecutive failures after which an extension is disabled, 0 for never
+pub fn max_failures() -> u32 {
+    static MAX: LazyLock<u32> = LazyLock::new(|| {
+        std::env::var(MAX_FAILURES_ENV)
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_FAILURES)
+    });
+    *MAX
+}
+
+/// Start tracking the extension `name`, so that it is listed before its
+/// first call
+pub fn track(name: &str) {
+    HEALTH
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(name.to_string())
+        .or_default();
+}
+
+/// Fail if the extension `name` is disabled
+pub fn check(name: &str) -> Result<(), EngineError> {
+    let health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+    match health.get(name) {
+        Some(h) if h.disabled => Err(EngineError::ExtensionDisabled(name.to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Run `f` on behalf of the extension `name`, see the [module docs](self)
+pub fn run<T>(name: &str, f: impl FnOnce() -> Result<T, EngineError>) -> Result<T, EngineError> {
+    check(name)?;
+    settle(name, std::panic::catch_unwind(AssertUnwindSafe(f)))
+}
+
+/// [`run`] for the futures of async extension calls
+pub async fn run_async<T>(
+    name: &str,
+    f: impl Future<Output = Result<T, EngineError>>,
+) -> Result<T, EngineError> {
+    check(name)?;
+    settle(name, AssertUnwindSafe(f).catch_unwind().await)
+}
+
+/// Re-enable an extension given by its name, e.g. `torchextension`, or its
+/// namespace, e.g. `torch`. Returns false if no such extension is tracked.
+pub fn reset(name: &str) -> bool {
+    let name = name.trim().to_lowercase();
+    let mut health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+    let key = if health.contains_key(&name) {
+        name
+    } else {
+        format!("{name}extension")
+    };
+    match health.get_mut(&key) {
+        Some(h) => {
+            h.disabled = false;
+            h.consecutive = 0;
+            log::info!("extension {key} re-enabled");
+            true
+        }
+        None => false,
+    }
+}
+
+/// Health of the tracked extensions, by name
+pub fn health() -> Vec<(String, Health)> {
+    let health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+    health.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+}
+
+/// Errors telling that the extension itself is broken, as opposed to
+/// unsupported or invalid requests
+///
+/// Extensions also report mistakes of the caller, such as a missing
+/// parameter or an unknown session, as `PluginError`; only internal errors
+/// count, besides panics.
+fn is_failure(err: &EngineError) -> bool {
+    matches!(err, EngineError::InternalError(_))
+}
+
+fn settle<T>(
+    name: &str,
+    result: std::thread::Result<Result<T, EngineError>>,
+) -> Result<T, EngineError> {
+    match result {
+        Ok(Err(err)) if is_failure(&err) => {
+            record(name, Some(err.to_string()), false);
+            Err(err)
+        }
+        Ok(result) => {
+            record(name, None, false);
+            result
+        }
+        Err(payload) => {
+            let message = format!("panicked: {}", panic_message(payload.as_ref()));
+            record(name, Some(message.clone()), true);
+            Err(EngineError::PluginError(format!("{name} {message}")))
+        }
+    }
+}
+
+fn record(name: &str, error: Option<String>, panicked: bool) {
+    let mut health = HEALTH.lock().unwrap_or_else(|e| e.into_inner());
+    let h = health.entry(name.to_string()).or_default();
+    h.calls += 1;
+    let Some(error) = error else {
+        h.consecutive = 0;
+        return;
+    };
+    h.failures += 1;
+    h.panics += panicked as u64;
+    h.consecutive += 1;
+    h.last_error = Some(error);
+    h.last_failure = Some(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as i64,
+    );
+    let max = max_failures();
+    if max > 0 && h.consecutive >= max && !h.disabled {
+        h.disabled = true;
+        log::error!("extension {name} disabled after {max} consecutive failures");
+        journal::record(
+            EntryKind::Extension,
+            name,
+            format!("disabled after {max} consecutive failures, reset with {RESET_KEY}"),
+        );
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+/// `extensions.status`, the [`Health`] of every extension
+#[derive(Default, Debug)]
+pub struct StatusTable {}
+
+impl CustomTable for StatusTable {
+    fn name() -> &'static str {
+        "status"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("state", DataType::Utf8, false),
+            Field::new("calls", DataType::Int64, false),
+            Field::new("failures", DataType::Int64, false),
+            Field::new("panics", DataType::Int64, false),
+            Field::new("consecutive_failures", DataType::Int64, false),
+            Field::new("last_error", DataType::Utf8, true),
+            Field::new("last_failure", DataType::Int64, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let health = health();
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    health.iter().map(|(name, _)| name),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    health.iter().map(|(_, h)| h.state()),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    health.iter().map(|(_, h)| h.calls as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    health.iter().map(|(_, h)| h.failures as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    health.iter().map(|(_, h)| h.panics as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    health.iter().map(|(_, h)| h.consecutive as i64),
+                )),
+                Arc::new(StringArray::from_iter(
+                    health.iter().map(|(_, h)| h.last_error.as_deref()),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    health.iter().map(|(_, h)| h.last_failure),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build extension status batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type StatusPlugin = TablePluginHelper<StatusTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panics_are_caught() {
+        let result: Result<(), _> = run("breakertestpanicextension", || panic!("boom"));
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("panicked: boom"), "{err}");
+
+        let (_, h) = health()
+            .into_iter()
+            .find(|(name, _)| name == "breakertestpanicextension")
+            .unwrap();
+        assert_eq!((h.calls, h.failures, h.panics), (1, 1, 1));
+        assert_eq!(h.state(), "failing");
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_and_resets() {
+        let name = "breakertestextension";
+        for _ in 0..DEFAULT_MAX_FAILURES {
+            let result: Result<(), _> = run_async(name, async {
+                Err(EngineError::InternalError("down".into()))
+            })
+            .await;
+            assert!(matches!(result, Err(EngineError::InternalError(_))));
+        }
+        // not run anymore
+        let result = run(name, || -> Result<(), EngineError> { unreachable!() });
+        assert!(matches!(result, Err(EngineError::ExtensionDisabled(_))));
+
+        // invalid requests do not count
+        assert!(reset("breakertest"));
+        let result: Result<(), _> = run(name, || Err(EngineError::UnsupportedCall));
+        assert!(matches!(result, Err(EngineError::UnsupportedCall)));
+        for _ in 0..DEFAULT_MAX_FAILURES {
+            let result: Result<(), _> =
+                run(name, || Err(EngineError::PluginError("no session".into())));
+            assert!(matches!(result, Err(EngineError::PluginError(_))));
+        }
+        let (_, h) = health().into_iter().find(|(n, _)| n == name).unwrap();
+        assert_eq!(h.state(), "ok");
+        assert_eq!(h.failures, DEFAULT_MAX_FAILURES as u64);
+        assert!(!reset("nosuchextension"));
+    }
+}
@@ -0,0 +1,263 @@
+//! Clock synchronization between cluster nodes.
+//!
+//! Every worker periodically probes the master clock while reporting its
+//! status and estimates its own offset the NTP way: with `t0` the local send
+//! time, `t1` the master time and `t3` the local receive time, the offset is
+//! `t1 - (t0 + t3) / 2` and the uncertainty is bounded by the round trip
+//! `t3 - t0`. The offset is reported to the master with the node status so
+//! that merged cross-rank timelines can be shifted onto the master clock.
+//!
+//! All timestamps are in microseconds since the unix epoch. The SQL function
+//! `clock_adjust` takes timestamps of any unit and returns them in
+//! nanoseconds, so that tables recorded in different units merge on one
+//! timeline.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use arrow::array::{Array, Int64Array, TimestampNanosecondArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, TimeUnit};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDF, Signature, SimpleScalarUDF, Volatility};
+
+use super::cluster::CLUSTER;
+use super::time;
+
+/// Number of recent samples kept to pick the best estimate from.
+const MAX_SAMPLES: usize = 8;
+
+/// One clock probe against the master.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    /// Estimated `master - local` offset in microseconds
+    pub offset: i64,
+    /// Round trip time of the probe in microseconds
+    pub rtt: u64,
+}
+
+impl ClockSample {
+    /// Estimate the offset from a probe sent at `t0`, answered by the master
+    /// at `t1` and received at `t3` (local clock).
+    pub fn estimate(t0: u64, t1: u64, t3: u64) -> Self {
+        let rtt = t3.saturating_sub(t0);
+        let midpoint = t0 as i64 + (rtt / 2) as i64;
+        ClockSample {
+            offset: t1 as i64 - midpoint,
+            rtt,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClockState {
+    samples: VecDeque<ClockSample>,
+}
+
+impl ClockState {
+    fn record(&mut self, sample: ClockSample) {
+        if self.samples.len() == MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// The sample with the shortest round trip has the tightest error bound.
+    fn best(&self) -> Option<ClockSample> {
+        self.samples.iter().min_by_key(|s| s.rtt).copied()
+    }
+}
+
+static CLOCK: LazyLock<RwLock<ClockState>> = LazyLock::new(Default::default);
+
+/// Current local time in microseconds since the unix epoch.
+pub fn now_micros() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as u64
+}
+
+/// Record a probe result and return the updated offset estimate.
+pub fn record_sample(sample: ClockSample) -> i64 {
+    let mut clock = CLOCK.write().unwrap();
+    clock.record(sample);
+    clock.best().map(|s| s.offset).unwrap_or_default()
+}
+
+/// Estimated `master - local` offset, `None` until the master was probed.
+pub fn local_offset() -> Option<i64> {
+    CLOCK.read().unwrap().best().map(|s| s.offset)
+}
+
+/// Offset reported by the node of `rank`, `0` if unknown.
+pub fn rank_offset(rank: i32) -> i64 {
+    CLUSTER
+        .read()
+        .unwrap()
+        .get(rank)
+        .and_then(|node| node.clock_offset)
+        .unwrap_or_default()
+}
+
+/// Shift a timestamp taken on a node with `offset` onto the master clock.
+pub fn adjust(ts: i64, offset: i64) -> i64 {
+    ts.saturating_add(offset)
+}
+
+/// SQL function `clock_adjust(ts, rank)` shifting a timestamp recorded on
+/// `rank` onto the master clock. Timestamps of any unit are accepted and
+/// returned as `Timestamp(Nanosecond, UTC)`; integer times have no unit and
+/// are converted first, e.g. the nanoseconds of trace spans and the
+/// microseconds of the `timestamp` column of Python tables:
+///
+/// ```sql
+/// SELECT clock_adjust(to_timestamp_ns(time), rank) AS ts FROM ... ORDER BY ts
+/// SELECT clock_adjust(to_timestamp_micros(timestamp), rank) AS ts FROM ...
+/// ```
+pub fn clock_adjust_udf() -> ScalarUDF {
+    ScalarUDF::from(SimpleScalarUDF::new_with_signature(
+        "clock_adjust",
+        Signature::any(2, Volatility::Stable),
+        time::timestamp_ns_type(),
+        Arc::new(clock_adjust_impl),
+    ))
+}
+
+fn clock_adjust_impl(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    if !matches!(arrays[0].data_type(), DataType::Timestamp(_, _)) {
+        return Err(DataFusionError::Execution(format!(
+            "clock_adjust: ts must be a timestamp, not {}; convert integers with \
+             to_timestamp_ns or to_timestamp_micros",
+            arrays[0].data_type()
+        )));
+    }
+    let ts = cast(&arrays[0], &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+    let ts = ts
+        .as_any()
+        .downcast_ref::<TimestampNanosecondArray>()
+        .ok_or_else(|| DataFusionError::Internal("clock_adjust: cast failed".into()))?;
+    let ranks = cast(&arrays[1], &DataType::Int64)
+        .map_err(|_| DataFusionError::Execution("clock_adjust: rank must be an integer".into()))?;
+    let ranks = ranks
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .ok_or_else(|| DataFusionError::Internal("clock_adjust: cast failed".into()))?;
+
+    let adjusted = (0..ts.len())
+        .map(|i| {
+            if ts.is_null(i) {
+                return None;
+            }
+            let offset = if ranks.is_null(i) {
+                0
+            } else {
+                rank_offset(ranks.value(i) as i32)
+            };
+            Some(adjust(ts.value(i), offset.saturating_mul(1_000)))
+        })
+        .collect::<TimestampNanosecondArray>()
+        .with_timezone(time::TIMEZONE);
+    Ok(ColumnarValue::Array(Arc::new(adjusted)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_symmetric_delay() {
+        // master is 500us ahead, 100us each way
+        let sample = ClockSample::estimate(1_000, 1_600, 1_200);
+        assert_eq!(sample.offset, 500);
+        assert_eq!(sample.rtt, 200);
+
+        // master is behind
+        let sample = ClockSample::estimate(10_000, 9_050, 10_100);
+        assert_eq!(sample.offset, -1_000);
+    }
+
+    #[test]
+    fn test_best_sample_has_lowest_rtt() {
+        let mut state = ClockState::default();
+        assert!(state.best().is_none());
+
+        state.record(ClockSample {
+            offset: 40,
+            rtt: 900,
+        });
+        state.record(ClockSample {
+            offset: 10,
+            rtt: 100,
+        });
+        state.record(ClockSample {
+            offset: 25,
+            rtt: 300,
+        });
+        assert_eq!(state.best().unwrap().offset, 10);
+
+        for _ in 0..MAX_SAMPLES {
+            state.record(ClockSample {
+                offset: 7,
+                rtt: 500,
+            });
+        }
+        assert_eq!(state.samples.len(), MAX_SAMPLES);
+        assert_eq!(state.best().unwrap().offset, 7);
+    }
+
+    #[tokio::test]
+    async fn test_clock_adjust_udf() {
+        use probing_proto::prelude::Node;
+
+        CLUSTER.write().unwrap().put(Node {
+            host: "clock-test".to_string(),
+            addr: "127.0.0.1:1".to_string(),
+            rank: Some(4242),
+            clock_offset: Some(-300),
+            ..Default::default()
+        });
+
+        let ctx = datafusion::prelude::SessionContext::new();
+        ctx.register_udf(clock_adjust_udf());
+        ctx.register_udf(time::to_timestamp_ns_udf());
+        let batches = ctx
+            .sql(
+                "SELECT clock_adjust(to_timestamp_micros(1000), CAST(4242 AS INT)) AS a, \
+                 clock_adjust(to_timestamp_ns(1000000), 4242) AS b, \
+                 clock_adjust(to_timestamp_micros(1000), 4343) AS c",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        let col = |i: usize| {
+            assert_eq!(
+                batches[0].schema().field(i).data_type(),
+                &time::timestamp_ns_type()
+            );
+            batches[0]
+                .column(i)
+                .as_any()
+                .downcast_ref::<TimestampNanosecondArray>()
+                .unwrap()
+                .value(0)
+        };
+        // microseconds and nanoseconds end up on the same timeline
+        assert_eq!(col(0), 700_000);
+        assert_eq!(col(1), 700_000);
+        assert_eq!(col(2), 1_000_000);
+
+        let err = ctx
+            .sql("SELECT clock_adjust(1000, 4242)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("to_timestamp_ns"));
+    }
+}
@@ -1,7 +1,9 @@
-use std::sync::{Arc, LazyLock, RwLock};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
 
-use arrow::array::{ArrayRef, Int32Array, StringArray, TimestampMicrosecondArray};
-use probing_proto::prelude::{Cluster, Node};
+use arrow::array::{ArrayRef, Int32Array, Int64Array, StringArray, TimestampMicrosecondArray};
+use probing_proto::prelude::{Cluster, Job, Node};
 
 pub trait IntoArrow {
     fn into_arrow_array(values: Vec<Self>) -> ArrayRef
@@ -28,6 +30,12 @@ impl IntoArrow for Option<i32> {
     }
 }
 
+impl IntoArrow for Option<i64> {
+    fn into_arrow_array(values: Vec<Self>) -> ArrayRef {
+        Arc::new(Int64Array::from(values))
+    }
+}
+
 impl IntoArrow for std::time::Duration {
     fn into_arrow_array(values: Vec<Self>) -> ArrayRef {
         Arc::new(TimestampMicrosecondArray::from(
@@ -50,12 +58,95 @@ where
 
 pub static CLUSTER: LazyLock<RwLock<Cluster>> = LazyLock::new(|| RwLock::new(Cluster::default()));
 
-pub fn update_node(mut node: Node) {
-    node.timestamp = std::time::SystemTime::now()
+/// Default of `cluster.lease_seconds`, three missed reports
+pub const DEFAULT_LEASE_SECONDS: u64 = 30;
+
+/// Transitions kept in `cluster.events`, older ones are dropped first
+const MAX_EVENTS: usize = 1000;
+
+static LEASE_SECONDS: AtomicU64 = AtomicU64::new(DEFAULT_LEASE_SECONDS);
+
+/// Seconds a node stays registered after its last report, 0 for ever
+pub fn lease_seconds() -> u64 {
+    LEASE_SECONDS.load(Ordering::Relaxed)
+}
+
+pub fn set_lease_seconds(seconds: u64) {
+    LEASE_SECONDS.store(seconds, Ordering::Relaxed);
+}
+
+/// Transition of a node in the registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeTransition {
+    /// First report of the node, or first after it left or expired
+    Join,
+    /// The node deregistered as its process exited
+    Leave,
+    /// The node stopped reporting for longer than its lease
+    Expire,
+}
+
+impl NodeTransition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeTransition::Join => "join",
+            NodeTransition::Leave => "leave",
+            NodeTransition::Expire => "expire",
+        }
+    }
+}
+
+/// A row of `cluster.events`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeEvent {
+    /// Microseconds since the unix epoch
+    pub time: u64,
+    pub transition: NodeTransition,
+    pub host: String,
+    pub addr: String,
+    pub rank: Option<i32>,
+    pub job_id: Option<String>,
+}
+
+static EVENTS: LazyLock<Mutex<VecDeque<NodeEvent>>> = LazyLock::new(Default::default);
+
+fn now_micros() -> u64 {
+    std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
-        .as_micros() as u64;
-    CLUSTER.write().unwrap().put(node);
+        .as_micros() as u64
+}
+
+fn record(transition: NodeTransition, node: &Node, time: u64) {
+    log::info!(
+        "node {}:{} (rank {:?}) {}",
+        node.host,
+        node.addr,
+        node.rank,
+        transition.as_str()
+    );
+    let mut events = EVENTS.lock().unwrap();
+    if events.len() >= MAX_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(NodeEvent {
+        time,
+        transition,
+        host: node.host.clone(),
+        addr: node.addr.clone(),
+        rank: node.rank,
+        job_id: node.job_id.clone(),
+    });
+}
+
+/// Register a report of `node`, renewing its lease
+pub fn update_node(mut node: Node) {
+    node.timestamp = now_micros();
+    let mut cluster = CLUSTER.write().unwrap();
+    if !node.is_expected() && cluster.get_by_addr(&node.host, &node.addr).is_none() {
+        record(NodeTransition::Join, &node, node.timestamp);
+    }
+    cluster.put(node);
 }
 
 pub fn update_nodes(nodes: Vec<Node>) {
@@ -66,6 +157,133 @@ pub fn update_nodes(nodes: Vec<Node>) {
     }
 }
 
+/// Record nodes expected to report, see [`Cluster::expect`]
+pub fn expect_nodes(nodes: Vec<Node>) {
+    let mut cluster = CLUSTER.write().unwrap();
+
+    for node in nodes {
+        cluster.expect(node);
+    }
+}
+
+/// Deregister the node `host:addr`, when its process exits
+pub fn remove_node(host: &str, addr: &str) -> Option<Node> {
+    let node = CLUSTER.write().unwrap().remove_by_addr(host, addr)?;
+    record(NodeTransition::Leave, &node, now_micros());
+    Some(node)
+}
+
+/// Drop the nodes whose last report is older than the lease at `now`, in
+/// microseconds; nodes expected but never reported are kept
+pub fn expire_nodes(now: u64) -> Vec<Node> {
+    let lease = lease_seconds() * 1_000_000;
+    if lease == 0 {
+        return vec![];
+    }
+    let mut cluster = CLUSTER.write().unwrap();
+    let stale = cluster
+        .nodes
+        .values()
+        .filter(|node| !node.is_expected() && node.timestamp.saturating_add(lease) < now)
+        .map(|node| (node.host.clone(), node.addr.clone()))
+        .collect::<Vec<_>>();
+    let mut expired = vec![];
+    for (host, addr) in stale {
+        if let Some(node) = cluster.remove_by_addr(&host, &addr) {
+            record(NodeTransition::Expire, &node, now);
+            expired.push(node);
+        }
+    }
+    expired
+}
+
 pub fn get_nodes() -> Vec<Node> {
+    expire_nodes(now_micros());
     CLUSTER.read().unwrap().list()
 }
+
+pub fn get_jobs() -> Vec<Job> {
+    expire_nodes(now_micros());
+    CLUSTER.read().unwrap().jobs()
+}
+
+pub fn get_job_nodes(job_id: &str) -> Vec<Node> {
+    expire_nodes(now_micros());
+    CLUSTER.read().unwrap().list_job(job_id)
+}
+
+/// Join, leave and expire transitions of the nodes, oldest first
+pub fn get_events() -> Vec<NodeEvent> {
+    expire_nodes(now_micros());
+    EVENTS.lock().unwrap().iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(host: &str, rank: i32) -> Node {
+        Node {
+            host: host.to_string(),
+            addr: "10.0.0.1:9700".to_string(),
+            rank: Some(rank),
+            status: Some("running".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn events_of(host: &str) -> Vec<NodeTransition> {
+        EVENTS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.host == host)
+            .map(|event| event.transition)
+            .collect()
+    }
+
+    #[test]
+    fn test_node_lease() {
+        update_node(node("lease-a", 1));
+        update_node(node("lease-a", 1));
+        update_node(node("lease-b", 2));
+        assert_eq!(events_of("lease-a"), [NodeTransition::Join]);
+
+        let reported = CLUSTER
+            .read()
+            .unwrap()
+            .get_by_addr("lease-a", "10.0.0.1:9700")
+            .unwrap()
+            .timestamp;
+        let lease = lease_seconds() * 1_000_000;
+        assert!(expire_nodes(reported + lease)
+            .iter()
+            .all(|n| !n.host.starts_with("lease-")));
+
+        let expired = expire_nodes(reported + lease + 60_000_000);
+        assert!(expired.iter().any(|n| n.host == "lease-a"));
+        assert_eq!(
+            events_of("lease-a"),
+            [NodeTransition::Join, NodeTransition::Expire]
+        );
+        assert!(CLUSTER
+            .read()
+            .unwrap()
+            .get_by_addr("lease-a", "10.0.0.1:9700")
+            .is_none());
+
+        // reporting again joins again
+        update_node(node("lease-a", 1));
+        assert!(remove_node("lease-a", "10.0.0.1:9700").is_some());
+        assert!(remove_node("lease-a", "10.0.0.1:9700").is_none());
+        assert_eq!(
+            events_of("lease-a"),
+            [
+                NodeTransition::Join,
+                NodeTransition::Expire,
+                NodeTransition::Join,
+                NodeTransition::Leave
+            ]
+        );
+    }
+}
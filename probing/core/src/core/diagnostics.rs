@@ -0,0 +1,246 @@
+//! Translate DataFusion errors into structured [`QueryError`]s.
+//!
+//! Raw DataFusion messages are hard to present in a UI, so the engine
+//! classifies them, extracts the offending table/column and SQL position, and
+//! suggests similar names from the catalog when something is misspelled.
+
+use datafusion::error::DataFusionError;
+use datafusion::sql::sqlparser::parser::ParserError;
+use probing_proto::prelude::{ErrorCode, QueryError};
+
+use super::{Engine, EngineError};
+
+/// Maximum number of names suggested in a hint.
+const MAX_SUGGESTIONS: usize = 3;
+
+impl Engine {
+    /// Convert a DataFusion error into a [`QueryError`] with hints.
+    pub fn query_error(&self, err: &DataFusionError) -> QueryError {
+        let diagnostic = err.diagnostic();
+        let message = diagnostic
+            .map(|d| d.message.clone())
+            .unwrap_or_else(|| err.find_root().to_string());
+
+        let mut error = match err.find_root() {
+            DataFusionError::SQL(ParserError::ParserError(msg), _)
+            | DataFusionError::SQL(ParserError::TokenizerError(msg), _) => {
+                let error = QueryError::new(ErrorCode::ParseError, message);
+                match parse_position(msg) {
+                    Some((line, column)) => error.with_position(line, column),
+                    None => error,
+                }
+            }
+            DataFusionError::SQL(_, _) => QueryError::new(ErrorCode::ParseError, message),
+            DataFusionError::SchemaError(
+                datafusion::common::SchemaError::FieldNotFound {
+                    field,
+                    valid_fields,
+                },
+                _,
+            ) => {
+                let candidates = valid_fields.iter().map(|f| f.name.clone());
+                let error = QueryError::new(ErrorCode::ColumnNotFound, message)
+                    .with_column(field.name.clone());
+                match suggest(&field.name, candidates) {
+                    Some(hint) => error.with_hint(hint),
+                    None => error,
+                }
+            }
+            DataFusionError::Plan(msg) => match missing_table(msg) {
+                Some(table) => {
+                    let error = QueryError::new(ErrorCode::TableNotFound, message)
+                        .with_table(table.clone());
+                    match suggest(&table, self.table_names()) {
+                        Some(hint) => error.with_hint(hint),
+                        None => error,
+                    }
+                }
+                None => QueryError::new(ErrorCode::PlanError, message),
+            },
+            DataFusionError::ResourcesExhausted(_) => {
+                QueryError::new(ErrorCode::ResourceExhausted, message)
+            }
+            DataFusionError::External(err) => match err.downcast_ref::<EngineError>() {
+                Some(EngineError::ValidationFailed(invalid)) => {
+                    let error = QueryError::new(ErrorCode::ExecutionError, invalid.to_string());
+                    match &invalid.suggestion {
+                        Some(hint) => error.with_hint(hint.clone()),
+                        None => error,
+                    }
+                }
+                _ => QueryError::new(ErrorCode::ExecutionError, message),
+            },
+            DataFusionError::Execution(_) | DataFusionError::ArrowError(_, _) => {
+                QueryError::new(ErrorCode::ExecutionError, message)
+            }
+            _ => QueryError::new(ErrorCode::Internal, message),
+        };
+
+        if error.position.is_none() {
+            if let Some(span) = diagnostic.and_then(|d| d.span) {
+                error = error.with_position(span.start.line, span.start.column);
+            }
+        }
+        error.with_details(err.to_string())
+    }
+
+    /// Names of all registered tables as `schema.table`.
+    pub fn table_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        for catalog_name in self.context.catalog_names() {
+            let Some(catalog) = self.context.catalog(&catalog_name) else {
+                continue;
+            };
+            for schema_name in catalog.schema_names() {
+                if schema_name == "information_schema" {
+                    continue;
+                }
+                if let Some(schema) = catalog.schema(&schema_name) {
+                    names.extend(
+                        schema
+                            .table_names()
+                            .into_iter()
+                            .map(|table| format!("{schema_name}.{table}")),
+                    );
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// Extract `line, column` from sqlparser messages ending with
+/// `at Line: 1, Column: 15`.
+fn parse_position(msg: &str) -> Option<(u64, u64)> {
+    let (_, loc) = msg.rsplit_once("at Line: ")?;
+    let (line, column) = loc.split_once(", Column: ")?;
+    let column: String = column.chars().take_while(|c| c.is_ascii_digit()).collect();
+    Some((line.trim().parse().ok()?, column.parse().ok()?))
+}
+
+/// Extract the table name from `table 'probe.python.foo' not found`,
+/// dropping the default catalog.
+fn missing_table(msg: &str) -> Option<String> {
+    let name = msg.strip_prefix("table '")?.strip_suffix("' not found")?;
+    Some(name.strip_prefix("probe.").unwrap_or(name).to_string())
+}
+
+/// Suggest names similar to `name`, comparing unqualified names so that
+/// `trace_evnt` still matches `python.trace_event`.
+pub(crate) fn suggest<I: IntoIterator<Item = String>>(name: &str, candidates: I) -> Option<String> {
+    let target = unqualified(name).to_lowercase();
+    let threshold = (target.len() / 3).max(1);
+
+    let mut scored: Vec<(usize, String)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let short = unqualified(&candidate).to_lowercase();
+            let distance = edit_distance(&target, &short);
+            (distance <= threshold || short.contains(&target) || target.contains(&short))
+                .then_some((distance, candidate))
+        })
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+
+    if scored.is_empty() {
+        return None;
+    }
+    let names: Vec<_> = scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| format!("'{name}'"))
+        .collect();
+    Some(format!("did you mean {}?", names.join(" or ")))
+}
+
+fn unqualified(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn engine_with_table() -> Engine {
+        let engine = Engine::builder().build().await.unwrap();
+        engine
+            .sql("CREATE TABLE trace_event (name VARCHAR, duration BIGINT)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        engine
+    }
+
+    async fn error_of(engine: &Engine, sql: &str) -> QueryError {
+        let err = match engine.sql(sql).await {
+            Ok(df) => df.collect().await.unwrap_err(),
+            Err(err) => err,
+        };
+        engine.query_error(&err)
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("trace_event", "trace_event"), 0);
+        assert_eq!(edit_distance("trace_evnt", "trace_event"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_parse_position() {
+        let msg = "Expected: an SQL statement, found: selec at Line: 2, Column: 7";
+        assert_eq!(parse_position(msg), Some((2, 7)));
+        assert_eq!(parse_position("Expected: an expression, found: EOF"), None);
+    }
+
+    #[tokio::test]
+    async fn test_table_not_found_suggests_similar_tables() {
+        let engine = engine_with_table().await;
+        let error = error_of(&engine, "SELECT * FROM trace_evnt").await;
+
+        assert_eq!(error.code, ErrorCode::TableNotFound);
+        assert_eq!(error.table.as_deref(), Some("probe.trace_evnt"));
+        assert_eq!(error.position.map(|p| p.column), Some(15));
+        assert!(error.hint.unwrap().contains("probe.trace_event"));
+    }
+
+    #[tokio::test]
+    async fn test_column_not_found_suggests_similar_columns() {
+        let engine = engine_with_table().await;
+        let error = error_of(&engine, "SELECT durration FROM trace_event").await;
+
+        assert_eq!(error.code, ErrorCode::ColumnNotFound);
+        assert_eq!(error.column.as_deref(), Some("durration"));
+        assert_eq!(error.hint.as_deref(), Some("did you mean 'duration'?"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_error_has_position() {
+        let engine = engine_with_table().await;
+        let error = error_of(&engine, "SELEC 1").await;
+
+        assert_eq!(error.code, ErrorCode::ParseError);
+        assert_eq!(error.position.map(|p| (p.line, p.column)), Some((1, 1)));
+        assert!(error.details.is_some());
+    }
+}
@@ -3,19 +3,30 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use arrow::compute::concat_batches;
+use arrow::record_batch::RecordBatch;
 use datafusion::catalog::MemoryCatalogProvider;
 use datafusion::catalog::MemorySchemaProvider;
 use datafusion::catalog::{CatalogProvider, SchemaProvider};
 use datafusion::config::ConfigExtension;
+use datafusion::datasource::MemTable;
 use datafusion::error::DataFusionError;
 use datafusion::error::Result;
 use datafusion::execution::SessionState;
 use datafusion::prelude::{DataFrame, SessionConfig, SessionContext};
+use datafusion::sql::parser::Statement;
+use datafusion::sql::planner::object_name_to_table_reference;
+use datafusion::sql::sqlparser::ast::{ObjectType, Statement as SqlStatement};
 use futures;
 
 use super::arrow_convert::arrow_array_to_seq;
 use super::extension::EngineExtension;
 use super::extension::EngineExtensionManager;
+use super::join;
+use super::lineage::{Lineage, LineageRecord, LineageTable, LINEAGE_TABLE};
+use super::materialized::MaterializedViews;
+use super::session::{SessionCatalog, Sessions};
+use super::shape::ResultShape;
+use super::union_view::UnionView;
 
 /// Defines the types of plugins supported by the Probing query engine.
 /// These plugin types determine how data sources are registered with the engine.
@@ -151,6 +162,14 @@ pub struct Engine {
     pub context: SessionContext,
     /// Registry of enabled plugins, mapped by their fully qualified names
     plugins: RwLock<HashMap<String, Arc<dyn Plugin + Sync + Send>>>,
+    /// Views unioning a table across storage tiers, re-planned on use
+    views: Arc<std::sync::RwLock<Vec<UnionView>>>,
+    /// Temporary tables of the open query sessions
+    sessions: Arc<Sessions>,
+    /// Lineage of the views and temporary tables, served as `engine.lineage`
+    lineage: Arc<Lineage>,
+    /// Materialized views, see [`super::materialized`]
+    pub(super) materialized: Arc<MaterializedViews>,
 }
 
 impl Clone for Engine {
@@ -162,6 +181,10 @@ impl Clone for Engine {
         Self {
             context: self.context.clone(),
             plugins: RwLock::new(plugins_clone),
+            views: self.views.clone(),
+            sessions: self.sessions.clone(),
+            lineage: self.lineage.clone(),
+            materialized: self.materialized.clone(),
         }
     }
 }
@@ -180,6 +203,10 @@ impl Default for Engine {
         Engine {
             context: SessionContext::new_with_config(config),
             plugins: Default::default(),
+            views: Default::default(),
+            sessions: Default::default(),
+            lineage: Default::default(),
+            materialized: Default::default(),
         }
     }
 }
@@ -199,32 +226,229 @@ impl Engine {
     }
 
     pub async fn sql(&self, query: &str) -> Result<DataFrame> {
-        self.context.sql(query).await
+        self.refresh_views(query).await?;
+        join::sql(&self.context, query).await
+    }
+
+    /// Register a view unioning `members`, see [`UnionView`]
+    pub fn register_union_view(&self, view: UnionView) -> Result<()> {
+        view.register_empty(&self.context)?;
+        self.views.write().unwrap().push(view);
+        Ok(())
+    }
+
+    /// Re-plan the union views referenced by `query` against the current member tables
+    pub(super) async fn refresh_views(&self, query: &str) -> Result<()> {
+        let views = self.views.read().unwrap().clone();
+        if views.is_empty() {
+            return Ok(());
+        }
+        let state = self.context.state();
+        let dialect = state.config().options().sql_parser.dialect.clone();
+        // leave reporting of malformed queries to the planner
+        let Ok(statement) = state.sql_to_statement(query, &dialect) else {
+            return Ok(());
+        };
+        let tables = state.resolve_table_references(&statement)?;
+        let namespace = self.default_namespace();
+        for view in views {
+            if tables
+                .iter()
+                .any(|table| view.is_referenced_by(table, &namespace))
+            {
+                if let Some(plan) = view.refresh(&self.context).await? {
+                    let mut record = LineageRecord::from_plan(&view.name, "view", &plan);
+                    record.definition = format!("union of {}", view.members.join(", "));
+                    self.record_lineage(record);
+                }
+            }
+        }
+        Ok(())
     }
 
     pub async fn async_query<T: Into<String>>(
         &self,
         query: T,
+    ) -> Result<Option<probing_proto::prelude::DataFrame>> {
+        self.shaped_query(query, &ResultShape::default()).await
+    }
+
+    /// Run `query` and return its result in `shape`, see [`super::shape`]
+    pub async fn shaped_query<T: Into<String>>(
+        &self,
+        query: T,
+        shape: &ResultShape,
+    ) -> Result<Option<probing_proto::prelude::DataFrame>> {
+        let query: String = query.into();
+        // make trace records buffered by other threads visible to the query
+        crate::trace::flush();
+        crate::probe_span!("engine.query", kind = "engine", sql = query.as_str())
+            .instrument(async {
+                let batches = shape.collect(self.sql(query.as_str()).await?).await?;
+                to_dataframe(batches)
+            })
+            .await
+    }
+
+    /// Run `query` in `session`, where `CREATE TEMP TABLE .. AS ..` stages a
+    /// result visible to the later queries of the same session and `DROP
+    /// TABLE` drops it again, see [`super::session`]
+    pub async fn session_query<T: Into<String>>(
+        &self,
+        session: &str,
+        query: T,
+    ) -> Result<Option<probing_proto::prelude::DataFrame>> {
+        self.shaped_session_query(session, query, &ResultShape::default())
+            .await
+    }
+
+    /// [`Self::session_query`] returning the result in `shape`
+    pub async fn shaped_session_query<T: Into<String>>(
+        &self,
+        session: &str,
+        query: T,
+        shape: &ResultShape,
     ) -> Result<Option<probing_proto::prelude::DataFrame>> {
         let query: String = query.into();
-        let batches = self.sql(query.as_str()).await?.collect().await?;
-        if batches.is_empty() {
-            return Ok(None);
+        crate::trace::flush();
+        crate::probe_span!(
+            "engine.query",
+            kind = "engine",
+            sql = query.as_str(),
+            session = session
+        )
+        .instrument(self.run_session_query(session, query, shape))
+        .await
+    }
+
+    async fn run_session_query(
+        &self,
+        session: &str,
+        query: String,
+        shape: &ResultShape,
+    ) -> Result<Option<probing_proto::prelude::DataFrame>> {
+        self.refresh_views(&query).await?;
+
+        let tables = self.sessions.tables(session);
+        let context = self.session_context(tables.clone())?;
+        let state = context.state();
+        let dialect = state.config().options().sql_parser.dialect.clone();
+        let statement = state.sql_to_statement(&query, &dialect)?;
+        let Statement::Statement(statement) = statement else {
+            return to_dataframe(shape.collect(join::sql(&context, &query).await?).await?);
+        };
+        match *statement {
+            SqlStatement::CreateTable(create) if create.temporary => {
+                let Some(select) = create.query else {
+                    return Err(DataFusionError::Plan(
+                        "CREATE TEMP TABLE needs an AS SELECT clause".to_string(),
+                    ));
+                };
+                let name = temp_table_name(create.name)?;
+                if tables.table_exist(&name) && !create.or_replace {
+                    if create.if_not_exists {
+                        return Ok(None);
+                    }
+                    return Err(DataFusionError::Plan(format!(
+                        "temporary table `{name}` already exists"
+                    )));
+                }
+                let df = join::sql(&context, &select.to_string()).await?;
+                let mut record = LineageRecord::from_plan(&name, "temp", df.logical_plan());
+                record.session = Some(session.to_string());
+                record.definition = query.clone();
+                let schema = df.schema().inner().clone();
+                let batches = df.collect().await?;
+                tables.register_table(name, Arc::new(MemTable::try_new(schema, vec![batches])?))?;
+                self.record_lineage(record);
+                Ok(None)
+            }
+            SqlStatement::Drop {
+                object_type: ObjectType::Table,
+                names,
+                ..
+            } if names.iter().all(|name| {
+                temp_table_name(name.clone()).is_ok_and(|name| tables.table_exist(&name))
+            }) =>
+            {
+                for name in names {
+                    let name = temp_table_name(name)?;
+                    tables.deregister_table(&name)?;
+                    self.lineage.remove(Some(session), &name);
+                }
+                Ok(None)
+            }
+            _ => to_dataframe(shape.collect(join::sql(&context, &query).await?).await?),
         }
-        let batch = concat_batches(&batches[0].schema(), batches.iter())?;
+    }
 
-        let names = batch
-            .schema()
-            .fields()
-            .iter()
-            .map(|x| x.name().clone())
-            .collect::<Vec<_>>();
-        let columns = batch
-            .columns()
-            .iter()
-            .map(|col| arrow_array_to_seq(col))
-            .collect::<Vec<_>>();
-        Ok(Some(probing_proto::prelude::DataFrame::new(names, columns)))
+    /// Close `session`, returning the names of the temporary tables dropped
+    /// with it
+    pub fn close_session(&self, session: &str) -> Vec<String> {
+        self.lineage.remove_session(session);
+        self.sessions.close(session)
+    }
+
+    /// Record the lineage of a derived table, with the options of the
+    /// extensions serving its source namespaces
+    pub(super) fn record_lineage(&self, mut record: LineageRecord) {
+        let namespaces = record.source_namespaces();
+        let state = self.context.state();
+        if let Some(eem) = state
+            .config()
+            .options()
+            .extensions
+            .get::<EngineExtensionManager>()
+        {
+            let mut options = eem
+                .try_options()
+                .into_iter()
+                .filter(|option| {
+                    option
+                        .key
+                        .split_once('.')
+                        .is_some_and(|(ns, _)| namespaces.contains(ns))
+                })
+                .filter_map(|option| Some(format!("{}={}", option.key, option.value?)))
+                .collect::<Vec<_>>();
+            options.sort();
+            record.config = options.join(" ");
+        }
+        self.lineage.record(record);
+    }
+
+    /// Ids of the sessions holding temporary tables
+    pub fn session_ids(&self) -> Vec<String> {
+        self.sessions.ids()
+    }
+
+    /// Context resolving the unqualified names of the default namespace to
+    /// `tables` first, sharing every other table with [`Self::context`]
+    pub(super) fn session_context(
+        &self,
+        tables: Arc<datafusion::catalog::MemorySchemaProvider>,
+    ) -> Result<SessionContext> {
+        let context = SessionContext::new_with_config(self.context.copied_config());
+        register_functions(&context);
+        let default_schema = self.default_namespace();
+        for catalog_name in self.context.catalog_names() {
+            let Some(catalog) = self.context.catalog(&catalog_name) else {
+                continue;
+            };
+            if catalog_name == "probe" {
+                context.register_catalog(
+                    &catalog_name,
+                    Arc::new(SessionCatalog {
+                        inner: catalog,
+                        default_schema: default_schema.clone(),
+                        temp: tables.clone(),
+                    }),
+                );
+            } else {
+                context.register_catalog(&catalog_name, catalog);
+            }
+        }
+        Ok(context)
     }
 
     #[deprecated]
@@ -244,7 +468,23 @@ impl Engine {
             .clone()
     }
 
+    /// Enable a plugin, publishing its table or namespace to
+    /// `catalog.changes`, see [`crate::catalog`]
     pub async fn enable(&self, plugin: Arc<dyn Plugin + Sync + Send>) -> Result<()> {
+        let kind = plugin.kind();
+        let name = match kind {
+            PluginType::Namespace => plugin.namespace(),
+            PluginType::Table => format!("{}.{}", plugin.namespace(), plugin.name()),
+        };
+        self.register_plugin(plugin).await?;
+        match kind {
+            PluginType::Namespace => crate::catalog::namespace_registered("engine", &name),
+            PluginType::Table => crate::catalog::table_registered("engine", &name),
+        }
+        Ok(())
+    }
+
+    async fn register_plugin(&self, plugin: Arc<dyn Plugin + Sync + Send>) -> Result<()> {
         let namespace = plugin.namespace();
 
         let catalog = if let Some(catalog) = self.context.catalog("probe") {
@@ -281,6 +521,124 @@ impl Engine {
         }
         Ok(())
     }
+
+    /// Register `engine.lineage`, see [`super::lineage`]
+    fn register_lineage_table(&self) -> Result<()> {
+        let (namespace, name) = LINEAGE_TABLE;
+        let catalog = self
+            .context
+            .catalog("probe")
+            .ok_or_else(|| DataFusionError::Internal("no catalog `probe`".to_string()))?;
+        if catalog.schema(namespace).is_none() {
+            catalog.register_schema(namespace, Arc::new(MemorySchemaProvider::new()))?;
+        }
+        self.context.register_table(
+            format!("probe.{namespace}.{name}").as_str(),
+            Arc::new(LineageTable(self.lineage.clone())),
+        )?;
+        Ok(())
+    }
+
+    /// Copy the current contents of every table into an independent engine.
+    ///
+    /// The snapshot holds immutable in-memory copies of the tables, so heavy
+    /// analytics can run against it without contending with live ingestion.
+    /// Tables that fail to scan are logged and left out of the snapshot.
+    pub async fn snapshot(&self) -> Result<Engine> {
+        let views = self.views.read().unwrap().clone();
+        for view in views {
+            view.refresh(&self.context).await?;
+        }
+
+        let context = SessionContext::new_with_config(self.context.copied_config());
+        register_functions(&context);
+
+        for catalog_name in self.context.catalog_names() {
+            let Some(catalog) = self.context.catalog(&catalog_name) else {
+                continue;
+            };
+            let snapshot_catalog = MemoryCatalogProvider::new();
+            for schema_name in catalog.schema_names() {
+                if schema_name == "information_schema" {
+                    continue;
+                }
+                let Some(schema) = catalog.schema(&schema_name) else {
+                    continue;
+                };
+                let snapshot_schema = MemorySchemaProvider::new();
+                for table_name in schema.table_names() {
+                    let Some(table) = schema.table(&table_name).await? else {
+                        continue;
+                    };
+                    let df = self.context.read_table(table)?;
+                    let table_schema = df.schema().inner().clone();
+                    let batches = match df.collect().await {
+                        Ok(batches) => batches,
+                        Err(err) => {
+                            log::warn!("skip {schema_name}.{table_name} in snapshot: {err}");
+                            continue;
+                        }
+                    };
+                    let table = MemTable::try_new(table_schema, vec![batches])?;
+                    snapshot_schema.register_table(table_name, Arc::new(table))?;
+                }
+                snapshot_catalog.register_schema(&schema_name, Arc::new(snapshot_schema))?;
+            }
+            context.register_catalog(&catalog_name, Arc::new(snapshot_catalog));
+        }
+
+        Ok(Engine {
+            context,
+            plugins: Default::default(),
+            views: Default::default(),
+            sessions: Default::default(),
+            lineage: Default::default(),
+            materialized: Default::default(),
+        })
+    }
+}
+
+/// Register the functions every engine context provides
+fn register_functions(context: &SessionContext) {
+    context.register_udf(super::clock::clock_adjust_udf());
+    context.register_udf(super::time::to_timestamp_ns_udf());
+    context.register_udf(super::job::job_id_udf());
+    join::register_join_functions(context);
+    #[cfg(feature = "wasm")]
+    super::wasm::register_functions(context);
+}
+
+/// Convert collected batches into a result, `None` if there are none
+fn to_dataframe(batches: Vec<RecordBatch>) -> Result<Option<probing_proto::prelude::DataFrame>> {
+    if batches.is_empty() {
+        return Ok(None);
+    }
+    let batch = concat_batches(&batches[0].schema(), batches.iter())?;
+    crate::probe_event!("engine.collected", rows = batch.num_rows() as i64);
+
+    let names = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|x| x.name().clone())
+        .collect::<Vec<_>>();
+    let columns = batch
+        .columns()
+        .iter()
+        .map(arrow_array_to_seq)
+        .collect::<Vec<_>>();
+    Ok(Some(probing_proto::prelude::DataFrame::new(names, columns)))
+}
+
+/// Normalized name of a temporary table, which cannot be qualified
+fn temp_table_name(name: datafusion::sql::sqlparser::ast::ObjectName) -> Result<String> {
+    let display = name.to_string();
+    match object_name_to_table_reference(name, true)? {
+        datafusion::common::TableReference::Bare { table } => Ok(table.to_string()),
+        _ => Err(DataFusionError::Plan(format!(
+            "temporary table `{display}` cannot be qualified with a namespace"
+        ))),
+    }
 }
 
 // Define the EngineBuilder struct
@@ -289,6 +647,7 @@ pub struct EngineBuilder {
     default_namespace: Option<String>,
     plugins: Vec<Arc<dyn Plugin + Sync + Send>>,
     extensions: HashMap<String, Arc<tokio::sync::Mutex<dyn EngineExtension + Send + Sync>>>,
+    views: Vec<UnionView>,
 }
 
 impl EngineBuilder {
@@ -299,6 +658,7 @@ impl EngineBuilder {
             default_namespace: None,
             plugins: Vec::new(),
             extensions: Default::default(),
+            views: Vec::new(),
         }
     }
 
@@ -314,6 +674,16 @@ impl EngineBuilder {
         self
     }
 
+    /// Add a view `name` unioning `members` across storage tiers, e.g. the
+    /// in-memory `python.trace_event` and the on-disk `archive.trace_event`
+    pub fn with_union_view(mut self, name: &str, members: &[&str]) -> Self {
+        self.views.push(UnionView::new(
+            name,
+            members.iter().map(|m| m.to_string()).collect(),
+        ));
+        self
+    }
+
     pub fn with_extension<T>(mut self, ext: T, namespace: &str, name: Option<&str>) -> Self
     where
         T: EngineExtension + Send + Sync + 'static,
@@ -347,13 +717,24 @@ impl EngineBuilder {
         self.config = self.config.with_information_schema(true);
 
         let context = SessionContext::new_with_config(self.config);
+        register_functions(&context);
         let engine = Engine {
             context,
             plugins: Default::default(),
+            views: Default::default(),
+            sessions: Default::default(),
+            lineage: Default::default(),
+            materialized: Default::default(),
         };
+        // the tables of a new engine are not changes
         for plugin in self.plugins {
-            engine.enable(plugin).await?;
+            engine.register_plugin(plugin).await?;
         }
+        for view in self.views {
+            engine.register_union_view(view)?;
+        }
+        engine.register_lineage_table()?;
+        crate::recorder::register_all(&engine);
 
         Ok(engine)
     }
@@ -367,7 +748,7 @@ impl Default for EngineBuilder {
 
 #[cfg(test)]
 mod tests {
-    use crate::core::{EngineCall, EngineDatasource};
+    use crate::core::{EngineCall, EngineDatasource, EngineExtensionOption};
 
     use super::*;
     use arrow::array::{Int32Array, StringArray};
@@ -532,6 +913,10 @@ mod tests {
             fn name(&self) -> String {
                 "test_extension".to_string()
             }
+
+            fn options(&self) -> Vec<EngineExtensionOption> {
+                vec![]
+            }
         }
 
         impl EngineCall for TestExtension {}
@@ -569,6 +954,26 @@ mod tests {
         assert!(engine.enable(namespace_plugin).await.is_ok());
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_query_span_leaves_no_thread_stack_behind() {
+        let engine = Arc::new(Engine::builder().build().await.unwrap());
+        let queries: Vec<_> = (0..16)
+            .map(|_| {
+                let engine = engine.clone();
+                tokio::spawn(async move { engine.async_query("SELECT 1 as num").await })
+            })
+            .collect();
+        for query in queries {
+            assert!(query.await.unwrap().unwrap().is_some());
+        }
+        let leftovers: Vec<_> = (0..64)
+            .map(|_| tokio::spawn(async { crate::trace::current_span().is_none() }))
+            .collect();
+        for leftover in leftovers {
+            assert!(leftover.await.unwrap());
+        }
+    }
+
     #[tokio::test]
     async fn test_basic_queries() {
         let engine = Engine::builder().build().await.unwrap();
@@ -996,4 +1401,109 @@ mod tests {
 
         Ok(())
     }
+
+    async fn count_events(engine: &Engine) -> Result<i64> {
+        let df = engine
+            .async_query("SELECT count(*) FROM events")
+            .await?
+            .unwrap();
+        match &df.cols[0] {
+            Seq::SeqI64(v) => Ok(v[0]),
+            _ => panic!("unexpected count type"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_is_isolated_from_live_tables() -> Result<()> {
+        let engine = Engine::builder()
+            .with_default_namespace("live")
+            .build()
+            .await?;
+        let plugin = Arc::new(TestTablePlugin::default());
+        engine.enable(plugin).await?;
+        engine
+            .sql("CREATE TABLE probe.live.events AS VALUES (1), (2)")
+            .await?
+            .collect()
+            .await?;
+
+        let snapshot = engine.snapshot().await?;
+        engine
+            .sql("INSERT INTO events VALUES (3)")
+            .await?
+            .collect()
+            .await?;
+
+        assert_eq!(count_events(&engine).await?, 3);
+        assert_eq!(count_events(&snapshot).await?, 2);
+
+        let result = snapshot
+            .async_query("SELECT * FROM test_namespace.test_table")
+            .await?
+            .unwrap();
+        assert_eq!(result.cols[0].len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_temp_tables() -> Result<()> {
+        let engine = Engine::builder().build().await?;
+        engine.enable(Arc::new(TestTablePlugin::default())).await?;
+
+        engine
+            .session_query(
+                "a",
+                "CREATE TEMP TABLE staged AS SELECT * FROM test_namespace.test_table WHERE id > 1",
+            )
+            .await?;
+        let result = engine
+            .session_query("a", "SELECT count(*) AS n FROM staged")
+            .await?
+            .unwrap();
+        assert_eq!(result.cols[0], Seq::SeqI64(vec![2]));
+
+        let lineage = engine
+            .async_query(
+                "SELECT source_table, source_column, session FROM engine.lineage \
+                 WHERE \"table\" = 'staged' AND \"column\" = 'id'",
+            )
+            .await?
+            .unwrap();
+        assert_eq!(
+            lineage.cols[0],
+            Seq::SeqText(vec!["test_namespace.test_table".to_string()])
+        );
+        assert_eq!(lineage.cols[1], Seq::SeqText(vec!["id".to_string()]));
+        assert_eq!(lineage.cols[2], Seq::SeqText(vec!["a".to_string()]));
+
+        // other sessions and plain queries do not see the table
+        assert!(engine
+            .session_query("b", "SELECT * FROM staged")
+            .await
+            .is_err());
+        assert!(engine.async_query("SELECT * FROM staged").await.is_err());
+        assert!(engine
+            .session_query("a", "CREATE TEMP TABLE staged AS SELECT 1")
+            .await
+            .is_err());
+        assert!(engine
+            .session_query("a", "CREATE TEMP TABLE x.staged AS SELECT 1")
+            .await
+            .is_err());
+
+        assert_eq!(engine.close_session("a"), vec!["staged".to_string()]);
+        assert!(engine.lineage.records().is_empty());
+        assert!(engine
+            .session_query("a", "SELECT * FROM staged")
+            .await
+            .is_err());
+
+        engine
+            .session_query("c", "CREATE TEMP TABLE t AS SELECT 1 AS v")
+            .await?;
+        engine.session_query("c", "DROP TABLE t").await?;
+        assert!(engine.close_session("c").is_empty());
+        Ok(())
+    }
 }
@@ -44,6 +44,10 @@ pub enum EngineError {
     #[error("Unsupported API call")]
     UnsupportedCall,
 
+    /// Extension disabled by its circuit breaker, see [`super::breaker`]
+    #[error("Extension disabled after repeated failures: {0}")]
+    ExtensionDisabled(String),
+
     // ===== Data Processing Errors =====
     /// Apache Arrow data processing error
     #[error("Arrow data error: {0}")]
@@ -76,6 +80,10 @@ pub enum EngineError {
     #[error("Invalid option value: {0}={1}")]
     InvalidOptionValue(String, String),
 
+    /// Write rejected by the schema of the option, see [`super::schema`]
+    #[error("{0}")]
+    ValidationFailed(probing_proto::prelude::ConfigValidationError),
+
     /// Attempt to modify read-only option
     #[error("Read-only option: {0}")]
     ReadOnlyOption(String),
@@ -116,9 +124,11 @@ impl EngineError {
 
             // Error variants that cannot or should not have context added
             e @ (EngineError::UnsupportedCall
+            | EngineError::ExtensionDisabled(_)
             | EngineError::ArrowError(_)
             | EngineError::DataFusionError(_)
             | EngineError::InvalidOptionValue(_, _)
+            | EngineError::ValidationFailed(_)
             | EngineError::EngineNotInitialized) => e,
         }
     }
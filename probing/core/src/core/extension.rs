@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::convert::Infallible;
@@ -9,11 +10,17 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use datafusion::config::{ConfigExtension, ExtensionOptions};
 use once_cell::sync::Lazy;
+use probing_proto::prelude::OptionSchema;
 use tokio::sync::{Mutex, RwLock};
 
+use super::breaker;
 use super::error::EngineError;
+use super::profile::{self, PROFILES_PREFIX, PROFILE_KEY};
+use super::schema;
 use super::Plugin;
 use crate::config;
+use crate::events;
+use crate::journal::{self, EntryKind};
 
 /// Global extensions registry.
 ///
@@ -24,6 +31,23 @@ pub static EXTENSIONS: Lazy<
     RwLock<BTreeMap<String, Arc<Mutex<dyn EngineExtension + Send + Sync>>>>,
 > = Lazy::new(|| RwLock::new(BTreeMap::new()));
 
+tokio::task_local! {
+    /// Metadata attached to the response of the extension call being served
+    static CALL_METADATA: RefCell<BTreeMap<String, String>>;
+}
+
+/// Attach `key=value` to the response of the extension call being served.
+///
+/// The server returns it to the client as an `x-probing-<key>` header. Outside
+/// of [`EngineExtensionManager::call_with_metadata`] this does nothing.
+pub fn set_call_metadata(key: &str, value: impl Display) {
+    let _ = CALL_METADATA.try_with(|metadata| {
+        metadata
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string())
+    });
+}
+
 #[derive(Clone, Debug, Default)]
 pub enum Maybe<T> {
     Just(T),
@@ -209,6 +233,10 @@ pub trait EngineExtension: Debug + Send + Sync + EngineCall + EngineDatasource {
     fn options(&self) -> Vec<EngineExtensionOption> {
         todo!()
     }
+    /// Types and constraints of the options, validated on every write
+    fn schema(&self) -> Vec<OptionSchema> {
+        vec![]
+    }
 }
 
 /// Engine extension management module for configurable functionality.
@@ -324,6 +352,8 @@ impl EngineExtensionManager {
         name: String,
         extension: Arc<Mutex<dyn EngineExtension + Send + Sync>>,
     ) {
+        breaker::track(&name);
+        schema::register(extension.lock().await.schema());
         EXTENSIONS.write().await.insert(name, extension);
     }
 
@@ -341,15 +371,37 @@ impl EngineExtensionManager {
     /// This is the core implementation that updates extension configuration.
     /// ConfigStore is not updated by this method.
     pub async fn set_option(&mut self, key: &str, value: &str) -> Result<(), EngineError> {
+        if key == PROFILE_KEY {
+            return self.apply_profile(value).await;
+        }
+        if let Some(name) = key.strip_prefix(PROFILES_PREFIX) {
+            profile::define(name, value)?;
+            events::config_changed("profile", key, value, None);
+            return Ok(());
+        }
+        if key == breaker::RESET_KEY {
+            if !breaker::reset(value) {
+                return Err(EngineError::InvalidOptionValue(
+                    key.to_string(),
+                    value.to_string(),
+                ));
+            }
+            events::config_changed("extensions", key, value, None);
+            return Ok(());
+        }
+        schema::validate(key, value).map_err(EngineError::ValidationFailed)?;
+
         let extensions_clone: Vec<_> = {
             let extensions = EXTENSIONS.read().await;
             extensions.values().cloned().collect()
         }; // Lock is released here
 
         for extension in extensions_clone {
-            let namespace = {
+            let (name, namespace) = {
                 let ext = extension.lock().await;
-                Self::extract_namespace(&ext.name())
+                let name = ext.name();
+                let namespace = Self::extract_namespace(&name);
+                (name, namespace)
             };
 
             if !key.starts_with(&namespace) {
@@ -359,7 +411,7 @@ impl EngineExtensionManager {
             let local_key = key.trim_start_matches(&namespace).to_string();
             let result = {
                 let mut ext = extension.lock().await;
-                ext.set(&local_key, value)
+                breaker::run(&name, || ext.set(&local_key, value))
             };
 
             match result {
@@ -368,6 +420,7 @@ impl EngineExtensionManager {
                         "setting update [{}]:{local_key}={value} <= {old}",
                         namespace.trim_end_matches('.')
                     );
+                    events::config_changed(namespace.trim_end_matches('.'), key, value, Some(&old));
                     return Ok(());
                 }
                 Err(EngineError::UnsupportedOption(_)) => continue,
@@ -377,6 +430,41 @@ impl EngineExtensionManager {
         Err(EngineError::UnsupportedOption(key.to_string()))
     }
 
+    /// Apply every option of the profile `name`.
+    ///
+    /// Options of extensions that are not loaded are skipped, the others are
+    /// all attempted and the profile fails if any of them was rejected.
+    async fn apply_profile(&mut self, name: &str) -> Result<(), EngineError> {
+        let options = profile::options_of(name).ok_or_else(|| {
+            EngineError::InvalidOptionValue(PROFILE_KEY.to_string(), name.to_string())
+        })?;
+
+        let mut rejected = vec![];
+        for (key, value) in options {
+            match Box::pin(self.set_option(&key, &value)).await {
+                Ok(()) => config::set(&format!("probing.{key}"), value.as_str()).await,
+                Err(EngineError::UnsupportedOption(_)) => {
+                    log::warn!("profile {name}: no extension provides {key}, skipped")
+                }
+                Err(e) => {
+                    log::error!("profile {name}: failed to set {key}={value}: {e}");
+                    rejected.push(key);
+                }
+            }
+        }
+
+        let old = profile::set_active(name);
+        events::config_changed("profile", PROFILE_KEY, name, old.as_deref());
+        if rejected.is_empty() {
+            Ok(())
+        } else {
+            Err(EngineError::InvalidOptionValue(
+                PROFILE_KEY.to_string(),
+                format!("{name} (rejected {})", rejected.join(", ")),
+            ))
+        }
+    }
+
     /// Set an option and update ConfigStore.
     ///
     /// This is a convenience wrapper that calls `set_option`
@@ -393,6 +481,10 @@ impl EngineExtensionManager {
     }
 
     pub async fn get_option(&self, key: &str) -> Result<String, EngineError> {
+        if key == PROFILE_KEY {
+            return Ok(profile::active().unwrap_or_default());
+        }
+
         let extensions_clone: Vec<_> = {
             let extensions = EXTENSIONS.read().await;
             extensions.values().cloned().collect()
@@ -405,7 +497,7 @@ impl EngineExtensionManager {
                 continue;
             }
             let local_key = key.trim_start_matches(&namespace);
-            match ext.get(local_key) {
+            match breaker::run(&ext.name(), || ext.get(local_key)) {
                 Ok(value) => {
                     log::info!("setting read [{}]:{local_key}={value}", ext.name());
                     return Ok(value);
@@ -428,9 +520,44 @@ impl EngineExtensionManager {
             let ext_guard = extension_arc.lock().await;
             all_options.extend(ext_guard.options());
         }
+        all_options.push(EngineExtensionOption {
+            key: PROFILE_KEY.to_string(),
+            value: profile::active(),
+            help: "Instrumentation profile: minimal, dataloader-debug, divergence-debug or a probing.profiles.<name>",
+        });
         all_options
     }
 
+    /// Options of the extensions not locked at the moment, for callers that
+    /// may run inside an extension call, where [`Self::options`] would wait
+    /// forever on the calling extension
+    pub fn try_options(&self) -> Vec<EngineExtensionOption> {
+        let Ok(extensions) = EXTENSIONS.try_read() else {
+            return vec![];
+        };
+        extensions
+            .values()
+            .filter_map(|extension| extension.try_lock().ok().map(|ext| ext.options()))
+            .flatten()
+            .collect()
+    }
+
+    /// [`Self::call`], also returning the metadata the extension attached
+    /// to the response with [`set_call_metadata`]
+    pub async fn call_with_metadata(
+        &self,
+        path: &str,
+        params: &HashMap<String, String>,
+        body: &[u8],
+    ) -> (Result<Vec<u8>, EngineError>, BTreeMap<String, String>) {
+        CALL_METADATA
+            .scope(RefCell::default(), async {
+                let result = self.call(path, params, body).await;
+                (result, CALL_METADATA.with(RefCell::take))
+            })
+            .await
+    }
+
     pub async fn call(
         &self,
         path: &str,
@@ -442,6 +569,7 @@ impl EngineExtensionManager {
             extensions.values().cloned().collect()
         }; // Lock is released here
 
+        let mut disabled = None;
         for extension in extensions_clone {
             let ext = extension.lock().await;
             let name = ext.name();
@@ -485,8 +613,11 @@ impl EngineExtensionManager {
             log::debug!("checking extension [{name}]:{path}");
             log::debug!("Extension [{name}] matched, local_path: {}", local_path);
 
-            // Call the extension's async call method
-            match ext.call(&local_path, params, body).await {
+            // Call the extension's async call method, a disabled extension
+            // leaves the path to the others
+            drop(ext);
+            let call = call_blocking(extension.clone(), local_path.clone(), params, body);
+            match breaker::run_async(&name, call).await {
                 Ok(value) => return Ok(value),
                 Err(EngineError::UnsupportedCall) => {
                     log::debug!(
@@ -495,21 +626,61 @@ impl EngineExtensionManager {
                     );
                     continue;
                 }
+                Err(e @ EngineError::ExtensionDisabled(_)) => {
+                    disabled = Some(e);
+                    continue;
+                }
                 Err(e) => {
                     log::error!(
                         "Extension [{name}] call failed for path '{}': {}",
                         local_path,
                         e
                     );
+                    journal::record(EntryKind::Extension, &name, format!("{local_path}: {e}"));
                     return Err(e);
                 }
             }
         }
+        if let Some(e) = disabled {
+            return Err(e);
+        }
         log::error!("No extension matched path: {}", path);
         Err(EngineError::CallError(format!("API call error: {}", path)))
     }
 }
 
+/// Run the call of `extension` on a blocking thread
+///
+/// Extensions do the work of a call synchronously, holding the GIL or reading
+/// files, so on a runtime thread the deadline of the request could not cut it
+/// short. Dropping the returned future abandons the call, which still runs to
+/// its end in the background while the extension stays locked.
+async fn call_blocking(
+    extension: Arc<Mutex<dyn EngineExtension + Send + Sync>>,
+    path: String,
+    params: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<Vec<u8>, EngineError> {
+    let params = params.clone();
+    let body = body.to_vec();
+    let handle = tokio::runtime::Handle::current();
+    let task = tokio::task::spawn_blocking(move || {
+        handle.block_on(CALL_METADATA.scope(RefCell::default(), async move {
+            let result = extension.lock().await.call(&path, &params, &body).await;
+            (result, CALL_METADATA.with(RefCell::take))
+        }))
+    });
+    match task.await {
+        Ok((result, metadata)) => {
+            let _ = CALL_METADATA.try_with(|m| m.borrow_mut().extend(metadata));
+            result
+        }
+        // rethrown for the circuit breaker to count
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(EngineError::CallError(e.to_string())),
+    }
+}
+
 impl ConfigExtension for EngineExtensionManager {
     const PREFIX: &'static str = "probing";
 }
@@ -530,8 +701,13 @@ impl ExtensionOptions for EngineExtensionManager {
 
     fn set(&mut self, key: &str, value: &str) -> datafusion::error::Result<()> {
         use futures::executor::block_on;
-        block_on(self.set_option(key, value))
-            .map_err(|e| datafusion::error::DataFusionError::Execution(e.to_string()))
+        block_on(self.set_option(key, value)).map_err(|e| match e {
+            // kept structured for `Engine::query_error`
+            EngineError::ValidationFailed(_) => {
+                datafusion::error::DataFusionError::External(Box::new(e))
+            }
+            e => datafusion::error::DataFusionError::Execution(e.to_string()),
+        })
     }
 
     fn entries(&self) -> Vec<datafusion::config::ConfigEntry> {
@@ -695,6 +871,128 @@ mod tests {
         teardown_test().await;
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_apply_profile() {
+        setup_test().await;
+
+        let mut manager = EngineExtensionManager;
+        let extension = Arc::new(Mutex::new(TestExtension::default()));
+        manager.register("test".to_string(), extension).await;
+
+        // options of extensions that are not loaded are skipped
+        manager
+            .set_option(
+                "profiles.test-debug",
+                "test.option=profiled missing.option=1",
+            )
+            .await
+            .unwrap();
+        manager.set_option("profile", "test-debug").await.unwrap();
+
+        assert_eq!(manager.get_option("test.option").await.unwrap(), "profiled");
+        assert_eq!(
+            config::get_str("probing.test.option").await,
+            Some("profiled".to_string())
+        );
+        assert_eq!(manager.get_option("profile").await.unwrap(), "test-debug");
+        assert!(matches!(
+            manager.set_option("profile", "no-such-profile").await,
+            Err(EngineError::InvalidOptionValue(..))
+        ));
+
+        teardown_test().await;
+    }
+
+    /// Extension accepting any option, to see what a profile sets
+    #[derive(Debug)]
+    struct RecordingExtension {
+        name: &'static str,
+        values: BTreeMap<String, String>,
+    }
+
+    impl EngineCall for RecordingExtension {}
+    impl EngineDatasource for RecordingExtension {}
+
+    impl EngineExtension for RecordingExtension {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn set(&mut self, key: &str, value: &str) -> Result<String, EngineError> {
+            Ok(self
+                .values
+                .insert(key.to_string(), value.to_string())
+                .unwrap_or_default())
+        }
+
+        fn get(&self, key: &str) -> Result<String, EngineError> {
+            self.values
+                .get(key)
+                .cloned()
+                .ok_or_else(|| EngineError::UnsupportedOption(key.to_string()))
+        }
+
+        fn options(&self) -> Vec<EngineExtensionOption> {
+            vec![]
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_builtin_profiles() {
+        setup_test().await;
+
+        let mut manager = EngineExtensionManager;
+        for name in ["pprof", "tracer", "tracing", "torch", "anomaly"] {
+            let extension = RecordingExtension {
+                name,
+                values: Default::default(),
+            };
+            manager
+                .register(name.to_string(), Arc::new(Mutex::new(extension)))
+                .await;
+        }
+
+        // each profile after the others, minimal undoing what they turned on
+        let expected = [
+            ("divergence-debug", vec![("torch.grad_stats", "on")]),
+            (
+                "dataloader-debug",
+                vec![
+                    ("pprof.sample_freq", "10"),
+                    ("tracer.sample_every", "1"),
+                    ("tracing.include", "torch.utils.data,importlib"),
+                ],
+            ),
+            (
+                "minimal",
+                vec![
+                    ("pprof.sample_freq", ""),
+                    ("tracer.sample_every", ""),
+                    ("tracing.include", ""),
+                    ("tracing.exclude", ""),
+                    ("tracer.max_events_per_sec", ""),
+                    ("torch.profiling", "off"),
+                    ("torch.grad_stats", "off"),
+                    ("anomaly.watch", ""),
+                ],
+            ),
+        ];
+        assert_eq!(expected.len(), profile::BUILTIN_PROFILES.len());
+        for (name, settings) in expected {
+            manager.set_option(PROFILE_KEY, name).await.unwrap();
+            assert_eq!(manager.get_option(PROFILE_KEY).await.unwrap(), name);
+            for (key, value) in settings {
+                assert_eq!(
+                    manager.get_option(key).await.unwrap(),
+                    value,
+                    "{key} after {name}"
+                );
+            }
+        }
+
+        teardown_test().await;
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_get_option_from_config_store() {
         setup_test().await;
@@ -708,4 +1006,56 @@ mod tests {
 
         teardown_test().await;
     }
+
+    /// Extension whose calls block the thread they run on
+    #[derive(Debug)]
+    struct SlowExtension;
+
+    #[async_trait]
+    impl EngineCall for SlowExtension {
+        async fn call(
+            &self,
+            _: &str,
+            _: &HashMap<String, String>,
+            _: &[u8],
+        ) -> Result<Vec<u8>, EngineError> {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            set_call_metadata("slept", 200);
+            Ok(b"done".to_vec())
+        }
+    }
+    impl EngineDatasource for SlowExtension {}
+
+    impl EngineExtension for SlowExtension {
+        fn name(&self) -> String {
+            "slow".to_string()
+        }
+
+        fn options(&self) -> Vec<EngineExtensionOption> {
+            vec![]
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_call_cut_short_by_timeout() {
+        setup_test().await;
+
+        let mut manager = EngineExtensionManager;
+        manager
+            .register("slow".to_string(), Arc::new(Mutex::new(SlowExtension)))
+            .await;
+        let params = HashMap::new();
+
+        let started = std::time::Instant::now();
+        let call = manager.call_with_metadata("/slow/", &params, b"");
+        let reply = tokio::time::timeout(std::time::Duration::from_millis(20), call).await;
+        assert!(reply.is_err());
+        assert!(started.elapsed() < std::time::Duration::from_millis(150));
+
+        let (result, metadata) = manager.call_with_metadata("/slow/", &params, b"").await;
+        assert_eq!(result.unwrap(), b"done");
+        assert_eq!(metadata.get("slept").map(String::as_str), Some("200"));
+
+        teardown_test().await;
+    }
 }
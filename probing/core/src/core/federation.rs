@@ -0,0 +1,850 @@
+//! Tables of external databases.
+//!
+//! Experiment tracking metadata, such as runs and their hyperparameters,
+//! usually lives in a central database. A remote table serves a table of a
+//! PostgreSQL or ClickHouse database as `remote.<name>`, so live probe data
+//! can be joined against it:
+//!
+//! ```sql
+//! SELECT r.learning_rate, avg(s.loss)
+//! FROM python.trainer_steps s JOIN remote.runs r ON r.run_id = s.run_id
+//! GROUP BY r.learning_rate;
+//! ```
+//!
+//! Every scan runs one query against the database, with the columns read, the
+//! simple comparisons of the filters (see [`Predicate`]) and the limit pushed
+//! down, and reads at most `max_rows` rows. Connections are read-only:
+//! PostgreSQL sessions default to read-only transactions and ClickHouse
+//! queries are sent with `readonly=1`.
+//!
+//! Passwords are read from the environment variable named by `password_env`,
+//! never from the url. PostgreSQL connections use TLS as the url's `sslmode`
+//! asks, `prefer` by default and `require` to refuse plain connections; the
+//! server certificate is checked against the Mozilla root certificates.
+//!
+//! Columns are read as booleans, 64-bit integers, floats or strings.
+//! Timestamps become nanoseconds since the unix epoch like the probe's own
+//! tables, and other types are read as their text. The connectors are built
+//! with the `postgres` and `clickhouse` features.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use datafusion::catalog::{MemorySchemaProvider, Session, TableProvider};
+use datafusion::datasource::memory::{DataSourceExec, MemorySourceConfig};
+use datafusion::datasource::TableType;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{Expr, TableProviderFilterPushDown};
+use datafusion::physical_plan::ExecutionPlan;
+use serde::{Deserialize, Serialize};
+
+use super::pushdown::{Condition, Key, Predicate};
+use super::Engine;
+
+/// Namespace of the remote tables
+pub const NAMESPACE: &str = "remote";
+
+/// Rows read by a scan without an explicit `max_rows`
+pub const DEFAULT_MAX_ROWS: usize = 100_000;
+
+/// Database a remote table is read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteKind {
+    Postgres,
+    Clickhouse,
+}
+
+impl RemoteKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemoteKind::Postgres => "postgres",
+            RemoteKind::Clickhouse => "clickhouse",
+        }
+    }
+
+    /// Whether the connector was built in
+    pub fn is_available(&self) -> bool {
+        match self {
+            RemoteKind::Postgres => cfg!(feature = "postgres"),
+            RemoteKind::Clickhouse => cfg!(feature = "clickhouse"),
+        }
+    }
+}
+
+/// A table of an external database, as declared in `federation.tables`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteTableSpec {
+    /// Name of the table in the probe, `remote.<name>`
+    pub name: String,
+    pub kind: RemoteKind,
+    /// `postgres://user@host:5432/db` or `http://user@host:8123/db`
+    pub url: String,
+    /// Table in the database, optionally qualified by its schema or database
+    pub table: String,
+    /// Environment variable holding the password, which cannot be part of
+    /// the url
+    #[serde(default)]
+    pub password_env: Option<String>,
+    /// Rows read by a scan at most
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+}
+
+impl RemoteTableSpec {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let name = &self.name;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid table name `{name}`"));
+        }
+        if !self.kind.is_available() {
+            return Err(format!(
+                "{name}: probing was built without the {} connector",
+                self.kind.as_str()
+            ));
+        }
+        let parts = self.table.split('.').collect::<Vec<_>>();
+        if parts.len() > 2
+            || parts.iter().any(|part| {
+                part.is_empty() || !part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            })
+        {
+            return Err(format!("{name}: invalid remote table `{}`", self.table));
+        }
+        let url = url::Url::parse(&self.url).map_err(|e| format!("{name}: {e}"))?;
+        let schemes: &[&str] = match self.kind {
+            RemoteKind::Postgres => &["postgres", "postgresql"],
+            RemoteKind::Clickhouse => &["http", "https"],
+        };
+        if !schemes.contains(&url.scheme()) {
+            return Err(format!(
+                "{name}: {} urls start with {}://",
+                self.kind.as_str(),
+                schemes[0]
+            ));
+        }
+        if url.password().is_some() || url.query_pairs().any(|(key, _)| key == "password") {
+            return Err(format!(
+                "{name}: pass the password with password_env, not in the url"
+            ));
+        }
+        if self.max_rows == Some(0) {
+            return Err(format!("{name}: max_rows must be positive"));
+        }
+        Ok(())
+    }
+
+    /// Password from `password_env`
+    pub fn password(&self) -> Option<String> {
+        std::env::var(self.password_env.as_ref()?).ok()
+    }
+
+    /// The url with its password hidden, for logs
+    pub fn display_url(&self) -> String {
+        match url::Url::parse(&self.url) {
+            Ok(mut url) if url.password().is_some() => {
+                let _ = url.set_password(Some("***"));
+                url.to_string()
+            }
+            _ => self.url.clone(),
+        }
+    }
+
+    /// The spec with the password of its url hidden, as served back
+    pub fn redacted(&self) -> Self {
+        Self {
+            url: self.display_url(),
+            ..self.clone()
+        }
+    }
+
+    fn max_rows(&self) -> usize {
+        self.max_rows.unwrap_or(DEFAULT_MAX_ROWS)
+    }
+}
+
+/// How a remote column is read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    Boolean,
+    Int,
+    Float,
+    /// Read as nanoseconds since the unix epoch
+    Timestamp,
+    Text,
+}
+
+impl ColumnType {
+    /// Type of a column of `information_schema.columns`
+    pub fn from_postgres(name: &str) -> Self {
+        let name = name.to_lowercase();
+        match name.as_str() {
+            "boolean" => ColumnType::Boolean,
+            "smallint" | "integer" | "bigint" => ColumnType::Int,
+            "real" | "double precision" | "numeric" => ColumnType::Float,
+            _ if name.starts_with("timestamp") || name == "date" => ColumnType::Timestamp,
+            _ => ColumnType::Text,
+        }
+    }
+
+    /// Type of a column as printed by ClickHouse's `DESCRIBE TABLE`
+    pub fn from_clickhouse(name: &str) -> Self {
+        let mut name = name.trim();
+        for wrapper in ["LowCardinality(", "Nullable("] {
+            if let Some(inner) = name.strip_prefix(wrapper) {
+                name = inner.strip_suffix(')').unwrap_or(inner);
+            }
+        }
+        match name {
+            "Bool" => ColumnType::Boolean,
+            _ if name.starts_with("Int") || name.starts_with("UInt") => ColumnType::Int,
+            _ if name.starts_with("Float") || name.starts_with("Decimal") => ColumnType::Float,
+            _ if name.starts_with("DateTime") || name.starts_with("Date") => ColumnType::Timestamp,
+            _ => ColumnType::Text,
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::Int | ColumnType::Timestamp => DataType::Int64,
+            ColumnType::Float => DataType::Float64,
+            ColumnType::Text => DataType::Utf8,
+        }
+    }
+
+    /// Whether a comparison with `key` can be run by the database
+    fn accepts(&self, key: &Key) -> bool {
+        match (self, key) {
+            (ColumnType::Int | ColumnType::Float | ColumnType::Timestamp, Key::Int(_)) => true,
+            (ColumnType::Int | ColumnType::Float, Key::Float(value)) => value.is_finite(),
+            (ColumnType::Text, Key::Text(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A column of a remote table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteColumn {
+    pub name: String,
+    pub kind: ColumnType,
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn literal(key: &Key) -> String {
+    match key {
+        Key::Int(value) => value.to_string(),
+        Key::Float(value) => format!("{value:?}"),
+        Key::Text(value) => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+/// A remote table served as `remote.<name>`
+#[derive(Debug)]
+pub struct RemoteTable {
+    pub spec: RemoteTableSpec,
+    columns: Vec<RemoteColumn>,
+    schema: SchemaRef,
+}
+
+impl RemoteTable {
+    /// Serve `spec` with the columns the database reports for it
+    pub async fn connect(spec: RemoteTableSpec) -> Result<Self> {
+        spec.validate().map_err(DataFusionError::Plan)?;
+        let columns = match spec.kind {
+            #[cfg(feature = "postgres")]
+            RemoteKind::Postgres => postgres::columns(&spec).await?,
+            #[cfg(feature = "clickhouse")]
+            RemoteKind::Clickhouse => clickhouse::columns(&spec).await?,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("validated"),
+        };
+        if columns.is_empty() {
+            return Err(DataFusionError::Plan(format!(
+                "no table {} at {}",
+                spec.table,
+                spec.display_url()
+            )));
+        }
+        Ok(Self::new(spec, columns))
+    }
+
+    pub fn new(spec: RemoteTableSpec, columns: Vec<RemoteColumn>) -> Self {
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|column| Field::new(&column.name, column.kind.data_type(), true))
+                .collect::<Vec<_>>(),
+        ));
+        Self {
+            spec,
+            columns,
+            schema,
+        }
+    }
+
+    /// Expression reading `column`, in the dialect of the database
+    fn expr(&self, column: &RemoteColumn) -> String {
+        let ident = quote_ident(&column.name);
+        match (column.kind, self.spec.kind) {
+            (ColumnType::Timestamp, RemoteKind::Postgres) => {
+                format!("CAST(EXTRACT(EPOCH FROM {ident}) * 1000000000 AS BIGINT)")
+            }
+            (ColumnType::Timestamp, RemoteKind::Clickhouse) => {
+                format!("toUnixTimestamp64Nano(toDateTime64({ident}, 9))")
+            }
+            _ => ident,
+        }
+    }
+
+    fn condition(&self, predicate: &Predicate) -> Option<String> {
+        let column = self.columns.iter().find(|c| c.name == predicate.column)?;
+        let keys = match &predicate.condition {
+            Condition::Eq(key)
+            | Condition::NotEq(key)
+            | Condition::Lt(key)
+            | Condition::LtEq(key)
+            | Condition::Gt(key)
+            | Condition::GtEq(key) => vec![key],
+            Condition::In(keys) => keys.iter().collect(),
+        };
+        if keys.is_empty() || !keys.iter().all(|key| column.kind.accepts(key)) {
+            return None;
+        }
+        let expr = self.expr(column);
+        Some(match &predicate.condition {
+            Condition::Eq(key) => format!("{expr} = {}", literal(key)),
+            Condition::NotEq(key) => format!("{expr} <> {}", literal(key)),
+            Condition::Lt(key) => format!("{expr} < {}", literal(key)),
+            Condition::LtEq(key) => format!("{expr} <= {}", literal(key)),
+            Condition::Gt(key) => format!("{expr} > {}", literal(key)),
+            Condition::GtEq(key) => format!("{expr} >= {}", literal(key)),
+            Condition::In(keys) => format!(
+                "{expr} IN ({})",
+                keys.iter().map(literal).collect::<Vec<_>>().join(", ")
+            ),
+        })
+    }
+
+    /// Query reading the columns `fetched` of the rows matching `filters`
+    pub fn select(&self, fetched: &[usize], filters: &[Expr], limit: Option<usize>) -> String {
+        let columns = fetched
+            .iter()
+            .map(|idx| {
+                let column = &self.columns[*idx];
+                format!("{} AS {}", self.expr(column), quote_ident(&column.name))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let table = self
+            .spec
+            .table
+            .split('.')
+            .map(quote_ident)
+            .collect::<Vec<_>>()
+            .join(".");
+        let mut sql = format!("SELECT {columns} FROM {table}");
+        let conditions = filters
+            .iter()
+            .flat_map(Predicate::parse)
+            .filter_map(|predicate| self.condition(&predicate))
+            .collect::<Vec<_>>();
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        let limit = limit.unwrap_or(usize::MAX).min(self.spec.max_rows());
+        sql.push_str(&format!(" LIMIT {limit}"));
+        sql
+    }
+
+    async fn fetch(&self, sql: &str) -> Result<Vec<Vec<Option<String>>>> {
+        log::debug!("{}.{}: {sql}", NAMESPACE, self.spec.name);
+        match self.spec.kind {
+            #[cfg(feature = "postgres")]
+            RemoteKind::Postgres => postgres::fetch(&self.spec, sql).await,
+            #[cfg(feature = "clickhouse")]
+            RemoteKind::Clickhouse => clickhouse::fetch(&self.spec, sql).await,
+            #[allow(unreachable_patterns)]
+            kind => Err(DataFusionError::Plan(format!(
+                "probing was built without the {} connector",
+                kind.as_str()
+            ))),
+        }
+    }
+}
+
+/// Build a batch of the columns `fetched` from rows of text values
+///
+/// Values that do not parse as the type of their column are read as nulls.
+pub fn to_batch(
+    schema: SchemaRef,
+    columns: &[&RemoteColumn],
+    rows: &[Vec<Option<String>>],
+) -> Result<RecordBatch> {
+    let values = |idx: usize| rows.iter().map(move |row| row.get(idx).cloned().flatten());
+    let arrays = columns
+        .iter()
+        .enumerate()
+        .map(|(idx, column)| -> ArrayRef {
+            match column.kind {
+                ColumnType::Boolean => {
+                    let mut builder = BooleanBuilder::with_capacity(rows.len());
+                    for value in values(idx) {
+                        builder.append_option(value.and_then(
+                            |v| match v.to_lowercase().as_str() {
+                                "t" | "true" | "1" => Some(true),
+                                "f" | "false" | "0" => Some(false),
+                                _ => None,
+                            },
+                        ));
+                    }
+                    Arc::new(builder.finish())
+                }
+                ColumnType::Int | ColumnType::Timestamp => {
+                    let mut builder = Int64Builder::with_capacity(rows.len());
+                    for value in values(idx) {
+                        builder.append_option(value.and_then(|v| v.trim().parse().ok()));
+                    }
+                    Arc::new(builder.finish())
+                }
+                ColumnType::Float => {
+                    let mut builder = Float64Builder::with_capacity(rows.len());
+                    for value in values(idx) {
+                        builder.append_option(value.and_then(|v| v.trim().parse().ok()));
+                    }
+                    Arc::new(builder.finish())
+                }
+                ColumnType::Text => {
+                    let mut builder = StringBuilder::new();
+                    for value in values(idx) {
+                        builder.append_option(value);
+                    }
+                    Arc::new(builder.finish())
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(RecordBatch::try_new(schema, arrays)?)
+}
+
+#[async_trait]
+impl TableProvider for RemoteTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(Predicate::support(filters))
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        // a column is read even for `count(*)`, the rows are what counts
+        let fetched = match projection {
+            Some(projection) if projection.is_empty() => vec![0],
+            Some(projection) => projection.clone(),
+            None => (0..self.columns.len()).collect(),
+        };
+        let sql = self.select(&fetched, filters, limit);
+        let rows = self.fetch(&sql).await?;
+
+        let schema = Arc::new(self.schema.project(&fetched)?);
+        let columns = fetched
+            .iter()
+            .map(|idx| &self.columns[*idx])
+            .collect::<Vec<_>>();
+        let batch = to_batch(schema.clone(), &columns, &rows)?;
+        let projection = projection.filter(|p| p.is_empty()).cloned();
+        let source = MemorySourceConfig::try_new(&[vec![batch]], schema, projection)?;
+        Ok(Arc::new(DataSourceExec::new(Arc::new(source))))
+    }
+}
+
+impl Engine {
+    /// Connect to the table of `spec` and serve it as `remote.<name>`,
+    /// replacing the remote table of the same name
+    pub async fn register_remote_table(&self, spec: RemoteTableSpec) -> Result<()> {
+        let table = Arc::new(RemoteTable::connect(spec).await?);
+        let catalog = self
+            .context
+            .catalog("probe")
+            .ok_or_else(|| DataFusionError::Internal("no catalog `probe`".to_string()))?;
+        if catalog.schema(NAMESPACE).is_none() {
+            catalog.register_schema(NAMESPACE, Arc::new(MemorySchemaProvider::new()))?;
+        }
+        let name = format!("{NAMESPACE}.{}", table.spec.name);
+        let table_name = format!("probe.{name}");
+        if self.context.table_exist(table_name.as_str())? {
+            self.context.deregister_table(table_name.as_str())?;
+        }
+        self.context.register_table(table_name.as_str(), table)?;
+        crate::catalog::table_registered("federation", &name);
+        Ok(())
+    }
+
+    /// Stop serving `remote.<name>`, returning whether it was served
+    pub fn drop_remote_table(&self, name: &str) -> Result<bool> {
+        let table_name = format!("probe.{NAMESPACE}.{name}");
+        if !self.context.table_exist(table_name.as_str())? {
+            return Ok(false);
+        }
+        self.context.deregister_table(table_name.as_str())?;
+        Ok(true)
+    }
+
+    /// Names of the remote tables served
+    pub fn remote_tables(&self) -> Vec<String> {
+        self.context
+            .catalog("probe")
+            .and_then(|catalog| catalog.schema(NAMESPACE))
+            .map(|schema| schema.table_names())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use once_cell::sync::Lazy;
+    use tokio_postgres::SimpleQueryMessage;
+    use tokio_postgres_rustls::MakeRustlsConnect;
+
+    use super::*;
+
+    /// TLS client trusting the Mozilla root certificates, used as `sslmode`
+    /// asks
+    static TLS: Lazy<std::result::Result<MakeRustlsConnect, String>> = Lazy::new(|| {
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(MakeRustlsConnect::new(config))
+    });
+
+    fn error(spec: &RemoteTableSpec, e: impl std::fmt::Display) -> DataFusionError {
+        DataFusionError::External(format!("{}: {e}", spec.display_url()).into())
+    }
+
+    async fn connect(spec: &RemoteTableSpec) -> Result<tokio_postgres::Client> {
+        let mut config: tokio_postgres::Config = spec.url.parse().map_err(|e| error(spec, e))?;
+        if let Some(password) = spec.password() {
+            config.password(password);
+        }
+        // every transaction of the session is read-only
+        config.options("-c default_transaction_read_only=on");
+        let tls = TLS.clone().map_err(|e| error(spec, e))?;
+        let (client, connection) = config.connect(tls).await.map_err(|e| error(spec, e))?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log::warn!("remote connection failed: {e}");
+            }
+        });
+        Ok(client)
+    }
+
+    pub async fn columns(spec: &RemoteTableSpec) -> Result<Vec<RemoteColumn>> {
+        let client = connect(spec).await?;
+        let (schema, table) = spec
+            .table
+            .split_once('.')
+            .unwrap_or(("public", spec.table.as_str()));
+        let rows = client
+            .query(
+                "SELECT column_name::text, data_type::text FROM information_schema.columns \
+                 WHERE table_schema = $1 AND table_name = $2 ORDER BY ordinal_position",
+                &[&schema, &table],
+            )
+            .await
+            .map_err(|e| error(spec, e))?;
+        Ok(rows
+            .iter()
+            .map(|row| RemoteColumn {
+                name: row.get(0),
+                kind: ColumnType::from_postgres(row.get(1)),
+            })
+            .collect())
+    }
+
+    pub async fn fetch(spec: &RemoteTableSpec, sql: &str) -> Result<Vec<Vec<Option<String>>>> {
+        let client = connect(spec).await?;
+        let messages = client.simple_query(sql).await.map_err(|e| error(spec, e))?;
+        Ok(messages
+            .iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => Some(
+                    (0..row.len())
+                        .map(|idx| row.get(idx).map(str::to_string))
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "clickhouse")]
+mod clickhouse {
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Run `sql` through the HTTP interface, blocking
+    fn query(spec: &RemoteTableSpec, sql: &str) -> std::result::Result<String, String> {
+        let url = url::Url::parse(&spec.url).map_err(|e| e.to_string())?;
+        let mut endpoint = url.clone();
+        let _ = endpoint.set_username("");
+        let _ = endpoint.set_password(None);
+        endpoint.set_path("/");
+        let mut request = ureq::post(endpoint.as_str())
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .query("readonly", "1");
+        let database = url.path().trim_matches('/');
+        if !database.is_empty() {
+            request = request.query("database", database);
+        }
+        if !url.username().is_empty() {
+            request = request.header("X-ClickHouse-User", url.username());
+        }
+        if let Some(password) = spec.password() {
+            request = request.header("X-ClickHouse-Key", &password);
+        }
+        let mut response = request.send(sql).map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body = response
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(format!("{status}: {}", body.trim()));
+        }
+        Ok(body)
+    }
+
+    async fn run(spec: &RemoteTableSpec, sql: String) -> Result<String> {
+        let spec = spec.clone();
+        tokio::task::spawn_blocking(move || {
+            query(&spec, &sql).map_err(|e| {
+                DataFusionError::External(format!("{}: {e}", spec.display_url()).into())
+            })
+        })
+        .await
+        .map_err(|e| DataFusionError::External(Box::new(e)))?
+    }
+
+    pub async fn columns(spec: &RemoteTableSpec) -> Result<Vec<RemoteColumn>> {
+        let table = spec
+            .table
+            .split('.')
+            .map(quote_ident)
+            .collect::<Vec<_>>()
+            .join(".");
+        let output = run(spec, format!("DESCRIBE TABLE {table} FORMAT JSONEachRow")).await?;
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let column: Value = serde_json::from_str(line)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                Ok(RemoteColumn {
+                    name: column["name"].as_str().unwrap_or_default().to_string(),
+                    kind: ColumnType::from_clickhouse(column["type"].as_str().unwrap_or_default()),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn fetch(spec: &RemoteTableSpec, sql: &str) -> Result<Vec<Vec<Option<String>>>> {
+        let output = run(spec, format!("{sql} FORMAT JSONCompactEachRow")).await?;
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let Value::Array(values) = serde_json::from_str(line)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?
+                else {
+                    return Err(DataFusionError::Execution(format!("unexpected row {line}")));
+                };
+                Ok(values
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::Null => None,
+                        Value::String(text) => Some(text),
+                        value => Some(value.to_string()),
+                    })
+                    .collect())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::common::ScalarValue;
+    use datafusion::logical_expr::{col, lit};
+
+    use super::*;
+
+    fn spec(kind: RemoteKind) -> RemoteTableSpec {
+        RemoteTableSpec {
+            name: "runs".to_string(),
+            kind,
+            url: "postgres://probe@db:5432/experiments".to_string(),
+            table: "public.runs".to_string(),
+            password_env: Some("PROBING_TEST_RUNS_PASSWORD".to_string()),
+            max_rows: Some(1000),
+        }
+    }
+
+    fn runs(kind: RemoteKind) -> RemoteTable {
+        RemoteTable::new(
+            spec(kind),
+            vec![
+                RemoteColumn {
+                    name: "run_id".to_string(),
+                    kind: ColumnType::Text,
+                },
+                RemoteColumn {
+                    name: "lr".to_string(),
+                    kind: ColumnType::Float,
+                },
+                RemoteColumn {
+                    name: "started".to_string(),
+                    kind: ColumnType::Timestamp,
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn test_column_types() {
+        assert_eq!(ColumnType::from_postgres("bigint"), ColumnType::Int);
+        assert_eq!(
+            ColumnType::from_postgres("timestamp with time zone"),
+            ColumnType::Timestamp
+        );
+        assert_eq!(ColumnType::from_postgres("jsonb"), ColumnType::Text);
+        assert_eq!(
+            ColumnType::from_clickhouse("Nullable(UInt64)"),
+            ColumnType::Int
+        );
+        assert_eq!(
+            ColumnType::from_clickhouse("LowCardinality(String)"),
+            ColumnType::Text
+        );
+        assert_eq!(
+            ColumnType::from_clickhouse("DateTime64(3, 'UTC')"),
+            ColumnType::Timestamp
+        );
+    }
+
+    #[test]
+    fn test_select_pushes_down() {
+        let table = runs(RemoteKind::Postgres);
+        let filters = vec![
+            col("lr").gt(lit(0.001)),
+            col("run_id").in_list(vec![lit("a"), lit("b'c")], false),
+            // compared in the database as nanoseconds
+            col("started").gt_eq(lit(ScalarValue::TimestampSecond(Some(1), None))),
+            // not comparable there, left to DataFusion
+            col("run_id").eq(lit(1)),
+        ];
+        assert_eq!(
+            table.select(&[0, 2], &filters, Some(10)),
+            "SELECT \"run_id\" AS \"run_id\", \
+             CAST(EXTRACT(EPOCH FROM \"started\") * 1000000000 AS BIGINT) AS \"started\" \
+             FROM \"public\".\"runs\" \
+             WHERE \"lr\" > 0.001 AND \"run_id\" IN ('a', 'b''c') AND \
+             CAST(EXTRACT(EPOCH FROM \"started\") * 1000000000 AS BIGINT) >= 1000000000 \
+             LIMIT 10"
+        );
+        // never more than max_rows
+        assert!(table.select(&[0], &[], None).ends_with("LIMIT 1000"));
+
+        let table = runs(RemoteKind::Clickhouse);
+        assert!(table
+            .select(&[2], &[], None)
+            .starts_with("SELECT toUnixTimestamp64Nano(toDateTime64(\"started\", 9))"));
+    }
+
+    #[test]
+    fn test_to_batch() {
+        let table = runs(RemoteKind::Postgres);
+        let columns = table.columns.iter().collect::<Vec<_>>();
+        let rows = vec![
+            vec![Some("a".into()), Some("0.1".into()), Some("5".into())],
+            vec![None, Some("n/a".into()), None],
+        ];
+        let batch = to_batch(table.schema(), &columns, &rows).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(1).null_count(), 1);
+        assert_eq!(batch.column(2).data_type(), &DataType::Int64);
+    }
+
+    #[test]
+    fn test_spec() {
+        let mut runs = spec(RemoteKind::Postgres);
+        std::env::set_var("PROBING_TEST_RUNS_PASSWORD", "secret");
+        assert_eq!(runs.password().as_deref(), Some("secret"));
+        if RemoteKind::Postgres.is_available() {
+            assert!(runs.validate().is_ok());
+            // passwords are only read from the environment
+            for url in [
+                "postgres://probe:secret@db:5432/experiments",
+                "postgres://probe@db:5432/experiments?password=secret",
+            ] {
+                let with_password = RemoteTableSpec {
+                    url: url.to_string(),
+                    ..runs.clone()
+                };
+                assert!(with_password.validate().is_err());
+            }
+        }
+        let leaked = RemoteTableSpec {
+            url: "postgres://probe:secret@db:5432/experiments".to_string(),
+            ..runs.clone()
+        };
+        assert_eq!(
+            leaked.redacted().url,
+            "postgres://probe:***@db:5432/experiments"
+        );
+        runs.table = "runs; DROP TABLE runs".to_string();
+        assert!(runs.validate().is_err());
+        runs.table = "runs".to_string();
+        runs.name = "a.b".to_string();
+        assert!(runs.validate().is_err());
+        runs.name = "runs".to_string();
+        runs.url = "http://db:8123".to_string();
+        assert!(runs.validate().is_err());
+    }
+}
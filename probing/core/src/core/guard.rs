@@ -0,0 +1,327 @@
+//! Guard against accidental heavy queries.
+//!
+//! Queries run inside the probed process, so a join of two large tables
+//! without a predicate, or a read of every row of a table holding millions
+//! of them, takes memory and CPU from the job being observed. Before a query
+//! runs, its optimized plan is checked for:
+//!
+//! - joins without any join predicate whose sides multiply to more than
+//!   [`max_rows`] rows
+//! - reads of all rows of a table holding more than [`max_rows`] rows, with
+//!   no `LIMIT`, filter or aggregation bounding the result
+//!
+//! Only tables reporting their row count, such as the Python tables, are
+//! weighed. Depending on [`mode`] a flagged query is refused, logged or run;
+//! the `force` query option runs a refused query anyway.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::datasource::source_as_provider;
+use datafusion::logical_expr::{FetchType, LogicalPlan, LogicalPlanBuilder, TableScan};
+use probing_proto::prelude::{ErrorCode, QueryError};
+
+use super::Engine;
+
+/// Default of `server.query_guard_rows`
+pub const DEFAULT_MAX_ROWS: usize = 1_000_000;
+
+/// What happens to a query the guard flags
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GuardMode {
+    /// Queries are not checked
+    Off,
+    /// Flagged queries run and are logged
+    Warn,
+    /// Flagged queries are refused unless forced
+    #[default]
+    Block,
+}
+
+impl GuardMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GuardMode::Off => "off",
+            GuardMode::Warn => "warn",
+            GuardMode::Block => "block",
+        }
+    }
+}
+
+impl FromStr for GuardMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" => Ok(GuardMode::Off),
+            "warn" => Ok(GuardMode::Warn),
+            "block" => Ok(GuardMode::Block),
+            other => Err(format!("unknown query guard mode `{other}`")),
+        }
+    }
+}
+
+static MODE: AtomicU8 = AtomicU8::new(GuardMode::Block as u8);
+
+static MAX_ROWS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_ROWS);
+
+pub fn mode() -> GuardMode {
+    match MODE.load(Ordering::Relaxed) {
+        m if m == GuardMode::Off as u8 => GuardMode::Off,
+        m if m == GuardMode::Warn as u8 => GuardMode::Warn,
+        _ => GuardMode::Block,
+    }
+}
+
+pub fn set_mode(mode: GuardMode) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn max_rows() -> usize {
+    MAX_ROWS.load(Ordering::Relaxed)
+}
+
+/// Flag queries reading more than `rows` rows
+pub fn set_max_rows(rows: usize) {
+    MAX_ROWS.store(rows, Ordering::Relaxed);
+}
+
+/// Why a query is heavy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// A join without predicate of sides holding `left` and `right` rows
+    CrossJoin { left: usize, right: usize },
+    /// A read of all `rows` rows of `table`
+    UnboundedScan { table: String, rows: usize },
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::CrossJoin { left, right } => {
+                write!(f, "join without predicate of {left} x {right} rows")
+            }
+            Finding::UnboundedScan { table, rows } => {
+                write!(f, "reads all {rows} rows of {table}")
+            }
+        }
+    }
+}
+
+/// Heavy operations of an optimized `plan`
+pub fn inspect(plan: &LogicalPlan, max_rows: usize) -> Vec<Finding> {
+    let mut findings = vec![];
+    let _ = plan.apply(|node| {
+        if let LogicalPlan::Join(join) = node {
+            if join.on.is_empty() && join.filter.is_none() {
+                let (left, right) = (rows(&join.left), rows(&join.right));
+                if left.saturating_mul(right) > max_rows {
+                    findings.push(Finding::CrossJoin { left, right });
+                }
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    });
+    unbounded_scans(plan, max_rows, &mut findings);
+    findings
+}
+
+/// Scans whose rows all reach the result of `plan`
+fn unbounded_scans(plan: &LogicalPlan, max_rows: usize, findings: &mut Vec<Finding>) {
+    match plan {
+        LogicalPlan::Limit(_)
+        | LogicalPlan::Filter(_)
+        | LogicalPlan::Aggregate(_)
+        | LogicalPlan::Join(_) => {}
+        LogicalPlan::TableScan(scan) => {
+            let rows = scan_rows(scan);
+            if scan.fetch.is_none() && scan.filters.is_empty() && rows > max_rows {
+                findings.push(Finding::UnboundedScan {
+                    table: scan.table_name.to_string(),
+                    rows,
+                });
+            }
+        }
+        plan => plan
+            .inputs()
+            .into_iter()
+            .for_each(|input| unbounded_scans(input, max_rows, findings)),
+    }
+}
+
+/// Upper estimate of the rows of `plan`, tables of unknown size count as empty
+fn rows(plan: &LogicalPlan) -> usize {
+    match plan {
+        LogicalPlan::TableScan(scan) => scan_rows(scan),
+        LogicalPlan::Limit(limit) => match limit.get_fetch_type() {
+            Ok(FetchType::Literal(Some(fetch))) => fetch.min(rows(&limit.input)),
+            _ => rows(&limit.input),
+        },
+        LogicalPlan::Join(join) if join.on.is_empty() && join.filter.is_none() => {
+            rows(&join.left).saturating_mul(rows(&join.right))
+        }
+        LogicalPlan::Union(union) => union
+            .inputs
+            .iter()
+            .fold(0, |total, input| total.saturating_add(rows(input))),
+        plan => plan.inputs().into_iter().map(rows).max().unwrap_or(0),
+    }
+}
+
+fn scan_rows(scan: &TableScan) -> usize {
+    let rows = source_as_provider(&scan.source)
+        .ok()
+        .and_then(|provider| provider.statistics())
+        .and_then(|stats| stats.num_rows.get_value().copied())
+        .unwrap_or(0);
+    scan.fetch.map_or(rows, |fetch| fetch.min(rows))
+}
+
+impl Engine {
+    /// Heavy operations of `query`, see [`inspect`]. `limit` is the row limit
+    /// of the query options; queries that fail to plan are left to fail when
+    /// they run.
+    pub async fn inspect_query(&self, query: &str, limit: Option<usize>) -> Vec<Finding> {
+        if self.refresh_views(query).await.is_err() {
+            return vec![];
+        }
+        let state = self.context.state();
+        let Ok(plan) = state.create_logical_plan(query).await else {
+            return vec![];
+        };
+        let plan = match limit {
+            Some(limit) => LogicalPlanBuilder::from(plan)
+                .limit(0, Some(limit))
+                .and_then(|builder| builder.build()),
+            None => Ok(plan),
+        };
+        match plan.and_then(|plan| state.optimize(&plan)) {
+            Ok(plan) => inspect(&plan, max_rows()),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Refuse `query` if the guard flags it and [`mode`] is `block`, log it
+    /// if the mode is `warn`
+    pub async fn guard_query(&self, query: &str, limit: Option<usize>) -> Result<(), QueryError> {
+        let mode = mode();
+        if mode == GuardMode::Off {
+            return Ok(());
+        }
+        let findings = self.inspect_query(query, limit).await;
+        if findings.is_empty() {
+            return Ok(());
+        }
+        let reasons = findings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        if mode == GuardMode::Warn {
+            log::warn!("Heavy query ({reasons}): {query}");
+            return Ok(());
+        }
+        Err(QueryError::new(
+            ErrorCode::ResourceExhausted,
+            format!("query refused by the query guard: {reasons}"),
+        )
+        .with_hint("add a LIMIT or a WHERE clause, or run it with the `force` option"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int64Array, RecordBatch};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+
+    use super::*;
+    use crate::core::LazyTableSource;
+
+    async fn engine() -> Engine {
+        let engine = Engine::default();
+        for name in ["a", "b"] {
+            let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, false)]));
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+            )
+            .unwrap();
+            let table = LazyTableSource {
+                name: name.to_string(),
+                schema: Some(schema),
+                data: vec![batch],
+            };
+            engine
+                .context
+                .register_table(name, Arc::new(table))
+                .unwrap();
+        }
+        engine
+    }
+
+    async fn findings(engine: &Engine, query: &str, limit: Option<usize>) -> Vec<Finding> {
+        let state = engine.context.state();
+        let plan = state.create_logical_plan(query).await.unwrap();
+        let plan = match limit {
+            Some(limit) => LogicalPlanBuilder::from(plan)
+                .limit(0, Some(limit))
+                .unwrap()
+                .build()
+                .unwrap(),
+            None => plan,
+        };
+        inspect(&state.optimize(&plan).unwrap(), 2)
+    }
+
+    #[tokio::test]
+    async fn test_unbounded_scans() {
+        let engine = engine().await;
+        assert_eq!(
+            findings(&engine, "SELECT * FROM a", None).await,
+            vec![Finding::UnboundedScan {
+                table: "a".to_string(),
+                rows: 3
+            }]
+        );
+        for query in [
+            "SELECT * FROM a LIMIT 2",
+            "SELECT * FROM a WHERE x > 1",
+            "SELECT count(*) FROM a",
+        ] {
+            assert!(findings(&engine, query, None).await.is_empty(), "{query}");
+        }
+        assert!(findings(&engine, "SELECT * FROM a", Some(2))
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cross_joins() {
+        let engine = engine().await;
+        let flagged = findings(&engine, "SELECT count(*) FROM a, b", None).await;
+        assert_eq!(flagged, vec![Finding::CrossJoin { left: 3, right: 3 }]);
+
+        let joined = "SELECT count(*) FROM a JOIN b ON a.x = b.x";
+        assert!(findings(&engine, joined, None).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_guard_query() {
+        let engine = engine().await;
+        assert_eq!(mode(), GuardMode::Block);
+        // no other test weighs queries against the global threshold
+        set_max_rows(2);
+        let refused = engine.guard_query("SELECT * FROM a", None).await;
+        let allowed = engine.guard_query("SELECT * FROM a", Some(1)).await;
+        set_max_rows(DEFAULT_MAX_ROWS);
+
+        assert_eq!(refused.unwrap_err().code, ErrorCode::ResourceExhausted);
+        assert!(allowed.is_ok());
+        assert_eq!("WARN".parse::<GuardMode>(), Ok(GuardMode::Warn));
+        assert!("sometimes".parse::<GuardMode>().is_err());
+    }
+}
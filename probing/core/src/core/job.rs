@@ -0,0 +1,114 @@
+//! Identity of the training job a process belongs to.
+//!
+//! Concurrent runs sharing hosts are told apart by a job id, reported with
+//! every node and available to queries as `job_id()`:
+//!
+//! ```sql
+//! SELECT job_id() AS job_id, name, count(*) FROM python.trace_event GROUP BY name
+//! ```
+//!
+//! The id is `PROBING_JOB_ID` when set, otherwise it is derived from the
+//! launcher environment, see [`resolve`].
+
+use std::sync::{Arc, LazyLock};
+
+use arrow::datatypes::DataType;
+use datafusion::error::Result;
+use datafusion::logical_expr::{ColumnarValue, ScalarUDF, Signature, SimpleScalarUDF, Volatility};
+use datafusion::scalar::ScalarValue;
+
+/// Variable setting the job id explicitly
+pub const JOB_ID_ENV: &str = "PROBING_JOB_ID";
+
+static JOB_ID: LazyLock<Option<String>> =
+    LazyLock::new(|| resolve(|name| std::env::var(name).ok()));
+
+/// Job id of this process, `None` outside of a known launcher
+pub fn job_id() -> Option<String> {
+    JOB_ID.clone()
+}
+
+/// Job id from the variables returned by `env`, in order of preference:
+///
+/// 1. `PROBING_JOB_ID`
+/// 2. `TORCHELASTIC_RUN_ID` set by torchrun, unless it is the default `none`
+/// 3. `SLURM_JOB_ID`
+/// 4. `MASTER_ADDR:MASTER_PORT`, shared by the ranks of a rendezvous
+pub fn resolve(env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let var = |name: &str| {
+        env(name)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    var(JOB_ID_ENV)
+        .or_else(|| var("TORCHELASTIC_RUN_ID").filter(|id| id != "none"))
+        .or_else(|| var("SLURM_JOB_ID"))
+        .or_else(|| Some(format!("{}:{}", var("MASTER_ADDR")?, var("MASTER_PORT")?)))
+}
+
+/// SQL function `job_id()` returning the job id of the process, `NULL` if
+/// it is unknown
+pub fn job_id_udf() -> ScalarUDF {
+    ScalarUDF::from(SimpleScalarUDF::new_with_signature(
+        "job_id",
+        Signature::exact(vec![], Volatility::Stable),
+        DataType::Utf8,
+        Arc::new(job_id_impl),
+    ))
+}
+
+fn job_id_impl(_args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    Ok(ColumnarValue::Scalar(ScalarValue::Utf8(job_id())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn test_resolve_job_id() {
+        let torchrun = [
+            ("TORCHELASTIC_RUN_ID", "run-42"),
+            ("SLURM_JOB_ID", "1234"),
+            ("MASTER_ADDR", "10.0.0.1"),
+            ("MASTER_PORT", "29500"),
+        ];
+        assert_eq!(resolve(env(&torchrun)).as_deref(), Some("run-42"));
+
+        let explicit = [(JOB_ID_ENV, " exp-7 "), ("TORCHELASTIC_RUN_ID", "run-42")];
+        assert_eq!(resolve(env(&explicit)).as_deref(), Some("exp-7"));
+
+        let slurm = [("TORCHELASTIC_RUN_ID", "none"), ("SLURM_JOB_ID", "1234")];
+        assert_eq!(resolve(env(&slurm)).as_deref(), Some("1234"));
+
+        let rendezvous = [("MASTER_ADDR", "10.0.0.1"), ("MASTER_PORT", "29500")];
+        assert_eq!(resolve(env(&rendezvous)).as_deref(), Some("10.0.0.1:29500"));
+
+        assert_eq!(resolve(env(&[("MASTER_ADDR", "10.0.0.1")])), None);
+        assert_eq!(resolve(env(&[(JOB_ID_ENV, "")])), None);
+    }
+
+    #[tokio::test]
+    async fn test_job_id_udf() {
+        let ctx = datafusion::prelude::SessionContext::new();
+        ctx.register_udf(job_id_udf());
+        let batches = ctx
+            .sql("SELECT job_id() AS job")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].num_rows(), 1);
+        assert_eq!(batches[0].schema().field(0).data_type(), &DataType::Utf8);
+    }
+}
@@ -0,0 +1,606 @@
+//! Table functions joining rows of two tables by time.
+//!
+//! Metrics such as GPU utilization are sampled at points in time while spans
+//! cover intervals, and matching them takes range joins that are tedious to
+//! write and run as nested loops. These functions sort once and binary
+//! search instead:
+//!
+//! - `asof_join(left, left_time, right, right_time [, by])` pairs every row
+//!   of `left` with the latest row of `right` at or before it, on the same
+//!   `by` value when given, or with nulls when there is none;
+//! - `span_window(spans, start, end, points, time)` pairs every span with
+//!   the rows of `points` within `[start, end)`; spans without an end are
+//!   still open and take every later point.
+//!
+//! Time columns are compared as integers, so nanosecond timestamps and
+//! `Timestamp` columns both work. Columns of the second table whose name is
+//! already taken are prefixed with its table name, e.g. `gpu_time`.
+//!
+//! Table functions are planned synchronously while table lookups are async,
+//! remote tables going over the network. [`sql`] looks the tables of the
+//! calls up ahead of planning; through a bare [`SessionContext`] only tables
+//! found without waiting can be joined.
+//!
+//! ```sql
+//! SELECT name, avg(util) FROM span_window('python.spans', 'start', 'end', 'nvidia.gpu', 'time')
+//! GROUP BY name
+//! ```
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, Int64Array, RecordBatch, StringArray, UInt32Array};
+use arrow::compute::{cast, concat_batches, take};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::catalog::{Session, TableFunctionImpl};
+use datafusion::common::{ScalarValue, TableReference};
+use datafusion::datasource::{MemTable, TableProvider, TableType};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::SessionState;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::{collect, ExecutionPlan};
+use datafusion::prelude::{DataFrame, SessionContext};
+use datafusion::sql::parser::Statement;
+use datafusion::sql::sqlparser::ast::{
+    Expr as SqlExpr, FunctionArg, FunctionArgExpr, TableFactor, Value, Visit, Visitor,
+};
+use futures::FutureExt;
+
+tokio::task_local! {
+    /// Tables of the join function calls of the query being planned
+    static TABLES: HashMap<String, Arc<dyn TableProvider>>;
+}
+
+/// Register `asof_join` and `span_window` on `context`
+pub fn register_join_functions(context: &SessionContext) {
+    // weak, the functions are owned by the session they look tables up in
+    let state = context.state_weak_ref();
+    let lookup: TableLookup = Arc::new(move |name: &str| {
+        if let Ok(Some(table)) = TABLES.try_with(|tables| tables.get(name).cloned()) {
+            return Ok(table);
+        }
+        let state = state
+            .upgrade()
+            .ok_or_else(|| DataFusionError::Internal("session is gone".to_string()))?;
+        let state = state.read();
+        find_table(&state, name).now_or_never().unwrap_or_else(|| {
+            Err(DataFusionError::Plan(format!(
+                "table `{name}` cannot be looked up while planning"
+            )))
+        })
+    });
+    context.register_udtf(
+        "asof_join",
+        Arc::new(JoinFunction {
+            kind: JoinKind::AsOf,
+            lookup: lookup.clone(),
+        }),
+    );
+    context.register_udtf(
+        "span_window",
+        Arc::new(JoinFunction {
+            kind: JoinKind::SpanWindow,
+            lookup,
+        }),
+    );
+}
+
+/// Plan `query` on `context` with the tables of its join function calls
+/// looked up ahead
+pub async fn sql(context: &SessionContext, query: &str) -> Result<DataFrame> {
+    let state = context.state();
+    let dialect = state.config().options().sql_parser.dialect.clone();
+    // leave reporting of malformed queries to the planner
+    let names = match state.sql_to_statement(query, &dialect) {
+        Ok(statement) => joined_tables(&statement),
+        Err(_) => vec![],
+    };
+    if names.is_empty() {
+        return context.sql(query).await;
+    }
+    let mut tables = HashMap::new();
+    for name in names {
+        let table = find_table(&state, &name).await?;
+        tables.insert(name, table);
+    }
+    TABLES.scope(tables, context.sql(query)).await
+}
+
+/// Look a table up the way a `FROM` clause would
+async fn find_table(state: &SessionState, name: &str) -> Result<Arc<dyn TableProvider>> {
+    let reference = TableReference::from(name);
+    let schema = state.schema_for_ref(reference.clone())?;
+    schema
+        .table(reference.table())
+        .await?
+        .ok_or_else(|| DataFusionError::Plan(format!("table `{name}` not found")))
+}
+
+/// Names of the tables passed to the join functions called in `statement`
+fn joined_tables(statement: &Statement) -> Vec<String> {
+    struct Calls(Vec<String>);
+
+    impl Visitor for Calls {
+        type Break = ();
+
+        fn pre_visit_table_factor(&mut self, factor: &TableFactor) -> ControlFlow<()> {
+            let TableFactor::Table {
+                name,
+                args: Some(args),
+                ..
+            } = factor
+            else {
+                return ControlFlow::Continue(());
+            };
+            let tables: &[usize] = match name.to_string().to_lowercase().as_str() {
+                "asof_join" => &[0, 2],
+                "span_window" => &[0, 3],
+                _ => &[],
+            };
+            for &i in tables {
+                if let Some(FunctionArg::Unnamed(FunctionArgExpr::Expr(SqlExpr::Value(value)))) =
+                    args.args.get(i)
+                {
+                    if let Value::SingleQuotedString(table) = &value.value {
+                        self.0.push(table.clone());
+                    }
+                }
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    let Statement::Statement(statement) = statement else {
+        return vec![];
+    };
+    let mut calls = Calls(vec![]);
+    let _ = statement.visit(&mut calls);
+    calls.0
+}
+
+/// Table lookup of the join functions
+type TableLookup = Arc<dyn Fn(&str) -> Result<Arc<dyn TableProvider>> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JoinKind {
+    AsOf,
+    SpanWindow,
+}
+
+struct JoinFunction {
+    kind: JoinKind,
+    lookup: TableLookup,
+}
+
+impl std::fmt::Debug for JoinFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JoinFunction")
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl JoinFunction {
+    fn usage(&self) -> &'static str {
+        match self.kind {
+            JoinKind::AsOf => "asof_join(left, left_time, right, right_time [, by])",
+            JoinKind::SpanWindow => "span_window(spans, start, end, points, time)",
+        }
+    }
+}
+
+impl TableFunctionImpl for JoinFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                Expr::Literal(
+                    ScalarValue::Utf8(Some(s))
+                    | ScalarValue::LargeUtf8(Some(s))
+                    | ScalarValue::Utf8View(Some(s)),
+                ) => Ok(s.clone()),
+                _ => Err(DataFusionError::Plan(format!(
+                    "{} takes string literals, got {arg}",
+                    self.usage()
+                ))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let (first, first_cols, second, second_cols, by) = match (self.kind, args.as_slice()) {
+            (JoinKind::AsOf, [left, left_time, right, right_time]) => {
+                (left, vec![left_time], right, vec![right_time], None)
+            }
+            (JoinKind::AsOf, [left, left_time, right, right_time, by]) => (
+                left,
+                vec![left_time],
+                right,
+                vec![right_time],
+                Some(by.clone()),
+            ),
+            (JoinKind::SpanWindow, [spans, start, end, points, time]) => {
+                (spans, vec![start, end], points, vec![time], None)
+            }
+            _ => return Err(DataFusionError::Plan(format!("usage: {}", self.usage()))),
+        };
+
+        let first_table = (self.lookup)(first)?;
+        let second_table = (self.lookup)(second)?;
+        let mut first_cols: Vec<String> = first_cols.into_iter().cloned().collect();
+        let mut second_cols: Vec<String> = second_cols.into_iter().cloned().collect();
+        if let Some(by) = &by {
+            first_cols.push(by.clone());
+            second_cols.push(by.clone());
+        }
+        for (table, name, columns) in [
+            (&first_table, first, &first_cols),
+            (&second_table, second, &second_cols),
+        ] {
+            for column in columns {
+                table.schema().index_of(column).map_err(|_| {
+                    DataFusionError::Plan(format!("table `{name}` has no column `{column}`"))
+                })?;
+            }
+        }
+
+        let schema = joined_schema(
+            &first_table.schema(),
+            &second_table.schema(),
+            TableReference::from(second.as_str()).table(),
+            self.kind == JoinKind::AsOf,
+        );
+        Ok(Arc::new(JoinTable {
+            kind: self.kind,
+            schema,
+            first: first_table,
+            second: second_table,
+            first_cols,
+            second_cols,
+        }))
+    }
+}
+
+/// Fields of `first` then of `second`, renaming the clashing ones
+fn joined_schema(first: &Schema, second: &Schema, prefix: &str, nullable: bool) -> SchemaRef {
+    let mut fields: Vec<Field> = first.fields().iter().map(|f| f.as_ref().clone()).collect();
+    for field in second.fields() {
+        let mut field = field.as_ref().clone();
+        if first.field_with_name(field.name()).is_ok() {
+            let name = format!("{prefix}_{}", field.name());
+            field = field.with_name(name);
+        }
+        let nullable = field.is_nullable() || nullable;
+        fields.push(field.with_nullable(nullable));
+    }
+    Arc::new(Schema::new(fields))
+}
+
+/// Result of a join function, computed when scanned
+#[derive(Debug)]
+struct JoinTable {
+    kind: JoinKind,
+    schema: SchemaRef,
+    first: Arc<dyn TableProvider>,
+    second: Arc<dyn TableProvider>,
+    /// Time columns, then the `by` column
+    first_cols: Vec<String>,
+    second_cols: Vec<String>,
+}
+
+#[async_trait]
+impl TableProvider for JoinTable {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let first = read(&self.first, state).await?;
+        let second = read(&self.second, state).await?;
+        let column = |batch: &RecordBatch, name: &str| -> Result<ArrayRef> {
+            Ok(batch.column(batch.schema().index_of(name)?).clone())
+        };
+
+        let (first_rows, second_rows) = match self.kind {
+            JoinKind::AsOf => {
+                let by = match (self.first_cols.get(1), self.second_cols.get(1)) {
+                    (Some(first_by), Some(second_by)) => Some((
+                        keys(&column(&first, first_by)?)?,
+                        keys(&column(&second, second_by)?)?,
+                    )),
+                    _ => None,
+                };
+                asof_indices(
+                    &times(&column(&first, &self.first_cols[0])?)?,
+                    &times(&column(&second, &self.second_cols[0])?)?,
+                    by.as_ref().map(|(first, second)| (first, second)),
+                )
+            }
+            JoinKind::SpanWindow => window_indices(
+                &times(&column(&first, &self.first_cols[0])?)?,
+                &times(&column(&first, &self.first_cols[1])?)?,
+                &times(&column(&second, &self.second_cols[0])?)?,
+            ),
+        };
+
+        let mut columns = vec![];
+        for array in first.columns() {
+            columns.push(take(array, &first_rows, None)?);
+        }
+        for array in second.columns() {
+            columns.push(take(array, &second_rows, None)?);
+        }
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        MemTable::try_new(self.schema.clone(), vec![vec![batch]])?
+            .scan(state, projection, filters, limit)
+            .await
+    }
+}
+
+/// All rows of `table` in a single batch
+async fn read(table: &Arc<dyn TableProvider>, state: &dyn Session) -> Result<RecordBatch> {
+    let plan = table.scan(state, None, &[], None).await?;
+    let schema = plan.schema();
+    let batches = collect(plan, state.task_ctx()).await?;
+    Ok(concat_batches(&schema, &batches)?)
+}
+
+fn times(array: &ArrayRef) -> Result<Int64Array> {
+    cast_to::<Int64Array>(array, DataType::Int64)
+}
+
+fn keys(array: &ArrayRef) -> Result<StringArray> {
+    cast_to::<StringArray>(array, DataType::Utf8)
+}
+
+fn cast_to<T: Array + Clone + 'static>(array: &ArrayRef, to: DataType) -> Result<T> {
+    cast(array, &to)?
+        .as_any()
+        .downcast_ref::<T>()
+        .cloned()
+        .ok_or_else(|| {
+            DataFusionError::Internal(format!(
+                "cast of {} to {to} gave another type",
+                array.data_type()
+            ))
+        })
+}
+
+/// For every left row, the latest right row at or before it with the same key
+fn asof_indices(
+    left: &Int64Array,
+    right: &Int64Array,
+    by: Option<(&StringArray, &StringArray)>,
+) -> (UInt32Array, UInt32Array) {
+    let key = |keys: Option<&StringArray>, i: usize| -> Option<String> {
+        keys.filter(|k| k.is_valid(i))
+            .map(|k| k.value(i).to_string())
+    };
+
+    let mut groups: HashMap<Option<String>, Vec<(i64, u32)>> = HashMap::new();
+    for i in 0..right.len() {
+        if right.is_valid(i) {
+            groups
+                .entry(key(by.map(|(_, r)| r), i))
+                .or_default()
+                .push((right.value(i), i as u32));
+        }
+    }
+    for rows in groups.values_mut() {
+        // stable, so the last row wins between rows of the same time
+        rows.sort_by_key(|(time, _)| *time);
+    }
+
+    let matches = (0..left.len()).map(|i| {
+        if left.is_null(i) {
+            return None;
+        }
+        let rows = groups.get(&key(by.map(|(l, _)| l), i))?;
+        let after = rows.partition_point(|(time, _)| *time <= left.value(i));
+        after.checked_sub(1).map(|j| rows[j].1)
+    });
+    let right_rows = UInt32Array::from_iter(matches);
+    let left_rows = UInt32Array::from_iter_values(0..left.len() as u32);
+    (left_rows, right_rows)
+}
+
+/// Every `(span, point)` pair with the point within `[start, end)`
+fn window_indices(
+    start: &Int64Array,
+    end: &Int64Array,
+    points: &Int64Array,
+) -> (UInt32Array, UInt32Array) {
+    let mut sorted: Vec<(i64, u32)> = (0..points.len())
+        .filter(|&i| points.is_valid(i))
+        .map(|i| (points.value(i), i as u32))
+        .collect();
+    sorted.sort_by_key(|(time, _)| *time);
+
+    let mut spans = vec![];
+    let mut matched = vec![];
+    for i in 0..start.len() {
+        if start.is_null(i) {
+            continue;
+        }
+        let end = if end.is_valid(i) {
+            end.value(i)
+        } else {
+            i64::MAX
+        };
+        let lo = sorted.partition_point(|(time, _)| *time < start.value(i));
+        let hi = sorted.partition_point(|(time, _)| *time < end);
+        for (_, point) in sorted[lo..hi.max(lo)].iter() {
+            spans.push(i as u32);
+            matched.push(*point);
+        }
+    }
+    (UInt32Array::from(spans), UInt32Array::from(matched))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Float64Array;
+
+    async fn context() -> SessionContext {
+        let ctx = SessionContext::new();
+        register_join_functions(&ctx);
+        for sql in [
+            "CREATE TABLE spans (name VARCHAR, start BIGINT, \"end\" BIGINT) AS VALUES \
+             ('fwd', 10, 20), ('bwd', 20, 40), ('open', 35, NULL)",
+            "CREATE TABLE gpu (time BIGINT, rank BIGINT, util DOUBLE) AS VALUES \
+             (5, 0, 0.1), (12, 0, 0.5), (15, 1, 0.7), (25, 0, 0.9), (38, 0, 1.0)",
+        ] {
+            ctx.sql(sql).await.unwrap().collect().await.unwrap();
+        }
+        ctx
+    }
+
+    async fn rows(ctx: &SessionContext, query: &str) -> RecordBatch {
+        let df = sql(ctx, query).await.unwrap();
+        let schema = df.schema().inner().clone();
+        concat_batches(&schema, &df.collect().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_span_window() {
+        let ctx = context().await;
+        let batch = rows(
+            &ctx,
+            "SELECT name, avg(util) AS util, count(*) AS n \
+             FROM span_window('spans', 'start', 'end', 'gpu', 'time') \
+             GROUP BY name ORDER BY name",
+        )
+        .await;
+        let names = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let util = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(names.value(0), "bwd");
+        assert_eq!(util.value(0), 0.95);
+        assert_eq!(names.value(1), "fwd");
+        assert_eq!(util.value(1), 0.6);
+        // open spans take every later point
+        assert_eq!(names.value(2), "open");
+        assert_eq!(util.value(2), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_asof_join() {
+        let ctx = context().await;
+        ctx.sql("CREATE TABLE steps (time BIGINT, rank BIGINT) AS VALUES (4, 0), (16, 0), (16, 1), (30, 1)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        let batch = rows(
+            &ctx,
+            "SELECT time, gpu_time, util FROM asof_join('steps', 'time', 'gpu', 'time') \
+             ORDER BY time",
+        )
+        .await;
+        assert_eq!(batch.num_rows(), 4);
+        assert!(batch.column(1).is_null(0));
+        let util = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(util.value(1), 0.7);
+        assert_eq!(util.value(3), 0.9);
+
+        let batch = rows(
+            &ctx,
+            "SELECT rank, util FROM asof_join('steps', 'time', 'gpu', 'time', 'rank') \
+             WHERE time = 16 ORDER BY rank",
+        )
+        .await;
+        let util = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(util.value(0), 0.5);
+        assert_eq!(util.value(1), 0.7);
+    }
+
+    /// Schema whose lookups wait, as remote ones do
+    #[derive(Debug)]
+    struct RemoteSchema(Arc<dyn TableProvider>);
+
+    #[async_trait]
+    impl datafusion::catalog::SchemaProvider for RemoteSchema {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn table_names(&self) -> Vec<String> {
+            vec!["gpu".to_string()]
+        }
+
+        async fn table(&self, name: &str) -> Result<Option<Arc<dyn TableProvider>>> {
+            tokio::task::yield_now().await;
+            Ok((name == "gpu").then(|| self.0.clone()))
+        }
+
+        fn table_exist(&self, name: &str) -> bool {
+            name == "gpu"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_tables() {
+        let ctx = context().await;
+        let gpu = ctx.table_provider("gpu").await.unwrap();
+        ctx.catalog("datafusion")
+            .unwrap()
+            .register_schema("remote", Arc::new(RemoteSchema(gpu)))
+            .unwrap();
+
+        let query = "SELECT count(*) AS n \
+                     FROM span_window('spans', 'start', 'end', 'remote.gpu', 'time')";
+        let err = ctx.sql(query).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("cannot be looked up while planning"));
+        let batch = rows(&ctx, query).await;
+        assert_eq!(batch.num_rows(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bad_arguments() {
+        let ctx = context().await;
+        let err = ctx
+            .sql("SELECT * FROM asof_join('gpu', 'time', 'spans')")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("usage: asof_join"));
+        let err = ctx
+            .sql("SELECT * FROM span_window('spans', 'begin', 'end', 'gpu', 'time')")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no column `begin`"));
+    }
+}
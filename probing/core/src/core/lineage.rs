@@ -0,0 +1,326 @@
+//! Column-level lineage of derived tables.
+//!
+//! Union views and session temporary tables are computed from other tables.
+//! When one of them is (re)defined, the raw tables and columns each of its
+//! columns is read from are recorded, along with the options of the
+//! extensions serving those tables at that time. `engine.lineage` lists them,
+//! so an exported analysis can tell exactly which instrumentation produced
+//! its inputs:
+//!
+//! ```sql
+//! SELECT "column", source_table, source_column, config
+//! FROM engine.lineage WHERE "table" = 'slow';
+//! ```
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, RwLock};
+
+use arrow::array::{Int64Array, RecordBatch, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::catalog::{Session, TableProvider};
+use datafusion::datasource::memory::{DataSourceExec, MemorySourceConfig};
+use datafusion::datasource::TableType;
+use datafusion::error::Result;
+use datafusion::logical_expr::{Expr, LogicalPlan};
+use datafusion::physical_plan::ExecutionPlan;
+
+/// Namespace and name of the lineage table
+pub const LINEAGE_TABLE: (&str, &str) = ("engine", "lineage");
+
+/// `(table, column)` pairs a column is computed from
+pub type Sources = BTreeSet<(String, String)>;
+
+/// Lineage of one derived table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineageRecord {
+    /// `namespace.table` of a view, bare name of a temporary table
+    pub table: String,
+    /// `view`, `materialized` or `temp`
+    pub kind: &'static str,
+    /// Session owning a temporary table
+    pub session: Option<String>,
+    /// SQL or description the table was defined with
+    pub definition: String,
+    /// `key=value` options of the extensions serving the source tables
+    pub config: String,
+    /// Microseconds since the epoch
+    pub created: i64,
+    /// Output columns, in order, with their sources
+    pub columns: Vec<(String, Sources)>,
+}
+
+impl LineageRecord {
+    /// Record the lineage of `plan`, the definition of `table`
+    pub fn from_plan(table: &str, kind: &'static str, plan: &LogicalPlan) -> Self {
+        let columns = plan
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .zip(column_sources(plan))
+            .collect();
+        Self {
+            table: table.to_string(),
+            kind,
+            session: None,
+            definition: String::new(),
+            config: String::new(),
+            created: chrono::Utc::now().timestamp_micros(),
+            columns,
+        }
+    }
+
+    /// Namespaces of the tables the record reads from
+    pub fn source_namespaces(&self) -> BTreeSet<String> {
+        self.columns
+            .iter()
+            .flat_map(|(_, sources)| sources)
+            .filter_map(|(table, _)| table.split_once('.').map(|(ns, _)| ns.to_string()))
+            .collect()
+    }
+}
+
+/// Sources of every output column of `plan`, in schema order
+pub fn column_sources(plan: &LogicalPlan) -> Vec<Sources> {
+    let width = plan.schema().fields().len();
+    let sources = match plan {
+        LogicalPlan::TableScan(scan) => scan
+            .projected_schema
+            .fields()
+            .iter()
+            .map(|field| Sources::from([(scan.table_name.to_string(), field.name().clone())]))
+            .collect(),
+        LogicalPlan::Projection(projection) => exprs_sources(&projection.expr, &projection.input),
+        LogicalPlan::Aggregate(aggregate) => {
+            let exprs = aggregate
+                .group_expr
+                .iter()
+                .chain(&aggregate.aggr_expr)
+                .cloned()
+                .collect::<Vec<_>>();
+            exprs_sources(&exprs, &aggregate.input)
+        }
+        LogicalPlan::Window(window) => {
+            let mut sources = column_sources(&window.input);
+            sources.extend(exprs_sources(&window.window_expr, &window.input));
+            sources
+        }
+        LogicalPlan::Union(union) => {
+            let mut sources = vec![Sources::new(); width];
+            for input in &union.inputs {
+                for (merged, input) in sources.iter_mut().zip(column_sources(input)) {
+                    merged.extend(input);
+                }
+            }
+            sources
+        }
+        // filters, sorts, limits, aliases and joins keep the columns of their inputs
+        other => other
+            .inputs()
+            .into_iter()
+            .flat_map(column_sources)
+            .collect(),
+    };
+    if sources.len() == width {
+        return sources;
+    }
+    // unknown shape, e.g. grouping sets: every column may come from any input
+    let all = plan
+        .inputs()
+        .into_iter()
+        .flat_map(column_sources)
+        .flatten()
+        .collect::<Sources>();
+    vec![all; width]
+}
+
+fn exprs_sources(exprs: &[Expr], input: &LogicalPlan) -> Vec<Sources> {
+    let input_sources = column_sources(input);
+    let schema = input.schema();
+    exprs
+        .iter()
+        .map(|expr| {
+            expr.column_refs()
+                .into_iter()
+                .filter_map(|column| schema.index_of_column(column).ok())
+                .filter_map(|idx| input_sources.get(idx))
+                .flatten()
+                .cloned()
+                .collect()
+        })
+        .collect()
+}
+
+/// Lineage of the derived tables of an engine
+#[derive(Debug, Default)]
+pub struct Lineage {
+    records: RwLock<BTreeMap<(Option<String>, String), LineageRecord>>,
+}
+
+impl Lineage {
+    /// Record `record`, replacing the lineage of an earlier table of the same
+    /// name; a view re-planned without changes keeps its creation time
+    pub fn record(&self, mut record: LineageRecord) {
+        let key = (record.session.clone(), record.table.clone());
+        let mut records = self.records.write().unwrap();
+        if let Some(old) = records.get(&key) {
+            if old.columns == record.columns && old.definition == record.definition {
+                record.created = old.created;
+            }
+        }
+        records.insert(key, record);
+    }
+
+    /// Forget a table dropped from `session`, or a view when `session` is `None`
+    pub fn remove(&self, session: Option<&str>, table: &str) {
+        let key = (session.map(str::to_string), table.to_string());
+        self.records.write().unwrap().remove(&key);
+    }
+
+    /// Forget the tables of a closed session
+    pub fn remove_session(&self, session: &str) {
+        self.records
+            .write()
+            .unwrap()
+            .retain(|(owner, _), _| owner.as_deref() != Some(session));
+    }
+
+    pub fn records(&self) -> Vec<LineageRecord> {
+        self.records.read().unwrap().values().cloned().collect()
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("table", DataType::Utf8, false),
+            Field::new("column", DataType::Utf8, false),
+            Field::new("source_table", DataType::Utf8, true),
+            Field::new("source_column", DataType::Utf8, true),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("session", DataType::Utf8, true),
+            Field::new("definition", DataType::Utf8, false),
+            Field::new("config", DataType::Utf8, false),
+            Field::new("created", DataType::Int64, false),
+        ]))
+    }
+
+    fn batch(&self) -> Result<RecordBatch> {
+        let mut rows = vec![];
+        for record in self.records() {
+            for (column, sources) in &record.columns {
+                if sources.is_empty() {
+                    rows.push((record.clone(), column.clone(), None));
+                }
+                for source in sources {
+                    rows.push((record.clone(), column.clone(), Some(source.clone())));
+                }
+            }
+        }
+        Ok(RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|(r, _, _)| &r.table),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|(_, c, _)| c),
+                )),
+                Arc::new(StringArray::from_iter(
+                    rows.iter().map(|(_, _, s)| s.as_ref().map(|(t, _)| t)),
+                )),
+                Arc::new(StringArray::from_iter(
+                    rows.iter().map(|(_, _, s)| s.as_ref().map(|(_, c)| c)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|(r, _, _)| r.kind),
+                )),
+                Arc::new(StringArray::from_iter(
+                    rows.iter().map(|(r, _, _)| r.session.as_deref()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|(r, _, _)| &r.definition),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|(r, _, _)| &r.config),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|(r, _, _)| r.created),
+                )),
+            ],
+        )?)
+    }
+}
+
+/// `engine.lineage`, read from the [`Lineage`] of the engine at scan time
+#[derive(Debug)]
+pub(crate) struct LineageTable(pub Arc<Lineage>);
+
+#[async_trait]
+impl TableProvider for LineageTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Lineage::schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let source = MemorySourceConfig::try_new(
+            &[vec![self.0.batch()?]],
+            self.schema(),
+            projection.cloned(),
+        )?;
+        Ok(Arc::new(DataSourceExec::new(Arc::new(source))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Engine;
+
+    #[tokio::test]
+    async fn test_column_sources() -> Result<()> {
+        let engine = Engine::builder().build().await?;
+        engine
+            .sql("CREATE TABLE steps AS VALUES (1, 0.5, 'a'), (2, 0.25, 'b')")
+            .await?
+            .collect()
+            .await?;
+        let df = engine
+            .context
+            .sql(
+                "SELECT column3 AS name, sum(column2 * column1) AS total, 1 AS one \
+                 FROM steps WHERE column1 > 1 GROUP BY column3",
+            )
+            .await?;
+        let sources = column_sources(df.logical_plan());
+        let columns = |names: &[&str]| {
+            names
+                .iter()
+                .map(|c| ("steps".to_string(), c.to_string()))
+                .collect::<Sources>()
+        };
+        assert_eq!(
+            sources,
+            vec![
+                columns(&["column3"]),
+                columns(&["column1", "column2"]),
+                Sources::new()
+            ]
+        );
+        Ok(())
+    }
+}
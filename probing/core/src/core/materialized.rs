@@ -0,0 +1,552 @@
+//! Materialized views.
+//!
+//! A materialized view stores the result of a query as `views.<name>` and
+//! refreshes it on an interval, so dashboards polling an aggregation read
+//! the stored rows instead of recomputing it on every poll. A view is
+//! maintained in one of three ways:
+//!
+//! - without a `source`, the query is run again on every refresh;
+//! - with a `source` table and its `watermark` column, the query reads the
+//!   rows of the source added since the last refresh as the table `delta`,
+//!   and its result is appended to the view;
+//! - with `merge` columns as well, the result is aggregated into the rows of
+//!   the view: rows with the same values in the other columns are combined
+//!   with `sum`, `min` or `max` of each merge column.
+//!
+//! Incremental views fit append-only sources such as time series, where the
+//! watermark, e.g. `timestamp` or `step`, only grows. Rows appended with a
+//! watermark below the largest one already seen are not read.
+//!
+//! ```sql
+//! -- query of a view `loss_by_rank` with source python.trainer_steps,
+//! -- watermark `step` and merge {"steps": "sum", "loss_sum": "sum"}
+//! SELECT rank, count(*) AS steps, sum(loss) AS loss_sum FROM delta GROUP BY rank;
+//! SELECT rank, loss_sum / steps FROM views.loss_by_rank;
+//! ```
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use arrow::array::RecordBatch;
+use arrow::compute::concat_batches;
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::catalog::{MemorySchemaProvider, SchemaProvider, Session, TableProvider};
+use datafusion::common::ScalarValue;
+use datafusion::datasource::memory::{DataSourceExec, MemorySourceConfig};
+use datafusion::datasource::{MemTable, TableType};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::functions_aggregate::expr_fn::{max, min, sum};
+use datafusion::logical_expr::{cast, Expr};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::{ident, lit, DataFrame, SessionContext};
+
+use super::lineage::LineageRecord;
+use super::Engine;
+
+/// Namespace of the materialized views
+pub const NAMESPACE: &str = "views";
+
+/// Name the query of an incremental view reads the new source rows from
+pub const DELTA_TABLE: &str = "delta";
+
+/// Rows kept by an appending view without an explicit capacity
+pub const DEFAULT_CAPACITY: usize = 100_000;
+
+/// How a merge column combines the rows of the view with new results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Merge {
+    /// Add the values, for sums and counts
+    Sum,
+    Min,
+    Max,
+}
+
+impl FromStr for Merge {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "sum" | "count" => Ok(Merge::Sum),
+            "min" => Ok(Merge::Min),
+            "max" => Ok(Merge::Max),
+            other => Err(format!("unknown merge `{other}`, expected sum, min or max")),
+        }
+    }
+}
+
+impl Merge {
+    fn aggregate(&self, column: &str) -> Expr {
+        match self {
+            Merge::Sum => sum(ident(column)),
+            Merge::Min => min(ident(column)),
+            Merge::Max => max(ident(column)),
+        }
+    }
+}
+
+/// How a view is maintained, see the [module documentation](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Full,
+    Append,
+    Merge,
+}
+
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Full => "full",
+            Mode::Append => "append",
+            Mode::Merge => "merge",
+        }
+    }
+}
+
+/// Definition of a materialized view
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterializedView {
+    pub name: String,
+    pub query: String,
+    /// Table read incrementally as `delta`
+    pub source: Option<String>,
+    /// Growing column of `source` telling the new rows apart
+    pub watermark: Option<String>,
+    /// Columns of the result combined with the rows of the view
+    pub merge: Vec<(String, Merge)>,
+    pub interval: Duration,
+    /// Rows kept by an appending view, the oldest are dropped first
+    pub capacity: usize,
+}
+
+impl MaterializedView {
+    pub fn mode(&self) -> Mode {
+        match (&self.source, self.merge.is_empty()) {
+            (None, _) => Mode::Full,
+            (Some(_), true) => Mode::Append,
+            (Some(_), false) => Mode::Merge,
+        }
+    }
+
+    /// Check that the fields of the definition fit together
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let name = &self.name;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid view name `{name}`"));
+        }
+        if self.query.trim().is_empty() {
+            return Err(format!("{name}: empty query"));
+        }
+        if self.interval.is_zero() {
+            return Err(format!("{name}: interval must be positive"));
+        }
+        match (&self.source, &self.watermark) {
+            (Some(_), None) => Err(format!("{name}: an incremental view needs a watermark")),
+            (None, Some(_)) => Err(format!("{name}: a watermark needs a source")),
+            (None, None) if !self.merge.is_empty() => {
+                Err(format!("{name}: merge columns need a source"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rows of a materialized view, served as `views.<name>`
+#[derive(Debug)]
+pub struct MaterializedTable {
+    pub view: MaterializedView,
+    schema: SchemaRef,
+    batches: RwLock<Vec<RecordBatch>>,
+    /// Largest watermark read from the source
+    watermark: Mutex<Option<ScalarValue>>,
+}
+
+impl MaterializedTable {
+    fn new(view: MaterializedView, schema: SchemaRef) -> Self {
+        // merged aggregates may be null where the query result is not
+        let fields: Vec<Field> = schema
+            .fields()
+            .iter()
+            .map(|field| field.as_ref().clone().with_nullable(true))
+            .collect();
+        Self {
+            view,
+            schema: Arc::new(Schema::new(fields)),
+            batches: Default::default(),
+            watermark: Default::default(),
+        }
+    }
+
+    pub fn num_rows(&self) -> usize {
+        let batches = self.batches.read().unwrap();
+        batches.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    /// Rebuild `batches` with the schema of the view
+    fn conform(&self, batches: Vec<RecordBatch>) -> Result<Vec<RecordBatch>> {
+        batches
+            .into_iter()
+            .map(|batch| {
+                if batch.num_columns() != self.schema.fields().len() {
+                    return Err(DataFusionError::Plan(format!(
+                        "the columns of view `{}` changed, define it again",
+                        self.view.name
+                    )));
+                }
+                RecordBatch::try_new(self.schema.clone(), batch.columns().to_vec())
+                    .map_err(Into::into)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl TableProvider for MaterializedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let batches = self.batches.read().unwrap().clone();
+        let source = MemorySourceConfig::try_new(&[batches], self.schema(), projection.cloned())?;
+        Ok(Arc::new(DataSourceExec::new(Arc::new(source))))
+    }
+}
+
+/// Materialized views of an engine, by name
+pub type MaterializedViews = RwLock<HashMap<String, Arc<MaterializedTable>>>;
+
+fn read_batches(
+    context: &SessionContext,
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+) -> Result<DataFrame> {
+    context.read_table(Arc::new(MemTable::try_new(schema, vec![batches])?))
+}
+
+/// Largest value of `column` in `batches`, `None` when they hold no value
+async fn max_value(
+    context: &SessionContext,
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+    column: &str,
+) -> Result<Option<ScalarValue>> {
+    let result = read_batches(context, schema, batches)?
+        .aggregate(vec![], vec![max(ident(column)).alias(column)])?
+        .collect()
+        .await?;
+    let value = match result.first() {
+        Some(batch) if batch.num_rows() > 0 => ScalarValue::try_from_array(batch.column(0), 0)?,
+        _ => return Ok(None),
+    };
+    Ok((!value.is_null()).then_some(value))
+}
+
+/// The last `capacity` rows of `batches`
+fn keep_last(
+    schema: &SchemaRef,
+    batches: Vec<RecordBatch>,
+    capacity: usize,
+) -> Result<Vec<RecordBatch>> {
+    let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+    if rows <= capacity {
+        return Ok(batches);
+    }
+    let all = concat_batches(schema, &batches)?;
+    Ok(vec![all.slice(rows - capacity, capacity)])
+}
+
+impl Engine {
+    /// Define `view`, replacing the view of the same name, and serve it as
+    /// `views.<name>`. It is empty until [`Self::refresh_materialized_view`].
+    pub async fn define_materialized_view(&self, view: MaterializedView) -> Result<()> {
+        view.validate().map_err(DataFusionError::Plan)?;
+        let (context, _) = self.materialized_context(&view, None, false).await?;
+        let plan = context.sql(&view.query).await?.into_unoptimized_plan();
+        let schema: SchemaRef = Arc::new(plan.schema().as_arrow().clone());
+        for (column, _) in &view.merge {
+            if schema.field_with_name(column).is_err() {
+                return Err(DataFusionError::Plan(format!(
+                    "merge column `{column}` is not a column of view `{}`",
+                    view.name
+                )));
+            }
+        }
+
+        let name = view.name.clone();
+        let mut record =
+            LineageRecord::from_plan(&format!("{NAMESPACE}.{name}"), "materialized", &plan);
+        record.definition = view.query.clone();
+
+        let table = Arc::new(MaterializedTable::new(view, schema));
+        let catalog = self
+            .context
+            .catalog("probe")
+            .ok_or_else(|| DataFusionError::Internal("no catalog `probe`".to_string()))?;
+        if catalog.schema(NAMESPACE).is_none() {
+            catalog.register_schema(NAMESPACE, Arc::new(MemorySchemaProvider::new()))?;
+        }
+        let table_name = format!("probe.{NAMESPACE}.{name}");
+        if self.context.table_exist(table_name.as_str())? {
+            self.context.deregister_table(table_name.as_str())?;
+        }
+        self.context
+            .register_table(table_name.as_str(), table.clone())?;
+        self.materialized.write().unwrap().insert(name, table);
+        self.record_lineage(record);
+        Ok(())
+    }
+
+    /// Drop the view `name`, returning whether it was defined
+    pub fn drop_materialized_view(&self, name: &str) -> Result<bool> {
+        if self.materialized.write().unwrap().remove(name).is_none() {
+            return Ok(false);
+        }
+        self.context
+            .deregister_table(format!("probe.{NAMESPACE}.{name}").as_str())?;
+        Ok(true)
+    }
+
+    /// Definitions of the materialized views
+    pub fn materialized_views(&self) -> Vec<MaterializedView> {
+        let views = self.materialized.read().unwrap();
+        views.values().map(|table| table.view.clone()).collect()
+    }
+
+    /// Update the rows of the view `name` and return their number
+    pub async fn refresh_materialized_view(&self, name: &str) -> Result<usize> {
+        let table = self
+            .materialized
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| DataFusionError::Plan(format!("no materialized view `{name}`")))?;
+        let view = &table.view;
+
+        let last = table.watermark.lock().unwrap().clone();
+        let (context, watermark) = self.materialized_context(view, last, true).await?;
+        let rows = table.conform(context.sql(&view.query).await?.collect().await?)?;
+        let schema = table.schema();
+
+        let batches = match view.mode() {
+            Mode::Full => rows,
+            Mode::Append => {
+                let mut batches = table.batches.read().unwrap().clone();
+                batches.extend(rows);
+                keep_last(&schema, batches, view.capacity)?
+            }
+            Mode::Merge => {
+                let mut batches = table.batches.read().unwrap().clone();
+                batches.extend(rows);
+                let keys = schema
+                    .fields()
+                    .iter()
+                    .filter(|field| !view.merge.iter().any(|(c, _)| c == field.name()))
+                    .map(|field| ident(field.name()))
+                    .collect();
+                let aggregates = view
+                    .merge
+                    .iter()
+                    .map(|(column, merge)| merge.aggregate(column).alias(column))
+                    .collect();
+                // sums widen the type of their column, cast it back
+                let columns = schema
+                    .fields()
+                    .iter()
+                    .map(|field| {
+                        cast(ident(field.name()), field.data_type().clone()).alias(field.name())
+                    })
+                    .collect::<Vec<_>>();
+                let merged = read_batches(&context, schema.clone(), batches)?
+                    .aggregate(keys, aggregates)?
+                    .select(columns)?
+                    .collect()
+                    .await?;
+                table.conform(merged)?
+            }
+        };
+
+        let rows = batches.iter().map(|batch| batch.num_rows()).sum();
+        *table.batches.write().unwrap() = batches;
+        if watermark.is_some() {
+            *table.watermark.lock().unwrap() = watermark;
+        }
+        Ok(rows)
+    }
+
+    /// Context running the query of `view`, where `delta` holds the rows of
+    /// its source above the watermark `last`, or no rows without
+    /// `read_source`, and the largest watermark of those rows
+    async fn materialized_context(
+        &self,
+        view: &MaterializedView,
+        last: Option<ScalarValue>,
+        read_source: bool,
+    ) -> Result<(SessionContext, Option<ScalarValue>)> {
+        let tables = Arc::new(MemorySchemaProvider::new());
+        let mut watermark = None;
+        if let (Some(source), Some(column)) = (&view.source, &view.watermark) {
+            let mut delta = self.context.table(source.as_str()).await?;
+            if let Some(last) = last {
+                delta = delta.filter(ident(column).gt(lit(last)))?;
+            }
+            if !read_source {
+                delta = delta.limit(0, Some(0))?;
+            }
+            let schema: SchemaRef = Arc::new(delta.schema().as_arrow().clone());
+            let batches = delta.collect().await?;
+            watermark = max_value(&self.context, schema.clone(), batches.clone(), column).await?;
+            tables.register_table(
+                DELTA_TABLE.to_string(),
+                Arc::new(MemTable::try_new(schema, vec![batches])?),
+            )?;
+        }
+        Ok((self.session_context(tables)?, watermark))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    async fn engine() -> Result<Engine> {
+        let engine = Engine::builder().build().await?;
+        engine
+            .sql("CREATE TABLE steps (step BIGINT, rank BIGINT, loss DOUBLE)")
+            .await?
+            .collect()
+            .await?;
+        Ok(engine)
+    }
+
+    async fn append(engine: &Engine, rows: &str) -> Result<()> {
+        engine
+            .sql(&format!("INSERT INTO steps VALUES {rows}"))
+            .await?
+            .collect()
+            .await?;
+        Ok(())
+    }
+
+    async fn query(engine: &Engine, query: &str) -> Result<Vec<i64>> {
+        let batches = engine.sql(query).await?.collect().await?;
+        Ok(batches
+            .iter()
+            .flat_map(|batch| {
+                let array = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap();
+                array.iter().flatten().collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    fn view(query: &str) -> MaterializedView {
+        MaterializedView {
+            name: "v".to_string(),
+            query: query.to_string(),
+            source: Some("steps".to_string()),
+            watermark: Some("step".to_string()),
+            merge: vec![],
+            interval: Duration::from_secs(1),
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_and_append_views() -> Result<()> {
+        let engine = engine().await?;
+        append(&engine, "(1, 0, 1.0), (2, 0, 2.0)").await?;
+
+        let full = MaterializedView {
+            source: None,
+            watermark: None,
+            ..view("SELECT count(*) AS n FROM steps")
+        };
+        engine.define_materialized_view(full).await?;
+        assert!(query(&engine, "SELECT n FROM views.v").await?.is_empty());
+        engine.refresh_materialized_view("v").await?;
+        assert_eq!(query(&engine, "SELECT n FROM views.v").await?, [2]);
+
+        let appending = MaterializedView {
+            capacity: 3,
+            ..view("SELECT step FROM delta WHERE loss > 1.5")
+        };
+        engine.define_materialized_view(appending).await?;
+        assert_eq!(engine.refresh_materialized_view("v").await?, 1);
+        append(&engine, "(3, 0, 3.0), (4, 0, 4.0), (5, 0, 0.5)").await?;
+        assert_eq!(engine.refresh_materialized_view("v").await?, 3);
+        // nothing new, nothing appended
+        assert_eq!(engine.refresh_materialized_view("v").await?, 3);
+        append(&engine, "(6, 0, 6.0)").await?;
+        engine.refresh_materialized_view("v").await?;
+        assert_eq!(
+            query(&engine, "SELECT step FROM views.v ORDER BY step").await?,
+            [3, 4, 6]
+        );
+
+        assert!(engine.drop_materialized_view("v")?);
+        assert!(engine.sql("SELECT * FROM views.v").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merged_view() -> Result<()> {
+        let engine = engine().await?;
+        append(&engine, "(1, 0, 1.0), (1, 1, 3.0)").await?;
+        let merged = MaterializedView {
+            merge: vec![
+                ("n".to_string(), Merge::Sum),
+                ("last".to_string(), Merge::Max),
+            ],
+            ..view("SELECT rank, count(*) AS n, max(step) AS last FROM delta GROUP BY rank")
+        };
+        engine.define_materialized_view(merged).await?;
+        engine.refresh_materialized_view("v").await?;
+        append(&engine, "(2, 0, 1.0), (3, 0, 1.0), (2, 2, 1.0)").await?;
+        assert_eq!(engine.refresh_materialized_view("v").await?, 3);
+
+        assert_eq!(
+            query(&engine, "SELECT n FROM views.v ORDER BY rank").await?,
+            [3, 1, 1]
+        );
+        assert_eq!(
+            query(&engine, "SELECT last FROM views.v ORDER BY rank").await?,
+            [3, 1, 2]
+        );
+
+        let invalid = MaterializedView {
+            merge: vec![("missing".to_string(), Merge::Sum)],
+            ..view("SELECT rank FROM delta")
+        };
+        assert!(engine.define_materialized_view(invalid).await.is_err());
+        let invalid = MaterializedView {
+            watermark: None,
+            ..view("SELECT rank FROM delta")
+        };
+        assert!(invalid.validate().is_err());
+        assert_eq!("count".parse::<Merge>(), Ok(Merge::Sum));
+        Ok(())
+    }
+}
@@ -1,10 +1,33 @@
 mod arrow_convert;
+pub mod breaker;
+pub mod clock;
 pub mod cluster;
 pub mod cluster_model;
+mod diagnostics;
 mod engine;
 mod error;
 pub mod extension;
+#[cfg(any(feature = "postgres", feature = "clickhouse"))]
+pub mod federation;
+pub mod guard;
+pub mod job;
+pub mod join;
+pub mod lineage;
+pub mod materialized;
+pub mod mpi;
 mod plugin;
+pub mod profile;
+pub mod pushdown;
+pub mod schema;
+pub mod session;
+pub mod shape;
+pub mod slurm;
+pub mod time;
+mod union_view;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use arrow_convert::dataframe_to_record_batch;
 
 pub use engine::Engine;
 pub use engine::EngineBuilder;
@@ -20,6 +43,12 @@ pub use plugin::LazyTableSource;
 pub use plugin::NamespacePluginHelper;
 pub use plugin::TablePluginHelper;
 
+pub use union_view::set_column_order;
+pub use union_view::set_computed_columns;
+pub use union_view::ComputedColumn;
+pub use union_view::UnionView;
+
+pub use extension::set_call_metadata;
 pub use extension::EngineCall;
 pub use extension::EngineDatasource;
 pub use extension::EngineExtension;
@@ -28,6 +57,7 @@ pub use extension::EngineExtensionOption;
 pub use extension::Maybe;
 
 pub use probing_macros::EngineExtension;
+pub use probing_proto::prelude::{OptionKind, OptionSchema};
 
 pub use datafusion::arrow::array::ArrayRef;
 pub use datafusion::arrow::array::Float32Array;
@@ -36,6 +66,7 @@ pub use datafusion::arrow::array::Int32Array;
 pub use datafusion::arrow::array::Int64Array;
 pub use datafusion::arrow::array::RecordBatch;
 pub use datafusion::arrow::array::StringArray;
+pub use datafusion::arrow::array::TimestampNanosecondArray;
 pub use datafusion::arrow::datatypes::DataType;
 pub use datafusion::arrow::datatypes::Field;
 pub use datafusion::arrow::datatypes::Schema;
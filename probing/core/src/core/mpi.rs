@@ -0,0 +1,204 @@
+//! Ranks of processes started by an MPI launcher.
+//!
+//! torchrun and the launchers imitating it describe a rank with `RANK`,
+//! `LOCAL_RANK` and `WORLD_SIZE`. `mpirun` sets variables of its own, which
+//! depend on the implementation:
+//!
+//! - OpenMPI: `OMPI_COMM_WORLD_RANK`, `OMPI_COMM_WORLD_LOCAL_RANK`,
+//!   `OMPI_COMM_WORLD_SIZE` and `OMPI_COMM_WORLD_LOCAL_SIZE`;
+//! - MPICH and its derivatives (Hydra, Intel MPI): `PMI_RANK`, `PMI_SIZE`,
+//!   `MPI_LOCALRANKID` and `MPI_LOCALNRANKS`.
+//!
+//! [`rank`], [`local_rank`] and [`world_size`] read the torchrun variables
+//! first, then the MPI ones and last `SLURM_PROCID`, `SLURM_LOCALID` and
+//! `SLURM_NTASKS` of `srun`, so ports are offset and nodes are reported the
+//! same way whichever launcher started the process.
+
+use std::sync::LazyLock;
+
+/// Prefixes of the variables set by MPI launchers, listed in `mpi.env`
+pub const VARIABLE_PREFIXES: &[&str] = &[
+    "OMPI_", "PMI_", "PMIX_", "MPI_", "HYDRA_", "I_MPI_", "MPICH_",
+];
+
+/// Ranks of the process from the MPI launcher environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MpiEnv {
+    /// `openmpi` or `mpich`
+    pub implementation: &'static str,
+    pub rank: Option<i32>,
+    pub local_rank: Option<i32>,
+    pub world_size: Option<i32>,
+    pub local_world_size: Option<i32>,
+}
+
+struct Variables {
+    implementation: &'static str,
+    rank: &'static str,
+    local_rank: &'static str,
+    world_size: &'static str,
+    local_world_size: &'static str,
+}
+
+/// Variables of the supported implementations, in order of detection
+const IMPLEMENTATIONS: &[Variables] = &[
+    Variables {
+        implementation: "openmpi",
+        rank: "OMPI_COMM_WORLD_RANK",
+        local_rank: "OMPI_COMM_WORLD_LOCAL_RANK",
+        world_size: "OMPI_COMM_WORLD_SIZE",
+        local_world_size: "OMPI_COMM_WORLD_LOCAL_SIZE",
+    },
+    Variables {
+        implementation: "mpich",
+        rank: "PMI_RANK",
+        local_rank: "MPI_LOCALRANKID",
+        world_size: "PMI_SIZE",
+        local_world_size: "MPI_LOCALNRANKS",
+    },
+];
+
+static MPI_ENV: LazyLock<Option<MpiEnv>> =
+    LazyLock::new(|| resolve(|name| std::env::var(name).ok()));
+
+/// MPI environment of this process, `None` when not started by `mpirun`
+pub fn mpi_env() -> Option<MpiEnv> {
+    MPI_ENV.clone()
+}
+
+/// MPI environment from the variables returned by `env`, detected by the
+/// rank variable of each implementation
+pub fn resolve(env: impl Fn(&str) -> Option<String>) -> Option<MpiEnv> {
+    let int = |name: &str| env(name).and_then(|v| v.trim().parse().ok());
+    IMPLEMENTATIONS.iter().find_map(|vars| {
+        let rank = int(vars.rank)?;
+        Some(MpiEnv {
+            implementation: vars.implementation,
+            rank: Some(rank),
+            local_rank: int(vars.local_rank),
+            world_size: int(vars.world_size),
+            local_world_size: int(vars.local_world_size),
+        })
+    })
+}
+
+fn torchrun_or_mpi(
+    env: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    mpi: impl Fn(&MpiEnv) -> Option<i32>,
+    slurm: &str,
+) -> Option<i32> {
+    let int = |name: &str| env(name).and_then(|v| v.trim().parse().ok());
+    int(name)
+        .or_else(|| resolve(env).as_ref().and_then(mpi))
+        .or_else(|| int(slurm))
+}
+
+/// `RANK`, the MPI rank or `SLURM_PROCID`, from the variables returned by
+/// `env`
+pub fn resolve_rank(env: impl Fn(&str) -> Option<String>) -> Option<i32> {
+    torchrun_or_mpi(&env, "RANK", |mpi| mpi.rank, "SLURM_PROCID")
+}
+
+/// `LOCAL_RANK`, the MPI rank on the node or `SLURM_LOCALID`, from the
+/// variables returned by `env`
+pub fn resolve_local_rank(env: impl Fn(&str) -> Option<String>) -> Option<i32> {
+    torchrun_or_mpi(&env, "LOCAL_RANK", |mpi| mpi.local_rank, "SLURM_LOCALID")
+}
+
+/// `WORLD_SIZE`, the MPI world size or `SLURM_NTASKS`, from the variables
+/// returned by `env`
+pub fn resolve_world_size(env: impl Fn(&str) -> Option<String>) -> Option<i32> {
+    torchrun_or_mpi(&env, "WORLD_SIZE", |mpi| mpi.world_size, "SLURM_NTASKS")
+}
+
+/// Rank of this process, from torchrun, MPI or Slurm variables
+pub fn rank() -> Option<i32> {
+    resolve_rank(|name| std::env::var(name).ok())
+}
+
+/// Rank of this process on its node, from torchrun, MPI or Slurm variables
+pub fn local_rank() -> Option<i32> {
+    resolve_local_rank(|name| std::env::var(name).ok())
+}
+
+/// Number of ranks of the run, from torchrun, MPI or Slurm variables
+pub fn world_size() -> Option<i32> {
+    resolve_world_size(|name| std::env::var(name).ok())
+}
+
+/// Whether the variable `name` is set by MPI launchers
+pub fn is_mpi_variable(name: &str) -> bool {
+    VARIABLE_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn test_resolve_mpi_env() {
+        let openmpi = [
+            ("OMPI_COMM_WORLD_RANK", "5"),
+            ("OMPI_COMM_WORLD_LOCAL_RANK", "1"),
+            ("OMPI_COMM_WORLD_SIZE", "8"),
+            ("OMPI_COMM_WORLD_LOCAL_SIZE", "4"),
+        ];
+        assert_eq!(
+            resolve(env(&openmpi)),
+            Some(MpiEnv {
+                implementation: "openmpi",
+                rank: Some(5),
+                local_rank: Some(1),
+                world_size: Some(8),
+                local_world_size: Some(4),
+            })
+        );
+
+        let mpich = [("PMI_RANK", "3"), ("PMI_SIZE", "4")];
+        let mpi = resolve(env(&mpich)).unwrap();
+        assert_eq!(mpi.implementation, "mpich");
+        assert_eq!((mpi.rank, mpi.local_rank), (Some(3), None));
+
+        assert_eq!(resolve(env(&[("PMI_SIZE", "4")])), None);
+        assert_eq!(resolve(env(&[("PMI_RANK", "x")])), None);
+    }
+
+    #[test]
+    fn test_torchrun_before_mpi() {
+        let mixed = [("RANK", "2"), ("OMPI_COMM_WORLD_RANK", "5")];
+        assert_eq!(resolve_rank(env(&mixed)), Some(2));
+
+        let mpich = [
+            ("PMI_RANK", "3"),
+            ("MPI_LOCALRANKID", "1"),
+            ("PMI_SIZE", "4"),
+        ];
+        assert_eq!(resolve_rank(env(&mpich)), Some(3));
+        assert_eq!(resolve_local_rank(env(&mpich)), Some(1));
+        assert_eq!(resolve_world_size(env(&mpich)), Some(4));
+        assert_eq!(resolve_local_rank(env(&[])), None);
+
+        // `srun` without an MPI library
+        let srun = [("SLURM_PROCID", "7"), ("PMI_SIZE", "8")];
+        assert_eq!(resolve_rank(env(&srun)), Some(7));
+        assert_eq!(
+            resolve_rank(env(&[("PMI_RANK", "3"), ("SLURM_PROCID", "7")])),
+            Some(3)
+        );
+
+        assert!(is_mpi_variable("OMPI_COMM_WORLD_RANK"));
+        assert!(is_mpi_variable("I_MPI_PIN"));
+        assert!(!is_mpi_variable("LOCAL_RANK"));
+    }
+}
@@ -6,6 +6,8 @@ use async_trait::async_trait;
 use datafusion::arrow::array::RecordBatch;
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::catalog::{CatalogProvider, SchemaProvider, Session, TableProvider};
+use datafusion::common::stats::Precision;
+use datafusion::common::Statistics;
 use datafusion::datasource::memory::DataSourceExec;
 use datafusion::datasource::memory::MemorySourceConfig;
 use datafusion::datasource::TableType;
@@ -163,6 +165,12 @@ impl TableProvider for LazyTableSource {
         TableType::Base
     }
 
+    fn statistics(&self) -> Option<Statistics> {
+        let mut stats = Statistics::new_unknown(&self.schema());
+        stats.num_rows = Precision::Exact(self.data.iter().map(|b| b.num_rows()).sum());
+        Some(stats)
+    }
+
     async fn scan(
         &self,
         _state: &dyn Session,
@@ -0,0 +1,184 @@
+//! Named instrumentation profiles.
+//!
+//! A profile is a set of extension options applied together by setting
+//! `probing.profile=<name>`, so that an incident needs one switch instead of
+//! a dozen options. The built-in profiles are listed in [`BUILTIN_PROFILES`].
+//! `probing.profiles.<name>="<key>=<value> ..."` defines a new profile or
+//! replaces a built-in one, and an empty definition restores the built-in.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+
+use super::error::EngineError;
+
+/// Option selecting the active profile
+pub const PROFILE_KEY: &str = "profile";
+
+/// Prefix of the options defining profiles
+pub const PROFILES_PREFIX: &str = "profiles.";
+
+/// A profile shipped with probing
+#[derive(Debug)]
+pub struct Profile {
+    pub name: &'static str,
+    pub help: &'static str,
+    /// Extension options, without the `probing.` prefix
+    pub options: &'static [(&'static str, &'static str)],
+}
+
+pub const BUILTIN_PROFILES: &[Profile] = &[
+    Profile {
+        name: "minimal",
+        help:
+            "Turn off stack and call sampling, torch tracing, gradient statistics and anomaly rules",
+        options: &[
+            ("pprof.sample_freq", ""),
+            ("tracer.sample_every", ""),
+            ("tracing.include", ""),
+            ("tracing.exclude", ""),
+            ("tracer.max_events_per_sec", ""),
+            ("torch.profiling", "off"),
+            ("torch.grad_stats", "off"),
+            ("anomaly.watch", ""),
+        ],
+    },
+    Profile {
+        name: "dataloader-debug",
+        help: "Sample stacks at 10Hz and record the calls of the data loaders and of imports",
+        options: &[
+            ("pprof.sample_freq", "10"),
+            ("tracer.sample_every", "1"),
+            ("tracing.include", "torch.utils.data,importlib"),
+            ("tracer.max_events_per_sec", "10000"),
+        ],
+    },
+    Profile {
+        name: "divergence-debug",
+        help: "Record the gradient statistics of every parameter group at every step",
+        options: &[("torch.grad_stats", "on")],
+    },
+];
+
+/// Option keys and values of a profile
+pub type ProfileOptions = Vec<(String, String)>;
+
+/// Profiles defined at runtime, taking precedence over the built-in ones
+static DEFINED: Lazy<RwLock<BTreeMap<String, ProfileOptions>>> = Lazy::new(Default::default);
+
+static ACTIVE: Lazy<RwLock<Option<String>>> = Lazy::new(Default::default);
+
+/// Parse a definition such as `pprof.sample_freq=10 torch.profiling=on`.
+///
+/// Options are separated by whitespace since values may contain commas, and
+/// `;` already separates `SET` statements.
+pub fn parse(spec: &str) -> Result<ProfileOptions, EngineError> {
+    spec.split_whitespace()
+        .map(|item| {
+            let invalid = || EngineError::InvalidOptionValue(PROFILES_PREFIX.into(), item.into());
+            let (key, value) = item.split_once('=').ok_or_else(invalid)?;
+            let key = key.trim();
+            let key = key.strip_prefix("probing.").unwrap_or(key);
+            if key.is_empty() || key == PROFILE_KEY || key.starts_with(PROFILES_PREFIX) {
+                return Err(invalid());
+            }
+            Ok((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Define the profile `name`, an empty `spec` drops the definition
+pub fn define(name: &str, spec: &str) -> Result<(), EngineError> {
+    if name.is_empty() {
+        return Err(EngineError::InvalidOptionValue(
+            format!("{PROFILES_PREFIX}{name}"),
+            spec.to_string(),
+        ));
+    }
+    let options = parse(spec)?;
+    let mut defined = DEFINED.write().unwrap();
+    if options.is_empty() {
+        defined.remove(name);
+    } else {
+        defined.insert(name.to_string(), options);
+    }
+    Ok(())
+}
+
+/// The options applied by the profile `name`
+pub fn options_of(name: &str) -> Option<ProfileOptions> {
+    if let Some(options) = DEFINED.read().unwrap().get(name) {
+        return Some(options.clone());
+    }
+    BUILTIN_PROFILES
+        .iter()
+        .find(|profile| profile.name == name)
+        .map(|profile| {
+            profile
+                .options
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        })
+}
+
+/// Names of all known profiles, sorted
+pub fn names() -> Vec<String> {
+    let mut names = BUILTIN_PROFILES
+        .iter()
+        .map(|profile| profile.name.to_string())
+        .chain(DEFINED.read().unwrap().keys().cloned())
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The profile applied last
+pub fn active() -> Option<String> {
+    ACTIVE.read().unwrap().clone()
+}
+
+/// Record `name` as the active profile, returning the previous one
+pub(crate) fn set_active(name: &str) -> Option<String> {
+    ACTIVE.write().unwrap().replace(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let options = parse("probing.pprof.sample_freq=10  anomaly.watch=a:spike,b:nan ").unwrap();
+        assert_eq!(
+            options,
+            vec![
+                ("pprof.sample_freq".to_string(), "10".to_string()),
+                ("anomaly.watch".to_string(), "a:spike,b:nan".to_string()),
+            ]
+        );
+        assert!(parse("pprof.sample_freq").is_err());
+        assert!(parse("profile=minimal").is_err());
+        assert!(parse("profiles.other=x").is_err());
+    }
+
+    #[test]
+    fn test_define_overrides_builtin() {
+        assert!(options_of("minimal").unwrap().len() > 1);
+        assert!(options_of("no-such-profile").is_none());
+
+        define("minimal", "torch.profiling=off").unwrap();
+        assert_eq!(
+            options_of("minimal").unwrap(),
+            vec![("torch.profiling".to_string(), "off".to_string())]
+        );
+        define("minimal", "").unwrap();
+        assert_eq!(
+            options_of("minimal").unwrap().len(),
+            BUILTIN_PROFILES[0].options.len()
+        );
+        assert!(names().contains(&"dataloader-debug".to_string()));
+    }
+}
@@ -0,0 +1,289 @@
+//! Filter pushdown for tables backed by in-memory rows.
+//!
+//! Tables that produce their rows on every scan, such as the external tables
+//! written from Python, can skip rows while building the batches instead of
+//! handing everything to DataFusion. [`Predicate::parse`] extracts the simple
+//! comparisons of a filter that such a table can check against its own values:
+//!
+//! - `col <op> literal` and `literal <op> col` for `=`, `!=`, `<`, `<=`, `>`, `>=`
+//! - `col BETWEEN low AND high` and `col IN (...)`
+//! - `to_timestamp_ns(col) <op> timestamp`, the usual time window on trace tables
+//!
+//! The check is conservative: a value that cannot be compared with the literal,
+//! a null or a NaN keeps the row. Tables therefore report the filters as
+//! [`TableProviderFilterPushDown::Inexact`] and DataFusion still applies them.
+
+use std::cmp::Ordering;
+
+use datafusion::common::ScalarValue;
+use datafusion::logical_expr::utils::split_conjunction;
+use datafusion::logical_expr::{Between, BinaryExpr, Expr, Operator, TableProviderFilterPushDown};
+use probing_proto::prelude::Ele;
+
+/// Comparison applied to a column
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Eq(Key),
+    NotEq(Key),
+    Lt(Key),
+    LtEq(Key),
+    Gt(Key),
+    GtEq(Key),
+    In(Vec<Key>),
+}
+
+/// A value normalized for comparison; timestamps are nanoseconds
+#[derive(Clone, Debug, PartialEq)]
+pub enum Key {
+    Int(i128),
+    Float(f64),
+    Text(String),
+}
+
+impl Key {
+    /// Key of a literal, `None` for types that are not pushed down
+    pub fn from_scalar(value: &ScalarValue) -> Option<Key> {
+        let int = |v: Option<i128>| v.map(Key::Int);
+        match value {
+            ScalarValue::Int8(v) => int(v.map(Into::into)),
+            ScalarValue::Int16(v) => int(v.map(Into::into)),
+            ScalarValue::Int32(v) => int(v.map(Into::into)),
+            ScalarValue::Int64(v) => int(v.map(Into::into)),
+            ScalarValue::UInt8(v) => int(v.map(Into::into)),
+            ScalarValue::UInt16(v) => int(v.map(Into::into)),
+            ScalarValue::UInt32(v) => int(v.map(Into::into)),
+            ScalarValue::UInt64(v) => int(v.map(Into::into)),
+            ScalarValue::Float32(v) => v.map(|v| Key::Float(v.into())),
+            ScalarValue::Float64(v) => v.map(Key::Float),
+            ScalarValue::Utf8(v) | ScalarValue::LargeUtf8(v) | ScalarValue::Utf8View(v) => {
+                v.clone().map(Key::Text)
+            }
+            ScalarValue::TimestampSecond(v, _) => int(v.map(|v| v as i128 * 1_000_000_000)),
+            ScalarValue::TimestampMillisecond(v, _) => int(v.map(|v| v as i128 * 1_000_000)),
+            ScalarValue::TimestampMicrosecond(v, _) => int(v.map(|v| v as i128 * 1_000)),
+            ScalarValue::TimestampNanosecond(v, _) => int(v.map(Into::into)),
+            _ => None,
+        }
+    }
+
+    /// Key of a stored value, `None` for nulls and types without an order
+    pub fn from_ele(value: &Ele) -> Option<Key> {
+        match value {
+            Ele::I32(v) => Some(Key::Int((*v).into())),
+            Ele::I64(v) => Some(Key::Int((*v).into())),
+            Ele::F32(v) => Some(Key::Float((*v).into())),
+            Ele::F64(v) => Some(Key::Float(*v)),
+            Ele::Text(v) | Ele::Url(v) => Some(Key::Text(v.clone())),
+            // microseconds
+            Ele::DataTime(v) => Some(Key::Int(*v as i128 * 1_000)),
+            Ele::Nil | Ele::BOOL(_) => None,
+        }
+    }
+
+    fn compare(&self, other: &Key) -> Option<Ordering> {
+        match (self, other) {
+            (Key::Int(a), Key::Int(b)) => Some(a.cmp(b)),
+            (Key::Float(a), Key::Float(b)) => a.partial_cmp(b),
+            (Key::Int(a), Key::Float(b)) => (*a as f64).partial_cmp(b),
+            (Key::Float(a), Key::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Key::Text(a), Key::Text(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+impl Condition {
+    /// Whether a value can satisfy the condition; values that cannot be
+    /// compared are kept
+    pub fn matches(&self, value: &Ele) -> bool {
+        let Some(value) = Key::from_ele(value) else {
+            return true;
+        };
+        let cmp = |key: &Key, accept: fn(Ordering) -> bool| value.compare(key).is_none_or(accept);
+        match self {
+            Condition::Eq(key) => cmp(key, Ordering::is_eq),
+            Condition::NotEq(key) => cmp(key, Ordering::is_ne),
+            Condition::Lt(key) => cmp(key, Ordering::is_lt),
+            Condition::LtEq(key) => cmp(key, Ordering::is_le),
+            Condition::Gt(key) => cmp(key, Ordering::is_gt),
+            Condition::GtEq(key) => cmp(key, Ordering::is_ge),
+            Condition::In(keys) => keys
+                .iter()
+                .any(|key| value.compare(key).is_none_or(Ordering::is_eq)),
+        }
+    }
+}
+
+/// A condition on one column, one conjunct of a filter
+#[derive(Clone, Debug, PartialEq)]
+pub struct Predicate {
+    pub column: String,
+    pub condition: Condition,
+}
+
+impl Predicate {
+    /// The conjuncts of `filter` that can be checked against stored values
+    pub fn parse(filter: &Expr) -> Vec<Predicate> {
+        split_conjunction(filter)
+            .into_iter()
+            .flat_map(Self::parse_conjunct)
+            .collect()
+    }
+
+    /// The pushdown support to report for `filters`
+    pub fn support(filters: &[&Expr]) -> Vec<TableProviderFilterPushDown> {
+        filters
+            .iter()
+            .map(|filter| {
+                if Self::parse(filter).is_empty() {
+                    TableProviderFilterPushDown::Unsupported
+                } else {
+                    TableProviderFilterPushDown::Inexact
+                }
+            })
+            .collect()
+    }
+
+    fn parse_conjunct(expr: &Expr) -> Vec<Predicate> {
+        match expr {
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (column, value, op) = match (column_of(left), literal_of(right)) {
+                    (Some(column), Some(value)) => (column, value, *op),
+                    _ => match (literal_of(left), column_of(right)) {
+                        (Some(value), Some(column)) => match op.swap() {
+                            Some(op) => (column, value, op),
+                            None => return vec![],
+                        },
+                        _ => return vec![],
+                    },
+                };
+                let condition = match op {
+                    Operator::Eq => Condition::Eq(value),
+                    Operator::NotEq => Condition::NotEq(value),
+                    Operator::Lt => Condition::Lt(value),
+                    Operator::LtEq => Condition::LtEq(value),
+                    Operator::Gt => Condition::Gt(value),
+                    Operator::GtEq => Condition::GtEq(value),
+                    _ => return vec![],
+                };
+                vec![Predicate { column, condition }]
+            }
+            Expr::Between(Between {
+                expr,
+                negated: false,
+                low,
+                high,
+            }) => match (column_of(expr), literal_of(low), literal_of(high)) {
+                (Some(column), Some(low), Some(high)) => vec![
+                    Predicate {
+                        column: column.clone(),
+                        condition: Condition::GtEq(low),
+                    },
+                    Predicate {
+                        column,
+                        condition: Condition::LtEq(high),
+                    },
+                ],
+                _ => vec![],
+            },
+            Expr::InList(list) if !list.negated => {
+                let keys = list.list.iter().map(literal_of).collect::<Option<Vec<_>>>();
+                match (column_of(&list.expr), keys) {
+                    (Some(column), Some(keys)) => vec![Predicate {
+                        column,
+                        condition: Condition::In(keys),
+                    }],
+                    _ => vec![],
+                }
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/// The column an operand reads, seeing through `to_timestamp_ns`, which
+/// keeps the nanoseconds unchanged
+fn column_of(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Column(column) => Some(column.name.clone()),
+        Expr::ScalarFunction(func) if func.name() == "to_timestamp_ns" => match &func.args[..] {
+            [Expr::Column(column)] => Some(column.name.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn literal_of(expr: &Expr) -> Option<Key> {
+    match expr {
+        Expr::Literal(value) => Key::from_scalar(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::logical_expr::{col, lit};
+
+    #[test]
+    fn test_parse_filters() {
+        let filter = col("trace_id")
+            .eq(lit(7i64))
+            .and(lit(100i64).lt(col("time")))
+            .and(col("record_type").in_list(vec![lit("span_start"), lit("event")], false))
+            .and(col("name").like(lit("%step%")));
+
+        let predicates = Predicate::parse(&filter);
+        assert_eq!(
+            predicates,
+            vec![
+                Predicate {
+                    column: "trace_id".into(),
+                    condition: Condition::Eq(Key::Int(7)),
+                },
+                Predicate {
+                    column: "time".into(),
+                    condition: Condition::Gt(Key::Int(100)),
+                },
+                Predicate {
+                    column: "record_type".into(),
+                    condition: Condition::In(vec![
+                        Key::Text("span_start".into()),
+                        Key::Text("event".into())
+                    ]),
+                },
+            ]
+        );
+
+        let like = col("name").like(lit("%step%"));
+        let between = col("time").between(lit(1i64), lit(2i64));
+        assert_eq!(
+            Predicate::support(&[&like, &between]),
+            vec![
+                TableProviderFilterPushDown::Unsupported,
+                TableProviderFilterPushDown::Inexact
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conditions_keep_incomparable_values() {
+        let gt = Condition::Gt(Key::Int(100));
+        assert!(gt.matches(&Ele::I64(101)));
+        assert!(!gt.matches(&Ele::I64(100)));
+        assert!(gt.matches(&Ele::F64(100.5)));
+        assert!(gt.matches(&Ele::Nil));
+        assert!(gt.matches(&Ele::Text("a".into())));
+        assert!(gt.matches(&Ele::F64(f64::NAN)));
+
+        // datetimes are stored in microseconds, compared in nanoseconds
+        let at = Condition::Eq(Key::Int(5_000));
+        assert!(at.matches(&Ele::DataTime(5)));
+        assert!(!at.matches(&Ele::DataTime(6)));
+
+        let one_of = Condition::In(vec![Key::Text("event".into())]);
+        assert!(one_of.matches(&Ele::Text("event".into())));
+        assert!(!one_of.matches(&Ele::Text("span_end".into())));
+    }
+}
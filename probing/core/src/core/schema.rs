@@ -0,0 +1,113 @@
+//! Schemas of extension options.
+//!
+//! Extensions derived with `#[derive(EngineExtension)]` declare the type,
+//! allowed values and restart requirement of each option, and the schemas
+//! are registered along with the extension. Every write through
+//! [`EngineExtensionManager::set_option`](super::EngineExtensionManager::set_option)
+//! is validated here first: a value of the wrong type is rejected instead of
+//! unsetting the option, and a key unknown to a namespace that declares its
+//! options, such as `torch.profilng`, is rejected with the closest keys
+//! instead of landing in the config store where nothing reads it.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use probing_proto::prelude::{ConfigValidationError, OptionSchema};
+
+use super::diagnostics::suggest;
+
+static SCHEMAS: Lazy<RwLock<BTreeMap<String, OptionSchema>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Register the schemas of an extension, replacing those of the same keys
+pub fn register(schemas: Vec<OptionSchema>) {
+    let mut registered = SCHEMAS.write().unwrap();
+    for schema in schemas {
+        registered.insert(schema.key.clone(), schema);
+    }
+}
+
+/// All registered schemas, sorted by key
+pub fn all() -> Vec<OptionSchema> {
+    SCHEMAS.read().unwrap().values().cloned().collect()
+}
+
+/// Schema of the option `key`, without the `probing.` prefix, or of an alias
+pub fn lookup(key: &str) -> Option<OptionSchema> {
+    SCHEMAS
+        .read()
+        .unwrap()
+        .values()
+        .find(|schema| schema.key == key || schema.aliases.iter().any(|alias| alias == key))
+        .cloned()
+}
+
+/// Check a write of `value` to `key`, without the `probing.` prefix
+///
+/// Keys of namespaces without schemas, such as `slo.<span>`, are left to
+/// their extension.
+pub fn validate(key: &str, value: &str) -> Result<(), ConfigValidationError> {
+    if let Some(schema) = lookup(key) {
+        return schema.validate(key, value);
+    }
+    let Some((namespace, _)) = key.split_once('.') else {
+        return Ok(());
+    };
+    let prefix = format!("{namespace}.");
+    let known: Vec<String> = SCHEMAS
+        .read()
+        .unwrap()
+        .keys()
+        .filter(|known| known.starts_with(&prefix))
+        .map(|known| format!("probing.{known}"))
+        .collect();
+    if known.is_empty() {
+        return Ok(());
+    }
+    Err(ConfigValidationError {
+        key: key.to_string(),
+        value: value.to_string(),
+        reason: format!("{namespace} has no such option"),
+        suggestion: suggest(key, known),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use probing_proto::prelude::OptionKind;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        register(vec![
+            OptionSchema {
+                key: "schematest.profiling".to_string(),
+                aliases: vec!["schematest.profiling_mode".to_string()],
+                allowed: vec!["on".to_string(), "off".to_string()],
+                ..Default::default()
+            },
+            OptionSchema {
+                key: "schematest.interval".to_string(),
+                kind: OptionKind::UInt,
+                ..Default::default()
+            },
+        ]);
+
+        assert!(validate("schematest.profiling", "on").is_ok());
+        assert!(validate("schematest.profiling_mode", "off").is_ok());
+        assert!(validate("schematest.interval", "10").is_ok());
+        assert!(validate("unknownns.anything", "1").is_ok());
+
+        let err = validate("schematest.interval", "often").unwrap_err();
+        assert_eq!(err.reason, "expected a uint value");
+
+        let err = validate("schematest.profilng", "on").unwrap_err();
+        assert_eq!(err.reason, "schematest has no such option");
+        assert_eq!(
+            err.suggestion.as_deref(),
+            Some("did you mean 'probing.schematest.profiling'?")
+        );
+    }
+}
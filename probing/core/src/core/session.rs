@@ -0,0 +1,213 @@
+//! Temporary tables scoped to a query session.
+//!
+//! A multi-step analysis can stage intermediate results without creating
+//! tables every other client sees:
+//!
+//! ```sql
+//! CREATE TEMP TABLE slow AS SELECT * FROM python.trace_event WHERE duration > 1e6;
+//! SELECT name, count(*) FROM slow GROUP BY name;
+//! ```
+//!
+//! Temporary tables resolve as unqualified names in queries run with the same
+//! session id, an HTTP client's session or a named REPL session, and shadow
+//! tables of the default namespace. They are dropped when the session is
+//! closed or has been idle for [`SESSION_TTL`].
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use datafusion::catalog::{CatalogProvider, MemorySchemaProvider, SchemaProvider};
+use datafusion::datasource::TableProvider;
+use datafusion::error::Result;
+
+/// How long the temporary tables of an idle session are kept
+pub const SESSION_TTL: Duration = Duration::from_secs(1800);
+
+struct Entry {
+    tables: Arc<MemorySchemaProvider>,
+    last_used: Instant,
+}
+
+/// Temporary tables of every open session, by session id
+pub struct Sessions {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        Self::new(SESSION_TTL)
+    }
+}
+
+impl Sessions {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Default::default(),
+        }
+    }
+
+    /// Temporary tables of `session`, opening it on first use
+    pub fn tables(&self, session: &str) -> Arc<MemorySchemaProvider> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|id, entry| {
+            let alive = id == session || now.duration_since(entry.last_used) < self.ttl;
+            if !alive {
+                log::info!("query session {id} expired");
+            }
+            alive
+        });
+        let entry = entries.entry(session.to_string()).or_insert_with(|| Entry {
+            tables: Arc::new(MemorySchemaProvider::new()),
+            last_used: now,
+        });
+        entry.last_used = now;
+        entry.tables.clone()
+    }
+
+    /// Close `session`, returning the names of the tables dropped with it
+    pub fn close(&self, session: &str) -> Vec<String> {
+        let Some(entry) = self.entries.lock().unwrap().remove(session) else {
+            return vec![];
+        };
+        let mut names = entry.tables.table_names();
+        names.sort();
+        log::info!("query session {session} closed, dropped {names:?}");
+        names
+    }
+
+    /// Ids of the open sessions
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids = self
+            .entries
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+}
+
+/// Catalog presenting the temporary tables of a session on top of the
+/// default namespace of `inner`
+#[derive(Debug)]
+pub(crate) struct SessionCatalog {
+    pub inner: Arc<dyn CatalogProvider>,
+    pub default_schema: String,
+    pub temp: Arc<MemorySchemaProvider>,
+}
+
+impl CatalogProvider for SessionCatalog {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema_names(&self) -> Vec<String> {
+        let mut names = self.inner.schema_names();
+        if !names.contains(&self.default_schema) {
+            names.push(self.default_schema.clone());
+        }
+        names
+    }
+
+    fn schema(&self, name: &str) -> Option<Arc<dyn SchemaProvider>> {
+        if name != self.default_schema {
+            return self.inner.schema(name);
+        }
+        Some(Arc::new(SessionSchema {
+            temp: self.temp.clone(),
+            inner: self.inner.schema(name),
+        }))
+    }
+
+    fn register_schema(
+        &self,
+        name: &str,
+        schema: Arc<dyn SchemaProvider>,
+    ) -> Result<Option<Arc<dyn SchemaProvider>>> {
+        self.inner.register_schema(name, schema)
+    }
+}
+
+/// Default namespace of a session, temporary tables first
+#[derive(Debug)]
+struct SessionSchema {
+    temp: Arc<MemorySchemaProvider>,
+    inner: Option<Arc<dyn SchemaProvider>>,
+}
+
+#[async_trait]
+impl SchemaProvider for SessionSchema {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        let mut names = self.temp.table_names();
+        if let Some(inner) = &self.inner {
+            for name in inner.table_names() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    async fn table(&self, name: &str) -> Result<Option<Arc<dyn TableProvider>>> {
+        if let Some(table) = self.temp.table(name).await? {
+            return Ok(Some(table));
+        }
+        match &self.inner {
+            Some(inner) => inner.table(name).await,
+            None => Ok(None),
+        }
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        self.temp.table_exist(name)
+            || self
+                .inner
+                .as_ref()
+                .is_some_and(|inner| inner.table_exist(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use datafusion::datasource::MemTable;
+
+    #[test]
+    fn test_sessions_expire_and_close() {
+        let sessions = Sessions::new(Duration::from_millis(20));
+        let table = MemTable::try_new(
+            Arc::new(datafusion::arrow::datatypes::Schema::empty()),
+            vec![vec![]],
+        )
+        .unwrap();
+        sessions
+            .tables("a")
+            .register_table("staged".to_string(), Arc::new(table))
+            .unwrap();
+        sessions.tables("b");
+        assert_eq!(sessions.ids(), vec!["a", "b"]);
+
+        std::thread::sleep(Duration::from_millis(30));
+        // touching `b` expires the idle `a`
+        sessions.tables("b");
+        assert_eq!(sessions.ids(), vec!["b"]);
+        assert!(sessions.close("a").is_empty());
+
+        assert!(sessions.tables("b").table_names().is_empty());
+        assert_eq!(sessions.close("b"), Vec::<String>::new());
+        assert!(sessions.ids().is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+//! Shaping of query results requested with the query options.
+//!
+//! Dashboards often want "these columns, 1% of the rows, text cut to 256
+//! characters" of a query. Instead of wrapping every query in SQL doing so,
+//! they set `columns`, `sample_rate` and `max_cell_chars` in the options and
+//! the engine applies them while collecting the result: the projection and
+//! the limit are added to the plan, rows are sampled and cells cut batch by
+//! batch as they are produced, so the whole result is never held.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, AsArray, BooleanArray, LargeStringArray, RecordBatch, StringArray,
+    StringViewArray,
+};
+use arrow::compute::filter_record_batch;
+use arrow::datatypes::DataType;
+use datafusion::dataframe::DataFrame;
+use datafusion::error::{DataFusionError, Result};
+use futures::StreamExt;
+use probing_proto::prelude::QueryOptions;
+
+/// Marks a text cell cut to `max_cell_chars`
+pub const ELLIPSIS: char = '…';
+
+/// Columns, rows and cell sizes of a result, see the [module docs](self)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResultShape {
+    /// Columns to keep, in this order
+    pub columns: Option<Vec<String>>,
+    /// Fraction of the rows to keep, in `(0, 1]`
+    pub sample_rate: Option<f64>,
+    /// Characters kept of a text cell
+    pub max_cell_chars: Option<usize>,
+    /// Rows to keep, after sampling
+    pub limit: Option<usize>,
+}
+
+impl From<&QueryOptions> for ResultShape {
+    fn from(opts: &QueryOptions) -> Self {
+        ResultShape {
+            columns: opts.columns.clone(),
+            sample_rate: opts.sample_rate,
+            max_cell_chars: opts.max_cell_chars,
+            limit: opts.limit,
+        }
+    }
+}
+
+impl ResultShape {
+    /// Whether results are returned as the query produces them
+    pub fn is_identity(&self) -> bool {
+        *self == ResultShape::default()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(rate) = self.sample_rate {
+            if !(rate > 0.0 && rate <= 1.0) {
+                return Err(DataFusionError::Plan(format!(
+                    "sample_rate must be in (0, 1], got {rate}"
+                )));
+            }
+        }
+        if matches!(&self.columns, Some(columns) if columns.is_empty()) {
+            return Err(DataFusionError::Plan("columns must not be empty".into()));
+        }
+        Ok(())
+    }
+
+    /// Collect `df` shaped
+    pub async fn collect(&self, df: DataFrame) -> Result<Vec<RecordBatch>> {
+        if self.is_identity() {
+            return df.collect().await;
+        }
+        self.validate()?;
+        let mut df = df;
+        if let Some(columns) = &self.columns {
+            let columns: Vec<_> = columns.iter().map(String::as_str).collect();
+            df = df.select_columns(&columns)?;
+        }
+        // without sampling the limit is pushed down to the scans
+        let sampled = matches!(self.sample_rate, Some(rate) if rate < 1.0);
+        if !sampled && self.limit.is_some() {
+            df = df.limit(0, self.limit)?;
+        }
+
+        let mut stream = df.execute_stream().await?;
+        let mut batches = vec![];
+        let mut rows = 0;
+        while let Some(batch) = stream.next().await {
+            let mut batch = batch?;
+            if sampled {
+                batch = sample(&batch, self.sample_rate.unwrap_or(1.0))?;
+            }
+            if let Some(limit) = self.limit {
+                if rows + batch.num_rows() >= limit {
+                    batches.push(self.cut(batch.slice(0, limit - rows))?);
+                    break;
+                }
+            }
+            rows += batch.num_rows();
+            batches.push(self.cut(batch)?);
+        }
+        Ok(batches)
+    }
+
+    /// Cut the text cells of `batch` to `max_cell_chars`
+    fn cut(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let Some(max) = self.max_cell_chars else {
+            return Ok(batch);
+        };
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|column| cut_column(column, max))
+            .collect();
+        Ok(RecordBatch::try_new(batch.schema(), columns)?)
+    }
+}
+
+fn cut_text(text: &str, max: usize) -> std::borrow::Cow<'_, str> {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}{ELLIPSIS}", &text[..end]).into(),
+        None => text.into(),
+    }
+}
+
+fn cut(value: Option<&str>, max: usize) -> Option<std::borrow::Cow<'_, str>> {
+    value.map(|value| cut_text(value, max))
+}
+
+fn cut_column(column: &ArrayRef, max: usize) -> ArrayRef {
+    match column.data_type() {
+        DataType::Utf8 => Arc::new(
+            column
+                .as_string::<i32>()
+                .iter()
+                .map(|v| cut(v, max))
+                .collect::<StringArray>(),
+        ),
+        DataType::LargeUtf8 => Arc::new(
+            column
+                .as_string::<i64>()
+                .iter()
+                .map(|v| cut(v, max))
+                .collect::<LargeStringArray>(),
+        ),
+        DataType::Utf8View => Arc::new(
+            column
+                .as_string_view()
+                .iter()
+                .map(|v| cut(v, max))
+                .collect::<StringViewArray>(),
+        ),
+        _ => column.clone(),
+    }
+}
+
+/// Keep each row of `batch` with probability `rate`
+fn sample(batch: &RecordBatch, rate: f64) -> Result<RecordBatch> {
+    let threshold = (rate * u64::MAX as f64) as u64;
+    let keep: BooleanArray = (0..batch.num_rows())
+        .map(|_| Some(next_random() <= threshold))
+        .collect();
+    Ok(filter_record_batch(batch, &keep)?)
+}
+
+/// splitmix64, sampling needs no better
+fn next_random() -> u64 {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let mut state = STATE.load(Ordering::Relaxed);
+    if state == 0 {
+        state = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64
+            | 1;
+    }
+    let next = state.wrapping_add(0x9e3779b97f4a7c15);
+    STATE.store(next, Ordering::Relaxed);
+    let mut z = next;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use datafusion::prelude::SessionContext;
+
+    async fn query(shape: &ResultShape) -> Result<Vec<RecordBatch>> {
+        let ids = Int64Array::from_iter_values(1..=1000);
+        let texts = StringArray::from_iter_values((1..=1000).map(|i| "x".repeat(i % 10)));
+        let batch = RecordBatch::try_from_iter(vec![
+            ("id", Arc::new(ids) as ArrayRef),
+            ("text", Arc::new(texts) as ArrayRef),
+        ])?;
+        let df = SessionContext::new().read_batch(batch)?;
+        shape.collect(df).await
+    }
+
+    fn rows(batches: &[RecordBatch]) -> usize {
+        batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    #[tokio::test]
+    async fn test_shape() {
+        assert_eq!(rows(&query(&ResultShape::default()).await.unwrap()), 1000);
+
+        let batches = query(&ResultShape {
+            columns: Some(vec!["text".into()]),
+            max_cell_chars: Some(4),
+            limit: Some(10),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(rows(&batches), 10);
+        assert_eq!(batches[0].num_columns(), 1);
+        let text = batches[0].column(0).as_string::<i32>();
+        assert_eq!(text.value(0), "x");
+        assert_eq!(text.value(8), "xxxx…");
+
+        let batches = query(&ResultShape {
+            sample_rate: Some(0.1),
+            limit: Some(900),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let sampled = rows(&batches);
+        assert!(sampled > 20 && sampled < 300, "{sampled}");
+
+        let invalid = ResultShape {
+            sample_rate: Some(0.0),
+            ..Default::default()
+        };
+        assert!(query(&invalid).await.is_err());
+        let unknown = ResultShape {
+            columns: Some(vec!["nope".into()]),
+            ..Default::default()
+        };
+        assert!(query(&unknown).await.is_err());
+    }
+
+    #[test]
+    fn test_cut_text() {
+        assert_eq!(cut_text("héllo", 2), "hé…");
+        assert_eq!(cut_text("héllo", 5), "héllo");
+        assert_eq!(cut_text("", 0), "");
+    }
+}
@@ -0,0 +1,238 @@
+//! Jobs run by Slurm.
+//!
+//! `srun` describes the job and the task of each process in `SLURM_*`
+//! variables: the job id and name, the partition, the compressed list of
+//! the nodes allocated to the job (`gpu[01-04,07]`) and the rank of the task
+//! (`SLURM_PROCID`). They are served as `slurm.job`.
+//!
+//! Knowing the nodes of the job, rank 0 records them in the cluster registry
+//! as `expected` before their ranks report in, so a node that never starts
+//! its probe shows up as missing rather than not at all. The nodelist is
+//! expanded with `scontrol show hostnames`, or by [`expand_nodelist`] where
+//! `scontrol` is not installed, e.g. in a container.
+
+use std::process::Command;
+use std::sync::LazyLock;
+
+use probing_proto::prelude::Node;
+pub use probing_proto::prelude::EXPECTED;
+
+/// Job and task of this process from the Slurm environment
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SlurmJob {
+    pub job_id: String,
+    pub job_name: Option<String>,
+    pub partition: Option<String>,
+    pub cluster_name: Option<String>,
+    /// Compressed list of the nodes of the job, e.g. `gpu[01-04,07]`
+    pub nodelist: Option<String>,
+    pub nnodes: Option<i32>,
+    pub ntasks: Option<i32>,
+    /// Rank of the task in the job
+    pub procid: Option<i32>,
+    /// Rank of the task on its node
+    pub localid: Option<i32>,
+    /// Index of the node of the task in the nodelist
+    pub nodeid: Option<i32>,
+}
+
+static SLURM_JOB: LazyLock<Option<SlurmJob>> =
+    LazyLock::new(|| resolve(|name| std::env::var(name).ok()));
+
+/// Slurm job of this process, `None` outside of Slurm
+pub fn job() -> Option<SlurmJob> {
+    SLURM_JOB.clone()
+}
+
+/// Slurm job from the variables returned by `env`, `None` without
+/// `SLURM_JOB_ID`
+pub fn resolve(env: impl Fn(&str) -> Option<String>) -> Option<SlurmJob> {
+    let var = |name: &str| {
+        env(name)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let int = |name: &str| var(name).and_then(|v| v.parse().ok());
+    Some(SlurmJob {
+        job_id: var("SLURM_JOB_ID").or_else(|| var("SLURM_JOBID"))?,
+        job_name: var("SLURM_JOB_NAME"),
+        partition: var("SLURM_JOB_PARTITION"),
+        cluster_name: var("SLURM_CLUSTER_NAME"),
+        nodelist: var("SLURM_JOB_NODELIST").or_else(|| var("SLURM_NODELIST")),
+        nnodes: int("SLURM_JOB_NUM_NODES").or_else(|| int("SLURM_NNODES")),
+        ntasks: int("SLURM_NTASKS"),
+        procid: int("SLURM_PROCID"),
+        localid: int("SLURM_LOCALID"),
+        nodeid: int("SLURM_NODEID"),
+    })
+}
+
+impl SlurmJob {
+    /// First node of the job, where its first task runs
+    pub fn first_host(&self) -> Option<String> {
+        let nodelist = self.nodelist.as_deref()?;
+        expand_nodelist(nodelist).ok()?.into_iter().next()
+    }
+}
+
+/// Host names of a compressed Slurm nodelist, e.g. `gpu[01-02],cpu7` is
+/// `gpu01`, `gpu02` and `cpu7`. Zero padding of the ranges is kept and
+/// names may hold several bracketed ranges, `r[1-2]n[1-2]`.
+pub fn expand_nodelist(nodelist: &str) -> Result<Vec<String>, String> {
+    let mut hosts = vec![];
+    for item in split_top_level(nodelist) {
+        let item = item.trim();
+        if !item.is_empty() {
+            hosts.extend(expand_item(item)?);
+        }
+    }
+    Ok(hosts)
+}
+
+/// Split at the commas outside of brackets
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&list[start..]);
+    items
+}
+
+fn expand_item(item: &str) -> Result<Vec<String>, String> {
+    let Some(open) = item.find('[') else {
+        return Ok(vec![item.to_string()]);
+    };
+    let close = item[open..]
+        .find(']')
+        .map(|i| open + i)
+        .ok_or_else(|| format!("unclosed bracket in nodelist `{item}`"))?;
+    let (prefix, ranges, rest) = (&item[..open], &item[open + 1..close], &item[close + 1..]);
+    let suffixes = expand_item(rest)?;
+
+    let mut hosts = vec![];
+    for range in ranges.split(',') {
+        let (lo, hi) = range.split_once('-').unwrap_or((range, range));
+        let invalid = || format!("invalid range `{range}` in nodelist `{item}`");
+        let (start, end) = (
+            lo.parse::<u64>().map_err(|_| invalid())?,
+            hi.parse::<u64>().map_err(|_| invalid())?,
+        );
+        if end < start {
+            return Err(invalid());
+        }
+        for n in start..=end {
+            for suffix in &suffixes {
+                hosts.push(format!("{prefix}{n:0width$}{suffix}", width = lo.len()));
+            }
+        }
+    }
+    Ok(hosts)
+}
+
+/// Host names of `nodelist` from `scontrol show hostnames`, or from
+/// [`expand_nodelist`] when `scontrol` cannot be run
+pub fn hosts(nodelist: &str) -> Result<Vec<String>, String> {
+    let output = Command::new("scontrol")
+        .args(["show", "hostnames", nodelist])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|host| !host.is_empty())
+            .map(str::to_string)
+            .collect()),
+        _ => expand_nodelist(nodelist),
+    }
+}
+
+/// Placeholder nodes, one per host of the job, with the status
+/// [`EXPECTED`] until a rank of the host reports
+pub fn expected_nodes(job: &SlurmJob, job_id: Option<String>) -> Vec<Node> {
+    let Some(nodelist) = &job.nodelist else {
+        return vec![];
+    };
+    let hosts = match hosts(nodelist) {
+        Ok(hosts) => hosts,
+        Err(e) => {
+            log::warn!("Failed to expand the Slurm nodelist: {e}");
+            return vec![];
+        }
+    };
+    hosts
+        .into_iter()
+        .map(|host| Node {
+            host,
+            world_size: job.ntasks,
+            status: Some(EXPECTED.to_string()),
+            job_id: job_id.clone(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_nodelist() {
+        assert_eq!(
+            expand_nodelist("gpu[01-03,07],cpu7").unwrap(),
+            ["gpu01", "gpu02", "gpu03", "gpu07", "cpu7"]
+        );
+        assert_eq!(
+            expand_nodelist("r[1-2]n[8-9]-ib").unwrap(),
+            ["r1n8-ib", "r1n9-ib", "r2n8-ib", "r2n9-ib"]
+        );
+        assert_eq!(expand_nodelist("node[098-101]").unwrap()[2], "node100");
+        assert!(expand_nodelist("").unwrap().is_empty());
+        assert!(expand_nodelist("gpu[01-").is_err());
+        assert!(expand_nodelist("gpu[3-1]").is_err());
+        assert!(expand_nodelist("gpu[a-b]").is_err());
+    }
+
+    #[test]
+    fn test_resolve_slurm_job() {
+        let vars = [
+            ("SLURM_JOB_ID", "4242"),
+            ("SLURM_JOB_NAME", "pretrain"),
+            ("SLURM_NODELIST", "gpu[01-02]"),
+            ("SLURM_NTASKS", "16"),
+            ("SLURM_PROCID", "9"),
+            ("SLURM_LOCALID", "1"),
+        ];
+        let env = |name: &str| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        };
+        let job = resolve(env).unwrap();
+        assert_eq!(job.job_id, "4242");
+        assert_eq!(job.nodelist.as_deref(), Some("gpu[01-02]"));
+        assert_eq!(
+            (job.procid, job.localid, job.nodeid),
+            (Some(9), Some(1), None)
+        );
+        assert_eq!(resolve(|_| None), None);
+
+        let nodes = expected_nodes(&job, Some("4242".to_string()));
+        let hosts: Vec<&str> = nodes.iter().map(|n| n.host.as_str()).collect();
+        assert_eq!(hosts, ["gpu01", "gpu02"]);
+        assert_eq!(nodes[0].status.as_deref(), Some(EXPECTED));
+        assert_eq!(nodes[0].job_id.as_deref(), Some("4242"));
+        assert_eq!(nodes[0].world_size, Some(16));
+        assert!(expected_nodes(&SlurmJob::default(), None).is_empty());
+        assert_eq!(job.first_host().as_deref(), Some("gpu01"));
+    }
+}
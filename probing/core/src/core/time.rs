@@ -0,0 +1,111 @@
+//! Time helpers for SQL queries.
+//!
+//! Tables built from Rust expose their `time` columns as timezone-aware
+//! nanosecond timestamps, see [`timestamp_ns_array`]. Trace tables store
+//! timestamps as raw nanoseconds since the unix epoch; `to_timestamp_ns(ts)`
+//! turns them into the same type so that time windows can be expressed
+//! naturally:
+//!
+//! ```sql
+//! SELECT name FROM python.trace_event
+//! WHERE to_timestamp_ns(time) > now() - INTERVAL '5 minutes'
+//! ```
+
+use std::sync::Arc;
+
+use arrow::array::{Array, Int64Array, TimestampNanosecondArray};
+use arrow::datatypes::{DataType, TimeUnit};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDF, Signature, SimpleScalarUDF, Volatility};
+
+/// Timezone attached to timestamps produced by the engine.
+pub const TIMEZONE: &str = "UTC";
+
+/// Arrow type of nanosecond timestamps produced by the engine.
+pub fn timestamp_ns_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Nanosecond, Some(TIMEZONE.into()))
+}
+
+/// Column of [`timestamp_ns_type`] from nanoseconds since the unix epoch
+pub fn timestamp_ns_array(nanos: impl IntoIterator<Item = i64>) -> TimestampNanosecondArray {
+    TimestampNanosecondArray::from_iter_values(nanos).with_timezone(TIMEZONE)
+}
+
+/// Column of [`timestamp_ns_type`] from microseconds since the unix epoch
+pub fn timestamp_us_array(micros: impl IntoIterator<Item = i64>) -> TimestampNanosecondArray {
+    timestamp_ns_array(micros.into_iter().map(|us| us.saturating_mul(1_000)))
+}
+
+/// SQL function `to_timestamp_ns(ts)` interpreting an integer as nanoseconds
+/// since the unix epoch and returning a `Timestamp(Nanosecond, UTC)`.
+pub fn to_timestamp_ns_udf() -> ScalarUDF {
+    ScalarUDF::from(SimpleScalarUDF::new_with_signature(
+        "to_timestamp_ns",
+        Signature::uniform(1, vec![DataType::Int64], Volatility::Immutable),
+        timestamp_ns_type(),
+        Arc::new(to_timestamp_ns_impl),
+    ))
+}
+
+fn to_timestamp_ns_impl(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    let arrays = ColumnarValue::values_to_arrays(args)?;
+    let nanos = arrays[0]
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .ok_or_else(|| DataFusionError::Execution("to_timestamp_ns: ts must be BIGINT".into()))?;
+
+    let timestamps = TimestampNanosecondArray::new(nanos.values().clone(), nanos.nulls().cloned())
+        .with_timezone(TIMEZONE);
+    Ok(ColumnarValue::Array(Arc::new(timestamps)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_arrays() {
+        let ns = timestamp_ns_array([1_700_000_000_123_456_789]);
+        let us = timestamp_us_array([1_700_000_000_123_456, i64::MAX]);
+        assert_eq!(ns.data_type(), &timestamp_ns_type());
+        assert_eq!(us.data_type(), &timestamp_ns_type());
+        assert_eq!(ns.value(0), 1_700_000_000_123_456_789);
+        assert_eq!(us.value(0), 1_700_000_000_123_456_000);
+        assert_eq!(us.value(1), i64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_to_timestamp_ns_in_where_clause() {
+        let ctx = datafusion::prelude::SessionContext::new();
+        ctx.register_udf(to_timestamp_ns_udf());
+        ctx.sql("CREATE TABLE t (name VARCHAR, time BIGINT) AS VALUES ('old', 1000000000), ('new', 1700000000123456789)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        let batches = ctx
+            .sql(
+                "SELECT name, to_timestamp_ns(time) AS ts FROM t \
+                 WHERE to_timestamp_ns(time) > TIMESTAMP '2023-01-01T00:00:00Z'",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        assert_eq!(batches[0].num_rows(), 1);
+        assert_eq!(
+            batches[0].schema().field(1).data_type(),
+            &timestamp_ns_type()
+        );
+        let ts = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<TimestampNanosecondArray>()
+            .unwrap();
+        assert_eq!(ts.value(0), 1_700_000_000_123_456_789);
+    }
+}
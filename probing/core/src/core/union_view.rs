@@ -0,0 +1,421 @@
+//! Views that union the same logical table across storage tiers.
+//!
+//! A union view such as `trace.all_events` combines the in-memory table with
+//! its on-disk history (`python.trace_event` and `archive.trace_event`), so
+//! queries do not have to spell out `UNION ALL` across tiers. Members may have
+//! drifted apart: columns are matched by name, columns missing from a member
+//! are filled with nulls and diverging types are widened. Each row carries the
+//! member it was read from in the `_source` column.
+//!
+//! ```sql
+//! SELECT _source, count(*) FROM trace.all_events GROUP BY _source
+//! ```
+//!
+//! Projections every query repeats can be defined once as computed columns,
+//! evaluated when the view is planned, and the columns listed first can be
+//! chosen, see [`set_computed_columns`] and [`set_column_order`]:
+//!
+//! ```sql
+//! SET trace.columns='time_ms = time / 1e6; module = split_part(name, ''.'', 1)';
+//! SET trace.column_order='time_ms,module,name';
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::catalog::MemorySchemaProvider;
+use datafusion::common::{Column, ScalarValue, TableReference};
+use datafusion::datasource::{MemTable, TableProvider};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{cast, lit, Expr, LogicalPlan};
+use datafusion::prelude::{DataFrame, SessionContext};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+
+/// Name of the column holding the member a row was read from
+pub const SOURCE_COLUMN: &str = "_source";
+
+/// A column computed from the other columns of a view
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputedColumn {
+    pub name: String,
+    /// SQL expression over the view columns
+    pub expr: String,
+}
+
+/// Computed columns of a view and the columns it lists first
+#[derive(Debug, Clone, Default)]
+struct Layout {
+    computed: Vec<ComputedColumn>,
+    order: Vec<String>,
+}
+
+static LAYOUTS: LazyLock<RwLock<HashMap<String, Layout>>> = LazyLock::new(Default::default);
+
+/// Parse `name = expr` definitions separated by `;`, quoted `;` excluded
+pub fn parse_computed_columns(spec: &str) -> Result<Vec<ComputedColumn>> {
+    let mut definitions = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in spec.chars() {
+        match c {
+            '\'' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ';' if !quoted => definitions.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    definitions.push(current);
+
+    definitions
+        .iter()
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty())
+        .map(|definition| {
+            let invalid = |reason: String| {
+                DataFusionError::Plan(format!("invalid column `{definition}`: {reason}"))
+            };
+            let (name, expr) = definition
+                .split_once('=')
+                .ok_or_else(|| invalid("expected `name = expr`".to_string()))?;
+            let (name, expr) = (name.trim(), expr.trim());
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(invalid(format!("`{name}` is not a column name")));
+            }
+            Parser::new(&GenericDialect {})
+                .try_with_sql(expr)
+                .and_then(|mut parser| parser.parse_expr())
+                .map_err(|e| invalid(e.to_string()))?;
+            Ok(ComputedColumn {
+                name: name.to_string(),
+                expr: expr.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Add the columns defined by `spec` to `view` when it is planned, see
+/// [`parse_computed_columns`]; an empty `spec` removes them
+pub fn set_computed_columns(view: &str, spec: &str) -> Result<()> {
+    let computed = parse_computed_columns(spec)?;
+    LAYOUTS
+        .write()
+        .unwrap()
+        .entry(view.to_string())
+        .or_default()
+        .computed = computed;
+    Ok(())
+}
+
+/// List the comma separated `columns` first in `view`, the others follow in
+/// their current order
+pub fn set_column_order(view: &str, columns: &str) {
+    LAYOUTS
+        .write()
+        .unwrap()
+        .entry(view.to_string())
+        .or_default()
+        .order = columns
+        .split(',')
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+}
+
+/// A view unioning `members`, rebuilt whenever a query references it
+#[derive(Debug, Clone)]
+pub struct UnionView {
+    /// `namespace.table` of the view
+    pub name: String,
+    /// `namespace.table` of the members, in the order their columns are listed
+    pub members: Vec<String>,
+}
+
+impl UnionView {
+    pub fn new<S: Into<String>>(name: S, members: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            members,
+        }
+    }
+
+    fn reference(&self) -> TableReference {
+        TableReference::from(self.name.as_str())
+    }
+
+    /// Whether `table` names this view, resolving unqualified names against
+    /// `default_namespace`
+    pub fn is_referenced_by(&self, table: &TableReference, default_namespace: &str) -> bool {
+        let view = self.reference();
+        table.table() == view.table()
+            && table.schema().unwrap_or(default_namespace) == view.schema().unwrap_or("probe")
+    }
+
+    /// Re-plan the view from the current member tables and register it,
+    /// returning the plan of the view, `None` while no member exists
+    pub async fn refresh(&self, context: &SessionContext) -> Result<Option<LogicalPlan>> {
+        let mut members = vec![];
+        for member in &self.members {
+            // members, or even their namespaces, may not be created yet
+            if matches!(context.table_exist(member.as_str()), Ok(true)) {
+                members.push((member.as_str(), context.table(member.as_str()).await?));
+            }
+        }
+
+        let fields = unified_fields(&members);
+        let mut view: Option<DataFrame> = None;
+        for (member, df) in members {
+            let projection = project(member, &df, &fields);
+            let df = df.select(projection)?;
+            view = Some(match view {
+                Some(view) => view.union(df)?,
+                None => df,
+            });
+        }
+
+        match view.map(|view| self.layout(view)) {
+            Some(view) => {
+                let plan = view.logical_plan().clone();
+                self.register(context, view.into_view())?;
+                Ok(Some(plan))
+            }
+            None => {
+                self.register_empty(context)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Add the computed columns and order the columns of the view; columns
+    /// that do not fit the current members are left out
+    fn layout(&self, mut view: DataFrame) -> DataFrame {
+        let Some(layout) = LAYOUTS.read().unwrap().get(&self.name).cloned() else {
+            return view;
+        };
+        for column in &layout.computed {
+            let computed = view
+                .parse_sql_expr(&column.expr)
+                .and_then(|expr| view.clone().with_column(&column.name, expr));
+            match computed {
+                Ok(computed) => view = computed,
+                Err(e) => log::warn!("Skipping column {} of {}: {e}", column.name, self.name),
+            }
+        }
+        if layout.order.is_empty() {
+            return view;
+        }
+        let names: Vec<String> = view
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        let columns: Vec<&str> = layout
+            .order
+            .iter()
+            .filter(|c| names.contains(c))
+            .chain(names.iter().filter(|n| !layout.order.contains(n)))
+            .map(String::as_str)
+            .collect();
+        match view.clone().select_columns(&columns) {
+            Ok(ordered) => ordered,
+            Err(e) => {
+                log::warn!("Failed to order the columns of {}: {e}", self.name);
+                view
+            }
+        }
+    }
+
+    /// Register the view without rows, keeping it queryable before any member exists
+    pub fn register_empty(&self, context: &SessionContext) -> Result<()> {
+        let schema = Schema::new(vec![Field::new(SOURCE_COLUMN, DataType::Utf8, false)]);
+        let empty = MemTable::try_new(Arc::new(schema), vec![vec![]])?;
+        self.register(context, Arc::new(empty))
+    }
+
+    fn register(&self, context: &SessionContext, table: Arc<dyn TableProvider>) -> Result<()> {
+        let reference = self.reference();
+        ensure_namespace(context, &reference)?;
+        context.deregister_table(reference.clone())?;
+        context.register_table(reference, table)?;
+        Ok(())
+    }
+}
+
+/// Columns of the view: every member column by name, in order of first appearance
+fn unified_fields(members: &[(&str, DataFrame)]) -> Vec<(String, DataType)> {
+    let mut fields: Vec<(String, DataType)> = vec![];
+    for (_, df) in members {
+        for field in df.schema().fields() {
+            match fields.iter_mut().find(|(name, _)| name == field.name()) {
+                Some((_, dtype)) => *dtype = widen(dtype, field.data_type()),
+                None => fields.push((field.name().clone(), field.data_type().clone())),
+            }
+        }
+    }
+    fields.retain(|(name, _)| name != SOURCE_COLUMN);
+    fields
+}
+
+/// Common type of a column whose type differs between members
+fn widen(a: &DataType, b: &DataType) -> DataType {
+    match (a, b) {
+        (a, b) if a == b => a.clone(),
+        (DataType::Null, other) | (other, DataType::Null) => other.clone(),
+        (a, b) if a.is_integer() && b.is_integer() => DataType::Int64,
+        (a, b) if a.is_numeric() && b.is_numeric() => DataType::Float64,
+        (DataType::Timestamp(unit, _), DataType::Timestamp(_, _)) => {
+            DataType::Timestamp(*unit, Some(super::time::TIMEZONE.into()))
+        }
+        _ => DataType::Utf8,
+    }
+}
+
+/// Project a member onto the view columns
+fn project(member: &str, df: &DataFrame, fields: &[(String, DataType)]) -> Vec<Expr> {
+    let schema = df.schema();
+    let mut exprs: Vec<Expr> = fields
+        .iter()
+        .map(|(name, dtype)| {
+            let expr = match schema.field_with_unqualified_name(name) {
+                Ok(field) if field.data_type() == dtype => {
+                    Expr::Column(Column::new_unqualified(name))
+                }
+                Ok(_) => cast(Expr::Column(Column::new_unqualified(name)), dtype.clone()),
+                Err(_) => lit(ScalarValue::try_from(dtype).unwrap_or(ScalarValue::Null)),
+            };
+            expr.alias(name)
+        })
+        .collect();
+    exprs.push(lit(member).alias(SOURCE_COLUMN));
+    exprs
+}
+
+fn ensure_namespace(context: &SessionContext, reference: &TableReference) -> Result<()> {
+    let Some(namespace) = reference.schema() else {
+        return Ok(());
+    };
+    let catalog = context
+        .catalog("probe")
+        .ok_or_else(|| DataFusionError::Internal("no catalog `probe`".to_string()))?;
+    if catalog.schema(namespace).is_none() {
+        catalog.register_schema(namespace, Arc::new(MemorySchemaProvider::new()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Engine;
+
+    async fn engine() -> Engine {
+        Engine::builder()
+            .with_union_view("trace.all_events", &["live.events", "archive.events"])
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_union_view_before_members_exist() {
+        let engine = engine().await;
+        let df = engine
+            .async_query("SELECT * FROM trace.all_events")
+            .await
+            .unwrap();
+        assert!(df.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_union_view_handles_schema_evolution() {
+        let engine = engine().await;
+        for sql in [
+            "CREATE SCHEMA live",
+            "CREATE SCHEMA archive",
+            // the live table gained a `cost` column and widened `id`
+            "CREATE TABLE live.events (id BIGINT, name VARCHAR, cost DOUBLE)",
+            "CREATE TABLE archive.events (id INT, name VARCHAR)",
+            "INSERT INTO live.events VALUES (3, 'c', 1.5)",
+            "INSERT INTO archive.events VALUES (1, 'a'), (2, 'b')",
+        ] {
+            engine.sql(sql).await.unwrap().collect().await.unwrap();
+        }
+
+        let df = engine
+            .async_query("SELECT id, name, cost, _source FROM trace.all_events ORDER BY id")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(df.names, vec!["id", "name", "cost", "_source"]);
+        assert_eq!(df.len(), 3);
+
+        let count = engine
+            .async_query(
+                "SELECT count(*) AS n FROM trace.all_events WHERE _source = 'archive.events' AND cost IS NULL",
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(count.iter().next().unwrap()[0].to_string(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_union_view_layout() {
+        let engine = Engine::builder()
+            .with_union_view("trace.laid_out", &["spans.live"])
+            .build()
+            .await
+            .unwrap();
+        for sql in [
+            "CREATE SCHEMA spans",
+            "CREATE TABLE spans.live (name VARCHAR, t0 BIGINT, t1 BIGINT)",
+            "INSERT INTO spans.live VALUES ('model.forward', 1000000, 3000000)",
+        ] {
+            engine.sql(sql).await.unwrap().collect().await.unwrap();
+        }
+        set_computed_columns(
+            "trace.laid_out",
+            "duration_ms = (t1 - t0) / 1000000; module = split_part(name, '.', 1); bad = missing + 1",
+        )
+        .unwrap();
+        set_column_order("trace.laid_out", "module, duration_ms, unknown");
+
+        let df = engine
+            .async_query("SELECT * FROM trace.laid_out")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            df.names,
+            vec!["module", "duration_ms", "name", "t0", "t1", "_source"]
+        );
+        let row = df.iter().next().unwrap();
+        assert_eq!(row[0].to_string(), "model");
+        assert_eq!(row[1].to_string(), "2");
+    }
+
+    #[test]
+    fn test_parse_computed_columns() {
+        let columns = parse_computed_columns("a = x + 1; b = regexp_replace(n, ';', '');").unwrap();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[1].expr, "regexp_replace(n, ';', '')");
+        assert!(parse_computed_columns("a + 1").is_err());
+        assert!(parse_computed_columns("a b = 1").is_err());
+        assert!(parse_computed_columns("a = (1").is_err());
+        assert!(parse_computed_columns("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_widen() {
+        assert_eq!(widen(&DataType::Int32, &DataType::Int64), DataType::Int64);
+        assert_eq!(
+            widen(&DataType::Int64, &DataType::Float32),
+            DataType::Float64
+        );
+        assert_eq!(widen(&DataType::Int64, &DataType::Utf8), DataType::Utf8);
+    }
+}
@@ -0,0 +1,449 @@
+//! Scalar functions loaded from WebAssembly modules.
+//!
+//! A module is compiled with wasmtime and every exported function taking
+//! and returning numbers, `i32`, `i64`, `f32` or `f64`, becomes the SQL
+//! function `<module>_<export>`:
+//!
+//! ```wat
+//! (module
+//!   (func (export "clamp") (param f64 f64 f64) (result f64)
+//!     (f64.min (f64.max (local.get 0) (local.get 1)) (local.get 2))))
+//! ```
+//!
+//! ```sql
+//! SELECT mathx_clamp(value, 0.0, 1.0) FROM python.loss
+//! ```
+//!
+//! Modules run sandboxed: they may not import anything, so they cannot reach
+//! the host, each call is given [`FUEL_PER_CALL`] units of fuel and each
+//! instance [`MAX_MEMORY`] bytes of linear memory. A function is
+//! instantiated once per batch, rows of a batch may share its globals, and
+//! returns null for rows with a null argument.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, RwLock};
+
+use arrow::array::{
+    Array, ArrayRef, AsArray, Float32Array, Float64Array, Int32Array, Int64Array, RecordBatch,
+    StringArray,
+};
+use arrow::datatypes::{DataType, Field, Float32Type, Float64Type, Int32Type, Int64Type, Schema};
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDF, Signature, SimpleScalarUDF, Volatility};
+use datafusion::prelude::SessionContext;
+use wasmtime::{
+    Config, Engine as WasmEngine, ExternType, Instance, Module, Store, StoreLimitsBuilder, Trap,
+    Val, ValType,
+};
+
+use super::error::EngineError;
+use super::{CustomTable, TablePluginHelper};
+use crate::journal::{self, EntryKind};
+
+/// Fuel given to each call, roughly the number of instructions it may run
+pub const FUEL_PER_CALL: u64 = 10_000_000;
+
+/// Bytes of linear memory an instance may grow to
+pub const MAX_MEMORY: usize = 64 << 20;
+
+/// Number type of a parameter or a result
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Num {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl Num {
+    fn from_val_type(ty: &ValType) -> Option<Num> {
+        match ty {
+            ValType::I32 => Some(Num::I32),
+            ValType::I64 => Some(Num::I64),
+            ValType::F32 => Some(Num::F32),
+            ValType::F64 => Some(Num::F64),
+            _ => None,
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            Num::I32 => DataType::Int32,
+            Num::I64 => DataType::Int64,
+            Num::F32 => DataType::Float32,
+            Num::F64 => DataType::Float64,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Num::I32 => "i32",
+            Num::I64 => "i64",
+            Num::F32 => "f32",
+            Num::F64 => "f64",
+        }
+    }
+
+    /// Value of `array` at `row`, the array being of [`Num::data_type`]
+    fn value(&self, array: &ArrayRef, row: usize) -> Option<Val> {
+        Some(match self {
+            Num::I32 => Val::I32(array.as_primitive_opt::<Int32Type>()?.value(row)),
+            Num::I64 => Val::I64(array.as_primitive_opt::<Int64Type>()?.value(row)),
+            Num::F32 => Val::from(array.as_primitive_opt::<Float32Type>()?.value(row)),
+            Num::F64 => Val::from(array.as_primitive_opt::<Float64Type>()?.value(row)),
+        })
+    }
+
+    fn array(&self, values: Vec<Option<Val>>) -> ArrayRef {
+        let values = values.into_iter();
+        match self {
+            Num::I32 => Arc::new(
+                values
+                    .map(|v| v.map(|v| v.unwrap_i32()))
+                    .collect::<Int32Array>(),
+            ),
+            Num::I64 => Arc::new(
+                values
+                    .map(|v| v.map(|v| v.unwrap_i64()))
+                    .collect::<Int64Array>(),
+            ),
+            Num::F32 => Arc::new(
+                values
+                    .map(|v| v.map(|v| v.unwrap_f32()))
+                    .collect::<Float32Array>(),
+            ),
+            Num::F64 => Arc::new(
+                values
+                    .map(|v| v.map(|v| v.unwrap_f64()))
+                    .collect::<Float64Array>(),
+            ),
+        }
+    }
+}
+
+/// An exported function of a loaded module
+#[derive(Debug)]
+pub struct WasmFunction {
+    /// SQL name, `<module>_<export>`
+    pub name: String,
+    pub module: String,
+    pub export: String,
+    params: Vec<Num>,
+    result: Num,
+    compiled: Module,
+    calls: AtomicU64,
+    traps: AtomicU64,
+}
+
+impl WasmFunction {
+    /// `(i64, f64) -> f64`
+    pub fn signature(&self) -> String {
+        let params: Vec<_> = self.params.iter().map(Num::as_str).collect();
+        format!("({}) -> {}", params.join(", "), self.result.as_str())
+    }
+
+    fn udf(self: &Arc<Self>) -> ScalarUDF {
+        let func = self.clone();
+        ScalarUDF::from(SimpleScalarUDF::new_with_signature(
+            &self.name,
+            Signature::exact(
+                self.params.iter().map(Num::data_type).collect(),
+                Volatility::Volatile,
+            ),
+            self.result.data_type(),
+            Arc::new(move |args| func.invoke(args)),
+        ))
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let error = |e: wasmtime::Error| {
+            self.traps.fetch_add(1, Ordering::Relaxed);
+            let message = match e.downcast_ref::<Trap>() {
+                Some(Trap::OutOfFuel) => format!("ran out of fuel ({FUEL_PER_CALL})"),
+                _ => e.to_string(),
+            };
+            DataFusionError::Execution(format!("{}: {message}", self.name))
+        };
+        let arrays = ColumnarValue::values_to_arrays(args)?;
+        let rows = arrays.first().map(|a| a.len()).unwrap_or_default();
+
+        let mut store = Store::new(
+            engine()?,
+            StoreLimitsBuilder::new()
+                .memory_size(MAX_MEMORY)
+                .instances(1)
+                .build(),
+        );
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(error)?;
+        let instance = Instance::new(&mut store, &self.compiled, &[]).map_err(error)?;
+        let func = instance
+            .get_func(&mut store, &self.export)
+            .ok_or_else(|| DataFusionError::Internal(format!("{} is not exported", self.export)))?;
+
+        let mut params = vec![Val::I32(0); self.params.len()];
+        let mut result = [Val::I32(0)];
+        let mut values = Vec::with_capacity(rows);
+        for row in 0..rows {
+            if arrays.iter().any(|a| a.is_null(row)) {
+                values.push(None);
+                continue;
+            }
+            for (param, (array, num)) in params.iter_mut().zip(arrays.iter().zip(&self.params)) {
+                *param = num.value(array, row).ok_or_else(|| {
+                    DataFusionError::Internal(format!("{}: expected {:?}", self.name, num))
+                })?;
+            }
+            store.set_fuel(FUEL_PER_CALL).map_err(error)?;
+            func.call(&mut store, &params, &mut result).map_err(error)?;
+            values.push(Some(result[0].clone()));
+        }
+        self.calls.fetch_add(rows as u64, Ordering::Relaxed);
+        Ok(ColumnarValue::Array(self.result.array(values)))
+    }
+}
+
+fn engine() -> Result<&'static WasmEngine> {
+    static ENGINE: LazyLock<std::result::Result<WasmEngine, String>> = LazyLock::new(|| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        WasmEngine::new(&config).map_err(|e| e.to_string())
+    });
+    ENGINE
+        .as_ref()
+        .map_err(|e| DataFusionError::Internal(format!("wasm engine: {e}")))
+}
+
+/// Functions of the loaded modules, by module name
+static MODULES: LazyLock<RwLock<BTreeMap<String, Vec<Arc<WasmFunction>>>>> =
+    LazyLock::new(Default::default);
+
+fn compile(name: &str, bytes: &[u8]) -> std::result::Result<Vec<Arc<WasmFunction>>, String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("invalid module name `{name}`"));
+    }
+    let module =
+        Module::new(engine().map_err(|e| e.to_string())?, bytes).map_err(|e| e.to_string())?;
+    if let Some(import) = module.imports().next() {
+        return Err(format!(
+            "modules may not import anything, imports {}.{}",
+            import.module(),
+            import.name()
+        ));
+    }
+
+    let mut functions = vec![];
+    for export in module.exports() {
+        let ExternType::Func(ty) = export.ty() else {
+            continue;
+        };
+        let params: Option<Vec<_>> = ty.params().map(|p| Num::from_val_type(&p)).collect();
+        let results: Option<Vec<_>> = ty.results().map(|r| Num::from_val_type(&r)).collect();
+        // functions without arguments do not know the number of rows
+        let (Some(params), Some([result])) = (params, results.as_deref()) else {
+            continue;
+        };
+        if params.is_empty() {
+            continue;
+        }
+        functions.push(Arc::new(WasmFunction {
+            name: format!("{name}_{}", export.name()).to_lowercase(),
+            module: name.to_string(),
+            export: export.name().to_string(),
+            params,
+            result: *result,
+            compiled: module.clone(),
+            calls: AtomicU64::new(0),
+            traps: AtomicU64::new(0),
+        }));
+    }
+    if functions.is_empty() {
+        return Err("no exported function takes and returns numbers".to_string());
+    }
+    Ok(functions)
+}
+
+/// Compile the module `name`, in binary or text format, and register its
+/// functions with `context`, replacing a module of the same name. Returns
+/// the names of the functions.
+pub fn load(context: &SessionContext, name: &str, bytes: &[u8]) -> super::Result<Vec<String>> {
+    let functions = compile(name, bytes)
+        .map_err(|e| EngineError::PluginRegistrationFailed(format!("wasm module {name}: {e}")))?;
+    unload(context, name);
+
+    let names: Vec<_> = functions.iter().map(|f| f.name.clone()).collect();
+    for func in &functions {
+        context.register_udf(func.udf());
+    }
+    MODULES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), functions);
+    log::warn!("wasm module {name} loaded: {}", names.join(", "));
+    journal::record(
+        EntryKind::Audit,
+        "wasm",
+        format!("module {name} loaded: {}", names.join(", ")),
+    );
+    Ok(names)
+}
+
+/// Remove the functions of the module `name`, false if it is not loaded
+pub fn unload(context: &SessionContext, name: &str) -> bool {
+    let Some(functions) = MODULES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(name)
+    else {
+        return false;
+    };
+    for func in functions {
+        context.deregister_udf(&func.name);
+    }
+    journal::record(EntryKind::Audit, "wasm", format!("module {name} unloaded"));
+    true
+}
+
+/// Functions of the loaded modules
+pub fn functions() -> Vec<Arc<WasmFunction>> {
+    let modules = MODULES.read().unwrap_or_else(|e| e.into_inner());
+    modules.values().flatten().cloned().collect()
+}
+
+/// Register the functions of the loaded modules with a new context
+pub(crate) fn register_functions(context: &SessionContext) {
+    for func in functions() {
+        context.register_udf(func.udf());
+    }
+}
+
+/// `wasm.functions`, the functions of the loaded modules
+#[derive(Default, Debug)]
+pub struct FunctionsTable {}
+
+impl CustomTable for FunctionsTable {
+    fn name() -> &'static str {
+        "functions"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("module", DataType::Utf8, false),
+            Field::new("export", DataType::Utf8, false),
+            Field::new("signature", DataType::Utf8, false),
+            Field::new("calls", DataType::Int64, false),
+            Field::new("traps", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let functions = functions();
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    functions.iter().map(|f| f.name.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    functions.iter().map(|f| f.module.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    functions.iter().map(|f| f.export.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    functions.iter().map(|f| f.signature()),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    functions
+                        .iter()
+                        .map(|f| f.calls.load(Ordering::Relaxed) as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    functions
+                        .iter()
+                        .map(|f| f.traps.load(Ordering::Relaxed) as i64),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build wasm functions batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type FunctionsPlugin = TablePluginHelper<FunctionsTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATHX: &str = r#"
+        (module
+          (func (export "clamp") (param f64 f64 f64) (result f64)
+            (f64.min (f64.max (local.get 0) (local.get 1)) (local.get 2)))
+          (func (export "double") (param i64) (result i64)
+            (i64.mul (local.get 0) (i64.const 2)))
+          (func (export "spin") (param i32) (result i32)
+            (loop (br 0))
+            (local.get 0))
+          (func (export "none")))
+    "#;
+
+    #[tokio::test]
+    async fn test_load_and_call() {
+        let ctx = SessionContext::new();
+        let mut names = load(&ctx, "mathx", MATHX.as_bytes()).unwrap();
+        names.sort();
+        assert_eq!(names, ["mathx_clamp", "mathx_double", "mathx_spin"]);
+
+        let batches = ctx
+            .sql(
+                "SELECT mathx_clamp(v, 0.0, 1.0) AS c, mathx_double(2) AS d \
+                  FROM (VALUES (-1.0), (0.5), (3.0), (NULL)) AS t(v)",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let c = batches[0].column(0).as_primitive::<Float64Type>();
+        assert_eq!(
+            c.iter().collect::<Vec<_>>(),
+            [Some(0.0), Some(0.5), Some(1.0), None]
+        );
+        assert_eq!(batches[0].column(1).as_primitive::<Int64Type>().value(0), 4);
+
+        // endless loops run out of fuel
+        let err = ctx
+            .sql("SELECT mathx_spin(CAST(1 AS INT))")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ran out of fuel"), "{err}");
+
+        assert!(unload(&ctx, "mathx"));
+        assert!(ctx.sql("SELECT mathx_double(2)").await.is_err());
+        assert!(!unload(&ctx, "mathx"));
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let ctx = SessionContext::new();
+        let imports = r#"(module (import "env" "f" (func)) (func (export "g") (param i32) (result i32) (local.get 0)))"#;
+        assert!(load(&ctx, "imports", imports.as_bytes()).is_err());
+        assert!(load(&ctx, "empty", b"(module)").is_err());
+        assert!(load(&ctx, "bad-name", MATHX.as_bytes()).is_err());
+        assert!(load(&ctx, "garbage", b"\0asm garbage").is_err());
+    }
+}
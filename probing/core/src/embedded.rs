@@ -0,0 +1,283 @@
+//! Embedding the engine in a Rust application.
+//!
+//! With the `embedded` feature, `probing-core` can be used as a plain
+//! library: no Python, no autostart when the library is loaded and no HTTP
+//! server. The application builds the engine with the tables it needs,
+//! records spans with [`probe_span!`](crate::probe_span) and queries
+//! everything in process:
+//!
+//! ```
+//! # tokio_test_block_on(async {
+//! use probing_core::embedded::Embedded;
+//! use probing_core::probe_span;
+//!
+//! let probe = Embedded::builder().with_span_table(1024).build().await?;
+//! {
+//!     let _span = probe_span!("decode", kind = "inference", tokens = 32i64);
+//! }
+//! let spans = probe
+//!     .query("SELECT name, duration FROM trace.spans WHERE kind = 'inference'")
+//!     .await?;
+//! assert_eq!(spans.len(), 1);
+//! # Ok::<(), anyhow::Error>(())
+//! # }).unwrap();
+//! # fn tokio_test_block_on<F: std::future::Future>(f: F) -> F::Output {
+//! #     futures::executor::block_on(f)
+//! # }
+//! ```
+//!
+//! The engine is the process wide [`ENGINE`](crate::ENGINE), so options set
+//! with [`Embedded::set`] reach the extensions the same way `SET` statements
+//! sent to a probe do.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, LazyLock, Mutex, Once};
+
+use anyhow::Result;
+use arrow::array::{Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use probing_proto::prelude::{DataFrame, Ele};
+
+use crate::core::{CustomTable, EngineBuilder, EngineExtension, Plugin, TablePluginHelper};
+use crate::trace::{register_sink, Attribute, Event, Span, SpanSink, StringsPlugin};
+use crate::ENGINE;
+
+/// Handle to the engine of an application embedding probing
+#[derive(Debug, Clone, Copy)]
+pub struct Embedded {}
+
+/// Builder of the embedded engine, see [`Embedded::builder`]
+pub struct EmbeddedBuilder {
+    builder: EngineBuilder,
+    spans: Option<usize>,
+}
+
+impl Embedded {
+    /// Engine with the `probe` default namespace and `trace.strings`
+    pub fn builder() -> EmbeddedBuilder {
+        EmbeddedBuilder {
+            builder: crate::create_engine().with_plugin(StringsPlugin::create("trace", "strings")),
+            spans: None,
+        }
+    }
+
+    /// Run `sql` and return its result, empty for statements such as `SET`
+    pub async fn query(&self, sql: &str) -> Result<DataFrame> {
+        let engine = ENGINE.read().await;
+        Ok(engine.async_query(sql).await?.unwrap_or_default())
+    }
+
+    /// [`query`](Self::query) for callers outside of an async runtime
+    pub fn query_blocking(&self, sql: &str) -> Result<DataFrame> {
+        futures::executor::block_on(self.query(sql))
+    }
+
+    /// Set the option `key`, e.g. `probing.profile`, see [`config::write`](crate::config::write)
+    pub async fn set(&self, key: &str, value: &str) -> Result<()> {
+        Ok(crate::config::write(key, value).await?)
+    }
+
+    /// Current value of the option `key`
+    pub async fn get(&self, key: &str) -> Option<String> {
+        crate::config::get_str(key).await
+    }
+}
+
+impl EmbeddedBuilder {
+    pub fn with_plugin(mut self, plugin: Arc<dyn Plugin + Sync + Send>) -> Self {
+        self.builder = self.builder.with_plugin(plugin);
+        self
+    }
+
+    pub fn with_extension<T>(mut self, ext: T, namespace: &str, name: Option<&str>) -> Self
+    where
+        T: EngineExtension + Send + Sync + 'static,
+    {
+        self.builder = self.builder.with_extension(ext, namespace, name);
+        self
+    }
+
+    /// Keep the last `capacity` spans ended on any thread as `trace.spans`
+    pub fn with_span_table(mut self, capacity: usize) -> Self {
+        self.spans = Some(capacity);
+        self
+    }
+
+    /// Build the engine and install it as the process wide engine
+    pub async fn build(self) -> Result<Embedded> {
+        let mut builder = self.builder;
+        if let Some(capacity) = self.spans {
+            SpansTable::install(capacity);
+            builder = builder.with_plugin(SpansPlugin::create("trace", "spans"));
+        }
+        crate::initialize_engine(builder).await?;
+        Ok(Embedded {})
+    }
+}
+
+/// Spans ended since [`SpansTable::install`], the oldest dropped first
+static SPANS: LazyLock<Mutex<(usize, VecDeque<Span>)>> = LazyLock::new(Default::default);
+
+/// Keeps ended spans for `trace.spans`, the span sink of embedded engines
+struct SpansSink;
+
+impl SpanSink for SpansSink {
+    fn on_start(&self, _span: &Span) {}
+
+    fn on_event(&self, _span: Option<&Span>, _event: &Event) {}
+
+    fn on_end(&self, span: &Span) {
+        let mut spans = SPANS.lock().unwrap();
+        let (capacity, spans) = &mut *spans;
+        if *capacity == 0 {
+            return;
+        }
+        if spans.len() == *capacity {
+            spans.pop_front();
+        }
+        spans.push_back(span.clone());
+    }
+}
+
+/// `trace.spans`: spans recorded with `probe_span!` in the embedding process
+#[derive(Default, Debug)]
+pub struct SpansTable {}
+
+impl SpansTable {
+    /// Start keeping the last `capacity` spans
+    fn install(capacity: usize) {
+        static SINK: Once = Once::new();
+        SINK.call_once(|| register_sink(Arc::new(SpansSink)));
+
+        let mut spans = SPANS.lock().unwrap();
+        spans.0 = capacity;
+        let excess = spans.1.len().saturating_sub(capacity);
+        spans.1.drain(..excess);
+    }
+
+    fn attrs_json(attrs: &[Attribute]) -> String {
+        let map: serde_json::Map<String, serde_json::Value> = attrs
+            .iter()
+            .map(|a| {
+                let value = match a.value() {
+                    Ele::Nil => serde_json::Value::Null,
+                    Ele::BOOL(x) => (*x).into(),
+                    Ele::I32(x) => (*x).into(),
+                    Ele::I64(x) => (*x).into(),
+                    Ele::F32(x) => (*x).into(),
+                    Ele::F64(x) => (*x).into(),
+                    other => other.to_string().into(),
+                };
+                (a.key().to_string(), value)
+            })
+            .collect();
+        serde_json::Value::Object(map).to_string()
+    }
+}
+
+impl CustomTable for SpansTable {
+    fn name() -> &'static str {
+        "spans"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("trace_id", DataType::Int64, false),
+            Field::new("span_id", DataType::Int64, false),
+            Field::new("parent_id", DataType::Int64, true),
+            Field::new("thread_id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, true),
+            Field::new("location", DataType::Utf8, true),
+            Field::new("start", DataType::Int64, false),
+            Field::new("duration", DataType::Int64, false),
+            Field::new("attributes", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        // spans ended on other threads may still be buffered
+        crate::trace::flush();
+        let spans = SPANS.lock().unwrap();
+        let spans = &spans.1;
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(
+                    spans.iter().map(|s| s.trace_id as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    spans.iter().map(|s| s.span_id as i64),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    spans.iter().map(|s| s.parent_id.map(|id| id as i64)),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    spans.iter().map(|s| s.thread_id as i64),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    spans.iter().map(|s| s.name.as_str()),
+                )),
+                Arc::new(StringArray::from_iter(
+                    spans.iter().map(|s| s.kind.map(|kind| kind.as_str())),
+                )),
+                Arc::new(StringArray::from_iter(
+                    spans.iter().map(|s| s.loc.as_ref().map(|loc| loc.as_str())),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    spans.iter().map(|s| s.start.as_nanos_i64()),
+                )),
+                Arc::new(Int64Array::from_iter_values(spans.iter().map(|s| {
+                    s.end
+                        .map(|end| end.duration_since(s.start).as_nanos() as i64)
+                        .unwrap_or_default()
+                }))),
+                Arc::new(StringArray::from_iter_values(
+                    spans.iter().map(|s| Self::attrs_json(&s.attrs)),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build spans batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type SpansPlugin = TablePluginHelper<SpansTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embedded_query_spans() {
+        let probe = Embedded::builder()
+            .with_span_table(2)
+            .build()
+            .await
+            .unwrap();
+        for step in 0..3i64 {
+            let _span = crate::probe_span!("embedded.step", kind = "test", step = step);
+        }
+
+        let df = probe
+            .query("SELECT name, attributes FROM trace.spans WHERE kind = 'test' ORDER BY span_id")
+            .await
+            .unwrap();
+        assert_eq!(df.len(), 2);
+        assert_eq!(df.cols[1].get_str(1).as_deref(), Some(r#"{"step":2}"#));
+
+        probe.set("probing.embedded.test", "on").await.unwrap();
+        assert_eq!(
+            probe.get("probing.embedded.test").await.as_deref(),
+            Some("on")
+        );
+        assert!(probe.query("SELECT * FROM trace.missing").await.is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Agent notification bus.
+//!
+//! Components publish [`AgentEvent`]s (config changes, profiler state
+//! transitions, alerts, crash handler activations, throttling) and
+//! subscribers such as the server `/events` SSE endpoint receive them.
+//! Publishing never blocks: if nobody is listening the event is dropped,
+//! and slow subscribers skip events they lagged behind on.
+
+use once_cell::sync::Lazy;
+use probing_proto::prelude::{AgentEvent, EventKind};
+use tokio::sync::broadcast;
+
+use crate::config;
+
+/// Maximum number of events buffered for each subscriber.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+static EVENT_BUS: Lazy<broadcast::Sender<AgentEvent>> =
+    Lazy::new(|| broadcast::channel(EVENT_CHANNEL_CAPACITY).0);
+
+/// Publish an event to all current subscribers.
+pub fn publish(event: AgentEvent) {
+    log::debug!("publish agent event: {event}");
+    // An error only means there are no subscribers right now.
+    let _ = EVENT_BUS.send(event);
+}
+
+/// Convenience wrapper around [`publish`] for events without details.
+pub fn notify<S: Into<String>, M: Into<String>>(kind: EventKind, source: S, message: M) {
+    publish(AgentEvent::new(kind, source, message));
+}
+
+/// Publish a [`EventKind::ConfigChanged`] event for `key`.
+///
+/// Values of credential-like options (e.g. `server.auth_token`) are redacted
+/// so they never leave the process through the event stream.
+pub fn config_changed(source: &str, key: &str, value: &str, old: Option<&str>) {
+    let redact = |v: &str| {
+        if config::is_secret_key(key) {
+            config::MASKED.to_string()
+        } else {
+            v.to_string()
+        }
+    };
+    let mut event = AgentEvent::new(EventKind::ConfigChanged, source, format!("{key} updated"))
+        .with_detail("key", key)
+        .with_detail("value", redact(value));
+    if let Some(old) = old {
+        event = event.with_detail("old", redact(old));
+    }
+    publish(event);
+}
+
+/// Subscribe to events published from now on.
+pub fn subscribe() -> broadcast::Receiver<AgentEvent> {
+    EVENT_BUS.subscribe()
+}
+
+/// Number of active subscribers.
+pub fn subscriber_count() -> usize {
+    EVENT_BUS.receiver_count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe_receives_published_events() {
+        let mut rx = subscribe();
+        publish(AgentEvent::new(EventKind::AlertFired, "test", "fired").with_detail("rule", "r1"));
+
+        let event = loop {
+            let event = rx.recv().await.unwrap();
+            if event.source == "test" {
+                break event;
+            }
+        };
+        assert_eq!(event.kind, EventKind::AlertFired);
+        assert_eq!(event.details, vec![("rule".to_string(), "r1".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_config_changed_redacts_sensitive_values() {
+        let mut rx = subscribe();
+        config_changed("server", "server.auth_token", "secret123", Some("old"));
+
+        let event = loop {
+            let event = rx.recv().await.unwrap();
+            if event.source == "server" {
+                break event;
+            }
+        };
+        assert_eq!(event.kind, EventKind::ConfigChanged);
+        assert!(event.details.iter().all(|(_, v)| !v.contains("secret")));
+        assert!(event
+            .details
+            .contains(&("value".to_string(), config::MASKED.to_string())));
+
+        config_changed(
+            "federation",
+            "federation.tables",
+            "db=postgres://u:pw@h/db",
+            None,
+        );
+        let event = loop {
+            let event = rx.recv().await.unwrap();
+            if event.source == "federation" {
+                break event;
+            }
+        };
+        assert!(event.details.iter().all(|(_, v)| !v.contains("pw")));
+    }
+
+    #[test]
+    fn test_publish_without_subscribers() {
+        notify(EventKind::Throttled, "test", "no one is listening");
+    }
+}
@@ -0,0 +1,288 @@
+//! Journal of the agent's own errors.
+//!
+//! Panics inside the agent, failed extension calls and data dropped by the
+//! agent are easy to miss: they only show up in the log of the traced process,
+//! and not at all when the HTTP server is the part that broke. The journal
+//! keeps them apart from the application's output, along with an audit trail
+//! of security relevant settings:
+//!
+//! - [`record`] and [`dropped`] never block, they push into a bounded lock-free
+//!   queue and only count the entry when the queue is full;
+//! - a background thread appends the queued entries as JSON lines to a per-pid
+//!   file, see [`path`], and keeps the last ones for the `agent.errors` table;
+//! - panics are written to the file from the panicking thread, so that they
+//!   are on disk even if the process does not survive them.
+//!
+//! The file is created under `./logs` so that it can be read back with the
+//! server's file API, `PROBING_ERROR_JOURNAL_DIR` selects another directory.
+
+use std::collections::VecDeque;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crossbeam_queue::ArrayQueue;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+/// Directory of the journal file unless `PROBING_ERROR_JOURNAL_DIR` is set
+pub const DEFAULT_DIR: &str = "./logs";
+
+/// Entries waiting to be flushed
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Entries kept in memory for the `agent.errors` table
+const MAX_RECENT: usize = 1024;
+
+/// Size at which the file is rotated to `<path>.1`
+const MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Interval between two background flushes
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What went wrong
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// A panic in the agent
+    Panic,
+    /// An extension call that failed
+    Extension,
+    /// Data the agent dropped, `count` tells how much
+    Dropped,
+    /// A change of a security relevant setting, e.g. the directories the
+    /// file API serves
+    Audit,
+}
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::Panic => "panic",
+            EntryKind::Extension => "extension",
+            EntryKind::Dropped => "dropped",
+            EntryKind::Audit => "audit",
+        }
+    }
+}
+
+/// One line of the journal
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Microseconds since the unix epoch
+    pub time: i64,
+    pub kind: EntryKind,
+    /// Component that reported the entry, e.g. the extension name
+    pub source: String,
+    pub message: String,
+    pub count: u64,
+}
+
+impl JournalEntry {
+    fn new(kind: EntryKind, source: &str, message: String, count: u64) -> Self {
+        JournalEntry {
+            time: now_micros(),
+            kind,
+            source: source.to_string(),
+            message,
+            count,
+        }
+    }
+}
+
+static QUEUE: Lazy<ArrayQueue<JournalEntry>> = Lazy::new(|| ArrayQueue::new(QUEUE_CAPACITY));
+
+/// Entries lost because the queue was full
+static OVERFLOW: AtomicU64 = AtomicU64::new(0);
+
+/// Flushed entries, oldest first; also serializes writers of the file
+static RECENT: Lazy<Mutex<VecDeque<JournalEntry>>> = Lazy::new(Default::default);
+
+/// Record an error of the agent
+pub fn record(kind: EntryKind, source: &str, message: impl Into<String>) {
+    submit(JournalEntry::new(kind, source, message.into(), 1));
+}
+
+/// Record that `source` dropped `count` items, e.g. events a slow
+/// subscriber skipped
+pub fn dropped(source: &str, count: u64, message: impl Into<String>) {
+    if count > 0 {
+        submit(JournalEntry::new(
+            EntryKind::Dropped,
+            source,
+            message.into(),
+            count,
+        ));
+    }
+}
+
+fn submit(entry: JournalEntry) {
+    start_flusher();
+    if QUEUE.push(entry).is_err() {
+        OVERFLOW.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Path of this process' journal file
+pub fn path() -> PathBuf {
+    let dir = std::env::var("PROBING_ERROR_JOURNAL_DIR").unwrap_or(DEFAULT_DIR.to_string());
+    PathBuf::from(dir).join(format!("probing-errors-{}.jsonl", std::process::id()))
+}
+
+/// Write the queued entries to the file and the in-memory history.
+///
+/// Failing to write the file is not reported anywhere else: the entries are
+/// still served by [`entries`].
+pub fn flush() {
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    flush_locked(&mut recent);
+}
+
+fn flush_locked(recent: &mut VecDeque<JournalEntry>) {
+    let mut pending = vec![];
+    let overflow = OVERFLOW.swap(0, Ordering::Relaxed);
+    if overflow > 0 {
+        pending.push(JournalEntry::new(
+            EntryKind::Dropped,
+            "journal",
+            "journal queue full".to_string(),
+            overflow,
+        ));
+    }
+    while let Some(entry) = QUEUE.pop() {
+        pending.push(entry);
+    }
+    if pending.is_empty() {
+        return;
+    }
+
+    if let Err(e) = append(&pending) {
+        log::debug!("failed to write error journal {}: {e}", path().display());
+    }
+    for entry in pending {
+        if recent.len() >= MAX_RECENT {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+}
+
+fn append(entries: &[JournalEntry]) -> std::io::Result<()> {
+    let path = path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if std::fs::metadata(&path).is_ok_and(|meta| meta.len() >= MAX_FILE_SIZE) {
+        let mut rotated = path.clone().into_os_string();
+        rotated.push(".1");
+        std::fs::rename(&path, rotated)?;
+    }
+
+    let mut lines = String::new();
+    for entry in entries {
+        if let Ok(line) = serde_json::to_string(entry) {
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    // a single write keeps the lines whole if the process dies meanwhile
+    file.write_all(lines.as_bytes())?;
+    file.sync_data()
+}
+
+/// The recorded entries, oldest first
+pub fn entries() -> Vec<JournalEntry> {
+    flush();
+    let recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    recent.iter().cloned().collect()
+}
+
+/// Journal panics, then run the previously installed hook.
+///
+/// The entry is flushed right away since the process may not outlive the
+/// panic.
+pub fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let thread = std::thread::current();
+            let source = thread.name().unwrap_or("unnamed");
+            let location = info
+                .location()
+                .map(|l| format!(" at {}:{}", l.file(), l.line()))
+                .unwrap_or_default();
+            let payload = info
+                .payload()
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| info.payload().downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Box<dyn Any>".to_string());
+            record(EntryKind::Panic, source, format!("{payload}{location}"));
+            // the panicking thread may be the one flushing, leave the entry
+            // to it rather than deadlock
+            if let Ok(mut recent) = RECENT.try_lock() {
+                flush_locked(&mut recent);
+            }
+            previous(info);
+        }));
+    });
+}
+
+fn start_flusher() {
+    static FLUSHER: Once = Once::new();
+    FLUSHER.call_once(|| {
+        let spawned = std::thread::Builder::new()
+            .name("probing-journal".to_string())
+            .spawn(|| loop {
+                std::thread::sleep(FLUSH_INTERVAL);
+                flush();
+            });
+        if let Err(err) = spawned {
+            log::error!("failed to start error journal: {err}");
+        }
+    });
+}
+
+fn now_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_flush() {
+        let dir = std::env::temp_dir().join(format!("probing-journal-{}", std::process::id()));
+        std::env::set_var("PROBING_ERROR_JOURNAL_DIR", &dir);
+
+        record(EntryKind::Extension, "test-ext", "call failed");
+        dropped("test-bus", 3, "subscriber lagged");
+        dropped("test-bus", 0, "nothing lost");
+        let entries = entries();
+        let ours = entries
+            .iter()
+            .filter(|e| e.source.starts_with("test-"))
+            .collect::<Vec<_>>();
+        assert_eq!(ours.len(), 2);
+        assert_eq!(ours[0].kind, EntryKind::Extension);
+        assert_eq!(ours[1].count, 3);
+
+        let written = std::fs::read_to_string(path()).unwrap();
+        let lines = written
+            .lines()
+            .map(|line| serde_json::from_str::<JournalEntry>(line).unwrap())
+            .filter(|e| e.source.starts_with("test-"))
+            .count();
+        assert_eq!(lines, 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,241 @@
+//! Latency of the agent's own operations.
+//!
+//! To tell which probe operations are slow in a given environment, every
+//! HTTP request, extension call and query is timed into a histogram of its
+//! endpoint, and calls taking at least [`slow_call_ms`] are kept with their
+//! parameters in a bounded log. Both are read through `agent.http_stats` and
+//! `agent.slow_calls`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Upper bounds of the histogram buckets in milliseconds, slower calls fall
+/// into a last, unbounded bucket
+pub const BUCKETS_MS: [u64; 12] = [1, 2, 5, 10, 25, 50, 100, 250, 500, 1000, 5000, 10000];
+
+/// Default of `agent.slow_call_ms`
+pub const DEFAULT_SLOW_CALL_MS: u64 = 1000;
+
+/// Slow calls kept, the oldest are dropped beyond
+const SLOW_CALLS_CAPACITY: usize = 256;
+
+/// Endpoints with a histogram, later ones are counted under [`OTHER`]
+const MAX_ENDPOINTS: usize = 512;
+
+/// Endpoint of the calls beyond [`MAX_ENDPOINTS`]
+pub const OTHER: &str = "<other>";
+
+/// Characters of the parameters kept with a slow call
+const MAX_PARAMS_CHARS: usize = 512;
+
+/// What was called
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CallKind {
+    /// An HTTP request, by route
+    Http,
+    /// An extension call, by path
+    Extension,
+    /// A SQL query, by the engine it ran on
+    Query,
+}
+
+impl CallKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallKind::Http => "http",
+            CallKind::Extension => "extension",
+            CallKind::Query => "query",
+        }
+    }
+}
+
+/// Latency histogram of one endpoint
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EndpointStats {
+    pub count: u64,
+    pub errors: u64,
+    pub total_us: u64,
+    pub max_us: u64,
+    /// Calls per bucket of [`BUCKETS_MS`], then the unbounded bucket
+    pub buckets: [u64; BUCKETS_MS.len() + 1],
+}
+
+impl EndpointStats {
+    fn add(&mut self, elapsed_us: u64, failed: bool) {
+        self.count += 1;
+        self.errors += failed as u64;
+        self.total_us = self.total_us.saturating_add(elapsed_us);
+        self.max_us = self.max_us.max(elapsed_us);
+        let bucket = BUCKETS_MS
+            .iter()
+            .position(|&bound| elapsed_us <= bound * 1000)
+            .unwrap_or(BUCKETS_MS.len());
+        self.buckets[bucket] += 1;
+    }
+
+    /// Mean latency in milliseconds
+    pub fn mean_ms(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            n => self.total_us as f64 / n as f64 / 1000.0,
+        }
+    }
+
+    /// Upper estimate of the `q` quantile in milliseconds: the bound of the
+    /// bucket it falls into, at most the slowest call
+    pub fn quantile_ms(&self, q: f64) -> f64 {
+        let max_ms = self.max_us as f64 / 1000.0;
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return match BUCKETS_MS.get(bucket) {
+                    Some(&bound) => (bound as f64).min(max_ms),
+                    None => max_ms,
+                };
+            }
+        }
+        max_ms
+    }
+}
+
+/// A call that took at least [`slow_call_ms`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlowCall {
+    /// Microseconds since the unix epoch the call ended at
+    pub time: i64,
+    pub kind: CallKind,
+    pub endpoint: String,
+    pub elapsed_us: u64,
+    pub failed: bool,
+    /// Query string, extension parameters or SQL of the call, cut to 512
+    /// characters
+    pub params: String,
+}
+
+static STATS: LazyLock<Mutex<BTreeMap<(CallKind, String), EndpointStats>>> =
+    LazyLock::new(Default::default);
+
+static SLOW_CALLS: LazyLock<Mutex<VecDeque<SlowCall>>> = LazyLock::new(Default::default);
+
+static SLOW_CALL_MS: AtomicU64 = AtomicU64::new(DEFAULT_SLOW_CALL_MS);
+
+pub fn slow_call_ms() -> u64 {
+    SLOW_CALL_MS.load(Ordering::Relaxed)
+}
+
+/// Log calls taking at least `ms` milliseconds, none if `0`
+pub fn set_slow_call_ms(ms: u64) {
+    SLOW_CALL_MS.store(ms, Ordering::Relaxed);
+}
+
+/// Record a call of `endpoint` that took `elapsed`; `params` is only
+/// evaluated for slow calls
+pub fn record(
+    kind: CallKind,
+    endpoint: &str,
+    elapsed: Duration,
+    failed: bool,
+    params: impl FnOnce() -> String,
+) {
+    let elapsed_us = elapsed.as_micros().min(u64::MAX as u128) as u64;
+    {
+        let mut stats = STATS.lock().unwrap();
+        let key = (kind, endpoint.to_string());
+        let key = if stats.contains_key(&key) || stats.len() < MAX_ENDPOINTS {
+            key
+        } else {
+            (kind, OTHER.to_string())
+        };
+        stats.entry(key).or_default().add(elapsed_us, failed);
+    }
+
+    let threshold = slow_call_ms();
+    if threshold == 0 || elapsed_us < threshold * 1000 {
+        return;
+    }
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as i64;
+    let mut params = params();
+    if let Some((end, _)) = params.char_indices().nth(MAX_PARAMS_CHARS) {
+        params.truncate(end);
+    }
+    let mut slow_calls = SLOW_CALLS.lock().unwrap();
+    if slow_calls.len() >= SLOW_CALLS_CAPACITY {
+        slow_calls.pop_front();
+    }
+    slow_calls.push_back(SlowCall {
+        time,
+        kind,
+        endpoint: endpoint.to_string(),
+        elapsed_us,
+        failed,
+        params,
+    });
+}
+
+/// Histograms of all endpoints, ordered by kind and endpoint
+pub fn stats() -> Vec<(CallKind, String, EndpointStats)> {
+    STATS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((kind, endpoint), stats)| (*kind, endpoint.clone(), stats.clone()))
+        .collect()
+}
+
+/// Slow calls, oldest first
+pub fn slow_calls() -> Vec<SlowCall> {
+    SLOW_CALLS.lock().unwrap().iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles() {
+        let mut stats = EndpointStats::default();
+        for ms in 1..=100 {
+            stats.add(ms * 1000, ms == 100);
+        }
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.quantile_ms(0.5), 50.0);
+        assert_eq!(stats.quantile_ms(0.9), 100.0);
+        assert_eq!(stats.quantile_ms(1.0), 100.0);
+        assert!((stats.mean_ms() - 50.5).abs() < 1e-9);
+
+        stats.add(60_000_000, false);
+        assert_eq!(stats.buckets[BUCKETS_MS.len()], 1);
+        assert_eq!(stats.quantile_ms(1.0), 60_000.0);
+    }
+
+    #[test]
+    fn test_slow_calls_keep_params() {
+        let endpoint = "/test/latency";
+        record(
+            CallKind::Http,
+            endpoint,
+            Duration::from_micros(10),
+            false,
+            || unreachable!("params of fast calls are not built"),
+        );
+        let slow = Duration::from_millis(slow_call_ms() + 1);
+        record(CallKind::Http, endpoint, slow, true, || "x".repeat(1000));
+
+        let stats = stats();
+        let (_, _, stats) = stats.iter().find(|(_, e, _)| e == endpoint).unwrap();
+        assert_eq!((stats.count, stats.errors), (2, 1));
+
+        let slow_calls = slow_calls();
+        let call = slow_calls.iter().find(|c| c.endpoint == endpoint).unwrap();
+        assert!(call.failed);
+        assert_eq!(call.params.len(), MAX_PARAMS_CHARS);
+    }
+}
@@ -1,8 +1,20 @@
+pub mod catalog;
 pub mod config;
 pub mod core;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+pub mod events;
+pub mod journal;
+pub mod latency;
+pub mod recorder;
+pub mod schedule;
+pub mod stacks;
 pub mod storage;
 pub mod trace;
 
+/// Version of this crate, listed in `/apis/version`
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 use self::core::Engine;
 use self::core::EngineBuilder;
 
@@ -10,12 +22,40 @@ pub fn create_engine() -> EngineBuilder {
     Engine::builder().with_default_namespace("probe")
 }
 
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use anyhow::Result;
 use once_cell::sync::Lazy;
 use tokio::sync::RwLock;
 
 pub static ENGINE: Lazy<RwLock<Engine>> = Lazy::new(|| RwLock::new(Engine::default()));
 
+/// Snapshot engine with the time it was taken, if one was taken
+pub type Snapshot = Option<(Instant, Arc<Engine>)>;
+
+/// Read-only copy of [`ENGINE`] for heavy ad-hoc analytics
+pub static SNAPSHOT: Lazy<RwLock<Snapshot>> = Lazy::new(|| RwLock::new(None));
+
+/// Default of `server.snapshot_ttl_seconds`
+pub const DEFAULT_SNAPSHOT_TTL_SECONDS: u64 = 60;
+
+static SNAPSHOT_TTL_SECONDS: AtomicU64 = AtomicU64::new(DEFAULT_SNAPSHOT_TTL_SECONDS);
+
+pub fn snapshot_ttl_seconds() -> u64 {
+    SNAPSHOT_TTL_SECONDS.load(Ordering::Relaxed)
+}
+
+/// Take a new snapshot once the current one is `seconds` old, never if `0`
+pub fn set_snapshot_ttl_seconds(seconds: u64) {
+    SNAPSHOT_TTL_SECONDS.store(seconds, Ordering::Relaxed);
+}
+
+fn is_expired(taken: Instant, ttl_seconds: u64) -> bool {
+    ttl_seconds > 0 && taken.elapsed() >= Duration::from_secs(ttl_seconds)
+}
+
 pub async fn initialize_engine(builder: EngineBuilder) -> Result<()> {
     let engine = match builder.build().await {
         Ok(engine) => engine,
@@ -30,3 +70,34 @@ pub async fn initialize_engine(builder: EngineBuilder) -> Result<()> {
 
     Ok(())
 }
+
+/// Replace [`SNAPSHOT`] with a fresh copy of the current tables.
+pub async fn take_snapshot() -> Result<Arc<Engine>> {
+    let snapshot = Arc::new(ENGINE.read().await.snapshot().await?);
+    *SNAPSHOT.write().await = Some((Instant::now(), snapshot.clone()));
+    Ok(snapshot)
+}
+
+/// Current snapshot, taking one if none exists yet or the current one is
+/// older than [`snapshot_ttl_seconds`].
+pub async fn get_snapshot() -> Result<Arc<Engine>> {
+    if let Some((taken, snapshot)) = SNAPSHOT.read().await.as_ref() {
+        if !is_expired(*taken, snapshot_ttl_seconds()) {
+            return Ok(snapshot.clone());
+        }
+    }
+    take_snapshot().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_expiry() {
+        let taken = Instant::now() - Duration::from_secs(30);
+        assert!(!is_expired(taken, 60));
+        assert!(is_expired(taken, 10));
+        assert!(!is_expired(taken, 0));
+    }
+}
@@ -0,0 +1,475 @@
+//! Time series recorded by extensions.
+//!
+//! A [`TimeSeriesRecorder`] holds the rows of one table, `namespace.name`,
+//! each a timestamp and a value per column. Old rows are discarded following
+//! the [`DiscardStrategy`] of the recorder, so a recorder fed for the whole
+//! run keeps a bounded window of it:
+//!
+//! ```ignore
+//! static CPU: LazyLock<Arc<TimeSeriesRecorder>> = LazyLock::new(|| {
+//!     TimeSeriesRecorder::builder("process", "cpu")
+//!         .with_column("cpu_utime", EleType::I64)
+//!         .with_column("cpu_stime", EleType::I64)
+//!         .with_capacity(3600)
+//!         .build()
+//! });
+//!
+//! CPU.record(vec![utime.into(), stime.into()])?;
+//! ```
+//!
+//! The table is registered with the engine by [`RecorderBuilder::build`] if
+//! the engine is running, and when the engine is built otherwise, so
+//! extensions need neither their own storage nor a plugin to serve it. Its
+//! `timestamp` column holds microseconds since the epoch.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use arrow::array::{
+    ArrayRef, BooleanArray, Float32Array, Float64Array, Int32Array, Int64Array, RecordBatch,
+    StringArray, TimestampNanosecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use async_trait::async_trait;
+use datafusion::catalog::{MemorySchemaProvider, Session, TableProvider};
+use datafusion::datasource::memory::{DataSourceExec, MemorySourceConfig};
+use datafusion::datasource::TableType;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::ExecutionPlan;
+use probing_proto::types::{DiscardStrategy, Ele, EleType, Series, TimeSeries, TimeSeriesError};
+
+use crate::core::{time, Engine};
+
+/// Rows kept by a recorder without an explicit discard strategy
+pub const DEFAULT_CAPACITY: usize = 100_000;
+
+/// Recorders by `namespace.name`
+static RECORDERS: LazyLock<RwLock<BTreeMap<String, Arc<TimeSeriesRecorder>>>> =
+    LazyLock::new(Default::default);
+
+/// Rows of a table appended by an extension, see the [module docs](self)
+#[derive(Debug)]
+pub struct TimeSeriesRecorder {
+    namespace: String,
+    name: String,
+    schema: SchemaRef,
+    dtypes: Vec<EleType>,
+    series: Mutex<TimeSeries>,
+}
+
+impl TimeSeriesRecorder {
+    pub fn builder(namespace: &str, name: &str) -> RecorderBuilder {
+        RecorderBuilder {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            columns: vec![],
+            discard_strategy: capacity_strategy(DEFAULT_CAPACITY),
+        }
+    }
+
+    /// The recorder of `namespace.name`, if one was built
+    pub fn get(namespace: &str, name: &str) -> Option<Arc<TimeSeriesRecorder>> {
+        RECORDERS
+            .read()
+            .unwrap()
+            .get(&format!("{namespace}.{name}"))
+            .cloned()
+    }
+
+    /// `namespace.name` of the table
+    pub fn table_name(&self) -> String {
+        format!("{}.{}", self.namespace, self.name)
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Append a row stamped with the current time, with a value for every
+    /// column in column order
+    pub fn record(&self, values: Vec<Ele>) -> Result<(), TimeSeriesError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as i64;
+        self.record_at(now, values)
+    }
+
+    /// Append a row stamped with `timestamp`, in microseconds since the
+    /// epoch.
+    ///
+    /// Values are coerced to the type of their column, see [`Ele::coerce`],
+    /// and [`Ele::Nil`] is recorded as a null. Nothing is appended when a
+    /// value does not fit its column.
+    pub fn record_at(&self, timestamp: i64, values: Vec<Ele>) -> Result<(), TimeSeriesError> {
+        if values.len() != self.dtypes.len() {
+            return Err(TimeSeriesError::ColumnCountMismatch {
+                expected: self.dtypes.len(),
+                got: values.len(),
+            });
+        }
+        let values = values
+            .into_iter()
+            .zip(&self.dtypes)
+            .enumerate()
+            .map(|(idx, (value, dtype))| {
+                value
+                    .coerce(dtype)
+                    .ok_or_else(|| TimeSeriesError::IncompatibleType {
+                        column: self.schema.field(idx + 1).name().clone(),
+                        expected: dtype.clone(),
+                        got: value.dtype(),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.series
+            .lock()
+            .unwrap()
+            .append(Ele::I64(timestamp), values)
+    }
+
+    /// Number of rows held
+    pub fn len(&self) -> usize {
+        self.series.lock().unwrap().retained()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of the oldest rows discarded so far
+    pub fn discarded(&self) -> usize {
+        self.series.lock().unwrap().discarded()
+    }
+
+    /// Release the capacity reserved for rows not recorded yet
+    pub fn shrink_to_fit(&self) {
+        self.series.lock().unwrap().shrink_to_fit();
+    }
+
+    /// Shrink every recorder, see [`TimeSeriesRecorder::shrink_to_fit`],
+    /// and return how many there are
+    pub fn shrink_all() -> usize {
+        let recorders: Vec<_> = RECORDERS.read().unwrap().values().cloned().collect();
+        recorders.iter().for_each(|r| r.shrink_to_fit());
+        recorders.len()
+    }
+
+    /// The rows held, oldest first
+    pub fn batch(&self) -> Result<RecordBatch> {
+        let series = self.series.lock().unwrap();
+        let mut columns = vec![vec![]; self.dtypes.len() + 1];
+        for (timestamp, row) in series.iter() {
+            columns[0].push(timestamp);
+            for (col, value) in columns[1..].iter_mut().zip(row) {
+                col.push(value);
+            }
+        }
+        drop(series);
+        let arrays = self
+            .schema
+            .fields()
+            .iter()
+            .zip(columns)
+            .map(|(field, values)| to_array(field.data_type(), values))
+            .collect();
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+}
+
+/// Builder of a [`TimeSeriesRecorder`]
+pub struct RecorderBuilder {
+    namespace: String,
+    name: String,
+    columns: Vec<(String, EleType)>,
+    discard_strategy: DiscardStrategy,
+}
+
+impl RecorderBuilder {
+    /// Add a column holding values of type `dtype`
+    pub fn with_column(mut self, name: &str, dtype: EleType) -> Self {
+        self.columns.push((name.to_string(), dtype));
+        self
+    }
+
+    /// Keep at most the last `rows` rows, older rows are discarded an
+    /// eighth of them at a time
+    pub fn with_capacity(mut self, rows: usize) -> Self {
+        self.discard_strategy = capacity_strategy(rows);
+        self
+    }
+
+    pub fn with_discard_strategy(mut self, discard_strategy: DiscardStrategy) -> Self {
+        self.discard_strategy = discard_strategy;
+        self
+    }
+
+    /// Build the recorder and register its table.
+    ///
+    /// Building a recorder for a table that already has one returns the
+    /// existing recorder, so that it may be built lazily from several places.
+    pub fn build(self) -> Arc<TimeSeriesRecorder> {
+        let table = format!("{}.{}", self.namespace, self.name);
+        let recorder = {
+            let mut recorders = RECORDERS.write().unwrap();
+            if let Some(recorder) = recorders.get(&table) {
+                return recorder.clone();
+            }
+            let recorder = Arc::new(self.into_recorder());
+            recorders.insert(table, recorder.clone());
+            recorder
+        };
+        // otherwise registered when the engine is built
+        if let Ok(engine) = crate::ENGINE.try_read() {
+            match register(&engine, &recorder) {
+                Ok(()) => crate::catalog::table_registered("recorder", &recorder.table_name()),
+                Err(e) => log::warn!("Failed to register table {}: {e}", recorder.table_name()),
+            }
+        }
+        recorder
+    }
+
+    fn into_recorder(self) -> TimeSeriesRecorder {
+        let series = |dtype: &EleType| {
+            Series::builder()
+                .with_dtype(dtype.clone())
+                .with_discard_strategy(self.discard_strategy.clone())
+                .build()
+        };
+        let mut fields = vec![Field::new("timestamp", DataType::Int64, false)];
+        fields.extend(
+            self.columns
+                .iter()
+                .map(|(name, dtype)| Field::new(name, arrow_type(dtype), true)),
+        );
+        let dtypes = self
+            .columns
+            .iter()
+            .map(|(_, dtype)| dtype.clone())
+            .collect();
+        let ts = TimeSeries {
+            names: self.columns.iter().map(|(name, _)| name.clone()).collect(),
+            timestamp: series(&EleType::I64),
+            cols: self
+                .columns
+                .iter()
+                .map(|(_, dtype)| series(dtype))
+                .collect(),
+        };
+        TimeSeriesRecorder {
+            namespace: self.namespace,
+            name: self.name,
+            schema: SchemaRef::new(Schema::new(fields)),
+            dtypes,
+            series: Mutex::new(ts),
+        }
+    }
+}
+
+/// Hold at most `rows` rows: the oldest chunk is discarded once the full
+/// chunks reach the threshold, and the open chunk holds less than a chunk
+fn capacity_strategy(rows: usize) -> DiscardStrategy {
+    let rows = rows.max(2);
+    let chunk_size = (rows / 8).clamp(1, 10_000);
+    DiscardStrategy::BaseElementCount {
+        discard_threshold: rows + 1 - chunk_size,
+        chunk_size,
+    }
+}
+
+fn arrow_type(dtype: &EleType) -> DataType {
+    match dtype {
+        EleType::BOOL => DataType::Boolean,
+        EleType::I32 => DataType::Int32,
+        EleType::I64 => DataType::Int64,
+        EleType::F32 => DataType::Float32,
+        EleType::F64 => DataType::Float64,
+        EleType::DataTime => time::timestamp_ns_type(),
+        _ => DataType::Utf8,
+    }
+}
+
+fn to_array(data_type: &DataType, values: Vec<Ele>) -> ArrayRef {
+    let values = values.into_iter();
+    match data_type {
+        DataType::Boolean => Arc::new(BooleanArray::from_iter(values.map(|x| match x {
+            Ele::BOOL(x) => Some(x),
+            _ => None,
+        }))),
+        DataType::Int32 => Arc::new(Int32Array::from_iter(values.map(|x| match x {
+            Ele::I32(x) => Some(x),
+            _ => None,
+        }))),
+        DataType::Int64 => Arc::new(Int64Array::from_iter(values.map(|x| match x {
+            Ele::I64(x) => Some(x),
+            _ => None,
+        }))),
+        DataType::Float32 => Arc::new(Float32Array::from_iter(values.map(|x| match x {
+            Ele::F32(x) => Some(x),
+            _ => None,
+        }))),
+        DataType::Float64 => Arc::new(Float64Array::from_iter(values.map(|x| match x {
+            Ele::F64(x) => Some(x),
+            _ => None,
+        }))),
+        DataType::Timestamp(_, tz) => Arc::new(
+            TimestampNanosecondArray::from_iter(values.map(|x| match x {
+                Ele::DataTime(_) => x.timestamp_nanos(),
+                _ => None,
+            }))
+            .with_timezone_opt(tz.clone()),
+        ),
+        _ => Arc::new(StringArray::from_iter(values.map(|x| match x {
+            Ele::Nil => None,
+            Ele::Text(x) | Ele::Url(x) => Some(x),
+            x => Some(x.to_string()),
+        }))),
+    }
+}
+
+/// Register the table of `recorder` with `engine`
+fn register(engine: &Engine, recorder: &Arc<TimeSeriesRecorder>) -> Result<()> {
+    let catalog = engine
+        .context
+        .catalog("probe")
+        .ok_or_else(|| DataFusionError::Internal("no catalog `probe`".to_string()))?;
+    if catalog.schema(&recorder.namespace).is_none() {
+        catalog.register_schema(&recorder.namespace, Arc::new(MemorySchemaProvider::new()))?;
+    }
+    engine.context.register_table(
+        format!("probe.{}", recorder.table_name()).as_str(),
+        Arc::new(RecorderTable(recorder.clone())),
+    )?;
+    Ok(())
+}
+
+/// Register the tables of the recorders built so far with `engine`
+pub(crate) fn register_all(engine: &Engine) {
+    for recorder in RECORDERS.read().unwrap().values() {
+        if let Err(e) = register(engine, recorder) {
+            log::warn!("Failed to register table {}: {e}", recorder.table_name());
+        }
+    }
+}
+
+/// Table of a recorder, read at scan time
+#[derive(Debug)]
+struct RecorderTable(Arc<TimeSeriesRecorder>);
+
+#[async_trait]
+impl TableProvider for RecorderTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.0.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let source = MemorySourceConfig::try_new(
+            &[vec![self.0.batch()?]],
+            self.schema(),
+            projection.cloned(),
+        )?;
+        Ok(Arc::new(DataSourceExec::new(Arc::new(source))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::Array;
+
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let recorder = TimeSeriesRecorder::builder("recorder_test", "record")
+            .with_column("step", EleType::I64)
+            .with_column("loss", EleType::F64)
+            .build();
+        recorder
+            .record_at(1, vec![1i64.into(), 0.5f64.into()])
+            .unwrap();
+        // coerced to the column types
+        recorder
+            .record_at(2, vec![2i32.into(), 1i64.into()])
+            .unwrap();
+        recorder.record_at(3, vec![3i64.into(), Ele::Nil]).unwrap();
+        assert!(recorder.record_at(4, vec![4i64.into()]).is_err());
+        assert!(recorder
+            .record_at(4, vec![4i64.into(), "high".to_string().into()])
+            .is_err());
+
+        let batch = recorder.batch().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let loss = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(loss.value(1), 1.0);
+        assert!(loss.is_null(2));
+
+        // built once per table
+        let again = TimeSeriesRecorder::builder("recorder_test", "record").build();
+        assert!(Arc::ptr_eq(&again, &recorder));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let recorder = TimeSeriesRecorder::builder("recorder_test", "capacity")
+            .with_column("value", EleType::I64)
+            .with_capacity(32)
+            .build();
+        for i in 0..100i64 {
+            recorder.record_at(i, vec![i.into()]).unwrap();
+        }
+        assert!(recorder.len() <= 32);
+        assert_eq!(recorder.len() + recorder.discarded(), 100);
+
+        let batch = recorder.batch().unwrap();
+        let values = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(values.value(values.len() - 1), 99);
+    }
+
+    #[tokio::test]
+    async fn test_table_registration() -> Result<()> {
+        let recorder = TimeSeriesRecorder::builder("recorder_test", "query")
+            .with_column("value", EleType::F64)
+            .build();
+        recorder.record_at(10, vec![1.5f64.into()]).unwrap();
+        recorder.record_at(20, vec![2.5f64.into()]).unwrap();
+
+        let engine = Engine::builder().build().await?;
+        let batches = engine
+            .sql("SELECT sum(value) AS total FROM recorder_test.query WHERE timestamp > 10")
+            .await?
+            .collect()
+            .await?;
+        let total = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(total.value(0), 2.5);
+        Ok(())
+    }
+}
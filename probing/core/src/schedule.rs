@@ -0,0 +1,274 @@
+//! Configuration changes planned ahead.
+//!
+//! `probing config schedule` queues a change applied at a time or once the
+//! training reaches a step, optionally reverted later, so that a capture
+//! window such as heavy profiling of steps 1000 to 1010 can be planned
+//! without someone watching the job. The step is reported by the optimizer
+//! hooks through [`report_step`], which applies the changes it makes due
+//! before the step returns; time triggers are checked by the server every
+//! second with [`apply_due`].
+
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use probing_proto::prelude::{ScheduleState, ScheduleTrigger, ScheduledChange};
+
+use crate::config;
+use crate::core::EngineError;
+
+static CHANGES: Lazy<Mutex<Vec<ScheduledChange>>> = Lazy::new(Default::default);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Last step reported, `-1` before the first one
+static STEP: AtomicI64 = AtomicI64::new(-1);
+
+/// Nanoseconds since the unix epoch
+pub fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_nanos() as i64)
+        .unwrap_or_default()
+}
+
+/// Last step reported by the training, `None` before the first one
+pub fn step() -> Option<i64> {
+    Some(STEP.load(Ordering::Relaxed)).filter(|step| *step >= 0)
+}
+
+/// Note that the training reached `step`
+pub fn report_step(step: i64) {
+    STEP.store(step, Ordering::Relaxed);
+}
+
+/// Queue `change`, checking its value against the schema of its option
+pub fn add(mut change: ScheduledChange) -> Result<ScheduledChange, EngineError> {
+    if let Some(key) = change.key.strip_prefix("probing.") {
+        crate::core::schema::validate(key, &change.value).map_err(EngineError::ValidationFailed)?;
+    }
+    if let Some(until) = change.until {
+        let ordered = match (change.at, until) {
+            (ScheduleTrigger::Time(at), ScheduleTrigger::Time(until))
+            | (ScheduleTrigger::Step(at), ScheduleTrigger::Step(until)) => until > at,
+            _ => true,
+        };
+        if !ordered {
+            return Err(EngineError::ConfigError(format!(
+                "the change of {} is reverted before it is applied",
+                change.key
+            )));
+        }
+    }
+    change.id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    change.state = ScheduleState::Pending;
+    change.previous = None;
+    change.error = None;
+    CHANGES.lock().unwrap().push(change.clone());
+    Ok(change)
+}
+
+/// Scheduled changes in the order they were queued
+pub fn list() -> Vec<ScheduledChange> {
+    CHANGES.lock().unwrap().clone()
+}
+
+/// Cancel the change `id` if it is pending, or drop its revert if it is
+/// applied; the value it wrote is kept
+pub fn cancel(id: u64) -> Option<ScheduledChange> {
+    let mut changes = CHANGES.lock().unwrap();
+    let change = changes.iter_mut().find(|x| x.id == id)?;
+    if matches!(
+        change.state,
+        ScheduleState::Pending | ScheduleState::Applied
+    ) {
+        change.state = ScheduleState::Cancelled;
+    }
+    Some(change.clone())
+}
+
+/// Writes due at `now` with the training at `step`
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Apply,
+    Revert,
+}
+
+fn action(change: &ScheduledChange, now: i64, step: Option<i64>) -> Option<Action> {
+    match change.state {
+        ScheduleState::Pending if change.at.is_due(now, step) => Some(Action::Apply),
+        ScheduleState::Applied if change.until.is_some_and(|x| x.is_due(now, step)) => {
+            Some(Action::Revert)
+        }
+        _ => None,
+    }
+}
+
+/// Whether a change is due at `now` with the training at `step`
+pub fn is_due(now: i64, step: Option<i64>) -> bool {
+    CHANGES
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|change| action(change, now, step).is_some())
+}
+
+/// Apply and revert the changes that are due, returning how many were
+pub async fn apply_due() -> usize {
+    let (now, step) = (now(), step());
+    // claimed under the lock, so the step hook and the server never write
+    // the same change twice
+    let due: Vec<(ScheduledChange, Action)> = {
+        let mut changes = CHANGES.lock().unwrap();
+        changes
+            .iter_mut()
+            .filter_map(|change| {
+                let action = action(change, now, step)?;
+                change.state = match action {
+                    Action::Apply => ScheduleState::Applied,
+                    Action::Revert => ScheduleState::Reverted,
+                };
+                Some((change.clone(), action))
+            })
+            .collect()
+    };
+
+    for (change, action) in &due {
+        let result = match action {
+            Action::Apply => {
+                // unmasked, the previous value is restored on revert
+                let dump = config::snapshot().await;
+                let previous = dump
+                    .options
+                    .get(&change.key)
+                    .or_else(|| dump.store.get(&change.key))
+                    .cloned();
+                update(change.id, |x| x.previous = previous);
+                log::info!(
+                    "scheduled change {}: {}={}",
+                    change.id,
+                    change.key,
+                    change.value
+                );
+                config::write(&change.key, &change.value).await
+            }
+            Action::Revert => {
+                log::info!("scheduled change {}: reverting {}", change.id, change.key);
+                match (&change.previous, change.key.starts_with("probing")) {
+                    (Some(previous), _) => config::write(&change.key, previous).await,
+                    // the empty value unsets an option
+                    (None, true) => config::write(&change.key, "").await,
+                    (None, false) => {
+                        config::remove(&change.key).await;
+                        Ok(())
+                    }
+                }
+            }
+        };
+        if let Err(err) = result {
+            log::warn!(
+                "scheduled change {} of {} failed: {err}",
+                change.id,
+                change.key
+            );
+            update(change.id, |x| {
+                x.state = ScheduleState::Failed;
+                x.error = Some(err.to_string());
+            });
+        }
+    }
+    due.len()
+}
+
+fn update(id: u64, f: impl FnOnce(&mut ScheduledChange)) {
+    if let Some(change) = CHANGES.lock().unwrap().iter_mut().find(|x| x.id == id) {
+        f(change);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(key: &str, at: ScheduleTrigger, until: Option<ScheduleTrigger>) -> ScheduledChange {
+        ScheduledChange {
+            id: 0,
+            key: key.to_string(),
+            value: "on".to_string(),
+            at,
+            until,
+            state: ScheduleState::Pending,
+            previous: None,
+            error: None,
+        }
+    }
+
+    fn state(id: u64) -> ScheduleState {
+        list().into_iter().find(|x| x.id == id).unwrap().state
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut window = change(
+            "k",
+            ScheduleTrigger::Step(1000),
+            Some(ScheduleTrigger::Step(1011)),
+        );
+        assert_eq!(action(&window, i64::MAX, Some(999)), None);
+        assert_eq!(action(&window, 0, Some(1000)), Some(Action::Apply));
+        window.state = ScheduleState::Applied;
+        assert_eq!(action(&window, 0, Some(1010)), None);
+        assert_eq!(action(&window, 0, Some(1011)), Some(Action::Revert));
+        window.state = ScheduleState::Cancelled;
+        assert_eq!(action(&window, 0, Some(2000)), None);
+    }
+
+    #[test]
+    fn test_rejects_revert_before_apply() {
+        let err = add(change(
+            "schedule.test.order",
+            ScheduleTrigger::Step(10),
+            Some(ScheduleTrigger::Step(10)),
+        ));
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apply_and_revert_store_key() {
+        let key = "schedule.test.window";
+        config::set(key, "off").await;
+        let added = add(change(
+            key,
+            ScheduleTrigger::Time(0),
+            Some(ScheduleTrigger::Time(1)),
+        ))
+        .unwrap();
+        assert!(added.id > 0);
+
+        apply_due().await;
+        assert_eq!(state(added.id), ScheduleState::Applied);
+        assert_eq!(config::get_str(key).await.as_deref(), Some("on"));
+        let applied = list().into_iter().find(|x| x.id == added.id).unwrap();
+        assert_eq!(applied.previous.as_deref(), Some("off"));
+
+        apply_due().await;
+        assert_eq!(state(added.id), ScheduleState::Reverted);
+        assert_eq!(config::get_str(key).await.as_deref(), Some("off"));
+        config::remove(key).await;
+    }
+
+    #[test]
+    fn test_cancel_pending() {
+        let added = add(change(
+            "schedule.test.cancel",
+            ScheduleTrigger::Step(i64::MAX),
+            None,
+        ))
+        .unwrap();
+        assert_eq!(
+            cancel(added.id).map(|x| x.state),
+            Some(ScheduleState::Cancelled)
+        );
+        assert!(cancel(u64::MAX).is_none());
+    }
+}
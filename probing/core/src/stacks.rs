@@ -0,0 +1,173 @@
+//! Deduplicated call stacks.
+//!
+//! Records that carry a call stack, such as snapshots, mostly capture the
+//! same few stacks over and over. Each distinct stack is stored once, keyed
+//! by its [`fingerprint`], and records keep the 8-byte id instead of the
+//! frames. The `stacks.dictionary` table maps ids back to frames, so that
+//! grouping by stack is a group by an integer:
+//!
+//! ```sql
+//! SELECT d.frames, count(*) FROM samples s JOIN stacks.dictionary d ON s.stack_id = d.id
+//! GROUP BY d.frames
+//! ```
+//!
+//! The fingerprint is a stable hash of the frames, the same in every process
+//! and in the Python fallback, so ids of archived records can be compared
+//! across runs. Past [`MAX_STACKS`] distinct stacks, new ones still get their
+//! id but their frames are not kept.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Once, RwLock};
+
+use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+
+use crate::core::{CustomTable, TablePluginHelper};
+
+/// Number of distinct stacks kept
+pub const MAX_STACKS: usize = 1 << 16;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+struct Stack {
+    frames: Box<[Box<str>]>,
+    /// Times the stack was interned
+    count: u64,
+}
+
+static STACKS: LazyLock<RwLock<HashMap<i64, Stack>>> = LazyLock::new(Default::default);
+
+/// Id of a stack: the 64-bit FNV-1a hash of its frames, outermost first,
+/// each followed by a NUL byte
+pub fn fingerprint<S: AsRef<str>>(frames: &[S]) -> i64 {
+    let mut hash = FNV_OFFSET;
+    for frame in frames {
+        for byte in frame.as_ref().bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash as i64
+}
+
+/// Id of the stack `frames`, outermost first, storing it on first use
+pub fn intern_stack<S: AsRef<str>>(frames: &[S]) -> i64 {
+    let id = fingerprint(frames);
+    let mut stacks = STACKS.write().unwrap();
+    if let Some(stack) = stacks.get_mut(&id) {
+        stack.count += 1;
+        return id;
+    }
+    if stacks.len() >= MAX_STACKS {
+        static FULL: Once = Once::new();
+        FULL.call_once(|| {
+            crate::journal::dropped(
+                "stacks.dictionary",
+                1,
+                format!("more than {MAX_STACKS} distinct stacks"),
+            )
+        });
+        return id;
+    }
+    let frames = frames.iter().map(|f| f.as_ref().into()).collect();
+    stacks.insert(id, Stack { frames, count: 1 });
+    id
+}
+
+/// Frames of the stack `id`, outermost first
+pub fn resolve_stack(id: i64) -> Option<Vec<String>> {
+    STACKS
+        .read()
+        .unwrap()
+        .get(&id)
+        .map(|stack| stack.frames.iter().map(|f| f.to_string()).collect())
+}
+
+/// `stacks.dictionary`, the frames of the stored stacks
+#[derive(Default, Debug)]
+pub struct StacksTable {}
+
+impl CustomTable for StacksTable {
+    fn name() -> &'static str {
+        "dictionary"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("depth", DataType::Int64, false),
+            Field::new("frames", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let stacks = STACKS.read().unwrap();
+        let mut ids: Vec<_> = stacks.keys().copied().collect();
+        ids.sort_unstable();
+        let stacks: Vec<_> = ids.iter().map(|id| &stacks[id]).collect();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from(ids.clone())),
+                Arc::new(Int64Array::from_iter_values(
+                    stacks.iter().map(|s| s.frames.len() as i64),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    stacks.iter().map(|s| s.frames.join("\n")),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stacks.iter().map(|s| s.count as i64),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build stacks batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type StacksPlugin = TablePluginHelper<StacksTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        // shared with the Python fallback in probing.inspect.stacks
+        assert_eq!(fingerprint::<&str>(&[]), FNV_OFFSET as i64);
+        assert_eq!(fingerprint(&["main (a.py:1)"]), -456493945050715160);
+        // frame boundaries are part of the stack
+        assert_ne!(fingerprint(&["ab", "c"]), fingerprint(&["a", "bc"]));
+    }
+
+    #[test]
+    fn test_intern_stack() {
+        let frames = ["stacks.test (a.py:1)", "inner (a.py:2)"];
+        let id = intern_stack(&frames);
+        assert_eq!(intern_stack(&frames.map(String::from)), id);
+        assert_ne!(intern_stack(&frames[..1]), id);
+        assert_eq!(resolve_stack(id).unwrap(), frames);
+
+        let batch = &StacksTable::data()[0];
+        let ids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        let row = ids.iter().position(|v| v == Some(id)).unwrap();
+        let counts = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(counts.value(row), 2);
+    }
+}
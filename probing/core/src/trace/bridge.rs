@@ -0,0 +1,259 @@
+//! Bridge from the `tracing` ecosystem into probing spans.
+//!
+//! [`ProbingLayer`] converts spans and events emitted through the `tracing`
+//! crate (DataFusion, axum, tokio, ...) into [`Span`]s and hands them to the
+//! registered [`SpanSink`](super::SpanSink)s, the same way
+//! [`probe_span!`](crate::probe_span) does for the agent's own code.
+
+use std::fmt::Debug;
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::buffer::{submit, TraceRecord};
+use super::span::{attr, Attribute, Event, Location, Span, Timestamp};
+
+/// Span kind assigned to spans coming from the `tracing` crate.
+const TRACING_KIND: &str = "tracing";
+
+/// `tracing_subscriber` layer forwarding spans to the probing trace sinks.
+pub struct ProbingLayer {
+    max_level: Level,
+}
+
+impl Default for ProbingLayer {
+    fn default() -> Self {
+        Self::new(Level::INFO)
+    }
+}
+
+impl ProbingLayer {
+    /// Capture spans and events up to `max_level` verbosity.
+    pub fn new(max_level: Level) -> Self {
+        Self { max_level }
+    }
+}
+
+/// Install a global subscriber made of a [`ProbingLayer`] capturing spans up
+/// to `level` (`error`, `warn`, `info`, `debug` or `trace`).
+///
+/// Fails if the process already set a global `tracing` subscriber.
+pub fn install_tracing_bridge(level: &str) -> anyhow::Result<()> {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let max_level: Level = level
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid tracing level: {level}"))?;
+    let subscriber = tracing_subscriber::registry().with(ProbingLayer::new(max_level));
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct AttrVisitor {
+    message: Option<String>,
+    attrs: Vec<Attribute>,
+}
+
+impl Visit for AttrVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.attrs.push(attr(field.name(), value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.attrs.push(attr(field.name(), value as i64));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.attrs.push(attr(field.name(), value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.attrs.push(attr(field.name(), value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.attrs.push(attr(field.name(), value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{value:?}"));
+        } else {
+            self.attrs.push(attr(field.name(), format!("{value:?}")));
+        }
+    }
+}
+
+fn location(meta: &Metadata<'_>) -> String {
+    match (meta.file(), meta.line()) {
+        (Some(file), Some(line)) => format!("{file}:{line}"),
+        _ => meta.target().to_string(),
+    }
+}
+
+impl<S> Layer<S> for ProbingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        *metadata.level() <= self.max_level
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+        let meta = attrs.metadata();
+        let loc = location(meta);
+
+        let mut span = match span_ref.parent() {
+            Some(parent) => match parent.extensions().get::<Span>() {
+                Some(parent) => {
+                    Span::new_child(parent, meta.name(), Some(TRACING_KIND), Some(&loc))
+                }
+                None => Span::new_root(meta.name(), Some(TRACING_KIND), Some(&loc)),
+            },
+            None => Span::new_root(meta.name(), Some(TRACING_KIND), Some(&loc)),
+        };
+
+        let mut visitor = AttrVisitor::default();
+        attrs.record(&mut visitor);
+        span.attrs.push(attr("target", meta.target()));
+        span.attrs.extend(visitor.attrs);
+
+        submit(TraceRecord::Start(span.clone()));
+        span_ref.extensions_mut().insert(span);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span_ref) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span_ref.extensions_mut();
+        if let Some(span) = extensions.get_mut::<Span>() {
+            let mut visitor = AttrVisitor::default();
+            values.record(&mut visitor);
+            for attr in visitor.attrs {
+                let _ = span.add_attr(attr.key(), attr.value().clone());
+            }
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        let mut visitor = AttrVisitor::default();
+        event.record(&mut visitor);
+
+        let mut attributes = vec![attr("level", meta.level().as_str())];
+        attributes.extend(visitor.attrs);
+        let record = Event {
+            name: visitor.message.unwrap_or_else(|| meta.name().to_string()),
+            location: Some(Location::new(&location(meta))),
+            timestamp: Timestamp::now(),
+            attributes,
+        };
+
+        let span = ctx
+            .event_span(event)
+            .and_then(|span_ref| span_ref.extensions().get::<Span>().cloned());
+        submit(TraceRecord::Event(span, record));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span_ref) = ctx.span(&id) else {
+            return;
+        };
+        let span = span_ref.extensions_mut().remove::<Span>();
+        if let Some(mut span) = span {
+            span.finish();
+            submit(TraceRecord::End(span));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::trace::{register_sink, Ele, SpanSink};
+
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<Span>>,
+        events: Mutex<Vec<(Event, Option<u64>)>>,
+    }
+
+    impl SpanSink for Recorder {
+        fn on_start(&self, _span: &Span) {}
+
+        fn on_event(&self, span: Option<&Span>, event: &Event) {
+            self.events
+                .lock()
+                .unwrap()
+                .push((event.clone(), span.map(|s| s.span_id)));
+        }
+
+        fn on_end(&self, span: &Span) {
+            self.spans.lock().unwrap().push(span.clone());
+        }
+    }
+
+    #[test]
+    fn test_tracing_spans_are_forwarded() {
+        let recorder = Arc::new(Recorder::default());
+        register_sink(recorder.clone());
+
+        let subscriber = tracing_subscriber::registry().with(ProbingLayer::default());
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("bridge_outer", rows = 3);
+            let _entered = outer.enter();
+            let inner = tracing::info_span!("bridge_inner");
+            inner.in_scope(|| tracing::info!(hit = true, "bridge event"));
+            drop(inner);
+            let _skipped = tracing::debug_span!("bridge_debug");
+        });
+        crate::trace::flush();
+
+        let spans: Vec<_> = recorder
+            .spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|s| s.name.starts_with("bridge_"))
+            .cloned()
+            .collect();
+        assert_eq!(spans.len(), 2, "debug span should be filtered out");
+        let (inner, outer) = (&spans[0], &spans[1]);
+        assert_eq!(inner.name, "bridge_inner");
+        assert_eq!(inner.parent_id, Some(outer.span_id));
+        assert_eq!(inner.trace_id, outer.trace_id);
+        assert_eq!(outer.kind.as_deref(), Some(TRACING_KIND));
+        assert!(outer.is_ended());
+        assert!(outer
+            .attrs
+            .iter()
+            .any(|a| a.key() == "rows" && a.value() == &Ele::I64(3)));
+
+        let events = recorder.events.lock().unwrap();
+        let (event, span_id) = events
+            .iter()
+            .find(|(e, _)| e.name == "bridge event")
+            .expect("event should be forwarded");
+        assert_eq!(*span_id, Some(inner.span_id));
+        assert!(event
+            .attributes
+            .iter()
+            .any(|a| a.key() == "hit" && a.value() == &Ele::BOOL(true)));
+    }
+}
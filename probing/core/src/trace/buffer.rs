@@ -0,0 +1,217 @@
+//! Per-thread ingestion buffers for trace records.
+//!
+//! Recording a span must not contend on a shared lock, so every thread pushes
+//! its records into its own bounded lock-free queue. A background collector
+//! drains all queues into the registered sinks. Records are never dropped:
+//!
+//! - a thread whose queue is full drains it inline before pushing;
+//! - a thread's queue is drained when the thread exits;
+//! - [`flush`] drains every queue on demand, e.g. before reading the tables.
+//!
+//! Each queue is drained under its own lock, so the sinks observe the records
+//! of one thread in the order they were recorded. Other producers, such as
+//! the tables Python appends rows to, queue their work with [`defer`] to get
+//! the same guarantees.
+
+use std::sync::{Arc, LazyLock, Mutex, Once};
+use std::time::Duration;
+
+use crossbeam_queue::ArrayQueue;
+
+use super::collector::dispatch;
+use super::span::{Event, Span};
+
+/// Number of records buffered per thread before draining inline.
+const BUFFER_CAPACITY: usize = 4096;
+
+/// Interval between two background collections.
+const COLLECT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A span lifecycle record waiting to be handed to the sinks.
+pub(crate) enum TraceRecord {
+    Start(Span),
+    Event(Option<Span>, Event),
+    End(Span),
+    /// Work queued with [`defer`]
+    Deferred(Box<dyn FnOnce() + Send>),
+}
+
+impl TraceRecord {
+    fn dispatch(self) {
+        match self {
+            TraceRecord::Start(span) => dispatch(|sink| sink.on_start(&span)),
+            TraceRecord::Event(span, event) => {
+                dispatch(|sink| sink.on_event(span.as_ref(), &event))
+            }
+            TraceRecord::End(span) => dispatch(|sink| sink.on_end(&span)),
+            TraceRecord::Deferred(f) => f(),
+        }
+    }
+}
+
+struct ThreadBuffer {
+    queue: ArrayQueue<TraceRecord>,
+    /// Serializes consumers; producers never take it.
+    drain: Mutex<()>,
+}
+
+impl ThreadBuffer {
+    fn drain(&self) {
+        let _guard = self.drain.lock().unwrap();
+        while let Some(record) = self.queue.pop() {
+            record.dispatch();
+        }
+    }
+}
+
+static BUFFERS: LazyLock<Mutex<Vec<Arc<ThreadBuffer>>>> = LazyLock::new(Default::default);
+
+/// Owner handle of the current thread's buffer, drained on thread exit.
+struct LocalBuffer(Arc<ThreadBuffer>);
+
+impl LocalBuffer {
+    fn new() -> Self {
+        start_collector();
+        let buffer = Arc::new(ThreadBuffer {
+            queue: ArrayQueue::new(BUFFER_CAPACITY),
+            drain: Mutex::new(()),
+        });
+        BUFFERS.lock().unwrap().push(buffer.clone());
+        LocalBuffer(buffer)
+    }
+}
+
+impl Drop for LocalBuffer {
+    fn drop(&mut self) {
+        self.0.drain();
+        if let Ok(mut buffers) = BUFFERS.lock() {
+            buffers.retain(|b| !Arc::ptr_eq(b, &self.0));
+        }
+    }
+}
+
+thread_local! {
+    static LOCAL_BUFFER: LocalBuffer = LocalBuffer::new();
+}
+
+/// Queue a record from the current thread.
+pub(crate) fn submit(record: TraceRecord) {
+    let mut record = Some(record);
+    let _ = LOCAL_BUFFER.try_with(|local| {
+        let Some(mut pending) = record.take() else {
+            return;
+        };
+        while let Err(rejected) = local.0.queue.push(pending) {
+            local.0.drain();
+            pending = rejected;
+        }
+    });
+    // The buffer is already gone during thread teardown, hand the record over
+    // directly instead.
+    if let Some(record) = record {
+        record.dispatch();
+    }
+}
+
+/// Run `f` when the buffer of the current thread is drained, after the
+/// records queued before it.
+pub fn defer(f: impl FnOnce() + Send + 'static) {
+    submit(TraceRecord::Deferred(Box::new(f)));
+}
+
+/// Drain the buffers of all threads into the sinks.
+pub fn flush() {
+    let buffers: Vec<_> = BUFFERS.lock().unwrap().clone();
+    for buffer in buffers {
+        buffer.drain();
+    }
+}
+
+fn start_collector() {
+    static COLLECTOR: Once = Once::new();
+    COLLECTOR.call_once(|| {
+        let spawned = std::thread::Builder::new()
+            .name("probing-trace-collector".to_string())
+            .spawn(|| loop {
+                std::thread::sleep(COLLECT_INTERVAL);
+                flush();
+            });
+        if let Err(err) = spawned {
+            log::error!("failed to start trace collector: {err}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::trace::{register_sink, SpanGuard, SpanSink};
+
+    const THREADS: usize = 8;
+    /// More than one buffer worth of records per thread to exercise overflow.
+    const SPANS_PER_THREAD: usize = BUFFER_CAPACITY;
+
+    #[derive(Default)]
+    struct Counter {
+        started: AtomicUsize,
+        ended: AtomicUsize,
+    }
+
+    impl SpanSink for Counter {
+        fn on_start(&self, span: &Span) {
+            if span.name == "buffer.span" {
+                self.started.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        fn on_event(&self, _span: Option<&Span>, _event: &Event) {}
+
+        fn on_end(&self, span: &Span) {
+            if span.name == "buffer.span" {
+                self.ended.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_records_from_all_threads_are_delivered() {
+        let counter = Arc::new(Counter::default());
+        register_sink(counter.clone());
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    for _ in 0..SPANS_PER_THREAD {
+                        let _span = SpanGuard::enter("buffer.span", None, None).started();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        flush();
+
+        assert_eq!(
+            counter.started.load(Ordering::Relaxed),
+            THREADS * SPANS_PER_THREAD
+        );
+        assert_eq!(
+            counter.ended.load(Ordering::Relaxed),
+            THREADS * SPANS_PER_THREAD
+        );
+    }
+
+    #[test]
+    fn test_deferred_work_runs_in_order() {
+        let order = Arc::new(Mutex::new(vec![]));
+        for i in 0..3 {
+            let order = order.clone();
+            defer(move || order.lock().unwrap().push(i));
+        }
+        flush();
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,307 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use super::buffer::{submit, TraceRecord};
+use super::span::{Attribute, Event, Span, Timestamp};
+
+/// Receiver of spans and events recorded by the agent's own Rust code.
+///
+/// Sinks are registered with [`register_sink`]. Records are buffered per
+/// thread and handed to the sinks from the collector, see [`flush`](super::flush).
+pub trait SpanSink: Send + Sync {
+    fn on_start(&self, span: &Span);
+    fn on_event(&self, span: Option<&Span>, event: &Event);
+    fn on_end(&self, span: &Span);
+}
+
+static SINKS: LazyLock<RwLock<Vec<Arc<dyn SpanSink>>>> = LazyLock::new(Default::default);
+
+thread_local! {
+    static SPAN_STACK: RefCell<Vec<Span>> = const { RefCell::new(Vec::new()) };
+}
+
+tokio::task_local! {
+    /// Span of the future run by [`SpanGuard::instrument`], which may move
+    /// between worker threads and so cannot live on [`SPAN_STACK`]
+    static TASK_SPAN: Span;
+}
+
+/// Innermost span of this thread, else the span of the current instrumented task.
+pub(crate) fn current_span() -> Option<Span> {
+    SPAN_STACK
+        .with(|stack| stack.borrow().last().cloned())
+        .or_else(|| TASK_SPAN.try_with(Span::clone).ok())
+}
+
+/// Register a sink receiving all spans created with [`probe_span!`](crate::probe_span).
+pub fn register_sink(sink: Arc<dyn SpanSink>) {
+    SINKS.write().unwrap().push(sink);
+}
+
+pub(crate) fn dispatch<F: Fn(&dyn SpanSink)>(f: F) {
+    for sink in SINKS.read().unwrap().iter() {
+        f(sink.as_ref());
+    }
+}
+
+/// RAII guard returned by [`probe_span!`](crate::probe_span).
+///
+/// The span becomes the parent of spans created on the same thread until the
+/// guard is dropped, at which point the span is ended and handed to the sinks.
+/// Do not hold a guard across `.await`, use [`SpanGuard::instrument`] instead.
+pub struct SpanGuard {
+    /// Always set, only taken when the guard is dropped
+    span: Option<Span>,
+}
+
+impl SpanGuard {
+    pub fn enter(name: &str, kind: Option<&str>, location: Option<&str>) -> Self {
+        let span = match current_span() {
+            Some(parent) => Span::new_child(&parent, name, kind, location),
+            None => Span::new_root(name, kind, location),
+        };
+        SPAN_STACK.with(|stack| stack.borrow_mut().push(span.clone()));
+        SpanGuard { span: Some(span) }
+    }
+
+    /// Attach a creation-time attribute, used by [`probe_span!`](crate::probe_span).
+    pub fn with_attr(mut self, attr: Attribute) -> Self {
+        self.record(attr);
+        self
+    }
+
+    /// Notify sinks that the span started, once all attributes are attached.
+    pub fn started(self) -> Self {
+        submit(TraceRecord::Start(self.span().clone()));
+        self
+    }
+
+    /// Record an attribute discovered while the span is running.
+    pub fn record(&mut self, attr: Attribute) {
+        if let Some(span) = self.span.as_mut() {
+            span.attrs.push(attr);
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        self.span.as_ref().expect("span is only taken on drop")
+    }
+
+    /// Run `future` inside the span and end the span when it completes.
+    ///
+    /// The span leaves the thread's stack right away and is the parent of
+    /// spans and events recorded by `future` on whichever thread polls it.
+    ///
+    /// ```
+    /// # async fn run() {
+    /// use probing_core::probe_span;
+    ///
+    /// let rows = probe_span!("engine.query", kind = "engine")
+    ///     .instrument(async { 42 })
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn instrument<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+        let span_id = self.span().span_id;
+        SPAN_STACK.with(|stack| stack.borrow_mut().retain(|s| s.span_id != span_id));
+        async move {
+            let output = TASK_SPAN.scope(self.span().clone(), future).await;
+            drop(self);
+            output
+        }
+    }
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        let Some(mut span) = self.span.take() else {
+            return;
+        };
+        let span_id = span.span_id;
+        // Instrumented guards already left the stack, so remove by id instead
+        // of blindly popping.
+        let _ = SPAN_STACK.try_with(|stack| stack.borrow_mut().retain(|s| s.span_id != span_id));
+        span.finish();
+        super::slo::observe_span(&span, false);
+        submit(TraceRecord::End(span));
+    }
+}
+
+/// Record an event on the innermost span of the current thread or task.
+pub fn record_event(name: &str, location: Option<&str>, attributes: Vec<Attribute>) {
+    let event = Event {
+        name: name.to_string(),
+        location: location.map(super::Location::new),
+        timestamp: Timestamp::now(),
+        attributes,
+    };
+    submit(TraceRecord::Event(current_span(), event));
+}
+
+/// Open a span around the agent's own code, ended when the guard is dropped.
+///
+/// ```
+/// use probing_core::probe_span;
+///
+/// let _span = probe_span!("engine.query", kind = "engine", sql = "select 1");
+/// ```
+#[macro_export]
+macro_rules! probe_span {
+    ($name:expr, kind = $kind:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::trace::SpanGuard::enter(
+            $name,
+            Some($kind),
+            Some(concat!(file!(), ":", line!())),
+        )
+        $(.with_attr($crate::trace::attr(stringify!($key), $value)))*
+        .started()
+    };
+    ($name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::trace::SpanGuard::enter($name, None, Some(concat!(file!(), ":", line!())))
+        $(.with_attr($crate::trace::attr(stringify!($key), $value)))*
+        .started()
+    };
+}
+
+/// Record an event on the current [`probe_span!`] of this thread.
+///
+/// ```
+/// use probing_core::probe_event;
+///
+/// probe_event!("cache.miss", key = "nodes");
+/// ```
+#[macro_export]
+macro_rules! probe_event {
+    ($name:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::trace::record_event(
+            $name,
+            Some(concat!(file!(), ":", line!())),
+            vec![$($crate::trace::attr(stringify!($key), $value)),*],
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::trace::Ele;
+
+    #[derive(Default)]
+    struct Recorder {
+        records: Mutex<Vec<(String, String, Option<u64>)>>,
+    }
+
+    impl SpanSink for Recorder {
+        fn on_start(&self, span: &Span) {
+            self.records.lock().unwrap().push((
+                "start".into(),
+                span.name.to_string(),
+                span.parent_id,
+            ));
+        }
+
+        fn on_event(&self, span: Option<&Span>, event: &Event) {
+            self.records.lock().unwrap().push((
+                "event".into(),
+                event.name.clone(),
+                span.map(|s| s.span_id),
+            ));
+        }
+
+        fn on_end(&self, span: &Span) {
+            assert!(span.is_ended());
+            self.records.lock().unwrap().push((
+                "end".into(),
+                span.name.to_string(),
+                Some(span.span_id),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_probe_span_nesting_and_events() {
+        let recorder = Arc::new(Recorder::default());
+        register_sink(recorder.clone());
+
+        let outer_id;
+        {
+            let outer = probe_span!("collector.outer", kind = "test", rows = 3i64);
+            outer_id = outer.span().span_id;
+            assert_eq!(outer.span().kind.as_deref(), Some("test"));
+            assert_eq!(outer.span().attrs[0].value(), &Ele::I64(3));
+            {
+                let _inner = probe_span!("collector.inner");
+                probe_event!("collector.event", hit = true);
+            }
+        }
+        crate::trace::flush();
+
+        let records: Vec<_> = recorder
+            .records
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, name, _)| name.starts_with("collector."))
+            .cloned()
+            .collect();
+        let kinds: Vec<_> = records.iter().map(|(k, n, _)| format!("{k}:{n}")).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "start:collector.outer",
+                "start:collector.inner",
+                "event:collector.event",
+                "end:collector.inner",
+                "end:collector.outer",
+            ]
+        );
+        assert_eq!(records[0].2, None);
+        assert_eq!(records[1].2, Some(outer_id));
+        SPAN_STACK.with(|stack| assert!(stack.borrow().is_empty()));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_instrumented_span_across_worker_threads() {
+        let recorder = Arc::new(Recorder::default());
+        register_sink(recorder.clone());
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                tokio::spawn(async {
+                    let outer = probe_span!("collector.task");
+                    let outer_id = outer.span().span_id;
+                    outer
+                        .instrument(async move {
+                            for _ in 0..8 {
+                                tokio::task::yield_now().await;
+                                SPAN_STACK.with(|stack| assert!(stack.borrow().is_empty()));
+                                let child = probe_span!("collector.task.child");
+                                assert_eq!(child.span().parent_id, Some(outer_id));
+                            }
+                        })
+                        .await;
+                    outer_id
+                })
+            })
+            .collect();
+        let mut ids = Vec::new();
+        for task in tasks {
+            ids.push(task.await.unwrap());
+        }
+        let leftovers: Vec<_> = (0..64)
+            .map(|_| tokio::spawn(async { SPAN_STACK.with(|stack| stack.borrow().len()) }))
+            .collect();
+        for leftover in leftovers {
+            assert_eq!(leftover.await.unwrap(), 0);
+        }
+        crate::trace::flush();
+
+        let records = recorder.records.lock().unwrap();
+        for id in ids {
+            assert!(records.contains(&("end".into(), "collector.task".into(), Some(id))));
+        }
+    }
+}
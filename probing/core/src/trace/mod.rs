@@ -1,6 +1,24 @@
+#[cfg(feature = "tracing-bridge")]
+mod bridge;
+mod buffer;
+mod collector;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+pub mod slo;
 mod span;
+mod strings;
 
+#[cfg(feature = "tracing-bridge")]
+pub use bridge::{install_tracing_bridge, ProbingLayer};
+pub use buffer::{defer, flush};
+#[cfg(test)]
+pub(crate) use collector::current_span;
+pub use collector::{record_event, register_sink, SpanGuard, SpanSink};
 pub use span::{attr, Attribute, Ele, Event, Location, Span, SpanStatus, Timestamp};
+pub use span::{cpu_time_enabled, set_cpu_time, thread_cpu_time};
+pub use strings::{
+    intern, resolve, strings, try_intern, InternedStr, StrId, StringsPlugin, StringsTable,
+};
 
 // --- Custom Error Type ---
 
@@ -0,0 +1,124 @@
+//! Protobuf encoding of trace records, see `TraceRecord` in `probing.proto`.
+//!
+//! The records mirror the rows of the `trace_event` table: a span produces a
+//! `span_start` and a `span_end` record, events are recorded on their own.
+
+use probing_proto::protobuf as pb;
+
+use super::span::{Attribute, Event, Location, Span, Timestamp};
+
+fn location(loc: Option<&Location>) -> Option<String> {
+    loc.map(|loc| loc.as_str().to_string())
+}
+
+fn attributes(attrs: &[Attribute]) -> Vec<pb::TraceAttribute> {
+    attrs
+        .iter()
+        .map(|attr| pb::TraceAttribute {
+            key: attr.key().to_string(),
+            value: Some(attr.value().clone().into()),
+        })
+        .collect()
+}
+
+fn time(ts: Timestamp) -> u64 {
+    u64::try_from(ts.0).unwrap_or(u64::MAX)
+}
+
+impl Span {
+    /// The `span_start` record of this span.
+    pub fn start_record(&self) -> pb::TraceRecord {
+        pb::TraceRecord {
+            record_type: pb::TraceRecordType::SpanStart.into(),
+            trace_id: self.trace_id,
+            span_id: self.span_id,
+            parent_id: self.parent_id,
+            name: self.name.to_string(),
+            time: time(self.start),
+            thread_id: self.thread_id,
+            kind: self.kind.as_ref().map(|kind| kind.to_string()),
+            location: location(self.loc.as_ref()),
+            attributes: attributes(&self.attrs),
+        }
+    }
+
+    /// The `span_end` record of this span, `None` while it is running.
+    pub fn end_record(&self) -> Option<pb::TraceRecord> {
+        let end = self.end?;
+        Some(pb::TraceRecord {
+            record_type: pb::TraceRecordType::SpanEnd.into(),
+            trace_id: self.trace_id,
+            span_id: self.span_id,
+            parent_id: self.parent_id,
+            name: self.name.to_string(),
+            time: time(end),
+            thread_id: self.thread_id,
+            ..Default::default()
+        })
+    }
+}
+
+impl Event {
+    /// The `event` record of this event, recorded on `span` if any.
+    pub fn record(&self, span: Option<&Span>) -> pb::TraceRecord {
+        pb::TraceRecord {
+            record_type: pb::TraceRecordType::Event.into(),
+            trace_id: span.map(|s| s.trace_id).unwrap_or_default(),
+            span_id: span.map(|s| s.span_id).unwrap_or_default(),
+            parent_id: span.and_then(|s| s.parent_id),
+            name: self.name.clone(),
+            time: time(self.timestamp),
+            thread_id: span.map(|s| s.thread_id).unwrap_or_default(),
+            kind: None,
+            location: location(self.location.as_ref()),
+            attributes: attributes(&self.attributes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message as _;
+
+    use super::*;
+    use crate::trace::attr;
+
+    #[test]
+    fn test_span_records() {
+        let mut span = Span::new_root("step", Some("train"), Some("train.py:42"));
+        span.attrs.push(attr("batch", 32i64));
+
+        let start = span.start_record();
+        assert_eq!(start.record_type(), pb::TraceRecordType::SpanStart);
+        assert_eq!(start.kind.as_deref(), Some("train"));
+        assert_eq!(start.location.as_deref(), Some("train.py:42"));
+        assert_eq!(start.attributes[0].key, "batch");
+        assert!(span.end_record().is_none());
+
+        span.finish();
+        let end = span.end_record().unwrap();
+        assert_eq!(end.record_type(), pb::TraceRecordType::SpanEnd);
+        assert_eq!(end.span_id, span.span_id);
+        assert!(end.time >= start.time);
+
+        let decoded = pb::TraceRecord::decode(start.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, start);
+    }
+
+    #[test]
+    fn test_event_record() {
+        let span = Span::new_root("step", None, None);
+        let event = Event {
+            name: "loss".to_string(),
+            location: None,
+            timestamp: Timestamp(42),
+            attributes: vec![attr("value", 0.5)],
+        };
+
+        let record = event.record(Some(&span));
+        assert_eq!(record.record_type(), pb::TraceRecordType::Event);
+        assert_eq!(record.span_id, span.span_id);
+        assert_eq!(record.time, 42);
+        assert_eq!(event.record(None).span_id, 0);
+    }
+}
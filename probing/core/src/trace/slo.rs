@@ -0,0 +1,390 @@
+//! Latency objectives of spans.
+//!
+//! `slo.<span>=<threshold>@<percent>[/<window>]`, e.g.
+//! `slo.train_step=250ms@99%`, declares that 99% of the spans named or of
+//! kind `train_step` end within 250ms. `_` and `.` are interchangeable in
+//! the span name, so the objective can be set from `PROBING_SLO_TRAIN_STEP`.
+//! Ended spans are counted in 10 second buckets over the window, one hour by
+//! default; a span over the threshold, or ended with an error, is bad.
+//!
+//! The burn rate is the fraction of bad spans divided by the fraction the
+//! objective allows: at 1 the error budget lasts exactly the window. When the
+//! burn rate of the last five minutes reaches [`FAST_BURN`] an `alert_fired`
+//! event is published, once until it drops below again.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use probing_proto::prelude::{AgentEvent, EventKind};
+
+use super::Span;
+
+/// Width of the buckets spans are counted in
+pub const BUCKET_SECONDS: u64 = 10;
+
+/// Window of an objective without an explicit one
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Window of the short burn rate, the one alerts are raised on
+pub const SHORT_WINDOW: Duration = Duration::from_secs(300);
+
+/// Short burn rate from which an alert is raised
+pub const FAST_BURN: f64 = 10.0;
+
+/// Spans of the short window needed before an alert is raised
+pub const MIN_SAMPLES: u64 = 10;
+
+/// A latency objective
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Objective {
+    pub threshold: Duration,
+    /// Fraction of the spans that must end within the threshold
+    pub target: f64,
+    pub window: Duration,
+}
+
+/// `250ms`, `1.5s`, `30m`, in ns, us, ms, s, m or h
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| c.is_ascii_alphabetic())
+        .ok_or_else(|| format!("duration `{text}` has no unit"))?;
+    let (value, unit) = text.split_at(split);
+    let value: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid duration `{text}`"))?;
+    let seconds = match unit {
+        "ns" => 1e-9,
+        "us" => 1e-6,
+        "ms" => 1e-3,
+        "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(format!("duration `{text}` has unknown unit `{unit}`")),
+    };
+    if !value.is_finite() || value <= 0.0 {
+        return Err(format!("duration `{text}` must be positive"));
+    }
+    Ok(Duration::from_secs_f64(value * seconds))
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (threshold, rest) = s
+            .split_once('@')
+            .ok_or_else(|| format!("objective `{s}` is not <threshold>@<percent>"))?;
+        let (percent, window) = match rest.split_once('/') {
+            Some((percent, window)) => (percent, Some(window)),
+            None => (rest, None),
+        };
+        let percent: f64 = percent
+            .trim()
+            .trim_end_matches('%')
+            .parse()
+            .map_err(|_| format!("invalid percent in `{s}`"))?;
+        if !(percent > 0.0 && percent < 100.0) {
+            return Err(format!("percent in `{s}` must be between 0 and 100"));
+        }
+        let window = match window {
+            Some(window) => parse_duration(window)?,
+            None => DEFAULT_WINDOW,
+        };
+        if window.as_secs() < BUCKET_SECONDS {
+            return Err(format!("window in `{s}` is shorter than {BUCKET_SECONDS}s"));
+        }
+        Ok(Objective {
+            threshold: parse_duration(threshold)?,
+            target: percent / 100.0,
+            window,
+        })
+    }
+}
+
+impl Display for Objective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}ms@{}%/{}s",
+            self.threshold.as_secs_f64() * 1e3,
+            self.target * 100.0,
+            self.window.as_secs()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    /// Seconds since the epoch divided by [`BUCKET_SECONDS`]
+    index: u64,
+    total: u64,
+    bad: u64,
+}
+
+#[derive(Debug)]
+struct Tracker {
+    /// Name the objective was declared with
+    name: String,
+    objective: Objective,
+    buckets: Mutex<VecDeque<Bucket>>,
+    burning: AtomicBool,
+}
+
+impl Tracker {
+    /// Spans and bad spans of the buckets in the `window` before `now`
+    fn counts(buckets: &VecDeque<Bucket>, now: u64, window: Duration) -> (u64, u64) {
+        let first = (now.saturating_sub(window.as_secs())) / BUCKET_SECONDS;
+        buckets
+            .iter()
+            .filter(|bucket| bucket.index > first)
+            .fold((0, 0), |(total, bad), b| (total + b.total, bad + b.bad))
+    }
+
+    fn burn_rate(&self, total: u64, bad: u64) -> Option<f64> {
+        (total > 0).then(|| bad as f64 / total as f64 / (1.0 - self.objective.target))
+    }
+
+    fn record(&self, now: u64, bad: bool) {
+        let index = now / BUCKET_SECONDS;
+        let (total, bad_short) = {
+            let mut buckets = self.buckets.lock().unwrap();
+            match buckets.back_mut() {
+                Some(bucket) if bucket.index == index => {
+                    bucket.total += 1;
+                    bucket.bad += bad as u64;
+                }
+                _ => buckets.push_back(Bucket {
+                    index,
+                    total: 1,
+                    bad: bad as u64,
+                }),
+            }
+            let oldest = now.saturating_sub(self.objective.window.as_secs()) / BUCKET_SECONDS;
+            while buckets.front().is_some_and(|bucket| bucket.index <= oldest) {
+                buckets.pop_front();
+            }
+            Self::counts(&buckets, now, SHORT_WINDOW)
+        };
+
+        let burn = self.burn_rate(total, bad_short).unwrap_or_default();
+        let fast = total >= MIN_SAMPLES && burn >= FAST_BURN;
+        if fast && !self.burning.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "SLO {} burns its error budget {burn:.1}x too fast",
+                self.name
+            );
+            crate::events::publish(
+                AgentEvent::new(
+                    EventKind::AlertFired,
+                    "slo",
+                    format!("{} burns its error budget {burn:.1}x too fast", self.name),
+                )
+                .with_detail("slo", &self.name)
+                .with_detail("objective", self.objective.to_string())
+                .with_detail("burn_rate", format!("{burn:.2}")),
+            );
+        } else if burn < FAST_BURN {
+            self.burning.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Objectives by normalized span name
+static OBJECTIVES: LazyLock<RwLock<BTreeMap<String, Tracker>>> = LazyLock::new(Default::default);
+
+/// Whether any objective is declared, checked before looking spans up
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+fn normalize(name: &str) -> String {
+    name.replace('_', ".")
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Declare the objective `spec` of the spans `name`, or remove it when
+/// `spec` is empty. Returns the previous objective.
+pub fn define(name: &str, spec: &str) -> Result<Option<Objective>, String> {
+    let key = normalize(name.trim());
+    if key.is_empty() {
+        return Err("empty span name".to_string());
+    }
+    let mut objectives = OBJECTIVES.write().unwrap();
+    let old = if spec.trim().is_empty() {
+        objectives.remove(&key)
+    } else {
+        let objective: Objective = spec.parse()?;
+        objectives.insert(
+            key,
+            Tracker {
+                name: name.trim().to_string(),
+                objective,
+                buckets: Default::default(),
+                burning: AtomicBool::new(false),
+            },
+        )
+    };
+    ACTIVE.store(!objectives.is_empty(), Ordering::Relaxed);
+    Ok(old.map(|tracker| tracker.objective))
+}
+
+/// Declared objectives, with the names they were declared with
+pub fn objectives() -> Vec<(String, Objective)> {
+    let objectives = OBJECTIVES.read().unwrap();
+    objectives
+        .values()
+        .map(|tracker| (tracker.name.clone(), tracker.objective))
+        .collect()
+}
+
+/// Count a span named `name` of kind `kind` that took `duration`
+pub fn observe(name: &str, kind: Option<&str>, duration: Duration, error: bool) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    observe_at(name, kind, duration, error, now_seconds());
+}
+
+fn observe_at(name: &str, kind: Option<&str>, duration: Duration, error: bool, now: u64) {
+    let objectives = OBJECTIVES.read().unwrap();
+    let keys = std::iter::once(name).chain(kind);
+    for key in keys {
+        if let Some(tracker) = objectives.get(&normalize(key)) {
+            tracker.record(now, error || duration > tracker.objective.threshold);
+            return;
+        }
+    }
+}
+
+/// Count an ended span
+pub fn observe_span(span: &Span, error: bool) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(duration) = span.duration() {
+        observe(&span.name, span.kind.as_deref(), duration, error);
+    }
+}
+
+/// A row of `slo.status`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SloStatus {
+    pub name: String,
+    pub objective: Objective,
+    /// Spans and bad spans in the window
+    pub total: u64,
+    pub bad: u64,
+    /// Percent of the spans of the window within the objective
+    pub compliance: Option<f64>,
+    pub burn_rate: Option<f64>,
+    /// Burn rate of the last five minutes
+    pub burn_rate_short: Option<f64>,
+    /// Fraction of the error budget of the window left, negative once spent
+    pub budget_remaining: Option<f64>,
+    pub burning: bool,
+}
+
+/// Compliance and burn rates of the declared objectives
+pub fn status() -> Vec<SloStatus> {
+    status_at(now_seconds())
+}
+
+fn status_at(now: u64) -> Vec<SloStatus> {
+    let objectives = OBJECTIVES.read().unwrap();
+    objectives
+        .values()
+        .map(|tracker| {
+            let buckets = tracker.buckets.lock().unwrap();
+            let (total, bad) = Tracker::counts(&buckets, now, tracker.objective.window);
+            let (short_total, short_bad) = Tracker::counts(&buckets, now, SHORT_WINDOW);
+            let burn_rate = tracker.burn_rate(total, bad);
+            SloStatus {
+                name: tracker.name.clone(),
+                objective: tracker.objective,
+                total,
+                bad,
+                compliance: (total > 0).then(|| 100.0 * (total - bad) as f64 / total as f64),
+                burn_rate,
+                burn_rate_short: tracker.burn_rate(short_total, short_bad),
+                budget_remaining: burn_rate.map(|burn| 1.0 - burn),
+                burning: tracker.burning.load(Ordering::Relaxed),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_objective() {
+        let objective: Objective = "250ms@99%".parse().unwrap();
+        assert_eq!(objective.threshold, Duration::from_millis(250));
+        assert!((objective.target - 0.99).abs() < 1e-9);
+        assert_eq!(objective.window, DEFAULT_WINDOW);
+
+        let objective: Objective = "1.5s@99.9/30m".parse().unwrap();
+        assert_eq!(objective.threshold, Duration::from_millis(1500));
+        assert_eq!(objective.window, Duration::from_secs(1800));
+
+        assert!("250ms".parse::<Objective>().is_err());
+        assert!("250@99%".parse::<Objective>().is_err());
+        assert!("250ms@100%".parse::<Objective>().is_err());
+        assert!("-1s@99%".parse::<Objective>().is_err());
+        assert!("1s@99%/5s".parse::<Objective>().is_err());
+    }
+
+    #[test]
+    fn test_burn_rate() {
+        define("slo_test_step", "100ms@95%/10m").unwrap();
+        assert!(objectives().iter().any(|(name, _)| name == "slo_test_step"));
+        let now = 1_000_000;
+        let fast = Duration::from_millis(10);
+        let slow = Duration::from_millis(500);
+        for _ in 0..8 {
+            observe_at("slo.test.step", None, fast, false, now);
+        }
+        observe_at("slo.test.step", None, slow, false, now);
+        // matched by kind, and bad as it failed
+        observe_at("other", Some("slo_test_step"), fast, true, now);
+
+        let find = |now| {
+            status_at(now)
+                .into_iter()
+                .find(|s| s.name == "slo_test_step")
+                .unwrap()
+        };
+        let status = find(now);
+        assert_eq!((status.total, status.bad), (10, 2));
+        assert_eq!(status.compliance, Some(80.0));
+        // 20% bad where 5% is allowed
+        assert!((status.burn_rate.unwrap() - 4.0).abs() < 1e-9);
+        assert!((status.budget_remaining.unwrap() + 3.0).abs() < 1e-9);
+        assert!(!status.burning);
+
+        for _ in 0..10 {
+            observe_at("slo_test_step", None, slow, false, now + 60);
+        }
+        let status = find(now + 60);
+        assert!(status.burning);
+        assert!((status.burn_rate_short.unwrap() - 12.0).abs() < 1e-9);
+
+        // out of the short window, then of the whole window
+        assert_eq!(find(now + 400).burn_rate_short, None);
+        assert_eq!(find(now + 700).total, 0);
+
+        assert!(define("slo_test_step", "").unwrap().is_some());
+        assert!(define("slo_test_step", "fast").is_err());
+    }
+}
@@ -1,8 +1,11 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::LazyLock;
 use std::time::{Duration, SystemTime};
 
 pub use probing_proto::types::Ele;
 
+use super::strings::{resolve, try_intern, InternedStr};
+
 // Global atomic counters for generating unique IDs.
 static NEXT_TRACE_ID: AtomicU64 = AtomicU64::new(1);
 static NEXT_SPAN_ID: AtomicU64 = AtomicU64::new(1);
@@ -34,7 +37,44 @@ fn current_thread_id() -> u64 {
     }
 }
 
+/// Whether spans measure the CPU time of their thread, on by default with
+/// `PROBING_TRACING_CPU_TIME=1`
+static CPU_TIME: LazyLock<AtomicBool> = LazyLock::new(|| {
+    let enabled = std::env::var("PROBING_TRACING_CPU_TIME")
+        .is_ok_and(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "on"));
+    AtomicBool::new(enabled)
+});
+
+/// Measure the CPU time of the thread running each span from now on
+pub fn set_cpu_time(enabled: bool) {
+    CPU_TIME.store(enabled, Ordering::Relaxed);
+}
+
+pub fn cpu_time_enabled() -> bool {
+    CPU_TIME.load(Ordering::Relaxed)
+}
+
+/// CPU time consumed by the calling thread in nanoseconds, from
+/// `CLOCK_THREAD_CPUTIME_ID`
+#[cfg(unix)]
+pub fn thread_cpu_time() -> Option<u64> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) } != 0 {
+        return None;
+    }
+    Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+}
+
+#[cfg(not(unix))]
+pub fn thread_cpu_time() -> Option<u64> {
+    None
+}
+
 // --- Timestamp ---
+/// Nanoseconds since the unix epoch.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Timestamp(pub u128);
 
@@ -48,15 +88,31 @@ impl Timestamp {
             )
     }
 
+    /// Nanoseconds since the unix epoch, saturating at `i64::MAX` (year 2262).
+    pub fn as_nanos_i64(&self) -> i64 {
+        i64::try_from(self.0).unwrap_or(i64::MAX)
+    }
+
+    /// Microseconds since the unix epoch, saturating at `i64::MAX`.
+    pub fn as_micros_i64(&self) -> i64 {
+        i64::try_from(self.0 / 1_000).unwrap_or(i64::MAX)
+    }
+
     pub fn duration_since(&self, earlier: Timestamp) -> Duration {
         if self.0 > earlier.0 {
-            Duration::from_nanos((self.0 - earlier.0) as u64)
+            Duration::from_nanos(u64::try_from(self.0 - earlier.0).unwrap_or(u64::MAX))
         } else {
             Duration::from_nanos(0) // Avoid panic if earlier is not actually earlier
         }
     }
 }
 
+impl From<Timestamp> for Ele {
+    fn from(ts: Timestamp) -> Self {
+        Ele::datetime_from_nanos(ts.0)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Attribute(pub String, pub Ele);
 
@@ -76,10 +132,31 @@ impl Attribute {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Location {
+    /// Interned location, see [`intern`](super::intern)
     KnownLocation(u64),
     UnknownLocation(String),
 }
 
+impl Location {
+    /// Intern `location`, or keep it as is once the registry is full
+    pub fn new(location: &str) -> Self {
+        match try_intern(location) {
+            Some(id) => Location::KnownLocation(id.id() as u64),
+            None => Location::UnknownLocation(location.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Location::KnownLocation(id) => u32::try_from(*id)
+                .ok()
+                .and_then(resolve)
+                .unwrap_or_default(),
+            Location::UnknownLocation(path) => path,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Event {
     pub name: String,
@@ -120,14 +197,19 @@ pub struct Span {
     pub thread_id: u64, // stable numeric id for the originating thread
 
     // === 基本信息 ===
-    pub name: String,
+    pub name: InternedStr,
 
     // === 时间信息 ===
     pub start: Timestamp,
     pub end: Option<Timestamp>,
+    /// CPU time of the thread when the span started, if measured
+    pub cpu_start: Option<u64>,
+    /// CPU time the thread spent in the span, known once it ended on the
+    /// thread that started it
+    pub cpu_time_ns: Option<u64>,
 
     // === 元数据 ===
-    pub kind: Option<String>,
+    pub kind: Option<InternedStr>,
     pub loc: Option<Location>,
 
     // === 扩展数据 ===
@@ -137,10 +219,10 @@ pub struct Span {
 
 impl Span {
     /// Creates a new root span (starts a new trace).
-    pub fn new_root<N: Into<String>>(name: N, kind: Option<&str>, location: Option<&str>) -> Self {
+    pub fn new_root<N: AsRef<str>>(name: N, kind: Option<&str>, location: Option<&str>) -> Self {
         let trace_id = NEXT_TRACE_ID.fetch_add(1, Ordering::Relaxed);
         let span_id = NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed);
-        let location = location.map(|loc_val| Location::UnknownLocation(loc_val.into()));
+        let location = location.map(Location::new);
         let thread_id = current_thread_id();
 
         Span {
@@ -148,10 +230,12 @@ impl Span {
             span_id,
             parent_id: None,
             thread_id,
-            name: name.into(),
+            name: InternedStr::new(name.as_ref()),
             start: Timestamp::now(),
             end: None,
-            kind: kind.map(|k| k.to_string()),
+            cpu_start: cpu_time_enabled().then(thread_cpu_time).flatten(),
+            cpu_time_ns: None,
+            kind: kind.map(InternedStr::new),
             loc: location,
             attrs: vec![],
             events: vec![],
@@ -159,14 +243,14 @@ impl Span {
     }
 
     /// Creates a new child span within an existing trace.
-    pub fn new_child<N: Into<String>>(
+    pub fn new_child<N: AsRef<str>>(
         parent: &Span,
         name: N,
         kind: Option<&str>,
         location: Option<&str>,
     ) -> Self {
         let span_id = NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed);
-        let location = location.map(|loc_val| Location::UnknownLocation(loc_val.into()));
+        let location = location.map(Location::new);
         let thread_id = current_thread_id(); // child bound to the current executing thread
 
         Span {
@@ -174,16 +258,33 @@ impl Span {
             span_id,
             parent_id: Some(parent.span_id),
             thread_id,
-            name: name.into(),
+            name: InternedStr::new(name.as_ref()),
             start: Timestamp::now(),
             end: None,
-            kind: kind.map(|k| k.to_string()),
+            cpu_start: cpu_time_enabled().then(thread_cpu_time).flatten(),
+            cpu_time_ns: None,
+            kind: kind.map(InternedStr::new),
             loc: location,
             attrs: vec![],
             events: vec![],
         }
     }
 
+    /// Creates a span continuing a trace started in another process, as a
+    /// child of its span `parent_id`.
+    pub fn new_remote_child<N: AsRef<str>>(
+        trace_id: u64,
+        parent_id: u64,
+        name: N,
+        kind: Option<&str>,
+        location: Option<&str>,
+    ) -> Self {
+        let mut span = Self::new_root(name, kind, location);
+        span.trace_id = trace_id;
+        span.parent_id = Some(parent_id);
+        span
+    }
+
     /// Adds an attribute to this span.
     ///
     /// Returns an error if the span has already been ended.
@@ -218,8 +319,16 @@ impl Span {
     }
 
     /// Ends this span.
+    ///
+    /// The CPU time is only taken on the thread that started the span, the
+    /// clock of another thread would be meaningless.
     pub fn finish(&mut self) {
         self.end = Some(Timestamp::now());
+        if let Some(start) = self.cpu_start {
+            if current_thread_id() == self.thread_id {
+                self.cpu_time_ns = thread_cpu_time().map(|now| now.saturating_sub(start));
+            }
+        }
     }
 
     /// Ends this span (alias for `finish()`).
@@ -262,6 +371,22 @@ mod tests {
     use super::*;
     use std::time::Duration as StdDuration;
 
+    #[test]
+    fn test_timestamp_conversions_saturate() {
+        let ts = Timestamp(1_700_000_000_123_456_789);
+        assert_eq!(ts.as_nanos_i64(), 1_700_000_000_123_456_789);
+        assert_eq!(ts.as_micros_i64(), 1_700_000_000_123_456);
+        assert_eq!(Ele::from(ts), Ele::DataTime(1_700_000_000_123_456));
+
+        let far = Timestamp(u128::MAX);
+        assert_eq!(far.as_nanos_i64(), i64::MAX);
+        assert_eq!(far.as_micros_i64(), i64::MAX);
+        assert_eq!(
+            far.duration_since(Timestamp(0)),
+            StdDuration::from_nanos(u64::MAX)
+        );
+    }
+
     // --- 1. Basic Span Functionality ---
 
     #[test]
@@ -273,7 +398,7 @@ mod tests {
         );
 
         assert_eq!(span.name, "process_incoming_request");
-        assert_eq!(span.kind, Some("server_op".to_string()));
+        assert_eq!(span.kind.as_deref(), Some("server_op"));
         assert_eq!(span.parent_id, None, "Root span has no parent");
         assert_eq!(
             span.status(),
@@ -281,8 +406,10 @@ mod tests {
             "New span should be active"
         );
         match &span.loc {
-            Some(Location::UnknownLocation(path)) => assert_eq!(path, "my_app::request_handler"),
-            _ => panic!("Expected UnknownLocation with the specified code_path"),
+            Some(loc @ Location::KnownLocation(_)) => {
+                assert_eq!(loc.as_str(), "my_app::request_handler")
+            }
+            _ => panic!("Expected an interned location with the specified code_path"),
         }
 
         assert!(span.trace_id > 0, "Trace ID should be positive");
@@ -323,6 +450,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_new_remote_child_span() {
+        let child = Span::new_remote_child(42, 7, "ray.task", Some("consumer"), None);
+
+        assert_eq!(child.trace_id, 42);
+        assert_eq!(child.parent_id, Some(7));
+        assert_ne!(child.span_id, 7);
+        assert_eq!(child.kind.as_deref(), Some("consumer"));
+        assert_eq!(child.status(), SpanStatus::Active);
+    }
+
     #[test]
     fn test_end_span() {
         let mut span = Span::new_root("single_task", None, None);
@@ -429,6 +567,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cpu_time() {
+        let mut idle = Span::new_root("idle", None, None);
+        idle.cpu_start = thread_cpu_time();
+        std::thread::sleep(StdDuration::from_millis(20));
+        idle.finish();
+
+        let mut busy = Span::new_root("busy", None, None);
+        busy.cpu_start = thread_cpu_time();
+        let begin = std::time::Instant::now();
+        let mut x = 0u64;
+        while begin.elapsed() < StdDuration::from_millis(20) {
+            x = std::hint::black_box(x.wrapping_add(1));
+        }
+        busy.finish();
+
+        let idle = idle.cpu_time_ns.unwrap();
+        let busy = busy.cpu_time_ns.unwrap();
+        assert!(idle < 10_000_000, "sleeping takes little CPU: {idle}");
+        assert!(busy >= 10_000_000, "spinning takes CPU: {busy}");
+
+        let mut moved = Span::new_root("moved", None, None);
+        moved.cpu_start = thread_cpu_time();
+        std::thread::spawn(move || {
+            moved.finish();
+            assert_eq!(moved.cpu_time_ns, None);
+        })
+        .join()
+        .unwrap();
+    }
+
     #[test]
     fn test_trace_id_generation() {
         // First trace - should get a trace_id from atomic counter
@@ -0,0 +1,295 @@
+//! Interned strings of trace records.
+//!
+//! Span names, kinds and locations repeat for every span a long run records,
+//! so each distinct string is stored once for the lifetime of the process and
+//! spans carry a 4-byte [`StrId`] instead of an owned `String`. Readers map
+//! the ids back with [`resolve`], or with the `trace.strings` table.
+//!
+//! Strings are never freed, so they are expected to come from a bounded set:
+//! values such as request ids belong in attributes. Past [`MAX_STRINGS`]
+//! distinct strings, [`intern`] returns [`OVERFLOW`] for new ones and spans
+//! keep them as owned strings, see [`InternedStr`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, LazyLock, Once, RwLock};
+
+use datafusion::arrow::array::{Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+
+use crate::core::{CustomTable, TablePluginHelper};
+
+/// Number of distinct strings kept
+pub const MAX_STRINGS: usize = 1 << 20;
+
+/// Stand-in for the strings interned past [`MAX_STRINGS`]
+pub const OVERFLOW: &str = "<too many strings>";
+
+#[derive(Default)]
+struct Registry {
+    ids: HashMap<&'static str, u32>,
+    strings: Vec<&'static str>,
+}
+
+static REGISTRY: LazyLock<RwLock<Registry>> = LazyLock::new(|| {
+    let mut registry = Registry::default();
+    // id 0 is the overflow marker, so it is valid from the start
+    registry.ids.insert(OVERFLOW, 0);
+    registry.strings.push(OVERFLOW);
+    RwLock::new(registry)
+});
+
+/// Id of an interned string, dereferences to the string itself
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StrId(u32);
+
+impl StrId {
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        resolve(self.0).unwrap_or(OVERFLOW)
+    }
+}
+
+/// Id of `value`, interning it on first use
+pub fn intern(value: &str) -> StrId {
+    try_intern(value).unwrap_or(StrId(0))
+}
+
+/// Id of `value`, interning it on first use, or `None` once [`MAX_STRINGS`]
+/// strings are interned
+pub fn try_intern(value: &str) -> Option<StrId> {
+    if let Some(id) = REGISTRY.read().unwrap().ids.get(value) {
+        return Some(StrId(*id));
+    }
+    let mut registry = REGISTRY.write().unwrap();
+    if let Some(id) = registry.ids.get(value) {
+        return Some(StrId(*id));
+    }
+    if registry.strings.len() >= MAX_STRINGS {
+        static FULL: Once = Once::new();
+        FULL.call_once(|| {
+            crate::journal::dropped(
+                "trace.strings",
+                1,
+                format!("more than {MAX_STRINGS} distinct span names, kinds and locations"),
+            )
+        });
+        return None;
+    }
+    let value: &'static str = Box::leak(value.into());
+    let id = registry.strings.len() as u32;
+    registry.strings.push(value);
+    registry.ids.insert(value, id);
+    Some(StrId(id))
+}
+
+/// The string interned as `id`
+pub fn resolve(id: u32) -> Option<&'static str> {
+    REGISTRY.read().unwrap().strings.get(id as usize).copied()
+}
+
+/// All interned strings, in id order
+pub fn strings() -> Vec<&'static str> {
+    REGISTRY.read().unwrap().strings.clone()
+}
+
+impl Deref for StrId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for StrId {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for StrId {
+    fn from(value: &str) -> Self {
+        intern(value)
+    }
+}
+
+impl From<String> for StrId {
+    fn from(value: String) -> Self {
+        intern(&value)
+    }
+}
+
+impl PartialEq<str> for StrId {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for StrId {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for StrId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for StrId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A string of a span, interned unless [`MAX_STRINGS`] strings already are
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum InternedStr {
+    Interned(StrId),
+    /// Kept as is since the registry is full
+    Literal(Arc<str>),
+}
+
+impl InternedStr {
+    pub fn new(value: &str) -> Self {
+        match try_intern(value) {
+            Some(id) => InternedStr::Interned(id),
+            None => InternedStr::Literal(value.into()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            InternedStr::Interned(id) => id.as_str(),
+            InternedStr::Literal(value) => value,
+        }
+    }
+}
+
+impl Deref for InternedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for InternedStr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for InternedStr {
+    fn from(value: &str) -> Self {
+        InternedStr::new(value)
+    }
+}
+
+impl PartialEq<str> for InternedStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for InternedStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for InternedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for InternedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `trace.strings`, the ids of the interned strings
+#[derive(Default, Debug)]
+pub struct StringsTable {}
+
+impl CustomTable for StringsTable {
+    fn name() -> &'static str {
+        "strings"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("value", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let strings = strings();
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..strings.len() as i64)),
+                Arc::new(StringArray::from_iter_values(strings)),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build strings batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type StringsPlugin = TablePluginHelper<StringsTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_is_stable() {
+        let a = intern("strings.test");
+        let b = intern(&String::from("strings.test"));
+        assert_eq!(a, b);
+        assert_ne!(a, intern("strings.other"));
+        assert_eq!(a, "strings.test");
+        assert_eq!(resolve(a.id()), Some("strings.test"));
+        assert_eq!(a.to_string(), "strings.test");
+        assert_eq!(resolve(0), Some(OVERFLOW));
+        assert_eq!(std::mem::size_of::<StrId>(), 4);
+    }
+
+    #[test]
+    fn test_interned_str() {
+        let name = InternedStr::new("strings.span");
+        assert_eq!(name, InternedStr::Interned(intern("strings.span")));
+        assert_eq!(name, "strings.span");
+
+        let literal = InternedStr::Literal("strings.literal".into());
+        assert_eq!(literal, "strings.literal");
+        assert_eq!(literal.to_string(), "strings.literal");
+        assert_ne!(literal, InternedStr::new("strings.literal"));
+    }
+
+    #[test]
+    fn test_strings_table() {
+        let id = intern("strings.table").id();
+        let batch = &StringsTable::data()[0];
+        let values = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(values.value(id as usize), "strings.table");
+    }
+}
@@ -0,0 +1,472 @@
+use std::time::{Duration, Instant};
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::client::conn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+
+use probing_proto::prelude::*;
+
+use crate::endpoint::{socket_path, Endpoint};
+use crate::error::{ClientError, Result};
+
+/// Variable holding the token of probes started with authentication
+pub const AUTH_TOKEN_ENV: &str = "PROBING_AUTH_TOKEN";
+
+/// Client of the API of one probe
+#[derive(Debug, Clone)]
+pub struct Client {
+    endpoint: Endpoint,
+    token: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl Client {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Client {
+            endpoint,
+            token: None,
+            timeout: None,
+        }
+    }
+
+    /// Authenticate with `token` as a bearer token
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    /// Authenticate with the token in `PROBING_AUTH_TOKEN`, if set
+    pub fn with_token_from_env(self) -> Self {
+        self.with_token(std::env::var(AUTH_TOKEN_ENV).ok())
+    }
+
+    /// Give up on replies not read within `timeout`, the probe is told so
+    /// and cancels the work of the request
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout.filter(|t| !t.is_zero());
+        self
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Send a request and return the response without reading the body,
+    /// a `POST` when there is a body and a `GET` otherwise; the timeout does
+    /// not apply, the response may be a stream
+    pub async fn send(&self, path: &str, body: Option<String>) -> Result<Response<Incoming>> {
+        self.send_until(path, body, None).await
+    }
+
+    async fn send_until(
+        &self,
+        path: &str,
+        body: Option<String>,
+        deadline: Option<Instant>,
+    ) -> Result<Response<Incoming>> {
+        let mut sender = match &self.endpoint {
+            Endpoint::Local { pid } => {
+                log::debug!("sending request to {pid} via unix socket: {path}");
+                let stream = tokio::net::UnixStream::connect(socket_path(*pid))
+                    .await
+                    .map_err(|e| ClientError::Connect(self.endpoint.to_string(), e))?;
+                handshake(TokioIo::new(stream)).await?
+            }
+            Endpoint::Remote { addr } => {
+                log::debug!("sending request to {addr} via tcp socket: {path}");
+                let stream = tokio::net::TcpStream::connect(addr)
+                    .await
+                    .map_err(|e| ClientError::Connect(self.endpoint.to_string(), e))?;
+                handshake(TokioIo::new(stream)).await?
+            }
+        };
+
+        let builder = match &body {
+            Some(_) => Request::builder().method("POST"),
+            None => Request::builder().method("GET"),
+        };
+        let builder = match &self.endpoint {
+            Endpoint::Remote { addr } => builder.header("Host", addr.as_str()),
+            Endpoint::Local { .. } => builder.header("Host", "localhost"),
+        };
+        let builder = match &self.token {
+            Some(token) => builder.header("Authorization", format!("Bearer {token}")),
+            None => builder,
+        };
+        let builder = match deadline {
+            Some(deadline) => builder.header(
+                TIMEOUT_HEADER,
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .as_millis()
+                    .to_string(),
+            ),
+            None => builder,
+        };
+        let request = builder
+            .uri(path)
+            .body(Full::<Bytes>::from(body.unwrap_or_default()))
+            .map_err(|e| ClientError::Decode(format!("invalid request {path}: {e}")))?;
+
+        Ok(sender.send_request(request).await?)
+    }
+
+    /// Send a request and return the body of the response, whatever its status
+    pub async fn request(&self, path: &str, body: Option<String>) -> Result<Vec<u8>> {
+        let (_, body) = self.exchange(path, body).await?;
+        Ok(body.to_vec())
+    }
+
+    /// `GET` `path` and return the body of a successful reply
+    pub async fn get(&self, path: &str) -> Result<Bytes> {
+        let (status, body) = self.exchange(path, None).await?;
+        if !status.is_success() {
+            return Err(ClientError::Status {
+                status: status.as_u16(),
+                body: String::from_utf8_lossy(&body).trim().to_string(),
+            });
+        }
+        Ok(body)
+    }
+
+    /// Send a request and read the whole response within the timeout
+    async fn exchange(
+        &self,
+        path: &str,
+        body: Option<String>,
+    ) -> Result<(hyper::StatusCode, Bytes)> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let exchange = async {
+            let res = self.send_until(path, body, deadline).await?;
+            let status = res.status();
+            Ok((status, res.collect().await?.to_bytes()))
+        };
+        let Some(timeout) = self.timeout else {
+            return exchange.await;
+        };
+        match tokio::time::timeout(timeout, exchange).await {
+            Ok(Ok((hyper::StatusCode::GATEWAY_TIMEOUT, _))) | Err(_) => {
+                Err(ClientError::Timeout(timeout))
+            }
+            Ok(reply) => reply,
+        }
+    }
+
+    /// `GET` `path` and decode its JSON reply
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        decode(&self.get(path).await?)
+    }
+
+    /// `POST` `body` to `path` and decode the JSON of a successful reply
+    pub async fn post_json<T: DeserializeOwned>(&self, path: &str, body: String) -> Result<T> {
+        let (status, body) = self.exchange(path, Some(body)).await?;
+        if !status.is_success() {
+            return Err(ClientError::Status {
+                status: status.as_u16(),
+                body: String::from_utf8_lossy(&body).trim().to_string(),
+            });
+        }
+        decode(&body)
+    }
+
+    /// Run a query and return its raw reply, a single page for paginated
+    /// queries
+    pub async fn query_data(&self, query: Query) -> Result<QueryDataFormat> {
+        let request = serde_json::to_string(&Message::new(query))
+            .map_err(|e| ClientError::Decode(e.to_string()))?;
+        let reply = self.request("/query", Some(request)).await?;
+        Ok(decode::<Message<QueryDataFormat>>(&reply)?.payload)
+    }
+
+    /// Run a query, fetching and joining all pages of a paginated result
+    pub async fn query(&self, query: Query) -> Result<DataFrame> {
+        let mut reply = self.query_data(query).await?;
+        let mut df = DataFrame::default();
+        while let QueryDataFormat::Page(page) = reply {
+            df.extend(page.df)
+                .map_err(|e| ClientError::Decode(e.to_string()))?;
+            let Some(cursor) = page.next_cursor else {
+                return Ok(df);
+            };
+            reply = self.query_data(Query::next_page(cursor)).await?;
+        }
+        into_dataframe(reply)
+    }
+
+    /// Apply `settings` as `set` statements, values quoted as strings
+    pub async fn set(&self, settings: &[(String, String)]) -> Result<()> {
+        let statements: String = settings
+            .iter()
+            .map(|(key, value)| format!("set {key}='{}';", value.replace('\'', "''")))
+            .collect();
+        match self.query_data(Query::new(statements)).await? {
+            QueryDataFormat::Error(err) => Err(ClientError::Query(Box::new(err))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Optional features built into the probe, all of them for probes that
+    /// predate `/apis/capabilities`
+    pub async fn capabilities(&self) -> Result<Capabilities> {
+        match self.get_json("/apis/capabilities").await {
+            Err(ClientError::Status { .. }) => Ok(Capabilities::legacy()),
+            result => result,
+        }
+    }
+
+    /// Build of the probe, `None` for probes that predate `/apis/version`
+    pub async fn version(&self) -> Result<Option<BuildInfo>> {
+        match self.get_json("/apis/version").await {
+            Err(ClientError::Status { .. }) => Ok(None),
+            result => result.map(Some),
+        }
+    }
+
+    /// The process the probe runs in
+    pub async fn overview(&self) -> Result<Process> {
+        self.get_json("/apis/overview").await
+    }
+
+    /// Extension options and config store entries
+    pub async fn config(&self) -> Result<ConfigDump> {
+        self.get_json("/apis/config").await
+    }
+
+    /// Types, defaults and constraints of the extension options
+    pub async fn config_schema(&self) -> Result<Vec<OptionSchema>> {
+        self.get_json("/apis/config/schema").await
+    }
+
+    /// Configuration changes scheduled on the probe
+    pub async fn config_schedule(&self) -> Result<Vec<ScheduledChange>> {
+        self.get_json("/apis/config/schedule").await
+    }
+
+    /// Queue `change`, returned with its id
+    pub async fn schedule_config(&self, change: &ScheduledChange) -> Result<ScheduledChange> {
+        let body = serde_json::to_string(change)
+            .map_err(|e| ClientError::Decode(format!("invalid change: {e}")))?;
+        self.post_json("/apis/config/schedule", body).await
+    }
+
+    /// Cancel the scheduled change `id`
+    pub async fn cancel_config_schedule(&self, id: u64) -> Result<ScheduledChange> {
+        self.post_json(&format!("/apis/config/schedule/{id}/cancel"), String::new())
+            .await
+    }
+
+    /// Nodes of the cluster, as known to this probe
+    pub async fn nodes(&self) -> Result<Vec<Node>> {
+        self.get_json("/apis/nodes").await
+    }
+
+    /// Training jobs of the cluster with their aggregates
+    pub async fn jobs(&self) -> Result<Vec<Job>> {
+        self.get_json("/apis/jobs").await
+    }
+
+    /// Call stack of the thread `tid`, of the main thread by default
+    pub async fn callstack(&self, tid: Option<i32>) -> Result<Vec<CallFrame>> {
+        match tid {
+            Some(tid) => {
+                self.get_json(&format!("/apis/pythonext/callstack?tid={tid}"))
+                    .await
+            }
+            None => self.get_json("/apis/pythonext/callstack").await,
+        }
+    }
+
+    /// Recorded spans and events in the Chrome trace format, at most
+    /// `limit` of them, all if `0`
+    pub async fn chrome_trace(&self, limit: usize) -> Result<serde_json::Value> {
+        self.get_json(&format!(
+            "/apis/pythonext/trace/chrome-tracing?limit={limit}"
+        ))
+        .await
+    }
+
+    /// Spans of the latest `limit` trace events nested under their parents,
+    /// with their `duration` and `self_time` in nanoseconds
+    pub async fn span_tree(&self, limit: usize) -> Result<serde_json::Value> {
+        self.get_json(&format!("/apis/pythonext/trace/span-tree?limit={limit}"))
+            .await
+    }
+
+    /// Pause state after requesting `path`, one of the `pause`, `resume`
+    /// and `paused` APIs of `/apis/pythonext`
+    pub async fn pause_state(&self, path: &str) -> Result<PauseState> {
+        self.get_json(path).await
+    }
+
+    pub async fn repl_sessions(&self) -> Result<Vec<ReplSession>> {
+        self.get_json("/apis/pythonext/repl/sessions").await
+    }
+
+    pub async fn close_repl_session(&self, name: &str) -> Result<ReplSession> {
+        self.get_json(&format!("/apis/pythonext/repl/sessions/close?name={name}"))
+            .await
+    }
+
+    /// Start evaluating `code` in a thread of its own, in the namespace of
+    /// the REPL `session` or in the main namespace without it
+    pub async fn start_eval_job(&self, code: &str, session: Option<&str>) -> Result<EvalJob> {
+        let path = match session {
+            Some(session) => format!("/apis/pythonext/eval/start?session={session}"),
+            None => "/apis/pythonext/eval/start".to_string(),
+        };
+        self.post_json(&path, code.to_string()).await
+    }
+
+    /// Output of the eval job `id` from byte `offset` on
+    pub async fn eval_job(&self, id: u64, offset: usize) -> Result<EvalJobOutput> {
+        self.get_json(&format!("/apis/pythonext/eval/job?id={id}&offset={offset}"))
+            .await
+    }
+
+    pub async fn eval_jobs(&self) -> Result<Vec<EvalJob>> {
+        self.get_json("/apis/pythonext/eval/jobs").await
+    }
+
+    /// Interrupt the eval job `id`
+    pub async fn cancel_eval_job(&self, id: u64) -> Result<EvalJob> {
+        self.get_json(&format!("/apis/pythonext/eval/cancel?id={id}"))
+            .await
+    }
+}
+
+async fn handshake<T>(io: TokioIo<T>) -> Result<conn::http1::SendRequest<Full<Bytes>>>
+where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let (sender, connection) = conn::http1::handshake(io).await?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            log::warn!("connection error: {err}");
+        }
+    });
+    Ok(sender)
+}
+
+fn decode<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|e| {
+        let body = String::from_utf8_lossy(body);
+        if body.trim().is_empty() || body.trim_start().starts_with(['{', '[']) {
+            ClientError::Decode(e.to_string())
+        } else {
+            // plain text replies are error messages of the probe
+            ClientError::Decode(body.trim().to_string())
+        }
+    })
+}
+
+fn into_dataframe(reply: QueryDataFormat) -> Result<DataFrame> {
+    match reply {
+        QueryDataFormat::Error(err) => Err(ClientError::Query(Box::new(err))),
+        QueryDataFormat::Nil => Ok(Default::default()),
+        QueryDataFormat::DataFrame(df) => Ok(df),
+        QueryDataFormat::Page(page) => Ok(page.df),
+        QueryDataFormat::TimeSeries(_) => Err(ClientError::Decode(
+            "time series replies are not supported".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve one HTTP request with `reply` and return the request received
+    async fn serve_once(reply: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{reply}",
+                reply.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn test_query_sends_token() {
+        let reply = serde_json::to_string(&Message::new(QueryDataFormat::DataFrame(
+            DataFrame::new(vec!["a".into()], vec![Seq::SeqI64(vec![1, 2])]),
+        )))
+        .unwrap();
+        let (addr, server) = serve_once(Box::leak(reply.into_boxed_str())).await;
+
+        let client = Client::new(Endpoint::Remote { addr }).with_token(Some("secret".to_string()));
+        let df = client
+            .query(Query::new("SELECT 1".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(df.len(), 2);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /query HTTP/1.1"));
+        assert!(request.contains("authorization: Bearer secret"));
+    }
+
+    #[tokio::test]
+    async fn test_query_error() {
+        let reply = serde_json::to_string(&Message::new(QueryDataFormat::Error(QueryError::new(
+            ErrorCode::TableNotFound,
+            "table `x` not found",
+        ))))
+        .unwrap();
+        let (addr, _server) = serve_once(Box::leak(reply.into_boxed_str())).await;
+
+        let err = Client::new(Endpoint::Remote { addr })
+            .query(Query::new("SELECT * FROM x".to_string()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Query(_)));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        // reads the request and keeps the connection open without replying
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            (String::from_utf8_lossy(&buf[..n]).to_string(), stream)
+        });
+
+        let err = Client::new(Endpoint::Remote { addr })
+            .with_timeout(Some(Duration::from_millis(200)))
+            .overview()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Timeout(_)));
+
+        let (request, _stream) = server.await.unwrap();
+        assert!(request.contains(&format!("{TIMEOUT_HEADER}: ")));
+    }
+
+    #[tokio::test]
+    async fn test_connect_error_keeps_io_error() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let err = Client::new(Endpoint::Remote { addr })
+            .overview()
+            .await
+            .unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt::Display;
+
+use probing_proto::prelude::Registration;
+use probing_proto::protocol::registry::registry_dir;
+
+use crate::error::ClientError;
+
+/// Where a probe serves its API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Unix socket of a process on this host
+    Local { pid: i32 },
+    /// TCP server of a probe, as `host:port`
+    Remote { addr: String },
+}
+
+impl TryFrom<&str> for Endpoint {
+    type Error = ClientError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let [_, _] = value.split(':').collect::<Vec<_>>()[..] {
+            return Ok(Self::Remote { addr: value.into() });
+        }
+        value
+            .parse::<i32>()
+            .map(|pid| Self::Local { pid })
+            .map_err(|_| ClientError::InvalidEndpoint(value.to_string()))
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Local { pid } => write!(f, "{pid}"),
+            Endpoint::Remote { addr } => write!(f, "{addr}"),
+        }
+    }
+}
+
+/// Path of the unix socket the probe of `pid` listens on
+pub fn socket_path(pid: i32) -> String {
+    #[cfg(target_os = "linux")]
+    let path = format!("\0probing-{}", pid);
+    #[cfg(not(target_os = "linux"))]
+    let path = {
+        let temp_dir = std::env::temp_dir();
+        let file_path = temp_dir.join(format!("probing-{}.sock", pid));
+        file_path.to_string_lossy().to_string()
+    };
+    path
+}
+
+/// Probes announced by their discovery files, see
+/// `probing_proto::protocol::registry`
+///
+/// Files left behind by processes that are gone are removed.
+pub fn registered() -> Result<Vec<Registration>, std::io::Error> {
+    let dir = registry_dir();
+    let mut registrations = Registration::list(&dir)?;
+    registrations.retain(|r| {
+        let alive = is_alive(r.pid);
+        if !alive {
+            log::debug!("removing discovery file of exited process {}", r.pid);
+            let _ = Registration::remove(&dir, r.pid);
+        }
+        alive
+    });
+    Ok(registrations)
+}
+
+fn is_alive(pid: i32) -> bool {
+    if pid <= 0 {
+        return false;
+    }
+    // signal 0 only checks that the process exists and may be signaled
+    let ret = unsafe { libc::kill(pid, 0) };
+    ret == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Probes listening on this host, as the pid and the socket name
+#[cfg(target_os = "linux")]
+pub fn discover() -> Result<Vec<(i32, String)>, std::io::Error> {
+    use std::io::{BufRead, BufReader};
+
+    let mut result = Vec::new();
+
+    // Read /proc/net/unix for abstract sockets
+    let file = std::fs::File::open("/proc/net/unix")?;
+    let reader = BufReader::new(file);
+
+    // Skip header
+    let mut lines = reader.lines();
+    let _ = lines.next();
+
+    for line in lines {
+        let line = line?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        // Check if we have enough fields and it's an abstract socket
+        if fields.len() >= 8 {
+            let socket_name_full = fields[7]; // e.g., @probing-12345
+
+            if let Some(pid_str) = socket_name_full.strip_prefix("@probing-") {
+                if let Ok(pid) = pid_str.parse::<i32>() {
+                    result.push((pid, socket_name_full.to_string()));
+                } else {
+                    log::warn!(
+                        "Failed to parse PID from socket name: {}. Expected format @probing-<pid>.",
+                        socket_name_full
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Probes listening on this host, as the pid and the socket path
+#[cfg(target_os = "macos")]
+pub fn discover() -> Result<Vec<(i32, String)>, std::io::Error> {
+    let mut result = Vec::new();
+    let temp_dir = std::env::temp_dir();
+
+    for entry in std::fs::read_dir(temp_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
+            // Extract PID from "probing-<pid>.sock"
+            if let Some(pid_str) = file_name
+                .strip_prefix("probing-")
+                .and_then(|s| s.strip_suffix(".sock"))
+            {
+                if let Ok(pid) = pid_str.parse::<i32>() {
+                    result.push((pid, path.to_string_lossy().to_string()));
+                } else {
+                    log::warn!("Failed to parse PID from socket file: {}", file_name);
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn discover() -> Result<Vec<(i32, String)>, std::io::Error> {
+    log::warn!("probe discovery is not implemented for this OS");
+    Ok(vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            Endpoint::try_from("1234").unwrap(),
+            Endpoint::Local { pid: 1234 }
+        );
+        let remote = Endpoint::try_from("node1:9700").unwrap();
+        assert_eq!(remote.to_string(), "node1:9700");
+        assert!(matches!(
+            Endpoint::try_from("node1"),
+            Err(ClientError::InvalidEndpoint(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_alive() {
+        assert!(is_alive(std::process::id() as i32));
+        assert!(!is_alive(0));
+        assert!(!is_alive(i32::MAX));
+    }
+}
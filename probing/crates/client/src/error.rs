@@ -0,0 +1,34 @@
+use probing_proto::prelude::QueryError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("invalid endpoint `{0}`, expected a pid or host:port")]
+    InvalidEndpoint(String),
+
+    #[error("failed to connect to {0}")]
+    Connect(String, #[source] std::io::Error),
+
+    #[error("request failed")]
+    Http(#[source] hyper::Error),
+
+    #[error("server returned {status}: {body}")]
+    Status { status: u16, body: String },
+
+    #[error("invalid reply: {0}")]
+    Decode(String),
+
+    #[error("no reply within {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("{0}")]
+    Query(Box<QueryError>),
+}
+
+impl From<hyper::Error> for ClientError {
+    fn from(err: hyper::Error) -> Self {
+        ClientError::Http(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
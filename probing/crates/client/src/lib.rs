@@ -0,0 +1,31 @@
+//! Client of the HTTP API served by probes.
+//!
+//! A [`Client`] talks to one probe, through the unix socket of a local
+//! process or the TCP server of a remote one, and decodes the replies into
+//! the types of `probing-proto`:
+//!
+//! ```no_run
+//! # async fn run() -> probing_client::Result<()> {
+//! use probing_client::{Client, Endpoint};
+//! use probing_proto::prelude::Query;
+//!
+//! let client = Client::new(Endpoint::try_from("10.0.0.2:9700")?).with_token_from_env();
+//! let process = client.overview().await?;
+//! let df = client
+//!     .query(Query::new("SELECT * FROM python.backtrace".to_string()))
+//!     .await?;
+//! println!("{} frames in {}", df.len(), process.pid);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`registered`] lists the probes running on the local host from their
+//! discovery files, [`discover`] by scanning the unix sockets.
+
+mod client;
+mod endpoint;
+mod error;
+
+pub use client::{Client, AUTH_TOKEN_ENV};
+pub use endpoint::{discover, registered, socket_path, Endpoint};
+pub use error::{ClientError, Result};
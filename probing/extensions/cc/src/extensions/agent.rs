@@ -0,0 +1,256 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{BooleanArray, Float64Array, RecordBatch, StringArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+
+use probing_core::core::{
+    time, CustomTable, EngineCall, EngineDatasource, EngineError, EngineExtension,
+    EngineExtensionOption, Maybe, TablePluginHelper,
+};
+use probing_core::{journal, latency};
+
+/// `agent.errors`: the agent's own errors, see [`probing_core::journal`]
+#[derive(Default, Debug)]
+pub struct ErrorsTable {}
+
+impl CustomTable for ErrorsTable {
+    fn name() -> &'static str {
+        "errors"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("source", DataType::Utf8, false),
+            Field::new("message", DataType::Utf8, false),
+            Field::new("count", DataType::UInt64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let entries = journal::entries();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_us_array(entries.iter().map(|e| e.time))),
+                Arc::new(StringArray::from_iter_values(
+                    entries.iter().map(|e| e.kind.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    entries.iter().map(|e| &e.source),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    entries.iter().map(|e| &e.message),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    entries.iter().map(|e| e.count),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build agent errors batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type ErrorsPlugin = TablePluginHelper<ErrorsTable>;
+
+/// `agent.http_stats`: latency histograms of the agent's endpoints, see
+/// [`probing_core::latency`]
+#[derive(Default, Debug)]
+pub struct HttpStatsTable {}
+
+impl CustomTable for HttpStatsTable {
+    fn name() -> &'static str {
+        "http_stats"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("endpoint", DataType::Utf8, false),
+            Field::new("count", DataType::UInt64, false),
+            Field::new("errors", DataType::UInt64, false),
+            Field::new("mean_ms", DataType::Float64, false),
+            Field::new("p50_ms", DataType::Float64, false),
+            Field::new("p90_ms", DataType::Float64, false),
+            Field::new("p99_ms", DataType::Float64, false),
+            Field::new("max_ms", DataType::Float64, false),
+            Field::new("histogram", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let stats = latency::stats();
+        let quantile =
+            |q: f64| Float64Array::from_iter_values(stats.iter().map(|(_, _, s)| s.quantile_ms(q)));
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    stats.iter().map(|(kind, _, _)| kind.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    stats.iter().map(|(_, endpoint, _)| endpoint),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    stats.iter().map(|(_, _, s)| s.count),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    stats.iter().map(|(_, _, s)| s.errors),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    stats.iter().map(|(_, _, s)| s.mean_ms()),
+                )),
+                Arc::new(quantile(0.5)),
+                Arc::new(quantile(0.9)),
+                Arc::new(quantile(0.99)),
+                Arc::new(Float64Array::from_iter_values(
+                    stats.iter().map(|(_, _, s)| s.max_us as f64 / 1000.0),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    stats.iter().map(|(_, _, s)| histogram(s)),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build agent http stats batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+/// Non-empty buckets as `<=<bound>ms:<calls>`, e.g. `<=1ms:12 <=5ms:3 >10000ms:1`
+fn histogram(stats: &latency::EndpointStats) -> String {
+    stats
+        .buckets
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(bucket, count)| match latency::BUCKETS_MS.get(bucket) {
+            Some(bound) => format!("<={bound}ms:{count}"),
+            None => format!(">{}ms:{count}", latency::BUCKETS_MS[bucket - 1]),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub type HttpStatsPlugin = TablePluginHelper<HttpStatsTable>;
+
+/// `agent.slow_calls`: calls slower than `agent.slow_call_ms` with their
+/// parameters
+#[derive(Default, Debug)]
+pub struct SlowCallsTable {}
+
+impl CustomTable for SlowCallsTable {
+    fn name() -> &'static str {
+        "slow_calls"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("endpoint", DataType::Utf8, false),
+            Field::new("duration_ms", DataType::Float64, false),
+            Field::new("failed", DataType::Boolean, false),
+            Field::new("params", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let calls = latency::slow_calls();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_us_array(calls.iter().map(|c| c.time))),
+                Arc::new(StringArray::from_iter_values(
+                    calls.iter().map(|c| c.kind.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    calls.iter().map(|c| &c.endpoint),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    calls.iter().map(|c| c.elapsed_us as f64 / 1000.0),
+                )),
+                Arc::new(BooleanArray::from(
+                    calls.iter().map(|c| c.failed).collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    calls.iter().map(|c| &c.params),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build agent slow calls batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type SlowCallsPlugin = TablePluginHelper<SlowCallsTable>;
+
+#[derive(Debug, EngineExtension)]
+pub struct AgentExtension {
+    /// Milliseconds from which calls are kept in `agent.slow_calls` (0 to
+    /// disable)
+    #[option(aliases=["slow.call.ms"])]
+    slow_call_ms: Maybe<u64>,
+}
+
+impl Default for AgentExtension {
+    fn default() -> Self {
+        Self {
+            slow_call_ms: Maybe::Just(latency::slow_call_ms()),
+        }
+    }
+}
+
+impl AgentExtension {
+    fn set_slow_call_ms(&mut self, slow_call_ms: Maybe<u64>) -> Result<(), EngineError> {
+        let ms = match slow_call_ms {
+            Maybe::Just(ms) => ms,
+            Maybe::Nothing => latency::DEFAULT_SLOW_CALL_MS,
+        };
+        latency::set_slow_call_ms(ms);
+        self.slow_call_ms = Maybe::Just(ms);
+        Ok(())
+    }
+}
+
+impl EngineCall for AgentExtension {}
+
+impl EngineDatasource for AgentExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == ErrorsTable::name() => {
+                Some(ErrorsPlugin::create(namespace, name))
+            }
+            Some(name) if name == HttpStatsTable::name() => {
+                Some(HttpStatsPlugin::create(namespace, name))
+            }
+            Some(name) if name == SlowCallsTable::name() => {
+                Some(SlowCallsPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! Resource controllers of the process's cgroup.
+//!
+//! Containers limit CPU and memory through cgroups, and a step slowed by CPU
+//! throttling or memory reclaim looks like any other slow step from inside
+//! the process. `cgroup.stats` reads the counters of the cgroup the process
+//! runs in, one row per key, for cgroup v2 and v1 hierarchies:
+//!
+//! ```sql
+//! SELECT key, value FROM cgroup.stats WHERE file = 'cpu.stat';
+//! SELECT file, key, value FROM cgroup.stats WHERE file LIKE '%.pressure' AND key LIKE 'some.%';
+//! ```
+//!
+//! Every query reads the files again, so two queries a few seconds apart
+//! give the throttling of that interval.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Float64Array, Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+
+use probing_core::core::{
+    CustomTable, EngineCall, EngineDatasource, EngineError, EngineExtension, EngineExtensionOption,
+    TablePluginHelper,
+};
+
+/// Where cgroup hierarchies are mounted
+const MOUNT: &str = "/sys/fs/cgroup";
+
+/// Files read from a cgroup v2 directory, by controller
+const V2_FILES: &[(&str, &[&str])] = &[
+    ("cpu", &["cpu.stat", "cpu.max", "cpu.pressure"]),
+    (
+        "memory",
+        &[
+            "memory.current",
+            "memory.max",
+            "memory.high",
+            "memory.swap.current",
+            "memory.swap.max",
+            "memory.events",
+            "memory.pressure",
+        ],
+    ),
+    ("io", &["io.stat", "io.pressure"]),
+    ("pids", &["pids.current", "pids.max"]),
+];
+
+/// Files read from the cgroup v1 hierarchy of each controller
+const V1_FILES: &[(&str, &[&str])] = &[
+    (
+        "cpu",
+        &["cpu.stat", "cpu.cfs_quota_us", "cpu.cfs_period_us"],
+    ),
+    (
+        "memory",
+        &[
+            "memory.usage_in_bytes",
+            "memory.limit_in_bytes",
+            "memory.max_usage_in_bytes",
+            "memory.failcnt",
+            "memory.oom_control",
+        ],
+    ),
+    ("pids", &["pids.current", "pids.max"]),
+];
+
+/// Pressure stall files, also served by the unified hierarchy of hybrid
+/// setups
+const PSI_FILES: &[(&str, &str)] = &[
+    ("cpu", "cpu.pressure"),
+    ("memory", "memory.pressure"),
+    ("io", "io.pressure"),
+];
+
+/// A value read from a controller file
+#[derive(Clone, Debug, PartialEq)]
+pub struct CgroupStat {
+    pub version: i64,
+    /// Directory the file was read from
+    pub path: String,
+    pub controller: String,
+    pub file: String,
+    pub key: String,
+    /// `None` for `max` and other values that are not numbers
+    pub value: Option<f64>,
+    pub raw: String,
+}
+
+/// Paths of the process in each hierarchy, from `/proc/self/cgroup`
+///
+/// The v2 hierarchy is keyed by an empty controller.
+pub fn memberships(proc_cgroup: &str) -> Vec<(String, String)> {
+    let mut memberships = vec![];
+    for line in proc_cgroup.lines() {
+        let mut fields = line.splitn(3, ':');
+        let (Some(_), Some(controllers), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        for controller in controllers.split(',') {
+            memberships.push((controller.to_string(), path.to_string()));
+        }
+    }
+    memberships
+}
+
+/// Directory of `path` below `mount`, or `mount` itself when the process's
+/// cgroup is the root of its namespace and `path` is not visible
+fn cgroup_dir(mount: &Path, path: &str) -> PathBuf {
+    let path = path.trim_start_matches('/');
+    let dir = mount.join(path);
+    if !path.is_empty() && dir.is_dir() {
+        dir
+    } else {
+        mount.to_path_buf()
+    }
+}
+
+fn number(value: &str) -> Option<f64> {
+    value.trim().parse().ok()
+}
+
+/// Keys and values of a controller file
+///
+/// Flat keyed files (`key value` lines) give one row per line, pressure
+/// files one per `some` or `full` average and total, `io.stat` the sums over
+/// devices, `cpu.max` the quota and period and single values the file name.
+pub fn parse(file: &str, content: &str) -> Vec<(String, Option<f64>, String)> {
+    let content = content.trim();
+    if file.ends_with(".pressure") {
+        let mut values = vec![];
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            let Some(kind) = fields.next() else {
+                continue;
+            };
+            for field in fields {
+                if let Some((key, value)) = field.split_once('=') {
+                    values.push((format!("{kind}.{key}"), number(value), value.to_string()));
+                }
+            }
+        }
+        return values;
+    }
+    if file == "io.stat" {
+        let mut sums: Vec<(String, f64)> = vec![];
+        for field in content
+            .lines()
+            .flat_map(|line| line.split_whitespace().skip(1))
+        {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            let value = number(value).unwrap_or_default();
+            match sums.iter_mut().find(|(k, _)| k == key) {
+                Some((_, sum)) => *sum += value,
+                None => sums.push((key.to_string(), value)),
+            }
+        }
+        return sums
+            .into_iter()
+            .map(|(key, sum)| (key, Some(sum), sum.to_string()))
+            .collect();
+    }
+    if file == "cpu.max" {
+        let mut fields = content.split_whitespace();
+        let quota = fields.next().unwrap_or_default();
+        let period = fields.next().unwrap_or_default();
+        return vec![
+            ("quota_usec".to_string(), number(quota), quota.to_string()),
+            (
+                "period_usec".to_string(),
+                number(period),
+                period.to_string(),
+            ),
+        ];
+    }
+    let lines = content.lines().collect::<Vec<_>>();
+    let keyed = lines.len() > 1
+        || lines
+            .first()
+            .is_some_and(|line| line.split_whitespace().count() == 2);
+    if keyed {
+        return lines
+            .iter()
+            .filter_map(|line| {
+                let (key, value) = line.trim().split_once(char::is_whitespace)?;
+                let value = value.trim();
+                Some((key.to_string(), number(value), value.to_string()))
+            })
+            .collect();
+    }
+    vec![(file.to_string(), number(content), content.to_string())]
+}
+
+fn read_files(
+    stats: &mut Vec<CgroupStat>,
+    version: i64,
+    dir: &Path,
+    controller: &str,
+    files: &[&str],
+) {
+    for file in files {
+        let Ok(content) = std::fs::read_to_string(dir.join(file)) else {
+            continue;
+        };
+        for (key, value, raw) in parse(file, &content) {
+            stats.push(CgroupStat {
+                version,
+                path: dir.display().to_string(),
+                controller: controller.to_string(),
+                file: file.to_string(),
+                key,
+                value,
+                raw,
+            });
+        }
+    }
+}
+
+/// Counters of the cgroup described by `proc_cgroup`, with hierarchies
+/// mounted below `mount`
+pub fn stats_at(mount: &Path, proc_cgroup: &str) -> Vec<CgroupStat> {
+    let memberships = memberships(proc_cgroup);
+    let unified_path = memberships
+        .iter()
+        .find(|(controller, _)| controller.is_empty())
+        .map(|(_, path)| path.as_str());
+    let mut stats = vec![];
+
+    if mount.join("cgroup.controllers").is_file() {
+        let dir = cgroup_dir(mount, unified_path.unwrap_or("/"));
+        for (controller, files) in V2_FILES {
+            read_files(&mut stats, 2, &dir, controller, files);
+        }
+        return stats;
+    }
+
+    for (controller, files) in V1_FILES {
+        let Some((_, path)) = memberships.iter().find(|(c, _)| c == controller) else {
+            continue;
+        };
+        let dir = cgroup_dir(&mount.join(controller), path);
+        read_files(&mut stats, 1, &dir, controller, files);
+    }
+    // hybrid setups keep pressure stall information in the unified hierarchy
+    let unified = mount.join("unified");
+    if let (Some(path), true) = (unified_path, unified.is_dir()) {
+        let dir = cgroup_dir(&unified, path);
+        for (controller, file) in PSI_FILES {
+            read_files(&mut stats, 2, &dir, controller, &[file]);
+        }
+    }
+    stats
+}
+
+/// Counters of the cgroup the process runs in
+pub fn stats() -> Vec<CgroupStat> {
+    match std::fs::read_to_string("/proc/self/cgroup") {
+        Ok(proc_cgroup) => stats_at(Path::new(MOUNT), &proc_cgroup),
+        Err(e) => {
+            log::debug!("failed to read cgroups: {e}");
+            vec![]
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct CgroupStatsTable {}
+
+impl CustomTable for CgroupStatsTable {
+    fn name() -> &'static str {
+        "stats"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("version", DataType::Int64, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("controller", DataType::Utf8, false),
+            Field::new("file", DataType::Utf8, false),
+            Field::new("key", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, true),
+            Field::new("raw", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let stats = stats();
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(
+                    stats.iter().map(|s| s.version),
+                )),
+                Arc::new(StringArray::from_iter_values(stats.iter().map(|s| &s.path))),
+                Arc::new(StringArray::from_iter_values(
+                    stats.iter().map(|s| &s.controller),
+                )),
+                Arc::new(StringArray::from_iter_values(stats.iter().map(|s| &s.file))),
+                Arc::new(StringArray::from_iter_values(stats.iter().map(|s| &s.key))),
+                Arc::new(Float64Array::from_iter(stats.iter().map(|s| s.value))),
+                Arc::new(StringArray::from_iter_values(stats.iter().map(|s| &s.raw))),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build cgroup batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type CgroupStatsPlugin = TablePluginHelper<CgroupStatsTable>;
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct CgroupExtension {}
+
+impl EngineCall for CgroupExtension {}
+
+impl EngineDatasource for CgroupExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == CgroupStatsTable::name() => {
+                Some(CgroupStatsPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(file: &str, content: &str) -> Vec<(String, Option<f64>)> {
+        parse(file, content)
+            .into_iter()
+            .map(|(key, value, _)| (key, value))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            values(
+                "cpu.stat",
+                "usage_usec 100\nnr_periods 10\nnr_throttled 3\nthrottled_usec 4500\n"
+            )[2..],
+            [
+                ("nr_throttled".to_string(), Some(3.0)),
+                ("throttled_usec".to_string(), Some(4500.0)),
+            ]
+        );
+        assert_eq!(
+            values("cpu.max", "max 100000"),
+            [
+                ("quota_usec".to_string(), None),
+                ("period_usec".to_string(), Some(100000.0)),
+            ]
+        );
+        assert_eq!(
+            values("memory.max", "max\n"),
+            [("memory.max".to_string(), None)]
+        );
+        assert_eq!(
+            values("memory.current", "1048576\n"),
+            [("memory.current".to_string(), Some(1048576.0))]
+        );
+        assert_eq!(
+            values("memory.events", "oom 0\noom_kill 1\n")[1],
+            ("oom_kill".to_string(), Some(1.0))
+        );
+
+        let psi = values(
+            "io.pressure",
+            "some avg10=1.50 avg60=0.00 avg300=0.00 total=12\n\
+             full avg10=0.00 avg60=0.00 avg300=0.00 total=3",
+        );
+        assert_eq!(psi.len(), 8);
+        assert_eq!(psi[0], ("some.avg10".to_string(), Some(1.5)));
+        assert_eq!(psi[7], ("full.total".to_string(), Some(3.0)));
+
+        assert_eq!(
+            values(
+                "io.stat",
+                "8:0 rbytes=100 wbytes=10 rios=1 wios=1\n8:16 rbytes=50 wbytes=0 rios=2 wios=0"
+            )[..2],
+            [
+                ("rbytes".to_string(), Some(150.0)),
+                ("wbytes".to_string(), Some(10.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memberships() {
+        let memberships = memberships("4:memory:/job\n2:cpu,cpuacct:/\n0::/user.slice\n");
+        assert_eq!(memberships[0], ("memory".to_string(), "/job".to_string()));
+        assert_eq!(memberships[2], ("cpuacct".to_string(), "/".to_string()));
+        assert_eq!(memberships[3], (String::new(), "/user.slice".to_string()));
+    }
+
+    #[test]
+    fn test_stats_at() {
+        let root = std::env::temp_dir().join(format!("probing-cgroup-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        // v2, with the cgroup of the process visible
+        let job = root.join("v2/job");
+        std::fs::create_dir_all(&job).unwrap();
+        std::fs::write(root.join("v2/cgroup.controllers"), "cpu memory").unwrap();
+        std::fs::write(job.join("cpu.stat"), "nr_periods 10\nnr_throttled 2\n").unwrap();
+        std::fs::write(job.join("memory.max"), "max\n").unwrap();
+        let stats = stats_at(&root.join("v2"), "0::/job\n");
+        assert_eq!(stats.len(), 3);
+        assert!(stats
+            .iter()
+            .all(|s| s.version == 2 && s.path.ends_with("job")));
+        assert_eq!(stats[1].key, "nr_throttled");
+        assert_eq!(stats[2].raw, "max");
+
+        // v1, with the process in the root of its namespace, and a unified
+        // hierarchy for pressure stall information
+        let cpu = root.join("v1/cpu");
+        let unified = root.join("v1/unified");
+        std::fs::create_dir_all(&cpu).unwrap();
+        std::fs::create_dir_all(&unified).unwrap();
+        std::fs::write(cpu.join("cpu.cfs_quota_us"), "-1\n").unwrap();
+        std::fs::write(unified.join("cpu.pressure"), "some avg10=0.50 total=7\n").unwrap();
+        let stats = stats_at(&root.join("v1"), "2:cpu:/hidden\n0::/\n");
+        assert_eq!(stats.len(), 3);
+        assert_eq!((stats[0].version, stats[0].value), (1, Some(-1.0)));
+        assert_eq!((stats[1].version, stats[1].key.as_str()), (2, "some.avg10"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
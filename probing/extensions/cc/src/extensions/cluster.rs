@@ -40,6 +40,8 @@ impl CustomTable for ClusterTable {
                 DataType::Timestamp(TimeUnit::Microsecond, None),
                 false,
             ),
+            Field::new("clock_offset", DataType::Int64, true),
+            Field::new("job_id", DataType::Utf8, true),
         ]))
     }
 
@@ -61,6 +63,8 @@ impl CustomTable for ClusterTable {
         fields.push(cluster::extract_array(&nodes, |n| {
             std::time::Duration::from_micros(n.timestamp)
         }));
+        fields.push(cluster::extract_array(&nodes, |n| n.clock_offset));
+        fields.push(cluster::extract_array(&nodes, |n| n.job_id.clone()));
 
         if let Ok(batches) = RecordBatch::try_new(Self::schema(), fields) {
             vec![batches]
@@ -72,12 +76,85 @@ impl CustomTable for ClusterTable {
 
 pub type ClusterPlugin = TablePluginHelper<ClusterTable>;
 
+/// Nodes joining, leaving and expiring, see [`cluster::NodeTransition`]
+#[derive(Default, Debug)]
+pub struct ClusterEventsTable {}
+
+impl CustomTable for ClusterEventsTable {
+    fn name() -> &'static str {
+        "events"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+            Field::new("event", DataType::Utf8, false),
+            Field::new("host", DataType::Utf8, false),
+            Field::new("addr", DataType::Utf8, false),
+            Field::new("rank", DataType::Int32, true),
+            Field::new("job_id", DataType::Utf8, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let events = cluster::get_events();
+        let fields: Vec<ArrayRef> = vec![
+            cluster::extract_array(&events, |e| std::time::Duration::from_micros(e.time)),
+            cluster::extract_array(&events, |e| e.transition.as_str().to_string()),
+            cluster::extract_array(&events, |e| e.host.clone()),
+            cluster::extract_array(&events, |e| e.addr.clone()),
+            cluster::extract_array(&events, |e| e.rank),
+            cluster::extract_array(&events, |e| e.job_id.clone()),
+        ];
+
+        match RecordBatch::try_new(Self::schema(), fields) {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build cluster events batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type ClusterEventsPlugin = TablePluginHelper<ClusterEventsTable>;
+
 use probing_core::core::EngineError;
 use probing_core::core::EngineExtension;
 use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
 
-#[derive(Debug, Default, EngineExtension)]
-pub struct ClusterExtension {}
+#[derive(Debug, EngineExtension)]
+pub struct ClusterExtension {
+    /// Seconds a node stays in `cluster.nodes` after its last report, 0 to
+    /// keep nodes until they deregister
+    #[option(aliases=["lease.seconds"])]
+    lease_seconds: Maybe<u64>,
+}
+
+impl Default for ClusterExtension {
+    fn default() -> Self {
+        Self {
+            lease_seconds: Maybe::Just(cluster::lease_seconds()),
+        }
+    }
+}
+
+impl ClusterExtension {
+    fn set_lease_seconds(&mut self, lease_seconds: Maybe<u64>) -> Result<(), EngineError> {
+        let seconds = match lease_seconds {
+            Maybe::Just(seconds) => seconds,
+            Maybe::Nothing => cluster::DEFAULT_LEASE_SECONDS,
+        };
+        cluster::set_lease_seconds(seconds);
+        self.lease_seconds = Maybe::Just(seconds);
+        Ok(())
+    }
+}
 
 impl EngineCall for ClusterExtension {}
 
@@ -88,6 +165,9 @@ impl EngineDatasource for ClusterExtension {
         name: Option<&str>,
     ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
         match name {
+            Some(name) if name == ClusterEventsTable::name() => {
+                Some(ClusterEventsPlugin::create(namespace, name))
+            }
             Some(name) => Some(ClusterPlugin::create(namespace, name)),
             None => None,
         }
@@ -1,8 +1,11 @@
 use std::sync::Arc;
 
-use datafusion::arrow::array::{GenericStringBuilder, RecordBatch};
+use datafusion::arrow::array::{
+    ArrayRef, GenericStringBuilder, Int32Array, RecordBatch, StringArray,
+};
 use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 
+use probing_core::core::{mpi, slurm};
 use probing_core::core::{CustomTable, EngineCall, EngineDatasource, TablePluginHelper};
 
 #[derive(Default, Debug)]
@@ -44,6 +47,115 @@ impl CustomTable for EnvTable {
 
 pub type EnvPlugin = TablePluginHelper<EnvTable>;
 
+/// Variables set by the MPI launcher, with the implementation detected from
+/// them, see [`probing_core::core::mpi`]
+#[derive(Default, Debug)]
+pub struct MpiEnvTable {}
+
+impl CustomTable for MpiEnvTable {
+    fn name() -> &'static str {
+        "env"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("implementation", DataType::Utf8, true),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let implementation = mpi::mpi_env().map(|env| env.implementation);
+        let mut envs = std::env::vars()
+            .filter(|(name, _)| mpi::is_mpi_variable(name))
+            .collect::<Vec<_>>();
+        envs.sort();
+
+        let mut implementations = GenericStringBuilder::<i32>::new();
+        let mut names = GenericStringBuilder::<i32>::new();
+        let mut values = GenericStringBuilder::<i32>::new();
+        for (name, value) in envs {
+            implementations.append_option(implementation);
+            names.append_value(name);
+            values.append_value(value);
+        }
+
+        match RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(implementations.finish()),
+                Arc::new(names.finish()),
+                Arc::new(values.finish()),
+            ],
+        ) {
+            Ok(batch) => vec![batch],
+            Err(_) => Default::default(),
+        }
+    }
+}
+
+pub type MpiEnvPlugin = TablePluginHelper<MpiEnvTable>;
+
+/// Slurm job and task of the process, one row under Slurm and none outside
+/// of it, see [`probing_core::core::slurm`]
+#[derive(Default, Debug)]
+pub struct SlurmJobTable {}
+
+impl CustomTable for SlurmJobTable {
+    fn name() -> &'static str {
+        "job"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("job_id", DataType::Utf8, false),
+            Field::new("job_name", DataType::Utf8, true),
+            Field::new("partition", DataType::Utf8, true),
+            Field::new("cluster_name", DataType::Utf8, true),
+            Field::new("nodelist", DataType::Utf8, true),
+            Field::new("hosts", DataType::Utf8, true),
+            Field::new("nnodes", DataType::Int32, true),
+            Field::new("ntasks", DataType::Int32, true),
+            Field::new("procid", DataType::Int32, true),
+            Field::new("localid", DataType::Int32, true),
+            Field::new("nodeid", DataType::Int32, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let jobs = slurm::job().into_iter().collect::<Vec<_>>();
+        let text = |f: fn(&slurm::SlurmJob) -> Option<String>| -> ArrayRef {
+            Arc::new(StringArray::from(jobs.iter().map(f).collect::<Vec<_>>()))
+        };
+        let int = |f: fn(&slurm::SlurmJob) -> Option<i32>| -> ArrayRef {
+            Arc::new(Int32Array::from(jobs.iter().map(f).collect::<Vec<_>>()))
+        };
+        let columns = vec![
+            text(|job| Some(job.job_id.clone())),
+            text(|job| job.job_name.clone()),
+            text(|job| job.partition.clone()),
+            text(|job| job.cluster_name.clone()),
+            text(|job| job.nodelist.clone()),
+            text(|job| {
+                let hosts = slurm::expand_nodelist(job.nodelist.as_deref()?).ok()?;
+                Some(hosts.join(","))
+            }),
+            int(|job| job.nnodes),
+            int(|job| job.ntasks),
+            int(|job| job.procid),
+            int(|job| job.localid),
+            int(|job| job.nodeid),
+        ];
+        match RecordBatch::try_new(Self::schema(), columns) {
+            Ok(batch) => vec![batch],
+            Err(_) => Default::default(),
+        }
+    }
+}
+
+pub type SlurmJobPlugin = TablePluginHelper<SlurmJobTable>;
+
 use probing_core::core::EngineError;
 use probing_core::core::EngineExtension;
 use probing_core::core::EngineExtensionOption;
@@ -59,9 +171,11 @@ impl EngineDatasource for EnvExtension {
         namespace: &str,
         name: Option<&str>,
     ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
-        match name {
-            Some(name) => Some(EnvPlugin::create(namespace, name)),
-            None => None,
+        match (namespace, name) {
+            ("mpi", Some(name)) => Some(MpiEnvPlugin::create(namespace, name)),
+            ("slurm", Some(name)) => Some(SlurmJobPlugin::create(namespace, name)),
+            (_, Some(name)) => Some(EnvPlugin::create(namespace, name)),
+            (_, None) => None,
         }
     }
 }
@@ -0,0 +1,577 @@
+//! Tables fed by external commands.
+//!
+//! Tools the agent cannot link against, e.g. `nvidia-smi`, are integrated by
+//! running them on an interval and parsing their output with a declared
+//! schema. Each source is recorded into `exec.<name>` with a
+//! [`TimeSeriesRecorder`], so the rows are stamped and bounded like any other
+//! time series:
+//!
+//! ```sql
+//! SET probing.exec.sources = '[{
+//!     "name": "gpu",
+//!     "command": ["nvidia-smi", "--query-gpu=index,utilization.gpu,memory.used",
+//!                 "--format=csv,noheader,nounits"],
+//!     "interval": 5000,
+//!     "format": "csv",
+//!     "columns": ["index:i64", "utilization:f64", "memory_used:f64"]
+//! }]';
+//! SELECT * FROM exec.gpu;
+//! ```
+//!
+//! The command is run without a shell. Setting the option replaces every
+//! source, and an empty value stops them all.
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::Value;
+
+use probing_core::core::{
+    EngineCall, EngineDatasource, EngineError, EngineExtension, EngineExtensionOption, Maybe,
+};
+use probing_core::journal::{self, EntryKind};
+use probing_core::recorder::TimeSeriesRecorder;
+use probing_proto::types::{Ele, EleType};
+
+/// Namespace of the tables of the sources
+pub const NAMESPACE: &str = "exec";
+
+/// Milliseconds between two runs of a source without an explicit interval
+pub const DEFAULT_INTERVAL: u64 = 10_000;
+
+/// Milliseconds a command may run before it is killed
+pub const DEFAULT_TIMEOUT: u64 = 10_000;
+
+/// Rows kept per source without an explicit capacity
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Output format of a command
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Comma separated values, a row per line, in column order
+    #[default]
+    Csv,
+    /// An object, an array of objects or an object per line, with a key per
+    /// column
+    Json,
+}
+
+/// A command line, given as its arguments or as a string split on whitespace
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum CommandLine {
+    Args(Vec<String>),
+    Line(String),
+}
+
+impl CommandLine {
+    fn args(&self) -> Vec<String> {
+        match self {
+            CommandLine::Args(args) => args.clone(),
+            CommandLine::Line(line) => line.split_whitespace().map(String::from).collect(),
+        }
+    }
+}
+
+/// A source as declared in `exec.sources`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceSpec {
+    pub name: String,
+    pub command: CommandLine,
+    /// Milliseconds between two runs
+    #[serde(default)]
+    pub interval: Option<u64>,
+    /// Milliseconds a run may take
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub format: Format,
+    /// Skip the first line of csv output
+    #[serde(default)]
+    pub header: bool,
+    /// `name:type` of every column, type one of bool, i32, i64, f32, f64 and
+    /// text
+    pub columns: Vec<String>,
+    /// Rows kept
+    #[serde(default)]
+    pub capacity: Option<usize>,
+}
+
+/// A source checked and ready to run
+#[derive(Clone, Debug)]
+pub struct Source {
+    pub name: String,
+    pub args: Vec<String>,
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub format: Format,
+    pub header: bool,
+    pub columns: Vec<(String, EleType)>,
+    pub capacity: usize,
+}
+
+/// Parse the value of `exec.sources`: a JSON array of sources, or a single
+/// source
+pub fn parse_sources(value: &str) -> Result<Vec<Source>, String> {
+    if value.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let specs: Vec<SourceSpec> = match serde_json::from_str::<Value>(value) {
+        Ok(Value::Array(_)) => serde_json::from_str(value),
+        Ok(_) => serde_json::from_str(value).map(|spec| vec![spec]),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut sources: Vec<Source> = vec![];
+    for spec in specs {
+        let source = Source::try_from(spec)?;
+        if sources.iter().any(|s| s.name == source.name) {
+            return Err(format!("duplicated source {}", source.name));
+        }
+        sources.push(source);
+    }
+    Ok(sources)
+}
+
+impl TryFrom<SourceSpec> for Source {
+    type Error = String;
+
+    fn try_from(spec: SourceSpec) -> Result<Self, Self::Error> {
+        let name = spec.name.trim().to_string();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("invalid source name `{name}`"));
+        }
+        let args = spec.command.args();
+        if args.is_empty() {
+            return Err(format!("{name}: empty command"));
+        }
+        if spec.columns.is_empty() {
+            return Err(format!("{name}: no columns"));
+        }
+        let columns = spec
+            .columns
+            .iter()
+            .map(|column| parse_column(column).map_err(|e| format!("{name}: {e}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let interval = spec.interval.unwrap_or(DEFAULT_INTERVAL);
+        if interval == 0 {
+            return Err(format!("{name}: interval must be positive"));
+        }
+        Ok(Source {
+            name,
+            args,
+            interval: Duration::from_millis(interval),
+            timeout: Duration::from_millis(spec.timeout.unwrap_or(DEFAULT_TIMEOUT)),
+            format: spec.format,
+            header: spec.header,
+            columns,
+            capacity: spec.capacity.unwrap_or(DEFAULT_CAPACITY).max(1),
+        })
+    }
+}
+
+fn parse_column(column: &str) -> Result<(String, EleType), String> {
+    let (name, dtype) = column
+        .split_once(':')
+        .ok_or_else(|| format!("column `{column}` is not name:type"))?;
+    let dtype = match dtype.trim().to_lowercase().as_str() {
+        "bool" => EleType::BOOL,
+        "i32" => EleType::I32,
+        "i64" => EleType::I64,
+        "f32" => EleType::F32,
+        "f64" => EleType::F64,
+        "text" => EleType::Text,
+        other => return Err(format!("column `{column}` has unsupported type {other}")),
+    };
+    let name = name.trim();
+    if name.is_empty() || name == "timestamp" {
+        return Err(format!("invalid column name `{name}`"));
+    }
+    Ok((name.to_string(), dtype))
+}
+
+impl Source {
+    /// Parse the output of a run into rows, a value per column
+    pub fn parse(&self, output: &str) -> Result<Vec<Vec<Ele>>, String> {
+        match self.format {
+            Format::Csv => output
+                .lines()
+                .skip(self.header as usize)
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    let fields = split_csv(line);
+                    if fields.len() != self.columns.len() {
+                        return Err(format!(
+                            "expected {} fields, got {}: {line}",
+                            self.columns.len(),
+                            fields.len()
+                        ));
+                    }
+                    fields
+                        .iter()
+                        .zip(&self.columns)
+                        .map(|(field, (name, dtype))| {
+                            parse_text(field, dtype).map_err(|e| format!("{name}: {e}"))
+                        })
+                        .collect()
+                })
+                .collect(),
+            Format::Json => {
+                let objects = match serde_json::from_str::<Value>(output) {
+                    Ok(Value::Array(objects)) => objects,
+                    Ok(object) => vec![object],
+                    // an object per line
+                    Err(_) => output
+                        .lines()
+                        .filter(|line| !line.trim().is_empty())
+                        .map(serde_json::from_str)
+                        .collect::<Result<_, _>>()
+                        .map_err(|e| e.to_string())?,
+                };
+                objects
+                    .iter()
+                    .map(|object| {
+                        let Value::Object(object) = object else {
+                            return Err(format!("expected an object, got {object}"));
+                        };
+                        self.columns
+                            .iter()
+                            .map(|(name, dtype)| {
+                                parse_json(object.get(name).unwrap_or(&Value::Null), dtype)
+                                    .map_err(|e| format!("{name}: {e}"))
+                            })
+                            .collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Run the command once and return its standard output
+    pub fn run(&self) -> Result<String, String> {
+        let mut child = Command::new(&self.args[0])
+            .args(&self.args[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("{}: {e}", self.args[0]))?;
+
+        // read in another thread, a full pipe would block the command
+        let mut stdout = child.stdout.take().ok_or("no stdout")?;
+        let reader = thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if started.elapsed() > self.timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("timed out after {:?}", self.timeout));
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(e) => return Err(e.to_string()),
+            }
+        };
+        let output = reader
+            .join()
+            .map_err(|_| "output reader panicked".to_string())?
+            .map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("exited with {status}"));
+        }
+        Ok(output)
+    }
+}
+
+/// Split a csv line, fields may be double-quoted
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Values tools print for missing data, recorded as nulls
+fn is_missing(text: &str) -> bool {
+    matches!(text, "" | "N/A" | "[N/A]" | "null" | "-")
+}
+
+fn parse_text(text: &str, dtype: &EleType) -> Result<Ele, String> {
+    let text = text.trim();
+    if is_missing(text) && *dtype != EleType::Text {
+        return Ok(Ele::Nil);
+    }
+    let invalid = || format!("`{text}` is not a {dtype}");
+    Ok(match dtype {
+        EleType::BOOL => match text.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Ele::BOOL(true),
+            "false" | "no" | "0" => Ele::BOOL(false),
+            _ => return Err(format!("`{text}` is not a bool")),
+        },
+        EleType::I32 => Ele::I32(text.parse().map_err(|_| invalid())?),
+        EleType::I64 => Ele::I64(text.parse().map_err(|_| invalid())?),
+        EleType::F32 => Ele::F32(text.parse().map_err(|_| invalid())?),
+        EleType::F64 => Ele::F64(text.parse().map_err(|_| invalid())?),
+        _ => Ele::Text(text.to_string()),
+    })
+}
+
+fn parse_json(value: &Value, dtype: &EleType) -> Result<Ele, String> {
+    match value {
+        Value::Null => Ok(Ele::Nil),
+        Value::String(text) => parse_text(text, dtype),
+        value if *dtype == EleType::Text => Ok(Ele::Text(value.to_string())),
+        Value::Bool(b) => Ok(Ele::BOOL(*b)),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) if *dtype != EleType::F32 && *dtype != EleType::F64 => {
+                parse_text(&i.to_string(), dtype)
+            }
+            _ => n
+                .as_f64()
+                .map(Ele::F64)
+                .ok_or_else(|| format!("{n} is out of range")),
+        },
+        value => Err(format!("{value} is not a {dtype}")),
+    }
+}
+
+/// A source running in its own thread
+struct Worker {
+    source: Source,
+    running: Arc<AtomicBool>,
+}
+
+static WORKERS: Lazy<Mutex<Vec<Worker>>> = Lazy::new(Default::default);
+
+/// Replace the running sources with `sources`
+fn start(sources: Vec<Source>) {
+    let mut workers = WORKERS.lock().unwrap_or_else(|e| e.into_inner());
+    for worker in workers.drain(..) {
+        worker.running.store(false, Ordering::SeqCst);
+    }
+    for source in sources {
+        let running = Arc::new(AtomicBool::new(true));
+        let worker = Worker {
+            source: source.clone(),
+            running: running.clone(),
+        };
+        let spawned = thread::Builder::new()
+            .name(format!("probing-exec-{}", source.name))
+            .spawn(move || run_source(source, running));
+        match spawned {
+            Ok(_) => workers.push(worker),
+            Err(e) => log::error!("Failed to start exec source {}: {e}", worker.source.name),
+        }
+    }
+}
+
+fn run_source(source: Source, running: Arc<AtomicBool>) {
+    // the option is set under the engine lock, wait for it to be released
+    // so that the recorder registers its table
+    drop(probing_core::ENGINE.blocking_read());
+    let mut builder =
+        TimeSeriesRecorder::builder(NAMESPACE, &source.name).with_capacity(source.capacity);
+    for (name, dtype) in &source.columns {
+        builder = builder.with_column(name, dtype.clone());
+    }
+    let recorder = builder.build();
+    let table = recorder.table_name();
+
+    while running.load(Ordering::SeqCst) {
+        let rows = source.run().and_then(|output| source.parse(&output));
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        match rows {
+            Ok(rows) => {
+                for row in rows {
+                    if let Err(e) = recorder.record(row) {
+                        log::warn!("Failed to record {table}: {e}");
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!("exec source {} failed: {e}", source.name);
+                journal::record(EntryKind::Extension, &table, e);
+            }
+        }
+        thread::sleep(source.interval);
+    }
+}
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct ExecExtension {
+    /// JSON list of the commands recorded into `exec.<name>`, each with a
+    /// name, command, interval, format and columns
+    #[option]
+    sources: Maybe<String>,
+}
+
+impl ExecExtension {
+    fn set_sources(&mut self, sources: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = sources.clone().into();
+        let invalid = |e: String| {
+            EngineError::InvalidOptionValue(
+                Self::OPTION_SOURCES.to_string(),
+                format!("{value} ({e})"),
+            )
+        };
+        let parsed = parse_sources(&value).map_err(invalid)?;
+        // a recorder keeps the columns it was built with
+        for source in &parsed {
+            if let Some(recorder) = TimeSeriesRecorder::get(NAMESPACE, &source.name) {
+                let schema = recorder.schema();
+                let columns: Vec<_> = schema.fields().iter().skip(1).map(|f| f.name()).collect();
+                if columns.len() != source.columns.len()
+                    || columns
+                        .iter()
+                        .zip(&source.columns)
+                        .any(|(a, (b, _))| *a != b)
+                {
+                    return Err(invalid(format!(
+                        "the columns of {} cannot change",
+                        recorder.table_name()
+                    )));
+                }
+            }
+        }
+
+        let names = parsed
+            .iter()
+            .map(|s| format!("{}={}", s.name, s.args.join(" ")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        log::warn!("exec sources changed: [{names}]");
+        journal::record(EntryKind::Audit, "exec.sources", format!("[{names}]"));
+
+        start(parsed);
+        self.sources = sources;
+        Ok(())
+    }
+}
+
+impl EngineCall for ExecExtension {}
+
+// `exec.<name>` is registered by the recorder of each source
+impl EngineDatasource for ExecExtension {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sources() {
+        let sources = parse_sources(
+            r#"{"name": "gpu", "command": "nvidia-smi --format=csv",
+                "columns": ["index:i64", "util:f64", "name:text"]}"#,
+        )
+        .unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].args, ["nvidia-smi", "--format=csv"]);
+        assert_eq!(sources[0].interval, Duration::from_millis(DEFAULT_INTERVAL));
+        assert_eq!(sources[0].columns[1], ("util".to_string(), EleType::F64));
+
+        assert!(parse_sources("").unwrap().is_empty());
+        assert!(parse_sources(r#"{"name": "gpu", "command": [], "columns": ["a:i64"]}"#).is_err());
+        assert!(
+            parse_sources(r#"{"name": "a.b", "command": "ls", "columns": ["a:i64"]}"#).is_err()
+        );
+        assert!(parse_sources(r#"{"name": "a", "command": "ls", "columns": ["a:u8"]}"#).is_err());
+        assert!(parse_sources(
+            r#"[{"name": "a", "command": "ls", "columns": ["a:i64"]},
+                {"name": "a", "command": "ls", "columns": ["a:i64"]}]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_output() {
+        let mut source = parse_sources(
+            r#"{"name": "t", "command": "true", "header": true,
+                "columns": ["index:i64", "util:f64", "name:text"]}"#,
+        )
+        .unwrap()
+        .remove(0);
+        let rows = source
+            .parse("index, util, name\n0, 12.5, \"A100, 80GB\"\n1, [N/A], H100\n")
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![Ele::I64(0), Ele::F64(12.5), Ele::Text("A100, 80GB".into())],
+                vec![Ele::I64(1), Ele::Nil, Ele::Text("H100".into())],
+            ]
+        );
+        assert!(source.parse("x\n0, 1\n").is_err());
+        assert!(source.parse("x\nzero, 1, a\n").is_err());
+
+        source.format = Format::Json;
+        let expected = vec![vec![Ele::I64(2), Ele::F64(3.0), Ele::Nil]];
+        assert_eq!(
+            source.parse(r#"{"index": 2, "util": 3}"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            source.parse(r#"[{"index": "2", "util": 3.0}]"#).unwrap(),
+            expected
+        );
+        assert_eq!(
+            source
+                .parse("{\"index\": 2, \"util\": 3}\n{\"index\": 2, \"util\": 3}\n")
+                .unwrap(),
+            vec![expected[0].clone(), expected[0].clone()]
+        );
+        assert!(source.parse("[1]").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let source = parse_sources(
+            r#"{"name": "echo", "command": ["echo", "1,2.5"], "columns": ["a:i64", "b:f64"]}"#,
+        )
+        .unwrap()
+        .remove(0);
+        let output = source.run().unwrap();
+        assert_eq!(
+            source.parse(&output).unwrap(),
+            vec![vec![Ele::I64(1), Ele::F64(2.5)]]
+        );
+
+        let mut sleep = parse_sources(
+            r#"{"name": "sleep", "command": "sleep 5", "timeout": 50, "columns": ["a:i64"]}"#,
+        )
+        .unwrap()
+        .remove(0);
+        assert!(sleep.run().unwrap_err().contains("timed out"));
+        sleep.args = vec!["false".into()];
+        assert!(sleep.run().is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Tables of external databases defined through an option.
+//!
+//! `federation.tables` serves tables of PostgreSQL or ClickHouse databases as
+//! `remote.<name>`, read-only, see [`probing_core::core::federation`]:
+//!
+//! ```sql
+//! SET probing.federation.tables = '[{
+//!     "name": "runs",
+//!     "kind": "postgres",
+//!     "url": "postgres://probe@tracking:5432/experiments",
+//!     "table": "public.runs",
+//!     "password_env": "TRACKING_PASSWORD"
+//! }]';
+//! SELECT * FROM remote.runs WHERE run_id = 'exp-42';
+//! ```
+//!
+//! Setting the option replaces every remote table, and an empty value drops
+//! them all. Passwords are only read from `password_env`, and the option
+//! reads back as the tables with their urls redacted.
+
+use std::thread;
+
+use serde_json::Value;
+
+use probing_core::core::federation::{RemoteTableSpec, NAMESPACE};
+use probing_core::core::{
+    EngineCall, EngineDatasource, EngineError, EngineExtension, EngineExtensionOption, Maybe,
+};
+use probing_core::journal::{self, EntryKind};
+use probing_core::ENGINE;
+
+/// Parse the value of `federation.tables`: a JSON array of tables, or a
+/// single table
+pub fn parse_tables(value: &str) -> Result<Vec<RemoteTableSpec>, String> {
+    if value.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let specs: Vec<RemoteTableSpec> = match serde_json::from_str::<Value>(value) {
+        Ok(Value::Array(_)) => serde_json::from_str(value),
+        Ok(_) => serde_json::from_str(value).map(|spec| vec![spec]),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut tables: Vec<RemoteTableSpec> = vec![];
+    for spec in specs {
+        spec.validate()?;
+        if tables.iter().any(|t| t.name == spec.name) {
+            return Err(format!("duplicated table {}", spec.name));
+        }
+        tables.push(spec);
+    }
+    Ok(tables)
+}
+
+/// Replace the remote tables with `tables`
+fn register(tables: Vec<RemoteTableSpec>) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Failed to create runtime for remote tables: {e}");
+            return;
+        }
+    };
+
+    // the option is set under the engine lock, the tables are registered once
+    // it is released
+    runtime.block_on(async {
+        let engine = ENGINE.read().await;
+        for name in engine.remote_tables() {
+            if !tables.iter().any(|t| t.name == name) {
+                if let Err(e) = engine.drop_remote_table(&name) {
+                    log::warn!("Failed to drop {NAMESPACE}.{name}: {e}");
+                }
+            }
+        }
+        for spec in tables {
+            let table = format!("{NAMESPACE}.{}", spec.name);
+            let url = spec.display_url();
+            match engine.register_remote_table(spec).await {
+                Ok(()) => log::info!("{table} registered from {url}"),
+                Err(e) => {
+                    log::warn!("Failed to register {table}: {e}");
+                    journal::record(EntryKind::Extension, &table, e.to_string());
+                }
+            }
+        }
+    });
+}
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct FederationExtension {
+    /// JSON list of the tables of external databases served as
+    /// `remote.<name>`, each with a name, kind, url and table
+    #[option]
+    tables: Maybe<String>,
+}
+
+impl FederationExtension {
+    fn set_tables(&mut self, tables: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = tables.into();
+        // not echoed back, in case it carries a password
+        let specs = parse_tables(&value)
+            .map_err(|e| EngineError::InvalidOptionValue(Self::OPTION_TABLES.to_string(), e))?;
+        let redacted = specs
+            .iter()
+            .map(RemoteTableSpec::redacted)
+            .collect::<Vec<_>>();
+
+        let sources = specs
+            .iter()
+            .map(|spec| format!("{}={}", spec.name, spec.display_url()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        journal::record(
+            EntryKind::Audit,
+            Self::OPTION_TABLES,
+            format!("remote tables: [{sources}]"),
+        );
+
+        let spawned = thread::Builder::new()
+            .name("probing-federation".to_string())
+            .spawn(move || register(specs));
+        if let Err(e) = spawned {
+            log::error!("Failed to register remote tables: {e}");
+        }
+        self.tables = if redacted.is_empty() {
+            Maybe::Nothing
+        } else {
+            Maybe::Just(serde_json::to_string(&redacted).unwrap_or_default())
+        };
+        Ok(())
+    }
+}
+
+impl EngineCall for FederationExtension {}
+
+// `remote.<name>` is registered by the engine once connected
+impl EngineDatasource for FederationExtension {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use probing_core::core::federation::RemoteKind;
+
+    #[test]
+    fn test_parse_tables() {
+        assert!(parse_tables("").unwrap().is_empty());
+        assert!(parse_tables("{").is_err());
+        assert!(
+            parse_tables(r#"{"name": "runs", "kind": "mysql", "url": "", "table": "t"}"#).is_err()
+        );
+
+        let value = r#"{"name": "runs", "kind": "postgres",
+            "url": "postgres://probe@db/experiments", "table": "runs", "max_rows": 10}"#;
+        if !RemoteKind::Postgres.is_available() {
+            assert!(parse_tables(value).is_err());
+            return;
+        }
+        let tables = parse_tables(value).unwrap();
+        assert_eq!(tables[0].max_rows, Some(10));
+        assert!(parse_tables(&format!("[{value}, {value}]")).is_err());
+        assert!(parse_tables(&value.replace("\"table\"", "\"tabel\"")).is_err());
+        assert!(parse_tables(&value.replace("probe@", "probe:secret@")).is_err());
+    }
+
+    #[test]
+    fn test_set_tables() {
+        let mut ext = FederationExtension::default();
+        ext.set_tables(Maybe::Just(String::new())).unwrap();
+        assert!(matches!(ext.tables, Maybe::Nothing));
+        assert!(ext
+            .set_tables(Maybe::Just(
+                r#"{"name": "runs", "kind": "postgres", "table": "runs",
+                    "url": "postgres://probe:secret@db/experiments"}"#
+                    .to_string()
+            ))
+            .is_err());
+        assert!(matches!(ext.tables, Maybe::Nothing));
+    }
+}
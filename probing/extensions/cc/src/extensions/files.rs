@@ -1,4 +1,5 @@
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
 use datafusion::catalog::TableProvider;
@@ -9,7 +10,56 @@ use datafusion::datasource::{
 use datafusion::error::Result;
 use datafusion::prelude::SessionContext;
 
-use probing_core::core::{CustomNamespace, EngineCall, EngineDatasource, NamespacePluginHelper};
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    CustomNamespace, EngineCall, EngineDatasource, Maybe, NamespacePluginHelper,
+};
+use probing_core::journal::{self, EntryKind};
+
+/// Directories served by the file API until `files.allowed_dirs` is set,
+/// relative to the working directory of the process
+pub const DEFAULT_ALLOWED_DIRS: &[&str] = &["./logs", "./data", "./config"];
+
+/// Canonical directories set with `files.allowed_dirs`, `None` for the defaults
+static ALLOWED_DIRS: Lazy<RwLock<Option<Vec<PathBuf>>>> = Lazy::new(Default::default);
+
+/// Base directories the file API may read from
+pub fn allowed_dirs() -> Vec<PathBuf> {
+    match &*ALLOWED_DIRS.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(dirs) => dirs.clone(),
+        None => DEFAULT_ALLOWED_DIRS.iter().map(PathBuf::from).collect(),
+    }
+}
+
+/// Parse a colon-separated list of directories into their canonical paths.
+///
+/// Every directory must exist, and the filesystem root is refused since it
+/// would expose every file of the host.
+pub fn parse_allowed_dirs(value: &str) -> Result<Vec<PathBuf>, String> {
+    let mut dirs: Vec<PathBuf> = vec![];
+    for dir in value.split(':').map(str::trim).filter(|d| !d.is_empty()) {
+        let canonical = Path::new(dir)
+            .canonicalize()
+            .map_err(|e| format!("{dir}: {e}"))?;
+        if !canonical.is_dir() {
+            return Err(format!("{dir}: not a directory"));
+        }
+        if canonical.parent().is_none() {
+            return Err(format!("{dir}: the filesystem root cannot be served"));
+        }
+        if !dirs.contains(&canonical) {
+            dirs.push(canonical);
+        }
+    }
+    Ok(dirs)
+}
+
+fn join_dirs(dirs: &[PathBuf]) -> String {
+    dirs.iter()
+        .map(|d| d.display().to_string())
+        .collect::<Vec<_>>()
+        .join(":")
+}
 
 #[derive(Default, Debug)]
 pub struct FileList {}
@@ -59,7 +109,41 @@ use probing_core::core::EngineExtension;
 use probing_core::core::EngineExtensionOption;
 
 #[derive(Debug, Default, EngineExtension)]
-pub struct FilesExtension {}
+pub struct FilesExtension {
+    /// Colon-separated directories served by the file API, empty for the
+    /// defaults (./logs, ./data and ./config)
+    #[option(aliases=["allowed.dirs"])]
+    allowed_dirs: Maybe<String>,
+}
+
+impl FilesExtension {
+    fn set_allowed_dirs(&mut self, dirs: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = dirs.clone().into();
+        let canonical = if value.trim().is_empty() {
+            None
+        } else {
+            Some(parse_allowed_dirs(&value).map_err(|e| {
+                EngineError::InvalidOptionValue(
+                    "allowed_dirs".to_string(),
+                    format!("{value} ({e})"),
+                )
+            })?)
+        };
+
+        let old = join_dirs(&allowed_dirs());
+        *ALLOWED_DIRS.write().unwrap_or_else(|e| e.into_inner()) = canonical;
+        let new = join_dirs(&allowed_dirs());
+        log::warn!("file API directories changed: {old} => {new}");
+        journal::record(
+            EntryKind::Audit,
+            "files.allowed_dirs",
+            format!("{old} => {new}"),
+        );
+
+        self.allowed_dirs = dirs;
+        Ok(())
+    }
+}
 
 impl EngineCall for FilesExtension {}
 
@@ -76,3 +160,44 @@ impl EngineDatasource for FilesExtension {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_dirs() {
+        let tmp = std::env::temp_dir().canonicalize().unwrap();
+        // the audit entry is flushed to the journal file, keep it out of the tree
+        let journal_dir = tmp.join(format!("probing-journal-{}", std::process::id()));
+        std::env::set_var("PROBING_ERROR_JOURNAL_DIR", &journal_dir);
+        let value = format!("{}: {}/. :", tmp.display(), tmp.display());
+        assert_eq!(parse_allowed_dirs(&value).unwrap(), vec![tmp.clone()]);
+        assert!(parse_allowed_dirs("/").is_err());
+        assert!(parse_allowed_dirs("/no/such/probing/dir").is_err());
+
+        let mut ext = FilesExtension::default();
+        assert!(ext.set("allowed.dirs", "/no/such/probing/dir").is_err());
+        assert_eq!(
+            allowed_dirs(),
+            [
+                Path::new("./logs"),
+                Path::new("./data"),
+                Path::new("./config")
+            ]
+        );
+
+        assert!(ext.set("allowed_dirs", tmp.to_str().unwrap()).is_ok());
+        assert_eq!(allowed_dirs(), vec![tmp.clone()]);
+        let audit = journal::entries()
+            .into_iter()
+            .rfind(|e| e.kind == EntryKind::Audit)
+            .unwrap();
+        assert!(audit.message.ends_with(&format!("=> {}", tmp.display())));
+
+        assert!(ext.set("allowed_dirs", "").is_ok());
+        assert_eq!(allowed_dirs().len(), DEFAULT_ALLOWED_DIRS.len());
+        journal::flush();
+        let _ = std::fs::remove_dir_all(journal_dir);
+    }
+}
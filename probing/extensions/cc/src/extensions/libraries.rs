@@ -0,0 +1,731 @@
+//! Shared objects loaded by the process.
+//!
+//! `process.libraries` lists every shared object mapped by the process with
+//! its version, GNU build-id, size and whether debug symbols are available.
+//! `process.library_checks` flags combinations of CUDA, NCCL and torch known
+//! to fail at runtime, such as a CUDA runtime loaded twice or a driver too
+//! old for it. Comparing the build-ids of two ranks shows whether they really
+//! run the same binaries:
+//!
+//! ```sql
+//! SELECT name, version, build_id FROM process.libraries WHERE name LIKE 'libnccl%';
+//! SELECT * FROM process.library_checks WHERE level <> 'ok';
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use datafusion::arrow::array::{BooleanArray, Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use once_cell::sync::Lazy;
+
+use probing_core::core::{
+    CustomTable, EngineCall, EngineDatasource, EngineError, EngineExtension, EngineExtensionOption,
+    TablePluginHelper,
+};
+
+/// Libraries checked for being loaded from more than one file
+const WATCHED: &[&str] = &["cudart", "nccl", "cudnn", "cublas"];
+
+/// Minimum driver of each major version of the CUDA runtime
+const MIN_DRIVER: &[(u32, u32)] = &[(11, 450), (12, 525), (13, 580)];
+
+/// Largest note segment or section name table read from a library
+const MAX_READ: u64 = 1 << 20;
+
+/// A shared object mapped by the process
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Library {
+    pub name: String,
+    pub path: String,
+    /// Python distribution shipping the library, e.g. `torch` or
+    /// `nvidia-nccl-cu12`
+    pub package: Option<String>,
+    pub version: Option<String>,
+    pub size: Option<u64>,
+    pub build_id: Option<String>,
+    pub debug_symbols: bool,
+}
+
+impl Library {
+    /// Name without the `lib` prefix, the hash suffix of bundled copies and
+    /// the `.so` extension, e.g. `cudart` for `libcudart-9335f6a2.so.12`
+    pub fn family(&self) -> &str {
+        let name = self.name.strip_prefix("lib").unwrap_or(&self.name);
+        let name = name.split(".so").next().unwrap_or(name);
+        match name.rsplit_once('-') {
+            Some((family, hash))
+                if hash.len() == 8 && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                family
+            }
+            _ => name,
+        }
+    }
+}
+
+/// What is read from the ELF file of a library
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ElfInfo {
+    build_id: Option<String>,
+    debug_info: bool,
+}
+
+/// Path, size and modification time of a file
+type FileKey = (String, u64, Option<SystemTime>);
+
+/// ELF details keyed by path, size and modification time
+static ELF_CACHE: Lazy<Mutex<HashMap<FileKey, ElfInfo>>> = Lazy::new(Default::default);
+
+/// Shared objects mapped by the process, one row per file
+pub fn libraries() -> Vec<Library> {
+    match std::fs::read_to_string("/proc/self/maps") {
+        Ok(maps) => mapped_paths(&maps).into_iter().map(library).collect(),
+        Err(e) => {
+            log::debug!("failed to read memory maps: {e}");
+            vec![]
+        }
+    }
+}
+
+/// Paths of the shared objects in `/proc/<pid>/maps`, in load order
+fn mapped_paths(maps: &str) -> Vec<String> {
+    let mut paths: Vec<String> = vec![];
+    for line in maps.lines() {
+        let Some(path) = line.split_whitespace().nth(5) else {
+            continue;
+        };
+        let name = path.rsplit('/').next().unwrap_or(path);
+        if path.starts_with('/') && name.contains(".so") && !paths.iter().any(|p| p == path) {
+            paths.push(path.to_string());
+        }
+    }
+    paths
+}
+
+fn library(path: String) -> Library {
+    let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+    let metadata = std::fs::metadata(&path).ok();
+    let size = metadata.as_ref().map(|m| m.len());
+    let (package, version) = match package_version(Path::new(&path)) {
+        Some((package, version)) => (Some(package), Some(version)),
+        None => (None, soname_version(&name)),
+    };
+
+    let key = (
+        path.clone(),
+        size.unwrap_or_default(),
+        metadata.and_then(|m| m.modified().ok()),
+    );
+    let cached = ELF_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+        .cloned();
+    let elf = cached.unwrap_or_else(|| {
+        let elf = read_elf(Path::new(&path)).unwrap_or_default();
+        ELF_CACHE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, elf.clone());
+        elf
+    });
+    let debug_symbols = elf.debug_info
+        || elf
+            .build_id
+            .as_deref()
+            .is_some_and(|id| separate_debug_file(id).exists());
+
+    Library {
+        name,
+        path,
+        package,
+        version,
+        size,
+        build_id: elf.build_id,
+        debug_symbols,
+    }
+}
+
+/// Version in the file name, e.g. `12.1.105` of `libcudart.so.12.1.105`
+fn soname_version(name: &str) -> Option<String> {
+    let (_, version) = name.split_once(".so.")?;
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then(|| version.to_string())
+}
+
+/// Python distribution of a library installed by pip, with its version:
+/// `<site>/nvidia/<component>/lib/<lib>` is shipped by the `nvidia_<component>_*`
+/// wheel, and `<site>/<package>/lib/<lib>` by a package with a `version.py`,
+/// as torch
+fn package_version(path: &Path) -> Option<(String, String)> {
+    let package = path.parent()?.parent()?;
+    let site = package.parent()?;
+    if site.file_name()? == "nvidia" {
+        let prefix = format!("nvidia_{}_", package.file_name()?.to_str()?);
+        let site = site.parent()?;
+        for entry in std::fs::read_dir(site).ok()?.flatten() {
+            let name = entry.file_name();
+            let Some((dist, version)) = name
+                .to_str()
+                .and_then(|name| name.strip_suffix(".dist-info"))
+                .and_then(|name| name.rsplit_once('-'))
+            else {
+                continue;
+            };
+            if dist.starts_with(&prefix) {
+                return Some((dist.replace('_', "-"), version.to_string()));
+            }
+        }
+        return None;
+    }
+    let version = std::fs::read_to_string(package.join("version.py")).ok()?;
+    let version = python_assignment(&version, "__version__")?;
+    Some((package.file_name()?.to_str()?.to_string(), version))
+}
+
+/// Value of `name = '<value>'` in a Python source
+fn python_assignment(source: &str, name: &str) -> Option<String> {
+    source.lines().find_map(|line| {
+        let (lhs, rhs) = line.split_once('=')?;
+        if lhs.split(':').next()?.trim() != name {
+            return None;
+        }
+        let value = rhs.trim().trim_matches(|c| c == '\'' || c == '"');
+        (!value.is_empty() && value != "None").then(|| value.to_string())
+    })
+}
+
+/// Debug file of a stripped library installed by the distribution
+fn separate_debug_file(build_id: &str) -> PathBuf {
+    let (dir, file) = build_id.split_at(build_id.len().min(2));
+    PathBuf::from(format!("/usr/lib/debug/.build-id/{dir}/{file}.debug"))
+}
+
+/// Read the build-id note and look for a `.debug_info` section, without
+/// reading more of the file than its headers, notes and section names
+fn read_elf(path: &Path) -> std::io::Result<ElfInfo> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 64];
+    file.read_exact(&mut header)?;
+    let Some(elf) = Elf::new(&header) else {
+        return Ok(ElfInfo::default());
+    };
+
+    let mut info = ElfInfo::default();
+    let phoff = elf.word(&header, if elf.is64 { 0x20 } else { 0x1c });
+    let phentsize = elf.u16(&header, if elf.is64 { 0x36 } else { 0x2a }) as u64;
+    let phnum = elf.u16(&header, if elf.is64 { 0x38 } else { 0x2c }) as u64;
+    for i in 0..phnum {
+        let ph = read_at(&mut file, phoff + i * phentsize, phentsize)?;
+        // PT_NOTE
+        if elf.u32(&ph, 0) != 4 {
+            continue;
+        }
+        let (offset, size) = if elf.is64 {
+            (elf.u64(&ph, 0x08), elf.u64(&ph, 0x20))
+        } else {
+            (elf.u32(&ph, 0x04) as u64, elf.u32(&ph, 0x10) as u64)
+        };
+        let notes = read_at(&mut file, offset, size.min(MAX_READ))?;
+        if let Some(id) = elf.build_id(&notes) {
+            info.build_id = Some(id);
+            break;
+        }
+    }
+
+    let shoff = elf.word(&header, if elf.is64 { 0x28 } else { 0x20 });
+    let shentsize = elf.u16(&header, if elf.is64 { 0x3a } else { 0x2e }) as u64;
+    let shnum = elf.u16(&header, if elf.is64 { 0x3c } else { 0x30 }) as u64;
+    let shstrndx = elf.u16(&header, if elf.is64 { 0x3e } else { 0x32 }) as u64;
+    if shoff == 0 || shstrndx >= shnum {
+        return Ok(info);
+    }
+    let sections = read_at(&mut file, shoff, shnum * shentsize)?;
+    let section = |i: u64| -> (u32, u64, u64) {
+        let sh = &sections[(i * shentsize) as usize..];
+        if elf.is64 {
+            (elf.u32(sh, 0), elf.u64(sh, 0x18), elf.u64(sh, 0x20))
+        } else {
+            (
+                elf.u32(sh, 0),
+                elf.u32(sh, 0x10) as u64,
+                elf.u32(sh, 0x14) as u64,
+            )
+        }
+    };
+    let (_, offset, size) = section(shstrndx);
+    let names = read_at(&mut file, offset, size.min(MAX_READ))?;
+    info.debug_info = (0..shnum).any(|i| {
+        let name = names.get(section(i).0 as usize..).unwrap_or_default();
+        let name = name.split(|&b| b == 0).next().unwrap_or_default();
+        name == b".debug_info" || name == b".zdebug_info"
+    });
+    Ok(info)
+}
+
+fn read_at(file: &mut File, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; len as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Class and byte order of an ELF file
+#[derive(Clone, Copy)]
+struct Elf {
+    is64: bool,
+    le: bool,
+}
+
+impl Elf {
+    fn new(header: &[u8]) -> Option<Self> {
+        if &header[..4] != b"\x7fELF" {
+            return None;
+        }
+        let is64 = match header[4] {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let le = match header[5] {
+            1 => true,
+            2 => false,
+            _ => return None,
+        };
+        Some(Self { is64, le })
+    }
+
+    fn bytes<const N: usize>(&self, buf: &[u8], offset: usize) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        if let Some(src) = buf.get(offset..offset + N) {
+            bytes.copy_from_slice(src);
+        }
+        if !self.le {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    fn u16(&self, buf: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(self.bytes(buf, offset))
+    }
+
+    fn u32(&self, buf: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(self.bytes(buf, offset))
+    }
+
+    fn u64(&self, buf: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(self.bytes(buf, offset))
+    }
+
+    /// An address or offset: 8 bytes in 64-bit files and 4 in 32-bit ones
+    fn word(&self, buf: &[u8], offset: usize) -> u64 {
+        if self.is64 {
+            self.u64(buf, offset)
+        } else {
+            self.u32(buf, offset) as u64
+        }
+    }
+
+    /// Hex of the `NT_GNU_BUILD_ID` note among `notes`
+    fn build_id(&self, notes: &[u8]) -> Option<String> {
+        let align = |n: usize| (n + 3) & !3;
+        let mut offset = 0;
+        while offset + 12 <= notes.len() {
+            let namesz = self.u32(notes, offset) as usize;
+            let descsz = self.u32(notes, offset + 4) as usize;
+            let kind = self.u32(notes, offset + 8);
+            let name = offset + 12;
+            let desc = name + align(namesz);
+            let end = desc + align(descsz);
+            if desc + descsz > notes.len() {
+                return None;
+            }
+            // NT_GNU_BUILD_ID
+            if kind == 3 && notes.get(name..name + namesz) == Some(b"GNU\0") {
+                let id = &notes[desc..desc + descsz];
+                return Some(id.iter().map(|b| format!("{b:02x}")).collect());
+            }
+            offset = end;
+        }
+        None
+    }
+}
+
+/// Result of a check of the loaded libraries
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryCheck {
+    pub check: &'static str,
+    /// `ok` or `fail`
+    pub level: &'static str,
+    pub message: String,
+}
+
+impl LibraryCheck {
+    fn new(check: &'static str, level: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            check,
+            level,
+            message: message.into(),
+        }
+    }
+}
+
+/// Leading numeric components of a version, e.g. `[2, 3, 0]` of `2.3.0+cu121`
+fn numbers(version: &str) -> Vec<u32> {
+    version
+        .split(['.', '+', '-'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// CUDA version torch was built for, e.g. `(12, 1)` of `2.3.0+cu121`
+fn torch_cuda(version: &str) -> Option<(u32, u32)> {
+    let (_, local) = version.split_once('+')?;
+    let digits = local.strip_prefix("cu")?;
+    if digits.len() < 3 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (major, minor) = digits.split_at(digits.len() - 1);
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// CUDA major version of an nvidia wheel, e.g. 12 of `nvidia-nccl-cu12`
+fn wheel_cuda(package: &str) -> Option<u32> {
+    package.rsplit_once("-cu")?.1.parse().ok()
+}
+
+/// Check the libraries for combinations that fail at runtime. Only checks
+/// that apply to the loaded libraries are reported.
+pub fn check(libraries: &[Library]) -> Vec<LibraryCheck> {
+    let mut checks = vec![];
+    let mut families: BTreeMap<&str, Vec<&Library>> = BTreeMap::new();
+    for library in libraries {
+        families.entry(library.family()).or_default().push(library);
+    }
+    let first = |family: &str| families.get(family).and_then(|libs| libs.first().copied());
+
+    for family in WATCHED {
+        let Some(libs) = families.get(family) else {
+            continue;
+        };
+        if libs.len() > 1 {
+            let paths = libs.iter().map(|l| l.path.as_str()).collect::<Vec<_>>();
+            checks.push(LibraryCheck::new(
+                "duplicates",
+                "fail",
+                format!(
+                    "lib{family} is loaded from {} files: {}",
+                    libs.len(),
+                    paths.join(", ")
+                ),
+            ));
+        }
+    }
+
+    let cudart = first("cudart").and_then(|l| Some((l, numbers(l.version.as_deref()?))));
+    let cudart_major = cudart.as_ref().and_then(|(_, v)| v.first().copied());
+
+    let torch = libraries
+        .iter()
+        .find(|l| l.package.as_deref() == Some("torch"))
+        .and_then(|l| Some((l.version.clone()?, torch_cuda(l.version.as_deref()?)?)));
+    if let (Some((torch, (major, minor))), Some(runtime)) = (&torch, cudart_major) {
+        if *major == runtime {
+            checks.push(LibraryCheck::new(
+                "torch_cuda",
+                "ok",
+                format!("torch {torch} built for CUDA {major}.{minor}, runtime {runtime}"),
+            ));
+        } else {
+            checks.push(LibraryCheck::new(
+                "torch_cuda",
+                "fail",
+                format!("torch {torch} is built for CUDA {major}.{minor} but the CUDA {runtime} runtime is loaded"),
+            ));
+        }
+    }
+
+    if let (Some(nccl), Some(runtime)) = (first("nccl"), cudart_major) {
+        if let Some(major) = nccl.package.as_deref().and_then(wheel_cuda) {
+            let version = nccl.version.as_deref().unwrap_or("?");
+            if major == runtime {
+                checks.push(LibraryCheck::new(
+                    "nccl_cuda",
+                    "ok",
+                    format!("NCCL {version} built for CUDA {major}"),
+                ));
+            } else {
+                checks.push(LibraryCheck::new(
+                    "nccl_cuda",
+                    "fail",
+                    format!("NCCL {version} is built for CUDA {major} but the CUDA {runtime} runtime is loaded"),
+                ));
+            }
+        }
+    }
+
+    let driver = first("cuda")
+        .and_then(|l| Some((l.version.clone()?, *numbers(l.version.as_deref()?).first()?)));
+    let required = cudart_major
+        .and_then(|runtime| Some((runtime, MIN_DRIVER.iter().find(|(m, _)| *m == runtime)?.1)));
+    if let (Some((driver, major)), Some((runtime, min))) = (driver, required) {
+        if major >= min {
+            checks.push(LibraryCheck::new(
+                "driver",
+                "ok",
+                format!("driver {driver} supports CUDA {runtime}"),
+            ));
+        } else {
+            checks.push(LibraryCheck::new(
+                "driver",
+                "fail",
+                format!("driver {driver} is older than {min}, required by CUDA {runtime}"),
+            ));
+        }
+    }
+
+    checks
+}
+
+/// `process.libraries`: the shared objects mapped by the process
+#[derive(Default, Debug)]
+pub struct LibrariesTable {}
+
+impl CustomTable for LibrariesTable {
+    fn name() -> &'static str {
+        "libraries"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("package", DataType::Utf8, true),
+            Field::new("version", DataType::Utf8, true),
+            Field::new("size", DataType::Int64, true),
+            Field::new("build_id", DataType::Utf8, true),
+            Field::new("debug_symbols", DataType::Boolean, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let libs = libraries();
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(libs.iter().map(|l| &l.name))),
+                Arc::new(StringArray::from_iter_values(libs.iter().map(|l| &l.path))),
+                Arc::new(StringArray::from_iter(
+                    libs.iter().map(|l| l.package.as_deref()),
+                )),
+                Arc::new(StringArray::from_iter(
+                    libs.iter().map(|l| l.version.as_deref()),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    libs.iter().map(|l| l.size.map(|s| s as i64)),
+                )),
+                Arc::new(StringArray::from_iter(
+                    libs.iter().map(|l| l.build_id.as_deref()),
+                )),
+                Arc::new(BooleanArray::from_iter(
+                    libs.iter().map(|l| Some(l.debug_symbols)),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build libraries batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type LibrariesPlugin = TablePluginHelper<LibrariesTable>;
+
+/// `process.library_checks`: mismatched CUDA, NCCL and torch libraries
+#[derive(Default, Debug)]
+pub struct LibraryChecksTable {}
+
+impl CustomTable for LibraryChecksTable {
+    fn name() -> &'static str {
+        "library_checks"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("level", DataType::Utf8, false),
+            Field::new("message", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let checks = check(&libraries());
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    checks.iter().map(|c| c.check),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    checks.iter().map(|c| c.level),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    checks.iter().map(|c| &c.message),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build library checks batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type LibraryChecksPlugin = TablePluginHelper<LibraryChecksTable>;
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct LibrariesExtension {}
+
+impl EngineCall for LibrariesExtension {}
+
+impl EngineDatasource for LibrariesExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some("library_checks") => {
+                Some(LibraryChecksPlugin::create(namespace, "library_checks"))
+            }
+            Some(name) => Some(LibrariesPlugin::create(namespace, name)),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lib(path: &str, package: Option<&str>, version: &str) -> Library {
+        Library {
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: path.to_string(),
+            package: package.map(str::to_string),
+            version: Some(version.to_string()),
+            build_id: Some("ab12".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_mapped_paths() {
+        let maps = "\
+7f0000000000-7f0000001000 r-xp 00000000 08:01 1 /usr/lib/libcudart.so.12.1.105
+7f0000001000-7f0000002000 r--p 00001000 08:01 1 /usr/lib/libcudart.so.12.1.105
+7f0000003000-7f0000004000 rw-p 00000000 00:00 0
+7f0000004000-7f0000005000 r-xp 00000000 08:01 2 /usr/bin/python3.10
+7ffd00000000-7ffd00001000 rw-p 00000000 00:00 0 [stack]";
+        assert_eq!(mapped_paths(maps), ["/usr/lib/libcudart.so.12.1.105"]);
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(
+            soname_version("libcudart.so.12.1.105").as_deref(),
+            Some("12.1.105")
+        );
+        assert_eq!(soname_version("libtorch.so"), None);
+        assert_eq!(torch_cuda("2.3.0+cu121"), Some((12, 1)));
+        assert_eq!(torch_cuda("2.3.0+cpu"), None);
+        assert_eq!(wheel_cuda("nvidia-nccl-cu12"), Some(12));
+        assert_eq!(
+            python_assignment(
+                "__version__ = '2.3.0+cu121'\ncuda: Optional[str] = '12.1'",
+                "cuda"
+            )
+            .as_deref(),
+            Some("12.1")
+        );
+        let library = lib("/x/libcudart-9335f6a2.so.12", None, "12");
+        assert_eq!(library.family(), "cudart");
+        assert_eq!(lib("/x/libc10_cuda.so", None, "1").family(), "c10_cuda");
+    }
+
+    #[test]
+    fn test_check() {
+        let libs = vec![
+            lib(
+                "/site/torch/lib/libtorch_cuda.so",
+                Some("torch"),
+                "2.3.0+cu121",
+            ),
+            lib(
+                "/site/nvidia/cuda_runtime/lib/libcudart.so.12",
+                Some("nvidia-cuda-runtime-cu12"),
+                "12.1.105",
+            ),
+            lib(
+                "/site/nvidia/nccl/lib/libnccl.so.2",
+                Some("nvidia-nccl-cu12"),
+                "2.20.5",
+            ),
+            lib("/usr/lib/libcuda.so.535.104.05", None, "535.104.05"),
+        ];
+        let checks = check(&libs);
+        assert_eq!(checks.len(), 3);
+        assert!(checks.iter().all(|c| c.level == "ok"));
+
+        let mut libs = libs;
+        libs.push(lib(
+            "/usr/local/cuda/lib64/libcudart.so.11.0",
+            None,
+            "11.0.221",
+        ));
+        libs[3].version = Some("470.82.01".to_string());
+        libs[2].package = Some("nvidia-nccl-cu11".to_string());
+        let checks = check(&libs);
+        let failed = checks
+            .iter()
+            .filter(|c| c.level == "fail")
+            .map(|c| c.check)
+            .collect::<Vec<_>>();
+        assert_eq!(failed, ["duplicates", "nccl_cuda", "driver"]);
+    }
+
+    #[test]
+    fn test_read_elf() {
+        let elf = Elf {
+            is64: true,
+            le: true,
+        };
+        let mut notes = vec![];
+        // an ABI tag note before the build-id
+        notes.extend([4u32, 16, 1].iter().flat_map(|n| n.to_le_bytes()));
+        notes.extend(b"GNU\0");
+        notes.extend([0u8; 16]);
+        notes.extend([4u32, 4, 3].iter().flat_map(|n| n.to_le_bytes()));
+        notes.extend(b"GNU\0");
+        notes.extend([0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(elf.build_id(&notes).as_deref(), Some("deadbeef"));
+        assert_eq!(elf.build_id(&notes[..40]), None);
+
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        assert_eq!(read_elf(&manifest).ok(), Some(ElfInfo::default()));
+        assert!(read_elf(&std::env::current_exe().unwrap()).is_ok());
+    }
+}
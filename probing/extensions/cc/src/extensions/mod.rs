@@ -3,12 +3,26 @@ pub mod taskstats;
 #[cfg(all(feature = "taskstats", not(target_os = "macos")))]
 pub use taskstats::TaskStatsExtension;
 
+pub mod agent;
+pub use agent::AgentExtension;
+
+pub mod cgroup;
+pub use cgroup::CgroupExtension;
+
 pub mod cluster;
 pub use cluster::ClusterExtension;
 
 pub mod envs;
 pub use envs::EnvExtension;
 
+pub mod exec;
+pub use exec::ExecExtension;
+
+#[cfg(any(feature = "postgres", feature = "clickhouse"))]
+pub mod federation;
+#[cfg(any(feature = "postgres", feature = "clickhouse"))]
+pub use federation::FederationExtension;
+
 pub mod files;
 pub use files::FilesExtension;
 
@@ -17,6 +31,18 @@ pub mod kmsg;
 #[cfg(feature = "kmsg")]
 pub use kmsg::KMsgExtension;
 
+pub mod libraries;
+pub use libraries::LibrariesExtension;
+
+pub mod slo;
+pub use slo::SloExtension;
+
+pub mod trace;
+pub use trace::TraceExtension;
+
+pub mod views;
+pub use views::ViewsExtension;
+
 #[cfg(not(target_os = "macos"))]
 pub mod rdma;
 #[cfg(not(target_os = "macos"))]
@@ -0,0 +1,152 @@
+//! Latency objectives of spans, declared as `slo.<span>` options.
+//!
+//! ```sql
+//! SET probing.slo.train_step = '250ms@99%';
+//! SELECT name, compliance, burn_rate, burn_rate_short FROM slo.status;
+//! ```
+//!
+//! See [`probing_core::trace::slo`] for the accounting and the alerts.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+
+use probing_core::core::{
+    CustomTable, EngineCall, EngineDatasource, EngineError, EngineExtension, EngineExtensionOption,
+    TablePluginHelper,
+};
+use probing_core::trace::slo;
+
+/// `slo.status`: compliance and burn rates of the declared objectives
+#[derive(Default, Debug)]
+pub struct SloStatusTable {}
+
+impl CustomTable for SloStatusTable {
+    fn name() -> &'static str {
+        "status"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("objective", DataType::Utf8, false),
+            Field::new("threshold_ms", DataType::Float64, false),
+            Field::new("target", DataType::Float64, false),
+            Field::new("window_s", DataType::Int64, false),
+            Field::new("total", DataType::Int64, false),
+            Field::new("bad", DataType::Int64, false),
+            Field::new("compliance", DataType::Float64, true),
+            Field::new("burn_rate", DataType::Float64, true),
+            Field::new("burn_rate_short", DataType::Float64, true),
+            Field::new("budget_remaining", DataType::Float64, true),
+            Field::new("burning", DataType::Boolean, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let rows = slo::status();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(rows.iter().map(|r| &r.name))),
+                Arc::new(StringArray::from_iter_values(
+                    rows.iter().map(|r| r.objective.to_string()),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter()
+                        .map(|r| r.objective.threshold.as_secs_f64() * 1e3),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    rows.iter().map(|r| r.objective.target * 100.0),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|r| r.objective.window.as_secs() as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|r| r.total as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    rows.iter().map(|r| r.bad as i64),
+                )),
+                Arc::new(Float64Array::from_iter(rows.iter().map(|r| r.compliance))),
+                Arc::new(Float64Array::from_iter(rows.iter().map(|r| r.burn_rate))),
+                Arc::new(Float64Array::from_iter(
+                    rows.iter().map(|r| r.burn_rate_short),
+                )),
+                Arc::new(Float64Array::from_iter(
+                    rows.iter().map(|r| r.budget_remaining),
+                )),
+                Arc::new(BooleanArray::from_iter(
+                    rows.iter().map(|r| Some(r.burning)),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build slo status batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type SloStatusPlugin = TablePluginHelper<SloStatusTable>;
+
+/// Objectives are keyed by span name, so the options are not fields and the
+/// extension is implemented without the derive macro
+#[derive(Debug, Default)]
+pub struct SloExtension {}
+
+impl EngineExtension for SloExtension {
+    fn name(&self) -> String {
+        "sloextension".to_string()
+    }
+
+    fn get(&self, key: &str) -> Result<String, EngineError> {
+        slo::objectives()
+            .into_iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, objective)| objective.to_string())
+            .ok_or_else(|| EngineError::UnsupportedOption(key.to_string()))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<String, EngineError> {
+        let old = slo::define(key, value).map_err(|e| {
+            EngineError::InvalidOptionValue(format!("slo.{key}"), format!("{value} ({e})"))
+        })?;
+        Ok(old
+            .map(|objective| objective.to_string())
+            .unwrap_or_default())
+    }
+
+    fn options(&self) -> Vec<EngineExtensionOption> {
+        slo::objectives()
+            .into_iter()
+            .map(|(name, objective)| EngineExtensionOption {
+                key: format!("slo.{name}"),
+                value: Some(objective.to_string()),
+                help: "Latency objective of the spans <name> as <threshold>@<percent>[/<window>], empty to remove it",
+            })
+            .collect()
+    }
+}
+
+impl EngineCall for SloExtension {}
+
+impl EngineDatasource for SloExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == SloStatusTable::name() => {
+                Some(SloStatusPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
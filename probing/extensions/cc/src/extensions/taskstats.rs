@@ -1,6 +1,5 @@
 use std::time::Duration;
 
-use datasrc::TaskStatsPlugin;
 use probing_core::core::EngineCall;
 use probing_core::core::EngineDatasource;
 use probing_core::core::EngineError;
@@ -19,16 +18,8 @@ pub struct TaskStatsExtension {
 
 impl EngineCall for TaskStatsExtension {}
 
-#[allow(unused)]
-impl EngineDatasource for TaskStatsExtension {
-    fn datasrc(
-        &self,
-        namespace: &str,
-        name: Option<&str>,
-    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
-        Some(TaskStatsPlugin::create(namespace))
-    }
-}
+// `process.cpu` is registered by the recorder of the worker
+impl EngineDatasource for TaskStatsExtension {}
 
 impl TaskStatsExtension {
     fn set_task_stats_interval(
@@ -7,17 +7,11 @@ use std::{
     time::Duration,
 };
 
-use anyhow::Result;
 use once_cell::sync::Lazy;
 use thiserror::Error;
 
-use probing_core::core::{
-    ArrayRef, CustomNamespace, DataType, Field, Float32Array, Float64Array, Int32Array, Int64Array,
-    NamespacePluginHelper, RecordBatch, Schema, SchemaRef, StringArray,
-};
-
-use probing_proto::prelude::{Ele, TimeSeries};
-use probing_proto::types; // Keep this for types::EleType
+use probing_core::recorder::TimeSeriesRecorder;
+use probing_proto::types::{Ele, EleType};
 
 #[allow(unused)]
 #[derive(Error, Debug)]
@@ -46,7 +40,7 @@ impl Default for TaskStatsConfig {
 
 pub struct TaskStatsWorker {
     running: Arc<AtomicBool>,
-    time_series: Arc<Mutex<TimeSeries>>,
+    recorder: Arc<TimeSeriesRecorder>,
     handle: Mutex<Option<JoinHandle<()>>>,
 }
 
@@ -54,11 +48,10 @@ impl TaskStatsWorker {
     pub fn instance() -> &'static Self {
         static INSTANCE: Lazy<TaskStatsWorker> = Lazy::new(|| TaskStatsWorker {
             running: Arc::new(AtomicBool::new(false)),
-            time_series: Arc::new(Mutex::new(
-                TimeSeries::builder()
-                    .with_columns(vec!["cpu_utime".to_string(), "cpu_stime".to_string()])
-                    .build(),
-            )),
+            recorder: TimeSeriesRecorder::builder("process", "cpu")
+                .with_column("cpu_utime", EleType::I64)
+                .with_column("cpu_stime", EleType::I64)
+                .build(),
             handle: Mutex::new(None),
         });
         &INSTANCE
@@ -70,7 +63,7 @@ impl TaskStatsWorker {
         }
 
         let running = self.running.clone();
-        let time_series = self.time_series.clone();
+        let recorder = self.recorder.clone();
 
         let handle = thread::spawn(move || {
             let task = match procfs::process::Process::myself() {
@@ -91,20 +84,11 @@ impl TaskStatsWorker {
                 }
 
                 if let Ok(stat) = task.stat() {
-                    let t = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_micros() as i64;
                     let cpu_utime: Ele = (stat.utime as i64).into();
                     let cpu_stime: Ele = (stat.stime as i64).into();
-                    match time_series
-                        .lock()
-                        .unwrap()
-                        .append(t.into(), vec![cpu_utime, cpu_stime])
-                    {
-                        Ok(_) => {}
-                        Err(e) => log::error!("Failed to append to time series: {e}"),
-                    };
+                    if let Err(e) = recorder.record(vec![cpu_utime, cpu_stime]) {
+                        log::error!("Failed to record task stats: {e}");
+                    }
                 }
                 thread::sleep(config.interval);
             }
@@ -128,158 +112,6 @@ impl TaskStatsWorker {
 
         Ok(())
     }
-
-    pub fn get_stats(&self) -> Result<TimeSeries, WorkerError> {
-        Ok(self.time_series.lock().unwrap().clone())
-    }
-}
-
-#[derive(Default, Debug)]
-pub struct TaskStatsSchema {}
-
-impl CustomNamespace for TaskStatsSchema {
-    fn name() -> &'static str {
-        "process"
-    }
-
-    fn list() -> Vec<String> {
-        vec!["cpu".to_string(), "memory".to_string(), "io".to_string()]
-    }
-
-    fn make_lazy(expr: &str) -> Arc<probing_core::core::LazyTableSource>
-    where
-        Self: Sized,
-    {
-        match expr {
-            "cpu" => Arc::new(probing_core::core::LazyTableSource {
-                name: expr.to_string(),
-                schema: Some(SchemaRef::new(Schema::new(vec![
-                    Field::new("timestamp", DataType::Int64, true),
-                    Field::new("cpu_utime", DataType::Int64, false),
-                    Field::new("cpu_stime", DataType::Int64, false),
-                ]))),
-                data: Self::data(expr),
-            }),
-            _ => Arc::new(probing_core::core::LazyTableSource {
-                name: expr.to_string(),
-                schema: None,
-                data: Default::default(),
-            }),
-        }
-    }
-
-    fn data(expr: &str) -> Vec<RecordBatch> {
-        match expr {
-            "cpu" => {
-                let time_series = TaskStatsWorker::instance().get_stats().unwrap();
-                let names = time_series.names.clone();
-                let batches = time_series_to_recordbatch(names, &time_series);
-
-                if let Ok(batches) = batches {
-                    batches
-                } else {
-                    log::error!("error convert time series to table: {:?}", batches.err());
-                    vec![]
-                }
-            }
-            _ => vec![],
-        }
-    }
-}
-
-pub type TaskStatsPlugin = NamespacePluginHelper<TaskStatsSchema>;
-
-pub fn time_series_to_recordbatch(names: Vec<String>, ts: &TimeSeries) -> Result<Vec<RecordBatch>> {
-    let mut fields: Vec<Field> = vec![];
-    let mut columns: Vec<ArrayRef> = vec![];
-
-    fields.push(Field::new("timestamp", DataType::Int64, true));
-    names.iter().zip(ts.cols.iter()).for_each(|(name, col)| {
-        let data_type = match col.dtype() {
-            types::EleType::I64 => DataType::Int64,
-            types::EleType::F64 => DataType::Float64,
-            types::EleType::I32 => DataType::Int32,
-            types::EleType::F32 => DataType::Float32,
-            _ => DataType::Utf8,
-        };
-        fields.push(Field::new(name, data_type, false));
-    });
-
-    let length = ts.len();
-
-    let timeseries = ts
-        .timestamp
-        .iter()
-        .take(length)
-        .map(|x| match x {
-            Ele::I64(x) => x,
-            _ => 0,
-        })
-        .collect::<Vec<_>>();
-    columns.push(Arc::new(Int64Array::from(timeseries)));
-
-    for col in ts.cols.iter() {
-        let col = match col.dtype() {
-            types::EleType::I64 => Arc::new(Int64Array::from(
-                col.iter()
-                    .take(length)
-                    .map(|x| match x {
-                        Ele::I64(x) => x,
-                        _ => 0,
-                    })
-                    .collect::<Vec<_>>(),
-            )) as ArrayRef,
-            types::EleType::F64 => Arc::new(Float64Array::from(
-                col.iter()
-                    .take(length)
-                    .map(|x| match x {
-                        Ele::F64(x) => x,
-                        _ => 0.0,
-                    })
-                    .collect::<Vec<_>>(),
-            )) as ArrayRef,
-            types::EleType::I32 => Arc::new(Int32Array::from(
-                col.iter()
-                    .take(length)
-                    .map(|x| match x {
-                        Ele::I32(x) => x,
-                        _ => 0,
-                    })
-                    .collect::<Vec<_>>(),
-            )) as ArrayRef,
-            types::EleType::F32 => Arc::new(Float32Array::from(
-                col.iter()
-                    .take(length)
-                    .map(|x| match x {
-                        Ele::F32(x) => x,
-                        _ => 0.0,
-                    })
-                    .collect::<Vec<_>>(),
-            )) as ArrayRef,
-            types::EleType::Text => Arc::new(StringArray::from(
-                col.iter()
-                    .take(length)
-                    .map(|x| match x {
-                        Ele::Text(x) => x,
-                        _ => x.to_string(),
-                    })
-                    .collect::<Vec<_>>(),
-            )) as ArrayRef,
-            _ => Arc::new(StringArray::from(
-                col.iter()
-                    .take(length)
-                    .map(|x| x.to_string())
-                    .collect::<Vec<_>>(),
-            )) as ArrayRef,
-        };
-
-        columns.push(col);
-    }
-
-    Ok(vec![RecordBatch::try_new(
-        SchemaRef::new(Schema::new(fields)),
-        columns,
-    )?])
 }
 
 #[cfg(test)]
@@ -296,7 +128,7 @@ mod test {
             .unwrap();
 
         std::thread::sleep(std::time::Duration::from_secs(2));
-        let length = TaskStatsWorker::instance().get_stats().unwrap().len();
+        let length = TaskStatsWorker::instance().recorder.len();
 
         assert_eq!(length, 1000);
     }
@@ -0,0 +1,57 @@
+use probing_core::core::{
+    set_computed_columns, CustomTable, EngineCall, EngineDatasource, EngineError, EngineExtension,
+    EngineExtensionOption, Maybe,
+};
+use probing_core::trace::{StringsPlugin, StringsTable};
+
+/// Union view of the live and archived trace events the options apply to
+pub const EVENTS_VIEW: &str = "trace.all_events";
+
+/// Options of the trace tables, serving `trace.strings`
+#[derive(Debug, Default, EngineExtension)]
+pub struct TraceExtension {
+    /// Computed columns of `trace.all_events` separated by `;`, e.g.
+    /// `time_ms = time / 1e6; module = split_part(name, '.', 1)`
+    #[option(aliases=["computed.columns"])]
+    columns: Maybe<String>,
+
+    /// Columns listed first in `trace.all_events`, separated by `,`
+    #[option(aliases=["column.order"])]
+    column_order: Maybe<String>,
+}
+
+impl TraceExtension {
+    fn set_columns(&mut self, columns: Maybe<String>) -> Result<(), EngineError> {
+        let spec: String = columns.clone().into();
+        set_computed_columns(EVENTS_VIEW, &spec).map_err(|e| {
+            log::error!("Failed to set trace columns '{spec}': {e}");
+            EngineError::InvalidOptionValue(Self::OPTION_COLUMNS.to_string(), spec.clone())
+        })?;
+        self.columns = columns;
+        Ok(())
+    }
+
+    fn set_column_order(&mut self, column_order: Maybe<String>) -> Result<(), EngineError> {
+        let columns: String = column_order.clone().into();
+        probing_core::core::set_column_order(EVENTS_VIEW, &columns);
+        self.column_order = column_order;
+        Ok(())
+    }
+}
+
+impl EngineCall for TraceExtension {}
+
+impl EngineDatasource for TraceExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == StringsTable::name() => {
+                Some(StringsPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Materialized views defined through an option.
+//!
+//! Dashboards polling an aggregation over a growing table recompute it on
+//! every poll. `views.materialized` defines views whose results are kept in
+//! `views.<name>` and refreshed on an interval instead, incrementally for
+//! append-only sources, see [`probing_core::core::materialized`]:
+//!
+//! ```sql
+//! SET probing.views.materialized = '[{
+//!     "name": "loss_by_rank",
+//!     "query": "SELECT rank, count(*) AS steps, sum(loss) AS loss_sum FROM delta GROUP BY rank",
+//!     "source": "python.trainer_steps",
+//!     "watermark": "step",
+//!     "merge": {"steps": "sum", "loss_sum": "sum"},
+//!     "interval": 5000
+//! }]';
+//! SELECT rank, loss_sum / steps AS loss FROM views.loss_by_rank;
+//! ```
+//!
+//! Setting the option replaces every view, and an empty value drops them all.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::Value;
+
+use probing_core::core::materialized::{MaterializedView, Merge, DEFAULT_CAPACITY};
+use probing_core::core::{
+    EngineCall, EngineDatasource, EngineError, EngineExtension, EngineExtensionOption, Maybe,
+};
+use probing_core::journal::{self, EntryKind};
+use probing_core::ENGINE;
+
+/// Milliseconds between two refreshes of a view without an explicit interval
+pub const DEFAULT_INTERVAL: u64 = 10_000;
+
+/// A view as declared in `views.materialized`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ViewSpec {
+    pub name: String,
+    pub query: String,
+    /// Table whose new rows the query reads as `delta`
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Growing column of the source
+    #[serde(default)]
+    pub watermark: Option<String>,
+    /// `sum`, `min` or `max` of the columns merged into the view
+    #[serde(default)]
+    pub merge: BTreeMap<String, String>,
+    /// Milliseconds between two refreshes
+    #[serde(default)]
+    pub interval: Option<u64>,
+    /// Rows kept by an appending view
+    #[serde(default)]
+    pub capacity: Option<usize>,
+}
+
+impl TryFrom<ViewSpec> for MaterializedView {
+    type Error = String;
+
+    fn try_from(spec: ViewSpec) -> Result<Self, Self::Error> {
+        let name = spec.name.trim().to_string();
+        let merge = spec
+            .merge
+            .into_iter()
+            .map(|(column, merge)| {
+                let merge = merge.parse::<Merge>().map_err(|e| format!("{name}: {e}"))?;
+                Ok((column, merge))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let view = MaterializedView {
+            name,
+            query: spec.query,
+            source: spec.source.filter(|s| !s.trim().is_empty()),
+            watermark: spec.watermark.filter(|w| !w.trim().is_empty()),
+            merge,
+            interval: Duration::from_millis(spec.interval.unwrap_or(DEFAULT_INTERVAL)),
+            capacity: spec.capacity.unwrap_or(DEFAULT_CAPACITY).max(1),
+        };
+        view.validate()?;
+        Ok(view)
+    }
+}
+
+/// Parse the value of `views.materialized`: a JSON array of views, or a
+/// single view
+pub fn parse_views(value: &str) -> Result<Vec<MaterializedView>, String> {
+    if value.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let specs: Vec<ViewSpec> = match serde_json::from_str::<Value>(value) {
+        Ok(Value::Array(_)) => serde_json::from_str(value),
+        Ok(_) => serde_json::from_str(value).map(|spec| vec![spec]),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let mut views: Vec<MaterializedView> = vec![];
+    for spec in specs {
+        let view = MaterializedView::try_from(spec)?;
+        if views.iter().any(|v| v.name == view.name) {
+            return Err(format!("duplicated view {}", view.name));
+        }
+        views.push(view);
+    }
+    Ok(views)
+}
+
+/// A view refreshed by its own thread
+struct Worker {
+    name: String,
+    running: Arc<AtomicBool>,
+}
+
+static WORKERS: Lazy<Mutex<Vec<Worker>>> = Lazy::new(Default::default);
+
+/// Replace the defined views with `views`
+fn start(views: Vec<MaterializedView>) {
+    let mut workers = WORKERS.lock().unwrap_or_else(|e| e.into_inner());
+    for worker in workers.drain(..) {
+        worker.running.store(false, Ordering::SeqCst);
+    }
+    let names: Vec<String> = views.iter().map(|view| view.name.clone()).collect();
+    let spawned = thread::Builder::new()
+        .name("probing-views-drop".to_string())
+        .spawn(move || drop_views_except(names));
+    if let Err(e) = spawned {
+        log::error!("Failed to drop materialized views: {e}");
+    }
+    for view in views {
+        let running = Arc::new(AtomicBool::new(true));
+        let worker = Worker {
+            name: view.name.clone(),
+            running: running.clone(),
+        };
+        let spawned = thread::Builder::new()
+            .name(format!("probing-view-{}", view.name))
+            .spawn(move || run_view(view, running));
+        match spawned {
+            Ok(_) => workers.push(worker),
+            Err(e) => log::error!("Failed to start materialized view {}: {e}", worker.name),
+        }
+    }
+}
+
+/// Drop the views that are no longer defined
+fn drop_views_except(names: Vec<String>) {
+    // the option is set under the engine lock
+    let engine = ENGINE.blocking_read();
+    for view in engine.materialized_views() {
+        if !names.contains(&view.name) {
+            if let Err(e) = engine.drop_materialized_view(&view.name) {
+                log::warn!("Failed to drop materialized view {}: {e}", view.name);
+            }
+        }
+    }
+}
+
+fn run_view(view: MaterializedView, running: Arc<AtomicBool>) {
+    let table = format!("views.{}", view.name);
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Failed to create runtime for {table}: {e}");
+            return;
+        }
+    };
+
+    // the option is set under the engine lock, the view is defined once it
+    // is released
+    let interval = view.interval;
+    let defined = runtime.block_on(async {
+        ENGINE
+            .read()
+            .await
+            .define_materialized_view(view.clone())
+            .await
+    });
+    if let Err(e) = defined {
+        log::warn!("Failed to define {table}: {e}");
+        journal::record(EntryKind::Extension, &table, e.to_string());
+        return;
+    }
+
+    while running.load(Ordering::SeqCst) {
+        let refreshed = runtime.block_on(async {
+            ENGINE
+                .read()
+                .await
+                .refresh_materialized_view(&view.name)
+                .await
+        });
+        match refreshed {
+            Ok(rows) => log::debug!("{table} refreshed, {rows} rows"),
+            Err(e) => {
+                log::warn!("Failed to refresh {table}: {e}");
+                journal::record(EntryKind::Extension, &table, e.to_string());
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct ViewsExtension {
+    /// JSON list of the views kept in `views.<name>`, each with a name, query,
+    /// interval and optionally a source, watermark and merge columns
+    #[option]
+    materialized: Maybe<String>,
+}
+
+impl ViewsExtension {
+    fn set_materialized(&mut self, materialized: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = materialized.clone().into();
+        let views = parse_views(&value).map_err(|e| {
+            EngineError::InvalidOptionValue(
+                Self::OPTION_MATERIALIZED.to_string(),
+                format!("{value} ({e})"),
+            )
+        })?;
+
+        let modes = views
+            .iter()
+            .map(|view| format!("{}={}", view.name, view.mode().as_str()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        log::info!("materialized views changed: [{modes}]");
+
+        start(views);
+        self.materialized = materialized;
+        Ok(())
+    }
+}
+
+impl EngineCall for ViewsExtension {}
+
+// `views.<name>` is registered by the engine when a view is defined
+impl EngineDatasource for ViewsExtension {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use probing_core::core::materialized::Mode;
+
+    #[test]
+    fn test_parse_views() {
+        let views = parse_views(
+            r#"{"name": "loss", "query": "SELECT rank, count(*) AS n FROM delta GROUP BY rank",
+                "source": "python.trainer_steps", "watermark": "step",
+                "merge": {"n": "count"}, "interval": 1000}"#,
+        )
+        .unwrap();
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].mode(), Mode::Merge);
+        assert_eq!(views[0].merge, [("n".to_string(), Merge::Sum)]);
+        assert_eq!(views[0].interval, Duration::from_millis(1000));
+
+        let views = parse_views(r#"[{"name": "a", "query": "SELECT 1"}]"#).unwrap();
+        assert_eq!(views[0].mode(), Mode::Full);
+        assert_eq!(views[0].interval, Duration::from_millis(DEFAULT_INTERVAL));
+
+        assert!(parse_views("").unwrap().is_empty());
+        assert!(parse_views(r#"{"name": "a.b", "query": "SELECT 1"}"#).is_err());
+        assert!(parse_views(r#"{"name": "a", "query": "SELECT 1", "source": "t"}"#).is_err());
+        assert!(parse_views(
+            r#"{"name": "a", "query": "SELECT 1", "source": "t", "watermark": "w",
+                "merge": {"n": "avg"}}"#
+        )
+        .is_err());
+        assert!(parse_views(
+            r#"[{"name": "a", "query": "SELECT 1"}, {"name": "a", "query": "SELECT 2"}]"#
+        )
+        .is_err());
+    }
+}
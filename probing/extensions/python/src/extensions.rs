@@ -1,7 +1,49 @@
+mod anomaly;
+#[cfg(feature = "analytics")]
+mod dynamo;
+#[cfg(feature = "analytics")]
+mod fsdp;
+#[cfg(feature = "analytics")]
+mod grad_stats;
+#[cfg(feature = "analytics")]
+mod inference;
+mod ingest;
+#[cfg(feature = "analytics")]
+mod kineto;
+#[cfg(feature = "analytics")]
+mod oom;
 mod pprof;
+mod privacy;
 pub mod python;
+mod shutdown;
+mod signals;
+#[cfg(feature = "analytics")]
+mod tasks;
 mod torch;
+mod tracer;
+mod tracing;
 
+pub use anomaly::AnomalyExtension;
+#[cfg(feature = "analytics")]
+pub use dynamo::DynamoExtension;
+#[cfg(feature = "analytics")]
+pub use fsdp::FsdpExtension;
+#[cfg(feature = "analytics")]
+pub use grad_stats::GradStatsExtension;
+#[cfg(feature = "analytics")]
+pub use inference::InferenceExtension;
+pub use ingest::IngestExtension;
+#[cfg(feature = "analytics")]
+pub use kineto::KinetoExtension;
+#[cfg(feature = "analytics")]
+pub use oom::OomExtension;
 pub use pprof::PprofExtension;
+pub use privacy::PrivacyExtension;
 pub use python::PythonExt;
+pub use shutdown::ShutdownExtension;
+pub use signals::SignalsExtension;
+#[cfg(feature = "analytics")]
+pub use tasks::TasksExtension;
 pub use torch::TorchExtension;
+pub use tracer::TracerExtension;
+pub use tracing::TracingExtension;
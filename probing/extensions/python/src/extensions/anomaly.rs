@@ -0,0 +1,46 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
+
+use crate::features::anomaly::{AnomaliesPlugin, AnomaliesTable};
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct AnomalyExtension {
+    /// Anomaly rules, e.g. `metrics.loss:spike,metrics.grad_norm:explode:20`
+    #[option]
+    watch: Maybe<String>,
+}
+
+impl EngineCall for AnomalyExtension {}
+
+impl EngineDatasource for AnomalyExtension {
+    /// Serve the `anomalies` found by the configured rules
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == AnomaliesTable::name() => {
+                Some(AnomaliesPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl AnomalyExtension {
+    fn set_watch(&mut self, watch: Maybe<String>) -> Result<(), EngineError> {
+        let spec: String = watch.clone().into();
+        crate::features::anomaly::configure(&spec).map_err(|e| {
+            log::error!("Failed to configure anomaly rules '{spec}': {e}");
+            EngineError::InvalidOptionValue(Self::OPTION_WATCH.to_string(), spec.clone())
+        })?;
+        self.watch = watch;
+        Ok(())
+    }
+}
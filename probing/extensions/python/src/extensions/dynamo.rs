@@ -0,0 +1,29 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+
+use crate::features::dynamo::{DynamoPlugin, DynamoTable};
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct DynamoExtension {}
+
+impl EngineCall for DynamoExtension {}
+
+impl EngineDatasource for DynamoExtension {
+    /// Serve the `dynamo` diagnostics of `torch.compile`
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == DynamoTable::name() => {
+                Some(DynamoPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
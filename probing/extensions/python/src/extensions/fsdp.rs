@@ -0,0 +1,39 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+
+use crate::features::fsdp::{
+    FsdpCollectivesPlugin, FsdpCollectivesTable, FsdpParamsPlugin, FsdpParamsTable,
+    FsdpUnitsPlugin, FsdpUnitsTable,
+};
+
+/// Serves the `fsdp_*` tables; registered once per table since an
+/// extension provides a single data source
+#[derive(Debug, Default, EngineExtension)]
+pub struct FsdpExtension {}
+
+impl EngineCall for FsdpExtension {}
+
+impl EngineDatasource for FsdpExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == FsdpUnitsTable::name() => {
+                Some(FsdpUnitsPlugin::create(namespace, name))
+            }
+            Some(name) if name == FsdpParamsTable::name() => {
+                Some(FsdpParamsPlugin::create(namespace, name))
+            }
+            Some(name) if name == FsdpCollectivesTable::name() => {
+                Some(FsdpCollectivesPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+
+use crate::features::grad_stats::{GradStatsPlugin, GradStatsTable};
+
+/// Serves `torch.grad_stats`; recording is switched by `torch.grad_stats`
+/// of [`super::TorchExtension`]
+#[derive(Debug, Default, EngineExtension)]
+pub struct GradStatsExtension {}
+
+impl EngineCall for GradStatsExtension {}
+
+impl EngineDatasource for GradStatsExtension {
+    /// Serve the gradient statistics of each parameter group
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == GradStatsTable::name() => {
+                Some(GradStatsPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+
+use crate::features::inference::{KvCachePlugin, KvCacheTable, RequestsPlugin, RequestsTable};
+
+/// Serves the `inference` tables; registered once per table since an
+/// extension provides a single data source
+#[derive(Debug, Default, EngineExtension)]
+pub struct InferenceExtension {}
+
+impl EngineCall for InferenceExtension {}
+
+impl EngineDatasource for InferenceExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == KvCacheTable::name() => {
+                Some(KvCachePlugin::create(namespace, name))
+            }
+            Some(name) if name == RequestsTable::name() => {
+                Some(RequestsPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
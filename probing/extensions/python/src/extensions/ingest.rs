@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
+
+use crate::features::ingest::{self, IngestStatsPlugin, IngestStatsTable};
+
+#[derive(Debug, EngineExtension)]
+pub struct IngestExtension {
+    /// Policy of full tables, e.g. `drop-newest,trace_event=block`
+    #[option]
+    policy: Maybe<String>,
+
+    /// Rows a table holds before `drop-newest` and `block` apply
+    #[option]
+    capacity: Maybe<u64>,
+
+    /// Longest wait of an append to a full `block` table, in milliseconds
+    #[option(aliases=["block.timeout.ms"])]
+    block_timeout_ms: Maybe<u64>,
+}
+
+impl Default for IngestExtension {
+    fn default() -> Self {
+        Self {
+            policy: Maybe::Just(ingest::IngestPolicy::default().as_str().to_string()),
+            capacity: Maybe::Just(ingest::DEFAULT_CAPACITY as u64),
+            block_timeout_ms: Maybe::Just(ingest::DEFAULT_BLOCK_TIMEOUT_MS),
+        }
+    }
+}
+
+impl EngineCall for IngestExtension {}
+
+impl EngineDatasource for IngestExtension {
+    /// Serve the `stats` of appended and dropped rows
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == IngestStatsTable::name() => {
+                Some(IngestStatsPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl IngestExtension {
+    fn set_policy(&mut self, policy: Maybe<String>) -> Result<(), EngineError> {
+        let spec: String = policy.clone().into();
+        ingest::set_policies(&spec).map_err(|e| {
+            log::error!("Failed to set ingest policy '{spec}': {e}");
+            EngineError::InvalidOptionValue(Self::OPTION_POLICY.to_string(), spec.clone())
+        })?;
+        self.policy = policy;
+        Ok(())
+    }
+
+    fn set_capacity(&mut self, capacity: Maybe<u64>) -> Result<(), EngineError> {
+        let rows = match &capacity {
+            Maybe::Just(rows) => *rows as usize,
+            Maybe::Nothing => ingest::DEFAULT_CAPACITY,
+        };
+        ingest::set_capacity(rows).map_err(|e| {
+            log::error!("Failed to set ingest capacity {rows}: {e}");
+            EngineError::InvalidOptionValue(Self::OPTION_CAPACITY.to_string(), rows.to_string())
+        })?;
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    fn set_block_timeout_ms(&mut self, timeout: Maybe<u64>) -> Result<(), EngineError> {
+        let ms = match &timeout {
+            Maybe::Just(ms) => *ms,
+            Maybe::Nothing => ingest::DEFAULT_BLOCK_TIMEOUT_MS,
+        };
+        ingest::set_block_timeout(Duration::from_millis(ms));
+        self.block_timeout_ms = timeout;
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+
+use crate::features::kineto::{KinetoPlugin, KinetoTable};
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct KinetoExtension {}
+
+impl EngineCall for KinetoExtension {}
+
+impl EngineDatasource for KinetoExtension {
+    /// Serve the `events` of the loaded `torch.profiler` traces
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == KinetoTable::name() => {
+                Some(KinetoPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
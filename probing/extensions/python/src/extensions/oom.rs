@@ -0,0 +1,27 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+
+use crate::features::oom::{OomPlugin, OomTable};
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct OomExtension {}
+
+impl EngineCall for OomExtension {}
+
+impl EngineDatasource for OomExtension {
+    /// Serve the `reports` captured on CUDA out of memory errors
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == OomTable::name() => Some(OomPlugin::create(namespace, name)),
+            _ => None,
+        }
+    }
+}
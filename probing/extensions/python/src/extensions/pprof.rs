@@ -17,34 +17,28 @@ impl EngineCall for PprofExtension {}
 impl EngineDatasource for PprofExtension {}
 
 impl PprofExtension {
+    /// Start sampling at the given frequency, or stop it when the value is empty
     fn set_sample_freq(&mut self, pprof_sample_freq: Maybe<i32>) -> Result<(), EngineError> {
-        match self.sample_freq {
-            Maybe::Just(_) => Err(EngineError::InvalidOptionValue(
+        match pprof_sample_freq {
+            Maybe::Nothing => {
+                crate::features::pprof::stop();
+                self.sample_freq = Maybe::Nothing;
+                Ok(())
+            }
+            Maybe::Just(freq) if freq < 1 => Err(EngineError::InvalidOptionValue(
                 Self::OPTION_SAMPLE_FREQ.to_string(),
                 pprof_sample_freq.clone().into(),
             )),
-            Maybe::Nothing => match pprof_sample_freq {
-                Maybe::Nothing => Err(EngineError::InvalidOptionValue(
-                    Self::OPTION_SAMPLE_FREQ.to_string(),
-                    pprof_sample_freq.clone().into(),
-                )),
-                Maybe::Just(freq) => {
-                    if freq < 1 {
-                        return Err(EngineError::InvalidOptionValue(
-                            Self::OPTION_SAMPLE_FREQ.to_string(),
-                            pprof_sample_freq.clone().into(),
-                        ));
-                    }
-                    self.sample_freq = pprof_sample_freq.clone();
-                    crate::features::pprof::setup(freq as u64).map_err(|e| {
-                        EngineError::InvalidOptionValue(
-                            Self::OPTION_SAMPLE_FREQ.to_string(),
-                            e.to_string(),
-                        )
-                    })?;
-                    Ok(())
-                }
-            },
+            Maybe::Just(freq) => {
+                crate::features::pprof::setup(freq as u64).map_err(|e| {
+                    EngineError::InvalidOptionValue(
+                        Self::OPTION_SAMPLE_FREQ.to_string(),
+                        e.to_string(),
+                    )
+                })?;
+                self.sample_freq = pprof_sample_freq;
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,29 @@
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct PrivacyExtension {
+    /// Redaction rules separated by `;`, e.g. `key:*email*;\d{3}-\d{2}-\d{4}`
+    #[option(aliases=["redact.patterns"])]
+    redact_patterns: Maybe<String>,
+}
+
+impl EngineCall for PrivacyExtension {}
+
+impl EngineDatasource for PrivacyExtension {}
+
+impl PrivacyExtension {
+    fn set_redact_patterns(&mut self, patterns: Maybe<String>) -> Result<(), EngineError> {
+        let spec: String = patterns.clone().into();
+        crate::features::privacy::configure(&spec).map_err(|e| {
+            log::error!("Failed to configure redact patterns '{spec}': {e}");
+            EngineError::InvalidOptionValue(Self::OPTION_REDACT_PATTERNS.to_string(), spec.clone())
+        })?;
+        self.redact_patterns = patterns;
+        Ok(())
+    }
+}
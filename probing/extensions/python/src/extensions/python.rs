@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 
+use probing_core::core::set_call_metadata;
 use probing_core::core::EngineCall;
 use probing_core::core::EngineDatasource;
 use probing_core::core::EngineError;
@@ -15,16 +17,25 @@ use pyo3::prelude::*;
 use pyo3::types::{PyAnyMethods, PyString};
 use pyo3::Python;
 
+pub use exttbls::shrink_tables;
 pub use exttbls::ExternalTable;
 pub use exttbls::PyExternalTableConfig;
+pub use exttbls::SchemaError;
+pub use exttbls::EXTERN_TABLES;
 pub use tbls::PythonPlugin;
 
+use crate::features::eval_jobs;
+use crate::features::gil;
+use crate::features::safepoint::SAFEPOINT;
 use crate::features::stack_tracer::{SignalTracer, StackTracer};
+use crate::features::symbolizer::SYMBOLIZER;
 use crate::python::enable_crash_handler;
 use crate::python::enable_monitoring;
 use crate::python::CRASH_HANDLER;
+use crate::repl::sessions;
 use crate::repl::PythonRepl;
 
+mod extsrc;
 /// Define a static Mutex for the backtrace function
 mod exttbls;
 mod stack;
@@ -71,6 +82,19 @@ pub struct PythonExt {
     #[option()]
     disabled: Maybe<String>,
 
+    /// Remote symbol server for native frames without local debug info
+    #[option(aliases = ["symbol.server"])]
+    symbol_server: Maybe<String>,
+
+    /// Milliseconds to wait for the GIL before serving cached data, 0 waits forever
+    #[option(aliases = ["gil.timeout.ms"])]
+    gil_timeout_ms: Maybe<u64>,
+
+    /// Partitions external table scans are split into, empty follows
+    /// `datafusion.execution.target_partitions`
+    #[option(aliases = ["scan.partitions"])]
+    scan_partitions: Maybe<u64>,
+
     tracer: Box<dyn StackTracer>,
 }
 
@@ -81,6 +105,9 @@ impl Default for PythonExt {
             monitoring: Default::default(),
             enabled: Default::default(),
             disabled: Default::default(),
+            symbol_server: Default::default(),
+            gil_timeout_ms: Maybe::Just(gil::DEFAULT_GIL_TIMEOUT_MS),
+            scan_partitions: Default::default(),
             tracer: Box::new(SignalTracer),
         }
     }
@@ -103,11 +130,66 @@ impl EngineCall for PythonExt {
 
         let normalized_path = path.trim_start_matches('/');
 
+        // Safe-point control must not wait for the GIL, which the paused main thread holds
+        if normalized_path == "pause" {
+            return self.handle_pause(params);
+        }
+        if normalized_path == "resume" {
+            return to_json(&SAFEPOINT.resume());
+        }
+        if normalized_path == "paused" {
+            return to_json(&SAFEPOINT.state());
+        }
+        // Pruning only touches the Rust tables, so it works while paused
+        if normalized_path == "trace/prune" {
+            return self.handle_trace_prune(params);
+        }
+        // REPL sessions are tracked in Rust and can be listed while paused
+        if normalized_path == "repl/sessions" {
+            return to_json(&sessions::sessions());
+        }
+        if normalized_path == "repl/sessions/close" {
+            let result = self.handle_close_session(params);
+            // the temporary tables staged from the session go with it
+            if let (Ok(_), Some(name)) = (&result, params.get("name")) {
+                probing_core::ENGINE.read().await.close_session(name);
+            }
+            return result;
+        }
+
+        // Eval jobs are tracked in Rust, starting and polling them never waits for the GIL
+        if let Some(action) = normalized_path.strip_prefix("eval/") {
+            return self.handle_eval_job(action, params, body);
+        }
+
         // Try Python extension handlers first - router will handle routing automatically
-        if let Ok(result_bytes) = call_python_handler(&normalized_path, params) {
-            // Check if this is a "No handler found" error from Python router
-            if !is_no_handler_found_error(&result_bytes) {
-                return Ok(result_bytes);
+        if SAFEPOINT.state().paused {
+            log::debug!("Process paused, skipping Python handlers");
+        } else {
+            let (path, args) = (normalized_path.to_string(), params.clone());
+            match gil::with_gil_timeout(move |py| call_python_handler(py, &path, &args)) {
+                Ok((result, waited)) => {
+                    set_call_metadata("gil-wait-ms", waited.as_millis());
+                    // Check if this is a "No handler found" error from Python router
+                    if let Ok(result_bytes) = result {
+                        if !is_no_handler_found_error(&result_bytes) {
+                            gil::RESPONSES.store(normalized_path, params, &result_bytes);
+                            return Ok(result_bytes);
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::warn!("Python handler for {normalized_path} skipped: {err}");
+                    set_call_metadata("gil-wait-ms", err.waited.as_millis());
+                    if let Some((cached, age)) = gil::RESPONSES.get(normalized_path, params) {
+                        set_call_metadata("stale-ms", age.as_millis());
+                        return Ok(cached);
+                    }
+                    // the signal tracer serves call stacks without the GIL
+                    if normalized_path != "callstack" {
+                        return Err(EngineError::PluginError(err.to_string()));
+                    }
+                }
             }
         }
 
@@ -116,7 +198,7 @@ impl EngineCall for PythonExt {
             return self.handle_callstack(params);
         }
         if normalized_path == "eval" {
-            return self.handle_eval(body);
+            return self.handle_eval(params, body);
         }
         if normalized_path == "flamegraph" {
             return Ok(crate::features::torch::flamegraph().into_bytes());
@@ -165,8 +247,63 @@ impl PythonExt {
         })
     }
 
-    /// Handle eval request
-    fn handle_eval(&self, body: &[u8]) -> Result<Vec<u8>, EngineError> {
+    /// Handle pause request, `duration` (seconds) bounds how long the process stays paused
+    fn handle_pause(&self, params: &HashMap<String, String>) -> Result<Vec<u8>, EngineError> {
+        let duration = match params.get("duration") {
+            Some(secs) => Some(Duration::from_secs_f64(secs.parse().map_err(|_| {
+                EngineError::PluginError(format!("Invalid duration: {secs}"))
+            })?)),
+            None => None,
+        };
+        let state = SAFEPOINT.pause(duration).map_err(|e| {
+            log::error!("Failed to pause: {e}");
+            EngineError::PluginError(format!("Failed to pause: {e}"))
+        })?;
+        to_json(&state)
+    }
+
+    /// Handle trace prune request, dropping matching rows from an external table
+    fn handle_trace_prune(&self, params: &HashMap<String, String>) -> Result<Vec<u8>, EngineError> {
+        let table = params
+            .get("table")
+            .map(String::as_str)
+            .unwrap_or(exttbls::DEFAULT_PRUNE_TABLE);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros() as i64;
+        let filter =
+            exttbls::PruneFilter::from_params(params, now).map_err(EngineError::PluginError)?;
+        let (removed, remaining) =
+            exttbls::prune(table, &filter).map_err(EngineError::PluginError)?;
+        log::info!("Pruned {removed} rows from {table} with {filter:?}, {remaining} left");
+        to_json(&serde_json::json!({
+            "table": table,
+            "removed": removed,
+            "remaining": remaining,
+        }))
+    }
+
+    /// Handle close session request, `name` selects the REPL session to close
+    fn handle_close_session(
+        &self,
+        params: &HashMap<String, String>,
+    ) -> Result<Vec<u8>, EngineError> {
+        let name = params
+            .get("name")
+            .ok_or_else(|| EngineError::PluginError("missing session name".to_string()))?;
+        let session = sessions::close(name)
+            .ok_or_else(|| EngineError::PluginError(format!("session not found: {name}")))?;
+        to_json(&session)
+    }
+
+    /// Handle eval request, code runs in the REPL session named by `session`
+    /// or in the main namespace without it
+    fn handle_eval(
+        &self,
+        params: &HashMap<String, String>,
+        body: &[u8],
+    ) -> Result<Vec<u8>, EngineError> {
         let code = String::from_utf8(body.to_vec()).map_err(|e| {
             log::error!("Failed to convert body to UTF-8 string: {e}");
             EngineError::PluginError(format!("Failed to convert body to UTF-8 string: {e}"))
@@ -174,8 +311,78 @@ impl PythonExt {
 
         log::debug!("Python eval code: {code}");
 
-        let mut repl = PythonRepl::default();
-        Ok(repl.process(code.as_str()).unwrap_or_default().into_bytes())
+        if SAFEPOINT.state().paused {
+            return Err(EngineError::PluginError(
+                "process is paused, resume it before evaluating code".to_string(),
+            ));
+        }
+
+        let session = params.get("session").cloned();
+        let (output, waited) = gil::with_gil_timeout(move |_| {
+            let mut repl = match session {
+                Some(session) => PythonRepl::attach(&session)?,
+                None => PythonRepl::default(),
+            };
+            Ok::<_, String>(repl.process(code.as_str()).unwrap_or_default())
+        })
+        .map_err(|e| EngineError::PluginError(e.to_string()))?;
+        set_call_metadata("gil-wait-ms", waited.as_millis());
+        Ok(output.map_err(EngineError::PluginError)?.into_bytes())
+    }
+
+    /// Handle eval job requests: `start` runs the code of the body in a new
+    /// thread, `job` polls the output of job `id` from byte `offset` on,
+    /// `cancel` interrupts it and `jobs` lists the jobs kept
+    fn handle_eval_job(
+        &self,
+        action: &str,
+        params: &HashMap<String, String>,
+        body: &[u8],
+    ) -> Result<Vec<u8>, EngineError> {
+        let id = || {
+            let id = params
+                .get("id")
+                .ok_or_else(|| EngineError::PluginError("missing eval job id".to_string()))?;
+            id.parse::<u64>()
+                .map_err(|_| EngineError::PluginError(format!("Invalid eval job id: {id}")))
+        };
+        match action {
+            "start" => {
+                if SAFEPOINT.state().paused {
+                    return Err(EngineError::PluginError(
+                        "process is paused, resume it before evaluating code".to_string(),
+                    ));
+                }
+                let code = String::from_utf8(body.to_vec()).map_err(|e| {
+                    EngineError::PluginError(format!("Failed to convert body to UTF-8 string: {e}"))
+                })?;
+                let session = params.get("session").cloned();
+                to_json(&eval_jobs::start(code, session).map_err(EngineError::PluginError)?)
+            }
+            "jobs" => to_json(&eval_jobs::jobs()),
+            "job" => {
+                let (id, offset) = (id()?, params.get("offset"));
+                let offset = match offset {
+                    Some(offset) => offset.parse::<usize>().map_err(|_| {
+                        EngineError::PluginError(format!("Invalid offset: {offset}"))
+                    })?,
+                    None => 0,
+                };
+                let output = eval_jobs::output(id, offset)
+                    .ok_or_else(|| EngineError::PluginError(format!("eval job {id} not found")))?;
+                to_json(&output)
+            }
+            "cancel" => {
+                let id = id()?;
+                let (job, waited) = gil::with_gil_timeout(move |py| eval_jobs::cancel(py, id))
+                    .map_err(|e| EngineError::PluginError(e.to_string()))?;
+                set_call_metadata("gil-wait-ms", waited.as_millis());
+                to_json(&job.map_err(EngineError::PluginError)?)
+            }
+            _ => Err(EngineError::PluginError(format!(
+                "unknown eval job API: {action}"
+            ))),
+        }
     }
 
     /// Set up a Python crash handler
@@ -242,6 +449,46 @@ impl PythonExt {
         }
     }
 
+    /// Set or clear the remote symbol server used for native frames
+    fn set_symbol_server(&mut self, symbol_server: Maybe<String>) -> Result<(), EngineError> {
+        match &symbol_server {
+            Maybe::Just(url) if !url.starts_with("http://") && !url.starts_with("https://") => {
+                return Err(EngineError::InvalidOptionValue(
+                    Self::OPTION_SYMBOL_SERVER.to_string(),
+                    url.clone(),
+                ));
+            }
+            Maybe::Just(url) => SYMBOLIZER.set_symbol_server(Some(url)),
+            Maybe::Nothing => SYMBOLIZER.set_symbol_server(None),
+        }
+        log::info!("Python symbol server set to: {symbol_server}");
+        self.symbol_server = symbol_server;
+        Ok(())
+    }
+
+    /// Set how long requests wait for the GIL, empty restores the default
+    fn set_gil_timeout_ms(&mut self, gil_timeout_ms: Maybe<u64>) -> Result<(), EngineError> {
+        let ms = match gil_timeout_ms {
+            Maybe::Just(ms) => ms,
+            Maybe::Nothing => gil::DEFAULT_GIL_TIMEOUT_MS,
+        };
+        gil::set_timeout_ms(ms);
+        self.gil_timeout_ms = Maybe::Just(ms);
+        Ok(())
+    }
+
+    /// Set the partitions of external table scans, empty or 0 follows the
+    /// session's target partitions
+    fn set_scan_partitions(&mut self, scan_partitions: Maybe<u64>) -> Result<(), EngineError> {
+        let partitions = match scan_partitions {
+            Maybe::Just(partitions) => partitions as usize,
+            Maybe::Nothing => 0,
+        };
+        extsrc::set_scan_partitions(partitions);
+        self.scan_partitions = scan_partitions;
+        Ok(())
+    }
+
     /// Enable a Python extension from code string
     fn set_enabled(&mut self, enabled: Maybe<String>) -> Result<(), EngineError> {
         let ext = match &enabled {
@@ -338,6 +585,10 @@ fn backtrace(tid: Option<i32>) -> Result<Vec<CallFrame>> {
     SignalTracer.trace(tid)
 }
 
+fn to_json<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, EngineError> {
+    serde_json::to_vec(value).map_err(|e| EngineError::PluginError(e.to_string()))
+}
+
 /// Check if the result bytes contain a "No handler found" error from Python router
 fn is_no_handler_found_error(result_bytes: &[u8]) -> bool {
     let Ok(result_str) = String::from_utf8(result_bytes.to_vec()) else {
@@ -366,37 +617,34 @@ fn str_to_py(py: Python, s: &str) -> PyObject {
 
 /// Call Python handler through the router system
 fn call_python_handler(
+    py: Python,
     path: &str,
     params: &HashMap<String, String>,
 ) -> Result<Vec<u8>, EngineError> {
-    Python::with_gil(|py| {
-        let router_module = py.import("probing.handlers.router").map_err(|e| {
-            EngineError::PluginError(format!("Failed to import router module: {e}"))
-        })?;
-
-        let handle_func = router_module.getattr("handle_request").map_err(|e| {
-            EngineError::PluginError(format!("Failed to get handle_request function: {e}"))
-        })?;
-
-        let params_dict = pyo3::types::PyDict::new(py);
-        for (key, value) in params {
-            params_dict
-                .set_item(key.as_str(), str_to_py(py, value))
-                .map_err(|e| {
-                    EngineError::PluginError(format!("Failed to set param '{key}': {e}"))
-                })?;
-        }
+    let router_module = py
+        .import("probing.handlers.router")
+        .map_err(|e| EngineError::PluginError(format!("Failed to import router module: {e}")))?;
+
+    let handle_func = router_module.getattr("handle_request").map_err(|e| {
+        EngineError::PluginError(format!("Failed to get handle_request function: {e}"))
+    })?;
+
+    let params_dict = pyo3::types::PyDict::new(py);
+    for (key, value) in params {
+        params_dict
+            .set_item(key.as_str(), str_to_py(py, value))
+            .map_err(|e| EngineError::PluginError(format!("Failed to set param '{key}': {e}")))?;
+    }
 
-        let result = handle_func
-            .call1((str_to_py(py, path), params_dict))
-            .map_err(|e| EngineError::PluginError(format!("Failed to call handle_request: {e}")))?;
+    let result = handle_func
+        .call1((str_to_py(py, path), params_dict))
+        .map_err(|e| EngineError::PluginError(format!("Failed to call handle_request: {e}")))?;
 
-        let result_str: String = result
-            .extract()
-            .map_err(|e| EngineError::PluginError(format!("Failed to extract result: {e}")))?;
+    let result_str: String = result
+        .extract()
+        .map_err(|e| EngineError::PluginError(format!("Failed to extract result: {e}")))?;
 
-        Ok(result_str.into_bytes())
-    })
+    Ok(result_str.into_bytes())
 }
 
 #[cfg(test)]
@@ -0,0 +1,496 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use async_trait::async_trait;
+use datafusion::catalog::{Session, TableProvider};
+use datafusion::common::stats::Precision;
+use datafusion::common::{ColumnStatistics, ScalarValue, Statistics};
+use datafusion::datasource::memory::{DataSourceExec, MemorySourceConfig};
+use datafusion::datasource::TableType;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::TableProviderFilterPushDown;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::Expr;
+use probing_core::core::pushdown::Predicate;
+use probing_core::core::{
+    time, ArrayRef, DataType, Field, Float32Array, Float64Array, Int32Array, Int64Array,
+    RecordBatch, Schema, SchemaRef, StringArray, TimestampNanosecondArray,
+};
+use probing_proto::prelude::{Ele, TimeSeries};
+use probing_proto::types::series::Series;
+
+use super::tbls::arrow_type;
+
+/// Rows below which a scan is not split further, threads and partitions
+/// cost more than they save on small tables
+pub const MIN_PARTITION_ROWS: usize = 8192;
+
+/// Partitions of a scan, 0 follows `datafusion.execution.target_partitions`
+static SCAN_PARTITIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the number of partitions external table scans are split into, 0
+/// follows the session's target partitions
+pub fn set_scan_partitions(partitions: usize) {
+    SCAN_PARTITIONS.store(partitions, Ordering::Relaxed);
+}
+
+/// Partitions for a scan of `rows` rows, each of at least
+/// [`MIN_PARTITION_ROWS`] rows
+fn partition_count(rows: usize, target: usize) -> usize {
+    let partitions = match SCAN_PARTITIONS.load(Ordering::Relaxed) {
+        0 => target,
+        n => n,
+    };
+    partitions.min(rows / MIN_PARTITION_ROWS).max(1)
+}
+
+/// Table provider over an external table written from Python.
+///
+/// Rows are read from the time series on every scan. Filters on plain columns
+/// are checked while reading so that, for example, a time window on a trace
+/// table only converts the rows inside it, and the row count and time range
+/// are reported as statistics.
+#[derive(Debug)]
+pub struct ExternalTableSource {
+    name: String,
+    schema: SchemaRef,
+    table: Arc<Mutex<TimeSeries>>,
+}
+
+impl ExternalTableSource {
+    pub fn new(name: &str, table: Arc<Mutex<TimeSeries>>) -> Self {
+        let schema = schema_of(&table.lock().unwrap());
+        Self {
+            name: name.to_string(),
+            schema,
+            table,
+        }
+    }
+
+    /// Values of every column, in schema order, for the rows that may
+    /// satisfy all `predicates`, at most `limit` of them
+    fn collect(&self, predicates: &[Predicate], limit: Option<usize>) -> Vec<Vec<Ele>> {
+        let checks = predicates
+            .iter()
+            .filter_map(|p| Some((self.schema.index_of(&p.column).ok()?, &p.condition)))
+            .collect::<Vec<_>>();
+        rows_of(
+            &self.table.lock().unwrap(),
+            |row| {
+                checks
+                    .iter()
+                    .all(|(col, condition)| condition.matches(row.get(*col).unwrap_or(&Ele::Nil)))
+            },
+            limit.unwrap_or(usize::MAX),
+        )
+    }
+}
+
+#[async_trait]
+impl TableProvider for ExternalTableSource {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(Predicate::support(filters))
+    }
+
+    fn statistics(&self) -> Option<Statistics> {
+        // recomputed on each call, the discard strategy drops old rows
+        let ts = self.table.lock().unwrap();
+        let column_statistics = self
+            .schema
+            .fields()
+            .iter()
+            .zip(series_of(&ts))
+            .map(|(field, series)| {
+                let mut stats = ColumnStatistics::new_unknown();
+                if let Some((min, max)) = time_range(field, series, ts.len()) {
+                    stats.min_value = Precision::Exact(min);
+                    stats.max_value = Precision::Exact(max);
+                }
+                stats
+            })
+            .collect();
+        Some(Statistics {
+            num_rows: Precision::Exact(ts.len()),
+            total_byte_size: Precision::Absent,
+            column_statistics,
+        })
+    }
+
+    /// Rows are split into partitions converted on their own threads, so
+    /// that both the conversion and the plan above the scan run in parallel
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let predicates = filters
+            .iter()
+            .flat_map(Predicate::parse)
+            .collect::<Vec<_>>();
+        // a limit is only pushed down when every filter has been checked
+        let limit = if filters.is_empty() { limit } else { None };
+        let values = self.collect(&predicates, limit);
+        let rows = values.first().map(Vec::len).unwrap_or_default();
+        let partitions = partition_count(rows, state.config().target_partitions());
+        let batches = to_partitions(self.schema.clone(), values, partitions).map_err(|e| {
+            DataFusionError::Execution(format!("failed to read table {}: {e}", self.name))
+        })?;
+        let partitions = batches.into_iter().map(|b| vec![b]).collect::<Vec<_>>();
+        let srccfg =
+            MemorySourceConfig::try_new(&partitions, self.schema.clone(), projection.cloned())?;
+        Ok(Arc::new(DataSourceExec::new(Arc::new(srccfg))))
+    }
+}
+
+/// Schema of an external table: an implicit `timestamp` column with the
+/// append time, unless the table has a column of that name, then the
+/// table's columns.
+///
+/// Columns are nullable since rows may miss columns added later on, and
+/// columns without any value yet are text.
+pub fn schema_of(ts: &TimeSeries) -> SchemaRef {
+    let mut fields = vec![];
+    if !has_timestamp(ts) {
+        fields.push(Field::new("timestamp", DataType::Int64, true));
+    }
+    for (name, col) in ts.names.iter().zip(ts.cols.iter()) {
+        fields.push(Field::new(name, arrow_type(&col.dtype()), true));
+    }
+    SchemaRef::new(Schema::new(fields))
+}
+
+fn has_timestamp(ts: &TimeSeries) -> bool {
+    ts.names.iter().any(|name| name == "timestamp")
+}
+
+/// The series backing each column of [`schema_of`]
+pub(super) fn series_of(ts: &TimeSeries) -> Vec<&Series> {
+    let implicit = (!has_timestamp(ts)).then_some(&ts.timestamp);
+    implicit.into_iter().chain(ts.cols.iter()).collect()
+}
+
+/// Values of every column of [`schema_of`], rows matched by offset so that
+/// columns added later are null in the rows before
+pub fn values_of(ts: &TimeSeries) -> Vec<Vec<Ele>> {
+    rows_of(ts, |_| true, usize::MAX)
+}
+
+/// Like [`values_of`], but only the first `limit` rows for which `keep`
+/// returns true are copied into the columns
+fn rows_of(ts: &TimeSeries, keep: impl Fn(&[Ele]) -> bool, limit: usize) -> Vec<Vec<Ele>> {
+    let implicit = !has_timestamp(ts);
+    let mut values = vec![vec![]; ts.cols.len() + implicit as usize];
+    let rows = ts.iter().filter_map(|(timestamp, mut row)| {
+        if implicit {
+            row.insert(0, timestamp);
+        }
+        keep(&row).then_some(row)
+    });
+    for row in rows.take(limit) {
+        for (col, value) in values.iter_mut().zip(row) {
+            col.push(value);
+        }
+    }
+    values
+}
+
+/// Min and max of a time column, the implicit `timestamp` or a datetime
+fn time_range(field: &Field, series: &Series, len: usize) -> Option<(ScalarValue, ScalarValue)> {
+    match field.data_type() {
+        DataType::Int64 if field.name() == "timestamp" => {
+            let values = series.iter().take(len).filter_map(|x| match x {
+                Ele::I64(x) => Some(x),
+                _ => None,
+            });
+            let (min, max) = min_max(values)?;
+            Some((ScalarValue::Int64(Some(min)), ScalarValue::Int64(Some(max))))
+        }
+        DataType::Timestamp(_, tz) => {
+            let values = series.iter().take(len).filter_map(|x| match x {
+                Ele::DataTime(_) => x.timestamp_nanos(),
+                _ => None,
+            });
+            let (min, max) = min_max(values)?;
+            Some((
+                ScalarValue::TimestampNanosecond(Some(min), tz.clone()),
+                ScalarValue::TimestampNanosecond(Some(max), tz.clone()),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn min_max(values: impl Iterator<Item = i64>) -> Option<(i64, i64)> {
+    values.fold(None, |range, x| match range {
+        None => Some((x, x)),
+        Some((min, max)) => Some((min.min(x), max.max(x))),
+    })
+}
+
+/// Build a batch from column values laid out as in `schema`
+pub fn to_recordbatch(
+    schema: SchemaRef,
+    values: Vec<Vec<Ele>>,
+) -> datafusion::arrow::error::Result<RecordBatch> {
+    let columns = schema
+        .fields()
+        .iter()
+        .zip(values)
+        .map(|(field, values)| to_array(field.data_type(), values))
+        .collect::<Vec<_>>();
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Build `partitions` batches of consecutive rows of similar sizes, each
+/// converted on its own thread
+pub fn to_partitions(
+    schema: SchemaRef,
+    values: Vec<Vec<Ele>>,
+    partitions: usize,
+) -> datafusion::arrow::error::Result<Vec<RecordBatch>> {
+    if partitions <= 1 {
+        return Ok(vec![to_recordbatch(schema, values)?]);
+    }
+    let rows = values.first().map(Vec::len).unwrap_or_default();
+    let size = rows.div_ceil(partitions).max(1);
+    let mut chunks = vec![Vec::with_capacity(values.len()); rows.div_ceil(size)];
+    for col in values {
+        let mut col = col.into_iter();
+        for chunk in chunks.iter_mut() {
+            chunk.push(col.by_ref().take(size).collect::<Vec<_>>());
+        }
+    }
+    thread::scope(|scope| {
+        let handles = chunks
+            .into_iter()
+            .map(|chunk| {
+                let schema = schema.clone();
+                scope.spawn(move || to_recordbatch(schema, chunk))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("conversion thread panicked"))
+            .collect()
+    })
+}
+
+/// Build a column, [`Ele::Nil`] values are nulls
+fn to_array(dtype: &DataType, values: Vec<Ele>) -> ArrayRef {
+    let values = values.into_iter();
+    match dtype {
+        DataType::Int64 => Arc::new(Int64Array::from(
+            values
+                .map(|x| match x {
+                    Ele::I64(x) => Some(x),
+                    Ele::Nil => None,
+                    _ => Some(0),
+                })
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Float64 => Arc::new(Float64Array::from(
+            values
+                .map(|x| match x {
+                    Ele::F64(x) => Some(x),
+                    Ele::Nil => None,
+                    _ => Some(0.0),
+                })
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Int32 => Arc::new(Int32Array::from(
+            values
+                .map(|x| match x {
+                    Ele::I32(x) => Some(x),
+                    Ele::Nil => None,
+                    _ => Some(0),
+                })
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Float32 => Arc::new(Float32Array::from(
+            values
+                .map(|x| match x {
+                    Ele::F32(x) => Some(x),
+                    Ele::Nil => None,
+                    _ => Some(0.0),
+                })
+                .collect::<Vec<_>>(),
+        )),
+        DataType::Timestamp(_, _) => Arc::new(
+            TimestampNanosecondArray::from(
+                values
+                    .map(|x| match x {
+                        Ele::Nil => None,
+                        x => Some(x.timestamp_nanos().unwrap_or_default()),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .with_timezone(time::TIMEZONE),
+        ),
+        _ => Arc::new(StringArray::from(
+            values
+                .map(|x| match x {
+                    Ele::Text(x) => Some(x),
+                    Ele::Nil => None,
+                    _ => Some(x.to_string()),
+                })
+                .collect::<Vec<_>>(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::logical_expr::{col, lit};
+    use datafusion::prelude::SessionContext;
+
+    use super::*;
+
+    fn table() -> Arc<Mutex<TimeSeries>> {
+        let mut ts = TimeSeries::builder()
+            .with_columns(vec!["record_type".to_string(), "trace_id".to_string()])
+            .build();
+        for i in 0..10i64 {
+            let kind = if i % 2 == 0 { "span_start" } else { "event" };
+            ts.append(
+                Ele::I64(i * 100),
+                vec![Ele::Text(kind.into()), Ele::I64(i / 4)],
+            )
+            .unwrap();
+        }
+        Arc::new(Mutex::new(ts))
+    }
+
+    #[test]
+    fn test_statistics() {
+        let source = ExternalTableSource::new("trace", table());
+        let stats = source.statistics().unwrap();
+        assert_eq!(stats.num_rows, Precision::Exact(10));
+        let timestamp = &stats.column_statistics[0];
+        assert_eq!(
+            timestamp.min_value,
+            Precision::Exact(ScalarValue::Int64(Some(0)))
+        );
+        assert_eq!(
+            timestamp.max_value,
+            Precision::Exact(ScalarValue::Int64(Some(900)))
+        );
+        assert_eq!(stats.column_statistics[2].max_value, Precision::Absent);
+    }
+
+    #[tokio::test]
+    async fn test_scan_skips_filtered_rows() {
+        let source = ExternalTableSource::new("trace", table());
+        let filters = [
+            col("timestamp").gt_eq(lit(300i64)),
+            col("record_type").eq(lit("event")),
+        ];
+        let refs = filters.iter().collect::<Vec<_>>();
+        assert_eq!(
+            source.supports_filters_pushdown(&refs).unwrap(),
+            vec![TableProviderFilterPushDown::Inexact; 2]
+        );
+
+        let ctx = SessionContext::new();
+        let plan = source
+            .scan(&ctx.state(), None, &filters, None)
+            .await
+            .unwrap();
+        let batches = datafusion::physical_plan::collect(plan, ctx.task_ctx())
+            .await
+            .unwrap();
+        let timestamps = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .values()
+            .to_vec();
+        assert_eq!(timestamps, [300, 500, 700, 900]);
+    }
+
+    #[test]
+    fn test_partition_count() {
+        assert_eq!(partition_count(10, 8), 1);
+        assert_eq!(partition_count(MIN_PARTITION_ROWS * 3, 8), 3);
+        assert_eq!(partition_count(MIN_PARTITION_ROWS * 100, 8), 8);
+        set_scan_partitions(2);
+        assert_eq!(partition_count(MIN_PARTITION_ROWS * 100, 8), 2);
+        set_scan_partitions(0);
+    }
+
+    #[test]
+    fn test_to_partitions() {
+        let schema = SchemaRef::new(Schema::new(vec![
+            Field::new("timestamp", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let values = vec![
+            (0..10).map(Ele::I64).collect(),
+            (0..10).map(|i| Ele::Text(format!("op{i}"))).collect(),
+        ];
+        let batches = to_partitions(schema, values, 3).unwrap();
+        let rows = batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>();
+        assert_eq!(rows, [4, 4, 2]);
+        let timestamps = batches
+            .iter()
+            .flat_map(|b| {
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, (0..10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_scan_added_column() {
+        let table = table();
+        table
+            .lock()
+            .unwrap()
+            .append_named(Ele::I64(1000), vec![("latency".to_string(), Ele::F64(1.5))])
+            .unwrap();
+        let source = ExternalTableSource::new("trace", table);
+        let field = source.schema().field_with_name("latency").unwrap().clone();
+        assert_eq!(field.data_type(), &DataType::Float64);
+        assert!(field.is_nullable());
+
+        let ctx = SessionContext::new();
+        let plan = source.scan(&ctx.state(), None, &[], None).await.unwrap();
+        let batches = datafusion::physical_plan::collect(plan, ctx.task_ctx())
+            .await
+            .unwrap();
+        let latency = batches[0].column_by_name("latency").unwrap();
+        assert_eq!(latency.len(), 11);
+        assert_eq!(latency.null_count(), 10);
+        assert_eq!(
+            batches[0]
+                .column_by_name("record_type")
+                .unwrap()
+                .null_count(),
+            1
+        );
+    }
+}
@@ -2,18 +2,83 @@ use std::sync::Arc;
 use std::{collections::HashMap, sync::Mutex};
 
 use once_cell::sync::Lazy;
-use probing_proto::prelude::{Ele, TimeSeries};
+use probing_core::{catalog, trace};
+use probing_proto::prelude::{Ele, Series, TimeSeries};
 use probing_proto::types::series::DiscardStrategy;
+use probing_proto::types::TimeSeriesError;
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyType};
 use pyo3::{pyclass, pymethods, Bound, PyObject, PyResult, Python};
 
+use crate::features::anomaly;
 use crate::features::convert::{ele_to_python, python_to_ele};
+use crate::features::ingest::{self, IngestPolicy};
+use crate::features::op_summary::{OP_SUMMARY, TORCH_TRACE_TABLE};
 
 fn value_to_object(py: Python, v: &probing_proto::prelude::Ele) -> PyObject {
     ele_to_python(py, v).unwrap_or_else(|_| py.None())
 }
 
+pyo3::create_exception!(
+    probing,
+    SchemaError,
+    pyo3::exceptions::PyValueError,
+    "A row that does not fit the schema of an external table.\n\nThe `table`, `column`, `expected` and `got` attributes describe the mismatch."
+);
+
+/// Report a rejected row as a [`SchemaError`]
+fn schema_error(table: &str, err: TimeSeriesError) -> PyErr {
+    let (column, expected, got) = match &err {
+        TimeSeriesError::IncompatibleType {
+            column,
+            expected,
+            got,
+        } => (Some(column.clone()), expected.to_string(), got.to_string()),
+        TimeSeriesError::ColumnCountMismatch { expected, got } => {
+            (None, expected.to_string(), got.to_string())
+        }
+        TimeSeriesError::ColumnTypeMismatch { expected, got } => {
+            (None, expected.to_string(), got.to_string())
+        }
+        _ => return pyo3::exceptions::PyValueError::new_err(err.to_string()),
+    };
+    Python::with_gil(|py| {
+        let pyerr = SchemaError::new_err(format!("table {table}: {err}"));
+        let value = pyerr.value(py);
+        for (name, attr) in [
+            ("table", Some(table.to_string())),
+            ("column", column),
+            ("expected", Some(expected)),
+            ("got", Some(got)),
+        ] {
+            let _ = value.setattr(name, attr);
+        }
+        pyerr
+    })
+}
+
+fn to_eles(values: Vec<PyObject>) -> Vec<Ele> {
+    Python::with_gil(|py| {
+        values
+            .into_iter()
+            .map(|v| python_to_ele(v.bind(py)).unwrap_or(Ele::Nil))
+            .collect()
+    })
+}
+
+/// Whether `value` is stored in `col` as is, so that a queued row holding it
+/// cannot be rejected when its queue is drained
+fn fits(col: &Series, value: &Ele) -> bool {
+    matches!(value, Ele::Nil) || value.dtype() == col.dtype()
+}
+
+fn now_micros() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as i64
+}
+
 #[pyclass]
 pub struct PyExternalTableConfig {
     #[pyo3(get)]
@@ -90,9 +155,23 @@ impl PyExternalTableConfig {
 pub static EXTERN_TABLES: Lazy<Mutex<HashMap<String, Arc<Mutex<TimeSeries>>>>> =
     Lazy::new(|| Mutex::new(Default::default()));
 
+/// First row of each table that did not fit its schema since the last
+/// `flush` of the table, raised from there
+static REJECTED: Lazy<Mutex<HashMap<String, TimeSeriesError>>> = Lazy::new(Default::default);
+
+/// Release the capacity the external tables reserved for rows not appended
+/// yet and return how many tables there are
+pub fn shrink_tables() -> usize {
+    let tables: Vec<_> = EXTERN_TABLES.lock().unwrap().values().cloned().collect();
+    for table in &tables {
+        table.lock().unwrap().shrink_to_fit();
+    }
+    tables.len()
+}
+
 #[pyclass]
 #[derive(Clone, Debug)]
-pub struct ExternalTable(Arc<Mutex<TimeSeries>>, usize);
+pub struct ExternalTable(Arc<Mutex<TimeSeries>>, String);
 
 #[pymethods]
 impl ExternalTable {
@@ -105,7 +184,6 @@ impl ExternalTable {
         discard_threshold: usize,
         discard_strategy: String,
     ) -> Self {
-        let ncolumn = columns.len();
         let config = PyExternalTableConfig {
             chunk_size,
             discard_threshold,
@@ -117,11 +195,17 @@ impl ExternalTable {
                 .with_columns(columns)
                 .build(),
         ));
-        EXTERN_TABLES
+        let replaced = EXTERN_TABLES
             .lock()
             .unwrap()
             .insert(name.to_string(), ts.clone());
-        ExternalTable(ts, ncolumn)
+        if replaced.is_none() {
+            catalog::table_registered("python", &format!("python.{name}"));
+        }
+        if name == TORCH_TRACE_TABLE {
+            OP_SUMMARY.lock().unwrap().clear();
+        }
+        ExternalTable(ts, name.to_string())
     }
 
     #[classmethod]
@@ -129,8 +213,7 @@ impl ExternalTable {
         let binding = EXTERN_TABLES.lock().unwrap();
         let ts = binding.get(name);
         if let Some(ts) = ts {
-            let ncolumn = ts.lock().unwrap().cols.len();
-            Ok(ExternalTable(ts.clone(), ncolumn))
+            Ok(ExternalTable(ts.clone(), name.to_string()))
         } else {
             Err(pyo3::exceptions::PyValueError::new_err(format!(
                 "table {name} not found"
@@ -151,10 +234,8 @@ impl ExternalTable {
         let mut binding = EXTERN_TABLES.lock().unwrap();
         let ts = binding.get(name);
         if let Some(ts) = ts {
-            let ncolumn = ts.lock().unwrap().cols.len();
-            Ok(ExternalTable(ts.clone(), ncolumn))
+            Ok(ExternalTable(ts.clone(), name.to_string()))
         } else {
-            let ncolumn = columns.len();
             let config = PyExternalTableConfig {
                 chunk_size,
                 discard_threshold,
@@ -167,66 +248,115 @@ impl ExternalTable {
                     .build(),
             ));
             binding.insert(name.to_string(), ts.clone());
-            Ok(ExternalTable(ts, ncolumn))
+            drop(binding);
+            catalog::table_registered("python", &format!("python.{name}"));
+            Ok(ExternalTable(ts, name.to_string()))
         }
     }
 
     #[classmethod]
     fn drop(_cls: &Bound<'_, PyType>, name: &str) -> PyResult<()> {
-        let _ = EXTERN_TABLES.lock().unwrap().remove(name);
+        let removed = EXTERN_TABLES.lock().unwrap().remove(name);
+        if removed.is_some() {
+            catalog::table_unregistered("python", &format!("python.{name}"));
+        }
+        ingest::forget(name);
+        if name == TORCH_TRACE_TABLE {
+            OP_SUMMARY.lock().unwrap().clear();
+        }
         Ok(())
     }
 
     fn names(&self) -> Vec<String> {
+        trace::flush();
         self.0.lock().unwrap().names.clone()
     }
 
-    fn append(&mut self, values: Vec<PyObject>) -> PyResult<()> {
-        if values.len() != self.1 {
-            return Err(pyo3::exceptions::PyValueError::new_err(
-                "column count mismatch",
-            ));
+    /// Append a row with a value for every column, in column order
+    fn append(&mut self, py: Python, values: Vec<PyObject>) -> PyResult<()> {
+        self.append_ts(py, now_micros(), values)
+    }
+
+    /// Append a row recorded at `t`. The row is queued on the calling thread
+    /// and stored when its buffer is drained, see `flush`. Rows of `block`
+    /// tables and rows whose values need coercing are stored right away,
+    /// raising `SchemaError` if they do not fit. A full table drops or blocks
+    /// the row as set by `ingest.policy`.
+    fn append_ts(&mut self, py: Python, t: i64, values: Vec<PyObject>) -> PyResult<()> {
+        let values = to_eles(values);
+        let table = self.clone();
+        let queue = {
+            let ts = self.0.lock().unwrap();
+            ts.cols.len() == values.len()
+                && ts.cols.iter().zip(&values).all(|(col, v)| fits(col, v))
+        };
+        if !queue || ingest::policy(&self.1) == IngestPolicy::Block {
+            return py
+                .allow_threads(move || table.store(t, values))
+                .map_err(|err| self.rejected(err));
         }
-        let t = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros() as i64;
-        let values: Vec<Ele> = Python::with_gil(|py| {
-            values
-                .into_iter()
-                .map(|v| {
-                    let bound = v.bind(py);
-                    python_to_ele(&bound).unwrap_or(Ele::Nil)
-                })
-                .collect()
+        trace::defer(move || {
+            if let Err(err) = table.store(t, values) {
+                table.reject(err);
+            }
         });
-        match self.0.lock().unwrap().append(t.into(), values) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(pyo3::exceptions::PyValueError::new_err(e.to_string())),
-        }
+        Ok(())
     }
 
-    fn append_ts(&mut self, t: i64, values: Vec<PyObject>) -> PyResult<()> {
-        if values.len() != self.1 {
-            return Err(pyo3::exceptions::PyValueError::new_err(
-                "column count mismatch",
-            ));
+    /// Append a row given as a dict of column values.
+    ///
+    /// Missing columns are null and unknown ones are added to the table,
+    /// values are coerced to the type of their column. Rows are queued like
+    /// those of `append_ts`; a row that adds columns or needs coercing is
+    /// stored right away and raises `SchemaError` if it does not fit, leaving
+    /// the table unchanged.
+    #[pyo3(signature = (row, t=None))]
+    fn append_dict(
+        &mut self,
+        py: Python,
+        row: HashMap<String, PyObject>,
+        t: Option<i64>,
+    ) -> PyResult<()> {
+        let (names, values): (Vec<_>, Vec<_>) = row.into_iter().unzip();
+        let row = names.into_iter().zip(to_eles(values)).collect::<Vec<_>>();
+        let t = t.unwrap_or_else(now_micros);
+        let table = self.clone();
+        let queue = {
+            let ts = self.0.lock().unwrap();
+            row.iter().all(|(name, v)| {
+                ts.names
+                    .iter()
+                    .position(|n| n == name)
+                    .is_some_and(|idx| fits(&ts.cols[idx], v))
+            })
+        };
+        if !queue || ingest::policy(&self.1) == IngestPolicy::Block {
+            return py
+                .allow_threads(move || table.store_named(t, row))
+                .map_err(|err| self.rejected(err));
         }
-        let values: Vec<Ele> = Python::with_gil(|py| {
-            values
-                .into_iter()
-                .map(|v| {
-                    let bound = v.bind(py);
-                    python_to_ele(&bound).unwrap_or(Ele::Nil)
-                })
-                .collect()
+        trace::defer(move || {
+            if let Err(err) = table.store_named(t, row) {
+                table.reject(err);
+            }
         });
-        let _ = self.0.lock().unwrap().append(t.into(), values);
         Ok(())
     }
 
+    /// Store the rows queued by all threads. Raises `SchemaError` for the
+    /// first queued row of this table that did not fit since the last call.
+    fn flush(&self) -> PyResult<()> {
+        trace::flush();
+        let rejected = REJECTED.lock().unwrap().remove(&self.1);
+        match rejected {
+            Some(err) => Err(schema_error(&self.1, err)),
+            None => Ok(()),
+        }
+    }
+
     #[pyo3(signature = (limit=None))]
     fn take(&self, limit: Option<usize>) -> PyResult<Vec<(PyObject, Vec<PyObject>)>> {
+        trace::flush();
         let result: Vec<(PyObject, Vec<PyObject>)> = self
             .0
             .lock()
@@ -248,6 +378,174 @@ impl ExternalTable {
     }
 }
 
+impl ExternalTable {
+    /// Store a row of `append_ts`, `Err` is a row that did not fit
+    fn store(&self, t: i64, values: Vec<Ele>) -> Result<(), TimeSeriesError> {
+        let mut ts = ingest::wait_for_room(&self.1, &self.0);
+        let discarded = ts.discarded();
+        match ingest::append(&self.1, &mut ts, |ts| ts.append(t.into(), values.clone())) {
+            Some(Ok(())) => self.summarize(&ts, discarded, &ts.names, &values),
+            Some(Err(err)) => return Err(err),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Store a row of `append_dict`, `Err` is a row that did not fit
+    fn store_named(&self, t: i64, row: Vec<(String, Ele)>) -> Result<(), TimeSeriesError> {
+        let mut ts = ingest::wait_for_room(&self.1, &self.0);
+        let discarded = ts.discarded();
+        match ingest::append(&self.1, &mut ts, |ts| {
+            ts.append_named(t.into(), row.clone())
+        }) {
+            Some(Ok(added)) => {
+                if !added.is_empty() {
+                    log::info!("columns added to table {}: {}", self.1, added.join(", "));
+                }
+                let (names, values): (Vec<_>, Vec<_>) = row.into_iter().unzip();
+                self.summarize(&ts, discarded, &names, &values);
+            }
+            Some(Err(err)) => return Err(err),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Count a row stored right away that did not fit, raised by the append
+    fn rejected(&self, err: TimeSeriesError) -> PyErr {
+        log::debug!("row rejected by table {}: {err}", self.1);
+        ingest::rejected(&self.1);
+        schema_error(&self.1, err)
+    }
+
+    /// Count a queued row that did not fit, the first one since the last
+    /// `flush` is raised from there
+    fn reject(&self, err: TimeSeriesError) {
+        log::debug!("row rejected by table {}: {err}", self.1);
+        ingest::rejected(&self.1);
+        REJECTED
+            .lock()
+            .unwrap()
+            .entry(self.1.clone())
+            .or_insert(err);
+    }
+
+    /// Keep the incremental summaries of well-known tables up to date and
+    /// feed the anomaly detectors watching this table; `discarded` is the
+    /// count of discarded rows of `ts` before the row was appended
+    fn summarize(&self, ts: &TimeSeries, discarded: usize, names: &[String], values: &[Ele]) {
+        if self.1 == TORCH_TRACE_TABLE {
+            let mut summary = OP_SUMMARY.lock().unwrap();
+            if ts.discarded() == discarded {
+                summary.ingest(names, values);
+            } else {
+                summary.rebuild(ts);
+            }
+        }
+        anomaly::ingest(&self.1, names, values);
+    }
+}
+
+/// Table pruned by `trace/prune` when no `table` parameter is given
+pub const DEFAULT_PRUNE_TABLE: &str = "trace_event";
+
+/// Rows to drop from an external table, all conditions must hold for a row
+/// to be removed
+#[derive(Debug, Default, PartialEq)]
+pub struct PruneFilter {
+    /// Drop rows recorded before this time, in microseconds since the epoch
+    pub before: Option<i64>,
+    /// Drop rows whose column renders to the given value
+    pub columns: Vec<(String, String)>,
+}
+
+impl PruneFilter {
+    /// Build a filter from call parameters; `before` takes an age such as
+    /// `30s`, `10m` or `2h`, or an absolute time in nanoseconds since the
+    /// epoch, every other parameter except `table` matches a column
+    pub fn from_params(params: &HashMap<String, String>, now_micros: i64) -> Result<Self, String> {
+        let mut filter = PruneFilter::default();
+        for (key, value) in params {
+            match key.as_str() {
+                "table" => {}
+                "before" => filter.before = Some(parse_before(value, now_micros)?),
+                _ => filter.columns.push((key.clone(), value.clone())),
+            }
+        }
+        filter.columns.sort();
+        if filter.before.is_none() && filter.columns.is_empty() {
+            return Err("at least one of `before` or a column filter is required".to_string());
+        }
+        Ok(filter)
+    }
+}
+
+fn parse_before(value: &str, now_micros: i64) -> Result<i64, String> {
+    if let Ok(nanos) = value.parse::<i64>() {
+        return Ok(nanos / 1000);
+    }
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("invalid before: {value}"))?;
+    let (amount, unit) = value.split_at(split);
+    let amount = amount
+        .parse::<i64>()
+        .map_err(|_| format!("invalid before: {value}"))?;
+    let micros = match unit {
+        "ms" => 1_000,
+        "s" => 1_000_000,
+        "m" => 60_000_000,
+        "h" => 3_600_000_000,
+        _ => {
+            return Err(format!(
+                "invalid before: {value}, expected a unit of ms, s, m or h"
+            ))
+        }
+    };
+    Ok(now_micros - amount * micros)
+}
+
+/// Drop the rows of an external table matching `filter`, returning the
+/// number of rows removed and the number left
+pub fn prune(table: &str, filter: &PruneFilter) -> Result<(usize, usize), String> {
+    trace::flush();
+    let ts = EXTERN_TABLES
+        .lock()
+        .unwrap()
+        .get(table)
+        .cloned()
+        .ok_or_else(|| format!("table not found: {table}"))?;
+    let mut ts = ts.lock().unwrap();
+    let columns = filter
+        .columns
+        .iter()
+        .map(|(name, value)| {
+            ts.names
+                .iter()
+                .position(|n| n == name)
+                .map(|idx| (idx, value.as_str()))
+                .ok_or_else(|| format!("column not found in {table}: {name}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let removed = ts
+        .retain(|t, row| {
+            let old = match (filter.before, t) {
+                (Some(before), Ele::I64(t)) => *t < before,
+                (Some(_), _) => false,
+                (None, _) => true,
+            };
+            !(old && columns.iter().all(|(idx, v)| row[*idx].to_string() == *v))
+        })
+        .map_err(|e| e.to_string())?;
+    if table == TORCH_TRACE_TABLE && removed > 0 {
+        OP_SUMMARY.lock().unwrap().rebuild(&ts);
+    }
+    let remaining = ts.len();
+    drop(ts);
+    ingest::notify_room();
+    Ok((removed, remaining))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -342,6 +640,40 @@ table = probing.ExternalTable.get_or_create("table2", ["a", "b"])
         });
     }
 
+    #[test]
+    fn test_evolve_table_in_python() {
+        setup();
+        Python::with_gil(|py| {
+            py.run(
+                c_str!(
+                    r#"
+import probing
+metrics = probing.ExternalTable("metrics_evolve", ["step", "loss"])
+metrics.append([1, 2])
+metrics.append_dict({"step": 2, "loss": 0.5, "lr": 0.1})
+metrics.append_dict({"step": 3})
+try:
+    metrics.append_dict({"step": 4, "loss": "nan?"})
+    raise AssertionError("text accepted in a float column")
+except ValueError as e:
+    assert (e.table, e.column, e.expected, e.got) == (
+        "metrics_evolve", "loss", "f64", "text"
+    ), e
+assert metrics.names() == ["step", "loss", "lr"]
+assert [row for _, row in metrics.take()] == [
+    [1, 2.0, None],
+    [2, 0.5, 0.1],
+    [3, None, None],
+]
+                    "#
+                ),
+                None,
+                None,
+            )
+            .unwrap();
+        });
+    }
+
     #[test]
     fn test_drop_table_in_python() {
         setup();
@@ -377,6 +709,129 @@ probing.ExternalTable.drop("table2")
         });
     }
 
+    #[test]
+    fn test_prune_table() {
+        let mut ts = TimeSeries::builder()
+            .with_columns(vec!["kind".to_string(), "name".to_string()])
+            .build();
+        for (t, kind) in [(1_000, "io"), (2_000, "io"), (3_000, "step"), (9_000, "io")] {
+            ts.append(Ele::I64(t), vec![kind.into(), "read".into()])
+                .unwrap();
+        }
+        EXTERN_TABLES
+            .lock()
+            .unwrap()
+            .insert("prune_test".to_string(), Arc::new(Mutex::new(ts)));
+
+        let params = HashMap::from([
+            ("table".to_string(), "prune_test".to_string()),
+            ("before".to_string(), "5ms".to_string()),
+            ("kind".to_string(), "io".to_string()),
+        ]);
+        let filter = PruneFilter::from_params(&params, 10_000).unwrap();
+        assert_eq!(filter.before, Some(5_000));
+        assert_eq!(prune("prune_test", &filter), Ok((2, 2)));
+
+        // absolute times are given in nanoseconds like the trace `time` column
+        let params = HashMap::from([("before".to_string(), "9500000".to_string())]);
+        let filter = PruneFilter::from_params(&params, 10_000).unwrap();
+        assert_eq!(prune("prune_test", &filter), Ok((2, 0)));
+
+        assert!(PruneFilter::from_params(&HashMap::new(), 0).is_err());
+        assert!(PruneFilter::from_params(&params_of("before", "10d"), 0).is_err());
+        let filter = PruneFilter::from_params(&params_of("thread", "1"), 0).unwrap();
+        assert!(prune("prune_test", &filter).is_err());
+        assert!(prune("missing", &filter).is_err());
+    }
+
+    #[test]
+    fn test_block_table_keeps_every_row() {
+        setup();
+        let _config = ingest::TEST_CONFIG.lock().unwrap();
+        ingest::set_policies("block_test=block").unwrap();
+        ingest::set_capacity(20).unwrap();
+        ingest::set_block_timeout(std::time::Duration::from_secs(10));
+        let table = ExternalTable::new(
+            "block_test",
+            vec!["v".to_string()],
+            10000,
+            20_000_000,
+            "BaseMemorySize".to_string(),
+        );
+
+        let appenders: Vec<_> = (0..4i64)
+            .map(|thread| {
+                let mut table = table.clone();
+                std::thread::spawn(move || {
+                    Python::with_gil(|py| {
+                        for i in 0..50i64 {
+                            let value = (thread * 50 + i).into_pyobject(py).unwrap();
+                            table
+                                .append_ts(py, i, vec![value.into_any().unbind()])
+                                .unwrap();
+                        }
+                    })
+                })
+            })
+            .collect();
+        let mut pruned = 0;
+        while !appenders.iter().all(|a| a.is_finished()) {
+            pruned += prune("block_test", &PruneFilter::default()).unwrap().0;
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        for appender in appenders {
+            appender.join().unwrap();
+        }
+        pruned += prune("block_test", &PruneFilter::default()).unwrap().0;
+
+        ingest::set_block_timeout(std::time::Duration::from_millis(
+            ingest::DEFAULT_BLOCK_TIMEOUT_MS,
+        ));
+        ingest::set_capacity(ingest::DEFAULT_CAPACITY).unwrap();
+        ingest::set_policies("").unwrap();
+        let stats = ingest::stats().remove("block_test").unwrap();
+        EXTERN_TABLES.lock().unwrap().remove("block_test");
+        ingest::forget("block_test");
+
+        assert_eq!(pruned, 200);
+        assert_eq!(stats.appended, 200);
+        assert_eq!(stats.dropped_newest, 0);
+        assert!(stats.blocked > 0);
+    }
+
+    #[test]
+    fn test_append_raises_schema_error() {
+        setup();
+        let mut table = ExternalTable::new(
+            "schema_error_test",
+            vec!["v".to_string()],
+            10000,
+            20_000_000,
+            "BaseMemorySize".to_string(),
+        );
+        Python::with_gil(|py| {
+            let int = 1i64.into_pyobject(py).unwrap().into_any().unbind();
+            let text = "x".into_pyobject(py).unwrap().into_any().unbind();
+            table.append_ts(py, 1, vec![int]).unwrap();
+            let err = table
+                .append_ts(py, 2, vec![text.clone_ref(py)])
+                .unwrap_err();
+            assert!(err.is_instance_of::<SchemaError>(py));
+            let row = HashMap::from([("v".to_string(), text)]);
+            let err = table.append_dict(py, row, Some(3)).unwrap_err();
+            assert!(err.is_instance_of::<SchemaError>(py));
+            table.flush().unwrap();
+        });
+        assert_eq!(table.0.lock().unwrap().retained(), 1);
+        assert_eq!(ingest::stats()["schema_error_test"].rejected, 2);
+        EXTERN_TABLES.lock().unwrap().remove("schema_error_test");
+        ingest::forget("schema_error_test");
+    }
+
+    fn params_of(key: &str, value: &str) -> HashMap<String, String> {
+        HashMap::from([(key.to_string(), value.to_string())])
+    }
+
     #[test]
     fn test_see_py_table_in_engine() {
         setup_table3();
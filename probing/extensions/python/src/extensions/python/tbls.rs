@@ -3,14 +3,19 @@ use std::ffi::CString;
 use std::sync::Arc;
 
 use anyhow::Result;
+use async_trait::async_trait;
+use datafusion::arrow::array::BooleanArray;
+use datafusion::catalog::TableProvider;
 
 use log::error;
+
+use super::extsrc::ExternalTableSource;
+use probing_core::core::{time, LazyTableSource};
 use probing_core::core::{
     ArrayRef, CustomNamespace, DataType, Field, Float64Array, Int64Array, NamespacePluginHelper,
     RecordBatch, Schema, SchemaRef, StringArray,
 };
-use probing_core::core::{Float32Array, Int32Array, LazyTableSource};
-use probing_proto::prelude::{CallFrame, Ele, TimeSeries};
+use probing_proto::prelude::{CallFrame, TimeSeries};
 use probing_proto::types;
 use pyo3::types::PyAnyMethods;
 use pyo3::types::PyDict;
@@ -26,6 +31,36 @@ use pyo3::Python;
 #[derive(Default, Debug)]
 pub struct PythonNamespace {}
 
+/// Columns of `python.threads`, in order
+const THREAD_COLUMNS: [&str; 14] = [
+    "thread_id",
+    "native_id",
+    "name",
+    "daemon",
+    "is_main",
+    "state",
+    "detail",
+    "lock_owner",
+    "kernel_state",
+    "wchan",
+    "func",
+    "file",
+    "lineno",
+    "stack_id",
+];
+const THREAD_INT_COLUMNS: [&str; 5] =
+    ["thread_id", "native_id", "lock_owner", "lineno", "stack_id"];
+const THREAD_TEXT_COLUMNS: [&str; 7] = [
+    "name",
+    "state",
+    "detail",
+    "kernel_state",
+    "wchan",
+    "func",
+    "file",
+];
+const THREAD_BOOL_COLUMNS: [&str; 2] = ["daemon", "is_main"];
+
 impl PythonNamespace {
     fn get_backtrace_data() -> Result<Vec<RecordBatch>> {
         let frames = crate::extensions::python::backtrace(None)?;
@@ -40,6 +75,7 @@ impl PythonNamespace {
         let mut linenos: Vec<Option<i64>> = Vec::new();
         let mut depth: Vec<Option<i64>> = Vec::new(); // Renamed from depths
         let mut frame_types: Vec<Option<String>> = Vec::new(); // Added for frame type
+        let mut symbol_status: Vec<Option<String>> = Vec::new();
         let mut current_depth_val: i64 = 0; // Renamed from current_depth to avoid conflict if depth was a scalar
 
         for frame in frames {
@@ -49,6 +85,7 @@ impl PythonNamespace {
                     file,
                     func,
                     lineno,
+                    status,
                 } => {
                     ips.push(Some(ip));
                     files.push(Some(file));
@@ -56,6 +93,7 @@ impl PythonNamespace {
                     linenos.push(Some(lineno));
                     depth.push(Some(current_depth_val)); // Use new variable name
                     frame_types.push(Some("Native".to_string())); // Add frame type
+                    symbol_status.push(Some(status.to_string()));
                     current_depth_val += 1;
                 }
                 CallFrame::PyFrame {
@@ -70,6 +108,7 @@ impl PythonNamespace {
                     linenos.push(Some(lineno));
                     depth.push(Some(current_depth_val)); // Use new variable name
                     frame_types.push(Some("Python".to_string())); // Add frame type
+                    symbol_status.push(None);
                     current_depth_val += 1;
                 }
             }
@@ -82,6 +121,7 @@ impl PythonNamespace {
             Field::new("lineno", DataType::Int64, true),
             Field::new("depth", DataType::Int64, true),
             Field::new("frame_type", DataType::Utf8, true), // Added frame_type field
+            Field::new("symbol_status", DataType::Utf8, true),
         ]));
 
         let columns: Vec<ArrayRef> = vec![
@@ -91,11 +131,77 @@ impl PythonNamespace {
             Arc::new(Int64Array::from(linenos)),
             Arc::new(Int64Array::from(depth)), // Use new variable name
             Arc::new(StringArray::from(frame_types)), // Added frame_type array
+            Arc::new(StringArray::from(symbol_status)),
         ];
 
         Ok(vec![RecordBatch::try_new(schema, columns)?])
     }
 
+    /// `python.threads`: state of every Python thread, classified by
+    /// `probing.inspect.threads`
+    fn get_threads_data() -> Result<Vec<RecordBatch>> {
+        Python::with_gil(|py| {
+            let rows = py
+                .import("probing.inspect.threads")?
+                .call_method0("thread_states")?;
+            let rows = rows
+                .downcast::<PyList>()
+                .map_err(|e| anyhow::anyhow!("thread_states did not return a list: {e}"))?;
+
+            let mut ints: HashMap<&str, Vec<Option<i64>>> = HashMap::new();
+            let mut texts: HashMap<&str, Vec<Option<String>>> = HashMap::new();
+            let mut bools: HashMap<&str, Vec<Option<bool>>> = HashMap::new();
+            for row in rows.try_iter()? {
+                let row = row?;
+                let row = row
+                    .downcast::<PyDict>()
+                    .map_err(|e| anyhow::anyhow!("invalid thread row: {e}"))?;
+                let get = |key: &str| row.get_item(key).ok().flatten().filter(|v| !v.is_none());
+                for key in THREAD_INT_COLUMNS {
+                    let value = get(key).and_then(|v| v.extract::<i64>().ok());
+                    ints.entry(key).or_default().push(value);
+                }
+                for key in THREAD_TEXT_COLUMNS {
+                    let value = get(key).and_then(|v| v.extract::<String>().ok());
+                    texts.entry(key).or_default().push(value);
+                }
+                for key in THREAD_BOOL_COLUMNS {
+                    let value = get(key).and_then(|v| v.extract::<bool>().ok());
+                    bools.entry(key).or_default().push(value);
+                }
+            }
+
+            let mut fields = vec![];
+            let mut columns: Vec<ArrayRef> = vec![];
+            for name in THREAD_COLUMNS {
+                if let Some(values) = ints.remove(name) {
+                    fields.push(Field::new(name, DataType::Int64, true));
+                    columns.push(Arc::new(Int64Array::from(values)));
+                } else if let Some(values) = texts.remove(name) {
+                    fields.push(Field::new(name, DataType::Utf8, true));
+                    columns.push(Arc::new(StringArray::from(values)));
+                } else if let Some(values) = bools.remove(name) {
+                    fields.push(Field::new(name, DataType::Boolean, true));
+                    columns.push(Arc::new(BooleanArray::from(values)));
+                }
+            }
+            if columns.is_empty() {
+                return Ok(vec![]);
+            }
+            let schema = SchemaRef::new(Schema::new(fields));
+            Ok(vec![RecordBatch::try_new(schema, columns)?])
+        })
+    }
+
+    /// Tables computed by the probe rather than read from Python objects
+    fn builtin_data(expr: &str) -> Option<Result<Vec<RecordBatch>>> {
+        match expr {
+            "backtrace" => Some(Self::get_backtrace_data()),
+            "threads" => Some(Self::get_threads_data()),
+            _ => None,
+        }
+    }
+
     fn data_from_python(expr: &str) -> Result<Vec<RecordBatch>> {
         Python::with_gil(|py| {
             let import_path = expr.split(|c| c == '(' || c == '[').next().unwrap_or(expr);
@@ -184,6 +290,7 @@ impl PythonNamespace {
     }
 }
 
+#[async_trait]
 impl CustomNamespace for PythonNamespace {
     fn name() -> &'static str {
         "python"
@@ -198,15 +305,16 @@ impl CustomNamespace for PythonNamespace {
             |binding| binding.keys().cloned().collect(),
         );
         tables.push("backtrace".to_string()); // Add backtrace to the list
+        tables.push("threads".to_string());
         tables
     }
 
     fn data(expr: &str) -> Vec<RecordBatch> {
-        if expr == "backtrace" {
-            match Self::get_backtrace_data() {
+        if let Some(data) = Self::builtin_data(expr) {
+            match data {
                 Ok(batches) => batches,
                 Err(e) => {
-                    error!("Error getting backtrace data: {e:?}");
+                    error!("Error getting {expr} data: {e:?}");
                     vec![]
                 }
             }
@@ -229,9 +337,22 @@ impl CustomNamespace for PythonNamespace {
         }
     }
 
+    /// External tables are read on every scan with filter pushdown, other
+    /// expressions are evaluated once into a lazy table
+    async fn table(expr: String) -> datafusion::error::Result<Option<Arc<dyn TableProvider>>> {
+        let table = super::exttbls::EXTERN_TABLES
+            .lock()
+            .ok()
+            .and_then(|tables| tables.get(&expr).cloned());
+        match table {
+            Some(table) => Ok(Some(Arc::new(ExternalTableSource::new(&expr, table)))),
+            None => Ok(Some(Self::make_lazy(&expr))),
+        }
+    }
+
     fn make_lazy(expr: &str) -> Arc<LazyTableSource> {
-        if expr == "backtrace" {
-            let data = Self::get_backtrace_data().unwrap_or_default();
+        if let Some(data) = Self::builtin_data(expr) {
+            let data = data.unwrap_or_default();
             let schema = if data.is_empty() {
                 None
             } else {
@@ -252,41 +373,8 @@ impl CustomNamespace for PythonNamespace {
             |binding| binding.clone(),
         );
 
-        if binding.contains_key(expr) {
-            let table = binding.get(expr).unwrap();
-            let names = table.lock().unwrap().names.clone();
-            let dtypes = table
-                .lock()
-                .unwrap()
-                .cols
-                .iter()
-                .map(|x| x.dtype())
-                .collect::<Vec<_>>();
-            let mut fields = Vec::new();
-
-            // Check if table already has a timestamp column
-            let has_timestamp = names.iter().any(|n| n == "timestamp");
-
-            // Only add timestamp if it doesn't already exist
-            if !has_timestamp {
-                fields.push(Field::new("timestamp", DataType::Int64, true));
-            }
-
-            for (name, dtype) in names.iter().zip(dtypes.iter()) {
-                fields.push(Field::new(
-                    name,
-                    match dtype {
-                        types::EleType::I64 => DataType::Int64,
-                        types::EleType::F64 => DataType::Float64,
-                        types::EleType::I32 => DataType::Int32,
-                        types::EleType::F32 => DataType::Float32,
-                        _ => DataType::Utf8,
-                    },
-                    false,
-                ));
-            }
-
-            let schema = Some(SchemaRef::new(Schema::new(fields)));
+        if let Some(table) = binding.get(expr) {
+            let schema = Some(super::extsrc::schema_of(&table.lock().unwrap()));
 
             Arc::new(LazyTableSource {
                 name: expr.to_string(),
@@ -309,101 +397,27 @@ impl CustomNamespace for PythonNamespace {
     }
 }
 
+/// Arrow type of an external table column, time columns become
+/// timezone-aware nanosecond timestamps.
+pub(super) fn arrow_type(dtype: &types::EleType) -> DataType {
+    match dtype {
+        types::EleType::I64 => DataType::Int64,
+        types::EleType::F64 => DataType::Float64,
+        types::EleType::I32 => DataType::Int32,
+        types::EleType::F32 => DataType::Float32,
+        types::EleType::DataTime => time::timestamp_ns_type(),
+        _ => DataType::Utf8,
+    }
+}
+
 impl PythonNamespace {
     pub fn time_series_to_recordbatch(
-        names: Vec<String>,
+        _names: Vec<String>,
         ts: &TimeSeries,
     ) -> Result<Vec<RecordBatch>> {
-        let mut fields: Vec<Field> = vec![];
-        let mut columns: Vec<ArrayRef> = vec![];
-
-        fields.push(Field::new("timestamp", DataType::Int64, true));
-        names.iter().zip(ts.cols.iter()).for_each(|(name, col)| {
-            let data_type = match col.dtype() {
-                types::EleType::I64 => DataType::Int64,
-                types::EleType::F64 => DataType::Float64,
-                types::EleType::I32 => DataType::Int32,
-                types::EleType::F32 => DataType::Float32,
-                _ => DataType::Utf8,
-            };
-            fields.push(Field::new(name, data_type, false));
-        });
-
-        let length = ts.len();
-
-        let timeseries = ts
-            .timestamp
-            .iter()
-            .take(length)
-            .map(|x| match x {
-                Ele::I64(x) => x,
-                _ => 0,
-            })
-            .collect::<Vec<_>>();
-        columns.push(Arc::new(Int64Array::from(timeseries)));
-
-        for col in ts.cols.iter() {
-            let col = match col.dtype() {
-                types::EleType::I64 => Arc::new(Int64Array::from(
-                    col.iter()
-                        .take(length)
-                        .map(|x| match x {
-                            Ele::I64(x) => x,
-                            _ => 0,
-                        })
-                        .collect::<Vec<_>>(),
-                )) as ArrayRef,
-                types::EleType::F64 => Arc::new(Float64Array::from(
-                    col.iter()
-                        .take(length)
-                        .map(|x| match x {
-                            Ele::F64(x) => x,
-                            _ => 0.0,
-                        })
-                        .collect::<Vec<_>>(),
-                )) as ArrayRef,
-                types::EleType::I32 => Arc::new(Int32Array::from(
-                    col.iter()
-                        .take(length)
-                        .map(|x| match x {
-                            Ele::I32(x) => x,
-                            _ => 0,
-                        })
-                        .collect::<Vec<_>>(),
-                )) as ArrayRef,
-                types::EleType::F32 => Arc::new(Float32Array::from(
-                    col.iter()
-                        .take(length)
-                        .map(|x| match x {
-                            Ele::F32(x) => x,
-                            _ => 0.0,
-                        })
-                        .collect::<Vec<_>>(),
-                )) as ArrayRef,
-                types::EleType::Text => Arc::new(StringArray::from(
-                    col.iter()
-                        .take(length)
-                        .map(|x| match x {
-                            Ele::Text(x) => x,
-                            _ => x.to_string(),
-                        })
-                        .collect::<Vec<_>>(),
-                )) as ArrayRef,
-                _ => Arc::new(StringArray::from(
-                    col.iter()
-                        .take(length)
-                        .map(|x| x.to_string())
-                        .collect::<Vec<_>>(),
-                )) as ArrayRef,
-            };
-
-            columns.push(col);
-        }
-
-        Ok(vec![RecordBatch::try_new(
-            SchemaRef::new(Schema::new(fields)),
-            columns,
-        )?])
+        let schema = super::extsrc::schema_of(ts);
+        let values = super::extsrc::values_of(ts);
+        Ok(vec![super::extsrc::to_recordbatch(schema, values)?])
     }
 
     pub fn object_to_recordbatch(obj: Bound<'_, PyAny>) -> Result<Vec<RecordBatch>> {
@@ -0,0 +1,73 @@
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
+
+use crate::features::shutdown;
+
+#[derive(Debug, EngineExtension)]
+pub struct ShutdownExtension {
+    /// Write a final snapshot on SIGTERM and SIGINT before the process exits
+    #[option(allowed = ["on", "off"])]
+    capture: Maybe<String>,
+
+    /// Directory the snapshots are written below, in `rank<N>/shutdown-<pid>.json`
+    #[option()]
+    dir: Maybe<String>,
+
+    /// Milliseconds the capture may delay the exit
+    #[option(aliases = ["timeout.ms"])]
+    timeout_ms: Maybe<u64>,
+}
+
+impl Default for ShutdownExtension {
+    fn default() -> Self {
+        Self {
+            capture: Maybe::Just("off".to_string()),
+            dir: Maybe::Just(shutdown::DEFAULT_DIR.to_string()),
+            timeout_ms: Maybe::Just(shutdown::DEFAULT_TIMEOUT_MS),
+        }
+    }
+}
+
+impl EngineCall for ShutdownExtension {}
+
+impl EngineDatasource for ShutdownExtension {}
+
+impl ShutdownExtension {
+    fn set_capture(&mut self, capture: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = capture.clone().into();
+        let enabled = match value.as_str() {
+            "on" => true,
+            "off" | "" => false,
+            _ => {
+                return Err(EngineError::InvalidOptionValue(
+                    Self::OPTION_CAPTURE.to_string(),
+                    value,
+                ))
+            }
+        };
+        shutdown::set_enabled(enabled).map_err(|e| {
+            log::error!("Failed to set up shutdown capture: {e}");
+            EngineError::InvalidOptionValue(Self::OPTION_CAPTURE.to_string(), value.clone())
+        })?;
+        self.capture = capture;
+        Ok(())
+    }
+
+    fn set_dir(&mut self, dir: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = dir.clone().into();
+        shutdown::set_dir(&value);
+        self.dir = dir;
+        Ok(())
+    }
+
+    fn set_timeout_ms(&mut self, timeout_ms: Maybe<u64>) -> Result<(), EngineError> {
+        let ms: Option<u64> = timeout_ms.clone().into();
+        shutdown::set_timeout_ms(ms.unwrap_or(shutdown::DEFAULT_TIMEOUT_MS));
+        self.timeout_ms = timeout_ms;
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
+
+use crate::features::signals::{SignalsPlugin, SignalsTable};
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct SignalsExtension {
+    /// Chain probing's signal handlers with handlers that replaced them
+    #[option]
+    chain: Maybe<bool>,
+}
+
+impl EngineCall for SignalsExtension {}
+
+impl EngineDatasource for SignalsExtension {
+    /// Serve the `signals` inventory
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == SignalsTable::name() => {
+                Some(SignalsPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl SignalsExtension {
+    fn set_chain(&mut self, chain: Maybe<bool>) -> Result<(), EngineError> {
+        let enabled = matches!(chain, Maybe::Just(true));
+        match crate::features::signals::set_chain(enabled) {
+            Ok(repaired) if !repaired.is_empty() => {
+                log::info!("chained signal handlers: {}", repaired.join(", "));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Failed to chain signal handlers: {e}");
+                return Err(EngineError::InvalidOptionValue(
+                    Self::OPTION_CHAIN.to_string(),
+                    chain.into(),
+                ));
+            }
+        }
+        self.chain = chain;
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+
+use crate::features::tasks::{
+    DaskTasksPlugin, DaskTasksTable, RayTasksPlugin, RayTasksTable, DASK, RAY,
+};
+
+/// Serves the `tasks` table of the `ray` and `dask` namespaces; registered
+/// once per namespace since an extension provides a single data source
+#[derive(Debug, Default, EngineExtension)]
+pub struct TasksExtension {}
+
+impl EngineCall for TasksExtension {}
+
+impl EngineDatasource for TasksExtension {
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match (namespace, name) {
+            (RAY, Some(name)) if name == RayTasksTable::name() => {
+                Some(RayTasksPlugin::create(namespace, name))
+            }
+            (DASK, Some(name)) if name == DaskTasksTable::name() => {
+                Some(DaskTasksPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
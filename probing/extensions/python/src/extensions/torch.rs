@@ -1,23 +1,60 @@
+use probing_core::core::CustomTable;
 use probing_core::core::EngineCall;
 use probing_core::core::EngineDatasource;
 use probing_core::core::EngineError;
 use probing_core::core::EngineExtension;
 use probing_core::core::EngineExtensionOption;
 use probing_core::core::Maybe;
+use probing_proto::prelude::{AgentEvent, EventKind};
 use pyo3::prelude::*;
 
+use crate::features::op_summary::{OpSummaryPlugin, OpSummaryTable};
+
 #[derive(Debug, Default, EngineExtension)]
 pub struct TorchExtension {
     /// Combined PyTorch profiling specification string (see TorchProbeConfig).
     #[option(aliases=["profiling_mode"])]
     profiling: Maybe<String>,
+
+    /// Gradient statistics of parameter groups: `on`, `every=<steps>` or `off`
+    #[option]
+    grad_stats: Maybe<String>,
 }
 
 impl EngineCall for TorchExtension {}
 
-impl EngineDatasource for TorchExtension {}
+impl EngineDatasource for TorchExtension {
+    /// Serve the incremental `op_summary` table of the torch trace
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == OpSummaryTable::name() => {
+                Some(OpSummaryPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
 
 impl TorchExtension {
+    fn set_grad_stats(&mut self, grad_stats: Maybe<String>) -> Result<(), EngineError> {
+        let spec: String = grad_stats.clone().into();
+        Python::with_gil(|py| -> pyo3::PyResult<()> {
+            py.import("probing.profiling.grad_stats")?
+                .call_method1("configure", (spec.as_str(),))?;
+            Ok(())
+        })
+        .map_err(|err| {
+            log::error!("Failed to configure gradient statistics with '{spec}': {err}");
+            EngineError::InvalidOptionValue(Self::OPTION_GRAD_STATS.to_string(), spec.clone())
+        })?;
+        self.grad_stats = grad_stats;
+        Ok(())
+    }
+
     fn set_profiling(&mut self, profiling: Maybe<String>) -> Result<(), EngineError> {
         let py_result = Python::with_gil(|py| -> pyo3::PyResult<()> {
             let module = py.import("probing.profiling.torch_probe")?;
@@ -38,6 +75,20 @@ impl TorchExtension {
 
         match py_result {
             Ok(()) => {
+                let value: String = profiling.clone().into();
+                let state = if value.trim().is_empty() {
+                    "disabled".to_string()
+                } else {
+                    format!("configured with '{value}'")
+                };
+                probing_core::events::publish(
+                    AgentEvent::new(
+                        EventKind::ProfilerState,
+                        "torch",
+                        format!("torch profiler {state}"),
+                    )
+                    .with_detail("spec", value),
+                );
                 self.profiling = profiling;
                 Ok(())
             }
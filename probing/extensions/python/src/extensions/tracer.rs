@@ -0,0 +1,66 @@
+use probing_core::core::CustomTable;
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
+
+use crate::features::vm_tracer::{self, CallsPlugin, CallsTable, SamplingPlugin, SamplingTable};
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct TracerExtension {
+    /// Record one Python call out of N into `tracer.calls`, 0 or empty records none
+    #[option(aliases = ["sample.every"])]
+    sample_every: Maybe<u64>,
+
+    /// Calls recorded per second at most, 0 or empty for no limit
+    #[option(aliases = ["max.events.per.sec"])]
+    max_events_per_sec: Maybe<u64>,
+}
+
+impl EngineCall for TracerExtension {}
+
+impl EngineDatasource for TracerExtension {
+    /// Serve the sampled calls and the sampling counters
+    fn datasrc(
+        &self,
+        namespace: &str,
+        name: Option<&str>,
+    ) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
+        match name {
+            Some(name) if name == CallsTable::name() => Some(CallsPlugin::create(namespace, name)),
+            Some(name) if name == SamplingTable::name() => {
+                Some(SamplingPlugin::create(namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl TracerExtension {
+    fn set_sample_every(&mut self, sample_every: Maybe<u64>) -> Result<(), EngineError> {
+        let mut sampling = vm_tracer::sampling();
+        sampling.every = match sample_every {
+            Maybe::Just(every) => every,
+            Maybe::Nothing => 0,
+        };
+        vm_tracer::set_sampling(sampling);
+        self.sample_every = sample_every;
+        Ok(())
+    }
+
+    fn set_max_events_per_sec(
+        &mut self,
+        max_events_per_sec: Maybe<u64>,
+    ) -> Result<(), EngineError> {
+        let mut sampling = vm_tracer::sampling();
+        sampling.max_per_sec = match max_events_per_sec {
+            Maybe::Just(max) => max,
+            Maybe::Nothing => 0,
+        };
+        vm_tracer::set_sampling(sampling);
+        self.max_events_per_sec = max_events_per_sec;
+        Ok(())
+    }
+}
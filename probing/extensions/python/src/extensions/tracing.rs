@@ -0,0 +1,43 @@
+use probing_core::core::EngineCall;
+use probing_core::core::EngineDatasource;
+use probing_core::core::EngineError;
+use probing_core::core::EngineExtension;
+use probing_core::core::EngineExtensionOption;
+use probing_core::core::Maybe;
+
+use crate::features::vm_tracer;
+
+#[derive(Debug, Default, EngineExtension)]
+pub struct TracingExtension {
+    /// Comma separated module patterns whose calls are traced, e.g. `my_project.*`; empty traces all
+    #[option]
+    include: Maybe<String>,
+
+    /// Comma separated module patterns never traced, e.g. `torch.*,numpy.*`
+    #[option]
+    exclude: Maybe<String>,
+}
+
+impl EngineCall for TracingExtension {}
+
+impl EngineDatasource for TracingExtension {}
+
+impl TracingExtension {
+    fn set_include(&mut self, include: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = include.clone().into();
+        let mut sampling = vm_tracer::sampling();
+        sampling.include = vm_tracer::parse_patterns(&value);
+        vm_tracer::set_sampling(sampling);
+        self.include = include;
+        Ok(())
+    }
+
+    fn set_exclude(&mut self, exclude: Maybe<String>) -> Result<(), EngineError> {
+        let value: String = exclude.clone().into();
+        let mut sampling = vm_tracer::sampling();
+        sampling.exclude = vm_tracer::parse_patterns(&value);
+        vm_tracer::set_sampling(sampling);
+        self.exclude = exclude;
+        Ok(())
+    }
+}
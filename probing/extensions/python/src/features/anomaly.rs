@@ -0,0 +1,600 @@
+//! Streaming anomaly detection on numeric metrics such as loss or gradient norms.
+//!
+//! Rules are configured with `anomaly.watch`, a comma separated list of
+//! `<source>:<kind>[:<threshold>]` entries, e.g. `metrics.loss:spike`. A
+//! source is either a column of an external table, fed as rows are appended,
+//! or `sql(<query>)`, whose first value is polled every [`QUERY_INTERVAL`].
+//!
+//! Non-finite values are reported by every rule, so NaN losses and overflowing
+//! gradients are caught without any extra configuration. Detected anomalies
+//! are kept in `alerts.anomalies` and published as `alert_fired` events.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    time, CustomTable, DataType, Field, Float64Array, RecordBatch, Schema, SchemaRef, StringArray,
+    TablePluginHelper,
+};
+use probing_core::ENGINE;
+use probing_proto::prelude::{AgentEvent, Ele, EventKind};
+
+/// Interval between two evaluations of `sql(...)` sources
+pub const QUERY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of samples observed before spikes and explosions are reported
+const WARMUP: u64 = 10;
+
+/// Smoothing factor of the moving mean and variance
+const ALPHA: f64 = 0.1;
+
+/// Maximum number of anomalies kept in `alerts.anomalies`
+const MAX_ANOMALIES: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectorKind {
+    /// Values more than `threshold` standard deviations from the moving mean
+    Spike,
+    /// Magnitudes more than `threshold` times the moving mean magnitude
+    Explode,
+    /// Only NaN and infinite values
+    Nan,
+}
+
+impl DetectorKind {
+    fn default_threshold(&self) -> f64 {
+        match self {
+            DetectorKind::Spike => 4.0,
+            DetectorKind::Explode => 10.0,
+            DetectorKind::Nan => 0.0,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DetectorKind::Spike => "spike",
+            DetectorKind::Explode => "explode",
+            DetectorKind::Nan => "nan",
+        }
+    }
+}
+
+impl FromStr for DetectorKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "spike" | "zscore" => Ok(DetectorKind::Spike),
+            "explode" | "exploding" => Ok(DetectorKind::Explode),
+            "nan" => Ok(DetectorKind::Nan),
+            other => Err(anyhow!(
+                "unknown anomaly detector `{other}`, expected spike, explode or nan"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A column of an external table, e.g. `metrics.loss`
+    Column { table: String, column: String },
+    /// A SQL query whose first value is checked
+    Query(String),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Column { table, column } => write!(f, "{table}.{column}"),
+            Source::Query(query) => write!(f, "sql({query})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub source: Source,
+    pub kind: DetectorKind,
+    pub threshold: f64,
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (source, rest) = if let Some(query) = s.strip_prefix("sql(") {
+            let end = closing_paren(query).ok_or_else(|| anyhow!("unbalanced sql( in `{s}`"))?;
+            let rest = query[end + 1..].trim_start();
+            let rest = match rest.strip_prefix(':') {
+                Some(rest) => rest,
+                None if rest.is_empty() => rest,
+                None => return Err(anyhow!("expected `:` after sql(...) in `{s}`")),
+            };
+            (Source::Query(query[..end].trim().to_string()), rest)
+        } else {
+            let (source, rest) = s.split_once(':').unwrap_or((s, ""));
+            let source = source.strip_prefix("python.").unwrap_or(source);
+            let (table, column) = source
+                .rsplit_once('.')
+                .ok_or_else(|| anyhow!("expected <table>.<column> in `{s}`"))?;
+            let source = Source::Column {
+                table: table.to_string(),
+                column: column.to_string(),
+            };
+            (source, rest)
+        };
+
+        let mut parts = rest.splitn(2, ':');
+        let kind = match parts.next().filter(|k| !k.trim().is_empty()) {
+            Some(kind) => kind.parse()?,
+            None => DetectorKind::Spike,
+        };
+        let threshold = match parts.next() {
+            Some(threshold) => threshold
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("invalid threshold `{threshold}` in `{s}`"))?,
+            None => kind.default_threshold(),
+        };
+        Ok(Rule {
+            source,
+            kind,
+            threshold,
+        })
+    }
+}
+
+/// Byte offset of the parenthesis closing an already opened one
+fn closing_paren(s: &str) -> Option<usize> {
+    let mut depth = 1;
+    for (idx, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse a comma separated list of rules; commas inside `sql(...)` are kept
+pub fn parse_rules(spec: &str) -> Result<Vec<Rule>> {
+    let mut rules = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in spec.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                rules.push(&spec[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    rules.push(&spec[start..]);
+    rules
+        .into_iter()
+        .filter(|rule| !rule.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Exponentially weighted mean and variance
+#[derive(Debug, Clone, Default)]
+struct Ewma {
+    mean: f64,
+    var: f64,
+    count: u64,
+}
+
+impl Ewma {
+    fn update(&mut self, value: f64) {
+        if self.count == 0 {
+            self.mean = value;
+        } else {
+            let diff = value - self.mean;
+            let incr = ALPHA * diff;
+            self.mean += incr;
+            self.var = (1.0 - ALPHA) * (self.var + diff * incr);
+        }
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    /// Microseconds since epoch
+    pub time: i64,
+    pub source: String,
+    pub kind: DetectorKind,
+    pub value: f64,
+    /// z-score for spikes, ratio to the mean magnitude for explosions
+    pub score: f64,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct Detector {
+    rule: Rule,
+    stats: Ewma,
+}
+
+impl Detector {
+    pub fn new(rule: Rule) -> Self {
+        Detector {
+            rule,
+            stats: Ewma::default(),
+        }
+    }
+
+    /// Check `value` against the history, then add it to the history
+    pub fn observe(&mut self, value: f64) -> Option<Anomaly> {
+        let source = self.rule.source.to_string();
+        let anomaly = |score: f64, message: String| Anomaly {
+            time: now_micros(),
+            source: source.clone(),
+            kind: self.rule.kind,
+            value,
+            score,
+            message,
+        };
+
+        if !value.is_finite() {
+            // keep non-finite values out of the history
+            return Some(anomaly(f64::NAN, format!("{source} is {value}")));
+        }
+
+        let sample = match self.rule.kind {
+            DetectorKind::Explode => value.abs(),
+            _ => value,
+        };
+        let mut found = None;
+        if self.stats.count >= WARMUP {
+            let mean = self.stats.mean;
+            let std = self.stats.var.sqrt();
+            match self.rule.kind {
+                DetectorKind::Spike => {
+                    let deviation = (sample - mean).abs();
+                    let score = if std > 0.0 {
+                        deviation / std
+                    } else if deviation > 0.0 {
+                        f64::INFINITY
+                    } else {
+                        0.0
+                    };
+                    if score > self.rule.threshold {
+                        found = Some(anomaly(
+                            score,
+                            format!("{source} = {value} is {score:.1} sigma from mean {mean:.4}"),
+                        ));
+                    }
+                }
+                DetectorKind::Explode if mean > 0.0 => {
+                    let score = sample / mean;
+                    if score > self.rule.threshold {
+                        found = Some(anomaly(
+                            score,
+                            format!("{source} = {value} is {score:.1}x its mean {mean:.4}"),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.stats.update(sample);
+        found
+    }
+}
+
+fn now_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as i64)
+        .unwrap_or_default()
+}
+
+fn as_f64(ele: &Ele) -> Option<f64> {
+    match ele {
+        Ele::F64(x) => Some(*x),
+        Ele::F32(x) => Some(*x as f64),
+        Ele::I64(x) => Some(*x as f64),
+        Ele::I32(x) => Some(*x as f64),
+        _ => None,
+    }
+}
+
+/// Configured detectors and the anomalies they found
+#[derive(Debug, Default)]
+pub struct AnomalyMonitor {
+    detectors: Vec<Detector>,
+    anomalies: VecDeque<Anomaly>,
+}
+
+impl AnomalyMonitor {
+    /// Replace the detectors, resetting their history
+    pub fn configure(&mut self, rules: Vec<Rule>) {
+        self.detectors = rules.into_iter().map(Detector::new).collect();
+    }
+
+    /// External tables with a column watched by a detector
+    pub fn tables(&self) -> HashSet<String> {
+        self.detectors
+            .iter()
+            .filter_map(|d| match &d.rule.source {
+                Source::Column { table, .. } => Some(table.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Check one external table row, given as column names and values
+    pub fn ingest(&mut self, table: &str, names: &[String], values: &[Ele]) {
+        let mut found = vec![];
+        for detector in self.detectors.iter_mut() {
+            let Source::Column {
+                table: ref t,
+                ref column,
+            } = detector.rule.source
+            else {
+                continue;
+            };
+            if t != table {
+                continue;
+            }
+            let value = names
+                .iter()
+                .position(|n| n == column)
+                .and_then(|idx| values.get(idx))
+                .and_then(as_f64);
+            if let Some(anomaly) = value.and_then(|v| detector.observe(v)) {
+                found.push(anomaly);
+            }
+        }
+        found.into_iter().for_each(|a| self.record(a));
+    }
+
+    /// Check the latest value of a `sql(...)` source
+    pub fn observe_query(&mut self, query: &str, value: f64) {
+        let mut found = vec![];
+        for detector in self.detectors.iter_mut() {
+            if matches!(&detector.rule.source, Source::Query(q) if q == query) {
+                found.extend(detector.observe(value));
+            }
+        }
+        found.into_iter().for_each(|a| self.record(a));
+    }
+
+    pub fn queries(&self) -> Vec<String> {
+        self.detectors
+            .iter()
+            .filter_map(|d| match &d.rule.source {
+                Source::Query(query) => Some(query.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn anomalies(&self) -> &VecDeque<Anomaly> {
+        &self.anomalies
+    }
+
+    fn record(&mut self, anomaly: Anomaly) {
+        log::warn!("anomaly detected: {}", anomaly.message);
+        probing_core::events::publish(
+            AgentEvent::new(EventKind::AlertFired, "anomaly", anomaly.message.clone())
+                .with_detail("source", anomaly.source.clone())
+                .with_detail("kind", anomaly.kind.as_str())
+                .with_detail("value", anomaly.value.to_string()),
+        );
+        if self.anomalies.len() >= MAX_ANOMALIES {
+            self.anomalies.pop_front();
+        }
+        self.anomalies.push_back(anomaly);
+    }
+}
+
+pub static ANOMALIES: Lazy<Mutex<AnomalyMonitor>> = Lazy::new(Default::default);
+
+/// [`AnomalyMonitor::tables`], so that appends to the other tables leave
+/// [`ANOMALIES`] alone
+static WATCHED: Lazy<RwLock<HashSet<String>>> = Lazy::new(Default::default);
+
+/// Feed a row appended to an external table to the detectors watching it
+pub fn ingest(table: &str, names: &[String], values: &[Ele]) {
+    if WATCHED.read().unwrap().contains(table) {
+        ANOMALIES.lock().unwrap().ingest(table, names, values);
+    }
+}
+
+/// Bumped on every reconfiguration so stale pollers exit
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Apply an `anomaly.watch` spec, starting a poller for `sql(...)` sources
+pub fn configure(spec: &str) -> Result<()> {
+    let rules = parse_rules(spec)?;
+    let mut monitor = ANOMALIES.lock().unwrap();
+    monitor.configure(rules);
+    *WATCHED.write().unwrap() = monitor.tables();
+    let generation = GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    if !monitor.queries().is_empty() {
+        std::thread::Builder::new()
+            .name("probing-anomaly".to_string())
+            .spawn(move || poll_queries(generation))?;
+    }
+    Ok(())
+}
+
+fn poll_queries(generation: u64) {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::error!("Failed to create runtime for anomaly queries: {e}");
+            return;
+        }
+    };
+
+    loop {
+        std::thread::sleep(QUERY_INTERVAL);
+        if GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        let queries = ANOMALIES.lock().unwrap().queries();
+        for query in queries {
+            let result = runtime.block_on(async { ENGINE.read().await.async_query(&query).await });
+            let value = match result {
+                Ok(Some(df)) => df.cols.first().filter(|c| !c.is_empty()).map(|c| c.get(0)),
+                Ok(None) => None,
+                Err(e) => {
+                    log::debug!("anomaly query `{query}` failed: {e}");
+                    None
+                }
+            };
+            if let Some(value) = value.as_ref().and_then(as_f64) {
+                ANOMALIES.lock().unwrap().observe_query(&query, value);
+            }
+        }
+    }
+}
+
+/// `alerts.anomalies`: anomalies found by the configured detectors
+#[derive(Default, Debug)]
+pub struct AnomaliesTable {}
+
+impl CustomTable for AnomaliesTable {
+    fn name() -> &'static str {
+        "anomalies"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("source", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, false),
+            Field::new("score", DataType::Float64, false),
+            Field::new("message", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let monitor = ANOMALIES.lock().unwrap();
+        let anomalies = monitor.anomalies();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_us_array(anomalies.iter().map(|a| a.time))),
+                Arc::new(StringArray::from_iter_values(
+                    anomalies.iter().map(|a| &a.source),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    anomalies.iter().map(|a| a.kind.as_str()),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    anomalies.iter().map(|a| a.value),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    anomalies.iter().map(|a| a.score),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    anomalies.iter().map(|a| &a.message),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build anomalies batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type AnomaliesPlugin = TablePluginHelper<AnomaliesTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = parse_rules(
+            "metrics.loss:spike, python.metrics.grad_norm:explode:20,\
+             sql(SELECT max(x) FROM t WHERE a IN (1, 2)):nan",
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(
+            rules[0].source,
+            Source::Column {
+                table: "metrics".into(),
+                column: "loss".into()
+            }
+        );
+        assert_eq!(rules[0].threshold, 4.0);
+        assert_eq!(rules[1].kind, DetectorKind::Explode);
+        assert_eq!(rules[1].threshold, 20.0);
+        assert_eq!(
+            rules[2].source,
+            Source::Query("SELECT max(x) FROM t WHERE a IN (1, 2)".into())
+        );
+        assert_eq!(rules[2].kind, DetectorKind::Nan);
+
+        assert!(parse_rules("loss:spike").is_err());
+        assert!(parse_rules("metrics.loss:wobble").is_err());
+        assert!(parse_rules("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_spike_after_warmup() {
+        let mut detector = Detector::new("metrics.loss:spike".parse().unwrap());
+        for i in 0..50 {
+            let value = 1.0 + 0.01 * (i % 5) as f64;
+            assert_eq!(detector.observe(value), None, "{value}");
+        }
+        let anomaly = detector.observe(5.0).unwrap();
+        assert_eq!(anomaly.kind, DetectorKind::Spike);
+        assert!(anomaly.score > 4.0);
+        assert!(detector.observe(f64::NAN).unwrap().score.is_nan());
+    }
+
+    #[test]
+    fn test_explode_and_ingest() {
+        let names = vec!["step".to_string(), "grad_norm".to_string()];
+        let mut monitor = AnomalyMonitor::default();
+        monitor.configure(parse_rules("metrics.grad_norm:explode").unwrap());
+        assert_eq!(monitor.tables(), HashSet::from(["metrics".to_string()]));
+
+        for step in 0..20 {
+            monitor.ingest("metrics", &names, &[Ele::I64(step), Ele::F64(2.0)]);
+        }
+        monitor.ingest("other", &names, &[Ele::I64(20), Ele::F64(1e6)]);
+        assert!(monitor.anomalies().is_empty());
+
+        monitor.ingest("metrics", &names, &[Ele::I64(21), Ele::F64(-1e3)]);
+        monitor.ingest("metrics", &names, &[Ele::I64(22), Ele::F64(f64::INFINITY)]);
+        let anomalies = monitor.anomalies();
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0].source, "metrics.grad_norm");
+        assert_eq!(anomalies[0].score, 500.0);
+        assert_eq!(anomalies[1].value, f64::INFINITY);
+    }
+}
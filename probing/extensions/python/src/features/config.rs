@@ -1,7 +1,8 @@
 use pyo3::prelude::*;
 use pyo3::types::PyModule;
 
-use probing_core::config;
+use probing_cc::extensions::files;
+use probing_core::{config, schedule};
 
 use crate::features::convert::{ele_to_python, python_to_ele};
 
@@ -108,6 +109,35 @@ fn is_empty(_py: Python) -> bool {
     block_on_async(config::is_empty())
 }
 
+/// Sorted extension options and configuration entries as JSON, with the
+/// secrets masked, see `probing_core::config::dump`.
+#[pyfunction(name = "config_dump")]
+fn dump(py: Python) -> PyResult<String> {
+    let dump = py.allow_threads(|| block_on_async(config::dump()));
+    serde_json::to_string(&dump).map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+}
+
+/// Directories the file API serves, see `files.allowed_dirs`.
+#[pyfunction(name = "config_allowed_dirs")]
+fn allowed_dirs() -> Vec<String> {
+    files::allowed_dirs()
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect()
+}
+
+/// Note the step of the training and apply the scheduled configuration
+/// changes it makes due before returning, so a change scheduled for a step
+/// is in effect when the step runs.
+#[pyfunction(name = "config_report_step")]
+fn report_step(py: Python, step: i64) {
+    schedule::report_step(step);
+    if schedule::is_due(schedule::now(), Some(step)) {
+        // options of Python extensions take the GIL when they are written
+        py.allow_threads(|| block_on_async(schedule::apply_due()));
+    }
+}
+
 /// Register the config functions directly to the probing Python module.
 pub fn register_config_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(get, module)?)?;
@@ -119,6 +149,9 @@ pub fn register_config_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(clear, module)?)?;
     module.add_function(wrap_pyfunction!(len, module)?)?;
     module.add_function(wrap_pyfunction!(is_empty, module)?)?;
+    module.add_function(wrap_pyfunction!(report_step, module)?)?;
+    module.add_function(wrap_pyfunction!(dump, module)?)?;
+    module.add_function(wrap_pyfunction!(allowed_dirs, module)?)?;
 
     Ok(())
 }
@@ -5,7 +5,11 @@
 
 use probing_proto::prelude::Ele;
 use pyo3::prelude::*;
-use pyo3::types::{PyBool, PyFloat, PyInt, PyString};
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyBool, PyFloat, PyInt, PyString, PyType};
+
+/// `datetime.datetime`, imported on the first conversion
+static DATETIME: GILOnceCell<Py<PyType>> = GILOnceCell::new();
 
 /// Convert Ele to Python object
 ///
@@ -46,6 +50,15 @@ pub fn python_to_ele(value: &Bound<'_, PyAny>) -> PyResult<Ele> {
         return Ok(Ele::Nil);
     }
 
+    // Try datetime.datetime, stored as microseconds since the unix epoch
+    let datetime = DATETIME.import(value.py(), "datetime", "datetime")?;
+    if value.is_instance(datetime)? {
+        let seconds: f64 = value.call_method0("timestamp")?.extract()?;
+        return Ok(Ele::DataTime(
+            (seconds * 1_000_000.0).round().max(0.0) as u64
+        ));
+    }
+
     // Try bool
     if let Ok(b) = value.extract::<bool>() {
         return Ok(Ele::BOOL(b));
@@ -0,0 +1,169 @@
+//! Graph breaks, recompilations and guard failures of `torch.compile`.
+//!
+//! `probing.profiling.dynamo` reads them from `torch._dynamo` and records them
+//! here with the optimizer step they happened in. The last [`MAX_EVENTS`] are
+//! served as `torch.dynamo`, so that a step time regression can be joined
+//! with the frames dynamo keeps recompiling:
+//!
+//! ```sql
+//! SELECT step, kind, code, reason, count FROM torch.dynamo WHERE kind != 'compile'
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    time, CustomTable, DataType, Field, Float64Array, Int64Array, RecordBatch, Schema, SchemaRef,
+    StringArray, TablePluginHelper,
+};
+use pyo3::prelude::*;
+
+/// Number of events kept, older ones are dropped first
+const MAX_EVENTS: usize = 10_000;
+
+/// One diagnostic reported by dynamo
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamoEvent {
+    /// Nanoseconds since the unix epoch
+    pub time: i64,
+    /// Optimizer step, `None` before the first step
+    pub step: Option<i64>,
+    /// `compile`, `recompile`, `graph_break` or `guard_failure`
+    pub kind: String,
+    /// Compiled frame, as `name (file:line)`
+    pub code: String,
+    pub reason: String,
+    /// Occurrences since the previous event of the same kind and reason
+    pub count: i64,
+    /// Compilation time in seconds
+    pub duration: f64,
+}
+
+pub static DYNAMO_EVENTS: Lazy<Mutex<VecDeque<DynamoEvent>>> = Lazy::new(Default::default);
+
+pub fn record(event: DynamoEvent) {
+    let mut events = DYNAMO_EVENTS.lock().unwrap();
+    if events.len() >= MAX_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Record a dynamo event from Python; `time` defaults to now
+#[pyfunction]
+#[pyo3(signature = (kind, code, reason, count=1, duration=0.0, step=None, time=None))]
+pub fn _record_dynamo_event(
+    kind: String,
+    code: String,
+    reason: String,
+    count: i64,
+    duration: f64,
+    step: Option<i64>,
+    time: Option<i64>,
+) {
+    let time = time.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_default()
+    });
+    record(DynamoEvent {
+        time,
+        step,
+        kind,
+        code,
+        reason,
+        count,
+        duration,
+    });
+}
+
+pub fn register_dynamo_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_record_dynamo_event, module)?)?;
+    Ok(())
+}
+
+/// `torch.dynamo`: diagnostics reported by `torch._dynamo`
+#[derive(Default, Debug)]
+pub struct DynamoTable {}
+
+impl CustomTable for DynamoTable {
+    fn name() -> &'static str {
+        "dynamo"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("step", DataType::Int64, true),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("code", DataType::Utf8, false),
+            Field::new("reason", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, false),
+            Field::new("duration", DataType::Float64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let events = DYNAMO_EVENTS.lock().unwrap();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_ns_array(events.iter().map(|e| e.time))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.step))),
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| &e.kind),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| &e.code),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| &e.reason),
+                )),
+                Arc::new(Int64Array::from_iter_values(events.iter().map(|e| e.count))),
+                Arc::new(Float64Array::from_iter_values(
+                    events.iter().map(|e| e.duration),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build dynamo batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type DynamoPlugin = TablePluginHelper<DynamoTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_bounded() {
+        for i in 0..MAX_EVENTS as i64 + 5 {
+            _record_dynamo_event(
+                "graph_break".into(),
+                "forward (model.py:10)".into(),
+                "call_function print".into(),
+                1,
+                0.0,
+                Some(i),
+                Some(i),
+            );
+        }
+        let events = DYNAMO_EVENTS.lock().unwrap();
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events.front().unwrap().step, Some(5));
+        drop(events);
+
+        let batches = DynamoTable::data();
+        assert_eq!(batches[0].num_rows(), MAX_EVENTS);
+    }
+}
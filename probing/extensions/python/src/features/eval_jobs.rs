@@ -0,0 +1,303 @@
+//! Python code evaluated as background jobs.
+//!
+//! `eval` runs code on the HTTP worker serving the request, so a diagnostic
+//! that takes longer than the request timeout fails although it keeps
+//! running. A job instead runs the code in a thread of its own and returns
+//! its id right away. The output written by the job thread is collected as
+//! it comes, so clients poll it from an offset while the job runs, and a
+//! running job is cancelled by raising `KeyboardInterrupt` in its thread.
+//!
+//! The interrupt is raised when the thread next runs Python bytecode, a job
+//! blocked inside a native call is cancelled once the call returns.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use probing_proto::prelude::{EvalJob, EvalJobOutput, EvalJobState};
+use pyo3::exceptions::PyKeyboardInterrupt;
+use pyo3::prelude::*;
+use pyo3::{ffi, Python};
+
+use crate::repl::sessions;
+use crate::repl::PythonRepl;
+
+/// Finished jobs kept for polling, older ones are dropped first
+const MAX_FINISHED: usize = 64;
+
+/// Output kept per job, later output is dropped
+const MAX_OUTPUT_BYTES: usize = 4 << 20;
+
+struct Job {
+    info: EvalJob,
+    output: String,
+    /// Python ident of the thread running the job
+    thread: Option<u64>,
+    cancelled: bool,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+static JOBS: Lazy<Mutex<BTreeMap<u64, Job>>> = Lazy::new(|| Mutex::new(Default::default()));
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as i64
+}
+
+/// Collects the output of the job `id`, handed to Python as its stream
+#[pyclass]
+struct JobWriter {
+    id: u64,
+}
+
+#[pymethods]
+impl JobWriter {
+    fn write(&self, text: &str) -> usize {
+        append(self.id, text);
+        text.len()
+    }
+
+    fn flush(&self) {}
+}
+
+fn append(id: u64, text: &str) {
+    if let Some(job) = JOBS.lock().unwrap().get_mut(&id) {
+        let room = MAX_OUTPUT_BYTES.saturating_sub(job.output.len());
+        let mut end = text.len().min(room);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        job.output.push_str(&text[..end]);
+        job.info.output_bytes = job.output.len();
+    }
+}
+
+fn register(session: Option<String>) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut jobs = JOBS.lock().unwrap();
+    jobs.insert(
+        id,
+        Job {
+            info: EvalJob {
+                id,
+                session,
+                started: now(),
+                ..Default::default()
+            },
+            output: String::new(),
+            thread: None,
+            cancelled: false,
+        },
+    );
+    let finished = jobs
+        .values()
+        .filter(|job| job.info.state.is_finished())
+        .map(|job| job.info.id)
+        .collect::<Vec<_>>();
+    for id in finished
+        .iter()
+        .take(finished.len().saturating_sub(MAX_FINISHED))
+    {
+        jobs.remove(id);
+    }
+    id
+}
+
+fn finish(id: u64, result: Result<Option<String>, (bool, String)>) {
+    let mut jobs = JOBS.lock().unwrap();
+    let Some(job) = jobs.get_mut(&id) else {
+        return;
+    };
+    let (state, tail) = match result {
+        Ok(value) => (EvalJobState::Done, value),
+        Err((true, _)) if job.cancelled => (EvalJobState::Cancelled, None),
+        Err((_, err)) => (EvalJobState::Failed, Some(err)),
+    };
+    drop(jobs);
+    if let Some(tail) = tail {
+        append(id, &tail);
+        if !tail.ends_with('\n') {
+            append(id, "\n");
+        }
+    }
+    if let Some(job) = JOBS.lock().unwrap().get_mut(&id) {
+        job.info.state = state;
+        job.info.finished = Some(now());
+        job.thread = None;
+    }
+    log::info!("Eval job {id} {state}");
+}
+
+/// Start evaluating `code` in a new thread, in the namespace of the REPL
+/// `session` or in the main namespace without it
+pub fn start(code: String, session: Option<String>) -> Result<EvalJob, String> {
+    if let Some(session) = &session {
+        sessions::check_name(session)?;
+    }
+    let id = register(session.clone());
+    std::thread::Builder::new()
+        .name(format!("probing-eval-{id}"))
+        .spawn(move || {
+            let result = Python::with_gil(|py| {
+                // attached in the job thread, creating a session needs the GIL
+                let repl = match &session {
+                    Some(session) => Some(PythonRepl::attach(session).map_err(|e| (false, e))?),
+                    None => None,
+                };
+                run(py, id, &code, repl.as_ref())
+            });
+            finish(id, result);
+        })
+        .map_err(|e| {
+            JOBS.lock().unwrap().remove(&id);
+            format!("failed to start eval job: {e}")
+        })?;
+    log::info!("Eval job {id} started");
+    job(id).ok_or_else(|| format!("eval job {id} not found"))
+}
+
+fn run(
+    py: Python,
+    id: u64,
+    code: &str,
+    repl: Option<&PythonRepl>,
+) -> Result<Option<String>, (bool, String)> {
+    let failed = |err: PyErr| {
+        (
+            err.is_instance_of::<PyKeyboardInterrupt>(py),
+            err.to_string(),
+        )
+    };
+    let ident = py
+        .import("threading")
+        .and_then(|threading| threading.getattr("get_ident")?.call0()?.extract::<u64>())
+        .map_err(failed)?;
+    let cancelled = JOBS.lock().unwrap().get_mut(&id).is_some_and(|job| {
+        job.thread = Some(ident);
+        job.cancelled
+    });
+    // cancelled before the thread got the GIL
+    if cancelled {
+        return Err((true, "KeyboardInterrupt".to_string()));
+    }
+    let console = repl.and_then(|repl| repl.console_object(py));
+    let writer = Py::new(py, JobWriter { id }).map_err(failed)?;
+    let value = py
+        .import("probing.repl.jobs")
+        .and_then(|jobs| jobs.getattr("run")?.call1((code, console, writer)))
+        .map_err(|err| {
+            let traceback = err
+                .traceback(py)
+                .and_then(|tb| tb.format().ok())
+                .unwrap_or_default();
+            (
+                err.is_instance_of::<PyKeyboardInterrupt>(py),
+                format!("{traceback}{err}"),
+            )
+        })?;
+    value.extract::<Option<String>>().map_err(failed)
+}
+
+/// The job `id`
+pub fn job(id: u64) -> Option<EvalJob> {
+    JOBS.lock().unwrap().get(&id).map(|job| job.info.clone())
+}
+
+/// All jobs kept, oldest first
+pub fn jobs() -> Vec<EvalJob> {
+    JOBS.lock()
+        .unwrap()
+        .values()
+        .map(|job| job.info.clone())
+        .collect()
+}
+
+/// The output of job `id` written from byte `offset` on
+pub fn output(id: u64, offset: usize) -> Option<EvalJobOutput> {
+    let jobs = JOBS.lock().unwrap();
+    let job = jobs.get(&id)?;
+    let mut start = offset.min(job.output.len());
+    while !job.output.is_char_boundary(start) {
+        start += 1;
+    }
+    Some(EvalJobOutput {
+        job: job.info.clone(),
+        output: job.output[start..].to_string(),
+        offset: job.output.len(),
+    })
+}
+
+/// Interrupt the job `id` with a `KeyboardInterrupt`, finished jobs are left
+/// as they are
+pub fn cancel(py: Python, id: u64) -> Result<EvalJob, String> {
+    let mut jobs = JOBS.lock().unwrap();
+    let job = jobs
+        .get_mut(&id)
+        .ok_or_else(|| format!("eval job {id} not found"))?;
+    if job.info.state.is_finished() {
+        return Ok(job.info.clone());
+    }
+    // a job whose thread has not started yet stops when it does
+    if let Some(thread) = job.thread {
+        let exc = py.get_type::<PyKeyboardInterrupt>();
+        // SAFETY: called with the GIL held, `exc` is a live exception type
+        let found = unsafe { ffi::PyThreadState_SetAsyncExc(thread as _, exc.as_ptr()) };
+        if found == 0 {
+            return Err(format!("thread of eval job {id} not found"));
+        }
+    }
+    job.cancelled = true;
+    log::info!("Eval job {id} cancelled");
+    Ok(job.info.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_output() {
+        let id = register(None);
+        append(id, "héllo ");
+        append(id, "wörld\n");
+
+        let all = output(id, 0).unwrap();
+        assert_eq!(all.output, "héllo wörld\n");
+        assert_eq!(all.offset, "héllo wörld\n".len());
+        assert_eq!(all.job.state, EvalJobState::Running);
+        // an offset inside a character moves on to the next one
+        assert_eq!(output(id, 2).unwrap().output, "llo wörld\n");
+        assert_eq!(output(id, all.offset).unwrap().output, "");
+        assert_eq!(output(id, 1000).unwrap().offset, all.offset);
+
+        finish(id, Ok(Some("42".to_string())));
+        let done = output(id, all.offset).unwrap();
+        assert_eq!(done.output, "42\n");
+        assert_eq!(done.job.state, EvalJobState::Done);
+        assert!(done.job.finished.is_some());
+        assert!(output(u64::MAX, 0).is_none());
+    }
+
+    #[test]
+    fn test_job_results() {
+        let failed = register(None);
+        finish(failed, Err((false, "NameError: x".to_string())));
+        assert_eq!(job(failed).unwrap().state, EvalJobState::Failed);
+        assert_eq!(output(failed, 0).unwrap().output, "NameError: x\n");
+
+        // an interrupt nobody asked for is a failure of the code
+        let interrupted = register(None);
+        finish(interrupted, Err((true, "KeyboardInterrupt".to_string())));
+        assert_eq!(job(interrupted).unwrap().state, EvalJobState::Failed);
+
+        let cancelled = register(None);
+        JOBS.lock().unwrap().get_mut(&cancelled).unwrap().cancelled = true;
+        finish(cancelled, Err((true, "KeyboardInterrupt".to_string())));
+        assert_eq!(job(cancelled).unwrap().state, EvalJobState::Cancelled);
+        assert_eq!(output(cancelled, 0).unwrap().output, "");
+    }
+}
@@ -0,0 +1,299 @@
+//! Sharding state of FSDP and `fully_shard` models.
+//!
+//! `probing.profiling.fsdp` inspects the FSDP units of the process and times
+//! their all-gathers and reduce-scatters, then hands the results over to the
+//! tables below:
+//!
+//! - `torch.fsdp_units`: one row per unit with its shard size on this rank,
+//!   to spot units that hold far more memory than the others;
+//! - `torch.fsdp_params`: where each original parameter lives in the flat
+//!   parameter of its unit and how much of it this rank holds;
+//! - `torch.fsdp_collectives`: the last [`MAX_COLLECTIVES`] collectives.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    time, CustomTable, DataType, Field, Float64Array, Int64Array, RecordBatch, Schema, SchemaRef,
+    StringArray, TablePluginHelper,
+};
+use pyo3::prelude::*;
+
+/// Number of collectives kept, older ones are dropped first
+const MAX_COLLECTIVES: usize = 10_000;
+
+/// An FSDP unit, i.e. a module owning a flat parameter or a parameter group
+#[derive(Debug, Clone, FromPyObject)]
+#[pyo3(from_item_all)]
+pub struct FsdpUnit {
+    /// Fully qualified name of the module, empty for the root
+    pub unit: String,
+    /// `FSDP` or `fully_shard`
+    pub api: String,
+    pub rank: i64,
+    /// Ranks the parameters are sharded over
+    pub world_size: i64,
+    pub params: i64,
+    /// Elements of the unsharded parameters
+    pub numel: i64,
+    /// Elements held by this rank, padding included
+    pub shard_numel: i64,
+    pub padding: i64,
+    pub shard_bytes: i64,
+    pub dtype: String,
+}
+
+/// An original parameter inside its unit
+#[derive(Debug, Clone, FromPyObject)]
+#[pyo3(from_item_all)]
+pub struct FsdpParam {
+    pub unit: String,
+    pub param: String,
+    pub shape: String,
+    pub numel: i64,
+    /// Offset in the flat parameter, `None` with `fully_shard` which shards
+    /// each parameter on its own
+    pub offset: Option<i64>,
+    /// Elements of the parameter held by this rank
+    pub shard_numel: i64,
+}
+
+/// A timed all-gather or reduce-scatter
+#[derive(Debug, Clone, FromPyObject)]
+#[pyo3(from_item_all)]
+pub struct FsdpCollective {
+    /// Nanoseconds since the unix epoch
+    pub time: i64,
+    pub step: Option<i64>,
+    pub unit: String,
+    /// `all_gather` or `reduce_scatter`
+    pub op: String,
+    /// Seconds, measured on the device when possible
+    pub duration: f64,
+    pub bytes: i64,
+}
+
+pub static FSDP_UNITS: Lazy<Mutex<Vec<FsdpUnit>>> = Lazy::new(Default::default);
+pub static FSDP_PARAMS: Lazy<Mutex<Vec<FsdpParam>>> = Lazy::new(Default::default);
+pub static FSDP_COLLECTIVES: Lazy<Mutex<VecDeque<FsdpCollective>>> = Lazy::new(Default::default);
+
+/// Replace the sharding layout
+#[pyfunction]
+pub fn _set_fsdp_layout(units: Vec<FsdpUnit>, params: Vec<FsdpParam>) {
+    *FSDP_UNITS.lock().unwrap() = units;
+    *FSDP_PARAMS.lock().unwrap() = params;
+}
+
+/// Record timed collectives
+#[pyfunction]
+pub fn _record_fsdp_collectives(collectives: Vec<FsdpCollective>) {
+    let mut recorded = FSDP_COLLECTIVES.lock().unwrap();
+    for collective in collectives {
+        if recorded.len() >= MAX_COLLECTIVES {
+            recorded.pop_front();
+        }
+        recorded.push_back(collective);
+    }
+}
+
+pub fn register_fsdp_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_set_fsdp_layout, module)?)?;
+    module.add_function(wrap_pyfunction!(_record_fsdp_collectives, module)?)?;
+    Ok(())
+}
+
+fn to_batch(
+    schema: SchemaRef,
+    columns: Vec<Arc<dyn datafusion::arrow::array::Array>>,
+) -> Vec<RecordBatch> {
+    match RecordBatch::try_new(schema, columns) {
+        Ok(batch) => vec![batch],
+        Err(e) => {
+            log::error!("Failed to build fsdp batch: {e}");
+            vec![]
+        }
+    }
+}
+
+/// `torch.fsdp_units`
+#[derive(Default, Debug)]
+pub struct FsdpUnitsTable {}
+
+impl CustomTable for FsdpUnitsTable {
+    fn name() -> &'static str {
+        "fsdp_units"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("unit", DataType::Utf8, false),
+            Field::new("api", DataType::Utf8, false),
+            Field::new("rank", DataType::Int64, false),
+            Field::new("world_size", DataType::Int64, false),
+            Field::new("params", DataType::Int64, false),
+            Field::new("numel", DataType::Int64, false),
+            Field::new("shard_numel", DataType::Int64, false),
+            Field::new("padding", DataType::Int64, false),
+            Field::new("shard_bytes", DataType::Int64, false),
+            Field::new("dtype", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let units = FSDP_UNITS.lock().unwrap();
+        let int =
+            |f: fn(&FsdpUnit) -> i64| Arc::new(Int64Array::from_iter_values(units.iter().map(f)));
+        to_batch(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(units.iter().map(|u| &u.unit))),
+                Arc::new(StringArray::from_iter_values(units.iter().map(|u| &u.api))),
+                int(|u| u.rank),
+                int(|u| u.world_size),
+                int(|u| u.params),
+                int(|u| u.numel),
+                int(|u| u.shard_numel),
+                int(|u| u.padding),
+                int(|u| u.shard_bytes),
+                Arc::new(StringArray::from_iter_values(
+                    units.iter().map(|u| &u.dtype),
+                )),
+            ],
+        )
+    }
+}
+
+/// `torch.fsdp_params`
+#[derive(Default, Debug)]
+pub struct FsdpParamsTable {}
+
+impl CustomTable for FsdpParamsTable {
+    fn name() -> &'static str {
+        "fsdp_params"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("unit", DataType::Utf8, false),
+            Field::new("param", DataType::Utf8, false),
+            Field::new("shape", DataType::Utf8, false),
+            Field::new("numel", DataType::Int64, false),
+            Field::new("offset", DataType::Int64, true),
+            Field::new("shard_numel", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let params = FSDP_PARAMS.lock().unwrap();
+        to_batch(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    params.iter().map(|p| &p.unit),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    params.iter().map(|p| &p.param),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    params.iter().map(|p| &p.shape),
+                )),
+                Arc::new(Int64Array::from_iter_values(params.iter().map(|p| p.numel))),
+                Arc::new(Int64Array::from_iter(params.iter().map(|p| p.offset))),
+                Arc::new(Int64Array::from_iter_values(
+                    params.iter().map(|p| p.shard_numel),
+                )),
+            ],
+        )
+    }
+}
+
+/// `torch.fsdp_collectives`
+#[derive(Default, Debug)]
+pub struct FsdpCollectivesTable {}
+
+impl CustomTable for FsdpCollectivesTable {
+    fn name() -> &'static str {
+        "fsdp_collectives"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("step", DataType::Int64, true),
+            Field::new("unit", DataType::Utf8, false),
+            Field::new("op", DataType::Utf8, false),
+            Field::new("duration", DataType::Float64, false),
+            Field::new("bytes", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let collectives = FSDP_COLLECTIVES.lock().unwrap();
+        to_batch(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_ns_array(collectives.iter().map(|c| c.time))),
+                Arc::new(Int64Array::from_iter(collectives.iter().map(|c| c.step))),
+                Arc::new(StringArray::from_iter_values(
+                    collectives.iter().map(|c| &c.unit),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    collectives.iter().map(|c| &c.op),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    collectives.iter().map(|c| c.duration),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    collectives.iter().map(|c| c.bytes),
+                )),
+            ],
+        )
+    }
+}
+
+pub type FsdpUnitsPlugin = TablePluginHelper<FsdpUnitsTable>;
+pub type FsdpParamsPlugin = TablePluginHelper<FsdpParamsTable>;
+pub type FsdpCollectivesPlugin = TablePluginHelper<FsdpCollectivesTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_from_python() {
+        Python::with_gil(|py| {
+            let locals = pyo3::types::PyDict::new(py);
+            py.run(
+                c"units = [dict(unit='layers.0', api='FSDP', rank=0, world_size=2, \
+                  params=2, numel=10, shard_numel=6, padding=2, shard_bytes=24, \
+                  dtype='torch.float32')]\n\
+                  params = [dict(unit='layers.0', param='weight', shape='[2, 4]', \
+                  numel=8, offset=0, shard_numel=6), dict(unit='layers.0', \
+                  param='bias', shape='[2]', numel=2, offset=None, shard_numel=0)]",
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let units = locals
+                .get_item("units")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            let params = locals
+                .get_item("params")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            _set_fsdp_layout(units, params);
+        });
+
+        let units = FsdpUnitsTable::data();
+        assert_eq!(units[0].num_rows(), 1);
+        let params = FsdpParamsTable::data();
+        assert_eq!(params[0].num_rows(), 2);
+        assert_eq!(params[0].column(4).null_count(), 1);
+    }
+}
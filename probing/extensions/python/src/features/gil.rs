@@ -0,0 +1,241 @@
+//! Bounded waits for the GIL.
+//!
+//! A thread stuck while holding the GIL would block every request that needs
+//! Python. Requests served by the agent therefore wait for the GIL on a helper
+//! thread and give up after [`timeout`], so that the caller can fall back to
+//! cached data or to the signal based tracer. A helper that gave up keeps
+//! waiting in the background but no longer runs its work. While one is still
+//! waiting later requests wait for it, within their own timeout, instead of
+//! piling up threads, and are served again as soon as it gets the GIL.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use pyo3::{ffi, Python};
+
+use super::spy::ffi::PyGILState_Check;
+
+/// Default of `python.gil_timeout_ms`
+pub const DEFAULT_GIL_TIMEOUT_MS: u64 = 5000;
+
+/// Number of responses kept to be served while the GIL is unavailable
+const MAX_CACHED: usize = 64;
+
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_GIL_TIMEOUT_MS);
+
+/// Number of helpers that gave up and are still waiting for the GIL,
+/// notified when one gets it
+static ABANDONED: Lazy<(Mutex<usize>, Condvar)> = Lazy::new(Default::default);
+
+const WAITING: u8 = 0;
+const ACQUIRED: u8 = 1;
+const GAVE_UP: u8 = 2;
+
+/// How long to wait for the GIL, `None` waits forever
+pub fn timeout() -> Option<Duration> {
+    match TIMEOUT_MS.load(Ordering::Relaxed) {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+/// Set the GIL timeout in milliseconds, 0 waits forever
+pub fn set_timeout_ms(ms: u64) {
+    TIMEOUT_MS.store(ms, Ordering::Relaxed);
+}
+
+/// The GIL could not be acquired in time
+#[derive(Debug, Clone, Copy)]
+pub struct GilUnavailable {
+    pub waited: Duration,
+}
+
+impl std::fmt::Display for GilUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GIL not available after {}ms, a thread may be stuck holding it",
+            self.waited.as_millis()
+        )
+    }
+}
+
+impl std::error::Error for GilUnavailable {}
+
+/// Run `f` with the GIL, waiting at most [`timeout`] for it.
+///
+/// Returns the result with the time spent waiting for the GIL.
+pub fn with_gil_timeout<F, R>(f: F) -> Result<(R, Duration), GilUnavailable>
+where
+    F: for<'py> FnOnce(Python<'py>) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    with_gil_within(timeout(), f)
+}
+
+fn with_gil_within<F, R>(timeout: Option<Duration>, f: F) -> Result<(R, Duration), GilUnavailable>
+where
+    F: for<'py> FnOnce(Python<'py>) -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let start = Instant::now();
+    // a thread holding the GIL would deadlock with the helper
+    let holds_gil = unsafe { ffi::Py_IsInitialized() != 0 && PyGILState_Check() != 0 };
+    let Some(timeout) = timeout.filter(|_| !holds_gil) else {
+        return Ok(Python::with_gil(|py| {
+            let waited = start.elapsed();
+            (f(py), waited)
+        }));
+    };
+    {
+        let (abandoned, released) = &*ABANDONED;
+        let abandoned = abandoned.lock().unwrap();
+        let (abandoned, _) = released
+            .wait_timeout_while(abandoned, timeout, |n| *n > 0)
+            .unwrap();
+        if *abandoned > 0 {
+            return Err(GilUnavailable {
+                waited: start.elapsed(),
+            });
+        }
+    }
+
+    let state = Arc::new(AtomicU8::new(WAITING));
+    let (tx, rx) = mpsc::sync_channel(1);
+    let helper = {
+        let state = state.clone();
+        std::thread::Builder::new()
+            .name("probing-gil".to_string())
+            .spawn(move || {
+                Python::with_gil(|py| {
+                    let waited = start.elapsed();
+                    let acquired = state
+                        .compare_exchange(WAITING, ACQUIRED, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok();
+                    if acquired {
+                        let _ = tx.send((f(py), waited));
+                    } else {
+                        let (abandoned, released) = &*ABANDONED;
+                        *abandoned.lock().unwrap() -= 1;
+                        released.notify_all();
+                    }
+                })
+            })
+    };
+    if let Err(e) = helper {
+        log::error!("failed to start GIL helper thread: {e}");
+        return Err(GilUnavailable {
+            waited: start.elapsed(),
+        });
+    }
+
+    match rx.recv_timeout(timeout.saturating_sub(start.elapsed())) {
+        Ok(result) => Ok(result),
+        Err(_) => {
+            // counted under the lock, before the helper can count it out
+            let mut abandoned = ABANDONED.0.lock().unwrap();
+            let gave_up = state
+                .compare_exchange(WAITING, GAVE_UP, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok();
+            if gave_up {
+                *abandoned += 1;
+                return Err(GilUnavailable {
+                    waited: start.elapsed(),
+                });
+            }
+            drop(abandoned);
+            // acquired right at the deadline, the work is running
+            rx.recv().map_err(|_| GilUnavailable {
+                waited: start.elapsed(),
+            })
+        }
+    }
+}
+
+/// Last successful responses, served while the GIL is unavailable
+#[derive(Default)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, (Vec<u8>, Instant)>>,
+}
+
+impl ResponseCache {
+    fn key(path: &str, params: &HashMap<String, String>) -> String {
+        let mut params = params.iter().collect::<Vec<_>>();
+        params.sort();
+        format!("{path}?{params:?}")
+    }
+
+    pub fn store(&self, path: &str, params: &HashMap<String, String>, response: &[u8]) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_CACHED {
+            if let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, (_, at))| *at)
+                .map(|(key, _)| key.clone())
+            {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(Self::key(path, params), (response.to_vec(), Instant::now()));
+    }
+
+    /// The cached response with its age
+    pub fn get(&self, path: &str, params: &HashMap<String, String>) -> Option<(Vec<u8>, Duration)> {
+        let entries = self.entries.lock().unwrap();
+        let (response, at) = entries.get(&Self::key(path, params))?;
+        Some((response.clone(), at.elapsed()))
+    }
+}
+
+pub static RESPONSES: Lazy<ResponseCache> = Lazy::new(ResponseCache::default);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_cache() {
+        let cache = ResponseCache::default();
+        let params = HashMap::from([
+            ("b".to_string(), "2".to_string()),
+            ("a".to_string(), "1".to_string()),
+        ]);
+        assert!(cache.get("trace/list", &params).is_none());
+
+        cache.store("trace/list", &params, b"[1]");
+        let (response, _) = cache.get("trace/list", &params.clone()).unwrap();
+        assert_eq!(response, b"[1]");
+        assert!(cache.get("trace/list", &HashMap::new()).is_none());
+    }
+
+    #[test]
+    fn test_with_gil_timeout_runs_with_gil() {
+        let (value, _) = with_gil_timeout(|py| py.version().to_string()).unwrap();
+        assert!(!value.is_empty());
+    }
+
+    #[test]
+    fn test_recover_after_abandoned_call() {
+        let short = Some(Duration::from_millis(50));
+        let (held, is_held) = mpsc::channel();
+        let holder = std::thread::spawn(move || {
+            Python::with_gil(|_| {
+                held.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(300));
+            })
+        });
+        is_held.recv().unwrap();
+
+        // the first helper gives up, the next call waits for it in vain
+        assert!(with_gil_within(short, |_| ()).is_err());
+        let err = with_gil_within(short, |_| ()).unwrap_err();
+        assert!(err.waited >= Duration::from_millis(50));
+
+        holder.join().unwrap();
+        let (value, _) = with_gil_within(Some(Duration::from_secs(5)), |_| 1).unwrap();
+        assert_eq!(value, 1);
+    }
+}
@@ -0,0 +1,213 @@
+//! Gradient statistics per parameter group.
+//!
+//! Divergence is rarely caught while someone is attached, so
+//! `probing.profiling.grad_stats` keeps a history: with
+//! `torch.grad_stats=on` (or `every=<steps>`) hooks on the parameters read
+//! the gradients produced by backward on sampled steps, and every optimizer
+//! step adds a row per parameter group with the norm, the largest magnitude
+//! and the non-finite counts. The last [`MAX_ROWS`] are served as
+//! `torch.grad_stats`:
+//!
+//! ```sql
+//! SELECT step, "group", norm, nan_count FROM torch.grad_stats ORDER BY step DESC
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    time, CustomTable, DataType, Field, Float64Array, Int64Array, RecordBatch, Schema, SchemaRef,
+    StringArray, TablePluginHelper,
+};
+use pyo3::prelude::*;
+
+/// Number of rows kept, older ones are dropped first
+const MAX_ROWS: usize = 100_000;
+
+/// Gradients of a parameter group at one optimizer step
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GradStat {
+    /// Nanoseconds since the unix epoch
+    pub time: i64,
+    /// Optimizer steps since the statistics were switched on
+    pub step: i64,
+    /// Class of the optimizer, with its index when there are several
+    pub optimizer: String,
+    /// Index of the parameter group
+    pub group: i64,
+    /// `name` of the parameter group, empty if unset
+    pub name: String,
+    /// Parameters of the group that had a gradient
+    pub params: i64,
+    /// Elements of those gradients
+    pub numel: i64,
+    /// L2 norm of the gradients of the group
+    pub norm: f64,
+    /// Largest absolute finite value
+    pub max_abs: f64,
+    pub nan_count: i64,
+    pub inf_count: i64,
+}
+
+pub static GRAD_STATS: Lazy<Mutex<VecDeque<GradStat>>> = Lazy::new(Default::default);
+
+pub fn record(stat: GradStat) {
+    let mut stats = GRAD_STATS.lock().unwrap();
+    if stats.len() >= MAX_ROWS {
+        stats.pop_front();
+    }
+    stats.push_back(stat);
+}
+
+/// Record the gradients of a parameter group from Python; `time` defaults
+/// to now
+#[pyfunction]
+#[pyo3(signature = (
+    step,
+    optimizer,
+    group,
+    name,
+    params,
+    numel,
+    norm,
+    max_abs,
+    nan_count,
+    inf_count,
+    time=None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn _record_grad_stats(
+    step: i64,
+    optimizer: String,
+    group: i64,
+    name: String,
+    params: i64,
+    numel: i64,
+    norm: f64,
+    max_abs: f64,
+    nan_count: i64,
+    inf_count: i64,
+    time: Option<i64>,
+) {
+    let time = time.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_default()
+    });
+    record(GradStat {
+        time,
+        step,
+        optimizer,
+        group,
+        name,
+        params,
+        numel,
+        norm,
+        max_abs,
+        nan_count,
+        inf_count,
+    });
+}
+
+pub fn register_grad_stats_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_record_grad_stats, module)?)?;
+    Ok(())
+}
+
+/// `torch.grad_stats`: gradients of each parameter group at sampled steps
+#[derive(Default, Debug)]
+pub struct GradStatsTable {}
+
+impl CustomTable for GradStatsTable {
+    fn name() -> &'static str {
+        "grad_stats"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("step", DataType::Int64, false),
+            Field::new("optimizer", DataType::Utf8, false),
+            Field::new("group", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("params", DataType::Int64, false),
+            Field::new("numel", DataType::Int64, false),
+            Field::new("norm", DataType::Float64, false),
+            Field::new("max_abs", DataType::Float64, false),
+            Field::new("nan_count", DataType::Int64, false),
+            Field::new("inf_count", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let stats = GRAD_STATS.lock().unwrap();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_ns_array(stats.iter().map(|s| s.time))),
+                Arc::new(Int64Array::from_iter_values(stats.iter().map(|s| s.step))),
+                Arc::new(StringArray::from_iter_values(
+                    stats.iter().map(|s| &s.optimizer),
+                )),
+                Arc::new(Int64Array::from_iter_values(stats.iter().map(|s| s.group))),
+                Arc::new(StringArray::from_iter_values(stats.iter().map(|s| &s.name))),
+                Arc::new(Int64Array::from_iter_values(stats.iter().map(|s| s.params))),
+                Arc::new(Int64Array::from_iter_values(stats.iter().map(|s| s.numel))),
+                Arc::new(Float64Array::from_iter_values(stats.iter().map(|s| s.norm))),
+                Arc::new(Float64Array::from_iter_values(
+                    stats.iter().map(|s| s.max_abs),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.iter().map(|s| s.nan_count),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.iter().map(|s| s.inf_count),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build grad_stats batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type GradStatsPlugin = TablePluginHelper<GradStatsTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grad_stats_are_bounded() {
+        for i in 0..MAX_ROWS as i64 + 2 {
+            _record_grad_stats(
+                i,
+                "AdamW".into(),
+                0,
+                "".into(),
+                2,
+                10,
+                1.5,
+                0.5,
+                0,
+                0,
+                Some(i),
+            );
+        }
+        let stats = GRAD_STATS.lock().unwrap();
+        assert_eq!(stats.len(), MAX_ROWS);
+        assert_eq!(stats.front().unwrap().step, 2);
+        drop(stats);
+
+        let batches = GradStatsTable::data();
+        assert_eq!(batches[0].num_rows(), MAX_ROWS);
+    }
+}
@@ -0,0 +1,257 @@
+//! Metrics of inference servers running in the probed process.
+//!
+//! The integrations of `probing.ext.inference` scrape the stats kept by the
+//! engines (vLLM, ...) and hand them over to the tables below:
+//!
+//! - `inference.kv_cache`: periodic samples of the KV cache occupancy and of
+//!   the scheduler queues, with the preemptions in between;
+//! - `inference.requests`: one row per finished request with its latencies.
+//!
+//! Both keep the last [`MAX_ROWS`] rows, so latency spikes can be matched
+//! with the cache pressure at that time.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    time, CustomTable, DataType, Field, Float64Array, Int64Array, RecordBatch, Schema, SchemaRef,
+    StringArray, TablePluginHelper,
+};
+use pyo3::prelude::*;
+
+/// Number of rows kept per table, older ones are dropped first
+const MAX_ROWS: usize = 10_000;
+
+/// A sample of the KV cache of an engine
+#[derive(Debug, Clone, FromPyObject)]
+#[pyo3(from_item_all)]
+pub struct KvCacheSample {
+    /// Nanoseconds since the unix epoch
+    pub time: i64,
+    /// Integration, e.g. `vllm`
+    pub source: String,
+    /// Engine within the process
+    pub engine: String,
+    pub gpu_blocks: Option<i64>,
+    pub free_gpu_blocks: Option<i64>,
+    /// Fraction of the GPU cache in use
+    pub gpu_usage: Option<f64>,
+    pub cpu_usage: Option<f64>,
+    pub prefix_hit_rate: Option<f64>,
+    pub running: Option<i64>,
+    pub waiting: Option<i64>,
+    pub swapped: Option<i64>,
+    /// Requests whose blocks were evicted since the previous sample
+    pub preemptions: Option<i64>,
+}
+
+/// A finished request
+#[derive(Debug, Clone, FromPyObject)]
+#[pyo3(from_item_all)]
+pub struct RequestSample {
+    /// Completion time, nanoseconds since the unix epoch
+    pub time: i64,
+    pub source: String,
+    pub engine: String,
+    pub request_id: String,
+    pub prompt_tokens: Option<i64>,
+    pub output_tokens: Option<i64>,
+    /// Seconds spent waiting to be scheduled
+    pub queue: Option<f64>,
+    /// Seconds to the first token
+    pub ttft: Option<f64>,
+    /// Seconds from arrival to completion
+    pub e2e: Option<f64>,
+    pub finish_reason: Option<String>,
+}
+
+pub static KV_CACHE: Lazy<Mutex<VecDeque<KvCacheSample>>> = Lazy::new(Default::default);
+pub static REQUESTS: Lazy<Mutex<VecDeque<RequestSample>>> = Lazy::new(Default::default);
+
+fn push_bounded<T>(rows: &mut VecDeque<T>, new: Vec<T>) {
+    for row in new {
+        if rows.len() >= MAX_ROWS {
+            rows.pop_front();
+        }
+        rows.push_back(row);
+    }
+}
+
+/// Record KV cache samples
+#[pyfunction]
+pub fn _record_kv_cache(samples: Vec<KvCacheSample>) {
+    push_bounded(&mut KV_CACHE.lock().unwrap(), samples);
+}
+
+/// Record finished requests
+#[pyfunction]
+pub fn _record_inference_requests(requests: Vec<RequestSample>) {
+    push_bounded(&mut REQUESTS.lock().unwrap(), requests);
+}
+
+pub fn register_inference_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_record_kv_cache, module)?)?;
+    module.add_function(wrap_pyfunction!(_record_inference_requests, module)?)?;
+    Ok(())
+}
+
+fn to_batch(
+    schema: SchemaRef,
+    columns: Vec<Arc<dyn datafusion::arrow::array::Array>>,
+) -> Vec<RecordBatch> {
+    match RecordBatch::try_new(schema, columns) {
+        Ok(batch) => vec![batch],
+        Err(e) => {
+            log::error!("Failed to build inference batch: {e}");
+            vec![]
+        }
+    }
+}
+
+/// `inference.kv_cache`
+#[derive(Default, Debug)]
+pub struct KvCacheTable {}
+
+impl CustomTable for KvCacheTable {
+    fn name() -> &'static str {
+        "kv_cache"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("source", DataType::Utf8, false),
+            Field::new("engine", DataType::Utf8, false),
+            Field::new("gpu_blocks", DataType::Int64, true),
+            Field::new("free_gpu_blocks", DataType::Int64, true),
+            Field::new("gpu_usage", DataType::Float64, true),
+            Field::new("cpu_usage", DataType::Float64, true),
+            Field::new("prefix_hit_rate", DataType::Float64, true),
+            Field::new("running", DataType::Int64, true),
+            Field::new("waiting", DataType::Int64, true),
+            Field::new("swapped", DataType::Int64, true),
+            Field::new("preemptions", DataType::Int64, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let samples = KV_CACHE.lock().unwrap();
+        let int = |f: fn(&KvCacheSample) -> Option<i64>| {
+            Arc::new(Int64Array::from_iter(samples.iter().map(f)))
+        };
+        let float = |f: fn(&KvCacheSample) -> Option<f64>| {
+            Arc::new(Float64Array::from_iter(samples.iter().map(f)))
+        };
+        to_batch(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_ns_array(samples.iter().map(|s| s.time))),
+                Arc::new(StringArray::from_iter_values(
+                    samples.iter().map(|s| &s.source),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    samples.iter().map(|s| &s.engine),
+                )),
+                int(|s| s.gpu_blocks),
+                int(|s| s.free_gpu_blocks),
+                float(|s| s.gpu_usage),
+                float(|s| s.cpu_usage),
+                float(|s| s.prefix_hit_rate),
+                int(|s| s.running),
+                int(|s| s.waiting),
+                int(|s| s.swapped),
+                int(|s| s.preemptions),
+            ],
+        )
+    }
+}
+
+/// `inference.requests`
+#[derive(Default, Debug)]
+pub struct RequestsTable {}
+
+impl CustomTable for RequestsTable {
+    fn name() -> &'static str {
+        "requests"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("source", DataType::Utf8, false),
+            Field::new("engine", DataType::Utf8, false),
+            Field::new("request_id", DataType::Utf8, false),
+            Field::new("prompt_tokens", DataType::Int64, true),
+            Field::new("output_tokens", DataType::Int64, true),
+            Field::new("queue", DataType::Float64, true),
+            Field::new("ttft", DataType::Float64, true),
+            Field::new("e2e", DataType::Float64, true),
+            Field::new("finish_reason", DataType::Utf8, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let requests = REQUESTS.lock().unwrap();
+        let float = |f: fn(&RequestSample) -> Option<f64>| {
+            Arc::new(Float64Array::from_iter(requests.iter().map(f)))
+        };
+        to_batch(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_ns_array(requests.iter().map(|r| r.time))),
+                Arc::new(StringArray::from_iter_values(
+                    requests.iter().map(|r| &r.source),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    requests.iter().map(|r| &r.engine),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    requests.iter().map(|r| &r.request_id),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    requests.iter().map(|r| r.prompt_tokens),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    requests.iter().map(|r| r.output_tokens),
+                )),
+                float(|r| r.queue),
+                float(|r| r.ttft),
+                float(|r| r.e2e),
+                Arc::new(StringArray::from_iter(
+                    requests.iter().map(|r| r.finish_reason.as_deref()),
+                )),
+            ],
+        )
+    }
+}
+
+pub type KvCachePlugin = TablePluginHelper<KvCacheTable>;
+pub type RequestsPlugin = TablePluginHelper<RequestsTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_are_bounded() {
+        let sample = |time| RequestSample {
+            time,
+            source: "vllm".into(),
+            engine: "0".into(),
+            request_id: time.to_string(),
+            prompt_tokens: Some(16),
+            output_tokens: None,
+            queue: None,
+            ttft: Some(0.1),
+            e2e: Some(1.0),
+            finish_reason: None,
+        };
+        _record_inference_requests((0..MAX_ROWS as i64 + 10).map(sample).collect());
+
+        let batches = RequestsTable::data();
+        assert_eq!(batches[0].num_rows(), MAX_ROWS);
+        assert_eq!(REQUESTS.lock().unwrap().front().unwrap().time, 10);
+        assert_eq!(batches[0].column(9).null_count(), MAX_ROWS);
+    }
+}
@@ -0,0 +1,411 @@
+//! Backpressure of the external tables Python hooks append to.
+//!
+//! Hooks append rows as fast as the application produces them. What happens
+//! when a table is full is set per table with `ingest.policy`, a list of
+//! entries separated by `,`; an entry `<table>=<policy>` applies to one table
+//! and a bare policy to all others:
+//!
+//! - `drop-oldest` (default): the table's discard strategy drops its oldest
+//!   rows, as before;
+//! - `drop-newest`: rows appended while the table holds `ingest.capacity`
+//!   rows are dropped;
+//! - `block`: the appending thread stores the row itself instead of queuing
+//!   it, waiting with the GIL released until rows are pruned or up to
+//!   `ingest.block_timeout_ms`, then drops the row.
+//!
+//! ```text
+//! probing -t <pid> config ingest.policy=drop-newest,trace_event=block
+//! ```
+//!
+//! Every row appended, dropped or rejected is counted in `ingest.stats`, so
+//! users can tell how complete the data they query is. A table that starts
+//! dropping new rows also publishes a `throttled` event on `/events`,
+//! once until a row fits again.
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    CustomTable, DataType, Field, Int64Array, RecordBatch, Schema, SchemaRef, StringArray,
+    TablePluginHelper,
+};
+use probing_proto::prelude::{AgentEvent, EventKind, TimeSeries};
+
+/// Rows a table holds before `drop-newest` and `block` apply
+pub const DEFAULT_CAPACITY: usize = 1_000_000;
+
+/// Longest wait of a blocked append
+pub const DEFAULT_BLOCK_TIMEOUT_MS: u64 = 100;
+
+/// What to do with rows appended to a full table
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IngestPolicy {
+    #[default]
+    DropOldest,
+    DropNewest,
+    Block,
+}
+
+impl IngestPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngestPolicy::DropOldest => "drop-oldest",
+            IngestPolicy::DropNewest => "drop-newest",
+            IngestPolicy::Block => "block",
+        }
+    }
+}
+
+impl FromStr for IngestPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "drop-oldest" => Ok(IngestPolicy::DropOldest),
+            "drop-newest" => Ok(IngestPolicy::DropNewest),
+            "block" => Ok(IngestPolicy::Block),
+            other => Err(anyhow!(
+                "unknown ingest policy `{other}`, expected drop-oldest, drop-newest or block"
+            )),
+        }
+    }
+}
+
+/// Policies set with `ingest.policy`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Policies {
+    pub default: IngestPolicy,
+    pub tables: HashMap<String, IngestPolicy>,
+}
+
+impl Policies {
+    pub fn get(&self, table: &str) -> IngestPolicy {
+        self.tables.get(table).copied().unwrap_or(self.default)
+    }
+}
+
+impl FromStr for Policies {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut policies = Policies::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((table, policy)) => {
+                    policies
+                        .tables
+                        .insert(table.trim().to_string(), policy.parse()?);
+                }
+                None => policies.default = entry.parse()?,
+            }
+        }
+        Ok(policies)
+    }
+}
+
+struct Config {
+    policies: Policies,
+    capacity: usize,
+    block_timeout: Duration,
+}
+
+static CONFIG: Lazy<RwLock<Config>> = Lazy::new(|| {
+    RwLock::new(Config {
+        policies: Default::default(),
+        capacity: DEFAULT_CAPACITY,
+        block_timeout: Duration::from_millis(DEFAULT_BLOCK_TIMEOUT_MS),
+    })
+});
+
+/// Counters of one table
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IngestStats {
+    pub appended: u64,
+    pub dropped_oldest: u64,
+    pub dropped_newest: u64,
+    /// Rows that did not fit the schema of the table
+    pub rejected: u64,
+    /// Appends that had to wait for room
+    pub blocked: u64,
+    pub blocked_us: u64,
+    /// Whether the last row appended was dropped
+    pub dropping: bool,
+}
+
+static STATS: Lazy<Mutex<BTreeMap<String, IngestStats>>> = Lazy::new(Default::default);
+
+/// Serializes the tests changing the ingest config, which is global
+#[cfg(test)]
+pub(crate) static TEST_CONFIG: Mutex<()> = Mutex::new(());
+
+/// Woken whenever rows are removed from a table, for blocked appends
+static ROOM: Lazy<(Mutex<()>, Condvar)> = Lazy::new(Default::default);
+
+pub fn set_policies(spec: &str) -> Result<()> {
+    CONFIG.write().unwrap().policies = spec.parse()?;
+    notify_room();
+    Ok(())
+}
+
+pub fn set_capacity(capacity: usize) -> Result<()> {
+    if capacity == 0 {
+        return Err(anyhow!("ingest capacity must be positive"));
+    }
+    CONFIG.write().unwrap().capacity = capacity;
+    notify_room();
+    Ok(())
+}
+
+pub fn set_block_timeout(timeout: Duration) {
+    CONFIG.write().unwrap().block_timeout = timeout;
+}
+
+/// Policy applying to `table`
+pub fn policy(table: &str) -> IngestPolicy {
+    CONFIG.read().unwrap().policies.get(table)
+}
+
+fn is_full(policy: IngestPolicy, rows: usize) -> bool {
+    policy != IngestPolicy::DropOldest && rows >= CONFIG.read().unwrap().capacity
+}
+
+fn update<R>(table: &str, f: impl FnOnce(&mut IngestStats) -> R) -> R {
+    f(STATS.lock().unwrap().entry(table.to_string()).or_default())
+}
+
+/// Tell event subscribers that `table` started dropping new rows
+fn throttled(table: &str) {
+    let policy = policy(table);
+    probing_core::events::publish(
+        AgentEvent::new(
+            EventKind::Throttled,
+            "ingest",
+            format!("{table} is full, new rows are dropped"),
+        )
+        .with_detail("table", table)
+        .with_detail("policy", policy.as_str()),
+    );
+}
+
+/// Tell blocked appends that rows were removed from a table
+pub fn notify_room() {
+    let _guard = ROOM.0.lock().unwrap();
+    ROOM.1.notify_all();
+}
+
+/// Lock `ts`, first waiting until it has room for a row if `table` is full
+/// and blocks, up to `ingest.block_timeout_ms`. The row is appended under the
+/// returned guard, so concurrent appends cannot overfill the table. Call it
+/// with the GIL released.
+pub fn wait_for_room<'a>(table: &str, ts: &'a Mutex<TimeSeries>) -> MutexGuard<'a, TimeSeries> {
+    let locked = ts.lock().unwrap();
+    if policy(table) != IngestPolicy::Block || !is_full(IngestPolicy::Block, locked.retained()) {
+        return locked;
+    }
+    drop(locked);
+    let timeout = CONFIG.read().unwrap().block_timeout;
+    let start = Instant::now();
+    // hold the room lock between checking and waiting so that no wake-up is lost
+    let mut guard = ROOM.0.lock().unwrap();
+    let locked = loop {
+        let locked = ts.lock().unwrap();
+        if !is_full(policy(table), locked.retained()) {
+            break locked;
+        }
+        let Some(left) = timeout.checked_sub(start.elapsed()) else {
+            break locked;
+        };
+        drop(locked);
+        guard = ROOM.1.wait_timeout(guard, left).unwrap().0;
+    };
+    drop(guard);
+    update(table, |stats| {
+        stats.blocked += 1;
+        stats.blocked_us += start.elapsed().as_micros() as u64;
+    });
+    locked
+}
+
+/// Append a row to `ts` with `append`, unless the policy of `table` drops
+/// it; `None` tells the row was dropped
+pub fn append<T, E>(
+    table: &str,
+    ts: &mut TimeSeries,
+    append: impl FnOnce(&mut TimeSeries) -> Result<T, E>,
+) -> Option<Result<T, E>> {
+    if is_full(policy(table), ts.retained()) {
+        let started = update(table, |stats| {
+            stats.dropped_newest += 1;
+            !std::mem::replace(&mut stats.dropping, true)
+        });
+        if started {
+            throttled(table);
+        }
+        return None;
+    }
+    let discarded = ts.discarded();
+    let result = append(ts);
+    if result.is_ok() {
+        let dropped = ts.discarded().saturating_sub(discarded) as u64;
+        update(table, |stats| {
+            stats.appended += 1;
+            stats.dropped_oldest += dropped;
+            stats.dropping = false;
+        });
+    }
+    Some(result)
+}
+
+/// Count a row `table` rejected
+pub fn rejected(table: &str) {
+    update(table, |stats| stats.rejected += 1);
+}
+
+/// Counters of every table, by name
+pub fn stats() -> BTreeMap<String, IngestStats> {
+    STATS.lock().unwrap().clone()
+}
+
+/// Forget the counters of a dropped table
+pub fn forget(table: &str) {
+    STATS.lock().unwrap().remove(table);
+    notify_room();
+}
+
+/// `ingest.stats`: rows appended and dropped per external table
+#[derive(Default, Debug)]
+pub struct IngestStatsTable {}
+
+impl CustomTable for IngestStatsTable {
+    fn name() -> &'static str {
+        "stats"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("table", DataType::Utf8, false),
+            Field::new("policy", DataType::Utf8, false),
+            Field::new("rows", DataType::Int64, false),
+            Field::new("capacity", DataType::Int64, false),
+            Field::new("appended", DataType::Int64, false),
+            Field::new("dropped_oldest", DataType::Int64, false),
+            Field::new("dropped_newest", DataType::Int64, false),
+            Field::new("rejected", DataType::Int64, false),
+            Field::new("blocked", DataType::Int64, false),
+            Field::new("blocked_ms", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let mut stats = stats();
+        let tables = crate::extensions::python::EXTERN_TABLES
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, ts)| (name.clone(), ts.clone()))
+            .collect::<Vec<_>>();
+        let mut rows = HashMap::new();
+        for (name, ts) in tables {
+            rows.insert(name.clone(), ts.lock().unwrap().retained() as i64);
+            stats.entry(name).or_default();
+        }
+        let capacity = CONFIG.read().unwrap().capacity as i64;
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(stats.keys())),
+                Arc::new(StringArray::from_iter_values(
+                    stats.keys().map(|name| policy(name).as_str()),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats
+                        .keys()
+                        .map(|name| rows.get(name).copied().unwrap_or(0)),
+                )),
+                Arc::new(Int64Array::from_iter_values(stats.keys().map(|_| capacity))),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.values().map(|s| s.appended as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.values().map(|s| s.dropped_oldest as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.values().map(|s| s.dropped_newest as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.values().map(|s| s.rejected as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.values().map(|s| s.blocked as i64),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    stats.values().map(|s| (s.blocked_us / 1000) as i64),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build ingest.stats: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type IngestStatsPlugin = TablePluginHelper<IngestStatsTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policies() {
+        let policies: Policies = "drop-newest, trace_event=block,torch_trace=DROP_OLDEST"
+            .parse()
+            .unwrap();
+        assert_eq!(policies.default, IngestPolicy::DropNewest);
+        assert_eq!(policies.get("trace_event"), IngestPolicy::Block);
+        assert_eq!(policies.get("torch_trace"), IngestPolicy::DropOldest);
+        assert_eq!(policies.get("metrics"), IngestPolicy::DropNewest);
+
+        assert_eq!("".parse::<Policies>().unwrap(), Policies::default());
+        assert!("trace_event=sometimes".parse::<Policies>().is_err());
+    }
+
+    #[test]
+    fn test_drop_newest_counts_rows() {
+        let _config = TEST_CONFIG.lock().unwrap();
+        let mut events = probing_core::events::subscribe();
+        set_policies("ingest_test_full=drop-newest").unwrap();
+        set_capacity(2).unwrap();
+        let mut ts = TimeSeries::builder()
+            .with_columns(vec!["a".to_string()])
+            .build();
+        for i in 0..5i64 {
+            let _ = append("ingest_test_full", &mut ts, |ts| {
+                ts.append(i.into(), vec![i.into()])
+            });
+        }
+        set_capacity(DEFAULT_CAPACITY).unwrap();
+        set_policies("").unwrap();
+
+        assert_eq!(ts.retained(), 2);
+        let stats = stats().remove("ingest_test_full").unwrap();
+        assert_eq!(stats.appended, 2);
+        assert_eq!(stats.dropped_newest, 3);
+        assert_eq!(stats.dropped_oldest, 0);
+        assert!(stats.dropping);
+
+        // one event for the three rows dropped in a row
+        let throttled = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|e| e.kind == EventKind::Throttled && e.message.starts_with("ingest_test_full"))
+            .count();
+        assert_eq!(throttled, 1);
+    }
+}
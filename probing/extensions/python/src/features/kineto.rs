@@ -0,0 +1,302 @@
+//! Kineto traces of `torch.profiler` as tables.
+//!
+//! `probing.profiling.kineto.load` parses the Chrome trace JSON exported by
+//! the profiler, the same document the timeline endpoint returns, and keeps
+//! its events here. They are served as `kineto.events` with timestamps in
+//! nanoseconds since the unix epoch, so GPU kernels can be joined with the
+//! probing spans recorded around them:
+//!
+//! ```sql
+//! SELECT s.name, sum(k.duration) FROM python.spans s JOIN kineto.events k
+//!   ON k.start >= s.start AND k.start < s."end"
+//! WHERE k.cat = 'kernel' GROUP BY s.name
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    CustomTable, DataType, Field, Int64Array, RecordBatch, Schema, SchemaRef, StringArray,
+    TablePluginHelper,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde_json::{Map, Value};
+
+/// Number of events kept, those of the oldest traces are dropped first
+const MAX_EVENTS: usize = 1_000_000;
+
+/// One event of a Kineto trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct KinetoEvent {
+    /// Trace the event was loaded from
+    pub trace: String,
+    pub name: String,
+    /// `cpu_op`, `kernel`, `gpu_memcpy`, `cuda_runtime`, ...
+    pub cat: String,
+    /// Chrome trace phase, `X` for complete events
+    pub ph: String,
+    /// Nanoseconds since the unix epoch
+    pub start: i64,
+    /// Nanoseconds, `None` for instant events
+    pub duration: Option<i64>,
+    pub pid: Option<i64>,
+    pub tid: Option<i64>,
+    pub device: Option<i64>,
+    pub stream: Option<i64>,
+    /// Links a kernel to the runtime call that launched it
+    pub correlation: Option<i64>,
+    /// Links runtime calls to the operator that issued them
+    pub external_id: Option<i64>,
+    /// Remaining arguments, as a JSON object
+    pub args: String,
+}
+
+pub static KINETO_EVENTS: Lazy<Mutex<VecDeque<KinetoEvent>>> = Lazy::new(Default::default);
+
+fn as_i64(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Microseconds, as written in the trace, to nanoseconds
+fn us_to_ns(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Number(n) => n.as_f64().map(|us| (us * 1000.0).round() as i64),
+        Value::String(s) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|us| (us * 1000.0).round() as i64),
+        _ => None,
+    }
+}
+
+/// Parse the events of a Chrome trace exported by `torch.profiler`
+///
+/// Metadata events (`ph` = `M`) only name processes and threads and are
+/// skipped. Recent Kineto versions write timestamps relative to
+/// `baseTimeNanoseconds`, older ones since the epoch.
+pub fn parse(trace: &str, json: &str) -> Result<Vec<KinetoEvent>> {
+    let document: Value =
+        serde_json::from_str(json).map_err(|e| anyhow!("invalid Kineto trace: {e}"))?;
+    let (events, base) = match &document {
+        Value::Object(object) => (
+            object.get("traceEvents"),
+            as_i64(object.get("baseTimeNanoseconds")).unwrap_or_default(),
+        ),
+        // the array form of the Chrome trace format
+        Value::Array(_) => (Some(&document), 0),
+        _ => (None, 0),
+    };
+    let Some(Value::Array(events)) = events else {
+        return Err(anyhow!("invalid Kineto trace: no traceEvents"));
+    };
+
+    let empty = Map::new();
+    Ok(events
+        .iter()
+        .filter_map(Value::as_object)
+        .filter_map(|event| {
+            let ph = event.get("ph").and_then(Value::as_str).unwrap_or_default();
+            if ph == "M" {
+                return None;
+            }
+            let start = base + us_to_ns(event.get("ts"))?;
+            let text = |key| {
+                event
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let mut args = event
+                .get("args")
+                .and_then(Value::as_object)
+                .unwrap_or(&empty)
+                .clone();
+            Some(KinetoEvent {
+                trace: trace.to_string(),
+                name: text("name"),
+                cat: text("cat"),
+                ph: ph.to_string(),
+                start,
+                duration: us_to_ns(event.get("dur")),
+                pid: as_i64(event.get("pid")),
+                tid: as_i64(event.get("tid")),
+                device: as_i64(args.remove("device").as_ref()),
+                stream: as_i64(args.remove("stream").as_ref()),
+                correlation: as_i64(args.remove("correlation").as_ref()),
+                external_id: as_i64(args.remove("External id").as_ref()),
+                args: Value::Object(args).to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Load the events of `json` as `trace`, replacing those of a previous load
+/// with the same name; returns the number of events loaded
+pub fn load(trace: &str, json: &str) -> Result<usize> {
+    let loaded = parse(trace, json)?;
+    let count = loaded.len();
+
+    let mut events = KINETO_EVENTS.lock().unwrap();
+    events.retain(|e| e.trace != trace);
+    events.extend(loaded);
+    let excess = events.len().saturating_sub(MAX_EVENTS);
+    events.drain(..excess);
+    Ok(count)
+}
+
+/// Load a Chrome trace from Python, see [`load`]
+#[pyfunction]
+pub fn _load_kineto_trace(trace: &str, json: &str) -> PyResult<usize> {
+    load(trace, json).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Drop the events of `trace`, or of every trace
+#[pyfunction]
+#[pyo3(signature = (trace=None))]
+pub fn _clear_kineto_traces(trace: Option<&str>) {
+    let mut events = KINETO_EVENTS.lock().unwrap();
+    match trace {
+        Some(trace) => events.retain(|e| e.trace != trace),
+        None => events.clear(),
+    }
+}
+
+pub fn register_kineto_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_load_kineto_trace, module)?)?;
+    module.add_function(wrap_pyfunction!(_clear_kineto_traces, module)?)?;
+    Ok(())
+}
+
+/// `kineto.events`: events of the loaded `torch.profiler` traces
+#[derive(Default, Debug)]
+pub struct KinetoTable {}
+
+impl CustomTable for KinetoTable {
+    fn name() -> &'static str {
+        "events"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("trace", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("cat", DataType::Utf8, false),
+            Field::new("ph", DataType::Utf8, false),
+            Field::new("start", DataType::Int64, false),
+            Field::new("duration", DataType::Int64, true),
+            Field::new("pid", DataType::Int64, true),
+            Field::new("tid", DataType::Int64, true),
+            Field::new("device", DataType::Int64, true),
+            Field::new("stream", DataType::Int64, true),
+            Field::new("correlation", DataType::Int64, true),
+            Field::new("external_id", DataType::Int64, true),
+            Field::new("args", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let events = KINETO_EVENTS.lock().unwrap();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| &e.trace),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| &e.name),
+                )),
+                Arc::new(StringArray::from_iter_values(events.iter().map(|e| &e.cat))),
+                Arc::new(StringArray::from_iter_values(events.iter().map(|e| &e.ph))),
+                Arc::new(Int64Array::from_iter_values(events.iter().map(|e| e.start))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.duration))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.pid))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.tid))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.device))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.stream))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.correlation))),
+                Arc::new(Int64Array::from_iter(events.iter().map(|e| e.external_id))),
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| &e.args),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build kineto batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type KinetoPlugin = TablePluginHelper<KinetoTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE: &str = r#"{
+        "schemaVersion": 1,
+        "baseTimeNanoseconds": 1700000000000000000,
+        "traceEvents": [
+            {"ph": "M", "name": "process_name", "pid": 0, "args": {"name": "python"}},
+            {"ph": "X", "cat": "cpu_op", "name": "aten::mm", "pid": 4242, "tid": 4242,
+             "ts": 10.5, "dur": 20, "args": {"External id": 7, "Input Dims": [[2, 2]]}},
+            {"ph": "X", "cat": "cuda_runtime", "name": "cudaLaunchKernel", "pid": 4242,
+             "tid": 4242, "ts": 12, "dur": 3, "args": {"External id": 7, "correlation": 99}},
+            {"ph": "X", "cat": "kernel", "name": "gemm", "pid": 0, "tid": "7",
+             "ts": 40, "dur": 5.25,
+             "args": {"device": 0, "stream": 7, "correlation": 99, "grid": [1, 1, 1]}},
+            {"ph": "i", "cat": "cpu_instant_event", "name": "mark", "pid": 4242,
+             "tid": 4242, "ts": 50, "s": "t"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_kineto_trace() {
+        let events = parse("step", TRACE).unwrap();
+        assert_eq!(events.len(), 4);
+
+        let op = &events[0];
+        assert_eq!(op.name, "aten::mm");
+        assert_eq!(op.start, 1700000000000010500);
+        assert_eq!(op.duration, Some(20_000));
+        assert_eq!(op.external_id, Some(7));
+        assert_eq!(op.args, r#"{"Input Dims":[[2,2]]}"#);
+
+        let kernel = &events[2];
+        assert_eq!(kernel.cat, "kernel");
+        assert_eq!(kernel.tid, Some(7));
+        assert_eq!(kernel.duration, Some(5_250));
+        assert_eq!((kernel.device, kernel.stream), (Some(0), Some(7)));
+        assert_eq!(kernel.correlation, events[1].correlation);
+
+        assert_eq!(events[3].duration, None);
+        assert!(parse("step", "[]").unwrap().is_empty());
+        assert!(parse("step", r#"{"traceEvents": 1}"#).is_err());
+        assert!(parse("step", "not json").is_err());
+    }
+
+    #[test]
+    fn test_load_replaces_trace() {
+        assert_eq!(load("a", TRACE).unwrap(), 4);
+        assert_eq!(load("b", TRACE).unwrap(), 4);
+        assert_eq!(load("a", TRACE).unwrap(), 4);
+
+        assert_eq!(KinetoTable::data()[0].num_rows(), 8);
+        _clear_kineto_traces(Some("a"));
+        assert_eq!(KinetoTable::data()[0].num_rows(), 4);
+        _clear_kineto_traces(Some("b"));
+    }
+}
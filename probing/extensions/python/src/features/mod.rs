@@ -1,9 +1,34 @@
+pub mod anomaly;
 pub mod config;
 pub mod convert;
+pub mod eval_jobs;
+#[cfg(feature = "analytics")]
+pub mod dynamo;
+#[cfg(feature = "analytics")]
+pub mod fsdp;
+pub mod gil;
+#[cfg(feature = "analytics")]
+pub mod grad_stats;
+#[cfg(feature = "analytics")]
+pub mod inference;
+pub mod ingest;
+#[cfg(feature = "analytics")]
+pub mod kineto;
+#[cfg(feature = "analytics")]
+pub mod oom;
+pub mod op_summary;
 pub mod pprof;
+pub mod privacy;
 pub mod python_api;
+pub mod safepoint;
+pub mod shutdown;
+pub mod signals;
 pub mod spy;
 pub mod stack_tracer;
+pub mod stacks;
+pub mod symbolizer;
+#[cfg(feature = "analytics")]
+pub mod tasks;
 pub mod torch;
 pub mod tracing;
 pub mod vm_tracer;
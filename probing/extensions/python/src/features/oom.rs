@@ -0,0 +1,234 @@
+//! Forensics of CUDA out of memory errors.
+//!
+//! `probing.profiling.oom` attaches an observer to the CUDA caching allocator
+//! that runs when an allocation fails, before `torch.cuda.OutOfMemoryError`
+//! is raised. It records what was using the memory at that moment: allocator
+//! statistics, a summary of the memory snapshot, the spans active in the
+//! failing thread and the largest live tensors. The last [`MAX_REPORTS`] are
+//! served as `oom.reports`, and survive the exception unwinding the stack:
+//!
+//! ```sql
+//! SELECT time, device, requested, free, spans, tensors FROM oom.reports
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    time, CustomTable, DataType, Field, Int64Array, RecordBatch, Schema, SchemaRef, StringArray,
+    TablePluginHelper,
+};
+use pyo3::prelude::*;
+
+/// Number of reports kept, older ones are dropped first
+const MAX_REPORTS: usize = 100;
+
+/// State of a device when an allocation failed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OomReport {
+    /// Nanoseconds since the unix epoch
+    pub time: i64,
+    /// Optimizer step, `None` before the first step
+    pub step: Option<i64>,
+    pub device: i64,
+    /// Bytes of the failed allocation
+    pub requested: i64,
+    /// Bytes allocated by the caching allocator
+    pub allocated: i64,
+    /// Bytes reserved by the caching allocator
+    pub reserved: i64,
+    /// Bytes the device reported free
+    pub free: i64,
+    /// Message of the exception, empty when reported by the allocator
+    pub message: String,
+    /// Active spans of the failing thread, outermost first, separated by ` > `
+    pub spans: String,
+    /// Largest live tensors as a JSON list
+    pub tensors: String,
+    /// `torch.cuda.memory_stats()` as JSON
+    pub stats: String,
+    /// Summary of `torch.cuda.memory_snapshot()` as JSON
+    pub snapshot: String,
+}
+
+pub static OOM_REPORTS: Lazy<Mutex<VecDeque<OomReport>>> = Lazy::new(Default::default);
+
+pub fn record(report: OomReport) {
+    let mut reports = OOM_REPORTS.lock().unwrap();
+    if reports.len() >= MAX_REPORTS {
+        reports.pop_front();
+    }
+    reports.push_back(report);
+}
+
+/// Record an out of memory report from Python; `time` defaults to now
+#[pyfunction]
+#[pyo3(signature = (
+    device,
+    requested,
+    allocated,
+    reserved,
+    free,
+    message="".to_string(),
+    spans="".to_string(),
+    tensors="[]".to_string(),
+    stats="{}".to_string(),
+    snapshot="{}".to_string(),
+    step=None,
+    time=None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn _record_oom_report(
+    device: i64,
+    requested: i64,
+    allocated: i64,
+    reserved: i64,
+    free: i64,
+    message: String,
+    spans: String,
+    tensors: String,
+    stats: String,
+    snapshot: String,
+    step: Option<i64>,
+    time: Option<i64>,
+) {
+    let time = time.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_default()
+    });
+    log::warn!(
+        "CUDA out of memory on device {device}: {requested} bytes requested, \
+         {allocated} allocated, {free} free"
+    );
+    record(OomReport {
+        time,
+        step,
+        device,
+        requested,
+        allocated,
+        reserved,
+        free,
+        message,
+        spans,
+        tensors,
+        stats,
+        snapshot,
+    });
+}
+
+pub fn register_oom_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_record_oom_report, module)?)?;
+    Ok(())
+}
+
+/// `oom.reports`: state of the device at each CUDA out of memory error
+#[derive(Default, Debug)]
+pub struct OomTable {}
+
+impl CustomTable for OomTable {
+    fn name() -> &'static str {
+        "reports"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", time::timestamp_ns_type(), false),
+            Field::new("step", DataType::Int64, true),
+            Field::new("device", DataType::Int64, false),
+            Field::new("requested", DataType::Int64, false),
+            Field::new("allocated", DataType::Int64, false),
+            Field::new("reserved", DataType::Int64, false),
+            Field::new("free", DataType::Int64, false),
+            Field::new("message", DataType::Utf8, false),
+            Field::new("spans", DataType::Utf8, false),
+            Field::new("tensors", DataType::Utf8, false),
+            Field::new("stats", DataType::Utf8, false),
+            Field::new("snapshot", DataType::Utf8, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let reports = OOM_REPORTS.lock().unwrap();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(time::timestamp_ns_array(reports.iter().map(|r| r.time))),
+                Arc::new(Int64Array::from_iter(reports.iter().map(|r| r.step))),
+                Arc::new(Int64Array::from_iter_values(
+                    reports.iter().map(|r| r.device),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    reports.iter().map(|r| r.requested),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    reports.iter().map(|r| r.allocated),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    reports.iter().map(|r| r.reserved),
+                )),
+                Arc::new(Int64Array::from_iter_values(reports.iter().map(|r| r.free))),
+                Arc::new(StringArray::from_iter_values(
+                    reports.iter().map(|r| &r.message),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    reports.iter().map(|r| &r.spans),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    reports.iter().map(|r| &r.tensors),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    reports.iter().map(|r| &r.stats),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    reports.iter().map(|r| &r.snapshot),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build oom batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type OomPlugin = TablePluginHelper<OomTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_are_bounded() {
+        for i in 0..MAX_REPORTS as i64 + 3 {
+            _record_oom_report(
+                0,
+                1 << 30,
+                i,
+                2 * i,
+                0,
+                "".into(),
+                "step > forward".into(),
+                "[]".into(),
+                "{}".into(),
+                "{}".into(),
+                None,
+                Some(i),
+            );
+        }
+        let reports = OOM_REPORTS.lock().unwrap();
+        assert_eq!(reports.len(), MAX_REPORTS);
+        assert_eq!(reports.front().unwrap().time, 3);
+        drop(reports);
+
+        let batches = OomTable::data();
+        assert_eq!(batches[0].num_rows(), MAX_REPORTS);
+    }
+}
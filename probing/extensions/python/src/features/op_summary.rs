@@ -0,0 +1,307 @@
+//! Incremental per-operator summary of `python.torch_trace`.
+//!
+//! Rows are folded into the summary as they are appended to the trace table,
+//! so `torch.op_summary` and the torch flamegraph read a few hundred
+//! aggregated rows instead of re-aggregating the full raw table. When rows
+//! leave the table, pruned or discarded to keep it within its limits, the
+//! summary is rebuilt from the rows that are left.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    CustomTable, DataType, Field, Float64Array, Int64Array, RecordBatch, Schema, SchemaRef,
+    StringArray, TablePluginHelper,
+};
+use probing_proto::prelude::{Ele, TimeSeries};
+
+/// Name of the external table written by the torch profiler
+pub const TORCH_TRACE_TABLE: &str = "torch_trace";
+
+/// Relative accuracy of the quantiles reported by [`QuantileSketch`]
+const RELATIVE_ACCURACY: f64 = 0.01;
+
+/// Streaming quantile estimator with logarithmic buckets
+///
+/// Values are mapped to buckets whose bounds grow by a constant factor, so any
+/// quantile is reported within [`RELATIVE_ACCURACY`] of the exact value while
+/// memory only grows with the dynamic range of the inputs.
+#[derive(Debug, Clone, Default)]
+pub struct QuantileSketch {
+    /// Values that are zero or negative
+    zeros: u64,
+    buckets: BTreeMap<i32, u64>,
+    count: u64,
+}
+
+impl QuantileSketch {
+    fn gamma() -> f64 {
+        (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY)
+    }
+
+    pub fn insert(&mut self, value: f64) {
+        self.count += 1;
+        if value <= 0.0 || !value.is_finite() {
+            self.zeros += 1;
+            return;
+        }
+        let index = (value.ln() / Self::gamma().ln()).ceil() as i32;
+        *self.buckets.entry(index).or_default() += 1;
+    }
+
+    /// Estimated `q`-quantile, `None` if the sketch is empty
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64) as u64;
+        if rank < self.zeros {
+            return Some(0.0);
+        }
+        let gamma = Self::gamma();
+        let mut seen = self.zeros;
+        for (index, count) in &self.buckets {
+            seen += count;
+            if seen > rank {
+                return Some(2.0 * gamma.powi(*index) / (gamma + 1.0));
+            }
+        }
+        None
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// Aggregated durations of one module and stage
+#[derive(Debug, Clone, Default)]
+pub struct OpStats {
+    sum: f64,
+    sketch: QuantileSketch,
+}
+
+impl OpStats {
+    pub fn record(&mut self, duration: f64) {
+        self.sum += duration;
+        self.sketch.insert(duration);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.sketch.count()
+    }
+
+    pub fn mean(&self) -> f64 {
+        match self.count() {
+            0 => 0.0,
+            n => self.sum / n as f64,
+        }
+    }
+
+    pub fn p50(&self) -> f64 {
+        self.sketch.quantile(0.5).unwrap_or_default()
+    }
+
+    pub fn p99(&self) -> f64 {
+        self.sketch.quantile(0.99).unwrap_or_default()
+    }
+}
+
+/// Per `(module, stage)` statistics of the torch trace
+#[derive(Debug, Default)]
+pub struct OpSummary {
+    ops: BTreeMap<(String, String), OpStats>,
+}
+
+impl OpSummary {
+    /// Fold one `torch_trace` row, given as column names and values
+    pub fn ingest(&mut self, names: &[String], values: &[Ele]) {
+        let column = |name: &str| {
+            names
+                .iter()
+                .position(|n| n == name)
+                .and_then(|idx| values.get(idx))
+        };
+
+        let module = match column("module") {
+            Some(Ele::Text(module)) if module != "None" => module.clone(),
+            _ => return,
+        };
+        let stage = match column("stage") {
+            Some(Ele::Text(stage)) => stage.clone(),
+            _ => String::new(),
+        };
+        let duration = match column("duration") {
+            Some(Ele::F64(x)) => *x,
+            Some(Ele::F32(x)) => *x as f64,
+            Some(Ele::I64(x)) => *x as f64,
+            Some(Ele::I32(x)) => *x as f64,
+            _ => return,
+        };
+
+        self.ops
+            .entry((module, stage))
+            .or_default()
+            .record(duration);
+    }
+
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /// Summarize the rows `ts` holds, dropping those it no longer does
+    pub fn rebuild(&mut self, ts: &TimeSeries) {
+        self.clear();
+        for (_, values) in ts.iter() {
+            self.ingest(&ts.names, &values);
+        }
+    }
+
+    /// Statistics keyed by `(module, stage)`
+    pub fn ops(&self) -> &BTreeMap<(String, String), OpStats> {
+        &self.ops
+    }
+}
+
+pub static OP_SUMMARY: Lazy<Mutex<OpSummary>> = Lazy::new(Default::default);
+
+/// `torch.op_summary`: count, mean, p50 and p99 of durations per module and stage
+#[derive(Default, Debug)]
+pub struct OpSummaryTable {}
+
+impl CustomTable for OpSummaryTable {
+    fn name() -> &'static str {
+        "op_summary"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("module", DataType::Utf8, false),
+            Field::new("stage", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, false),
+            Field::new("mean", DataType::Float64, false),
+            Field::new("p50", DataType::Float64, false),
+            Field::new("p99", DataType::Float64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let summary = OP_SUMMARY.lock().unwrap();
+        let ops = summary.ops();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    ops.keys().map(|(module, _)| module),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    ops.keys().map(|(_, stage)| stage),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    ops.values().map(|stats| stats.count() as i64),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    ops.values().map(OpStats::mean),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    ops.values().map(OpStats::p50),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    ops.values().map(OpStats::p99),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build op_summary batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type OpSummaryPlugin = TablePluginHelper<OpSummaryTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sketch_quantiles_within_accuracy() {
+        let mut sketch = QuantileSketch::default();
+        for i in 1..=1000 {
+            sketch.insert(i as f64 * 1e-4);
+        }
+        let p50 = sketch.quantile(0.5).unwrap();
+        let p99 = sketch.quantile(0.99).unwrap();
+        assert!((p50 - 0.05).abs() / 0.05 < 2.0 * RELATIVE_ACCURACY, "{p50}");
+        assert!(
+            (p99 - 0.099).abs() / 0.099 < 2.0 * RELATIVE_ACCURACY,
+            "{p99}"
+        );
+        assert_eq!(QuantileSketch::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_ingest_groups_by_module_and_stage() {
+        let names: Vec<String> = ["step", "module", "stage", "duration"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let row = |module: Ele, stage: &str, duration: f64| {
+            vec![
+                Ele::I64(1),
+                module,
+                Ele::Text(stage.to_string()),
+                Ele::F64(duration),
+            ]
+        };
+
+        let mut summary = OpSummary::default();
+        summary.ingest(&names, &row(Ele::Text("model.fc".into()), "forward", 1.0));
+        summary.ingest(&names, &row(Ele::Text("model.fc".into()), "forward", 3.0));
+        summary.ingest(&names, &row(Ele::Text("model.fc".into()), "backward", 2.0));
+        summary.ingest(&names, &row(Ele::Text("None".into()), "step", 2.0));
+        summary.ingest(&names, &row(Ele::Nil, "step", 2.0));
+
+        let ops = summary.ops();
+        assert_eq!(ops.len(), 2);
+        let forward = &ops[&("model.fc".to_string(), "forward".to_string())];
+        assert_eq!(forward.count(), 2);
+        assert_eq!(forward.mean(), 2.0);
+        let backward = &ops[&("model.fc".to_string(), "backward".to_string())];
+        assert!((backward.p99() - 2.0).abs() < 2.0 * RELATIVE_ACCURACY * 2.0);
+    }
+
+    #[test]
+    fn test_rebuild_follows_the_rows_left() {
+        let names: Vec<String> = ["module", "stage", "duration"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut ts = TimeSeries::builder().with_columns(names.clone()).build();
+        let mut summary = OpSummary::default();
+        for (t, stage) in ["forward", "forward", "backward"].iter().enumerate() {
+            let row = vec![
+                Ele::Text("model.fc".into()),
+                Ele::Text(stage.to_string()),
+                Ele::F64(1.0),
+            ];
+            ts.append(Ele::I64(t as i64), row.clone()).unwrap();
+            summary.ingest(&names, &row);
+        }
+
+        ts.retain(|_, row| row[1] != Ele::Text("forward".into()))
+            .unwrap();
+        summary.rebuild(&ts);
+        let ops = summary.ops();
+        assert_eq!(ops.len(), 1);
+        assert_eq!(
+            ops[&("model.fc".to_string(), "backward".to_string())].count(),
+            1
+        );
+    }
+}
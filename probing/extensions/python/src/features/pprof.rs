@@ -50,6 +50,30 @@ impl PprofHolder {
             Err(anyhow::anyhow!("no pprof"))
         }
     }
+
+    /// Samples as folded lines (`thread;outer;...;inner count`)
+    pub fn folded(&self) -> Result<Vec<String>> {
+        let holder = self.0.lock().unwrap();
+
+        let Some(pp) = holder.as_ref() else {
+            return Err(anyhow::anyhow!("no pprof"));
+        };
+        let report = pp.report().build()?;
+        Ok(report
+            .data
+            .iter()
+            .map(|(frames, count)| {
+                let mut line = frames.thread_name_or_id();
+                for frame in frames.frames.iter().rev() {
+                    for symbol in frame.iter().rev() {
+                        line.push(';');
+                        line.push_str(&symbol.to_string());
+                    }
+                }
+                format!("{line} {count}")
+            })
+            .collect())
+    }
 }
 
 pub static PPROF_HOLDER: Lazy<PprofHolder> = Lazy::new(|| PprofHolder(Mutex::new(None)));
@@ -59,10 +83,20 @@ pub fn pprof_handler() {
 }
 
 pub fn setup(freq: u64) -> Result<()> {
+    // only one profiler may run, drop the previous one before starting anew
+    PPROF_HOLDER.reset();
     PPROF_HOLDER.setup(freq as i32);
     Ok(())
 }
 
+pub fn stop() {
+    PPROF_HOLDER.reset();
+}
+
 pub fn flamegraph() -> Result<String> {
     PPROF_HOLDER.flamegraph()
 }
+
+pub fn folded() -> Result<Vec<String>> {
+    PPROF_HOLDER.folded()
+}
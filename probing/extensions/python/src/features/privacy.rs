@@ -0,0 +1,170 @@
+//! Redaction of sensitive values before they are stored or sent.
+//!
+//! Rules are configured with `privacy.redact_patterns`, a list of entries
+//! separated by `;` or newlines. An entry `key:<glob>` hides the whole value
+//! of variables and keys whose name matches the glob, e.g. `key:*email*`.
+//! Any other entry, optionally prefixed with `re:`, is a regular expression
+//! whose matches are replaced in the text of a value.
+//!
+//! Redaction is applied to variable watches, snapshot variables, REPL and
+//! eval outputs and exceptions raised by them, so that personal data found
+//! in batches does not end up in trace tables or support bundles.
+
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use pyo3::prelude::*;
+use regex::{Regex, RegexBuilder};
+
+/// Text replacing redacted values and matches
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Rules set with `privacy.redact_patterns`
+static REDACTOR: Lazy<RwLock<Redactor>> = Lazy::new(Default::default);
+
+#[derive(Debug, Default, Clone)]
+pub struct Redactor {
+    /// Globs on names, compiled into anchored case-insensitive regexes
+    keys: Vec<Regex>,
+    /// Patterns replaced in the text of values
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Whether values named `name` are hidden entirely
+    pub fn is_redacted_key(&self, name: &str) -> bool {
+        self.keys.iter().any(|k| k.is_match(name.trim()))
+    }
+
+    /// Replace every match of the patterns in `text`
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, REDACTED) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+
+    /// Redact the value of `name`, hiding it whole if the name matches a key
+    pub fn redact_value<'a>(&self, name: &str, text: &'a str) -> Cow<'a, str> {
+        if self.is_redacted_key(name) {
+            Cow::Borrowed(REDACTED)
+        } else {
+            self.redact(text)
+        }
+    }
+}
+
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Ok(RegexBuilder::new(&pattern).case_insensitive(true).build()?)
+}
+
+impl FromStr for Redactor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut redactor = Redactor::default();
+        for entry in s
+            .split([';', '\n'])
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            if let Some(glob) = entry.strip_prefix("key:") {
+                let glob = glob.trim();
+                if glob.is_empty() {
+                    return Err(anyhow!("empty key pattern in `{entry}`"));
+                }
+                redactor.keys.push(glob_to_regex(glob)?);
+            } else {
+                let pattern = entry.strip_prefix("re:").unwrap_or(entry);
+                let regex = Regex::new(pattern)
+                    .map_err(|e| anyhow!("invalid redact pattern `{pattern}`: {e}"))?;
+                if regex.is_match("") {
+                    return Err(anyhow!("redact pattern `{pattern}` matches empty text"));
+                }
+                redactor.patterns.push(regex);
+            }
+        }
+        Ok(redactor)
+    }
+}
+
+/// Replace the rules with those of `spec`, an empty spec disables redaction
+pub fn configure(spec: &str) -> Result<()> {
+    let redactor = spec.parse::<Redactor>()?;
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = redactor;
+    Ok(())
+}
+
+/// Redact `text` with the configured rules
+pub fn redact(text: &str) -> String {
+    let redactor = REDACTOR.read().unwrap_or_else(|e| e.into_inner());
+    redactor.redact(text).into_owned()
+}
+
+/// Redact the value of `name` with the configured rules
+pub fn redact_value(name: &str, text: &str) -> String {
+    let redactor = REDACTOR.read().unwrap_or_else(|e| e.into_inner());
+    redactor.redact_value(name, text).into_owned()
+}
+
+#[pyfunction]
+fn _redact(text: &str) -> String {
+    redact(text)
+}
+
+#[pyfunction]
+fn _redact_value(name: &str, text: &str) -> String {
+    redact_value(name, text)
+}
+
+pub fn register_privacy_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_redact, module)?)?;
+    module.add_function(wrap_pyfunction!(_redact_value, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_patterns() {
+        let redactor: Redactor = r"key:*password*; key:user_?d
+            re:[\w.]+@[\w.]+\.\w+;\b\d{3}-\d{2}-\d{4}\b"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            redactor.redact("batch of alice@example.com, ssn 123-45-6789"),
+            "batch of [REDACTED], ssn [REDACTED]"
+        );
+        assert!(matches!(redactor.redact("loss=0.5"), Cow::Borrowed(_)));
+        assert_eq!(redactor.redact_value("db_PASSWORD", "'hunter2'"), REDACTED);
+        assert_eq!(redactor.redact_value("user_id", "42"), REDACTED);
+        assert_eq!(redactor.redact_value("user_ids", "[42]"), "[42]");
+
+        assert!("".parse::<Redactor>().unwrap().is_empty());
+        assert!("re:(".parse::<Redactor>().is_err());
+        assert!("a*".parse::<Redactor>().is_err());
+        assert!("key:".parse::<Redactor>().is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use pyo3::prelude::*;
 
+use probing_cli::cli::targets::TargetsFailed;
 use probing_cli::cli_main as cli_main_impl;
+use probing_core::core::DataFusionError;
 use probing_core::ENGINE;
 
 #[pyfunction]
@@ -14,14 +16,15 @@ pub fn is_enabled() -> bool {
 }
 
 #[pyfunction]
-pub fn query_json(_py: Python, sql: String) -> PyResult<String> {
+#[pyo3(signature = (sql, session=None))]
+pub fn query_json(_py: Python, sql: String, session: Option<String>) -> PyResult<String> {
     let result = match tokio::runtime::Handle::try_current() {
         Ok(_handle) => std::thread::spawn(move || {
             tokio::runtime::Builder::new_current_thread()
                 .enable_all()
                 .build()
                 .unwrap_or_else(|e| panic!("Failed to create current-thread runtime: {e}"))
-                .block_on(async { ENGINE.read().await.async_query(sql.as_str()).await })
+                .block_on(run_query(sql, session))
         })
         .join()
         .map_err(|_| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("Thread panicked"))?
@@ -31,7 +34,7 @@ pub fn query_json(_py: Python, sql: String) -> PyResult<String> {
             .enable_all()
             .build()
             .unwrap_or_else(|e| panic!("Failed to create multi-thread runtime: {e}"))
-            .block_on(async { ENGINE.read().await.async_query(sql.as_str()).await })
+            .block_on(run_query(sql, session))
             .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string())),
     };
 
@@ -40,9 +43,23 @@ pub fn query_json(_py: Python, sql: String) -> PyResult<String> {
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))
 }
 
+async fn run_query(
+    sql: String,
+    session: Option<String>,
+) -> Result<Option<probing_proto::prelude::DataFrame>, DataFusionError> {
+    let engine = ENGINE.read().await;
+    match session {
+        Some(session) => engine.session_query(&session, sql).await,
+        None => engine.async_query(sql).await,
+    }
+}
+
 #[pyfunction]
 pub fn cli_main(_py: Python, args: Vec<String>) -> PyResult<()> {
     if let Err(e) = cli_main_impl(args) {
+        if let Some(failed) = e.downcast_ref::<TargetsFailed>() {
+            return Err(pyo3::exceptions::PySystemExit::new_err(failed.code));
+        }
         return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
             e.to_string(),
         ));
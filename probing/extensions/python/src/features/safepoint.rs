@@ -0,0 +1,215 @@
+//! Suspending the Python threads of the process at a safe point.
+//!
+//! A pause schedules a pending call, which the interpreter runs on the main
+//! thread between two bytecodes. The call blocks while holding the GIL, so
+//! every other Python thread stops at its next bytecode boundary as well, and
+//! stacks captured while paused are consistent across threads. Threads running
+//! native code without the GIL continue until they need it again.
+//!
+//! Signal based backtraces keep working while paused; anything that needs the
+//! GIL, such as Python backed tables, waits until the process is resumed.
+
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use probing_proto::prelude::PauseState;
+use pyo3::ffi;
+
+/// How long a pause waits for the main thread to reach a safe point
+const REACH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct State {
+    requested: bool,
+    paused: bool,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+}
+
+impl State {
+    fn snapshot(&self) -> PauseState {
+        let micros = |t: &SystemTime| {
+            t.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_micros() as u64)
+                .unwrap_or_default()
+        };
+        PauseState {
+            requested: self.requested,
+            paused: self.paused,
+            since: self.since.as_ref().map(micros),
+            until: self.until.as_ref().map(micros),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SafePoint {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl SafePoint {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stop Python execution at the next bytecode boundary of the main thread
+    ///
+    /// Returns once the safe point is reached. With `duration`, the process
+    /// resumes on its own after that long.
+    pub fn pause(&self, duration: Option<Duration>) -> Result<PauseState> {
+        self.request(duration, schedule_pending_call)
+    }
+
+    fn request<F>(&self, duration: Option<Duration>, schedule: F) -> Result<PauseState>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let mut state = self.lock();
+        if state.requested {
+            return Ok(state.snapshot());
+        }
+        state.requested = true;
+        state.until = duration.map(|d| SystemTime::now() + d);
+        drop(state);
+
+        if let Err(err) = schedule() {
+            self.cancel(&mut self.lock());
+            return Err(err);
+        }
+
+        let (mut state, _) = self
+            .cond
+            .wait_timeout_while(self.lock(), REACH_TIMEOUT, |s| s.requested && !s.paused)
+            .unwrap_or_else(|e| e.into_inner());
+        if !state.paused {
+            self.cancel(&mut state);
+            return Err(anyhow::anyhow!(
+                "main thread did not reach a safe point within {REACH_TIMEOUT:?}, \
+                 it may be blocked outside the interpreter"
+            ));
+        }
+        Ok(state.snapshot())
+    }
+
+    fn cancel(&self, state: &mut State) {
+        state.requested = false;
+        state.until = None;
+        self.cond.notify_all();
+    }
+
+    /// Let the paused thread continue, waiting until it has left the safe point
+    pub fn resume(&self) -> PauseState {
+        let mut state = self.lock();
+        self.cancel(&mut state);
+        let (state, _) = self
+            .cond
+            .wait_timeout_while(state, REACH_TIMEOUT, |s| s.paused)
+            .unwrap_or_else(|e| e.into_inner());
+        state.snapshot()
+    }
+
+    pub fn state(&self) -> PauseState {
+        self.lock().snapshot()
+    }
+
+    /// Block the calling thread until resumed, run at the safe point
+    fn hold(&self) {
+        let mut state = self.lock();
+        if !state.requested {
+            // the pause timed out or was resumed before the safe point
+            return;
+        }
+        state.paused = true;
+        state.since = Some(SystemTime::now());
+        self.cond.notify_all();
+
+        while state.requested {
+            state = match state.until {
+                None => self.cond.wait(state).unwrap_or_else(|e| e.into_inner()),
+                Some(until) => match until.duration_since(SystemTime::now()) {
+                    Ok(remaining) => {
+                        self.cond
+                            .wait_timeout(state, remaining)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0
+                    }
+                    Err(_) => {
+                        log::info!("pause expired, resuming");
+                        state.requested = false;
+                        state
+                    }
+                },
+            };
+        }
+
+        state.paused = false;
+        state.since = None;
+        state.until = None;
+        self.cond.notify_all();
+    }
+}
+
+pub static SAFEPOINT: Lazy<SafePoint> = Lazy::new(SafePoint::default);
+
+extern "C" fn safepoint_callback(_: *mut std::ffi::c_void) -> std::ffi::c_int {
+    SAFEPOINT.hold();
+    0
+}
+
+fn schedule_pending_call() -> Result<()> {
+    // Py_AddPendingCall may be called from any thread without holding the GIL
+    let ret = unsafe { ffi::Py_AddPendingCall(Some(safepoint_callback), std::ptr::null_mut()) };
+    if ret != 0 {
+        return Err(anyhow::anyhow!("failed to schedule pending call"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn pause(safepoint: &Arc<SafePoint>, duration: Option<Duration>) -> PauseState {
+        let target = safepoint.clone();
+        safepoint
+            .request(duration, move || {
+                std::thread::spawn(move || target.hold());
+                Ok(())
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let safepoint = Arc::new(SafePoint::default());
+        let state = pause(&safepoint, None);
+        assert!(state.paused && state.requested);
+        assert!(state.since.is_some() && state.until.is_none());
+
+        let state = safepoint.resume();
+        assert_eq!(state, PauseState::default());
+    }
+
+    #[test]
+    fn test_pause_expires() {
+        let safepoint = Arc::new(SafePoint::default());
+        let state = pause(&safepoint, Some(Duration::from_millis(50)));
+        assert!(state.paused && state.until.is_some());
+
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(!safepoint.state().paused);
+    }
+
+    #[test]
+    fn test_pause_without_safe_point() {
+        let safepoint = SafePoint::default();
+        assert!(safepoint
+            .request(None, || Err(anyhow::anyhow!("queue full")))
+            .is_err());
+        assert_eq!(safepoint.state(), PauseState::default());
+    }
+}
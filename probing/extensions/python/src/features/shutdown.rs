@@ -0,0 +1,335 @@
+//! Diagnostics captured when the process is asked to terminate.
+//!
+//! Preemptible clusters stop jobs with SIGTERM and interrupted jobs get
+//! SIGINT; either way the stacks and spans telling where the job was go away
+//! with the process. With `shutdown.capture=on` probing handles both signals:
+//! the handler only wakes a capture thread, which writes a final snapshot
+//! with the stacks of all threads, the last spans and the step reached to
+//! [`path`], and then hands the signal to the handler it replaced, so the
+//! process exits as it would have.
+//!
+//! The capture is bounded by `shutdown.timeout_ms`. When the GIL is not
+//! available in time, the snapshot only holds what is known without Python.
+//! A second signal during the capture is forwarded right away.
+
+use std::ffi::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex, Once};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use nix::libc;
+use once_cell::sync::Lazy;
+use pyo3::prelude::*;
+
+use crate::features::signals;
+
+/// Signals a snapshot is taken on
+pub const SIGNALS: [c_int; 2] = [libc::SIGTERM, libc::SIGINT];
+
+/// Directory of the snapshots unless `shutdown.dir` is set
+pub const DEFAULT_DIR: &str = "./logs";
+
+/// Default of `shutdown.timeout_ms`
+pub const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+/// Upper bound of the signal numbers in [`SIGNALS`]
+const MAX_SIGNAL: usize = 32;
+
+static DIR: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(DEFAULT_DIR.to_string()));
+
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT_MS);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Read from the signal handler, so plain atomics instead of a lock
+/// Signal being captured, 0 when none
+static PENDING: AtomicI32 = AtomicI32::new(0);
+/// Write end of the pipe waking the capture thread
+static WAKE: AtomicI32 = AtomicI32::new(-1);
+static PREVIOUS: [AtomicUsize; MAX_SIGNAL] = [const { AtomicUsize::new(0) }; MAX_SIGNAL];
+static PREVIOUS_FLAGS: [AtomicI32; MAX_SIGNAL] = [const { AtomicI32::new(0) }; MAX_SIGNAL];
+static INSTALLED: [AtomicBool; MAX_SIGNAL] = [const { AtomicBool::new(false) }; MAX_SIGNAL];
+
+static CAPTURE_THREAD: Once = Once::new();
+
+/// Set the directory snapshots are written below
+pub fn set_dir(dir: &str) {
+    let dir = if dir.is_empty() { DEFAULT_DIR } else { dir };
+    *DIR.lock().unwrap() = dir.to_string();
+}
+
+/// Set how long the capture may delay the exit, in milliseconds
+pub fn set_timeout_ms(ms: u64) {
+    TIMEOUT_MS.store(ms, Ordering::Relaxed);
+}
+
+/// Path of the snapshot of this process, `<dir>/rank<RANK>/shutdown-<pid>.json`
+///
+/// The `rank<N>` directories follow the layout of exported archives, so
+/// pointing `shutdown.dir` at an archive keeps the snapshots with its tables.
+pub fn path(dir: &Path) -> PathBuf {
+    dir.join(format!("rank{}", rank()))
+        .join(format!("shutdown-{}.json", std::process::id()))
+}
+
+fn rank() -> i64 {
+    std::env::var("RANK")
+        .ok()
+        .and_then(|rank| rank.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Take over [`SIGNALS`] when `enabled`, or give them back
+///
+/// Signals the process ignores are left alone, they do not end it.
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    if !enabled {
+        ENABLED.store(false, Ordering::SeqCst);
+        for sig in SIGNALS {
+            if INSTALLED[sig as usize].load(Ordering::SeqCst) {
+                restore(sig);
+                signals::disown(sig);
+            }
+        }
+        return Ok(());
+    }
+    start_capture_thread()?;
+    ENABLED.store(true, Ordering::SeqCst);
+    for sig in SIGNALS {
+        install(sig)?;
+    }
+    Ok(())
+}
+
+fn install(sig: c_int) -> Result<()> {
+    let mut current: libc::sigaction = unsafe { std::mem::zeroed() };
+    if unsafe { libc::sigaction(sig, std::ptr::null(), &mut current) } != 0 {
+        return Err(anyhow!("failed to query handler of signal {sig}"));
+    }
+    if current.sa_sigaction == handler_address() || current.sa_sigaction == libc::SIG_IGN {
+        return Ok(());
+    }
+    PREVIOUS[sig as usize].store(current.sa_sigaction, Ordering::SeqCst);
+    PREVIOUS_FLAGS[sig as usize].store(current.sa_flags, Ordering::SeqCst);
+
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = handler_address();
+    action.sa_flags = libc::SA_RESTART;
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+    if unsafe { libc::sigaction(sig, &action, std::ptr::null_mut()) } != 0 {
+        return Err(anyhow!(
+            "failed to install handler of signal {sig}: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    INSTALLED[sig as usize].store(true, Ordering::SeqCst);
+    let callback: fn() = if sig == libc::SIGTERM {
+        on_sigterm
+    } else {
+        on_sigint
+    };
+    signals::own(sig, "shutdown capture", callback);
+    Ok(())
+}
+
+/// Reinstall the handler [`install`] replaced, only async-signal-safe calls
+fn restore(sig: c_int) {
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = PREVIOUS[sig as usize].load(Ordering::SeqCst);
+    action.sa_flags = PREVIOUS_FLAGS[sig as usize].load(Ordering::SeqCst);
+    unsafe {
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(sig, &action, std::ptr::null_mut());
+    }
+    INSTALLED[sig as usize].store(false, Ordering::SeqCst);
+}
+
+/// Hand `sig` to the handler it would have reached without probing
+///
+/// Called from the signal handler too, so the ownership recorded in
+/// [`signals`] is left to the caller.
+fn forward(sig: c_int) {
+    restore(sig);
+    unsafe { libc::kill(libc::getpid(), sig) };
+}
+
+fn handler_address() -> usize {
+    handle_signal as *const () as usize
+}
+
+extern "C" fn handle_signal(sig: c_int) {
+    notify(sig);
+}
+
+fn on_sigterm() {
+    notify(libc::SIGTERM);
+}
+
+fn on_sigint() {
+    notify(libc::SIGINT);
+}
+
+fn notify(sig: c_int) {
+    if PENDING
+        .compare_exchange(0, sig, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        // asked again while capturing: do not make the user wait
+        forward(sig);
+        return;
+    }
+    let fd = WAKE.load(Ordering::SeqCst);
+    let byte = 1u8;
+    if fd < 0 || unsafe { libc::write(fd, &byte as *const u8 as *const c_void, 1) } != 1 {
+        forward(sig);
+        signals::disown(sig);
+        PENDING.store(0, Ordering::SeqCst);
+    }
+}
+
+fn start_capture_thread() -> Result<()> {
+    let mut result = Ok(());
+    CAPTURE_THREAD.call_once(|| {
+        let mut fds = [0 as c_int; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            result = Err(anyhow!(
+                "failed to create shutdown pipe: {}",
+                std::io::Error::last_os_error()
+            ));
+            return;
+        }
+        let [read, write] = fds;
+        let spawned = std::thread::Builder::new()
+            .name("probing-shutdown".to_string())
+            .spawn(move || wait_for_signals(read));
+        match spawned {
+            Ok(_) => WAKE.store(write, Ordering::SeqCst),
+            Err(e) => result = Err(anyhow!("failed to start shutdown capture: {e}")),
+        }
+    });
+    result
+}
+
+fn wait_for_signals(fd: c_int) {
+    loop {
+        let mut byte = 0u8;
+        let n = unsafe { libc::read(fd, &mut byte as *mut u8 as *mut c_void, 1) };
+        if n < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+            continue;
+        }
+        if n <= 0 {
+            log::error!(
+                "shutdown capture stopped: {}",
+                std::io::Error::last_os_error()
+            );
+            return;
+        }
+        let sig = PENDING.load(Ordering::SeqCst);
+        if sig == 0 {
+            continue;
+        }
+        if ENABLED.load(Ordering::SeqCst) {
+            let target = path(Path::new(&*DIR.lock().unwrap()));
+            match capture(sig, &target) {
+                Ok(()) => log::warn!(
+                    "received {}, wrote shutdown snapshot to {}",
+                    signal_name(sig),
+                    target.display()
+                ),
+                Err(e) => log::error!("failed to write shutdown snapshot: {e}"),
+            }
+        }
+        forward(sig);
+        signals::disown(sig);
+        PENDING.store(0, Ordering::SeqCst);
+    }
+}
+
+fn signal_name(sig: c_int) -> &'static str {
+    nix::sys::signal::Signal::try_from(sig)
+        .map(|signal| signal.as_str())
+        .unwrap_or("signal")
+}
+
+/// Write the snapshot of `sig` to `target`, from Python when the GIL comes
+/// in time
+fn capture(sig: c_int, target: &Path) -> Result<()> {
+    let timeout = Duration::from_millis(TIMEOUT_MS.load(Ordering::Relaxed));
+    let name = signal_name(sig);
+    let step = probing_core::schedule::step();
+
+    let (tx, rx) = mpsc::channel();
+    let path = target.to_string_lossy().into_owned();
+    // left waiting for the GIL when it gives up, the process exits anyway
+    std::thread::spawn(move || {
+        let result = Python::with_gil(|py| -> PyResult<()> {
+            py.import("probing.inspect.shutdown")?
+                .call_method1("capture", (path, name, step))?;
+            Ok(())
+        });
+        let _ = tx.send(result.map_err(|e| e.to_string()));
+    });
+    let error = match rx.recv_timeout(timeout) {
+        Ok(Ok(())) => return Ok(()),
+        Ok(Err(e)) => e,
+        Err(_) => format!(
+            "GIL not available within {}ms, stacks and spans are missing",
+            timeout.as_millis()
+        ),
+    };
+    write_fallback(target, name, step, &error)?;
+    Err(anyhow!(error))
+}
+
+/// The snapshot without what needs Python
+fn write_fallback(target: &Path, signal: &str, step: Option<i64>, error: &str) -> Result<()> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or_default();
+    let snapshot = serde_json::json!({
+        "signal": signal,
+        "time": time,
+        "pid": std::process::id(),
+        "rank": rank(),
+        "step": step,
+        "stacks": [],
+        "spans": [],
+        "error": error,
+    });
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(target, serde_json::to_vec_pretty(&snapshot)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_follows_archive_layout() {
+        let path = path(Path::new("/runs/42"));
+        let pid = std::process::id();
+        assert!(path.starts_with("/runs/42"));
+        assert!(path.ends_with(format!("shutdown-{pid}.json")));
+        let rank = path.parent().unwrap().file_name().unwrap();
+        assert!(rank.to_string_lossy().starts_with("rank"));
+    }
+
+    #[test]
+    fn test_write_fallback() {
+        let dir = std::env::temp_dir().join(format!("probing-shutdown-{}", std::process::id()));
+        let target = path(&dir);
+        write_fallback(&target, "SIGTERM", Some(12), "no GIL").unwrap();
+        let snapshot: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&target).unwrap()).unwrap();
+        assert_eq!(snapshot["signal"], "SIGTERM");
+        assert_eq!(snapshot["step"], 12);
+        assert_eq!(snapshot["error"], "no GIL");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
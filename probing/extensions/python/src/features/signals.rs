@@ -0,0 +1,403 @@
+//! Inventory of signal dispositions and conflicts with probing's own handlers.
+//!
+//! Probing relies on signal handlers, e.g. SIGUSR2 for the stack tracer. A
+//! framework installing its own handler afterwards silently replaces ours, and
+//! the tracer then times out. Handlers installed by probing are recorded here,
+//! so `process.signals` can report when they were replaced and by whom.
+//!
+//! With `signals.chain=true`, replaced handlers are repaired by installing a
+//! trampoline that runs probing's callback and then the foreign handler, so
+//! both keep working.
+
+use std::collections::BTreeMap;
+use std::ffi::{c_int, c_void, CStr};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Result};
+use nix::libc;
+use nix::sys::signal::Signal;
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    CustomTable, DataType, Field, Int32Array, RecordBatch, Schema, SchemaRef, StringArray,
+    TablePluginHelper,
+};
+
+/// Upper bound of signal numbers, covering realtime signals
+const MAX_SIGNAL: usize = 65;
+
+/// A handler installed by probing
+#[derive(Debug, Clone)]
+struct Owned {
+    owner: &'static str,
+    /// Address of the installed handler
+    handler: usize,
+}
+
+static OWNED: Lazy<Mutex<BTreeMap<c_int, Owned>>> = Lazy::new(Default::default);
+
+static CHAIN: AtomicBool = AtomicBool::new(false);
+
+// Read from the trampoline, so plain atomics instead of a lock
+static CALLBACKS: [AtomicUsize; MAX_SIGNAL] = [const { AtomicUsize::new(0) }; MAX_SIGNAL];
+static FOREIGN: [AtomicUsize; MAX_SIGNAL] = [const { AtomicUsize::new(0) }; MAX_SIGNAL];
+static FOREIGN_FLAGS: [AtomicI32; MAX_SIGNAL] = [const { AtomicI32::new(0) }; MAX_SIGNAL];
+
+/// Current disposition of one signal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disposition {
+    pub signal: c_int,
+    pub name: String,
+    /// `default`, `ignore` or `handler`
+    pub action: &'static str,
+    pub handler: usize,
+    /// Symbol and library of the handler
+    pub symbol: String,
+    pub flags: String,
+    /// Component of probing owning the handler
+    pub owner: Option<String>,
+    pub conflict: Option<String>,
+}
+
+/// Record the handler just installed by probing for `sig`
+///
+/// `callback` is what the handler runs, reused by the chaining trampoline.
+pub fn own(sig: c_int, owner: &'static str, callback: fn()) {
+    let Some(action) = query(sig) else {
+        return;
+    };
+    CALLBACKS[sig as usize].store(callback as usize, Ordering::SeqCst);
+    OWNED.lock().unwrap().insert(
+        sig,
+        Owned {
+            owner,
+            handler: action.sa_sigaction,
+        },
+    );
+}
+
+/// Forget the handler of `sig` once probing gave the signal back
+pub fn disown(sig: c_int) {
+    if OWNED.lock().unwrap().remove(&sig).is_some() {
+        CALLBACKS[sig as usize].store(0, Ordering::SeqCst);
+    }
+}
+
+fn query(sig: c_int) -> Option<libc::sigaction> {
+    if sig <= 0 || sig as usize >= MAX_SIGNAL {
+        return None;
+    }
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::sigaction(sig, std::ptr::null(), &mut action) };
+    (ret == 0).then_some(action)
+}
+
+fn trampoline_address() -> usize {
+    chain_handler as *const () as usize
+}
+
+/// Symbol and library containing `addr`, as resolved by `dladdr`
+fn describe(addr: usize) -> String {
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    if unsafe { libc::dladdr(addr as *const c_void, &mut info) } == 0 {
+        return String::new();
+    }
+    let text = |ptr: *const libc::c_char| {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(
+                unsafe { CStr::from_ptr(ptr) }
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        }
+    };
+    let library = text(info.dli_fname).unwrap_or_default();
+    match text(info.dli_sname) {
+        Some(symbol) => format!("{symbol} ({library})"),
+        None => library,
+    }
+}
+
+fn describe_flags(flags: c_int) -> String {
+    [
+        (libc::SA_SIGINFO, "SA_SIGINFO"),
+        (libc::SA_ONSTACK, "SA_ONSTACK"),
+        (libc::SA_RESTART, "SA_RESTART"),
+        (libc::SA_NODEFER, "SA_NODEFER"),
+        (libc::SA_RESETHAND, "SA_RESETHAND"),
+    ]
+    .iter()
+    .filter(|(flag, _)| flags & flag != 0)
+    .map(|(_, name)| *name)
+    .collect::<Vec<_>>()
+    .join("|")
+}
+
+fn disposition(signal: Signal, owned: &BTreeMap<c_int, Owned>) -> Option<Disposition> {
+    let sig = signal as c_int;
+    let action = query(sig)?;
+    let handler = action.sa_sigaction;
+    let kind = match handler {
+        libc::SIG_DFL => "default",
+        libc::SIG_IGN => "ignore",
+        _ => "handler",
+    };
+    let symbol = if kind == "handler" {
+        describe(handler)
+    } else {
+        String::new()
+    };
+
+    let (owner, conflict) = match owned.get(&sig) {
+        Some(ours) if handler == ours.handler => (Some(ours.owner.to_string()), None),
+        Some(ours) if handler == trampoline_address() => {
+            (Some(format!("{} (chained)", ours.owner)), None)
+        }
+        Some(ours) => {
+            let by = if symbol.is_empty() {
+                format!("{kind} {handler:#x}")
+            } else {
+                symbol.clone()
+            };
+            (
+                None,
+                Some(format!(
+                    "{} handler of {signal} was replaced by {by}",
+                    ours.owner
+                )),
+            )
+        }
+        None => (None, None),
+    };
+
+    Some(Disposition {
+        signal: sig,
+        name: signal.as_str().to_string(),
+        action: kind,
+        handler,
+        symbol,
+        flags: describe_flags(action.sa_flags),
+        owner,
+        conflict,
+    })
+}
+
+/// Dispositions of all standard signals
+pub fn inventory() -> Vec<Disposition> {
+    let owned = OWNED.lock().unwrap().clone();
+    Signal::iterator()
+        .filter_map(|signal| disposition(signal, &owned))
+        .collect()
+}
+
+/// Check that probing's handler for `sig` is still installed
+///
+/// In chain mode a replaced handler is repaired, otherwise the conflict is
+/// returned as an error.
+pub fn ensure(sig: c_int) -> Result<()> {
+    let Ok(signal) = Signal::try_from(sig) else {
+        return Ok(());
+    };
+    let owned = OWNED.lock().unwrap().clone();
+    let Some(conflict) = disposition(signal, &owned).and_then(|d| d.conflict) else {
+        return Ok(());
+    };
+    if CHAIN.load(Ordering::SeqCst) {
+        return chain(sig);
+    }
+    Err(anyhow!(
+        "{conflict}, set signals.chain=true to chain both handlers"
+    ))
+}
+
+/// Enable or disable chain mode, repairing current conflicts when enabled
+pub fn set_chain(enabled: bool) -> Result<Vec<String>> {
+    CHAIN.store(enabled, Ordering::SeqCst);
+    if !enabled {
+        return Ok(vec![]);
+    }
+    repair()
+}
+
+/// Chain every replaced handler, returning the names of the signals repaired
+pub fn repair() -> Result<Vec<String>> {
+    let mut repaired = vec![];
+    for disposition in inventory() {
+        if disposition.conflict.is_some() {
+            chain(disposition.signal)?;
+            repaired.push(disposition.name);
+        }
+    }
+    Ok(repaired)
+}
+
+/// Install the trampoline for `sig`, forwarding to the current handler
+fn chain(sig: c_int) -> Result<()> {
+    let foreign = query(sig).ok_or_else(|| anyhow!("invalid signal {sig}"))?;
+    if foreign.sa_sigaction == trampoline_address() {
+        return Ok(());
+    }
+    FOREIGN[sig as usize].store(foreign.sa_sigaction, Ordering::SeqCst);
+    FOREIGN_FLAGS[sig as usize].store(foreign.sa_flags, Ordering::SeqCst);
+
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = trampoline_address();
+    action.sa_mask = foreign.sa_mask;
+    action.sa_flags = (foreign.sa_flags | libc::SA_SIGINFO) & !libc::SA_RESETHAND;
+    if unsafe { libc::sigaction(sig, &action, std::ptr::null_mut()) } != 0 {
+        return Err(anyhow!(
+            "failed to chain handler of signal {sig}: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    log::warn!("chained probing's handler of signal {sig} with the handler that replaced it");
+    Ok(())
+}
+
+extern "C" fn chain_handler(sig: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let Some(idx) = usize::try_from(sig).ok().filter(|idx| *idx < MAX_SIGNAL) else {
+        return;
+    };
+    let callback = CALLBACKS[idx].load(Ordering::SeqCst);
+    if callback != 0 {
+        let callback: fn() = unsafe { std::mem::transmute(callback) };
+        callback();
+    }
+
+    let foreign = FOREIGN[idx].load(Ordering::SeqCst);
+    if foreign == libc::SIG_DFL || foreign == libc::SIG_IGN {
+        return;
+    }
+    if FOREIGN_FLAGS[idx].load(Ordering::SeqCst) & libc::SA_SIGINFO != 0 {
+        let handler: extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) =
+            unsafe { std::mem::transmute(foreign) };
+        handler(sig, info, context);
+    } else {
+        let handler: extern "C" fn(c_int) = unsafe { std::mem::transmute(foreign) };
+        handler(sig);
+    }
+}
+
+/// `process.signals`: dispositions of all signals and conflicts with probing
+#[derive(Default, Debug)]
+pub struct SignalsTable {}
+
+impl CustomTable for SignalsTable {
+    fn name() -> &'static str {
+        "signals"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("signal", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("action", DataType::Utf8, false),
+            Field::new("handler", DataType::Utf8, false),
+            Field::new("symbol", DataType::Utf8, false),
+            Field::new("flags", DataType::Utf8, false),
+            Field::new("owner", DataType::Utf8, true),
+            Field::new("conflict", DataType::Utf8, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let signals = inventory();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(
+                    signals.iter().map(|s| s.signal),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    signals.iter().map(|s| &s.name),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    signals.iter().map(|s| s.action),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    signals.iter().map(|s| format!("{:#x}", s.handler)),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    signals.iter().map(|s| &s.symbol),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    signals.iter().map(|s| &s.flags),
+                )),
+                Arc::new(StringArray::from_iter(
+                    signals.iter().map(|s| s.owner.as_deref()),
+                )),
+                Arc::new(StringArray::from_iter(
+                    signals.iter().map(|s| s.conflict.as_deref()),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build signals batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type SignalsPlugin = TablePluginHelper<SignalsTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static OURS: AtomicUsize = AtomicUsize::new(0);
+    static THEIRS: AtomicUsize = AtomicUsize::new(0);
+
+    fn ours() {
+        OURS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    extern "C" fn theirs(_: c_int) {
+        THEIRS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn find(sig: c_int) -> Disposition {
+        inventory().into_iter().find(|d| d.signal == sig).unwrap()
+    }
+
+    #[test]
+    fn test_conflict_and_chain() {
+        // SIGWINCH is ignored by default and unused by the test harness
+        let sig = libc::SIGWINCH;
+        unsafe { signal_hook_registry::register_unchecked(sig, |_| ours()) }.unwrap();
+        own(sig, "test", ours);
+        let disposition = find(sig);
+        assert_eq!(disposition.owner.as_deref(), Some("test"));
+        assert_eq!(disposition.conflict, None);
+        assert!(ensure(sig).is_ok());
+
+        unsafe { libc::signal(sig, theirs as *const () as libc::sighandler_t) };
+        let disposition = find(sig);
+        assert_eq!(disposition.owner, None);
+        assert!(disposition.conflict.unwrap().contains("replaced by"));
+        assert!(ensure(sig).is_err());
+
+        assert_eq!(set_chain(true).unwrap(), vec!["SIGWINCH".to_string()]);
+        let disposition = find(sig);
+        assert_eq!(disposition.owner.as_deref(), Some("test (chained)"));
+        assert!(disposition.flags.contains("SA_SIGINFO"));
+
+        unsafe { libc::raise(sig) };
+        assert_eq!(OURS.load(Ordering::SeqCst), 1);
+        assert_eq!(THEIRS.load(Ordering::SeqCst), 1);
+        set_chain(false).unwrap();
+    }
+
+    #[test]
+    fn test_inventory_lists_standard_signals() {
+        let signals = inventory();
+        let sigkill = signals.iter().find(|d| d.name == "SIGKILL").unwrap();
+        assert_eq!(sigkill.action, "default");
+        assert!(signals.iter().any(|d| d.name == "SIGUSR2"));
+    }
+}
@@ -22,6 +22,9 @@ extern "C" {
 
     pub fn PyInterpreterState_Get() -> *mut PyInterpreterState;
 
+    /// Whether the current thread holds the GIL.
+    pub fn PyGILState_Check() -> c_int;
+
     pub fn _PyEval_EvalFrameDefault(
         ts: *mut PyThreadState,
         frame: *mut PyFrameObject,
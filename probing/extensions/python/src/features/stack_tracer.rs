@@ -1,16 +1,16 @@
-use std::collections::HashSet;
 use std::sync::mpsc;
 use std::sync::Mutex;
 use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use lazy_static::lazy_static;
 use nix::libc;
 use once_cell::sync::Lazy;
 
-use probing_proto::prelude::CallFrame;
+use probing_proto::prelude::{CallFrame, SymbolStatus};
 
+use crate::features::signals;
+use crate::features::symbolizer::SYMBOLIZER;
 use crate::features::vm_tracer::get_python_stacks_raw;
 
 #[async_trait]
@@ -22,34 +22,18 @@ pub trait StackTracer: Send + Sync + std::fmt::Debug {
 pub struct SignalTracer;
 
 impl SignalTracer {
+    /// Capture the raw instruction pointers of the current thread
+    ///
+    /// Symbolization is left to the receiving side, see [`SYMBOLIZER`].
     fn get_native_stacks() -> Option<Vec<CallFrame>> {
         let mut frames = vec![];
         backtrace::trace(|frame| {
-            let ip = frame.ip();
-            let symbol_address = frame.symbol_address(); // Keep as *mut c_void for formatting
-            backtrace::resolve_frame(frame, |symbol| {
-                let func_name = symbol
-                    .name()
-                    .and_then(|name| name.as_str())
-                    .map(|raw_name| {
-                        cpp_demangle::Symbol::new(raw_name)
-                            .ok()
-                            .map(|demangled| demangled.to_string())
-                            .unwrap_or_else(|| raw_name.to_string())
-                    })
-                    .unwrap_or_else(|| format!("unknown@{symbol_address:p}"));
-
-                let file_name = symbol
-                    .filename()
-                    .map(|path| path.to_string_lossy().into_owned())
-                    .unwrap_or_default();
-
-                frames.push(CallFrame::CFrame {
-                    ip: format!("{ip:p}"),
-                    file: file_name,
-                    func: func_name,
-                    lineno: symbol.lineno().unwrap_or(0) as i64,
-                });
+            frames.push(CallFrame::CFrame {
+                ip: format!("{:p}", frame.ip()),
+                file: String::new(),
+                func: String::new(),
+                lineno: 0,
+                status: SymbolStatus::Unresolved,
             });
             true
         });
@@ -69,71 +53,6 @@ impl SignalTracer {
             Err(_) => Err(anyhow::anyhow!("Failed to send frames via channel")),
         }
     }
-
-    fn merge_python_native_stacks(
-        python_stacks: Vec<CallFrame>,
-        native_stacks: Vec<CallFrame>,
-    ) -> Vec<CallFrame> {
-        let mut merged = vec![];
-        let mut python_frame_index = 0;
-
-        enum MergeType {
-            Ignore,
-            MergeNativeFrame,
-            MergePythonFrame,
-        }
-
-        fn get_merge_strategy(frame: &CallFrame) -> MergeType {
-            lazy_static! {
-                static ref WHITELISTED_PREFIXES_SET: HashSet<&'static str> = {
-                    const PREFIXES: &[&str] = &[
-                        "time",
-                        "sys",
-                        "gc",
-                        "os",
-                        "unicode",
-                        "thread",
-                        "stringio",
-                        "sre",
-                        "PyGilState",
-                        "PyThread",
-                        "lock",
-                    ];
-                    PREFIXES.iter().cloned().collect()
-                };
-            }
-            let symbol = match frame {
-                CallFrame::CFrame { func, .. } => func,
-                CallFrame::PyFrame { func, .. } => func,
-            };
-            let mut tokens = symbol.split(['_', '.']).filter(|s| !s.is_empty());
-            match tokens.next() {
-                Some("PyEval") => match tokens.next() {
-                    Some("EvalFrameDefault" | "EvalFrameEx") => MergeType::MergePythonFrame,
-                    _ => MergeType::Ignore,
-                },
-                Some(prefix) if WHITELISTED_PREFIXES_SET.contains(prefix) => {
-                    MergeType::MergeNativeFrame
-                }
-                _ => MergeType::MergeNativeFrame,
-            }
-        }
-
-        for frame in native_stacks {
-            // log::debug!("Processing native frame: {:?}", frame);
-            match get_merge_strategy(&frame) {
-                MergeType::Ignore => {} // Do nothing
-                MergeType::MergeNativeFrame => merged.push(frame),
-                MergeType::MergePythonFrame => {
-                    if let Some(py_frame) = python_stacks.get(python_frame_index) {
-                        merged.push(py_frame.clone());
-                    }
-                    python_frame_index += 1; // Advance index regardless of whether a Python frame was available
-                }
-            }
-        }
-        merged
-    }
 }
 
 #[async_trait]
@@ -158,6 +77,9 @@ impl StackTracer for SignalTracer {
             })?
             .replace(tx);
 
+        // a handler installed over ours would never answer
+        signals::ensure(libc::SIGUSR2)?;
+
         log::debug!("Sending SIGUSR2 signal to process {pid} (thread: {tid})");
 
         #[cfg(target_os = "linux")]
@@ -174,13 +96,11 @@ impl StackTracer for SignalTracer {
             return Err(anyhow::anyhow!(error_msg));
         }
 
-        let native_frames = rx.recv_timeout(Duration::from_secs(2))?;
+        let mut native_frames = rx.recv_timeout(Duration::from_secs(2))?;
         let python_frames = rx.recv_timeout(Duration::from_secs(2))?;
+        SYMBOLIZER.symbolize_frames(&mut native_frames);
 
-        Ok(Self::merge_python_native_stacks(
-            python_frames,
-            native_frames,
-        ))
+        Ok(merge_stacks(python_frames, native_frames))
     }
 }
 
@@ -195,8 +115,186 @@ pub fn backtrace_signal_handler() {
     }
 }
 
+/// What a native frame is to the merged stack
+#[derive(Debug, PartialEq, Eq)]
+enum NativeKind {
+    /// Our eval frame hook, see `vm_tracer::rust_eval_frame`: runs exactly
+    /// one Python frame
+    Hook,
+    /// The CPython eval loop, runs one Python frame when no hook is installed
+    Eval,
+    /// CPython call machinery between Python and C extensions, hidden
+    Glue,
+    /// Any other native frame, kept
+    Native,
+}
+
+/// CPython functions that only forward calls, with leading underscores
+/// stripped
+const GLUE_PREFIXES: &[&str] = &[
+    "PyEval_",
+    "PyObject_Call",
+    "PyObject_Vectorcall",
+    "PyObject_FastCall",
+    "PyObject_MakeTpCall",
+    "PyFunction_",
+    "PyVectorcall_",
+    "PyCFunction_",
+    "cfunction_",
+    "method_vectorcall",
+    "slot_tp_",
+    "vectorcall_",
+    "call_function",
+    "do_call_core",
+    "function_code_fastcall",
+];
+
+fn classify(func: &str) -> NativeKind {
+    if func.contains("rust_eval_frame") {
+        return NativeKind::Hook;
+    }
+    let func = func.trim_start_matches('_');
+    if func.starts_with("PyEval_EvalFrame") {
+        NativeKind::Eval
+    } else if GLUE_PREFIXES.iter().any(|prefix| func.starts_with(prefix)) {
+        NativeKind::Glue
+    } else {
+        NativeKind::Native
+    }
+}
+
+/// Interleave the Python frames of a thread into its native frames, both
+/// innermost first.
+///
+/// Each frame running the eval loop is replaced by the Python frame it
+/// evaluates, so a stack reads `libcublas <- at::matmul <- forward <-
+/// train_step` instead of two disjoint views. The eval frame hook marks the
+/// frames exactly; without it, e.g. when its symbol is unresolved, the eval
+/// loop frames are used instead. CPython call glue is dropped, and Python
+/// frames left without a native frame are kept at the outer end.
+pub(crate) fn merge_stacks(
+    python_frames: Vec<CallFrame>,
+    native_frames: Vec<CallFrame>,
+) -> Vec<CallFrame> {
+    if native_frames.is_empty() {
+        return python_frames;
+    }
+    let kinds = native_frames
+        .iter()
+        .map(|frame| match frame {
+            CallFrame::CFrame { func, .. } => classify(func),
+            CallFrame::PyFrame { .. } => NativeKind::Native,
+        })
+        .collect::<Vec<_>>();
+    let anchor = if kinds.contains(&NativeKind::Hook) {
+        NativeKind::Hook
+    } else {
+        NativeKind::Eval
+    };
+
+    let mut python_frames = python_frames.into_iter();
+    let mut merged = vec![];
+    for (frame, kind) in native_frames.into_iter().zip(kinds) {
+        if kind == anchor {
+            merged.extend(python_frames.next());
+        } else if kind == NativeKind::Native {
+            merged.push(frame);
+        }
+    }
+    merged.extend(python_frames);
+    merged
+}
+
 /// Define a static Mutex for the backtrace function
 static BACKTRACE_MUTEX: Lazy<tokio::sync::Mutex<()>> = Lazy::new(|| tokio::sync::Mutex::new(()));
 
 pub static NATIVE_CALLSTACK_SENDER_SLOT: Lazy<Mutex<Option<mpsc::Sender<Vec<CallFrame>>>>> =
     Lazy::new(|| Mutex::new(None));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn native(func: &str) -> CallFrame {
+        CallFrame::CFrame {
+            ip: "0x0".to_string(),
+            file: String::new(),
+            func: func.to_string(),
+            lineno: 0,
+            status: SymbolStatus::Partial,
+        }
+    }
+
+    fn python(func: &str) -> CallFrame {
+        CallFrame::PyFrame {
+            file: "train.py".to_string(),
+            func: func.to_string(),
+            lineno: 1,
+            locals: Default::default(),
+        }
+    }
+
+    fn funcs(frames: &[CallFrame]) -> Vec<&str> {
+        frames
+            .iter()
+            .map(|frame| match frame {
+                CallFrame::CFrame { func, .. } | CallFrame::PyFrame { func, .. } => func.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_on_hook_frames() {
+        let native_frames = vec![
+            native("cublasSgemm_v2"),
+            native("at::native::matmul"),
+            native("cfunction_call"),
+            native("_PyObject_MakeTpCall"),
+            native("probing_python::features::vm_tracer::rust_eval_frame"),
+            native("_PyEval_EvalFrameDefault"),
+            native("_PyFunction_Vectorcall"),
+            native("probing_python::features::vm_tracer::rust_eval_frame"),
+            native("_PyEval_EvalFrameDefault"),
+            native("PyEval_EvalCode"),
+            native("Py_RunMain"),
+            native("main"),
+        ];
+        let python_frames = vec![python("train_step"), python("<module>")];
+
+        let merged = merge_stacks(python_frames, native_frames);
+        assert_eq!(
+            funcs(&merged),
+            [
+                "cublasSgemm_v2",
+                "at::native::matmul",
+                "train_step",
+                "<module>",
+                "Py_RunMain",
+                "main"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_on_eval_frames() {
+        let native_frames = vec![
+            native("lock_PyThread_acquire_lock"),
+            native("_PyEval_EvalFrameDefault"),
+            native(""),
+            native("_PyEval_EvalFrameDefault"),
+        ];
+        let python_frames = vec![python("wait"), python("run"), python("<module>")];
+
+        let merged = merge_stacks(python_frames, native_frames);
+        assert_eq!(
+            funcs(&merged),
+            ["lock_PyThread_acquire_lock", "wait", "", "run", "<module>"]
+        );
+    }
+
+    #[test]
+    fn test_merge_without_native_frames() {
+        let merged = merge_stacks(vec![python("run")], vec![]);
+        assert_eq!(funcs(&merged), ["run"]);
+    }
+}
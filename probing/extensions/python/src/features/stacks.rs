@@ -0,0 +1,22 @@
+//! Python access to the stack dictionary of `probing_core::stacks`, used by
+//! `probing.inspect.stacks`.
+
+use probing_core::stacks::{intern_stack, resolve_stack};
+use pyo3::prelude::*;
+
+/// Id of the stack `frames`, outermost first, stored in `stacks.dictionary`
+#[pyfunction]
+fn _intern_stack(frames: Vec<String>) -> i64 {
+    intern_stack(&frames)
+}
+
+#[pyfunction]
+fn _resolve_stack(id: i64) -> Option<Vec<String>> {
+    resolve_stack(id)
+}
+
+pub fn register_stacks_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_intern_stack, module)?)?;
+    module.add_function(wrap_pyfunction!(_resolve_stack, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,471 @@
+//! Symbolization of native addresses captured in mixed stacks.
+//!
+//! Native frames are captured as raw instruction pointers inside the signal
+//! handler and resolved afterwards by a chain of [`Symbolizer`] backends:
+//! the in-process DWARF symbolizer first, then an optional remote symbol
+//! server for modules shipped without debug info. Results are cached per
+//! address, so repeated stack dumps of a hot loop only pay once.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::RuntimeFlavor;
+
+use probing_proto::prelude::{CallFrame, SymbolStatus};
+
+/// Upper bound of cached addresses, the cache is reset once it is reached
+const CACHE_CAPACITY: usize = 64 * 1024;
+
+/// Function, file and line of a native address
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Symbol {
+    pub func: String,
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub lineno: Option<u32>,
+}
+
+impl Symbol {
+    pub fn status(&self) -> SymbolStatus {
+        match (&self.file, self.lineno) {
+            (Some(_), Some(_)) => SymbolStatus::Resolved,
+            _ => SymbolStatus::Partial,
+        }
+    }
+}
+
+/// A backend resolving native addresses of the current process
+pub trait Symbolizer: Send + Sync + std::fmt::Debug {
+    /// Resolve `addrs`, returning one entry per address
+    fn symbolize(&self, addrs: &[u64]) -> Vec<Option<Symbol>>;
+}
+
+/// Resolve addresses from the DWARF info of the loaded modules
+///
+/// Backed by `backtrace`, which reads the debug info with gimli/addr2line.
+#[derive(Debug, Default)]
+pub struct DwarfSymbolizer;
+
+impl Symbolizer for DwarfSymbolizer {
+    fn symbolize(&self, addrs: &[u64]) -> Vec<Option<Symbol>> {
+        addrs.iter().map(|addr| Self::resolve(*addr)).collect()
+    }
+}
+
+impl DwarfSymbolizer {
+    fn resolve(addr: u64) -> Option<Symbol> {
+        let mut resolved: Option<Symbol> = None;
+        // Frames hold return addresses, step back into the call instruction
+        let pc = addr.saturating_sub(1) as usize as *mut std::ffi::c_void;
+        backtrace::resolve(pc, |symbol| {
+            // Inlined call sites report several symbols, keep the innermost one
+            if resolved.is_some() {
+                return;
+            }
+            let Some(func) = symbol.name().and_then(|name| name.as_str()).map(demangle) else {
+                return;
+            };
+            resolved = Some(Symbol {
+                func,
+                file: symbol
+                    .filename()
+                    .map(|path| path.to_string_lossy().into_owned()),
+                lineno: symbol.lineno(),
+            });
+        });
+        resolved
+    }
+}
+
+fn demangle(raw: &str) -> String {
+    cpp_demangle::Symbol::new(raw)
+        .ok()
+        .map(|demangled| demangled.to_string())
+        .unwrap_or_else(|| raw.to_string())
+}
+
+/// Resolve addresses with a remote symbol server
+///
+/// Addresses are sent as file offsets into their module, one request per
+/// module: `POST <url>/symbolize` with `{"module": "<path>", "offsets": [..]}`.
+/// The server answers with one [`Symbol`] or `null` per offset.
+#[derive(Debug)]
+pub struct RemoteSymbolizer {
+    url: String,
+    timeout: Duration,
+}
+
+#[derive(Debug, Serialize)]
+struct RemoteRequest<'a> {
+    module: &'a str,
+    offsets: Vec<u64>,
+}
+
+impl RemoteSymbolizer {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    fn request(&self, module: &str, offsets: Vec<u64>) -> Result<Vec<Option<Symbol>>> {
+        let expected = offsets.len();
+        let symbols: Vec<Option<Symbol>> = ureq::post(format!("{}/symbolize", self.url))
+            .config()
+            .timeout_global(Some(self.timeout))
+            .build()
+            .send_json(RemoteRequest { module, offsets })?
+            .body_mut()
+            .read_json()?;
+        if symbols.len() != expected {
+            return Err(anyhow::anyhow!(
+                "symbol server returned {} symbols for {expected} offsets",
+                symbols.len()
+            ));
+        }
+        Ok(symbols)
+    }
+}
+
+impl Symbolizer for RemoteSymbolizer {
+    fn symbolize(&self, addrs: &[u64]) -> Vec<Option<Symbol>> {
+        let mut results = vec![None; addrs.len()];
+        let maps = match MemoryMaps::current() {
+            Ok(maps) => maps,
+            Err(e) => {
+                log::warn!("failed to read memory maps for remote symbolization: {e}");
+                return results;
+            }
+        };
+
+        // Group addresses by module to issue one request per module
+        let mut modules: HashMap<&str, Vec<(usize, u64)>> = HashMap::new();
+        for (idx, addr) in addrs.iter().enumerate() {
+            if let Some((module, offset)) = maps.lookup(addr.saturating_sub(1)) {
+                modules.entry(module).or_default().push((idx, offset));
+            }
+        }
+
+        for (module, entries) in modules {
+            let offsets = entries.iter().map(|(_, offset)| *offset).collect();
+            match blocking(|| self.request(module, offsets)) {
+                Ok(symbols) => {
+                    for ((idx, _), symbol) in entries.into_iter().zip(symbols) {
+                        results[idx] = symbol;
+                    }
+                }
+                Err(e) => log::warn!("symbol server {} failed for {module}: {e}", self.url),
+            }
+        }
+        results
+    }
+}
+
+/// Run the blocking `f`, handing the other tasks of the worker thread it is
+/// called on, e.g. by a table scan, over to other workers first
+fn blocking<R>(f: impl FnOnce() -> R) -> R {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+/// File-backed executable mappings of the current process
+#[derive(Debug, Default)]
+struct MemoryMaps {
+    /// `(start, end, file offset, path)`, sorted by start address
+    regions: Vec<(u64, u64, u64, String)>,
+}
+
+impl MemoryMaps {
+    fn current() -> Result<Self> {
+        Ok(Self::parse(&std::fs::read_to_string("/proc/self/maps")?))
+    }
+
+    fn parse(maps: &str) -> Self {
+        let mut regions: Vec<_> = maps
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let (start, end) = fields.next()?.split_once('-')?;
+                let perms = fields.next()?;
+                let offset = fields.next()?;
+                let path = fields.nth(2)?;
+                if !perms.contains('x') || !path.starts_with('/') {
+                    return None;
+                }
+                Some((
+                    u64::from_str_radix(start, 16).ok()?,
+                    u64::from_str_radix(end, 16).ok()?,
+                    u64::from_str_radix(offset, 16).ok()?,
+                    path.to_string(),
+                ))
+            })
+            .collect();
+        regions.sort_by_key(|region| region.0);
+        Self { regions }
+    }
+
+    /// Module path and file offset of `addr`
+    fn lookup(&self, addr: u64) -> Option<(&str, u64)> {
+        let idx = self.regions.partition_point(|region| region.0 <= addr);
+        let (start, end, offset, path) = self.regions.get(idx.checked_sub(1)?)?;
+        (addr < *end).then(|| (path.as_str(), addr - start + offset))
+    }
+}
+
+/// Chain of symbolizer backends with a per-address cache
+#[derive(Debug)]
+pub struct SymbolizerService {
+    backends: RwLock<Vec<Arc<dyn Symbolizer>>>,
+    /// Consulted after all other backends
+    remote: RwLock<Option<Arc<dyn Symbolizer>>>,
+    cache: Mutex<HashMap<u64, Option<Symbol>>>,
+}
+
+impl Default for SymbolizerService {
+    fn default() -> Self {
+        Self::new(vec![Arc::new(DwarfSymbolizer)])
+    }
+}
+
+impl SymbolizerService {
+    pub fn new(backends: Vec<Arc<dyn Symbolizer>>) -> Self {
+        Self {
+            backends: RwLock::new(backends),
+            remote: Default::default(),
+            cache: Default::default(),
+        }
+    }
+
+    /// Append a backend, consulted for addresses earlier backends could not fully resolve
+    pub fn register(&self, backend: Arc<dyn Symbolizer>) {
+        self.backends.write().unwrap().push(backend);
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Use `url` as remote symbol server, or stop using one with `None`
+    pub fn set_symbol_server(&self, url: Option<&str>) {
+        *self.remote.write().unwrap() =
+            url.map(|url| Arc::new(RemoteSymbolizer::new(url)) as Arc<dyn Symbolizer>);
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Resolve `addrs`, returning one entry per address
+    pub fn symbolize(&self, addrs: &[u64]) -> Vec<Option<Symbol>> {
+        let mut results: Vec<Option<Option<Symbol>>> = {
+            let cache = self.cache.lock().unwrap();
+            addrs.iter().map(|addr| cache.get(addr).cloned()).collect()
+        };
+
+        let mut resolved: Vec<Option<Symbol>> = vec![None; addrs.len()];
+        let mut pending: Vec<usize> = (0..addrs.len())
+            .filter(|idx| results[*idx].is_none())
+            .collect();
+        let backends = self.backends.read().unwrap().clone();
+        let remote = self.remote.read().unwrap().clone();
+        for backend in backends.iter().chain(remote.iter()) {
+            if pending.is_empty() {
+                break;
+            }
+            let query: Vec<u64> = pending.iter().map(|idx| addrs[*idx]).collect();
+            for (idx, symbol) in pending.iter().zip(backend.symbolize(&query)) {
+                // A later backend only replaces a partial result with a better one
+                if let Some(symbol) = symbol {
+                    if resolved[*idx].is_none() || symbol.status() == SymbolStatus::Resolved {
+                        resolved[*idx] = Some(symbol);
+                    }
+                }
+            }
+            pending.retain(|idx| {
+                resolved[*idx].as_ref().map(Symbol::status) != Some(SymbolStatus::Resolved)
+            });
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() + addrs.len() > CACHE_CAPACITY {
+            cache.clear();
+        }
+        for (idx, addr) in addrs.iter().enumerate() {
+            if results[idx].is_none() {
+                cache.insert(*addr, resolved[idx].clone());
+                results[idx] = Some(resolved[idx].take());
+            }
+        }
+        results.into_iter().map(Option::flatten).collect()
+    }
+
+    /// Fill in function, file and line of the native frames in `frames`
+    pub fn symbolize_frames(&self, frames: &mut [CallFrame]) {
+        let addrs: Vec<u64> = frames
+            .iter()
+            .filter_map(|frame| match frame {
+                CallFrame::CFrame {
+                    ip,
+                    status: SymbolStatus::Unresolved,
+                    ..
+                } => parse_address(ip),
+                _ => None,
+            })
+            .collect();
+        let mut symbols = self.symbolize(&addrs).into_iter();
+
+        for frame in frames.iter_mut() {
+            let CallFrame::CFrame {
+                ip,
+                file,
+                func,
+                lineno,
+                status,
+            } = frame
+            else {
+                continue;
+            };
+            if *status != SymbolStatus::Unresolved || parse_address(ip).is_none() {
+                continue;
+            }
+            match symbols.next().flatten() {
+                Some(symbol) => {
+                    *status = symbol.status();
+                    *func = symbol.func;
+                    *file = symbol.file.unwrap_or_default();
+                    *lineno = symbol.lineno.unwrap_or(0) as i64;
+                }
+                None => *func = format!("unknown@{ip}"),
+            }
+        }
+    }
+}
+
+fn parse_address(ip: &str) -> Option<u64> {
+    u64::from_str_radix(ip.trim_start_matches("0x"), 16).ok()
+}
+
+pub static SYMBOLIZER: Lazy<SymbolizerService> = Lazy::new(|| {
+    let service = SymbolizerService::default();
+    if let Ok(url) = std::env::var("PROBING_SYMBOL_SERVER") {
+        if !url.is_empty() {
+            service.set_symbol_server(Some(&url));
+        }
+    }
+    service
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct FixedSymbolizer(HashMap<u64, Symbol>);
+
+    impl Symbolizer for FixedSymbolizer {
+        fn symbolize(&self, addrs: &[u64]) -> Vec<Option<Symbol>> {
+            addrs.iter().map(|addr| self.0.get(addr).cloned()).collect()
+        }
+    }
+
+    fn symbol(func: &str, line: Option<u32>) -> Symbol {
+        Symbol {
+            func: func.to_string(),
+            file: line.map(|_| "a.cc".to_string()),
+            lineno: line,
+        }
+    }
+
+    #[test]
+    fn test_backend_chain_prefers_resolved_symbols() {
+        let partial = FixedSymbolizer(HashMap::from([
+            (0x10, symbol("foo", None)),
+            (0x20, symbol("bar", None)),
+        ]));
+        let full = FixedSymbolizer(HashMap::from([(0x10, symbol("foo", Some(3)))]));
+        let service = SymbolizerService::new(vec![Arc::new(partial), Arc::new(full)]);
+
+        let symbols = service.symbolize(&[0x10, 0x20, 0x30]);
+        assert_eq!(symbols[0], Some(symbol("foo", Some(3))));
+        assert_eq!(symbols[1], Some(symbol("bar", None)));
+        assert_eq!(symbols[2], None);
+        assert_eq!(service.cache.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_symbolize_frames_sets_status() {
+        let fixed = FixedSymbolizer(HashMap::from([(0x10, symbol("foo", Some(3)))]));
+        let service = SymbolizerService::new(vec![Arc::new(fixed)]);
+        let frame = |ip: &str| CallFrame::CFrame {
+            ip: ip.to_string(),
+            file: String::new(),
+            func: String::new(),
+            lineno: 0,
+            status: SymbolStatus::Unresolved,
+        };
+        let mut frames = vec![frame("0x10"), frame("0x30")];
+        service.symbolize_frames(&mut frames);
+
+        assert_eq!(
+            frames[0],
+            CallFrame::CFrame {
+                ip: "0x10".to_string(),
+                file: "a.cc".to_string(),
+                func: "foo".to_string(),
+                lineno: 3,
+                status: SymbolStatus::Resolved,
+            }
+        );
+        assert!(matches!(
+            &frames[1],
+            CallFrame::CFrame { func, status: SymbolStatus::Unresolved, .. } if func == "unknown@0x30"
+        ));
+    }
+
+    #[test]
+    fn test_dwarf_symbolizer_resolves_captured_frame() {
+        let mut ips = vec![];
+        backtrace::trace(|frame| {
+            ips.push(frame.ip() as u64);
+            true
+        });
+        let symbols = DwarfSymbolizer.symbolize(&ips);
+        assert!(symbols.iter().flatten().any(|symbol| symbol
+            .func
+            .contains("test_dwarf_symbolizer_resolves_captured_frame")));
+    }
+
+    #[test]
+    fn test_memory_maps_lookup() {
+        let maps = MemoryMaps::parse(
+            "7f0000000000-7f0000001000 r--p 00000000 08:01 1 /usr/lib/libfoo.so\n\
+             7f0000001000-7f0000003000 r-xp 00001000 08:01 1 /usr/lib/libfoo.so\n\
+             7ffd00000000-7ffd00001000 r-xp 00000000 00:00 0 [vdso]\n",
+        );
+        assert_eq!(
+            maps.lookup(0x7f0000001010),
+            Some(("/usr/lib/libfoo.so", 0x1010))
+        );
+        assert_eq!(maps.lookup(0x7f0000000010), None);
+        assert_eq!(maps.lookup(0x7ffd00000010), None);
+    }
+
+    #[test]
+    fn test_blocking_on_any_runtime() {
+        assert_eq!(blocking(|| 1), 1);
+        for runtime in [
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap(),
+            tokio::runtime::Builder::new_multi_thread().build().unwrap(),
+        ] {
+            let value =
+                runtime.block_on(async { tokio::spawn(async { blocking(|| 2) }).await.unwrap() });
+            assert_eq!(value, 2);
+        }
+    }
+}
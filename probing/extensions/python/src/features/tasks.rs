@@ -0,0 +1,224 @@
+//! Tasks run by distributed task frameworks in the probed process.
+//!
+//! The integrations of `probing.ext.tasks` wrap the functions submitted as
+//! Ray and Dask tasks, so a worker running one records it when it starts and
+//! again when it ends, with the ids the framework gave it:
+//!
+//! - `ray.tasks`: Ray tasks, with their task, worker, node, job and actor ids;
+//! - `dask.tasks`: Dask tasks, keyed by the task key, on the worker address.
+//!
+//! Each table keeps the last [`MAX_ROWS`] tasks, running tasks included.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use probing_core::core::{
+    CustomTable, DataType, Field, Float64Array, Int64Array, RecordBatch, Schema, SchemaRef,
+    StringArray, TablePluginHelper,
+};
+use pyo3::prelude::*;
+
+/// Number of tasks kept per framework, older ones are dropped first
+const MAX_ROWS: usize = 10_000;
+
+pub const RAY: &str = "ray";
+pub const DASK: &str = "dask";
+
+/// A task run in this process
+#[derive(Debug, Clone, FromPyObject)]
+#[pyo3(from_item_all)]
+pub struct TaskRecord {
+    pub task_id: String,
+    /// Qualified name of the task function
+    pub name: String,
+    /// `running`, `finished` or `failed`
+    pub state: String,
+    pub worker_id: String,
+    pub node_id: Option<String>,
+    pub job_id: Option<String>,
+    pub actor_id: Option<String>,
+    /// Start time, nanoseconds since the unix epoch
+    pub start: i64,
+    pub end: Option<i64>,
+    /// Seconds
+    pub duration: Option<f64>,
+    pub error: Option<String>,
+    /// Trace of the span running the task, continued from the submitter
+    pub trace_id: Option<i64>,
+    pub span_id: Option<i64>,
+    /// Span that submitted the task, in the submitting process
+    pub parent_span_id: Option<i64>,
+}
+
+pub static TASKS: Lazy<Mutex<HashMap<String, VecDeque<TaskRecord>>>> = Lazy::new(Default::default);
+
+/// Record a task of `framework`, replacing the row of the same task recorded
+/// while it was running
+#[pyfunction]
+pub fn _record_task(framework: String, task: TaskRecord) {
+    let mut tasks = TASKS.lock().unwrap();
+    let rows = tasks.entry(framework).or_default();
+    let running = rows
+        .iter()
+        .rposition(|row| row.task_id == task.task_id && row.state == "running");
+    match running {
+        Some(pos) => rows[pos] = task,
+        None => {
+            if rows.len() >= MAX_ROWS {
+                rows.pop_front();
+            }
+            rows.push_back(task);
+        }
+    }
+}
+
+pub fn register_tasks_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_record_task, module)?)?;
+    Ok(())
+}
+
+fn schema() -> SchemaRef {
+    SchemaRef::new(Schema::new(vec![
+        Field::new("task_id", DataType::Utf8, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("state", DataType::Utf8, false),
+        Field::new("worker_id", DataType::Utf8, false),
+        Field::new("node_id", DataType::Utf8, true),
+        Field::new("job_id", DataType::Utf8, true),
+        Field::new("actor_id", DataType::Utf8, true),
+        Field::new("start", DataType::Int64, false),
+        Field::new("end", DataType::Int64, true),
+        Field::new("duration", DataType::Float64, true),
+        Field::new("error", DataType::Utf8, true),
+        Field::new("trace_id", DataType::Int64, true),
+        Field::new("span_id", DataType::Int64, true),
+        Field::new("parent_span_id", DataType::Int64, true),
+    ]))
+}
+
+fn data(framework: &str) -> Vec<RecordBatch> {
+    let tasks = TASKS.lock().unwrap();
+    let empty = VecDeque::new();
+    let rows = tasks.get(framework).unwrap_or(&empty);
+    let text =
+        |f: fn(&TaskRecord) -> &str| Arc::new(StringArray::from_iter_values(rows.iter().map(f)));
+    let opt_text =
+        |f: fn(&TaskRecord) -> Option<&str>| Arc::new(StringArray::from_iter(rows.iter().map(f)));
+    let opt_int =
+        |f: fn(&TaskRecord) -> Option<i64>| Arc::new(Int64Array::from_iter(rows.iter().map(f)));
+    let columns: Vec<Arc<dyn datafusion::arrow::array::Array>> = vec![
+        text(|t| t.task_id.as_str()),
+        text(|t| t.name.as_str()),
+        text(|t| t.state.as_str()),
+        text(|t| t.worker_id.as_str()),
+        opt_text(|t| t.node_id.as_deref()),
+        opt_text(|t| t.job_id.as_deref()),
+        opt_text(|t| t.actor_id.as_deref()),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|t| t.start))),
+        opt_int(|t| t.end),
+        Arc::new(Float64Array::from_iter(rows.iter().map(|t| t.duration))),
+        opt_text(|t| t.error.as_deref()),
+        opt_int(|t| t.trace_id),
+        opt_int(|t| t.span_id),
+        opt_int(|t| t.parent_span_id),
+    ];
+    match RecordBatch::try_new(schema(), columns) {
+        Ok(batch) => vec![batch],
+        Err(e) => {
+            log::error!("Failed to build {framework} tasks batch: {e}");
+            vec![]
+        }
+    }
+}
+
+/// `ray.tasks`
+#[derive(Default, Debug)]
+pub struct RayTasksTable {}
+
+impl CustomTable for RayTasksTable {
+    fn name() -> &'static str {
+        "tasks"
+    }
+
+    fn schema() -> SchemaRef {
+        schema()
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        data(RAY)
+    }
+}
+
+/// `dask.tasks`
+#[derive(Default, Debug)]
+pub struct DaskTasksTable {}
+
+impl CustomTable for DaskTasksTable {
+    fn name() -> &'static str {
+        "tasks"
+    }
+
+    fn schema() -> SchemaRef {
+        schema()
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        data(DASK)
+    }
+}
+
+pub type RayTasksPlugin = TablePluginHelper<RayTasksTable>;
+pub type DaskTasksPlugin = TablePluginHelper<DaskTasksTable>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_tasks() {
+        let task = |id: &str, state: &str| {
+            Python::with_gil(|py| {
+                let row = pyo3::types::PyDict::new(py);
+                for (key, value) in [
+                    ("task_id", id),
+                    ("name", "train.step"),
+                    ("state", state),
+                    ("worker_id", "tcp://10.0.0.1:4000"),
+                ] {
+                    row.set_item(key, value).unwrap();
+                }
+                row.set_item("start", 1_i64).unwrap();
+                for key in [
+                    "node_id",
+                    "job_id",
+                    "actor_id",
+                    "end",
+                    "duration",
+                    "error",
+                    "trace_id",
+                    "span_id",
+                    "parent_span_id",
+                ] {
+                    row.set_item(key, py.None()).unwrap();
+                }
+                row.extract::<TaskRecord>().unwrap()
+            })
+        };
+        _record_task(DASK.to_string(), task("a", "running"));
+        _record_task(DASK.to_string(), task("b", "running"));
+        _record_task(DASK.to_string(), task("a", "finished"));
+
+        let batches = DaskTasksTable::data();
+        assert_eq!(batches[0].num_rows(), 2);
+        let states = batches[0]
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(states.value(0), "finished");
+        assert_eq!(states.value(1), "running");
+        assert_eq!(batches[0].column(4).null_count(), 2);
+        assert_eq!(RayTasksTable::data()[0].num_rows(), 0);
+    }
+}
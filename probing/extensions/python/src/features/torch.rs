@@ -1,11 +1,11 @@
-use std::{collections::BTreeMap, thread};
+use std::collections::BTreeMap;
 
 use anyhow::Result;
 use html_escape::encode_text;
 use inferno;
 use log::{error, warn};
 
-use crate::extensions::python::PythonPlugin;
+use crate::features::op_summary::OP_SUMMARY;
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct Frame {
@@ -13,70 +13,19 @@ struct Frame {
     module: String,
 }
 
+/// Folded flamegraph lines built from the median duration of every module and stage
 pub fn query_profiling() -> Result<Vec<String>> {
-    let data = thread::spawn(|| -> Result<probing_proto::types::DataFrame> {
-        let engine = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap()
-            .block_on(async {
-                probing_core::create_engine()
-                    .with_plugin(PythonPlugin::create("python"))
-                    .build()
-                    .await
-            })?;
-
-        let query = r#"
-        select module, stage, median(duration)
-            from python.torch_trace
-            where module <> 'None'
-            group by module, stage
-            order by (stage, module);
-        "#;
-
-        // Check if we're already inside a tokio runtime to avoid nested runtime panic
-        match tokio::runtime::Handle::try_current() {
-            Ok(_handle) => {
-                // Inside a runtime, spawn a new thread
-                std::thread::spawn(move || {
-                    tokio::runtime::Builder::new_current_thread()
-                        .enable_all()
-                        .build()
-                        .unwrap()
-                        .block_on(async { engine.async_query(query).await })
-                })
-                .join()
-                .map_err(|_| anyhow::anyhow!("Thread panicked"))?
-                .map_err(|e| anyhow::anyhow!(e))?
-                .ok_or_else(|| anyhow::anyhow!("Query returned no data"))
-            }
-            Err(_) => {
-                // Not in a runtime, create a new one
-                tokio::runtime::Builder::new_multi_thread()
-                    .worker_threads(4)
-                    .enable_all()
-                    .build()
-                    .unwrap()
-                    .block_on(async { engine.async_query(query).await })?
-                    .ok_or_else(|| anyhow::anyhow!("Query returned no data"))
-            }
-        }
-    })
-    .join()
-    .map_err(|_| anyhow::anyhow!("error joining thread"))??;
-
     let mut frames = BTreeMap::default();
 
-    for line in data.iter() {
+    let summary = OP_SUMMARY
+        .lock()
+        .map_err(|e| anyhow::anyhow!("failed to lock op summary: {e}"))?;
+    for ((module, stage), stats) in summary.ops() {
         let frame = Frame {
-            stage: line[1].to_string(),
-            module: line[0].to_string(),
-        };
-        let duration = match line[2] {
-            probing_proto::types::Ele::F32(x) => x as f64,
-            probing_proto::types::Ele::F64(x) => x,
-            _ => 0 as f64,
+            stage: stage.clone(),
+            module: module.clone(),
         };
+        let duration = stats.p50();
 
         frames
             .entry(frame.clone())
@@ -5,7 +5,9 @@ use std::cell::RefCell;
 use std::sync::{Arc, Mutex};
 
 use probing_core::trace::Span as RawSpan;
-use probing_core::trace::{attr, Event as RawEvent, SpanStatus, Timestamp};
+use probing_core::trace::{attr, Attribute, Event as RawEvent, Location, SpanStatus, Timestamp};
+use probing_core::trace::{register_sink, slo, SpanSink};
+use probing_proto::prelude::{Ele, TimeSeries};
 
 use crate::features::convert::{ele_to_python, python_to_ele};
 
@@ -14,6 +16,25 @@ thread_local! {
     static SPAN_STACK: RefCell<Vec<PyObject>> = RefCell::new(Vec::new());
 }
 
+/// Converts an attribute value, objects other than scalars as the compact JSON
+/// of their serializer in `probing.tracing`, e.g. the shape, dtype and hash of
+/// a tensor rather than its repr
+fn attribute_to_ele(value: &Bound<'_, PyAny>) -> PyResult<Ele> {
+    let json = value
+        .py()
+        .import("probing.tracing")
+        .and_then(|tracing| tracing.call_method1("_attribute_json", (value,)))
+        .and_then(|json| json.extract::<Option<String>>());
+    match json {
+        Ok(Some(json)) => Ok(Ele::Text(json)),
+        Ok(None) => python_to_ele(value),
+        Err(err) => {
+            log::debug!("Failed to serialize a span attribute: {err}");
+            python_to_ele(value)
+        }
+    }
+}
+
 /// Python binding for Span
 #[pyclass]
 #[derive(Clone)]
@@ -54,6 +75,29 @@ impl Span {
         }
     }
 
+    /// Creates a span continuing a trace started in another process, as a
+    /// child of its span `parent_id`.
+    #[staticmethod]
+    #[pyo3(signature = (trace_id, parent_id, name, *, kind=None, location=None))]
+    fn remote_child(
+        trace_id: u64,
+        parent_id: u64,
+        name: String,
+        kind: Option<String>,
+        location: Option<String>,
+    ) -> Self {
+        let span = RawSpan::new_remote_child(
+            trace_id,
+            parent_id,
+            name,
+            kind.as_deref(),
+            location.as_deref(),
+        );
+        Span {
+            inner: Arc::new(Mutex::new(span)),
+        }
+    }
+
     /// Gets the trace ID.
     #[getter]
     fn trace_id(&self) -> u64 {
@@ -97,7 +141,7 @@ impl Span {
             .lock()
             .expect("Failed to acquire lock on span (lock poisoned)")
             .name
-            .clone()
+            .to_string()
     }
 
     /// Gets the span kind.
@@ -107,7 +151,8 @@ impl Span {
             .lock()
             .expect("Failed to acquire lock on span (lock poisoned)")
             .kind
-            .clone()
+            .as_deref()
+            .map(str::to_string)
     }
 
     /// Gets the span status.
@@ -163,6 +208,16 @@ impl Span {
             .map(|t| t.0)
     }
 
+    /// Gets the CPU time in nanoseconds the thread spent in the span, if it
+    /// was measured and the span ended on the thread that started it.
+    #[getter]
+    fn cpu_time_ns(&self) -> Option<u64> {
+        self.inner
+            .lock()
+            .expect("Failed to acquire lock on span (lock poisoned)")
+            .cpu_time_ns
+    }
+
     /// Gets the location from location if available.
     #[getter]
     fn location(&self) -> Option<String> {
@@ -171,10 +226,7 @@ impl Span {
             .expect("Failed to acquire lock on span (lock poisoned)")
             .loc
             .as_ref()
-            .and_then(|loc| match loc {
-                probing_core::trace::Location::UnknownLocation(path) => Some(path.clone()),
-                probing_core::trace::Location::KnownLocation(_) => None,
-            })
+            .map(|loc| loc.as_str().to_string())
     }
 
     /// Internal method to set initial attributes during span creation.
@@ -191,7 +243,7 @@ impl Span {
             .expect("Failed to acquire lock on span (lock poisoned)");
         for (key, value) in attrs_dict.iter() {
             let key_str = key.extract::<String>()?;
-            let ele = python_to_ele(&value)?;
+            let ele = attribute_to_ele(&value)?;
             inner.attrs.push(attr(key_str, ele));
         }
         Ok(())
@@ -212,14 +264,14 @@ impl Span {
                 if let Ok(dict) = attr_obj.bind(py).downcast::<PyDict>() {
                     for (k, v) in dict.iter() {
                         let key = k.extract::<String>()?;
-                        let ele = python_to_ele(&v)?;
+                        let ele = attribute_to_ele(&v)?;
                         converted.push(attr(key, ele));
                     }
                 } else if let Ok(list) = attr_obj.bind(py).downcast::<PyList>() {
                     if list.len() == 2 {
                         let key = list.get_item(0)?.extract::<String>()?;
                         let value = list.get_item(1)?;
-                        let ele = python_to_ele(&value)?;
+                        let ele = attribute_to_ele(&value)?;
                         converted.push(attr(key, ele));
                     }
                 }
@@ -239,18 +291,12 @@ impl Span {
 
     /// Ends the span.
     fn end(&mut self) {
-        self.inner
-            .lock()
-            .expect("Failed to acquire lock on span (lock poisoned)")
-            .end();
+        self.finish(false, |span| span.end());
     }
 
     /// Ends the span with an error message.
     fn end_error(&mut self, error_message: Option<String>) {
-        self.inner
-            .lock()
-            .expect("Failed to acquire lock on span (lock poisoned)")
-            .end_error(error_message);
+        self.finish(true, |span| span.end_error(error_message));
     }
 
     /// Gets all attributes as a dictionary.
@@ -319,6 +365,13 @@ impl Span {
                     return Ok(py.None());
                 }
             }
+            "cpu_time_ns" => {
+                if let Some(ns) = self.cpu_time_ns() {
+                    return Ok(ns.into_bound_py_any(py)?.into());
+                } else {
+                    return Ok(py.None());
+                }
+            }
             _ => {}
         }
 
@@ -351,14 +404,11 @@ impl Span {
     /// Context manager exit (for `with` statement support).
     fn __exit__(
         slf: PyRef<Self>,
-        _exc_type: Option<&Bound<'_, PyAny>>,
+        exc_type: Option<&Bound<'_, PyAny>>,
         _exc_val: Option<&Bound<'_, PyAny>>,
         _exc_tb: Option<&Bound<'_, PyAny>>,
     ) -> PyResult<bool> {
-        slf.inner
-            .lock()
-            .expect("Failed to acquire lock on span (lock poisoned)")
-            .end();
+        slf.finish(exc_type.is_some(), |span| span.end());
 
         SPAN_STACK.with(|stack| {
             let mut stack = stack.borrow_mut();
@@ -387,6 +437,22 @@ impl Span {
     }
 }
 
+impl Span {
+    /// End the span with `end`, counting it against its objective the first
+    /// time it ends, see [`slo`]
+    fn finish(&self, error: bool, end: impl FnOnce(&mut RawSpan)) {
+        let mut span = self
+            .inner
+            .lock()
+            .expect("Failed to acquire lock on span (lock poisoned)");
+        let ended = span.is_ended();
+        end(&mut span);
+        if !ended {
+            slo::observe_span(&span, error);
+        }
+    }
+}
+
 /// Gets the current active span.
 #[pyfunction]
 fn current_span(py: Python) -> PyResult<Option<PyObject>> {
@@ -396,6 +462,25 @@ fn current_span(py: Python) -> PyResult<Option<PyObject>> {
     })
 }
 
+/// Copy of the active spans of the calling thread, outermost first.
+#[pyfunction]
+fn _capture_span_stack(py: Python) -> Vec<PyObject> {
+    SPAN_STACK.with(|stack| stack.borrow().iter().map(|s| s.clone_ref(py)).collect())
+}
+
+/// Replace the active spans of the calling thread, returning the previous ones.
+///
+/// Spans captured in one thread are restored in another so that work handed
+/// over to a thread pool stays a child of the span that submitted it.
+#[pyfunction]
+fn _restore_span_stack(stack: Vec<Bound<'_, PyAny>>) -> PyResult<Vec<PyObject>> {
+    let stack = stack
+        .into_iter()
+        .map(|span| Ok(span.downcast_into::<Span>()?.into_any().unbind()))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(SPAN_STACK.with(|current| current.replace(stack)))
+}
+
 /// Internal function to create a span - called by Python wrapper.
 /// This is a low-level function that directly creates a span.
 #[pyfunction]
@@ -422,6 +507,19 @@ fn _span_raw(
     Ok(span)
 }
 
+/// Measure the CPU time of the thread running each span, for spans started
+/// from now on.
+#[pyfunction]
+fn _set_span_cpu_time(enabled: bool) {
+    probing_core::trace::set_cpu_time(enabled);
+}
+
+/// Whether spans measure the CPU time of their thread.
+#[pyfunction]
+fn _span_cpu_time_enabled() -> bool {
+    probing_core::trace::cpu_time_enabled()
+}
+
 /// Python binding for Event
 #[pyclass]
 pub struct Event {
@@ -440,14 +538,14 @@ impl Event {
                 if let Ok(dict) = attr_obj.bind(py).downcast::<PyDict>() {
                     for (k, v) in dict.iter() {
                         let key = k.extract::<String>()?;
-                        let ele = python_to_ele(&v)?;
+                        let ele = attribute_to_ele(&v)?;
                         converted.push(attr(key, ele));
                     }
                 } else if let Ok(list) = attr_obj.bind(py).downcast::<PyList>() {
                     if list.len() == 2 {
                         let key = list.get_item(0)?.extract::<String>()?;
                         let value = list.get_item(1)?;
-                        let ele = python_to_ele(&value)?;
+                        let ele = attribute_to_ele(&value)?;
                         converted.push(attr(key, ele));
                     }
                 }
@@ -498,11 +596,165 @@ impl Event {
     }
 }
 
+/// Name and columns of the table written by `probing.tracing.TraceEvent`
+const TRACE_TABLE: &str = "trace_event";
+const TRACE_COLUMNS: [&str; 12] = [
+    "record_type",
+    "trace_id",
+    "span_id",
+    "name",
+    "time",
+    "thread_id",
+    "parent_id",
+    "kind",
+    "location",
+    "attributes",
+    "event_attributes",
+    "cpu_time_ns",
+];
+
+/// Writes spans from `probe_span!` into the same table as Python spans, so
+/// agent-internal latency shows up next to user traces.
+struct TraceTableSink;
+
+impl TraceTableSink {
+    fn table() -> Arc<Mutex<TimeSeries>> {
+        crate::extensions::python::EXTERN_TABLES
+            .lock()
+            .unwrap()
+            .entry(TRACE_TABLE.to_string())
+            .or_insert_with(|| {
+                probing_core::catalog::table_registered("python", &format!("python.{TRACE_TABLE}"));
+                Arc::new(Mutex::new(
+                    TimeSeries::builder()
+                        .with_columns(TRACE_COLUMNS.iter().map(|c| c.to_string()).collect())
+                        .build(),
+                ))
+            })
+            .clone()
+    }
+
+    fn attrs_json(attrs: &[Attribute]) -> String {
+        if attrs.is_empty() {
+            return String::new();
+        }
+        let map: serde_json::Map<String, serde_json::Value> = attrs
+            .iter()
+            .map(|a| {
+                let value = match a.value() {
+                    Ele::Nil => serde_json::Value::Null,
+                    Ele::BOOL(x) => (*x).into(),
+                    Ele::I32(x) => (*x).into(),
+                    Ele::I64(x) => (*x).into(),
+                    Ele::F32(x) => (*x).into(),
+                    Ele::F64(x) => (*x).into(),
+                    other => other.to_string().into(),
+                };
+                (a.key().to_string(), value)
+            })
+            .collect();
+        serde_json::Value::Object(map).to_string()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write(
+        record_type: &str,
+        span: Option<&RawSpan>,
+        name: &str,
+        time: Timestamp,
+        location: &str,
+        attributes: String,
+        event_attributes: String,
+        cpu_time_ns: Option<u64>,
+    ) {
+        let values: Vec<Ele> = vec![
+            record_type.into(),
+            (span.map(|s| s.trace_id).unwrap_or_default() as i64).into(),
+            (span.map(|s| s.span_id).unwrap_or_default() as i64).into(),
+            name.into(),
+            time.as_nanos_i64().into(),
+            (span.map(|s| s.thread_id).unwrap_or_default() as i64).into(),
+            span.and_then(|s| s.parent_id)
+                .map(|id| id as i64)
+                .unwrap_or(-1)
+                .into(),
+            span.and_then(|s| s.kind.as_deref())
+                .unwrap_or_default()
+                .into(),
+            location.into(),
+            attributes.into(),
+            event_attributes.into(),
+            cpu_time_ns.map(|ns| ns as i64).unwrap_or(-1).into(),
+        ];
+        let t = time.as_micros_i64();
+        let table = Self::table();
+        let mut ts = table.lock().unwrap();
+        // spans are flushed by queries, so a full table never blocks here
+        if let Some(Err(err)) =
+            crate::features::ingest::append(TRACE_TABLE, &mut ts, |ts| ts.append(t.into(), values))
+        {
+            log::debug!("failed to record internal span: {err}");
+        }
+    }
+}
+
+fn location_str(span: &RawSpan) -> &str {
+    span.loc.as_ref().map(Location::as_str).unwrap_or_default()
+}
+
+impl SpanSink for TraceTableSink {
+    fn on_start(&self, span: &RawSpan) {
+        Self::write(
+            "span_start",
+            Some(span),
+            &span.name,
+            span.start,
+            location_str(span),
+            Self::attrs_json(&span.attrs),
+            String::new(),
+            None,
+        );
+    }
+
+    fn on_event(&self, span: Option<&RawSpan>, event: &RawEvent) {
+        Self::write(
+            "event",
+            span,
+            &event.name,
+            event.timestamp,
+            span.map(location_str).unwrap_or_default(),
+            String::new(),
+            Self::attrs_json(&event.attributes),
+            None,
+        );
+    }
+
+    fn on_end(&self, span: &RawSpan) {
+        Self::write(
+            "span_end",
+            Some(span),
+            "",
+            span.end.unwrap_or_else(Timestamp::now),
+            "",
+            String::new(),
+            String::new(),
+            span.cpu_time_ns,
+        );
+    }
+}
+
 pub fn register_tracing_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    static SINK: std::sync::Once = std::sync::Once::new();
+    SINK.call_once(|| register_sink(Arc::new(TraceTableSink)));
+
     module.add_class::<Span>()?;
     module.add_class::<Event>()?;
     module.add_function(wrap_pyfunction!(_span_raw, module)?)?;
     module.add_function(wrap_pyfunction!(current_span, module)?)?;
+    module.add_function(wrap_pyfunction!(_capture_span_stack, module)?)?;
+    module.add_function(wrap_pyfunction!(_restore_span_stack, module)?)?;
+    module.add_function(wrap_pyfunction!(_set_span_cpu_time, module)?)?;
+    module.add_function(wrap_pyfunction!(_span_cpu_time_enabled, module)?)?;
 
     Ok(())
 }
@@ -1,7 +1,29 @@
+//! Eval frame hook of the Python VM.
+//!
+//! The hook keeps a shadow stack of the running Python frames for the stack
+//! tracer, and records the calls it samples into `tracer.calls`. Sampling is
+//! off until `tracer.sample_every` is set, and is bounded by three controls
+//! applied in order: one call out of `sample_every`, calls of the modules
+//! passing the `tracing.include` / `tracing.exclude` filter, and at most
+//! `tracer.max_events_per_sec` calls per second through a token bucket. Calls
+//! left out by each control are counted in `tracer.sampling`, so the cost of
+//! function level tracing stays bounded in production and what was missed is
+//! known. The same filter limits the calls `probing.inspect.trace` follows.
+
 use core::ffi::c_int;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use nix::libc;
+use once_cell::sync::Lazy;
 use pyo3::prelude::*;
 
+use probing_core::core::{
+    ArrayRef, CustomTable, DataType, Field, Int64Array, RecordBatch, Schema, SchemaRef,
+    StringArray, TablePluginHelper,
+};
 use probing_proto::prelude::CallFrame;
 
 use crate::features::spy::call::RawCallLocation;
@@ -45,12 +67,390 @@ unsafe extern "C" fn rust_eval_frame(
     frame: *mut pyo3::ffi::PyFrameObject,
     extra: c_int,
 ) -> *mut pyo3::ffi::PyObject {
-    PYSTACKS.push(RawCallLocation::from(frame as usize, Some(ts as usize)));
+    let location = RawCallLocation::from(frame as usize, Some(ts as usize));
+    let sampled = sample(&location);
+    PYSTACKS.push(location);
     let ret = PYFRAMEEVAL(ts, frame, extra);
     PYSTACKS.pop();
+    if let Some(call) = sampled {
+        call.finish();
+    }
     ret
 }
 
+/// Calls kept in `tracer.calls`, the oldest are dropped first
+pub const CALLS_CAPACITY: usize = 10_000;
+
+/// Sampling of the calls seen by the eval frame hook
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sampling {
+    /// Record one call out of `every`, none when 0
+    pub every: u64,
+    /// Module patterns whose calls are recorded, all when empty
+    pub include: Vec<String>,
+    /// Module patterns whose calls are never recorded, winning over `include`
+    pub exclude: Vec<String>,
+    /// Calls recorded per second at most, unlimited when 0
+    pub max_per_sec: u64,
+}
+
+/// A call recorded by the hook
+#[derive(Clone, Debug, Default)]
+pub struct VmCall {
+    /// Microseconds since epoch when the call started
+    pub time: i64,
+    pub duration_us: i64,
+    pub thread_id: u64,
+    pub file: String,
+    pub func: String,
+    pub lineno: i64,
+    pub caller: Option<String>,
+}
+
+/// Calls seen while sampling is on, and what became of them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SamplingCounters {
+    pub calls: u64,
+    pub sampled: u64,
+    pub skipped_every: u64,
+    pub skipped_module: u64,
+    pub skipped_rate: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    calls: AtomicU64,
+    sampled: AtomicU64,
+    skipped_every: AtomicU64,
+    skipped_module: AtomicU64,
+    skipped_rate: AtomicU64,
+}
+
+/// Refilled with `rate` tokens per second up to `rate`; a call is recorded
+/// when it takes a token
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last: now,
+        }
+    }
+
+    fn take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Sampler {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    bucket: Option<TokenBucket>,
+}
+
+/// Checked first by the hook, so that it costs one load while sampling is off
+static SAMPLE_EVERY: AtomicU64 = AtomicU64::new(0);
+static SEEN: AtomicU64 = AtomicU64::new(0);
+static COUNTERS: Lazy<Counters> = Lazy::new(Default::default);
+static SAMPLER: Lazy<Mutex<Sampler>> = Lazy::new(Default::default);
+static SAMPLING: Lazy<Mutex<Sampling>> = Lazy::new(Default::default);
+static CALLS: Lazy<Mutex<VecDeque<VmCall>>> = Lazy::new(Default::default);
+
+/// Replace the sampling of the hook; counters and recorded calls are kept
+pub fn set_sampling(sampling: Sampling) {
+    let mut sampler = SAMPLER.lock().unwrap_or_else(|e| e.into_inner());
+    sampler.include = sampling.include.clone();
+    sampler.exclude = sampling.exclude.clone();
+    sampler.bucket =
+        (sampling.max_per_sec > 0).then(|| TokenBucket::new(sampling.max_per_sec, Instant::now()));
+    SAMPLE_EVERY.store(sampling.every, Ordering::Relaxed);
+    *SAMPLING.lock().unwrap_or_else(|e| e.into_inner()) = sampling;
+}
+
+pub fn sampling() -> Sampling {
+    SAMPLING.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn sampling_counters() -> SamplingCounters {
+    SamplingCounters {
+        calls: COUNTERS.calls.load(Ordering::Relaxed),
+        sampled: COUNTERS.sampled.load(Ordering::Relaxed),
+        skipped_every: COUNTERS.skipped_every.load(Ordering::Relaxed),
+        skipped_module: COUNTERS.skipped_module.load(Ordering::Relaxed),
+        skipped_rate: COUNTERS.skipped_rate.load(Ordering::Relaxed),
+    }
+}
+
+/// Calls recorded by the hook, oldest first
+pub fn recorded_calls() -> Vec<VmCall> {
+    CALLS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Module patterns of a comma separated list, e.g. `my_project.*,train`
+pub fn parse_patterns(spec: &str) -> Vec<String> {
+    spec.split(',')
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty())
+        .collect()
+}
+
+/// The module a pattern names: `torch.*` and `torch` both name `torch` and
+/// its submodules
+fn pattern_module(pattern: &str) -> &str {
+    pattern.strip_suffix(".*").unwrap_or(pattern)
+}
+
+/// Whether `file` belongs to the module of `pattern`: `torch.nn` matches files
+/// under a `torch/nn/` directory and `torch/nn.py`, `importlib` also matches
+/// the frozen `<frozen importlib._bootstrap>`
+fn file_matches(pattern: &str, file: &str) -> bool {
+    let module = pattern_module(pattern);
+    let frozen = file
+        .strip_prefix("<frozen ")
+        .and_then(|name| name.strip_suffix('>'));
+    let path = module.replace('.', "/");
+    file.contains(&format!("/{path}/"))
+        || file.starts_with(&format!("{path}/"))
+        || file.ends_with(&format!("/{path}.py"))
+        || file == format!("{path}.py")
+        || frozen.is_some_and(|name| name_matches(pattern, name))
+}
+
+/// Whether the dotted module `name` is the module of `pattern` or one of its
+/// submodules
+fn name_matches(pattern: &str, name: &str) -> bool {
+    let module = pattern_module(pattern);
+    name == module
+        || name
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Whether a module passes the filter: none of `exclude` matches, and one of
+/// `include` does unless it is empty
+fn filter_allows(include: &[String], exclude: &[String], matches: impl Fn(&str) -> bool) -> bool {
+    !exclude.iter().any(|pattern| matches(pattern))
+        && (include.is_empty() || include.iter().any(|pattern| matches(pattern)))
+}
+
+/// Whether the calls of the file `file` pass the filter
+fn module_allowed(include: &[String], exclude: &[String], file: &str) -> bool {
+    let file = file.replace('\\', "/");
+    filter_allows(include, exclude, |pattern| file_matches(pattern, &file))
+}
+
+/// Whether the calls of the module named `name` pass the current filter, for
+/// the tracer of `probing.inspect.trace`
+#[pyfunction]
+fn _trace_module_allowed(name: &str) -> bool {
+    let sampler = SAMPLER.lock().unwrap_or_else(|e| e.into_inner());
+    filter_allows(&sampler.include, &sampler.exclude, |pattern| {
+        name_matches(pattern, name)
+    })
+}
+
+pub fn register_tracer_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(_trace_module_allowed, module)?)?;
+    Ok(())
+}
+
+/// A sampled call, recorded once it returns
+struct PendingCall {
+    call: VmCall,
+    start: Instant,
+}
+
+impl PendingCall {
+    fn finish(mut self) {
+        self.call.duration_us = self.start.elapsed().as_micros() as i64;
+        let mut calls = CALLS.lock().unwrap_or_else(|e| e.into_inner());
+        if calls.len() >= CALLS_CAPACITY {
+            calls.pop_front();
+        }
+        calls.push_back(self.call);
+    }
+}
+
+/// Decide whether the call at `location` is recorded
+#[inline(always)]
+fn sample(location: &RawCallLocation) -> Option<PendingCall> {
+    let every = SAMPLE_EVERY.load(Ordering::Relaxed);
+    if every == 0 {
+        return None;
+    }
+    COUNTERS.calls.fetch_add(1, Ordering::Relaxed);
+    if !SEEN.fetch_add(1, Ordering::Relaxed).is_multiple_of(every) {
+        COUNTERS.skipped_every.fetch_add(1, Ordering::Relaxed);
+        return None;
+    }
+    let location = location.resolve().ok()?;
+
+    let mut sampler = SAMPLER.lock().unwrap_or_else(|e| e.into_inner());
+    if !module_allowed(&sampler.include, &sampler.exclude, &location.callee.file) {
+        COUNTERS.skipped_module.fetch_add(1, Ordering::Relaxed);
+        return None;
+    }
+    let start = Instant::now();
+    if let Some(bucket) = sampler.bucket.as_mut() {
+        if !bucket.take(start) {
+            COUNTERS.skipped_rate.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+    }
+    drop(sampler);
+    COUNTERS.sampled.fetch_add(1, Ordering::Relaxed);
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as i64)
+        .unwrap_or_default();
+    Some(PendingCall {
+        call: VmCall {
+            time,
+            duration_us: 0,
+            // `threading.get_ident()` of the thread
+            thread_id: unsafe { libc::pthread_self() } as u64,
+            file: location.callee.file,
+            func: location.callee.name,
+            lineno: location.callee.line as i64,
+            caller: location.caller.map(|caller| caller.name),
+        },
+        start,
+    })
+}
+
+/// `tracer.calls`: the calls sampled by the eval frame hook
+#[derive(Default, Debug)]
+pub struct CallsTable {}
+
+impl CustomTable for CallsTable {
+    fn name() -> &'static str {
+        "calls"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("time", DataType::Int64, false),
+            Field::new("duration_us", DataType::Int64, false),
+            Field::new("thread_id", DataType::Int64, false),
+            Field::new("file", DataType::Utf8, false),
+            Field::new("func", DataType::Utf8, false),
+            Field::new("lineno", DataType::Int64, false),
+            Field::new("caller", DataType::Utf8, true),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let calls = recorded_calls();
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(Int64Array::from_iter_values(calls.iter().map(|c| c.time))),
+                Arc::new(Int64Array::from_iter_values(
+                    calls.iter().map(|c| c.duration_us),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    calls.iter().map(|c| c.thread_id as i64),
+                )),
+                Arc::new(StringArray::from_iter_values(calls.iter().map(|c| &c.file))),
+                Arc::new(StringArray::from_iter_values(calls.iter().map(|c| &c.func))),
+                Arc::new(Int64Array::from_iter_values(calls.iter().map(|c| c.lineno))),
+                Arc::new(StringArray::from_iter(
+                    calls.iter().map(|c| c.caller.as_deref()),
+                )),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build calls batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type CallsPlugin = TablePluginHelper<CallsTable>;
+
+/// `tracer.sampling`: the sampling controls and the calls each one skipped
+#[derive(Default, Debug)]
+pub struct SamplingTable {}
+
+impl CustomTable for SamplingTable {
+    fn name() -> &'static str {
+        "sampling"
+    }
+
+    fn schema() -> SchemaRef {
+        SchemaRef::new(Schema::new(vec![
+            Field::new("sample_every", DataType::Int64, false),
+            Field::new("include", DataType::Utf8, false),
+            Field::new("exclude", DataType::Utf8, false),
+            Field::new("max_events_per_sec", DataType::Int64, false),
+            Field::new("calls", DataType::Int64, false),
+            Field::new("sampled", DataType::Int64, false),
+            Field::new("skipped_every", DataType::Int64, false),
+            Field::new("skipped_module", DataType::Int64, false),
+            Field::new("skipped_rate", DataType::Int64, false),
+            Field::new("recorded", DataType::Int64, false),
+        ]))
+    }
+
+    fn data() -> Vec<RecordBatch> {
+        let sampling = sampling();
+        let counters = sampling_counters();
+        let recorded = CALLS.lock().unwrap_or_else(|e| e.into_inner()).len();
+        let int = |value: u64| -> ArrayRef { Arc::new(Int64Array::from(vec![value as i64])) };
+
+        let batch = RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                int(sampling.every),
+                Arc::new(StringArray::from(vec![sampling.include.join(",")])) as ArrayRef,
+                Arc::new(StringArray::from(vec![sampling.exclude.join(",")])) as ArrayRef,
+                int(sampling.max_per_sec),
+                int(counters.calls),
+                int(counters.sampled),
+                int(counters.skipped_every),
+                int(counters.skipped_module),
+                int(counters.skipped_rate),
+                int(recorded as u64),
+            ],
+        );
+        match batch {
+            Ok(batch) => vec![batch],
+            Err(e) => {
+                log::error!("Failed to build sampling batch: {e}");
+                vec![]
+            }
+        }
+    }
+}
+
+pub type SamplingPlugin = TablePluginHelper<SamplingTable>;
+
 #[allow(static_mut_refs)]
 #[pyfunction]
 pub fn enable_tracer() -> PyResult<()> {
@@ -195,3 +595,97 @@ pub fn get_python_frames_raw(current_frame: Option<usize>) -> Vec<CallFrame> {
     }
     frames
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, start);
+        assert!(bucket.take(start));
+        assert!(bucket.take(start));
+        assert!(!bucket.take(start));
+        assert!(bucket.take(start + Duration::from_millis(500)));
+        assert!(!bucket.take(start + Duration::from_millis(600)));
+        // refilled up to the rate only
+        let later = start + Duration::from_secs(60);
+        assert!(bucket.take(later));
+        assert!(bucket.take(later));
+        assert!(!bucket.take(later));
+    }
+
+    #[test]
+    fn test_module_allowed() {
+        let include = vec!["torch.nn".to_string(), "train".to_string()];
+        assert!(module_allowed(&[], &[], "/any/file.py"));
+        assert!(module_allowed(
+            &include,
+            &[],
+            "/site/torch/nn/modules/linear.py"
+        ));
+        assert!(module_allowed(&include, &[], "/work/train.py"));
+        assert!(module_allowed(&include, &[], "train.py"));
+        assert!(!module_allowed(&include, &[], "/site/torch/optim/adam.py"));
+        assert!(!module_allowed(&include, &[], "/work/pretrain.py"));
+        let include = vec!["importlib".to_string()];
+        assert!(module_allowed(
+            &include,
+            &[],
+            "<frozen importlib._bootstrap>"
+        ));
+        assert!(module_allowed(
+            &include,
+            &[],
+            "/usr/lib/python3.11/importlib/__init__.py"
+        ));
+        assert!(!module_allowed(&include, &[], "<frozen importlibx>"));
+        assert!(!module_allowed(&include, &[], "<frozen zipimport>"));
+    }
+
+    #[test]
+    fn test_module_filter_patterns() {
+        let include = parse_patterns("my_project.*, __main__");
+        let exclude = parse_patterns("my_project.vendored.*,numpy.*");
+        assert_eq!(include, vec!["my_project.*", "__main__"]);
+        assert!(module_allowed(
+            &include,
+            &exclude,
+            "/src/my_project/__init__.py"
+        ));
+        assert!(module_allowed(
+            &include,
+            &exclude,
+            "/src/my_project/models/bert.py"
+        ));
+        assert!(!module_allowed(
+            &include,
+            &exclude,
+            "/src/my_project/vendored/six.py"
+        ));
+        assert!(!module_allowed(
+            &include,
+            &exclude,
+            "/src/my_project2/train.py"
+        ));
+        assert!(!module_allowed(
+            &[],
+            &exclude,
+            "/site/numpy/core/numeric.py"
+        ));
+        assert!(module_allowed(
+            &[],
+            &exclude,
+            "/site/torch/nn/modules/linear.py"
+        ));
+
+        assert!(name_matches("my_project.*", "my_project"));
+        assert!(name_matches("my_project.*", "my_project.models.bert"));
+        assert!(name_matches("__main__", "__main__"));
+        assert!(!name_matches("my_project.*", "my_project2"));
+        assert!(!name_matches("torch.nn", "torch"));
+    }
+}
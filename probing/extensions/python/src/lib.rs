@@ -12,3 +12,6 @@ pub mod python;
 pub mod repl;
 
 mod setup;
+
+/// Version of this crate, listed in `/apis/version`
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -3,6 +3,7 @@ use std::sync::Mutex;
 
 use anyhow::Result;
 use once_cell::sync::Lazy;
+use probing_proto::prelude::{AgentEvent, EventKind};
 use pyo3::prelude::*;
 use pyo3::types::PyTuple;
 use pyo3::{types::PyDict, Python};
@@ -144,6 +145,14 @@ pub fn crash_handler(typ: Py<PyAny>, value: Py<PyAny>, traceback: Py<PyAny>) {
         CRASH_HANDLER.lock().unwrap().clone()
     );
     if let Some(handler) = CRASH_HANDLER.lock().unwrap().as_ref() {
+        probing_core::events::publish(
+            AgentEvent::new(
+                EventKind::CrashHandler,
+                "python",
+                "uncaught exception, invoking crash handler",
+            )
+            .with_detail("handler", handler.as_str()),
+        );
         let ret = match handler.as_str() {
             "default" => call_default_handler(typ, value, traceback),
             handler => call_custom_handler(handler, typ, value, traceback),
@@ -167,9 +176,23 @@ pub fn enable_crash_handler() -> anyhow::Result<()> {
         sys.setattr("excepthook", func)?;
         Ok(())
     })?;
+    probing_core::events::notify(EventKind::CrashHandler, "python", "crash handler installed");
     Ok(())
 }
 
+/// ABI tag of the interpreter, e.g. `cp312` or `cp313t` for a free-threaded build
+pub fn abi_tag() -> String {
+    Python::with_gil(|py| -> PyResult<String> {
+        let ver = py.version_info();
+        let abiflags: String = py.import("sys")?.getattr("abiflags")?.extract()?;
+        Ok(format!("cp{}{}{abiflags}", ver.major, ver.minor))
+    })
+    .unwrap_or_else(|err| {
+        log::warn!("Failed to read the Python ABI: {err}");
+        String::new()
+    })
+}
+
 pub fn enable_monitoring(filename: &str) -> anyhow::Result<()> {
     Python::with_gil(|py| {
         let ver = py.version_info();
@@ -1,10 +1,24 @@
 use pyo3::ffi::c_str;
 use pyo3::{
+    pyclass, pymethods,
     types::{PyAnyMethods, PyDict},
-    Bound, Py, PyAny, Python,
+    Bound, Py, PyAny, PyObject, PyResult, Python,
 };
 
-use crate::repl::python_repl::PythonConsole;
+use crate::repl::python_repl::{OutputSink, PythonConsole, ReplOutput};
+
+/// Hands the output of a running command over, see `ReplOutput::on_output`
+#[pyclass]
+struct ReplWriter {
+    on_output: OutputSink,
+}
+
+#[pymethods]
+impl ReplWriter {
+    fn __call__(&self, text: &str) {
+        (self.on_output)(text)
+    }
+}
 
 pub struct NativePythonConsole {
     console: Py<PyAny>,
@@ -30,17 +44,83 @@ impl Default for NativePythonConsole {
     }
 }
 
+impl NativePythonConsole {
+    /// Console of a named REPL session, see `probing.repl.session_console`
+    #[inline(never)]
+    pub fn session(name: &str) -> Self {
+        Self {
+            console: Python::with_gil(|py| {
+                let global = PyDict::new(py);
+                let _ = global.set_item("name", name);
+                let code = c_str!(
+                    "from probing.repl import session_console\nconsole = session_console(name)"
+                );
+                let _ = py.run(code, Some(&global), Some(&global));
+                let ret: Bound<'_, PyAny> = global
+                    .get_item("console")
+                    .map_err(|err| {
+                        eprintln!("error initializing console for session {name}: {err}");
+                    })
+                    .unwrap();
+                ret.unbind()
+            }),
+        }
+    }
+
+    /// Console of a connection naming no session, see
+    /// `probing.repl.private_console`
+    #[inline(never)]
+    pub fn private() -> Self {
+        Self {
+            console: Python::with_gil(|py| {
+                let global = PyDict::new(py);
+                let code =
+                    c_str!("from probing.repl import private_console\nconsole = private_console()");
+                let _ = py.run(code, Some(&global), Some(&global));
+                let ret: Bound<'_, PyAny> = global
+                    .get_item("console")
+                    .map_err(|err| {
+                        eprintln!("error initializing private console: {err}");
+                    })
+                    .unwrap();
+                ret.unbind()
+            }),
+        }
+    }
+}
+
 impl PythonConsole for NativePythonConsole {
     fn try_execute(&mut self, cmd: String) -> Option<String> {
-        Python::with_gil(|py| match self.console.call_method1(py, "push", (cmd,)) {
-            Ok(obj) => {
-                if obj.is_none(py) {
-                    None
-                } else {
-                    Some(obj.to_string())
+        self.try_execute_with(cmd, &ReplOutput::default())
+    }
+
+    fn try_execute_with(&mut self, cmd: String, output: &ReplOutput) -> Option<String> {
+        Python::with_gil(|py| {
+            let push = || -> PyResult<PyObject> {
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("max_output", output.max_bytes)?;
+                if let Some(on_output) = &output.on_output {
+                    let writer = ReplWriter {
+                        on_output: on_output.clone(),
+                    };
+                    kwargs.set_item("on_output", Py::new(py, writer)?)?;
+                }
+                self.console.call_method(py, "push", (cmd,), Some(&kwargs))
+            };
+            match push() {
+                Ok(obj) => {
+                    if obj.is_none(py) {
+                        None
+                    } else {
+                        Some(obj.to_string())
+                    }
                 }
+                Err(err) => Some(err.to_string()),
             }
-            Err(err) => Some(err.to_string()),
         })
     }
+
+    fn object(&self, py: Python) -> Option<Py<PyAny>> {
+        Some(self.console.clone_ref(py))
+    }
 }
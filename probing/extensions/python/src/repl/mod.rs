@@ -1,5 +1,8 @@
 mod console;
 mod python_repl;
+pub mod sessions;
 
+pub use crate::repl::python_repl::OutputSink;
 pub use crate::repl::python_repl::PythonRepl;
 pub use crate::repl::python_repl::Repl;
+pub use crate::repl::python_repl::ReplOutput;
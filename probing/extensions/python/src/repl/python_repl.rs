@@ -1,19 +1,46 @@
 use crate::repl::console::NativePythonConsole;
+use crate::repl::sessions;
 use std::sync::{Arc, Mutex};
 
+use pyo3::{Py, PyAny, Python};
+
 pub trait Repl {
     fn feed(&mut self, s: String) -> Option<String>;
     fn is_alive(&self) -> bool;
 }
 
+/// Receives the output of a running command as it is written
+pub type OutputSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Where the output of a command goes while it runs
+#[derive(Clone, Default)]
+pub struct ReplOutput {
+    /// Output kept per command, later output is dropped and counted in the
+    /// `truncated` field of the response; 0 keeps everything
+    pub max_bytes: usize,
+    /// Receives the output as the command writes it, instead of the response
+    pub on_output: Option<OutputSink>,
+}
+
 pub trait PythonConsole {
     fn try_execute(&mut self, cmd: String) -> Option<String>;
+
+    /// [`Self::try_execute`], the output of the command going to `output`
+    fn try_execute_with(&mut self, cmd: String, _output: &ReplOutput) -> Option<String> {
+        self.try_execute(cmd)
+    }
+
+    /// The Python console object, if the console is backed by one
+    fn object(&self, _py: Python) -> Option<Py<PyAny>> {
+        None
+    }
 }
 
 pub struct PythonRepl {
     console: Arc<Mutex<dyn PythonConsole + Send>>,
     buf: String,
     live: bool,
+    session: Option<String>,
 }
 
 impl Default for PythonRepl {
@@ -23,18 +50,50 @@ impl Default for PythonRepl {
             console: Arc::new(Mutex::new(NativePythonConsole::default())),
             buf: Default::default(),
             live: true,
+            session: None,
         }
     }
 }
 
 impl PythonRepl {
+    /// Attach to the named session, whose namespace is kept after this REPL
+    /// is dropped so a later connection can pick it up again
+    pub fn attach(session: &str) -> Result<Self, String> {
+        Ok(Self {
+            console: sessions::attach(session)?,
+            buf: Default::default(),
+            live: true,
+            session: Some(session.to_string()),
+        })
+    }
+
+    /// A REPL with a console of its own, dropped with it
+    pub fn private() -> Self {
+        Self {
+            console: Arc::new(Mutex::new(NativePythonConsole::private())),
+            buf: Default::default(),
+            live: true,
+            session: None,
+        }
+    }
+
     pub fn process(&mut self, cmd: &str) -> Option<String> {
-        self.console.lock().unwrap().try_execute(cmd.to_string())
+        self.process_with(cmd, &ReplOutput::default())
     }
-}
 
-impl Repl for PythonRepl {
-    fn feed(&mut self, s: String) -> Option<String> {
+    /// [`Self::process`], the output of the command going to `output`
+    pub fn process_with(&mut self, cmd: &str, output: &ReplOutput) -> Option<String> {
+        if let Some(session) = &self.session {
+            sessions::touch(session);
+        }
+        self.console
+            .lock()
+            .unwrap()
+            .try_execute_with(cmd.to_string(), output)
+    }
+
+    /// [`Repl::feed`], the output of the commands going to `output`
+    pub fn feed_with(&mut self, s: String, output: &ReplOutput) -> Option<String> {
         self.buf += &s;
         if !self.buf.contains('\n') {
             return None;
@@ -43,12 +102,31 @@ impl Repl for PythonRepl {
             Some((cmd, rest)) => {
                 let cmd = cmd.to_string();
                 self.buf = rest.to_string();
-                self.process(cmd.as_str())
+                self.process_with(cmd.as_str(), output)
             }
             None => None,
         }
     }
 
+    /// The Python console object code of this REPL runs in
+    pub fn console_object(&self, py: Python) -> Option<Py<PyAny>> {
+        self.console.lock().unwrap().object(py)
+    }
+}
+
+impl Drop for PythonRepl {
+    fn drop(&mut self) {
+        if let Some(session) = &self.session {
+            sessions::detach(session);
+        }
+    }
+}
+
+impl Repl for PythonRepl {
+    fn feed(&mut self, s: String) -> Option<String> {
+        self.feed_with(s, &ReplOutput::default())
+    }
+
     fn is_alive(&self) -> bool {
         self.live
     }
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use probing_proto::prelude::ReplSession;
+
+use crate::repl::console::NativePythonConsole;
+use crate::repl::python_repl::PythonConsole;
+
+pub type SharedConsole = Arc<Mutex<dyn PythonConsole + Send>>;
+
+const MAX_SESSION_NAME: usize = 64;
+
+struct Session {
+    console: SharedConsole,
+    info: ReplSession,
+}
+
+/// Named REPL sessions, kept until closed so their namespace survives the
+/// connections attached to them
+static SESSIONS: Lazy<Mutex<HashMap<String, Session>>> =
+    Lazy::new(|| Mutex::new(Default::default()));
+
+fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as i64
+}
+
+pub(crate) fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_SESSION_NAME
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid session name `{name}`, use up to {MAX_SESSION_NAME} letters, digits, `_`, `-` or `.`"
+        ))
+    }
+}
+
+/// Attach to the named session, creating it on first use
+pub fn attach(name: &str) -> Result<SharedConsole, String> {
+    attach_with(name, || {
+        Arc::new(Mutex::new(NativePythonConsole::session(name))) as SharedConsole
+    })
+}
+
+pub(crate) fn attach_with<F>(name: &str, create: F) -> Result<SharedConsole, String>
+where
+    F: FnOnce() -> SharedConsole,
+{
+    check_name(name)?;
+    let mut sessions = SESSIONS.lock().unwrap();
+    let session = sessions.entry(name.to_string()).or_insert_with(|| {
+        log::info!("REPL session {name} created");
+        Session {
+            console: create(),
+            info: ReplSession {
+                name: name.to_string(),
+                created: now(),
+                last_active: now(),
+                ..Default::default()
+            },
+        }
+    });
+    session.info.attached += 1;
+    Ok(session.console.clone())
+}
+
+/// Release a connection from the named session, the session itself stays
+pub fn detach(name: &str) {
+    if let Some(session) = SESSIONS.lock().unwrap().get_mut(name) {
+        session.info.attached = session.info.attached.saturating_sub(1);
+    }
+}
+
+/// Count a command executed in the named session
+pub fn touch(name: &str) {
+    if let Some(session) = SESSIONS.lock().unwrap().get_mut(name) {
+        session.info.commands += 1;
+        session.info.last_active = now();
+    }
+}
+
+/// Close the named session, its namespace is released once the connections
+/// still attached to it are gone
+pub fn close(name: &str) -> Option<ReplSession> {
+    let session = SESSIONS.lock().unwrap().remove(name)?;
+    log::info!("REPL session {name} closed");
+    Some(session.info)
+}
+
+/// All sessions, by name
+pub fn sessions() -> Vec<ReplSession> {
+    let mut sessions = SESSIONS
+        .lock()
+        .unwrap()
+        .values()
+        .map(|s| s.info.clone())
+        .collect::<Vec<_>>();
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EchoConsole;
+
+    impl PythonConsole for EchoConsole {
+        fn try_execute(&mut self, cmd: String) -> Option<String> {
+            Some(cmd)
+        }
+    }
+
+    fn echo() -> SharedConsole {
+        Arc::new(Mutex::new(EchoConsole))
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let first = attach_with("debug-1", echo).unwrap();
+        let second = attach_with("debug-1", || panic!("session must be reused")).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        touch("debug-1");
+        detach("debug-1");
+        let info = sessions()
+            .into_iter()
+            .find(|s| s.name == "debug-1")
+            .unwrap();
+        assert_eq!((info.attached, info.commands), (1, 1));
+
+        // the session outlives its connections until closed
+        detach("debug-1");
+        assert!(sessions().iter().any(|s| s.name == "debug-1"));
+        assert_eq!(close("debug-1").unwrap().attached, 0);
+        assert!(close("debug-1").is_none());
+
+        assert!(attach_with("", echo).is_err());
+        assert!(attach_with("../x", echo).is_err());
+    }
+}
@@ -1,11 +1,9 @@
-pub fn register_signal_handler<F>(sig: std::ffi::c_int, handler: F)
-where
-    F: Fn() + Sync + Send + 'static,
-{
+pub fn register_signal_handler(sig: std::ffi::c_int, owner: &'static str, handler: fn()) {
     unsafe {
         match signal_hook_registry::register_unchecked(sig, move |_: &_| handler()) {
             Ok(_) => {
                 log::debug!("Registered signal handler for signal {sig}");
+                crate::features::signals::own(sig, owner, handler);
             }
             Err(e) => log::error!("Failed to register signal handler: {e}"),
         }
@@ -21,6 +19,7 @@ fn setup() {
     }
     register_signal_handler(
         nix::libc::SIGUSR2,
+        "stack tracer",
         crate::features::stack_tracer::backtrace_signal_handler,
     );
 }
@@ -11,6 +11,9 @@ struct OptionMetadata {
     aliases: Vec<String>,
     description: String,
     managed: bool,
+    kind: &'static str,
+    allowed: Vec<String>,
+    restart: bool,
 }
 
 #[proc_macro_derive(EngineExtension, attributes(option))]
@@ -93,6 +96,31 @@ fn impl_engine_extension(ast: &DeriveInput) -> TokenStream {
         }
     });
 
+    let schemas = field_metadata.iter().map(|meta| {
+        let name = format!("{}.{}", namespace.to_lowercase(), meta.name);
+        let aliases = meta
+            .aliases
+            .iter()
+            .map(|alias| format!("{}.{}", namespace.to_lowercase(), alias));
+        let kind = format_ident!("{}", meta.kind);
+        let allowed = &meta.allowed;
+        let restart = meta.restart;
+        let help = &meta.description;
+        let field_ident = format_ident!("{}", meta.field);
+
+        quote! {
+            probing_core::core::OptionSchema {
+                key: #name.to_string(),
+                aliases: vec![#(#aliases.to_string()),*],
+                kind: probing_core::core::OptionKind::#kind,
+                default: Some(self.#field_ident.to_string()).filter(|value| !value.is_empty()),
+                allowed: vec![#(#allowed.to_string()),*],
+                requires_restart: #restart,
+                help: #help.to_string(),
+            }
+        }
+    });
+
     // Generate option name constants for consistent usage
     let option_constants = field_metadata.iter().map(|meta| {
         let const_name = format_ident!("OPTION_{}", meta.field.to_uppercase());
@@ -129,6 +157,12 @@ fn impl_engine_extension(ast: &DeriveInput) -> TokenStream {
                 ]
             }
 
+            fn schema(&self) -> Vec<probing_core::core::OptionSchema> {
+                vec![
+                    #(#schemas,)*
+                ]
+            }
+
             // fn datasrc(&self, namespace: &str, name: Option<&str>) -> Option<std::sync::Arc<dyn probing_core::core::Plugin + Sync + Send>> {
             //     self.plugin(namespace, name)
             // }
@@ -150,6 +184,9 @@ fn parse_field_metadata(field: &Field) -> OptionMetadata {
         aliases: vec![],
         description: String::new(),
         managed: false,
+        kind: option_kind(&field.ty),
+        allowed: vec![],
+        restart: false,
     };
 
     let mut descriptions: Vec<String> = vec![];
@@ -168,6 +205,7 @@ fn parse_field_metadata(field: &Field) -> OptionMetadata {
                         let value = match &nv.value {
                             syn::Expr::Lit(lit) => match &lit.lit {
                                 syn::Lit::Str(s) => s.value(),
+                                syn::Lit::Bool(b) => b.value.to_string(),
                                 _ => continue,
                             },
                             syn::Expr::Array(array) => {
@@ -193,6 +231,8 @@ fn parse_field_metadata(field: &Field) -> OptionMetadata {
                         match name.as_str() {
                             "name" => metadata.name = value,
                             "aliases" => metadata.aliases = parse_string_array(&value),
+                            "allowed" => metadata.allowed = parse_string_array(&value),
+                            "restart" => metadata.restart = value == "true",
                             _ => {}
                         }
                     }
@@ -230,3 +270,27 @@ fn parse_string_array(input: &str) -> Vec<String> {
         .map(|s| s.trim().trim_matches('"').to_string())
         .collect()
 }
+
+/// `OptionKind` variant of a field, looking through `Maybe<T>` and `Option<T>`
+fn option_kind(ty: &syn::Type) -> &'static str {
+    let syn::Type::Path(path) = ty else {
+        return "String";
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return "String";
+    };
+    if segment.ident == "Maybe" || segment.ident == "Option" {
+        if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+            if let Some(syn::GenericArgument::Type(inner)) = args.args.first() {
+                return option_kind(inner);
+            }
+        }
+    }
+    match segment.ident.to_string().as_str() {
+        "bool" => "Bool",
+        "i8" | "i16" | "i32" | "i64" | "isize" => "Int",
+        "u8" | "u16" | "u32" | "u64" | "usize" => "UInt",
+        "f32" | "f64" => "Float",
+        _ => "String",
+    }
+}
@@ -7,6 +7,7 @@ use probing_core::core::EngineDatasource;
 use probing_core::core::EngineError;
 use probing_core::core::EngineExtension;
 use probing_core::core::EngineExtensionOption;
+use probing_core::core::OptionKind;
 
 #[derive(Debug)]
 enum Maybe<T> {
@@ -53,7 +54,7 @@ fn test_macro() {
         managed_field_name2: String,
 
         /// describe managed_field_name3
-        #[option]
+        #[option(allowed = ["A", "B"], restart = true)]
         managed_field_name3: Maybe<String>,
 
         /// this is a unmanaged field
@@ -133,4 +134,20 @@ fn test_macro() {
     assert_eq!(opts[2].key, "test.managed_field_name3");
     assert_eq!(opts[2].value, Some("B".to_string()));
     // assert_eq!(opts[2].help, "describe managed_field_name3");
+
+    let schema = ext.schema();
+    assert_eq!(schema.len(), 3);
+    assert_eq!(schema[0].key, "test.managed_field_name1");
+    assert_eq!(schema[0].aliases, vec!["test.mfn1", "test.a"]);
+    assert_eq!(schema[0].kind, OptionKind::Int);
+    assert_eq!(schema[0].default, Some("4".to_string()));
+    assert_eq!(schema[1].kind, OptionKind::String);
+    assert_eq!(
+        schema[1].help,
+        "describe managed_field_name2\nwith multiline docstring"
+    );
+    assert_eq!(schema[2].kind, OptionKind::String);
+    assert_eq!(schema[2].allowed, vec!["A", "B"]);
+    assert!(schema[2].requires_restart);
+    assert!(!schema[0].requires_restart);
 }
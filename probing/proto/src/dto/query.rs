@@ -20,6 +20,38 @@ pub struct QueryRequestDto {
 pub struct QueryOptionsDto {
     /// Maximum number of rows to return
     pub limit: Option<usize>,
+
+    /// Run against the read-only snapshot instead of the live tables
+    #[serde(default)]
+    pub snapshot: bool,
+
+    /// Return the result in pages of this many rows
+    #[serde(default)]
+    pub page_size: Option<usize>,
+
+    /// Fetch the page a previous response pointed to
+    #[serde(default)]
+    pub cursor: Option<String>,
+
+    /// Session whose temporary tables the query sees and creates
+    #[serde(default)]
+    pub session: Option<String>,
+
+    /// Columns of the result to return, in this order
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+
+    /// Fraction of the rows to return, drawn at random
+    #[serde(default)]
+    pub sample_rate: Option<f64>,
+
+    /// Characters of a text cell to return
+    #[serde(default)]
+    pub max_cell_chars: Option<usize>,
+
+    /// Run the query even if the query guard flags it as heavy
+    #[serde(default)]
+    pub force: bool,
 }
 
 impl QueryRequestDto {
@@ -32,7 +64,17 @@ impl QueryRequestDto {
     pub fn with_options(expr: String, limit: Option<usize>) -> Self {
         Self {
             expr,
-            opts: Some(QueryOptionsDto { limit }),
+            opts: Some(QueryOptionsDto {
+                limit,
+                snapshot: false,
+                page_size: None,
+                cursor: None,
+                session: None,
+                columns: None,
+                sample_rate: None,
+                max_cell_chars: None,
+                force: false,
+            }),
         }
     }
 }
@@ -67,6 +109,22 @@ pub enum QueryDataDto {
 
         /// Error message
         message: String,
+
+        /// Table the error refers to
+        #[serde(skip_serializing_if = "Option::is_none")]
+        table: Option<String>,
+
+        /// Column the error refers to
+        #[serde(skip_serializing_if = "Option::is_none")]
+        column: Option<String>,
+
+        /// Location of the offending token in the SQL text
+        #[serde(skip_serializing_if = "Option::is_none")]
+        position: Option<crate::protocol::query::SqlPosition>,
+
+        /// Suggestion for fixing the query
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hint: Option<String>,
     },
 
     /// Data frame result
@@ -74,6 +132,22 @@ pub enum QueryDataDto {
 
     /// Time series result
     TimeSeries(super::time_series::TimeSeries),
+
+    /// One page of a paginated data frame result
+    Page {
+        /// Rows of the page
+        data: super::dataframe::DataFrame,
+
+        /// Index of the first row of the page in the whole result
+        offset: usize,
+
+        /// Number of rows of the whole result
+        total: usize,
+
+        /// Cursor of the next page, absent on the last page
+        #[serde(skip_serializing_if = "Option::is_none")]
+        next_cursor: Option<String>,
+    },
 }
 
 impl QueryResponseDto {
@@ -93,6 +167,10 @@ impl QueryResponseDto {
             payload: QueryDataDto::Error {
                 code,
                 message: message.clone(),
+                table: None,
+                column: None,
+                position: None,
+                hint: None,
             },
             timestamp: Self::now(),
             success: false,
@@ -130,7 +208,17 @@ impl From<crate::protocol::query::Query> for QueryRequestDto {
     fn from(query: crate::protocol::query::Query) -> Self {
         Self {
             expr: query.expr,
-            opts: query.opts.map(|opts| QueryOptionsDto { limit: opts.limit }),
+            opts: query.opts.map(|opts| QueryOptionsDto {
+                limit: opts.limit,
+                snapshot: opts.snapshot,
+                page_size: opts.page_size,
+                cursor: opts.cursor,
+                session: opts.session,
+                columns: opts.columns,
+                sample_rate: opts.sample_rate,
+                max_cell_chars: opts.max_cell_chars,
+                force: opts.force,
+            }),
         }
     }
 }
@@ -140,9 +228,17 @@ impl From<QueryRequestDto> for crate::protocol::query::Query {
     fn from(dto: QueryRequestDto) -> Self {
         Self {
             expr: dto.expr,
-            opts: dto
-                .opts
-                .map(|opts| crate::protocol::query::Options { limit: opts.limit }),
+            opts: dto.opts.map(|opts| crate::protocol::query::Options {
+                limit: opts.limit,
+                snapshot: opts.snapshot,
+                page_size: opts.page_size,
+                cursor: opts.cursor,
+                session: opts.session,
+                columns: opts.columns,
+                sample_rate: opts.sample_rate,
+                max_cell_chars: opts.max_cell_chars,
+                force: opts.force,
+            }),
         }
     }
 }
@@ -162,6 +258,30 @@ fn convert_ele(ele: crate::types::basic::Ele) -> super::basic::Ele {
     }
 }
 
+/// Convert internal DataFrame to DTO DataFrame
+fn convert_dataframe(df: crate::types::DataFrame) -> super::dataframe::DataFrame {
+    let cols = df
+        .cols
+        .into_iter()
+        .map(|seq| match seq {
+            crate::types::Seq::Nil => super::basic::Seq::Nil,
+            crate::types::Seq::SeqBOOL(vec) => super::basic::Seq::SeqBOOL(vec),
+            crate::types::Seq::SeqI32(vec) => super::basic::Seq::SeqI32(vec),
+            crate::types::Seq::SeqI64(vec) => super::basic::Seq::SeqI64(vec),
+            crate::types::Seq::SeqF32(vec) => super::basic::Seq::SeqF32(vec),
+            crate::types::Seq::SeqF64(vec) => super::basic::Seq::SeqF64(vec),
+            crate::types::Seq::SeqText(vec) => super::basic::Seq::SeqText(vec),
+            crate::types::Seq::SeqDateTime(vec) => super::basic::Seq::SeqDateTime(vec),
+        })
+        .collect();
+
+    super::dataframe::DataFrame {
+        names: df.names,
+        cols,
+        size: df.size,
+    }
+}
+
 /// Convert internal Data to DTO
 impl From<crate::protocol::query::Data> for QueryDataDto {
     fn from(data: crate::protocol::query::Data) -> Self {
@@ -170,29 +290,20 @@ impl From<crate::protocol::query::Data> for QueryDataDto {
             crate::protocol::query::Data::Error(error) => QueryDataDto::Error {
                 code: format!("{:?}", error.code),
                 message: error.message,
+                table: error.table,
+                column: error.column,
+                position: error.position,
+                hint: error.hint,
             },
             crate::protocol::query::Data::DataFrame(df) => {
-                let cols = df
-                    .cols
-                    .into_iter()
-                    .map(|seq| match seq {
-                        crate::types::Seq::Nil => super::basic::Seq::Nil,
-                        crate::types::Seq::SeqBOOL(vec) => super::basic::Seq::SeqBOOL(vec),
-                        crate::types::Seq::SeqI32(vec) => super::basic::Seq::SeqI32(vec),
-                        crate::types::Seq::SeqI64(vec) => super::basic::Seq::SeqI64(vec),
-                        crate::types::Seq::SeqF32(vec) => super::basic::Seq::SeqF32(vec),
-                        crate::types::Seq::SeqF64(vec) => super::basic::Seq::SeqF64(vec),
-                        crate::types::Seq::SeqText(vec) => super::basic::Seq::SeqText(vec),
-                        crate::types::Seq::SeqDateTime(vec) => super::basic::Seq::SeqDateTime(vec),
-                    })
-                    .collect();
-
-                QueryDataDto::DataFrame(super::dataframe::DataFrame {
-                    names: df.names,
-                    cols,
-                    size: df.size,
-                })
+                QueryDataDto::DataFrame(convert_dataframe(df))
             }
+            crate::protocol::query::Data::Page(page) => QueryDataDto::Page {
+                data: convert_dataframe(page.df),
+                offset: page.offset,
+                total: page.total,
+                next_cursor: page.next_cursor,
+            },
             crate::protocol::query::Data::TimeSeries(ts) => {
                 let timestamp = ts.timestamp.iter().map(convert_ele).collect();
                 let cols = ts
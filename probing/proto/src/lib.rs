@@ -1,16 +1,34 @@
 pub mod dto;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
 pub mod protocol;
 pub mod types;
 
+/// Version of this crate, listed in `/apis/version`
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub mod prelude {
     // --- Protocol Structures ---
-    pub use crate::protocol::cluster::{Cluster, Node};
+    pub use crate::protocol::capabilities::{
+        Capabilities, FEATURE_ANALYTICS, FEATURE_CLICKHOUSE, FEATURE_POSTGRES, FEATURE_WASM,
+    };
+    pub use crate::protocol::cluster::{Cluster, Job, Node, DEFAULT_JOB, EXPECTED};
+    pub use crate::protocol::config::{ConfigChange, ConfigDump, ConfigValidationError};
+    pub use crate::protocol::config::{OptionKind, OptionSchema};
+    pub use crate::protocol::config::{ScheduleState, ScheduleTrigger, ScheduledChange};
+    pub use crate::protocol::event::{AgentEvent, EventKind};
+    pub use crate::protocol::flamegraph::{FlameMatch, FlameNode};
     pub use crate::protocol::message::Message;
-    pub use crate::protocol::process::{CallFrame, Process};
+    pub use crate::protocol::process::{CallFrame, PauseState, Process, SymbolStatus};
 
+    pub use crate::protocol::query::TIMEOUT_HEADER;
     pub use crate::protocol::query::{Data as QueryDataFormat, Options as QueryOptions, Query};
-    pub use crate::protocol::query::{ErrorCode, QueryError};
-    pub use crate::protocol::version::ProtocolVersion;
+    pub use crate::protocol::query::{ErrorCode, Page as QueryPage, QueryError, SqlPosition};
+    pub use crate::protocol::registry::Registration;
+    pub use crate::protocol::repl::{EvalJob, EvalJobOutput, EvalJobState};
+    pub use crate::protocol::repl::{ReplCompression, ReplSession};
+    pub use crate::protocol::version::{BuildInfo, ProtocolVersion};
+    pub use crate::protocol::waterfall::{PhaseSegment, StepPhase, StepWaterfall};
 
     // --- Core Data Types ---
     pub use crate::types::DataFrame;
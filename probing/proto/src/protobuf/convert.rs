@@ -0,0 +1,444 @@
+use prost::Message as _;
+
+use super::messages as pb;
+use crate::protocol::message::Message;
+use crate::protocol::query::{Data, ErrorCode, Options, Page, Query, QueryError, SqlPosition};
+use crate::protocol::version::ProtocolVersion;
+use crate::types::{DataFrame, Ele, ProtoError, Seq, TimeSeries};
+
+fn invalid<S: Into<String>>(msg: S) -> ProtoError {
+    ProtoError::DeserializationError(msg.into())
+}
+
+impl<T> Message<T>
+where
+    T: Clone + TryInto<pb::message::Payload, Error = ProtoError>,
+{
+    /// Encode the message as protobuf, see `protobuf/probing.proto`.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        self.try_to_protobuf()
+            .expect("payload can always be represented in protobuf")
+    }
+
+    /// Encode the message as protobuf, failing on payloads the schema
+    /// cannot represent.
+    pub fn try_to_protobuf(&self) -> Result<Vec<u8>, ProtoError> {
+        let message = pb::Message {
+            version: Some((&self.version).into()),
+            message_id: self.message_id.clone(),
+            timestamp: self.timestamp,
+            payload: Some(self.payload.clone().try_into()?),
+        };
+        Ok(message.encode_to_vec())
+    }
+}
+
+impl<T> Message<T>
+where
+    T: TryFrom<pb::message::Payload, Error = ProtoError>,
+{
+    /// Decode a protobuf encoded message.
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, ProtoError> {
+        let message = pb::Message::decode(bytes)
+            .map_err(|err| invalid(format!("invalid protobuf: {err}")))?;
+        Ok(Message {
+            version: message
+                .version
+                .map(ProtocolVersion::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            message_id: message.message_id,
+            timestamp: message.timestamp,
+            payload: message
+                .payload
+                .ok_or_else(|| invalid("message without payload"))?
+                .try_into()?,
+        })
+    }
+}
+
+// --- Envelope ---
+
+impl From<&ProtocolVersion> for pb::ProtocolVersion {
+    fn from(version: &ProtocolVersion) -> Self {
+        pb::ProtocolVersion {
+            major: version.major.into(),
+            minor: version.minor.into(),
+            patch: version.patch.into(),
+        }
+    }
+}
+
+impl TryFrom<pb::ProtocolVersion> for ProtocolVersion {
+    type Error = ProtoError;
+
+    fn try_from(version: pb::ProtocolVersion) -> Result<Self, ProtoError> {
+        let part = |value: u32| {
+            u16::try_from(value).map_err(|_| invalid(format!("invalid version number {value}")))
+        };
+        Ok(ProtocolVersion {
+            major: part(version.major)?,
+            minor: part(version.minor)?,
+            patch: part(version.patch)?,
+        })
+    }
+}
+
+impl TryFrom<Query> for pb::message::Payload {
+    type Error = ProtoError;
+
+    fn try_from(query: Query) -> Result<Self, ProtoError> {
+        Ok(pb::message::Payload::Query(query.into()))
+    }
+}
+
+impl TryFrom<pb::message::Payload> for Query {
+    type Error = ProtoError;
+
+    fn try_from(payload: pb::message::Payload) -> Result<Self, ProtoError> {
+        match payload {
+            pb::message::Payload::Query(query) => Ok(query.into()),
+            pb::message::Payload::Data(_) => Err(invalid("expected a query payload")),
+        }
+    }
+}
+
+impl TryFrom<Data> for pb::message::Payload {
+    type Error = ProtoError;
+
+    fn try_from(data: Data) -> Result<Self, ProtoError> {
+        Ok(pb::message::Payload::Data(data.try_into()?))
+    }
+}
+
+impl TryFrom<pb::message::Payload> for Data {
+    type Error = ProtoError;
+
+    fn try_from(payload: pb::message::Payload) -> Result<Self, ProtoError> {
+        match payload {
+            pb::message::Payload::Data(data) => data.try_into(),
+            pb::message::Payload::Query(_) => Err(invalid("expected a data payload")),
+        }
+    }
+}
+
+// --- Basic types ---
+
+impl From<Ele> for pb::Ele {
+    fn from(ele: Ele) -> Self {
+        use pb::ele::Value;
+        let value = match ele {
+            Ele::Nil => None,
+            Ele::BOOL(x) => Some(Value::Bool(x)),
+            Ele::I32(x) => Some(Value::I32(x)),
+            Ele::I64(x) => Some(Value::I64(x)),
+            Ele::F32(x) => Some(Value::F32(x)),
+            Ele::F64(x) => Some(Value::F64(x)),
+            Ele::Text(x) => Some(Value::Text(x)),
+            Ele::Url(x) => Some(Value::Url(x)),
+            Ele::DataTime(x) => Some(Value::Datetime(x)),
+        };
+        pb::Ele { value }
+    }
+}
+
+impl From<pb::Ele> for Ele {
+    fn from(ele: pb::Ele) -> Self {
+        use pb::ele::Value;
+        match ele.value {
+            None => Ele::Nil,
+            Some(Value::Bool(x)) => Ele::BOOL(x),
+            Some(Value::I32(x)) => Ele::I32(x),
+            Some(Value::I64(x)) => Ele::I64(x),
+            Some(Value::F32(x)) => Ele::F32(x),
+            Some(Value::F64(x)) => Ele::F64(x),
+            Some(Value::Text(x)) => Ele::Text(x),
+            Some(Value::Url(x)) => Ele::Url(x),
+            Some(Value::Datetime(x)) => Ele::DataTime(x),
+        }
+    }
+}
+
+impl From<Seq> for pb::Seq {
+    fn from(seq: Seq) -> Self {
+        use pb::seq::Values;
+        let values = match seq {
+            Seq::Nil => None,
+            Seq::SeqBOOL(values) => Some(Values::Bool(pb::BoolSeq { values })),
+            Seq::SeqI32(values) => Some(Values::I32(pb::I32Seq { values })),
+            Seq::SeqI64(values) => Some(Values::I64(pb::I64Seq { values })),
+            Seq::SeqF32(values) => Some(Values::F32(pb::F32Seq { values })),
+            Seq::SeqF64(values) => Some(Values::F64(pb::F64Seq { values })),
+            Seq::SeqText(values) => Some(Values::Text(pb::TextSeq { values })),
+            Seq::SeqDateTime(values) => Some(Values::Datetime(pb::DateTimeSeq { values })),
+        };
+        pb::Seq { values }
+    }
+}
+
+impl From<pb::Seq> for Seq {
+    fn from(seq: pb::Seq) -> Self {
+        use pb::seq::Values;
+        match seq.values {
+            None => Seq::Nil,
+            Some(Values::Bool(seq)) => Seq::SeqBOOL(seq.values),
+            Some(Values::I32(seq)) => Seq::SeqI32(seq.values),
+            Some(Values::I64(seq)) => Seq::SeqI64(seq.values),
+            Some(Values::F32(seq)) => Seq::SeqF32(seq.values),
+            Some(Values::F64(seq)) => Seq::SeqF64(seq.values),
+            Some(Values::Text(seq)) => Seq::SeqText(seq.values),
+            Some(Values::Datetime(seq)) => Seq::SeqDateTime(seq.values),
+        }
+    }
+}
+
+impl From<DataFrame> for pb::DataFrame {
+    fn from(df: DataFrame) -> Self {
+        pb::DataFrame {
+            names: df.names,
+            cols: df.cols.into_iter().map(Into::into).collect(),
+            size: df.size,
+        }
+    }
+}
+
+impl From<pb::DataFrame> for DataFrame {
+    fn from(df: pb::DataFrame) -> Self {
+        DataFrame {
+            names: df.names,
+            cols: df.cols.into_iter().map(Into::into).collect(),
+            size: df.size,
+        }
+    }
+}
+
+/// Materialize a column of elements, `Seq::append` has no boolean support.
+fn collect_seq<I: IntoIterator<Item = Ele>>(values: I) -> Result<Seq, ProtoError> {
+    let mut seq = Seq::Nil;
+    for value in values {
+        match (&mut seq, value) {
+            (Seq::Nil, Ele::BOOL(x)) => seq = Seq::SeqBOOL(vec![x]),
+            (Seq::SeqBOOL(vec), Ele::BOOL(x)) => vec.push(x),
+            (_, value) => seq.append(value)?,
+        }
+    }
+    Ok(seq)
+}
+
+impl TryFrom<&TimeSeries> for pb::TimeSeries {
+    type Error = ProtoError;
+
+    fn try_from(ts: &TimeSeries) -> Result<Self, ProtoError> {
+        let rows = ts.take(None);
+        let timestamp = collect_seq(rows.iter().map(|(t, _)| t.clone()))?;
+        let cols = (0..ts.names.len())
+            .map(|i| collect_seq(rows.iter().map(|(_, values)| values[i].clone())))
+            .map(|seq| seq.map(Into::into))
+            .collect::<Result<_, _>>()?;
+        Ok(pb::TimeSeries {
+            names: ts.names.clone(),
+            timestamp: Some(timestamp.into()),
+            cols,
+        })
+    }
+}
+
+impl TryFrom<pb::TimeSeries> for TimeSeries {
+    type Error = ProtoError;
+
+    fn try_from(ts: pb::TimeSeries) -> Result<Self, ProtoError> {
+        let timestamp: Seq = ts.timestamp.unwrap_or_default().into();
+        let cols: Vec<Seq> = ts.cols.into_iter().map(Into::into).collect();
+        if cols.len() != ts.names.len() {
+            return Err(invalid("time series column count mismatch"));
+        }
+
+        let mut series = TimeSeries::builder().with_columns(ts.names).build();
+        for i in 0..timestamp.len() {
+            let values = cols.iter().map(|col| col.get(i)).collect();
+            series
+                .append(timestamp.get(i), values)
+                .map_err(|err| invalid(format!("invalid time series row {i}: {err}")))?;
+        }
+        Ok(series)
+    }
+}
+
+// --- Query ---
+
+impl From<Options> for pb::QueryOptions {
+    fn from(opts: Options) -> Self {
+        pb::QueryOptions {
+            limit: opts.limit.map(|limit| limit as u64),
+            snapshot: opts.snapshot,
+            page_size: opts.page_size.map(|size| size as u64),
+            cursor: opts.cursor,
+            session: opts.session,
+            columns: opts.columns.unwrap_or_default(),
+            sample_rate: opts.sample_rate,
+            max_cell_chars: opts.max_cell_chars.map(|chars| chars as u64),
+            force: opts.force,
+        }
+    }
+}
+
+impl From<pb::QueryOptions> for Options {
+    fn from(opts: pb::QueryOptions) -> Self {
+        Options {
+            limit: opts
+                .limit
+                .map(|limit| usize::try_from(limit).unwrap_or(usize::MAX)),
+            snapshot: opts.snapshot,
+            page_size: opts
+                .page_size
+                .map(|size| usize::try_from(size).unwrap_or(usize::MAX)),
+            cursor: opts.cursor,
+            session: opts.session,
+            columns: (!opts.columns.is_empty()).then_some(opts.columns),
+            sample_rate: opts.sample_rate,
+            max_cell_chars: opts
+                .max_cell_chars
+                .map(|chars| usize::try_from(chars).unwrap_or(usize::MAX)),
+            force: opts.force,
+        }
+    }
+}
+
+impl From<Query> for pb::Query {
+    fn from(query: Query) -> Self {
+        pb::Query {
+            expr: query.expr,
+            opts: query.opts.map(Into::into),
+        }
+    }
+}
+
+impl From<pb::Query> for Query {
+    fn from(query: pb::Query) -> Self {
+        Query {
+            expr: query.expr,
+            opts: query.opts.map(Into::into),
+        }
+    }
+}
+
+impl From<ErrorCode> for pb::ErrorCode {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::ParseError => pb::ErrorCode::ParseError,
+            ErrorCode::PlanError => pb::ErrorCode::PlanError,
+            ErrorCode::ExecutionError => pb::ErrorCode::ExecutionError,
+            ErrorCode::TimeoutError => pb::ErrorCode::TimeoutError,
+            ErrorCode::ResourceExhausted => pb::ErrorCode::ResourceExhausted,
+            ErrorCode::PermissionDenied => pb::ErrorCode::PermissionDenied,
+            ErrorCode::NotFound => pb::ErrorCode::NotFound,
+            ErrorCode::TableNotFound => pb::ErrorCode::TableNotFound,
+            ErrorCode::ColumnNotFound => pb::ErrorCode::ColumnNotFound,
+            ErrorCode::Internal => pb::ErrorCode::Internal,
+        }
+    }
+}
+
+impl From<pb::ErrorCode> for ErrorCode {
+    fn from(code: pb::ErrorCode) -> Self {
+        match code {
+            pb::ErrorCode::ParseError => ErrorCode::ParseError,
+            pb::ErrorCode::PlanError => ErrorCode::PlanError,
+            pb::ErrorCode::ExecutionError => ErrorCode::ExecutionError,
+            pb::ErrorCode::TimeoutError => ErrorCode::TimeoutError,
+            pb::ErrorCode::ResourceExhausted => ErrorCode::ResourceExhausted,
+            pb::ErrorCode::PermissionDenied => ErrorCode::PermissionDenied,
+            pb::ErrorCode::NotFound => ErrorCode::NotFound,
+            pb::ErrorCode::TableNotFound => ErrorCode::TableNotFound,
+            pb::ErrorCode::ColumnNotFound => ErrorCode::ColumnNotFound,
+            pb::ErrorCode::Internal | pb::ErrorCode::Unspecified => ErrorCode::Internal,
+        }
+    }
+}
+
+impl From<QueryError> for pb::QueryError {
+    fn from(error: QueryError) -> Self {
+        pb::QueryError {
+            code: pb::ErrorCode::from(error.code).into(),
+            message: error.message,
+            details: error.details,
+            table: error.table,
+            column: error.column,
+            position: error
+                .position
+                .map(|SqlPosition { line, column }| pb::SqlPosition { line, column }),
+            hint: error.hint,
+        }
+    }
+}
+
+impl From<pb::QueryError> for QueryError {
+    fn from(error: pb::QueryError) -> Self {
+        // unknown codes from newer peers degrade to `Internal`
+        let code = pb::ErrorCode::try_from(error.code).unwrap_or(pb::ErrorCode::Internal);
+        QueryError {
+            code: code.into(),
+            message: error.message,
+            details: error.details,
+            table: error.table,
+            column: error.column,
+            position: error
+                .position
+                .map(|pb::SqlPosition { line, column }| SqlPosition { line, column }),
+            hint: error.hint,
+        }
+    }
+}
+
+impl From<Page> for pb::QueryPage {
+    fn from(page: Page) -> Self {
+        pb::QueryPage {
+            dataframe: Some(page.df.into()),
+            offset: page.offset as u64,
+            total: page.total as u64,
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+impl From<pb::QueryPage> for Page {
+    fn from(page: pb::QueryPage) -> Self {
+        Page {
+            df: page.dataframe.map(Into::into).unwrap_or_default(),
+            offset: usize::try_from(page.offset).unwrap_or(usize::MAX),
+            total: usize::try_from(page.total).unwrap_or(usize::MAX),
+            next_cursor: page.next_cursor,
+        }
+    }
+}
+
+impl TryFrom<Data> for pb::QueryData {
+    type Error = ProtoError;
+
+    fn try_from(data: Data) -> Result<Self, ProtoError> {
+        use pb::query_data::Data as PbData;
+        let data = match data {
+            Data::Nil => None,
+            Data::Error(error) => Some(PbData::Error(error.into())),
+            Data::DataFrame(df) => Some(PbData::Dataframe(df.into())),
+            Data::TimeSeries(ts) => Some(PbData::TimeSeries((&ts).try_into()?)),
+            Data::Page(page) => Some(PbData::Page(page.into())),
+        };
+        Ok(pb::QueryData { data })
+    }
+}
+
+impl TryFrom<pb::QueryData> for Data {
+    type Error = ProtoError;
+
+    fn try_from(data: pb::QueryData) -> Result<Self, ProtoError> {
+        use pb::query_data::Data as PbData;
+        Ok(match data.data {
+            None => Data::Nil,
+            Some(PbData::Error(error)) => Data::Error(error.into()),
+            Some(PbData::Dataframe(df)) => Data::DataFrame(df.into()),
+            Some(PbData::TimeSeries(ts)) => Data::TimeSeries(ts.try_into()?),
+            Some(PbData::Page(page)) => Data::Page(page.into()),
+        })
+    }
+}
@@ -0,0 +1,315 @@
+//! Rust bindings of `protobuf/probing.proto` (package `probing.v1`).
+//!
+//! Written in the shape `prost-build` generates so that the crate builds
+//! without `protoc`. Keep the tags in sync with the schema file, the
+//! `test_bindings_match_schema` test of `tests/protobuf_compat.rs` compares them.
+
+// --- Envelope ---
+
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ProtocolVersion {
+    #[prost(uint32, tag = "1")]
+    pub major: u32,
+    #[prost(uint32, tag = "2")]
+    pub minor: u32,
+    #[prost(uint32, tag = "3")]
+    pub patch: u32,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Message {
+    #[prost(message, optional, tag = "1")]
+    pub version: ::core::option::Option<ProtocolVersion>,
+    #[prost(string, optional, tag = "2")]
+    pub message_id: ::core::option::Option<::prost::alloc::string::String>,
+    /// Microseconds since the unix epoch
+    #[prost(uint64, tag = "3")]
+    pub timestamp: u64,
+    #[prost(oneof = "message::Payload", tags = "4, 5")]
+    pub payload: ::core::option::Option<message::Payload>,
+}
+
+pub mod message {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "4")]
+        Query(super::Query),
+        #[prost(message, tag = "5")]
+        Data(super::QueryData),
+    }
+}
+
+// --- Basic types ---
+
+/// A single value, unset means nil.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Ele {
+    #[prost(oneof = "ele::Value", tags = "1, 2, 3, 4, 5, 6, 7, 8")]
+    pub value: ::core::option::Option<ele::Value>,
+}
+
+pub mod ele {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Value {
+        #[prost(bool, tag = "1")]
+        Bool(bool),
+        #[prost(int32, tag = "2")]
+        I32(i32),
+        #[prost(int64, tag = "3")]
+        I64(i64),
+        #[prost(float, tag = "4")]
+        F32(f32),
+        #[prost(double, tag = "5")]
+        F64(f64),
+        #[prost(string, tag = "6")]
+        Text(::prost::alloc::string::String),
+        #[prost(string, tag = "7")]
+        Url(::prost::alloc::string::String),
+        /// Microseconds since the unix epoch
+        #[prost(uint64, tag = "8")]
+        Datetime(u64),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BoolSeq {
+    #[prost(bool, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<bool>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct I32Seq {
+    #[prost(int32, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct I64Seq {
+    #[prost(int64, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct F32Seq {
+    #[prost(float, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<f32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct F64Seq {
+    #[prost(double, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<f64>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TextSeq {
+    #[prost(string, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+
+/// Microseconds since the unix epoch
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DateTimeSeq {
+    #[prost(uint64, repeated, tag = "1")]
+    pub values: ::prost::alloc::vec::Vec<u64>,
+}
+
+/// A homogeneous column, unset means nil.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Seq {
+    #[prost(oneof = "seq::Values", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub values: ::core::option::Option<seq::Values>,
+}
+
+pub mod seq {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Values {
+        #[prost(message, tag = "1")]
+        Bool(super::BoolSeq),
+        #[prost(message, tag = "2")]
+        I32(super::I32Seq),
+        #[prost(message, tag = "3")]
+        I64(super::I64Seq),
+        #[prost(message, tag = "4")]
+        F32(super::F32Seq),
+        #[prost(message, tag = "5")]
+        F64(super::F64Seq),
+        #[prost(message, tag = "6")]
+        Text(super::TextSeq),
+        #[prost(message, tag = "7")]
+        Datetime(super::DateTimeSeq),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DataFrame {
+    #[prost(string, repeated, tag = "1")]
+    pub names: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "2")]
+    pub cols: ::prost::alloc::vec::Vec<Seq>,
+    #[prost(uint64, tag = "3")]
+    pub size: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TimeSeries {
+    #[prost(string, repeated, tag = "1")]
+    pub names: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "2")]
+    pub timestamp: ::core::option::Option<Seq>,
+    #[prost(message, repeated, tag = "3")]
+    pub cols: ::prost::alloc::vec::Vec<Seq>,
+}
+
+// --- Query ---
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryOptions {
+    #[prost(uint64, optional, tag = "1")]
+    pub limit: ::core::option::Option<u64>,
+    #[prost(bool, tag = "2")]
+    pub snapshot: bool,
+    #[prost(uint64, optional, tag = "3")]
+    pub page_size: ::core::option::Option<u64>,
+    #[prost(string, optional, tag = "4")]
+    pub cursor: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "5")]
+    pub session: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "6")]
+    pub columns: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(double, optional, tag = "7")]
+    pub sample_rate: ::core::option::Option<f64>,
+    #[prost(uint64, optional, tag = "8")]
+    pub max_cell_chars: ::core::option::Option<u64>,
+    #[prost(bool, tag = "9")]
+    pub force: bool,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Query {
+    #[prost(string, tag = "1")]
+    pub expr: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub opts: ::core::option::Option<QueryOptions>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ErrorCode {
+    Unspecified = 0,
+    ParseError = 1,
+    PlanError = 2,
+    ExecutionError = 3,
+    TimeoutError = 4,
+    ResourceExhausted = 5,
+    PermissionDenied = 6,
+    NotFound = 7,
+    TableNotFound = 8,
+    ColumnNotFound = 9,
+    Internal = 10,
+}
+
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct SqlPosition {
+    #[prost(uint64, tag = "1")]
+    pub line: u64,
+    #[prost(uint64, tag = "2")]
+    pub column: u64,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryError {
+    #[prost(enumeration = "ErrorCode", tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "3")]
+    pub details: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub table: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "5")]
+    pub column: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "6")]
+    pub position: ::core::option::Option<SqlPosition>,
+    #[prost(string, optional, tag = "7")]
+    pub hint: ::core::option::Option<::prost::alloc::string::String>,
+}
+
+/// One page of a paginated result.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryPage {
+    #[prost(message, optional, tag = "1")]
+    pub dataframe: ::core::option::Option<DataFrame>,
+    #[prost(uint64, tag = "2")]
+    pub offset: u64,
+    #[prost(uint64, tag = "3")]
+    pub total: u64,
+    #[prost(string, optional, tag = "4")]
+    pub next_cursor: ::core::option::Option<::prost::alloc::string::String>,
+}
+
+/// Result of a query, unset means nil.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryData {
+    #[prost(oneof = "query_data::Data", tags = "1, 2, 3, 4")]
+    pub data: ::core::option::Option<query_data::Data>,
+}
+
+pub mod query_data {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Data {
+        #[prost(message, tag = "1")]
+        Error(super::QueryError),
+        #[prost(message, tag = "2")]
+        Dataframe(super::DataFrame),
+        #[prost(message, tag = "3")]
+        TimeSeries(super::TimeSeries),
+        #[prost(message, tag = "4")]
+        Page(super::QueryPage),
+    }
+}
+
+// --- Tracing ---
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TraceRecordType {
+    Unspecified = 0,
+    SpanStart = 1,
+    Event = 2,
+    SpanEnd = 3,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TraceAttribute {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub value: ::core::option::Option<Ele>,
+}
+
+/// One row of the `trace_event` table.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TraceRecord {
+    #[prost(enumeration = "TraceRecordType", tag = "1")]
+    pub record_type: i32,
+    #[prost(uint64, tag = "2")]
+    pub trace_id: u64,
+    #[prost(uint64, tag = "3")]
+    pub span_id: u64,
+    #[prost(uint64, optional, tag = "4")]
+    pub parent_id: ::core::option::Option<u64>,
+    #[prost(string, tag = "5")]
+    pub name: ::prost::alloc::string::String,
+    /// Nanoseconds since the unix epoch
+    #[prost(uint64, tag = "6")]
+    pub time: u64,
+    #[prost(uint64, tag = "7")]
+    pub thread_id: u64,
+    #[prost(string, optional, tag = "8")]
+    pub kind: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "9")]
+    pub location: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "10")]
+    pub attributes: ::prost::alloc::vec::Vec<TraceAttribute>,
+}
@@ -0,0 +1,25 @@
+//! Protobuf encoding of the protocol, enabled with the `protobuf` feature.
+//!
+//! The schema lives in `protobuf/probing.proto` so that non-Rust clients can
+//! generate their own bindings. Messages are converted from and to the serde
+//! types of this crate, the JSON encoding stays the default:
+//!
+//! ```
+//! use probing_proto::prelude::{Message, Query};
+//!
+//! let request = Message::new(Query::new("SELECT 1".to_string()));
+//! let bytes = request.to_protobuf();
+//! let decoded = Message::<Query>::from_protobuf(&bytes).unwrap();
+//! assert_eq!(decoded.payload.expr, "SELECT 1");
+//! ```
+
+mod convert;
+mod messages;
+
+pub use messages::*;
+
+/// Content type of protobuf encoded HTTP bodies.
+pub const CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// The protobuf schema, e.g. to serve it to clients generating bindings.
+pub const SCHEMA: &str = include_str!("../../protobuf/probing.proto");
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+/// Framework tables (`torch.*`, `inference.*`, `kineto.events`,
+/// `oom.reports`) and the HTML report
+pub const FEATURE_ANALYTICS: &str = "analytics";
+
+/// SQL functions loaded from WebAssembly modules at `/apis/wasm/<module>`
+pub const FEATURE_WASM: &str = "wasm";
+
+/// PostgreSQL tables served as `remote.<name>`
+pub const FEATURE_POSTGRES: &str = "postgres";
+
+/// ClickHouse tables served as `remote.<name>`
+pub const FEATURE_CLICKHOUSE: &str = "clickhouse";
+
+/// Features compiled into a probe, served at `/apis/capabilities`
+///
+/// Every probe serves stacks, eval, queries of the core tables and
+/// configuration; `features` lists what comes on top of that, so that
+/// clients can tell a minimal build from an older probe.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Capabilities {
+    /// Version of the probe
+    pub version: String,
+
+    /// Optional features built in, such as [`FEATURE_ANALYTICS`]
+    pub features: Vec<String>,
+}
+
+impl Capabilities {
+    /// Capabilities assumed for probes that do not advertise them, which
+    /// predate optional features and have all of them
+    pub fn legacy() -> Self {
+        Self {
+            version: String::new(),
+            features: vec![FEATURE_ANALYTICS.to_string()],
+        }
+    }
+
+    pub fn has(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has() {
+        let minimal: Capabilities =
+            serde_json::from_str(r#"{"version": "0.2.3", "features": []}"#).unwrap();
+        assert!(!minimal.has(FEATURE_ANALYTICS));
+        assert!(Capabilities::legacy().has(FEATURE_ANALYTICS));
+    }
+}
@@ -22,13 +22,21 @@ pub struct Node {
 
     pub status: Option<String>,
     pub timestamp: u64,
+
+    /// Estimated `master - local` clock offset in microseconds
+    #[serde(default)]
+    pub clock_offset: Option<i64>,
+
+    /// Training job the node belongs to, see `probing_core::core::job`
+    #[serde(default)]
+    pub job_id: Option<String>,
 }
 
 impl Display for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Node {{ host: {}, addr: {}, local_rank: {:?}, rank: {:?}, world_size: {:?}, group_rank: {:?}, group_world_size: {:?}, role_name: {:?}, role_rank: {:?}, role_world_size: {:?}, status: {:?}, timestamp: {} }}",
+            "Node {{ host: {}, addr: {}, local_rank: {:?}, rank: {:?}, world_size: {:?}, group_rank: {:?}, group_world_size: {:?}, role_name: {:?}, role_rank: {:?}, role_world_size: {:?}, status: {:?}, timestamp: {}, clock_offset: {:?}, job_id: {:?} }}",
             self.host,
             self.addr,
             self.local_rank,
@@ -40,11 +48,53 @@ impl Display for Node {
             self.role_rank,
             self.role_world_size,
             self.status,
-            self.timestamp
+            self.timestamp,
+            self.clock_offset,
+            self.job_id
         )
     }
 }
 
+/// Job of the nodes that reported no job id
+pub const DEFAULT_JOB: &str = "default";
+
+/// Status of the nodes known to be part of a job, e.g. from the Slurm
+/// nodelist, that have not reported yet. They have no address, and a report
+/// from their host replaces them.
+pub const EXPECTED: &str = "expected";
+
+/// Aggregate over the nodes of one training job
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Job {
+    pub job_id: String,
+    pub nodes: usize,
+    pub hosts: Vec<String>,
+    pub ranks: Vec<i32>,
+    /// Largest world size reported by the nodes
+    pub world_size: Option<i32>,
+    /// Number of nodes per status
+    pub status: HashMap<String, usize>,
+    /// Timestamp of the latest report, in microseconds
+    pub last_seen: u64,
+}
+
+impl Node {
+    /// Job of the node, [`DEFAULT_JOB`] if it reported none
+    pub fn job(&self) -> &str {
+        self.job_id.as_deref().unwrap_or(DEFAULT_JOB)
+    }
+
+    /// Whether the node is a placeholder that has not reported yet
+    pub fn is_expected(&self) -> bool {
+        self.status.as_deref() == Some(EXPECTED)
+    }
+
+    /// Host name without the domain
+    fn short_host(&self) -> &str {
+        self.host.split('.').next().unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
 pub struct Cluster {
     pub nodes: HashMap<String, Node>,     // 使用host:addr作为key
@@ -52,7 +102,30 @@ pub struct Cluster {
 }
 
 impl Cluster {
+    /// Record `node` as expected unless its host has reported already
+    pub fn expect(&mut self, node: Node) {
+        let reported = self.nodes.values().any(|other| {
+            !other.is_expected()
+                && other.short_host() == node.short_host()
+                && other.job() == node.job()
+        });
+        if !reported {
+            self.put(Node {
+                status: Some(EXPECTED.to_string()),
+                ..node
+            });
+        }
+    }
+
     pub fn put(&mut self, node: Node) {
+        if !node.is_expected() {
+            // the host of an expected node reported
+            self.nodes.retain(|_, other| {
+                !(other.is_expected()
+                    && other.short_host() == node.short_host()
+                    && other.job() == node.job())
+            });
+        }
         let key = format!("{}:{}", node.host, node.addr);
 
         // 如果有rank，维护rank索引
@@ -98,4 +171,115 @@ impl Cluster {
     pub fn list(&self) -> Vec<Node> {
         self.nodes.values().cloned().collect()
     }
+
+    /// Nodes of the job `job_id`
+    pub fn list_job(&self, job_id: &str) -> Vec<Node> {
+        self.nodes
+            .values()
+            .filter(|node| node.job() == job_id)
+            .cloned()
+            .collect()
+    }
+
+    /// One aggregate per job, sorted by job id
+    pub fn jobs(&self) -> Vec<Job> {
+        let mut jobs: HashMap<&str, Job> = HashMap::new();
+        for node in self.nodes.values() {
+            let job = jobs.entry(node.job()).or_insert_with(|| Job {
+                job_id: node.job().to_string(),
+                ..Default::default()
+            });
+            job.nodes += 1;
+            if !job.hosts.contains(&node.host) {
+                job.hosts.push(node.host.clone());
+            }
+            job.ranks.extend(node.rank);
+            job.world_size = job.world_size.max(node.world_size);
+            let status = node.status.clone().unwrap_or_default();
+            *job.status.entry(status).or_default() += 1;
+            job.last_seen = job.last_seen.max(node.timestamp);
+        }
+
+        let mut jobs: Vec<Job> = jobs.into_values().collect();
+        for job in jobs.iter_mut() {
+            job.hosts.sort();
+            job.ranks.sort();
+        }
+        jobs.sort_by(|a, b| a.job_id.cmp(&b.job_id));
+        jobs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(host: &str, addr: &str, rank: i32, job_id: Option<&str>) -> Node {
+        Node {
+            host: host.to_string(),
+            addr: addr.to_string(),
+            rank: Some(rank),
+            world_size: Some(2),
+            status: Some("running".to_string()),
+            timestamp: rank as u64,
+            job_id: job_id.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_jobs_on_shared_hosts() {
+        let mut cluster = Cluster::default();
+        cluster.put(node("h1", "10.0.0.1:9700", 0, Some("a")));
+        cluster.put(node("h2", "10.0.0.2:9700", 1, Some("a")));
+        cluster.put(node("h1", "10.0.0.1:9701", 0, Some("b")));
+        cluster.put(node("h1", "10.0.0.1:9702", 3, None));
+
+        let jobs = cluster.jobs();
+        let ids: Vec<&str> = jobs.iter().map(|j| j.job_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", DEFAULT_JOB]);
+
+        let a = &jobs[0];
+        assert_eq!(a.nodes, 2);
+        assert_eq!(a.hosts, ["h1", "h2"]);
+        assert_eq!(a.ranks, [0, 1]);
+        assert_eq!(a.world_size, Some(2));
+        assert_eq!(a.status.get("running"), Some(&2));
+        assert_eq!(a.last_seen, 1);
+
+        assert_eq!(cluster.list_job("b")[0].addr, "10.0.0.1:9701");
+        assert_eq!(cluster.list_job(DEFAULT_JOB)[0].rank, Some(3));
+        assert!(cluster.list_job("c").is_empty());
+    }
+
+    #[test]
+    fn test_expected_nodes() {
+        let mut cluster = Cluster::default();
+        let expected = |host: &str| Node {
+            host: host.to_string(),
+            job_id: Some("a".to_string()),
+            ..Default::default()
+        };
+        cluster.put(node("gpu01", "10.0.0.1:9700", 0, Some("a")));
+        cluster.expect(expected("gpu01"));
+        cluster.expect(expected("gpu02"));
+        cluster.expect(expected("gpu03"));
+        assert_eq!(cluster.jobs()[0].status.get(EXPECTED), Some(&2));
+
+        // a report from the domain name of the host replaces it
+        cluster.put(node("gpu02.cluster", "10.0.0.2:9700", 1, Some("a")));
+        cluster.put(node("gpu02.cluster", "10.0.0.2:9701", 2, Some("a")));
+        let jobs = cluster.jobs();
+        assert_eq!(jobs[0].status.get(EXPECTED), Some(&1));
+        assert_eq!(jobs[0].status.get("running"), Some(&3));
+        assert_eq!(jobs[0].ranks, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_node_without_job_id() {
+        let node: Node =
+            serde_json::from_str(r#"{"host": "h", "addr": "a", "timestamp": 0}"#).unwrap();
+        assert_eq!(node.job_id, None);
+        assert_eq!(node.job(), DEFAULT_JOB);
+    }
 }
@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Sorted snapshot of the configuration of a probed process
+///
+/// Served at `/apis/config`, sorted maps keep dumps of two ranks diffable.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct ConfigDump {
+    /// Extension options, keyed as `probing.<extension>.<option>`
+    pub options: BTreeMap<String, String>,
+
+    /// Entries of the configuration store
+    pub store: BTreeMap<String, String>,
+}
+
+/// Type of the values of an extension option
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionKind {
+    Bool,
+    Int,
+    UInt,
+    Float,
+    #[default]
+    String,
+}
+
+impl OptionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptionKind::Bool => "bool",
+            OptionKind::Int => "int",
+            OptionKind::UInt => "uint",
+            OptionKind::Float => "float",
+            OptionKind::String => "string",
+        }
+    }
+
+    /// Whether `value` parses as this type
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            OptionKind::Bool => value.parse::<bool>().is_ok(),
+            OptionKind::Int => value.parse::<i64>().is_ok(),
+            OptionKind::UInt => value.parse::<u64>().is_ok(),
+            OptionKind::Float => value.parse::<f64>().is_ok(),
+            OptionKind::String => true,
+        }
+    }
+}
+
+/// Declared type, default and constraints of an extension option
+///
+/// Served at `/apis/config/schema`, writes to options are validated against
+/// it before they reach the extension.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct OptionSchema {
+    /// Key without the `probing.` prefix, e.g. `torch.profiling`
+    pub key: String,
+
+    /// Other keys accepted for the option
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    pub kind: OptionKind,
+
+    /// Value of the option when the extension was registered
+    #[serde(default)]
+    pub default: Option<String>,
+
+    /// Values accepted regardless of case, any value of `kind` when empty
+    #[serde(default)]
+    pub allowed: Vec<String>,
+
+    /// Whether a new value only takes effect once the process restarts
+    #[serde(default)]
+    pub requires_restart: bool,
+
+    #[serde(default)]
+    pub help: String,
+}
+
+impl OptionSchema {
+    /// Check `value` against the type and the allowed values; the empty
+    /// value unsets the option and is always accepted
+    pub fn validate(&self, key: &str, value: &str) -> Result<(), ConfigValidationError> {
+        if value.is_empty() {
+            return Ok(());
+        }
+        let reason = if !self.kind.accepts(value) {
+            format!("expected a {} value", self.kind.as_str())
+        } else if !self.allowed.is_empty()
+            && !self.allowed.iter().any(|v| v.eq_ignore_ascii_case(value))
+        {
+            format!("expected one of {}", self.allowed.join(", "))
+        } else {
+            return Ok(());
+        };
+        Err(ConfigValidationError {
+            key: key.to_string(),
+            value: value.to_string(),
+            reason,
+            suggestion: None,
+        })
+    }
+}
+
+/// Write to an option rejected by its schema
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct ConfigValidationError {
+    /// Key written, without the `probing.` prefix
+    pub key: String,
+    pub value: String,
+    /// What is wrong, e.g. `expected one of on, off` or `unknown option`
+    pub reason: String,
+    /// Known keys close to a misspelled one
+    #[serde(default)]
+    pub suggestion: Option<String>,
+}
+
+impl Display for ConfigValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid setting probing.{}={:?}: {}",
+            self.key, self.value, self.reason
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, ", {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigValidationError {}
+
+/// Change of one configuration entry between two dumps
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ConfigChange {
+    /// `options` or `store`
+    pub section: &'static str,
+    pub key: String,
+    /// Value before the change, `None` if the entry was added
+    pub old: Option<String>,
+    /// Value after the change, `None` if the entry was removed
+    pub new: Option<String>,
+}
+
+impl ConfigDump {
+    /// Changes turning `self` into `newer`, sorted by section and key
+    pub fn diff(&self, newer: &ConfigDump) -> Vec<ConfigChange> {
+        let mut changes = diff_section("options", &self.options, &newer.options);
+        changes.extend(diff_section("store", &self.store, &newer.store));
+        changes
+    }
+}
+
+fn diff_section(
+    section: &'static str,
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) -> Vec<ConfigChange> {
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let (before, after) = (old.get(key), new.get(key));
+            (before != after).then(|| ConfigChange {
+                section,
+                key: key.clone(),
+                old: before.cloned(),
+                new: after.cloned(),
+            })
+        })
+        .collect()
+}
+
+impl Display for ConfigChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.old, &self.new) {
+            (None, Some(new)) => write!(f, "+ [{}] {} = {new:?}", self.section, self.key),
+            (Some(old), None) => write!(f, "- [{}] {} = {old:?}", self.section, self.key),
+            (Some(old), Some(new)) => {
+                write!(f, "~ [{}] {}: {old:?} -> {new:?}", self.section, self.key)
+            }
+            (None, None) => write!(f, "  [{}] {}", self.section, self.key),
+        }
+    }
+}
+
+/// When a scheduled configuration change is due
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleTrigger {
+    /// Nanoseconds since the unix epoch
+    Time(i64),
+    /// Once the training reaches this step
+    Step(i64),
+}
+
+impl ScheduleTrigger {
+    /// Whether the trigger fired at `now`, in nanoseconds since the epoch,
+    /// with the training at `step`
+    pub fn is_due(&self, now: i64, step: Option<i64>) -> bool {
+        match self {
+            ScheduleTrigger::Time(at) => now >= *at,
+            ScheduleTrigger::Step(at) => step.is_some_and(|step| step >= *at),
+        }
+    }
+}
+
+/// Progress of a scheduled configuration change
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleState {
+    #[default]
+    Pending,
+    /// Written, waiting for `until` if there is one
+    Applied,
+    /// Previous value restored at `until`
+    Reverted,
+    Cancelled,
+    Failed,
+}
+
+/// Configuration change applied at a time or step, and optionally reverted
+///
+/// Served at `/apis/config/schedule`, which also takes new changes; their
+/// `id` and state are assigned by the probe.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct ScheduledChange {
+    #[serde(default)]
+    pub id: u64,
+
+    /// Key written, e.g. `probing.torch.profiling`
+    pub key: String,
+    pub value: String,
+
+    pub at: ScheduleTrigger,
+
+    /// When the value before the change is restored
+    #[serde(default)]
+    pub until: Option<ScheduleTrigger>,
+
+    #[serde(default)]
+    pub state: ScheduleState,
+
+    /// Value of the key when the change was applied, `None` if it was unset
+    #[serde(default)]
+    pub previous: Option<String>,
+
+    /// Why the write failed
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(options: &[(&str, &str)], store: &[(&str, &str)]) -> ConfigDump {
+        let map = |entries: &[(&str, &str)]| {
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        ConfigDump {
+            options: map(options),
+            store: map(store),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = dump(
+            &[("probing.torch.profiling", "off")],
+            &[("a", "1"), ("b", "2")],
+        );
+        let new = dump(
+            &[("probing.torch.profiling", "on")],
+            &[("b", "2"), ("c", "3")],
+        );
+
+        let changes: Vec<_> = old.diff(&new).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            vec![
+                r#"~ [options] probing.torch.profiling: "off" -> "on""#,
+                r#"- [store] a = "1""#,
+                r#"+ [store] c = "3""#,
+            ]
+        );
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn test_option_schema_validate() {
+        let schema = OptionSchema {
+            key: "server.query_guard".to_string(),
+            kind: OptionKind::String,
+            allowed: vec!["block".into(), "warn".into(), "off".into()],
+            ..Default::default()
+        };
+        assert!(schema.validate(&schema.key, "WARN").is_ok());
+        assert!(schema.validate(&schema.key, "").is_ok());
+        let err = schema.validate(&schema.key, "on").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"invalid setting probing.server.query_guard="on": expected one of block, warn, off"#
+        );
+
+        let schema = OptionSchema {
+            key: "tracer.sample_every".to_string(),
+            kind: OptionKind::UInt,
+            ..Default::default()
+        };
+        assert!(schema.validate(&schema.key, "10").is_ok());
+        let err = schema.validate(&schema.key, "-1").unwrap_err();
+        assert_eq!(err.reason, "expected a uint value");
+    }
+
+    #[test]
+    fn test_schedule_trigger() {
+        assert!(ScheduleTrigger::Time(10).is_due(10, None));
+        assert!(!ScheduleTrigger::Time(10).is_due(9, Some(100)));
+        assert!(ScheduleTrigger::Step(1000).is_due(0, Some(1000)));
+        assert!(!ScheduleTrigger::Step(1000).is_due(i64::MAX, None));
+
+        let json = serde_json::to_string(&ScheduleTrigger::Step(5)).unwrap();
+        assert_eq!(json, r#"{"step":5}"#);
+    }
+}
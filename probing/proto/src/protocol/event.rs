@@ -0,0 +1,84 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Category of a notification emitted by the probing agent
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum EventKind {
+    /// A configuration option was changed
+    ConfigChanged,
+    /// A profiler was started, stopped or reconfigured
+    ProfilerState,
+    /// An alert condition fired
+    AlertFired,
+    /// The crash handler was installed or invoked
+    CrashHandler,
+    /// Data collection was throttled or samples were dropped
+    Throttled,
+    /// A table or namespace was registered or unregistered
+    CatalogChanged,
+}
+
+impl EventKind {
+    /// Name used for the SSE `event:` field
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::ConfigChanged => "config_changed",
+            EventKind::ProfilerState => "profiler_state",
+            EventKind::AlertFired => "alert_fired",
+            EventKind::CrashHandler => "crash_handler",
+            EventKind::Throttled => "throttled",
+            EventKind::CatalogChanged => "catalog_changed",
+        }
+    }
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A structured notification about an agent state change
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct AgentEvent {
+    /// Event category
+    pub kind: EventKind,
+
+    /// Component that emitted the event (e.g. `config`, `torch`, `python`)
+    pub source: String,
+
+    /// Human readable description
+    pub message: String,
+
+    /// Optional key/value details, e.g. the option key and its new value
+    #[serde(default)]
+    pub details: Vec<(String, String)>,
+}
+
+impl AgentEvent {
+    pub fn new<S: Into<String>, M: Into<String>>(kind: EventKind, source: S, message: M) -> Self {
+        Self {
+            kind,
+            source: source.into(),
+            message: message.into(),
+            details: vec![],
+        }
+    }
+
+    /// Attach a key/value detail to the event
+    pub fn with_detail<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.details.push((key.into(), value.into()));
+        self
+    }
+}
+
+impl Display for AgentEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.kind, self.source, self.message)?;
+        for (k, v) in &self.details {
+            write!(f, " {k}={v}")?;
+        }
+        Ok(())
+    }
+}
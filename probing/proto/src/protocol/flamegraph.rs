@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+
+/// Node of a flamegraph, aggregated from folded stacks
+///
+/// Served at `/apis/flamegraph/{profiler}/tree`, clients render the tree
+/// themselves and fetch deeper levels on demand instead of a prebuilt SVG.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct FlameNode {
+    pub name: String,
+
+    /// Samples of this frame, including its callees
+    pub total: u64,
+
+    /// Samples of this frame itself
+    #[serde(rename = "self")]
+    pub self_value: u64,
+
+    /// Callees, largest first
+    #[serde(default)]
+    pub children: Vec<FlameNode>,
+
+    /// Whether `children` was cut off and can be fetched by path
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Frame whose name matches a flamegraph search
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct FlameMatch {
+    /// Frame names from the root's child down to the matching frame
+    pub path: Vec<String>,
+    pub total: u64,
+}
+
+impl FlameNode {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Build a tree rooted at `all` from folded lines (`a;b;c 42`)
+    ///
+    /// Malformed lines are skipped.
+    pub fn from_folded<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut root = FlameNode::new("all");
+        for line in lines {
+            let Some((stack, count)) = line.as_ref().trim().rsplit_once(' ') else {
+                continue;
+            };
+            let Ok(count) = count.parse::<u64>() else {
+                continue;
+            };
+
+            root.total += count;
+            let mut node = &mut root;
+            for frame in stack.split(';').filter(|frame| !frame.is_empty()) {
+                let idx = match node.children.iter().position(|c| c.name == frame) {
+                    Some(idx) => idx,
+                    None => {
+                        node.children.push(FlameNode::new(frame));
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[idx];
+                node.total += count;
+            }
+            node.self_value += count;
+        }
+        root.sort();
+        root
+    }
+
+    fn sort(&mut self) {
+        self.children
+            .sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+
+    /// Copy of the node at `path`, keeping `depth` levels of callees
+    pub fn subtree<S: AsRef<str>>(&self, path: &[S], depth: usize) -> Option<FlameNode> {
+        let mut node = self;
+        for name in path {
+            node = node.children.iter().find(|c| c.name == name.as_ref())?;
+        }
+        Some(node.truncate(depth))
+    }
+
+    fn truncate(&self, depth: usize) -> FlameNode {
+        let mut node = FlameNode {
+            name: self.name.clone(),
+            total: self.total,
+            self_value: self.self_value,
+            children: vec![],
+            truncated: false,
+        };
+        if depth == 0 {
+            node.truncated = !self.children.is_empty();
+        } else {
+            node.children = self
+                .children
+                .iter()
+                .map(|c| c.truncate(depth - 1))
+                .collect();
+        }
+        node
+    }
+
+    /// Frames whose name contains `pattern`, case-insensitively, largest first
+    pub fn search(&self, pattern: &str, limit: usize) -> Vec<FlameMatch> {
+        let pattern = pattern.to_lowercase();
+        let mut matches = vec![];
+        let mut path = vec![];
+        for child in &self.children {
+            child.collect_matches(&pattern, &mut path, &mut matches);
+        }
+        matches.sort_by_key(|m| std::cmp::Reverse(m.total));
+        matches.truncate(limit);
+        matches
+    }
+
+    fn collect_matches(
+        &self,
+        pattern: &str,
+        path: &mut Vec<String>,
+        matches: &mut Vec<FlameMatch>,
+    ) {
+        path.push(self.name.clone());
+        if self.name.to_lowercase().contains(pattern) {
+            matches.push(FlameMatch {
+                path: path.clone(),
+                total: self.total,
+            });
+        }
+        for child in &self.children {
+            child.collect_matches(pattern, path, matches);
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> FlameNode {
+        FlameNode::from_folded([
+            "main;train;forward 30",
+            "main;train;backward 50",
+            "main;train 5",
+            "main;eval;forward 10",
+            "broken line",
+        ])
+    }
+
+    #[test]
+    fn test_from_folded() {
+        let root = tree();
+        assert_eq!(root.total, 95);
+
+        let train = &root.children[0].children[0];
+        assert_eq!(train.name, "train");
+        assert_eq!((train.total, train.self_value), (85, 5));
+        // callees are sorted by total, largest first
+        assert_eq!(train.children[0].name, "backward");
+    }
+
+    #[test]
+    fn test_subtree_truncates() {
+        let root = tree();
+        let main = root.subtree(&["main"], 1).unwrap();
+        assert_eq!(main.children.len(), 2);
+        assert!(main.children.iter().all(|c| c.truncated));
+        assert!(main.children[0].children.is_empty());
+
+        let leaf = root.subtree(&["main", "eval", "forward"], 1).unwrap();
+        assert!(!leaf.truncated);
+        assert!(root.subtree(&["main", "missing"], 1).is_none());
+    }
+
+    #[test]
+    fn test_search() {
+        let matches = tree().search("FORWARD", 10);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].path, vec!["main", "train", "forward"]);
+        assert_eq!(matches[0].total, 30);
+        assert_eq!(tree().search("forward", 1).len(), 1);
+    }
+}
@@ -1,5 +1,12 @@
+pub mod capabilities;
 pub mod cluster;
+pub mod config;
+pub mod event;
+pub mod flamegraph;
 pub mod message;
 pub mod process;
 pub mod query;
+pub mod registry;
+pub mod repl;
 pub mod version;
+pub mod waterfall;
@@ -24,6 +24,8 @@ pub enum CallFrame {
         file: String,
         func: String,
         lineno: i64,
+        #[serde(default)]
+        status: SymbolStatus,
     },
     PyFrame {
         file: String,
@@ -33,6 +35,28 @@ pub enum CallFrame {
     },
 }
 
+/// How far a native frame could be symbolized
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+pub enum SymbolStatus {
+    /// Function, file and line are known
+    Resolved,
+    /// Only the function is known, e.g. the module was built without debug info
+    Partial,
+    /// Only the raw address is known
+    #[default]
+    Unresolved,
+}
+
+impl Display for SymbolStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolStatus::Resolved => write!(f, "resolved"),
+            SymbolStatus::Partial => write!(f, "partial"),
+            SymbolStatus::Unresolved => write!(f, "unresolved"),
+        }
+    }
+}
+
 impl Display for CallFrame {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -41,9 +65,22 @@ impl Display for CallFrame {
                 file,
                 func,
                 lineno,
+                status: SymbolStatus::Resolved,
             } => {
                 write!(f, "[C/C++] {ip}, file: {file}:{lineno}\n\t{func}\n")
             }
+            CallFrame::CFrame {
+                ip,
+                file,
+                func,
+                lineno,
+                status,
+            } => {
+                write!(
+                    f,
+                    "[C/C++] {ip}, file: {file}:{lineno} ({status})\n\t{func}\n"
+                )
+            }
             CallFrame::PyFrame {
                 file,
                 func,
@@ -60,3 +97,20 @@ impl Display for CallFrame {
         }
     }
 }
+
+/// Whether the Python threads of a process are held at a safe point
+///
+/// Timestamps are microseconds since the Unix epoch.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct PauseState {
+    /// A pause was requested and has not been resumed yet
+    pub requested: bool,
+    /// The main thread holds the GIL at a bytecode boundary, no Python code runs
+    pub paused: bool,
+    /// When the safe point was reached
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// When the process resumes on its own, `None` to stay paused until resumed
+    #[serde(default)]
+    pub until: Option<u64>,
+}
@@ -4,9 +4,46 @@ use serde::{Deserialize, Serialize};
 
 use crate::types::{DataFrame, TimeSeries};
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+/// Header carrying the milliseconds a client still waits for the reply; the
+/// probe cancels the work of the request once they are over
+pub const TIMEOUT_HEADER: &str = "x-probing-timeout-ms";
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Clone)]
 pub struct Options {
     pub limit: Option<usize>,
+
+    /// Run against the read-only snapshot instead of the live tables
+    #[serde(default)]
+    pub snapshot: bool,
+
+    /// Return the result in pages of this many rows, see [`Page`]
+    #[serde(default)]
+    pub page_size: Option<usize>,
+
+    /// Fetch the page a previous reply pointed to; `expr` is not run again
+    #[serde(default)]
+    pub cursor: Option<String>,
+
+    /// Session whose temporary tables the query sees and creates
+    #[serde(default)]
+    pub session: Option<String>,
+
+    /// Columns of the result to return, in this order
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+
+    /// Fraction of the rows to return, drawn at random, in `(0, 1]`
+    #[serde(default)]
+    pub sample_rate: Option<f64>,
+
+    /// Characters of a text cell to return, longer cells are cut and end
+    /// with `…`
+    #[serde(default)]
+    pub max_cell_chars: Option<usize>,
+
+    /// Run the query even if the query guard flags it as heavy
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
@@ -19,6 +56,17 @@ impl Query {
     pub fn new(expr: String) -> Self {
         Self { expr, opts: None }
     }
+
+    /// Request for the page of a paginated result that `cursor` points to
+    pub fn next_page(cursor: String) -> Self {
+        Self {
+            expr: String::new(),
+            opts: Some(Options {
+                cursor: Some(cursor),
+                ..Default::default()
+            }),
+        }
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
@@ -28,6 +76,23 @@ pub enum Data {
     Error(QueryError),
     DataFrame(DataFrame),
     TimeSeries(TimeSeries),
+    Page(Page),
+}
+
+/// One page of a result requested with `page_size`. The rest of the result
+/// is kept on the server for a while and fetched with `next_cursor`.
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Clone)]
+pub struct Page {
+    pub df: DataFrame,
+
+    /// Index of the first row of the page in the whole result
+    pub offset: usize,
+
+    /// Number of rows of the whole result
+    pub total: usize,
+
+    /// Cursor of the next page, `None` on the last page
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -35,21 +100,95 @@ pub struct QueryError {
     pub code: ErrorCode,
     pub message: String,
     pub details: Option<String>,
+
+    /// Table the error refers to, e.g. a table that does not exist
+    #[serde(default)]
+    pub table: Option<String>,
+
+    /// Column the error refers to, e.g. an unknown field
+    #[serde(default)]
+    pub column: Option<String>,
+
+    /// Location of the offending token in the SQL text
+    #[serde(default)]
+    pub position: Option<SqlPosition>,
+
+    /// Suggestion for fixing the query, e.g. similar table names
+    #[serde(default)]
+    pub hint: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// 1-based line and column in a SQL statement
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+pub struct SqlPosition {
+    pub line: u64,
+    pub column: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
 pub enum ErrorCode {
     ParseError,
+    PlanError,
     ExecutionError,
     TimeoutError,
     ResourceExhausted,
     PermissionDenied,
     NotFound,
+    TableNotFound,
+    ColumnNotFound,
     Internal,
 }
 
+impl QueryError {
+    pub fn new<S: Into<String>>(code: ErrorCode, message: S) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+            table: None,
+            column: None,
+            position: None,
+            hint: None,
+        }
+    }
+
+    pub fn with_details<S: Into<String>>(mut self, details: S) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    pub fn with_table<S: Into<String>>(mut self, table: S) -> Self {
+        self.table = Some(table.into());
+        self
+    }
+
+    pub fn with_column<S: Into<String>>(mut self, column: S) -> Self {
+        self.column = Some(column.into());
+        self
+    }
+
+    pub fn with_position(mut self, line: u64, column: u64) -> Self {
+        self.position = Some(SqlPosition { line, column });
+        self
+    }
+
+    pub fn with_hint<S: Into<String>>(mut self, hint: S) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
 impl Display for QueryError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "QueryError: {:?} - {}", self.code, self.message)
+        write!(f, "QueryError: {:?} - {}", self.code, self.message)?;
+        if let Some(SqlPosition { line, column }) = self.position {
+            write!(f, " (at line {line}, column {column})")?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, ", {hint}")?;
+        }
+        Ok(())
     }
 }
+
+impl std::error::Error for QueryError {}
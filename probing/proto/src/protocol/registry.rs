@@ -0,0 +1,211 @@
+//! Discovery files of running probes.
+//!
+//! Every probe writes a small JSON file named after its pid into
+//! [`registry_dir`] when it starts and removes it when the process exits,
+//! so that clients find the probes of a host by listing one directory.
+//!
+//! The directory is created readable by its user only. One owned by another
+//! user, or writable by others, is refused: whoever can write there can
+//! point clients at their own sockets.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Variable overriding the directory of the discovery files
+pub const REGISTRY_DIR_ENV: &str = "PROBING_REGISTRY_DIR";
+
+/// Discovery file of one probe
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct Registration {
+    pub pid: i32,
+    /// Unix socket of the probe, `@probing-<pid>` for abstract sockets
+    pub socket: String,
+    /// TCP address of the server, once started
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Last characters of the auth token, if the server requires one
+    #[serde(default)]
+    pub token_hint: Option<String>,
+    /// Start time of the probe in microseconds since the unix epoch
+    pub started: u64,
+}
+
+/// `$PROBING_REGISTRY_DIR`, or `$XDG_RUNTIME_DIR/probing`, or a `probing-<user>`
+/// directory in the temp dir when the runtime dir is not set
+pub fn registry_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(REGISTRY_DIR_ENV).filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir).join("probing");
+    }
+    let user = std::env::var("USER").unwrap_or_default();
+    std::env::temp_dir().join(format!("probing-{user}"))
+}
+
+/// Create `dir` with mode 0700, and check it with [`check_private_dir`]
+pub fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    #[cfg(not(unix))]
+    std::fs::create_dir_all(dir)?;
+    check_private_dir(dir)
+}
+
+/// Fail unless `dir` is a directory of the current user that others cannot
+/// write to
+pub fn check_private_dir(dir: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(dir)?;
+    let refuse = |reason: String| {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("refusing registry dir {}: {reason}", dir.display()),
+        ))
+    };
+    if !metadata.is_dir() {
+        return refuse("not a directory".to_string());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let uid = unsafe { libc::geteuid() };
+        if metadata.uid() != uid {
+            return refuse(format!("owned by uid {}", metadata.uid()));
+        }
+        if metadata.mode() & 0o022 != 0 {
+            return refuse(format!(
+                "writable by others, mode {:o}",
+                metadata.mode() & 0o777
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Last characters of `token`, enough to tell tokens apart without
+/// revealing them
+pub fn token_hint(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+    if chars.len() > 8 {
+        format!("...{tail}")
+    } else {
+        "...".to_string()
+    }
+}
+
+impl Registration {
+    pub fn path(dir: &Path, pid: i32) -> PathBuf {
+        dir.join(format!("{pid}.json"))
+    }
+
+    /// Write the discovery file, replacing the previous one atomically
+    pub fn write(&self, dir: &Path) -> std::io::Result<()> {
+        create_private_dir(dir)?;
+        let tmp = dir.join(format!(".{}.json.tmp", self.pid));
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, Self::path(dir, self.pid))
+    }
+
+    /// Remove the discovery file of `pid`, if any
+    pub fn remove(dir: &Path, pid: i32) -> std::io::Result<()> {
+        match std::fs::remove_file(Self::path(dir, pid)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Registrations found in `dir`, sorted by pid; unreadable files are skipped
+    pub fn list(dir: &Path) -> std::io::Result<Vec<Registration>> {
+        match check_private_dir(dir) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            result => result?,
+        }
+        let entries = std::fs::read_dir(dir)?;
+        let mut registrations: Vec<Registration> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| std::fs::read(path).ok())
+            .filter_map(|data| serde_json::from_slice(&data).ok())
+            .collect();
+        registrations.sort_by_key(|r| r.pid);
+        Ok(registrations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_roundtrip() {
+        let dir =
+            std::env::temp_dir().join(format!("probing-registry-test-{}", std::process::id()));
+        let registration = Registration {
+            pid: 42,
+            socket: "@probing-42".to_string(),
+            started: 1,
+            ..Default::default()
+        };
+        registration.write(&dir).unwrap();
+        Registration {
+            pid: 7,
+            address: Some("0.0.0.0:9700".to_string()),
+            ..registration.clone()
+        }
+        .write(&dir)
+        .unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let found = Registration::list(&dir).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].address.as_deref(), Some("0.0.0.0:9700"));
+        assert_eq!(found[1], registration);
+
+        Registration::remove(&dir, 7).unwrap();
+        Registration::remove(&dir, 7).unwrap();
+        assert_eq!(Registration::list(&dir).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(Registration::list(&dir).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_registry_dir_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("probing-private-test-{}", std::process::id()));
+        let registration = Registration {
+            pid: 42,
+            ..Default::default()
+        };
+        registration.write(&dir).unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        let err = registration.write(&dir).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(Registration::list(&dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::write(&dir, "").unwrap();
+        assert!(check_private_dir(&dir).is_err());
+        std::fs::remove_file(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_token_hint() {
+        assert_eq!(token_hint("s3cr3t-token-abcd"), "...abcd");
+        assert_eq!(token_hint("short"), "...");
+    }
+}
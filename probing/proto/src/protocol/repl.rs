@@ -0,0 +1,235 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::types::ProtoError;
+
+/// Status of a REPL frame that is followed by more output of the same command
+pub const PARTIAL_STATUS: &str = "partial";
+
+/// Frames smaller than this are sent uncompressed even when compression is on
+pub const COMPRESSION_MIN_BYTES: usize = 1024;
+
+/// Compression applied to REPL frames, negotiated by the client with the
+/// `compress` query parameter of the `/ws` endpoint
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplCompression {
+    #[default]
+    None,
+    /// Raw deflate, compressed frames are sent as binary messages. The
+    /// WebSocket permessage-deflate extension would do this transparently,
+    /// but tungstenite, under both axum and the CLI, does not implement it
+    Deflate,
+}
+
+impl ReplCompression {
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, ProtoError> {
+        match self {
+            ReplCompression::None => Ok(data.to_vec()),
+            ReplCompression::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder
+                    .write_all(data)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|e| ProtoError::CompressError(e.to_string()))
+            }
+        }
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<u8>, ProtoError> {
+        match self {
+            ReplCompression::None => Ok(data.to_vec()),
+            ReplCompression::Deflate => {
+                let mut decoded = Vec::new();
+                DeflateDecoder::new(data)
+                    .read_to_end(&mut decoded)
+                    .map_err(|e| ProtoError::CompressError(e.to_string()))?;
+                Ok(decoded)
+            }
+        }
+    }
+}
+
+impl FromStr for ReplCompression {
+    type Err = ProtoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "" | "none" => Ok(ReplCompression::None),
+            "deflate" => Ok(ReplCompression::Deflate),
+            _ => Err(ProtoError::DeserializationError(format!(
+                "unknown repl compression: {s}"
+            ))),
+        }
+    }
+}
+
+impl Display for ReplCompression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplCompression::None => f.write_str("none"),
+            ReplCompression::Deflate => f.write_str("deflate"),
+        }
+    }
+}
+
+/// A named REPL session, its namespace outlives the connections attached to it
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ReplSession {
+    pub name: String,
+    /// Creation time, in microseconds since the epoch
+    pub created: i64,
+    /// Time of the last command, in microseconds since the epoch
+    pub last_active: i64,
+    /// Number of connections currently attached
+    pub attached: u32,
+    /// Number of commands executed
+    pub commands: u64,
+}
+
+/// State of an eval job
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvalJobState {
+    #[default]
+    Running,
+    /// The code ran to its end
+    Done,
+    /// The code raised an exception
+    Failed,
+    /// The code was interrupted by a cancel request
+    Cancelled,
+}
+
+impl EvalJobState {
+    pub fn is_finished(&self) -> bool {
+        *self != EvalJobState::Running
+    }
+}
+
+impl Display for EvalJobState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalJobState::Running => f.write_str("running"),
+            EvalJobState::Done => f.write_str("done"),
+            EvalJobState::Failed => f.write_str("failed"),
+            EvalJobState::Cancelled => f.write_str("cancelled"),
+        }
+    }
+}
+
+/// Python code evaluated in a thread of its own, see `/apis/pythonext/eval/start`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EvalJob {
+    pub id: u64,
+    /// REPL session whose namespace the code runs in, the main namespace if empty
+    #[serde(default)]
+    pub session: Option<String>,
+    pub state: EvalJobState,
+    /// Start time, in microseconds since the epoch
+    pub started: i64,
+    /// End time, in microseconds since the epoch
+    #[serde(default)]
+    pub finished: Option<i64>,
+    /// Bytes of output produced so far
+    pub output_bytes: usize,
+}
+
+/// Output of an eval job from a byte offset on, polled with
+/// `/apis/pythonext/eval/job?id=<id>&offset=<offset>`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EvalJobOutput {
+    pub job: EvalJob,
+    pub output: String,
+    /// Offset to poll from next
+    pub offset: usize,
+}
+
+/// End `output` with a marker telling that `dropped` bytes of it were cut
+/// off by `repl.max_output_bytes`, nothing when none were
+pub fn mark_truncated(output: &mut String, dropped: usize) {
+    if dropped == 0 {
+        return;
+    }
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output.push_str(&format!(
+        "... [{dropped} bytes truncated, see repl.max_output_bytes]\n"
+    ));
+}
+
+/// Split `output` into pieces of at most `chunk_bytes`, never inside a
+/// character; a `chunk_bytes` of 0 keeps the output whole
+pub fn split_output(output: &str, chunk_bytes: usize) -> Vec<&str> {
+    if chunk_bytes == 0 || output.len() <= chunk_bytes {
+        return vec![output];
+    }
+    let mut chunks = Vec::new();
+    let mut rest = output;
+    while rest.len() > chunk_bytes {
+        let mut end = chunk_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        // a single character wider than the chunk still has to go out
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let data = "layer.weight ".repeat(1000);
+        for codec in [ReplCompression::None, ReplCompression::Deflate] {
+            let encoded = codec.encode(data.as_bytes()).unwrap();
+            assert_eq!(codec.decode(&encoded).unwrap(), data.as_bytes());
+        }
+        let deflated = ReplCompression::Deflate.encode(data.as_bytes()).unwrap();
+        assert!(deflated.len() < data.len() / 10);
+
+        assert_eq!(
+            "deflate".parse::<ReplCompression>().unwrap(),
+            ReplCompression::Deflate
+        );
+        assert_eq!(
+            "".parse::<ReplCompression>().unwrap(),
+            ReplCompression::None
+        );
+        assert!("zstd".parse::<ReplCompression>().is_err());
+    }
+
+    #[test]
+    fn test_mark_truncated_and_split_output() {
+        let mut output = "héllo".to_string();
+        mark_truncated(&mut output, 0);
+        assert_eq!(output, "héllo");
+        mark_truncated(&mut output, 7);
+        assert_eq!(
+            output,
+            "héllo\n... [7 bytes truncated, see repl.max_output_bytes]\n"
+        );
+
+        assert_eq!(split_output("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(split_output("abc", 0), vec!["abc"]);
+        assert_eq!(split_output("ééé", 3), vec!["é", "é", "é"]);
+        assert_eq!(split_output("ééé", 1), vec!["é", "é", "é"]);
+    }
+}
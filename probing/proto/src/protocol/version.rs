@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 /// Protocol version information
@@ -32,3 +35,67 @@ impl ProtocolVersion {
         Self::default()
     }
 }
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Build of a probe, served at `/apis/version`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BuildInfo {
+    /// Version of the probe
+    pub version: String,
+
+    /// Commit the probe was built from, empty outside of a git checkout
+    pub git_hash: String,
+
+    /// Protocol spoken by the probe
+    pub protocol: ProtocolVersion,
+
+    /// Optional features built in, as in [`super::capabilities::Capabilities`]
+    pub features: Vec<String>,
+
+    /// ABI tag of the interpreter the probe runs in, e.g. `cp312`
+    pub python_abi: String,
+
+    /// Versions of the probing crates linked into the probe
+    pub crates: BTreeMap<String, String>,
+}
+
+impl BuildInfo {
+    /// Why a client speaking `protocol` cannot talk to this probe, if it cannot
+    pub fn incompatibility(&self, protocol: &ProtocolVersion) -> Option<String> {
+        if self.protocol.is_compatible_with(protocol) {
+            return None;
+        }
+        Some(format!(
+            "probe {} speaks protocol {}, outside of the {}.x supported by this client {}",
+            self.version, self.protocol, protocol.major, protocol
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incompatibility() {
+        let current = ProtocolVersion::current();
+        let mut build = BuildInfo {
+            version: "0.2.3".to_string(),
+            protocol: ProtocolVersion {
+                minor: current.minor + 1,
+                ..current.clone()
+            },
+            ..Default::default()
+        };
+        assert_eq!(build.incompatibility(&current), None);
+
+        build.protocol.major = current.major + 1;
+        let reason = build.incompatibility(&current).unwrap();
+        assert!(reason.starts_with(&format!("probe 0.2.3 speaks protocol {}", build.protocol)));
+    }
+}
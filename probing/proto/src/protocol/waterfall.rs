@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+/// Phase of a training step drawn in the step-time waterfall
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepPhase {
+    DataLoad,
+    /// Host to device memory copies
+    H2d,
+    Forward,
+    Backward,
+    Optimizer,
+    /// Collective kernels such as NCCL all-reduces
+    Comm,
+}
+
+impl StepPhase {
+    /// Phases in the order they are drawn
+    pub const ALL: [StepPhase; 6] = [
+        StepPhase::DataLoad,
+        StepPhase::H2d,
+        StepPhase::Forward,
+        StepPhase::Backward,
+        StepPhase::Optimizer,
+        StepPhase::Comm,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepPhase::DataLoad => "data_load",
+            StepPhase::H2d => "h2d",
+            StepPhase::Forward => "forward",
+            StepPhase::Backward => "backward",
+            StepPhase::Optimizer => "optimizer",
+            StepPhase::Comm => "comm",
+        }
+    }
+}
+
+/// Time range of a phase in a step
+///
+/// `start` and `end` are nanoseconds from the start of the step, `busy` the
+/// time actually spent in the phase within that range.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PhaseSegment {
+    pub phase: StepPhase,
+    pub start: i64,
+    pub end: i64,
+    pub busy: i64,
+}
+
+/// One step of the waterfall served at `/apis/waterfall`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StepWaterfall {
+    pub step: i64,
+
+    /// Nanoseconds since the unix epoch, `None` when no span or profiler
+    /// event marks the step and only the offsets of its modules are known
+    #[serde(default)]
+    pub start: Option<i64>,
+
+    /// Nanoseconds from the start of the step to the end of its last phase
+    pub duration: i64,
+
+    /// Phases present in the step, in the order of [`StepPhase::ALL`]
+    #[serde(default)]
+    pub segments: Vec<PhaseSegment>,
+
+    /// Nanoseconds of collective kernels overlapped by compute kernels
+    #[serde(default)]
+    pub comm_overlap: i64,
+}
+
+impl StepWaterfall {
+    /// Segment of `phase`, if the step has one
+    pub fn segment(&self, phase: StepPhase) -> Option<&PhaseSegment> {
+        self.segments.iter().find(|x| x.phase == phase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_names() {
+        for phase in StepPhase::ALL {
+            let json = serde_json::to_string(&phase).unwrap();
+            assert_eq!(json, format!("\"{}\"", phase.as_str()));
+        }
+    }
+}
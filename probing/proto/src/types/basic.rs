@@ -19,6 +19,38 @@ pub enum EleType {
     DataTime,
 }
 
+impl Display for EleType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EleType::Nil => "nil",
+            EleType::BOOL => "bool",
+            EleType::I32 => "i32",
+            EleType::I64 => "i64",
+            EleType::F32 => "f32",
+            EleType::F64 => "f64",
+            EleType::Text => "text",
+            EleType::Url => "url",
+            EleType::DataTime => "datetime",
+        })
+    }
+}
+
+impl EleType {
+    /// The type a column of this type is widened to so that it can hold
+    /// values of type `other`, e.g. integers to floats.
+    ///
+    /// `None` when no widening is needed, see [`Ele::coerce`], or when the
+    /// types are not compatible.
+    pub fn widen(&self, other: &EleType) -> Option<EleType> {
+        use EleType::*;
+        match (self, other) {
+            (BOOL, I32 | I64) | (I32, I64) => Some(other.clone()),
+            (BOOL | I32 | I64 | F32, F32 | F64) if self != other => Some(F64),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub enum Ele {
     Nil,
@@ -52,6 +84,79 @@ impl Display for Ele {
     }
 }
 
+impl Ele {
+    pub fn dtype(&self) -> EleType {
+        match self {
+            Ele::Nil => EleType::Nil,
+            Ele::BOOL(_) => EleType::BOOL,
+            Ele::I32(_) => EleType::I32,
+            Ele::I64(_) => EleType::I64,
+            Ele::F32(_) => EleType::F32,
+            Ele::F64(_) => EleType::F64,
+            Ele::Text(_) => EleType::Text,
+            Ele::Url(_) => EleType::Url,
+            Ele::DataTime(_) => EleType::DataTime,
+        }
+    }
+
+    /// Convert the element to `dtype` without losing information.
+    ///
+    /// Booleans and integers are widened to larger integers and floats, and
+    /// any element can be stored as text. [`Ele::Nil`] converts to every type.
+    /// Other conversions are refused with `None`.
+    pub fn coerce(&self, dtype: &EleType) -> Option<Ele> {
+        if *self == Ele::Nil || self.dtype() == *dtype {
+            return Some(self.clone());
+        }
+        match (self, dtype) {
+            (Ele::BOOL(x), EleType::I32) => Some(Ele::I32(*x as i32)),
+            (Ele::BOOL(x), EleType::I64) => Some(Ele::I64(*x as i64)),
+            (Ele::BOOL(x), EleType::F64) => Some(Ele::F64(*x as i64 as f64)),
+            (Ele::I32(x), EleType::I64) => Some(Ele::I64(*x as i64)),
+            (Ele::I32(x), EleType::F64) => Some(Ele::F64(*x as f64)),
+            (Ele::I64(x), EleType::F64) => Some(Ele::F64(*x as f64)),
+            (Ele::F32(x), EleType::F64) => Some(Ele::F64(*x as f64)),
+            (Ele::Text(x) | Ele::Url(x), EleType::Text) => Some(Ele::Text(x.clone())),
+            (x, EleType::Text) => Some(Ele::Text(x.to_string())),
+            _ => None,
+        }
+    }
+
+    /// Build a [`Ele::DataTime`] from nanoseconds since the unix epoch, as
+    /// recorded by trace spans. Sub-microsecond precision is dropped and
+    /// out of range values saturate.
+    pub fn datetime_from_nanos(nanos: u128) -> Self {
+        Ele::DataTime(u64::try_from(nanos / 1_000).unwrap_or(u64::MAX))
+    }
+
+    /// Nanoseconds since the unix epoch of a time-like element.
+    ///
+    /// [`Ele::DataTime`] holds microseconds, integers are taken as
+    /// nanoseconds. Other elements are not time-like and yield `None`.
+    pub fn timestamp_nanos(&self) -> Option<i64> {
+        match self {
+            Ele::DataTime(micros) => Some(
+                i64::try_from(*micros)
+                    .unwrap_or(i64::MAX)
+                    .saturating_mul(1_000),
+            ),
+            Ele::I64(nanos) => Some(*nanos),
+            Ele::I32(nanos) => Some(*nanos as i64),
+            _ => None,
+        }
+    }
+}
+
+impl From<SystemTime> for Ele {
+    fn from(time: SystemTime) -> Self {
+        let micros = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_micros())
+            .unwrap_or_default();
+        Ele::DataTime(u64::try_from(micros).unwrap_or(u64::MAX))
+    }
+}
+
 impl From<&str> for Ele {
     fn from(val: &str) -> Self {
         Ele::Text(val.to_string())
@@ -187,6 +292,20 @@ impl Seq {
         }
     }
 
+    /// Release the capacity reserved beyond the values held
+    pub fn shrink_to_fit(&mut self) {
+        match self {
+            Seq::SeqBOOL(vec) => vec.shrink_to_fit(),
+            Seq::SeqI32(vec) => vec.shrink_to_fit(),
+            Seq::SeqI64(vec) => vec.shrink_to_fit(),
+            Seq::SeqF32(vec) => vec.shrink_to_fit(),
+            Seq::SeqF64(vec) => vec.shrink_to_fit(),
+            Seq::SeqText(vec) => vec.shrink_to_fit(),
+            Seq::SeqDateTime(vec) => vec.shrink_to_fit(),
+            Seq::Nil => {}
+        }
+    }
+
     pub fn get_str(&self, idx: usize) -> Option<String> {
         match self {
             Seq::SeqBOOL(vec) => vec.get(idx).map(|x| x.to_string()),
@@ -238,6 +357,41 @@ impl Seq {
         }
         Ok(())
     }
+
+    /// Copy of the values in `start..end`, clamped to the length of the sequence
+    pub fn slice(&self, start: usize, end: usize) -> Seq {
+        fn range<T: Clone>(vec: &[T], start: usize, end: usize) -> Vec<T> {
+            let end = end.min(vec.len());
+            vec[start.min(end)..end].to_vec()
+        }
+        match self {
+            Seq::SeqBOOL(vec) => Seq::SeqBOOL(range(vec, start, end)),
+            Seq::SeqI32(vec) => Seq::SeqI32(range(vec, start, end)),
+            Seq::SeqI64(vec) => Seq::SeqI64(range(vec, start, end)),
+            Seq::SeqF32(vec) => Seq::SeqF32(range(vec, start, end)),
+            Seq::SeqF64(vec) => Seq::SeqF64(range(vec, start, end)),
+            Seq::SeqText(vec) => Seq::SeqText(range(vec, start, end)),
+            Seq::SeqDateTime(vec) => Seq::SeqDateTime(range(vec, start, end)),
+            Seq::Nil => Seq::Nil,
+        }
+    }
+
+    /// Append all values of `other`, which must have the same type
+    pub fn extend(&mut self, other: Seq) -> Result<(), ProtoError> {
+        match (&mut *self, other) {
+            (_, Seq::Nil) => {}
+            (Seq::Nil, other) => *self = other,
+            (Seq::SeqBOOL(vec), Seq::SeqBOOL(other)) => vec.extend(other),
+            (Seq::SeqI32(vec), Seq::SeqI32(other)) => vec.extend(other),
+            (Seq::SeqI64(vec), Seq::SeqI64(other)) => vec.extend(other),
+            (Seq::SeqF32(vec), Seq::SeqF32(other)) => vec.extend(other),
+            (Seq::SeqF64(vec), Seq::SeqF64(other)) => vec.extend(other),
+            (Seq::SeqText(vec), Seq::SeqText(other)) => vec.extend(other),
+            (Seq::SeqDateTime(vec), Seq::SeqDateTime(other)) => vec.extend(other),
+            _ => return Err(ProtoError::WrongSequenceType),
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
@@ -262,6 +416,21 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_datetime_normalization() {
+        let ele = Ele::datetime_from_nanos(1_700_000_000_123_456_789);
+        assert_eq!(ele, Ele::DataTime(1_700_000_000_123_456));
+        assert_eq!(ele.timestamp_nanos(), Some(1_700_000_000_123_456_000));
+        assert_eq!(Ele::I64(42).timestamp_nanos(), Some(42));
+        assert_eq!(Ele::Text("42".into()).timestamp_nanos(), None);
+
+        assert_eq!(Ele::datetime_from_nanos(u128::MAX), Ele::DataTime(u64::MAX));
+        assert_eq!(Ele::DataTime(u64::MAX).timestamp_nanos(), Some(i64::MAX));
+
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_micros(5);
+        assert_eq!(Ele::from(epoch), Ele::DataTime(5));
+    }
+
     #[test]
     fn test_seq_append_from_nil() {
         let mut seq = Seq::Nil;
@@ -309,4 +478,17 @@ mod tests {
         assert_eq!(seq.len(), 0);
         assert!(seq.is_empty());
     }
+
+    #[test]
+    fn test_seq_slice_and_extend() {
+        let seq = Seq::SeqI64(vec![1, 2, 3, 4]);
+        assert_eq!(seq.slice(1, 3), Seq::SeqI64(vec![2, 3]));
+        assert_eq!(seq.slice(3, 10), Seq::SeqI64(vec![4]));
+        assert_eq!(seq.slice(10, 20), Seq::SeqI64(vec![]));
+
+        let mut head = seq.slice(0, 2);
+        assert!(head.extend(seq.slice(2, 4)).is_ok());
+        assert_eq!(head, seq);
+        assert!(head.extend(Seq::SeqF64(vec![1.0])).is_err());
+    }
 }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use super::Ele;
+use super::ProtoError;
 use super::Seq;
 
 #[derive(Debug, Default, Deserialize, Serialize, PartialEq, Clone)]
@@ -37,6 +38,31 @@ impl DataFrame {
             current: 0,
         }
     }
+
+    /// Rows `offset..offset + len`, clamped to the rows of the frame
+    pub fn slice(&self, offset: usize, len: usize) -> DataFrame {
+        let end = offset.saturating_add(len);
+        DataFrame {
+            names: self.names.clone(),
+            cols: self.cols.iter().map(|col| col.slice(offset, end)).collect(),
+            size: 0,
+        }
+    }
+
+    /// Append the rows of `other`, which must have the same columns
+    pub fn extend(&mut self, other: DataFrame) -> Result<(), ProtoError> {
+        if self.cols.is_empty() {
+            *self = other;
+            return Ok(());
+        }
+        if other.names != self.names {
+            return Err(ProtoError::WrongSequenceType);
+        }
+        for (col, other) in self.cols.iter_mut().zip(other.cols) {
+            col.extend(other)?;
+        }
+        Ok(())
+    }
 }
 
 pub struct DataFrameIterator<'a> {
@@ -17,4 +17,4 @@ pub use convert::{EleExt, FromEle, ToEle};
 pub use dataframe::DataFrame;
 pub use error::ProtoError;
 pub use series::{DiscardStrategy, Series};
-pub use time_series::TimeSeries;
+pub use time_series::{TimeSeries, TimeSeriesError};
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::Bound::Included;
 
 use anyhow::Result;
@@ -191,6 +191,7 @@ impl SeriesConfig {
             dropped: 0,
             slices: Default::default(),
             current_slice: None,
+            nulls: Default::default(),
             commit_nbytes: 0,
             commit_counts: 0,
         }
@@ -209,6 +210,9 @@ pub struct Series {
     pub dropped: usize,
     pub slices: BTreeMap<usize, Slice>,
     current_slice: Option<Slice>,
+    /// Offsets of null values, stored as placeholders in the slices
+    #[serde(default)]
+    pub nulls: BTreeSet<usize>,
 
     commit_nbytes: usize,
     commit_counts: usize,
@@ -271,10 +275,36 @@ impl Series {
             Ele::F32(data) => self.append(data),
             Ele::F64(data) => self.append(data),
             Ele::Text(data) => self.append(data),
+            Ele::DataTime(data) => self.append(data),
+            Ele::BOOL(data) => self.append(data),
+            Ele::Nil => {
+                self.append_nulls(1);
+                Ok(())
+            }
             _ => Err(ProtoError::InvalidValueDateType),
         }
     }
 
+    /// Append `n` null values.
+    ///
+    /// Before the first value, when the type of the series is still unknown,
+    /// nulls take no space and the series starts at a later offset.
+    /// Afterwards they are stored as placeholders and reported as
+    /// [`Ele::Nil`] by [`Series::get`] and [`Series::iter`].
+    pub fn append_nulls(&mut self, n: usize) {
+        let Some(placeholder) = placeholder(&self.config.dtype) else {
+            self.offset = self.offset.saturating_add(n);
+            self.dropped = self.offset;
+            return;
+        };
+        for _ in 0..n {
+            let offset = self.offset;
+            if self.append_value(placeholder.clone()).is_ok() {
+                self.nulls.insert(offset);
+            }
+        }
+    }
+
     pub fn dtype(&self) -> EleType {
         self.config.dtype.clone()
     }
@@ -298,6 +328,29 @@ impl Series {
         self.commit_counts
     }
 
+    /// Release the capacity reserved for the chunk being filled; it grows
+    /// again with the next appends
+    pub fn shrink_to_fit(&mut self) {
+        if let Some(Slice {
+            data: Page::Raw(array),
+            ..
+        }) = self.current_slice.as_mut()
+        {
+            array.shrink_to_fit();
+        }
+    }
+
+    /// Offset of the first value still held, the values before it were
+    /// discarded by the discard strategy
+    pub fn first_offset(&self) -> usize {
+        self.slices
+            .keys()
+            .next()
+            .copied()
+            .or(self.current_slice.as_ref().map(|slice| slice.offset))
+            .unwrap_or(self.offset)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
@@ -307,6 +360,9 @@ impl Series {
         if idx >= self.offset || idx < self.dropped {
             return None;
         }
+        if self.nulls.contains(&idx) {
+            return Some(Ele::Nil);
+        }
 
         // Check current slice first
         if let Some(slice) = self.current_slice.as_ref() {
@@ -330,6 +386,51 @@ impl Series {
     pub fn iter(&self) -> SeriesIterator<'_> {
         SeriesIterator::new(self)
     }
+
+    /// An empty series with the configuration of this one
+    pub fn empty_like(&self) -> Series {
+        self.config.clone().build()
+    }
+
+    /// An empty series with the configuration of this one, whose type is
+    /// set by its first value
+    pub fn empty_untyped(&self) -> Series {
+        self.config.clone().with_dtype(EleType::Nil).build()
+    }
+
+    /// Convert the values to `dtype`, see [`Ele::coerce`].
+    ///
+    /// Offsets are kept, so the series stays aligned with the series it was
+    /// built along with.
+    pub fn cast(&self, dtype: &EleType) -> Result<Series, ProtoError> {
+        let mut cast = self.config.clone().with_dtype(dtype.clone()).build();
+        let iter = self.iter();
+        let start = iter.index().unwrap_or(self.offset);
+        cast.offset = start;
+        cast.dropped = start;
+        for value in iter {
+            let value = value.coerce(dtype).ok_or(ProtoError::TypeMismatch {
+                expected: dtype.clone(),
+                got: value.dtype(),
+            })?;
+            cast.append_value(value)?;
+        }
+        Ok(cast)
+    }
+}
+
+/// Value stored in place of nulls in a series of type `dtype`
+fn placeholder(dtype: &EleType) -> Option<Ele> {
+    match dtype {
+        EleType::BOOL => Some(Ele::BOOL(false)),
+        EleType::I32 => Some(Ele::I32(0)),
+        EleType::I64 => Some(Ele::I64(0)),
+        EleType::F32 => Some(Ele::F32(0.0)),
+        EleType::F64 => Some(Ele::F64(0.0)),
+        EleType::Text => Some(Ele::Text(String::new())),
+        EleType::DataTime => Some(Ele::DataTime(0)),
+        EleType::Nil | EleType::Url => None,
+    }
 }
 
 impl Series {
@@ -352,27 +453,35 @@ impl Series {
                 discard_threshold, ..
             } => {
                 while self.nbytes() > discard_threshold {
-                    if let Some((_offset, slice)) = self.slices.pop_first() {
-                        self.dropped += slice.offset + slice.length;
-                        self.commit_nbytes -= slice.nbytes();
-                    }
+                    let Some((_offset, slice)) = self.slices.pop_first() else {
+                        break;
+                    };
+                    self.dropped = slice.offset + slice.length;
+                    self.commit_nbytes -= slice.nbytes();
                 }
             }
             DiscardStrategy::BaseElementCount {
                 discard_threshold, ..
             } => {
-                while self.ncounts() >= (discard_threshold - 1) {
-                    if let Some((_offset, slice)) = self.slices.pop_first() {
-                        self.dropped += slice.offset + slice.length;
-                        self.commit_nbytes -= slice.nbytes();
-                        self.commit_counts = 0;
-                    }
+                // commit_counts is the number of values held
+                while self.ncounts() >= discard_threshold {
+                    let Some((_offset, slice)) = self.slices.pop_first() else {
+                        break;
+                    };
+                    self.dropped = slice.offset + slice.length;
+                    self.commit_nbytes -= slice.nbytes();
+                    self.commit_counts -= slice.length;
                 }
             }
             DiscardStrategy::None => {
                 // No action needed
             }
         }
+
+        let start = self.slices.keys().next().copied().unwrap_or(self.offset);
+        if self.nulls.first().is_some_and(|&null| null < start) {
+            self.nulls = self.nulls.split_off(&start);
+        }
     }
 }
 
@@ -411,17 +520,21 @@ macro_rules! impl_array_type {
 }
 
 // 使用宏实现所有基本类型
+impl_array_type!(bool, BOOL, SeqBOOL);
 impl_array_type!(i32, I32, SeqI32);
 impl_array_type!(i64, I64, SeqI64);
 impl_array_type!(f32, F32, SeqF32);
 impl_array_type!(f64, F64, SeqF64);
 impl_array_type!(String, Text, SeqText);
+// `u64` values are microseconds since the unix epoch, see `Ele::DataTime`
+impl_array_type!(u64, DataTime, SeqDateTime);
 
 pub struct SeriesIterator<'a> {
     current_btree_iter: std::collections::btree_map::Iter<'a, usize, Slice>,
     current_btree_slice: Option<(&'a usize, &'a Slice)>,
     current_slice: Option<&'a Slice>,
     elem_idx: usize,
+    nulls: &'a BTreeSet<usize>,
 
     cache: Seq,
 }
@@ -430,12 +543,44 @@ impl<'a> SeriesIterator<'a> {
     pub fn new(series: &'a Series) -> Self {
         let mut current_btree_iter = series.slices.iter();
         let current_btree_slice = current_btree_iter.next();
-        SeriesIterator {
+        let mut iter = SeriesIterator {
             current_btree_iter,
             current_btree_slice,
             current_slice: series.current_slice.as_ref(),
             elem_idx: 0,
+            nulls: &series.nulls,
             cache: Seq::Nil,
+        };
+        iter.settle();
+        iter
+    }
+
+    /// Offset in the series of the next value, `None` at the end
+    pub fn index(&self) -> Option<usize> {
+        self.slice().map(|slice| slice.offset + self.elem_idx)
+    }
+
+    fn slice(&self) -> Option<&'a Slice> {
+        match self.current_btree_slice {
+            Some((_, slice)) => Some(slice),
+            None => self.current_slice,
+        }
+    }
+
+    /// Move past exhausted slices
+    fn settle(&mut self) {
+        while let Some((_, slice)) = self.current_btree_slice {
+            if self.elem_idx < slice.length {
+                return;
+            }
+            self.current_btree_slice = self.current_btree_iter.next();
+            self.elem_idx = 0;
+        }
+        if let Some(slice) = self.current_slice {
+            if self.elem_idx >= slice.length {
+                self.current_slice = None;
+                self.elem_idx = 0;
+            }
         }
     }
 
@@ -481,27 +626,15 @@ impl Iterator for SeriesIterator<'_> {
     type Item = Ele;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // Process BTreeMap slices first
-        while let Some((_, slice)) = self.current_btree_slice {
-            if let Some(value) = self.get_value_from_slice(slice) {
-                return Some(value);
-            }
-            // Move to next slice
-            self.current_btree_slice = self.current_btree_iter.next();
-            self.elem_idx = 0;
+        // BTreeMap slices come first, then the current slice
+        let index = self.index()?;
+        let slice = self.slice()?;
+        let value = self.get_value_from_slice(slice)?;
+        self.settle();
+        if self.nulls.contains(&index) {
+            return Some(Ele::Nil);
         }
-
-        // Then try current_slice
-        if let Some(slice) = self.current_slice {
-            if let Some(value) = self.get_value_from_slice(slice) {
-                return Some(value);
-            }
-            // Done with current_slice
-            self.current_slice = None;
-            self.elem_idx = 0;
-        }
-
-        None
+        Some(value)
     }
 }
 
@@ -523,6 +656,35 @@ mod test {
         assert!(series.dropped == 10);
     }
 
+    #[test]
+    fn test_series_element_count_window() {
+        let mut series = super::Series::builder()
+            .with_discard_strategy(crate::types::series::DiscardStrategy::BaseElementCount {
+                discard_threshold: 20,
+                chunk_size: 4,
+            })
+            .build();
+        for i in 0..1000 {
+            series.append(i as i64).unwrap();
+            assert!(series.len() - series.first_offset() < 24);
+        }
+        assert_eq!(series.dropped, series.first_offset());
+        assert_eq!(series.get(999), Some(super::Ele::I64(999)));
+        assert_eq!(series.get(series.dropped - 1), None);
+    }
+
+    #[test]
+    fn test_series_shrink_to_fit() {
+        let mut series = super::Series::builder().build();
+        for i in 0..3 {
+            series.append(i as i64).unwrap();
+        }
+        series.shrink_to_fit();
+        series.append(3i64).unwrap();
+        assert_eq!(series.len(), 4);
+        assert_eq!(series.get(3), Some(super::Ele::I64(3)));
+    }
+
     #[test]
     fn test_new_series() {
         let series = super::Series::builder().build();
@@ -569,6 +731,22 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_series_datetime_values() {
+        let mut series = super::Series::builder().build();
+        for i in 0..16u64 {
+            series
+                .append_value(super::Ele::DataTime(1_700_000_000_000_000 + i))
+                .unwrap();
+        }
+
+        assert_eq!(series.dtype(), super::EleType::DataTime);
+        assert_eq!(
+            series.get(3).unwrap(),
+            super::Ele::DataTime(1_700_000_000_000_003)
+        );
+    }
+
     #[test]
     fn test_series_get_from_compressed() {
         let mut series = super::Series::builder()
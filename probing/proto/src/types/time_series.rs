@@ -8,10 +8,16 @@ use super::{basic::EleType, series::SeriesConfig, Ele, Series};
 
 #[derive(Debug, Error)]
 pub enum TimeSeriesError {
-    #[error("column count mismatch")]
+    #[error("column count mismatch, expected {expected} got {got}")]
     ColumnCountMismatch { expected: usize, got: usize },
     #[error("column type mismatch")]
     ColumnTypeMismatch { expected: EleType, got: EleType },
+    #[error("column `{column}` holds {expected} values, got {got}")]
+    IncompatibleType {
+        column: String,
+        expected: EleType,
+        got: EleType,
+    },
     #[error("invalid timestamp type")]
     InvalidTimestampType,
     #[error("unkown error")]
@@ -56,11 +62,32 @@ impl TimeSeries {
         self.timestamp.ncounts()
     }
 
+    /// Number of the oldest rows discarded to keep within the discard strategy
+    pub fn discarded(&self) -> usize {
+        self.timestamp.first_offset()
+    }
+
+    /// Number of rows still held
+    pub fn retained(&self) -> usize {
+        self.len() - self.discarded()
+    }
+
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Release the capacity reserved for the chunks being filled
+    pub fn shrink_to_fit(&mut self) {
+        self.timestamp.shrink_to_fit();
+        self.cols.iter_mut().for_each(Series::shrink_to_fit);
+    }
+
+    /// Append a row with a value for every column, in column order.
+    ///
+    /// Values are coerced to the type of their column, see [`Ele::coerce`],
+    /// and integer columns are widened when they receive larger integers or
+    /// floats. Nothing is appended when a value does not fit its column.
     pub fn append(&mut self, timestamp: Ele, values: Vec<Ele>) -> Result<(), TimeSeriesError> {
         if self.cols.len() != values.len() {
             return Err(TimeSeriesError::ColumnCountMismatch {
@@ -68,13 +95,85 @@ impl TimeSeries {
                 got: values.len(),
             });
         }
+        let values = self.coerce(values)?;
         self.timestamp.append_value(timestamp)?;
-        for (i, item) in values.iter().enumerate().take(self.cols.len()) {
-            self.cols[i].append_value(item.clone())?;
+        for (col, value) in self.cols.iter_mut().zip(values) {
+            col.append_value(value)?;
         }
         Ok(())
     }
 
+    /// Append a row given as `(column, value)` pairs.
+    ///
+    /// Columns missing from the row get a null, unknown columns are added to
+    /// the table with nulls for the rows before. Returns the names of the
+    /// added columns.
+    pub fn append_named(
+        &mut self,
+        timestamp: Ele,
+        row: Vec<(String, Ele)>,
+    ) -> Result<Vec<String>, TimeSeriesError> {
+        let mut values = vec![Ele::Nil; self.cols.len()];
+        let mut added = vec![];
+        for (name, value) in row {
+            match self.names.iter().position(|n| *n == name) {
+                Some(idx) => values[idx] = value,
+                None if added.contains(&name) => {}
+                None => {
+                    added.push(name);
+                    values.push(value);
+                }
+            }
+        }
+        // check the row before changing the schema, new columns take any type
+        let known = values.split_off(self.cols.len());
+        let mut values = self.coerce(values)?;
+        values.extend(known);
+        for name in &added {
+            self.add_column(name);
+        }
+        self.append(timestamp, values)?;
+        Ok(added)
+    }
+
+    /// Add a column, null in the rows appended so far
+    pub fn add_column(&mut self, name: &str) {
+        let mut col = self.timestamp.empty_untyped();
+        col.append_nulls(self.timestamp.len());
+        self.names.push(name.to_string());
+        self.cols.push(col);
+    }
+
+    /// Coerce `values` to the types of the columns, widening the columns
+    /// that need it
+    fn coerce(&mut self, values: Vec<Ele>) -> Result<Vec<Ele>, TimeSeriesError> {
+        let mut widened = vec![];
+        for (idx, value) in values.iter().enumerate() {
+            let dtype = self.cols[idx].dtype();
+            if dtype == EleType::Nil || value.coerce(&dtype).is_some() {
+                continue;
+            }
+            match dtype.widen(&value.dtype()) {
+                Some(wider) => widened.push((idx, wider)),
+                None => {
+                    return Err(TimeSeriesError::IncompatibleType {
+                        column: self.names[idx].clone(),
+                        expected: dtype,
+                        got: value.dtype(),
+                    })
+                }
+            }
+        }
+        for (idx, dtype) in widened {
+            self.cols[idx] = self.cols[idx].cast(&dtype)?;
+        }
+        Ok(values
+            .into_iter()
+            .zip(&self.cols)
+            .map(|(value, col)| value.coerce(&col.dtype()).unwrap_or(value))
+            .collect())
+    }
+
     pub fn iter(&'_ self) -> TimeSeriesIter<'_> {
         TimeSeriesIter {
             timestamp: self.timestamp.iter(),
@@ -82,6 +181,32 @@ impl TimeSeries {
         }
     }
 
+    /// Keep the rows for which `keep` returns true and return the number of
+    /// rows removed.
+    ///
+    /// The series are rebuilt from the kept rows, so the memory of the removed
+    /// ones is released; rows already discarded are not counted.
+    pub fn retain<F>(&mut self, mut keep: F) -> Result<usize, TimeSeriesError>
+    where
+        F: FnMut(&Ele, &[Ele]) -> bool,
+    {
+        let mut kept = TimeSeries {
+            names: self.names.clone(),
+            timestamp: self.timestamp.empty_like(),
+            cols: self.cols.iter().map(Series::empty_like).collect(),
+        };
+        let mut removed = 0;
+        for (timestamp, values) in self.iter() {
+            if keep(&timestamp, &values) {
+                kept.append(timestamp, values)?;
+            } else {
+                removed += 1;
+            }
+        }
+        *self = kept;
+        Ok(removed)
+    }
+
     pub fn take(&self, limit: Option<usize>) -> Vec<(Ele, Vec<Ele>)> {
         let iter = self.iter();
         if let Some(limit) = limit {
@@ -143,13 +268,24 @@ pub struct TimeSeriesIter<'a> {
 impl Iterator for TimeSeriesIter<'_> {
     type Item = (Ele, Vec<Ele>);
 
+    /// Rows are matched by offset, columns added later or whose old values
+    /// were discarded give nulls for the rows they miss
     fn next(&mut self) -> Option<Self::Item> {
+        let row = self.timestamp.index()?;
         let timestamp = self.timestamp.next()?;
         let cols = self
             .cols
             .iter_mut()
-            .map(|s| s.next())
-            .collect::<Option<Vec<_>>>()?;
+            .map(|col| {
+                while col.index().is_some_and(|idx| idx < row) {
+                    col.next();
+                }
+                match col.index() {
+                    Some(idx) if idx == row => col.next().unwrap_or(Ele::Nil),
+                    _ => Ele::Nil,
+                }
+            })
+            .collect();
         Some((timestamp, cols))
     }
 }
@@ -207,6 +343,8 @@ mod test {
             );
         }
         assert_eq!(ts.cnts(), 6);
+        assert_eq!(ts.discarded(), 10);
+        assert_eq!(ts.retained(), 6);
     }
 
     #[test]
@@ -247,4 +385,81 @@ mod test {
 
         assert!(iter.next().is_none());
     }
+
+    #[test]
+    fn test_timeseries_retain() {
+        let mut ts = super::TimeSeries::builder()
+            .with_discard_strategy(DiscardStrategy::BaseElementCount {
+                discard_threshold: 100,
+                chunk_size: 4,
+            })
+            .with_columns(vec!["kind".to_string()])
+            .build();
+        for i in 0..10 {
+            let kind = if i % 2 == 0 { "even" } else { "odd" };
+            ts.append(super::Ele::I64(i), vec![kind.into()]).unwrap();
+        }
+
+        let removed = ts
+            .retain(|t, row| matches!(t, super::Ele::I64(4..)) || row[0] == "odd".into())
+            .unwrap();
+        assert_eq!(removed, 2);
+        let times = ts.iter().map(|(t, _)| t).collect::<Vec<_>>();
+        assert_eq!(times.len(), 8);
+        assert_eq!(
+            times[..3],
+            [super::Ele::I64(1), super::Ele::I64(3), super::Ele::I64(4)]
+        );
+
+        // rows appended after a prune are kept apart from the rebuilt ones
+        ts.append(super::Ele::I64(10), vec!["even".into()]).unwrap();
+        assert_eq!(ts.len(), 9);
+    }
+
+    #[test]
+    fn test_timeseries_schema_evolution() {
+        use super::{Ele, EleType, TimeSeriesError};
+
+        let mut ts = super::TimeSeries::builder()
+            .with_columns(vec!["step".to_string(), "loss".to_string()])
+            .build();
+        ts.append(Ele::I64(0), vec![Ele::I32(0), Ele::I32(2)])
+            .unwrap();
+        // a larger step widens the column, a float loss turns it into floats
+        ts.append(Ele::I64(1), vec![Ele::I64(1 << 40), Ele::F64(1.5)])
+            .unwrap();
+        assert_eq!(ts.cols[0].dtype(), EleType::I64);
+        assert_eq!(ts.cols[1].dtype(), EleType::F64);
+
+        let added = ts
+            .append_named(
+                Ele::I64(2),
+                vec![
+                    ("step".to_string(), Ele::I32(2)),
+                    ("lr".to_string(), Ele::F64(0.1)),
+                ],
+            )
+            .unwrap();
+        assert_eq!(added, vec!["lr".to_string()]);
+        assert_eq!(ts.names, vec!["step", "loss", "lr"]);
+
+        let err = ts
+            .append_named(Ele::I64(3), vec![("loss".to_string(), "high".into())])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TimeSeriesError::IncompatibleType { ref column, .. } if column == "loss"
+        ));
+        assert_eq!(err.to_string(), "column `loss` holds f64 values, got text");
+
+        let rows = ts.iter().map(|(_, row)| row).collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                vec![Ele::I64(0), Ele::F64(2.0), Ele::Nil],
+                vec![Ele::I64(1 << 40), Ele::F64(1.5), Ele::Nil],
+                vec![Ele::I64(2), Ele::Nil, Ele::F64(0.1)],
+            ]
+        );
+    }
 }
@@ -51,4 +51,22 @@ mod tests {
         assert_eq!(seq.len(), 0);
         assert!(seq.is_empty());
     }
+
+    #[test]
+    fn test_query_error_without_structured_fields() {
+        use probing_proto::prelude::{ErrorCode, QueryError};
+
+        let json = r#"{"code":"Internal","message":"boom","details":null}"#;
+        let error: QueryError = serde_json::from_str(json).unwrap();
+        assert_eq!(error.code, ErrorCode::Internal);
+        assert!(error.table.is_none() && error.hint.is_none());
+
+        let error = QueryError::new(ErrorCode::TableNotFound, "table 'foo' not found")
+            .with_position(1, 15)
+            .with_hint("did you mean 'python.foo'?");
+        assert_eq!(
+            error.to_string(),
+            "QueryError: TableNotFound - table 'foo' not found (at line 1, column 15), did you mean 'python.foo'?"
+        );
+    }
 }
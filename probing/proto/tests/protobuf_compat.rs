@@ -0,0 +1,415 @@
+//! Wire compatibility of the protobuf encoding.
+//!
+//! The golden byte strings pin the field numbers of `protobuf/probing.proto`:
+//! if one of these tests fails, the change breaks existing non-Rust clients.
+
+use std::collections::BTreeSet;
+
+use prost::Message as _;
+
+use probing_proto::prelude::*;
+use probing_proto::protobuf as pb;
+
+fn roundtrip_data(data: QueryDataFormat) -> QueryDataFormat {
+    let bytes = Message::new(data).to_protobuf();
+    Message::<QueryDataFormat>::from_protobuf(&bytes)
+        .unwrap()
+        .payload
+}
+
+#[test]
+fn test_query_message_roundtrip() {
+    let mut query = Query::new("SELECT * FROM python.trace_event".to_string());
+    query.opts = Some(QueryOptions {
+        limit: Some(100),
+        snapshot: true,
+        page_size: Some(1000),
+        cursor: Some("7f3a:1000".to_string()),
+        session: Some("analysis".to_string()),
+        columns: Some(vec!["name".to_string(), "duration".to_string()]),
+        sample_rate: Some(0.01),
+        max_cell_chars: Some(256),
+        force: true,
+    });
+    let request = Message::with_id(query, "req-1".to_string());
+
+    let decoded = Message::<Query>::from_protobuf(&request.to_protobuf()).unwrap();
+    assert_eq!(decoded.version, request.version);
+    assert_eq!(decoded.message_id.as_deref(), Some("req-1"));
+    assert_eq!(decoded.timestamp, request.timestamp);
+    assert_eq!(decoded.payload.expr, request.payload.expr);
+    assert_eq!(decoded.payload.opts, request.payload.opts);
+}
+
+#[test]
+fn test_dataframe_roundtrip_all_column_types() {
+    let df = DataFrame::new(
+        vec!["b", "i32", "i64", "f32", "f64", "text", "time", "nil"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        vec![
+            Seq::SeqBOOL(vec![true, false]),
+            Seq::SeqI32(vec![-1, 2]),
+            Seq::SeqI64(vec![i64::MIN, i64::MAX]),
+            Seq::SeqF32(vec![1.5, -0.25]),
+            Seq::SeqF64(vec![std::f64::consts::PI, 0.0]),
+            Seq::SeqText(vec!["a".to_string(), "".to_string()]),
+            Seq::SeqDateTime(vec![1_700_000_000_000_000, 0]),
+            Seq::Nil,
+        ],
+    );
+
+    match roundtrip_data(QueryDataFormat::DataFrame(df.clone())) {
+        QueryDataFormat::DataFrame(decoded) => assert_eq!(decoded, df),
+        other => panic!("unexpected payload: {other:?}"),
+    }
+}
+
+#[test]
+fn test_error_and_nil_roundtrip() {
+    let error = QueryError::new(ErrorCode::TableNotFound, "table not found")
+        .with_details("table 'probe.foo' not found")
+        .with_table("probe.foo")
+        .with_position(1, 15)
+        .with_hint("did you mean 'python.foo'?");
+
+    match roundtrip_data(QueryDataFormat::Error(error.clone())) {
+        QueryDataFormat::Error(decoded) => {
+            assert_eq!(decoded.code, error.code);
+            assert_eq!(decoded.message, error.message);
+            assert_eq!(decoded.details, error.details);
+            assert_eq!(decoded.table, error.table);
+            assert_eq!(decoded.column, None);
+            assert_eq!(decoded.position, error.position);
+            assert_eq!(decoded.hint, error.hint);
+        }
+        other => panic!("unexpected payload: {other:?}"),
+    }
+
+    assert!(matches!(
+        roundtrip_data(QueryDataFormat::Nil),
+        QueryDataFormat::Nil
+    ));
+}
+
+#[test]
+fn test_page_roundtrip() {
+    let df = DataFrame::new(vec!["a".to_string()], vec![Seq::SeqI64(vec![1, 2, 3])]);
+    let page = QueryPage {
+        df: df.slice(1, 2),
+        offset: 1,
+        total: 3,
+        next_cursor: None,
+    };
+
+    match roundtrip_data(QueryDataFormat::Page(page.clone())) {
+        QueryDataFormat::Page(decoded) => {
+            assert_eq!(decoded, page);
+            assert_eq!(decoded.df.cols[0], Seq::SeqI64(vec![2, 3]));
+        }
+        other => panic!("unexpected payload: {other:?}"),
+    }
+}
+
+#[test]
+fn test_time_series_roundtrip() {
+    let mut ts = TimeSeries::builder()
+        .with_columns(vec!["loss".to_string(), "step".to_string()])
+        .build();
+    for step in 0..10i64 {
+        ts.append(
+            Ele::I64(step * 1000),
+            vec![Ele::F64(1.0 / (step + 1) as f64), Ele::I64(step)],
+        )
+        .unwrap();
+    }
+
+    match roundtrip_data(QueryDataFormat::TimeSeries(ts.clone())) {
+        QueryDataFormat::TimeSeries(decoded) => {
+            assert_eq!(decoded.names, ts.names);
+            assert_eq!(decoded.take(None), ts.take(None));
+        }
+        other => panic!("unexpected payload: {other:?}"),
+    }
+}
+
+#[test]
+fn test_golden_query_bytes() {
+    let query = pb::Query {
+        expr: "SELECT 1".to_string(),
+        opts: Some(pb::QueryOptions {
+            limit: Some(10),
+            snapshot: true,
+            ..Default::default()
+        }),
+    };
+    let expected = [
+        &[0x0a, 0x08][..],
+        b"SELECT 1",
+        &[0x12, 0x04, 0x08, 0x0a, 0x10, 0x01],
+    ]
+    .concat();
+    assert_eq!(query.encode_to_vec(), expected);
+}
+
+#[test]
+fn test_golden_dataframe_bytes() {
+    let df: pb::DataFrame =
+        DataFrame::new(vec!["a".to_string()], vec![Seq::SeqI64(vec![1, 2])]).into();
+    let expected = [
+        0x0a, 0x01, b'a', // names
+        0x12, 0x06, 0x1a, 0x04, 0x0a, 0x02, 0x01, 0x02, // cols[0].i64, packed
+    ];
+    assert_eq!(df.encode_to_vec(), expected);
+}
+
+#[test]
+fn test_unknown_fields_are_ignored() {
+    // a newer peer added field 15 to Query
+    let mut bytes = pb::Query {
+        expr: "SELECT 1".to_string(),
+        opts: None,
+    }
+    .encode_to_vec();
+    bytes.extend_from_slice(&[0x78, 0x01]);
+
+    let query = pb::Query::decode(bytes.as_slice()).unwrap();
+    assert_eq!(query.expr, "SELECT 1");
+}
+
+#[test]
+fn test_payload_type_mismatch_is_an_error() {
+    let bytes = Message::new(Query::new("SELECT 1".to_string())).to_protobuf();
+    assert!(Message::<QueryDataFormat>::from_protobuf(&bytes).is_err());
+    assert!(Message::<Query>::from_protobuf(&[0xff, 0xff]).is_err());
+}
+
+#[test]
+fn test_schema_declares_all_messages() {
+    for name in [
+        "ProtocolVersion",
+        "Message",
+        "Ele",
+        "Seq",
+        "DataFrame",
+        "TimeSeries",
+        "QueryOptions",
+        "Query",
+        "SqlPosition",
+        "QueryError",
+        "QueryPage",
+        "QueryData",
+        "TraceAttribute",
+        "TraceRecord",
+    ] {
+        assert!(
+            pb::SCHEMA.contains(&format!("message {name} {{")),
+            "{name} missing from probing.proto"
+        );
+    }
+    assert!(pb::SCHEMA.contains("package probing.v1;"));
+}
+
+/// Message, field, wire type, label and tag of a declared field.
+type Field = (String, String, String, String, u32);
+
+const SCALARS: [&str; 8] = [
+    "bool", "int32", "int64", "uint32", "uint64", "float", "double", "string",
+];
+
+fn camel(snake: &str) -> String {
+    snake
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string() + &chars.as_str().to_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn snake(camel: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in camel.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+/// Fields and enum values declared in `probing.proto`, in prost's terms.
+fn schema_fields() -> (BTreeSet<Field>, BTreeSet<(String, String, u32)>) {
+    let source: String = pb::SCHEMA
+        .lines()
+        .map(|line| line.split("//").next().unwrap())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('{', " { ")
+        .replace('}', " } ")
+        .replace(';', " ; ");
+    let tokens: Vec<&str> = source.split_whitespace().collect();
+
+    // (kind, name) of the enclosing message, enum and oneof blocks
+    let mut scopes: Vec<(&str, &str)> = vec![];
+    let mut raw = vec![];
+    let mut values = BTreeSet::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            kind @ ("message" | "enum" | "oneof") => {
+                scopes.push((kind, tokens[i + 1]));
+                i += 3;
+                continue;
+            }
+            "}" => {
+                scopes.pop();
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let end = i + tokens[i..].iter().position(|t| *t == ";").unwrap();
+        let statement = &tokens[i..end];
+        i = end + 1;
+        match scopes.last() {
+            Some(("enum", name)) => {
+                let prefix = format!("{}_", snake(name).to_uppercase());
+                let value = statement[0].strip_prefix(&prefix).unwrap();
+                values.insert((
+                    name.to_string(),
+                    camel(value),
+                    statement[2].parse().unwrap(),
+                ));
+            }
+            Some((kind, _)) => {
+                let message = scopes
+                    .iter()
+                    .rev()
+                    .find(|(k, _)| *k == "message")
+                    .unwrap()
+                    .1;
+                let (label, rest) = match statement[0] {
+                    "optional" | "repeated" => (statement[0], &statement[1..]),
+                    _ if *kind == "oneof" => ("oneof", statement),
+                    _ => ("", statement),
+                };
+                raw.push((
+                    message,
+                    rest[1],
+                    rest[0],
+                    label,
+                    rest[3].parse::<u32>().unwrap(),
+                ));
+            }
+            None => {}
+        }
+    }
+
+    let enums: BTreeSet<_> = values.iter().map(|(name, _, _)| name.clone()).collect();
+    let fields = raw
+        .into_iter()
+        .map(|(message, field, ty, label, tag)| {
+            let ty = if SCALARS.contains(&ty) {
+                ty.to_string()
+            } else if enums.contains(ty) {
+                "enumeration".to_string()
+            } else {
+                "message".to_string()
+            };
+            // singular message fields have explicit presence
+            let label = match label {
+                "oneof" => "",
+                "" if ty == "message" => "optional",
+                label => label,
+            };
+            (
+                message.to_string(),
+                field.to_string(),
+                ty,
+                label.to_string(),
+                tag,
+            )
+        })
+        .collect();
+    (fields, values)
+}
+
+/// Fields and enum values of the hand-written bindings in `messages.rs`.
+fn binding_fields() -> (BTreeSet<Field>, BTreeSet<(String, String, u32)>) {
+    let source = include_str!("../src/protobuf/messages.rs");
+    let mut fields = BTreeSet::new();
+    let mut values = BTreeSet::new();
+    let mut owner: Option<String> = None;
+    let mut enumeration: Option<String> = None;
+    let mut attr: Option<&str> = None;
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if line == "}" {
+            (owner, enumeration) = (None, None);
+        } else if let Some(name) = line.strip_prefix("pub struct ") {
+            owner = Some(name.trim_end_matches(" {").to_string());
+        } else if let Some(name) = line.strip_prefix("pub mod ") {
+            owner = Some(camel(name.trim_end_matches(" {")));
+        } else if let Some(name) = line.strip_prefix("pub enum ") {
+            enumeration = Some(name.trim_end_matches(" {").to_string());
+        } else if let Some(args) = trimmed.strip_prefix("#[prost(") {
+            attr = Some(args.trim_end_matches(")]"));
+        } else if let (Some(name), Some((value, tag))) = (&enumeration, trimmed.split_once(" = ")) {
+            let tag = tag.trim_end_matches(',').parse().unwrap();
+            values.insert((name.clone(), value.to_string(), tag));
+        } else if let (Some(message), Some(args)) = (&owner, attr) {
+            if trimmed.starts_with("///") || args.starts_with("oneof") {
+                continue;
+            }
+            attr = None;
+            let field = match trimmed.strip_prefix("pub ") {
+                Some(field) => field.split(':').next().unwrap().to_string(),
+                None => snake(trimmed.split('(').next().unwrap()),
+            };
+            let args: Vec<&str> = args.split(", ").collect();
+            let ty = args[0].split(" = ").next().unwrap().to_string();
+            let label = match args[1] {
+                "optional" | "repeated" => args[1],
+                _ => "",
+            };
+            let tag = args
+                .last()
+                .unwrap()
+                .trim_start_matches("tag = \"")
+                .trim_end_matches('"');
+            fields.insert((
+                message.clone(),
+                field,
+                ty,
+                label.to_string(),
+                tag.parse().unwrap(),
+            ));
+        }
+    }
+    (fields, values)
+}
+
+#[test]
+fn test_bindings_match_schema() {
+    let (schema, schema_values) = schema_fields();
+    let (bindings, binding_values) = binding_fields();
+    assert!(schema.len() > 50, "parsed only {} fields", schema.len());
+    assert_eq!(
+        schema.difference(&bindings).collect::<Vec<_>>(),
+        Vec::<&Field>::new(),
+        "fields of probing.proto missing from or differing in messages.rs"
+    );
+    assert_eq!(
+        bindings.difference(&schema).collect::<Vec<_>>(),
+        Vec::<&Field>::new(),
+        "fields of messages.rs not declared in probing.proto"
+    );
+    assert_eq!(schema_values, binding_values);
+}
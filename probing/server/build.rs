@@ -0,0 +1,19 @@
+// build.rs
+use std::path::Path;
+use std::process::Command;
+
+/// Expose the commit being built as `PROBING_GIT_HASH`, served at `/apis/version`
+fn main() {
+    let head = Path::new("../../.git/HEAD");
+    if head.exists() {
+        println!("cargo:rerun-if-changed={}", head.display());
+    }
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=PROBING_GIT_HASH={hash}");
+}
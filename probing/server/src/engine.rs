@@ -1,23 +1,90 @@
 use anyhow::{self, Result};
+use once_cell::sync::Lazy;
+use probing_core::core::shape::ResultShape;
+use probing_core::latency::{self, CallKind};
 use probing_proto::prelude::*;
 
 use crate::extensions as se;
+use crate::pagination::RESULTS;
 use probing_cc::extensions as cc;
 use probing_python::extensions as py;
 
+use crate::server::deadline::Deadline;
 use crate::server::error::ApiResult;
 
 pub use probing_core::ENGINE;
 
+/// Runtime for queries against the snapshot, kept apart from the server
+/// runtime so heavy analytics do not starve request handling.
+static SNAPSHOT_RUNTIME: Lazy<tokio::runtime::Runtime> =
+    Lazy::new(|| crate::runtime::build("snapshot runtime", 2).unwrap());
+
 pub async fn initialize_engine() -> Result<()> {
     let builder = probing_core::create_engine()
         .with_extension(py::PprofExtension::default(), "pprof", None)
-        .with_extension(py::TorchExtension::default(), "torch", None)
+        .with_extension(py::TorchExtension::default(), "torch", Some("op_summary"))
+        .with_extension(py::AnomalyExtension::default(), "alerts", Some("anomalies"))
+        .with_extension(py::IngestExtension::default(), "ingest", Some("stats"))
         .with_extension(se::ServerExtension::default(), "server", None)
+        .with_extension(se::ReplExtension::default(), "repl", None)
         .with_extension(py::PythonExt::default(), "python", None)
         .with_extension(cc::ClusterExtension::default(), "cluster", Some("nodes"))
+        .with_extension(cc::ClusterExtension::default(), "cluster", Some("events"))
         .with_extension(cc::EnvExtension::default(), "process", Some("envs"))
-        .with_extension(cc::FilesExtension::default(), "files", None);
+        .with_extension(cc::CgroupExtension::default(), "cgroup", Some("stats"))
+        .with_extension(cc::EnvExtension::default(), "mpi", Some("env"))
+        .with_extension(cc::EnvExtension::default(), "slurm", Some("job"))
+        .with_extension(py::SignalsExtension::default(), "process", Some("signals"))
+        .with_extension(py::ShutdownExtension::default(), "shutdown", None)
+        .with_extension(
+            cc::LibrariesExtension::default(),
+            "process",
+            Some("libraries"),
+        )
+        .with_extension(
+            cc::LibrariesExtension::default(),
+            "process",
+            Some("library_checks"),
+        )
+        .with_extension(py::PrivacyExtension::default(), "privacy", None)
+        .with_extension(cc::FilesExtension::default(), "files", None)
+        .with_extension(cc::ExecExtension::default(), "exec", None)
+        .with_extension(cc::ViewsExtension::default(), "views", None)
+        .with_extension(cc::AgentExtension::default(), "agent", Some("errors"))
+        .with_extension(cc::AgentExtension::default(), "agent", Some("http_stats"))
+        .with_extension(cc::AgentExtension::default(), "agent", Some("slow_calls"))
+        .with_extension(cc::SloExtension::default(), "slo", Some("status"))
+        .with_extension(cc::TraceExtension::default(), "trace", Some("strings"))
+        .with_extension(py::TracerExtension::default(), "tracer", Some("calls"))
+        .with_extension(py::TracerExtension::default(), "tracer", Some("sampling"))
+        .with_extension(py::TracingExtension::default(), "tracing", None)
+        .with_plugin(probing_core::stacks::StacksPlugin::create(
+            "stacks",
+            "dictionary",
+        ))
+        .with_plugin(probing_core::catalog::ChangesPlugin::create(
+            "catalog", "changes",
+        ))
+        .with_plugin(probing_core::core::breaker::StatusPlugin::create(
+            "extensions",
+            "status",
+        ))
+        .with_union_view(
+            cc::trace::EVENTS_VIEW,
+            &["python.trace_event", "archive.trace_event"],
+        );
+
+    #[cfg(feature = "analytics")]
+    let builder = with_analytics_extensions(builder);
+
+    #[cfg(feature = "wasm")]
+    let builder = builder.with_plugin(probing_core::core::wasm::FunctionsPlugin::create(
+        "wasm",
+        "functions",
+    ));
+
+    #[cfg(any(feature = "postgres", feature = "clickhouse"))]
+    let builder = builder.with_extension(cc::FederationExtension::default(), "federation", None);
 
     #[cfg(target_os = "linux")]
     let builder = builder.with_extension(cc::RdmaExtension::default(), "taskstats", None);
@@ -28,9 +95,92 @@ pub async fn initialize_engine() -> Result<()> {
     probing_core::initialize_engine(builder).await
 }
 
-pub async fn handle_query(request: Query) -> Result<QueryDataFormat> {
-    let Query { expr, opts: _ } = request;
+/// Tables of training and inference frameworks, built with the `analytics`
+/// feature
+#[cfg(feature = "analytics")]
+fn with_analytics_extensions(
+    builder: probing_core::core::EngineBuilder,
+) -> probing_core::core::EngineBuilder {
+    builder
+        .with_extension(py::DynamoExtension::default(), "torch", Some("dynamo"))
+        .with_extension(
+            py::GradStatsExtension::default(),
+            "torch",
+            Some("grad_stats"),
+        )
+        .with_extension(py::FsdpExtension::default(), "torch", Some("fsdp_units"))
+        .with_extension(py::FsdpExtension::default(), "torch", Some("fsdp_params"))
+        .with_extension(
+            py::FsdpExtension::default(),
+            "torch",
+            Some("fsdp_collectives"),
+        )
+        .with_extension(
+            py::InferenceExtension::default(),
+            "inference",
+            Some("kv_cache"),
+        )
+        .with_extension(
+            py::InferenceExtension::default(),
+            "inference",
+            Some("requests"),
+        )
+        .with_extension(py::KinetoExtension::default(), "kineto", Some("events"))
+        .with_extension(py::OomExtension::default(), "oom", Some("reports"))
+        .with_extension(py::TasksExtension::default(), "ray", Some("tasks"))
+        .with_extension(py::TasksExtension::default(), "dask", Some("tasks"))
+}
+
+/// Run a query, cancelling it once `deadline` is over
+pub async fn handle_query(request: Query, deadline: Deadline) -> Result<QueryDataFormat> {
+    let Query { expr, opts } = request;
+    let opts = opts.unwrap_or_default();
+
+    if let Some(cursor) = &opts.cursor {
+        return Ok(QueryDataFormat::Page(
+            RESULTS.fetch(cursor, opts.page_size)?,
+        ));
+    }
 
+    let shape = ResultShape::from(&opts);
+    let endpoint = match (&opts.session, opts.snapshot) {
+        (_, true) => "snapshot",
+        (Some(_), _) => "session",
+        _ => "live",
+    };
+    if !opts.force {
+        ENGINE.read().await.guard_query(&expr, opts.limit).await?;
+    }
+    let started = std::time::Instant::now();
+    let params = expr.clone();
+    // dropping the query futures at the deadline cancels their streams
+    let reply = if opts.snapshot {
+        SNAPSHOT_RUNTIME
+            .spawn(deadline.run(snapshot_query(expr, shape)))
+            .await?
+    } else if let Some(session) = &opts.session {
+        deadline.run(session_query(session, expr, &shape)).await
+    } else {
+        deadline.run(live_query(expr, &shape)).await
+    };
+    let failed = !matches!(reply, Some(Ok(_)));
+    latency::record(CallKind::Query, endpoint, started.elapsed(), failed, || {
+        params
+    });
+    let reply = match reply {
+        Some(reply) => reply?,
+        None => return Err(deadline_exceeded(deadline).into()),
+    };
+
+    match (reply, opts.page_size) {
+        (QueryDataFormat::DataFrame(df), Some(page_size)) => {
+            Ok(QueryDataFormat::Page(RESULTS.paginate(df, page_size)))
+        }
+        (reply, _) => Ok(reply),
+    }
+}
+
+async fn live_query(expr: String, shape: &ResultShape) -> Result<QueryDataFormat> {
     // No more thread::spawn or block_on needed here.
     // We are already running within the Axum/Tokio runtime.
 
@@ -65,20 +215,126 @@ pub async fn handle_query(request: Query) -> Result<QueryDataFormat> {
     } else {
         log::debug!("Executing SELECT query: {expr}");
         // Use the fully async query method and await it
-        match engine.async_query(&expr).await {
+        match engine.shaped_query(&expr, shape).await {
             Ok(Some(dataframe)) => Ok(QueryDataFormat::DataFrame(dataframe)),
             Ok(None) => Ok(QueryDataFormat::Nil),
             Err(e) => {
                 log::error!("Error executing SELECT query '{expr}': {e}");
-                // Convert DataFusionError/EngineError into anyhow::Error
-                Err(e.into())
+                // Keep the structured error so clients can show hints
+                Err(engine.query_error(&e).into())
             }
         }
     }
 }
 
+async fn snapshot_query(expr: String, shape: ResultShape) -> Result<QueryDataFormat> {
+    let snapshot = probing_core::get_snapshot().await?;
+    log::debug!("Executing query on snapshot: {expr}");
+    match snapshot.shaped_query(&expr, &shape).await {
+        Ok(Some(dataframe)) => Ok(QueryDataFormat::DataFrame(dataframe)),
+        Ok(None) => Ok(QueryDataFormat::Nil),
+        Err(e) => {
+            log::error!("Error executing snapshot query '{expr}': {e}");
+            Err(snapshot.query_error(&e).into())
+        }
+    }
+}
+
+async fn session_query(
+    session: &str,
+    expr: String,
+    shape: &ResultShape,
+) -> Result<QueryDataFormat> {
+    let engine = ENGINE.read().await;
+    log::debug!("Executing query in session {session}: {expr}");
+    match engine.shaped_session_query(session, &expr, shape).await {
+        Ok(Some(dataframe)) => Ok(QueryDataFormat::DataFrame(dataframe)),
+        Ok(None) => Ok(QueryDataFormat::Nil),
+        Err(e) => {
+            log::error!("Error executing query '{expr}' in session {session}: {e}");
+            Err(engine.query_error(&e).into())
+        }
+    }
+}
+
+fn deadline_exceeded(deadline: Deadline) -> QueryError {
+    let timeout = deadline.timeout().unwrap_or_default();
+    QueryError::new(
+        ErrorCode::TimeoutError,
+        format!("query cancelled, no reply within {}ms", timeout.as_millis()),
+    )
+    .with_hint(format!("allow more time with the {TIMEOUT_HEADER} header"))
+}
+
+/// Close a query session and return the temporary tables dropped with it
+pub async fn close_session(
+    axum::extract::Path(session): axum::extract::Path<String>,
+) -> axum::Json<Vec<String>> {
+    axum::Json(ENGINE.read().await.close_session(&session))
+}
+
+/// Load the WebAssembly module of the request body as the SQL functions
+/// `<module>_<export>` and return their names
+#[cfg(feature = "wasm")]
+pub async fn load_wasm_module(
+    axum::extract::Path(module): axum::extract::Path<String>,
+    body: bytes::Bytes,
+) -> axum::response::Response {
+    use axum::response::IntoResponse;
+
+    let engine = ENGINE.read().await;
+    match probing_core::core::wasm::load(&engine.context, &module, &body) {
+        Ok(functions) => axum::Json(functions).into_response(),
+        Err(e) => (axum::http::StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+/// Remove the functions of a WebAssembly module
+#[cfg(feature = "wasm")]
+pub async fn unload_wasm_module(
+    axum::extract::Path(module): axum::extract::Path<String>,
+) -> axum::http::StatusCode {
+    let engine = ENGINE.read().await;
+    if probing_core::core::wasm::unload(&engine.context, &module) {
+        axum::http::StatusCode::NO_CONTENT
+    } else {
+        axum::http::StatusCode::NOT_FOUND
+    }
+}
+
+/// Refresh the read-only snapshot and return the tables it contains
+pub async fn refresh_snapshot() -> ApiResult<axum::Json<Vec<String>>> {
+    let snapshot = SNAPSHOT_RUNTIME
+        .spawn(probing_core::take_snapshot())
+        .await??;
+    Ok(axum::Json(snapshot.table_names()))
+}
+
+/// Run a query and wrap the result, or the error, into a reply message
+pub async fn execute(request: Query, deadline: Deadline) -> Message<QueryDataFormat> {
+    // Await the async handle_query function
+    let reply_payload = match handle_query(request, deadline).await {
+        Ok(reply) => reply,
+        // Error already logged in handle_query if it originated there
+        Err(err) => match err.downcast::<QueryError>() {
+            Ok(error) => QueryDataFormat::Error(error),
+            Err(err) => {
+                QueryDataFormat::Error(QueryError::new(ErrorCode::Internal, err.to_string()))
+            }
+        },
+    };
+
+    Message::new(reply_payload)
+}
+
 // 处理Web API查询请求
-pub async fn query(req: String) -> ApiResult<String> {
+pub async fn query(req: String, deadline: Deadline) -> ApiResult<String> {
+    probing_core::probe_span!("server.query", kind = "server")
+        .instrument(run_query(req, deadline))
+        .await
+}
+
+async fn run_query(req: String, deadline: Deadline) -> ApiResult<String> {
     let request = serde_json::from_str::<Message<Query>>(&req);
     let request = match request {
         Ok(request) => request.payload,
@@ -88,21 +344,7 @@ pub async fn query(req: String) -> ApiResult<String> {
         }
     };
 
-    // Await the async handle_query function
-    let reply_payload = match handle_query(request).await {
-        Ok(reply) => reply,
-        Err(err) => {
-            // Error already logged in handle_query if it originated there
-            QueryDataFormat::Error(QueryError {
-                code: ErrorCode::Internal,
-                message: err.to_string(),
-                details: None,
-            })
-        }
-    };
-
-    // Wrap the payload in a Message
-    let reply_message = Message::new(reply_payload);
+    let reply_message = execute(request, deadline).await;
 
     // Serialize the response message
     serde_json::to_string(&reply_message).map_err(|e| {
@@ -1,7 +1,13 @@
+use probing_core::core::guard::{self, GuardMode};
 use probing_core::core::{
     EngineCall, EngineDatasource, EngineError, EngineExtension, EngineExtensionOption, Maybe,
 };
 
+use crate::janitor;
+use crate::runtime;
+use crate::server::repl::{
+    CHUNK_BYTES, DEFAULT_CHUNK_BYTES, DEFAULT_MAX_OUTPUT_BYTES, MAX_OUTPUT_BYTES,
+};
 use crate::{start_remote, start_report_worker};
 
 #[derive(Debug, EngineExtension)]
@@ -36,12 +42,39 @@ pub struct ServerExtension {
     debug: Maybe<bool>,
 
     /// Log level (trace, debug, info, warn, error)
-    #[option(aliases=["loglevel"])]
+    #[option(aliases=["loglevel"], allowed = ["trace", "debug", "info", "warn", "error"])]
     log_level: Maybe<String>,
 
     /// Root path for assets used by the probing UI dashboard
     #[option(aliases=["assets.root"])]
     assets_root: Maybe<String>,
+
+    /// Worker threads of the probe's runtime, fixed once the server started
+    /// (set with PROBING_SERVER_WORKER_THREADS)
+    #[option(aliases=["worker.threads"], restart = true)]
+    worker_threads: Maybe<u64>,
+
+    /// Nice value of the probe's runtime threads, lowering it needs CAP_SYS_NICE
+    #[option()]
+    nice: Maybe<i32>,
+
+    /// Minutes without requests after which caches are dropped and free
+    /// memory is returned to the OS (0 to disable)
+    #[option(aliases=["idle.reclaim.minutes"])]
+    idle_reclaim_minutes: Maybe<u64>,
+
+    /// What happens to heavy queries: block, warn or off
+    #[option(aliases=["query.guard"], allowed = ["block", "warn", "off"])]
+    query_guard: Maybe<String>,
+
+    /// Rows from which a query counts as heavy for `query_guard`
+    #[option(aliases=["query.guard.rows"])]
+    query_guard_rows: Maybe<u64>,
+
+    /// Seconds after which `--snapshot` queries take a new snapshot of the
+    /// tables (0 to keep it until refreshed)
+    #[option(aliases=["snapshot.ttl.seconds"])]
+    snapshot_ttl_seconds: Maybe<u64>,
 }
 
 impl EngineCall for ServerExtension {}
@@ -60,6 +93,12 @@ impl Default for ServerExtension {
             debug: Maybe::Just(false),        // Debug mode off by default
             log_level: Maybe::Just("info".to_string()), // Default log level
             assets_root: Maybe::Nothing,
+            worker_threads: Maybe::Just(runtime::worker_threads() as u64),
+            nice: Maybe::Just(runtime::nice()),
+            idle_reclaim_minutes: Maybe::Just(janitor::idle_minutes()),
+            query_guard: Maybe::Just(guard::mode().as_str().to_string()),
+            query_guard_rows: Maybe::Just(guard::max_rows() as u64),
+            snapshot_ttl_seconds: Maybe::Just(probing_core::snapshot_ttl_seconds()),
         }
     }
 }
@@ -143,13 +182,127 @@ impl ServerExtension {
         self.assets_root = assets_root;
         Ok(())
     }
+
+    /// The runtime cannot be resized, only its current size is accepted
+    fn set_worker_threads(&mut self, worker_threads: Maybe<u64>) -> Result<(), EngineError> {
+        match worker_threads {
+            Maybe::Just(n) if n != runtime::worker_threads() as u64 => {
+                Err(EngineError::InvalidOptionValue(
+                    "worker_threads".to_string(),
+                    format!("{n}, set PROBING_SERVER_WORKER_THREADS before the probe starts"),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn set_nice(&mut self, nice: Maybe<i32>) -> Result<(), EngineError> {
+        let value = match nice {
+            Maybe::Just(value) => value,
+            Maybe::Nothing => runtime::DEFAULT_NICE,
+        };
+        runtime::set_nice(value).map_err(|e| {
+            EngineError::InvalidOptionValue("nice".to_string(), format!("{value}: {e}"))
+        })?;
+        self.nice = Maybe::Just(value);
+        Ok(())
+    }
+
+    fn set_idle_reclaim_minutes(&mut self, minutes: Maybe<u64>) -> Result<(), EngineError> {
+        let minutes = match minutes {
+            Maybe::Just(minutes) => minutes,
+            Maybe::Nothing => janitor::DEFAULT_IDLE_MINUTES,
+        };
+        janitor::set_idle_minutes(minutes);
+        self.idle_reclaim_minutes = Maybe::Just(minutes);
+        Ok(())
+    }
+
+    fn set_query_guard(&mut self, query_guard: Maybe<String>) -> Result<(), EngineError> {
+        let mode = match query_guard {
+            Maybe::Just(mode) => mode
+                .parse::<GuardMode>()
+                .map_err(|e| EngineError::InvalidOptionValue("query_guard".to_string(), e))?,
+            Maybe::Nothing => GuardMode::default(),
+        };
+        guard::set_mode(mode);
+        self.query_guard = Maybe::Just(mode.as_str().to_string());
+        Ok(())
+    }
+
+    fn set_query_guard_rows(&mut self, rows: Maybe<u64>) -> Result<(), EngineError> {
+        let rows = match rows {
+            Maybe::Just(rows) => rows,
+            Maybe::Nothing => guard::DEFAULT_MAX_ROWS as u64,
+        };
+        guard::set_max_rows(usize::try_from(rows).unwrap_or(usize::MAX));
+        self.query_guard_rows = Maybe::Just(rows);
+        Ok(())
+    }
+
+    fn set_snapshot_ttl_seconds(&mut self, seconds: Maybe<u64>) -> Result<(), EngineError> {
+        let seconds = match seconds {
+            Maybe::Just(seconds) => seconds,
+            Maybe::Nothing => probing_core::DEFAULT_SNAPSHOT_TTL_SECONDS,
+        };
+        probing_core::set_snapshot_ttl_seconds(seconds);
+        self.snapshot_ttl_seconds = Maybe::Just(seconds);
+        Ok(())
+    }
+}
+
+#[derive(Debug, EngineExtension)]
+pub struct ReplExtension {
+    /// Maximum output of a single REPL command in bytes, the rest is replaced
+    /// by a truncation marker (0 to disable)
+    #[option(aliases=["max.output.bytes"])]
+    max_output_bytes: Maybe<u64>,
+
+    /// Size in bytes of the frames long outputs are streamed in (0 to disable)
+    #[option(aliases=["chunk.bytes"])]
+    chunk_bytes: Maybe<u64>,
+}
+
+impl EngineCall for ReplExtension {}
+
+impl EngineDatasource for ReplExtension {}
+
+impl Default for ReplExtension {
+    fn default() -> Self {
+        Self {
+            max_output_bytes: Maybe::Just(DEFAULT_MAX_OUTPUT_BYTES as u64),
+            chunk_bytes: Maybe::Just(DEFAULT_CHUNK_BYTES as u64),
+        }
+    }
+}
+
+impl ReplExtension {
+    fn set_max_output_bytes(&mut self, max_output_bytes: Maybe<u64>) -> Result<(), EngineError> {
+        let bytes = match max_output_bytes {
+            Maybe::Just(bytes) => bytes as usize,
+            Maybe::Nothing => DEFAULT_MAX_OUTPUT_BYTES,
+        };
+        MAX_OUTPUT_BYTES.store(bytes, std::sync::atomic::Ordering::Relaxed);
+        self.max_output_bytes = Maybe::Just(bytes as u64);
+        Ok(())
+    }
+
+    fn set_chunk_bytes(&mut self, chunk_bytes: Maybe<u64>) -> Result<(), EngineError> {
+        let bytes = match chunk_bytes {
+            Maybe::Just(bytes) => bytes as usize,
+            Maybe::Nothing => DEFAULT_CHUNK_BYTES,
+        };
+        CHUNK_BYTES.store(bytes, std::sync::atomic::Ordering::Relaxed);
+        self.chunk_bytes = Maybe::Just(bytes as u64);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use probing_core::core::EngineExtension;
 
-    use crate::extensions::ServerExtension;
+    use crate::extensions::{ReplExtension, ServerExtension};
 
     #[test]
     fn test_server_extension() {
@@ -192,13 +345,30 @@ mod test {
         assert!(ext.set("report_addr", "127.0.0.1:9922").is_ok());
         assert_eq!(ext.get("report_addr").unwrap(), "127.0.0.1:9922");
 
+        // Test worker threads, fixed once the runtime is built
+        let threads = crate::runtime::worker_threads().to_string();
+        assert!(ext.set("worker_threads", &threads).is_ok());
+        assert!(ext.set("worker_threads", "1000").is_err());
+        assert!(ext.set("nice", "20").is_err());
+
+        // Test query guard, left blocking as other tests expect
+        assert!(ext.set("query.guard", "WARN").is_ok());
+        assert_eq!(ext.get("query_guard").unwrap(), "warn");
+        assert!(ext.set("query_guard", "sometimes").is_err());
+        assert!(ext.set("query_guard", "block").is_ok());
+
+        // Test snapshot TTL, restored to the default
+        assert!(ext.set("snapshot.ttl.seconds", "5").is_ok());
+        assert_eq!(probing_core::snapshot_ttl_seconds(), 5);
+        assert!(ext.set("snapshot_ttl_seconds", "60").is_ok());
+
         // Test invalid option
         assert!(ext.set("invalid.key", "value").is_err());
         assert!(ext.get("invalid.key").is_err());
 
         // Test options list
         let options = ext.options();
-        assert_eq!(options.len(), 9); // Updated count to include all options
+        assert_eq!(options.len(), 15); // Updated count to include all options
         assert!(options.iter().any(|opt| opt.key == "server.address"));
         assert!(options.iter().any(|opt| opt.key == "server.unix_socket"));
         assert!(options.iter().any(|opt| opt.key == "server.report_addr"));
@@ -210,4 +380,22 @@ mod test {
         assert!(options.iter().any(|opt| opt.key == "server.debug"));
         assert!(options.iter().any(|opt| opt.key == "server.log_level"));
     }
+
+    #[test]
+    fn test_repl_extension() {
+        let mut ext = ReplExtension::default();
+        assert_eq!(ext.get("max_output_bytes").unwrap(), "1048576");
+
+        assert!(ext.set("max.output.bytes", "4096").is_ok());
+        assert_eq!(ext.get("max_output_bytes").unwrap(), "4096");
+        assert!(ext.set("chunk_bytes", "0").is_ok());
+        assert_eq!(ext.get("chunk_bytes").unwrap(), "0");
+        // an empty value restores the default
+        assert!(ext.set("max_output_bytes", "").is_ok());
+        assert_eq!(ext.get("max_output_bytes").unwrap(), "1048576");
+
+        let options = ext.options();
+        assert!(options.iter().any(|opt| opt.key == "repl.max_output_bytes"));
+        assert!(options.iter().any(|opt| opt.key == "repl.chunk_bytes"));
+    }
 }
@@ -0,0 +1,151 @@
+//! Memory reclamation of an idle probe.
+//!
+//! Long-lived processes are probed rarely, yet the probe keeps what its last
+//! requests left behind: paginated results, the snapshot, the capacity its
+//! ingestion buffers reserved and the free memory the allocators hold on to.
+//! Once no request came in for `server.idle_reclaim_minutes`, the janitor
+//! drops the caches, shrinks the buffers and returns free memory to the OS,
+//! once per idle period; `0` disables it. Allocators other than the system
+//! one, e.g. mimalloc, register their purge with [`on_reclaim`].
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use once_cell::sync::Lazy;
+
+use crate::pagination::RESULTS;
+use crate::server::SERVER_RUNTIME;
+
+/// Default of `server.idle_reclaim_minutes`
+pub const DEFAULT_IDLE_MINUTES: u64 = 10;
+
+/// Interval between two checks of the idle time
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+static IDLE_MINUTES: AtomicU64 = AtomicU64::new(DEFAULT_IDLE_MINUTES);
+
+static STARTED: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Milliseconds after [`STARTED`] of the last request
+static LAST_REQUEST_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Whether memory was reclaimed since the last request
+static RECLAIMED: AtomicBool = AtomicBool::new(false);
+
+static RELEASERS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+/// What a reclamation released
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reclaimed {
+    /// Paginated results dropped
+    pub results: usize,
+    /// Whether the snapshot was dropped
+    pub snapshot: bool,
+    /// Recorders and external tables shrunk
+    pub buffers: usize,
+}
+
+pub fn idle_minutes() -> u64 {
+    IDLE_MINUTES.load(Ordering::Relaxed)
+}
+
+/// Reclaim memory after `minutes` without requests, never if `0`
+pub fn set_idle_minutes(minutes: u64) {
+    IDLE_MINUTES.store(minutes, Ordering::Relaxed);
+}
+
+/// Run `release` after the caches are dropped, to return the memory of an
+/// allocator to the OS
+pub fn on_reclaim(release: fn()) {
+    RELEASERS.lock().unwrap().push(release);
+}
+
+/// Note a request, the probe is no longer idle
+pub fn touch() {
+    LAST_REQUEST_MS.store(STARTED.elapsed().as_millis() as u64, Ordering::Relaxed);
+    RECLAIMED.store(false, Ordering::Relaxed);
+}
+
+/// Time since the last request, or since the probe started
+pub fn idle_for() -> Duration {
+    STARTED.elapsed().saturating_sub(Duration::from_millis(
+        LAST_REQUEST_MS.load(Ordering::Relaxed),
+    ))
+}
+
+/// Middleware noting every request, see [`touch`]
+pub async fn activity_middleware(request: Request, next: Next) -> Response {
+    touch();
+    next.run(request).await
+}
+
+/// Drop the caches, shrink the ingestion buffers and return free memory to
+/// the OS
+pub async fn reclaim() -> Reclaimed {
+    let results = RESULTS.clear();
+    let snapshot = probing_core::SNAPSHOT.write().await.take().is_some();
+    let buffers = probing_core::recorder::TimeSeriesRecorder::shrink_all()
+        + probing_python::extensions::python::shrink_tables();
+
+    let releasers = RELEASERS.lock().unwrap().clone();
+    releasers.iter().for_each(|release| release());
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    unsafe {
+        libc::malloc_trim(0);
+    }
+
+    Reclaimed {
+        results,
+        snapshot,
+        buffers,
+    }
+}
+
+/// Start checking the idle time in the background
+pub(crate) fn start() {
+    Lazy::force(&STARTED);
+    SERVER_RUNTIME.spawn(async {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let minutes = idle_minutes();
+            if minutes == 0
+                || RECLAIMED.load(Ordering::Relaxed)
+                || idle_for() < Duration::from_secs(minutes * 60)
+            {
+                continue;
+            }
+            RECLAIMED.store(true, Ordering::Relaxed);
+            let reclaimed = reclaim().await;
+            log::info!("idle for {minutes} minutes, reclaimed memory: {reclaimed:?}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reclaim_drops_results() {
+        let df = probing_proto::prelude::DataFrame::new(
+            vec!["n".to_string()],
+            vec![probing_proto::prelude::Seq::SeqI64((0..10).collect())],
+        );
+        assert!(RESULTS.paginate(df, 4).next_cursor.is_some());
+
+        // other tests may paginate concurrently
+        assert!(reclaim().await.results >= 1);
+    }
+
+    #[test]
+    fn test_touch_resets_idle_time() {
+        touch();
+        assert!(idle_for() < Duration::from_secs(1));
+        assert!(!RECLAIMED.load(Ordering::Relaxed));
+    }
+}
@@ -3,7 +3,11 @@ mod asset;
 pub mod auth;
 mod engine;
 mod extensions;
+pub mod janitor;
+mod pagination;
+mod registry;
 mod report;
+mod runtime;
 // Make server module public for integration tests in tests/ directory
 pub mod server;
 mod vars;
@@ -22,6 +26,8 @@ pub fn cleanup() -> anyhow::Result<()> {
     if path.exists() {
         std::fs::remove_file(path)?;
     }
+    report::deregister();
+    registry::unregister()?;
 
     Ok(())
 }
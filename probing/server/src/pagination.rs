@@ -0,0 +1,199 @@
+//! Server side cache behind paginated query results.
+//!
+//! A query run with `page_size` returns its first page right away; the full
+//! result stays here and the following pages are cut from it on request, so
+//! a large result is neither recomputed nor sent in one response. Cursors are
+//! `<result id>:<offset>` and stop working once the result has not been read
+//! for [`RESULT_TTL`].
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use probing_proto::prelude::*;
+
+/// How long a result is kept after its last page was read
+pub const RESULT_TTL: Duration = Duration::from_secs(300);
+
+/// Number of results kept at once, the one expiring first is dropped beyond it
+const MAX_RESULTS: usize = 16;
+
+pub static RESULTS: Lazy<ResultCache> = Lazy::new(|| ResultCache::new(RESULT_TTL));
+
+struct Entry {
+    df: Arc<DataFrame>,
+    page_size: usize,
+    expires: Instant,
+}
+
+pub struct ResultCache {
+    ttl: Duration,
+    next_id: AtomicU64,
+    entries: Mutex<HashMap<u64, Entry>>,
+}
+
+impl ResultCache {
+    pub fn new(ttl: Duration) -> Self {
+        // cursors of a restarted agent should not point into the new cache
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            ttl,
+            next_id: AtomicU64::new(seed << 16),
+            entries: Default::default(),
+        }
+    }
+
+    /// First page of `df`, keeping the result for the next pages if it does
+    /// not fit in one
+    pub fn paginate(&self, df: DataFrame, page_size: usize) -> QueryPage {
+        let page_size = page_size.max(1);
+        let total = df.len();
+        if total <= page_size {
+            return QueryPage {
+                df,
+                offset: 0,
+                total,
+                next_cursor: None,
+            };
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let df = Arc::new(df);
+        let page = page_of(id, &df, 0, page_size);
+
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, entry| entry.expires > now);
+        if entries.len() >= MAX_RESULTS {
+            if let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires)
+                .map(|(id, _)| *id)
+            {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            id,
+            Entry {
+                df,
+                page_size,
+                expires: now + self.ttl,
+            },
+        );
+        page
+    }
+
+    /// The page `cursor` points to, `page_size` overrides the size the
+    /// result was first requested with. Fails with a [`QueryError`].
+    pub fn fetch(&self, cursor: &str, page_size: Option<usize>) -> Result<QueryPage> {
+        let (id, offset) = parse_cursor(cursor).ok_or_else(|| {
+            QueryError::new(ErrorCode::ParseError, format!("invalid cursor: {cursor}"))
+        })?;
+
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, entry| entry.expires > now);
+        let Some(entry) = entries.get_mut(&id) else {
+            return Err(QueryError::new(
+                ErrorCode::NotFound,
+                format!("cursor {cursor} has expired or is unknown"),
+            )
+            .with_hint("run the query again to get a new cursor")
+            .into());
+        };
+
+        let page_size = page_size.unwrap_or(entry.page_size).max(1);
+        let page = page_of(id, &entry.df, offset, page_size);
+        if page.next_cursor.is_none() {
+            // the last page has been read
+            entries.remove(&id);
+        } else {
+            entry.expires = now + self.ttl;
+        }
+        Ok(page)
+    }
+
+    /// Drop every result, their cursors stop working; returns how many
+    /// were held
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let n = entries.len();
+        entries.clear();
+        n
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+fn page_of(id: u64, df: &DataFrame, offset: usize, page_size: usize) -> QueryPage {
+    let total = df.len();
+    let end = offset.saturating_add(page_size);
+    QueryPage {
+        df: df.slice(offset, page_size),
+        offset,
+        total,
+        next_cursor: (end < total).then(|| format!("{id:x}:{end}")),
+    }
+}
+
+fn parse_cursor(cursor: &str) -> Option<(u64, usize)> {
+    let (id, offset) = cursor.split_once(':')?;
+    Some((u64::from_str_radix(id, 16).ok()?, offset.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(rows: i64) -> DataFrame {
+        DataFrame::new(
+            vec!["n".to_string()],
+            vec![Seq::SeqI64((0..rows).collect())],
+        )
+    }
+
+    #[test]
+    fn test_pages_cover_the_result() {
+        let cache = ResultCache::new(RESULT_TTL);
+        assert!(cache.paginate(frame(3), 5).next_cursor.is_none());
+        assert_eq!(cache.len(), 0);
+
+        let mut page = cache.paginate(frame(10), 4);
+        let mut rows = vec![];
+        loop {
+            assert_eq!(page.total, 10);
+            assert_eq!(page.offset, rows.len());
+            rows.extend(page.df.iter().map(|row| row[0].clone()));
+            match page.next_cursor {
+                Some(cursor) => page = cache.fetch(&cursor, None).unwrap(),
+                None => break,
+            }
+        }
+        assert_eq!(rows, (0..10).map(Ele::I64).collect::<Vec<_>>());
+        // released after the last page
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_invalid_and_expired_cursors() {
+        let cache = ResultCache::new(Duration::ZERO);
+        let cursor = cache.paginate(frame(10), 4).next_cursor.unwrap();
+
+        let code = |cursor: &str| {
+            let err = cache.fetch(cursor, None).unwrap_err();
+            err.downcast::<QueryError>().unwrap().code
+        };
+        assert_eq!(code(&cursor), ErrorCode::NotFound);
+        assert_eq!(code("not-a-cursor"), ErrorCode::ParseError);
+    }
+}
@@ -0,0 +1,46 @@
+//! Discovery file of this probe, see `probing_proto::protocol::registry`.
+
+use std::sync::{LazyLock, Mutex};
+
+use probing_core::core::clock;
+use probing_proto::prelude::Registration;
+use probing_proto::protocol::registry::{registry_dir, token_hint};
+
+static REGISTRATION: LazyLock<Mutex<Registration>> = LazyLock::new(|| {
+    let pid = std::process::id() as i32;
+    Mutex::new(Registration {
+        pid,
+        socket: format!("@probing-{pid}"),
+        started: clock::now_micros(),
+        ..Default::default()
+    })
+});
+
+fn update(f: impl FnOnce(&mut Registration)) {
+    let mut registration = REGISTRATION.lock().unwrap();
+    f(&mut registration);
+    if let Err(e) = registration.write(&registry_dir()) {
+        log::warn!(
+            "failed to write discovery file to {:?}: {e}",
+            registry_dir()
+        );
+    }
+}
+
+/// Announce the unix socket `socket` of this probe
+pub fn register(socket: &str) {
+    update(|r| r.socket = socket.replace('\0', "@"));
+}
+
+/// Announce the TCP server of this probe
+pub fn register_address(address: &str, token: Option<&str>) {
+    update(|r| {
+        r.address = Some(address.to_string());
+        r.token_hint = token.filter(|t| !t.is_empty()).map(token_hint);
+    });
+}
+
+/// Remove the discovery file of this probe
+pub fn unregister() -> std::io::Result<()> {
+    Registration::remove(&registry_dir(), std::process::id() as i32)
+}
@@ -1,11 +1,20 @@
+use std::sync::Mutex;
 use std::time::Duration;
 
 use anyhow::Result;
 
 use super::vars::PROBING_ADDRESS;
 use crate::server::SERVER_RUNTIME;
+use probing_core::core::clock::{self, ClockSample};
+use probing_core::core::job;
+use probing_core::core::mpi;
+use probing_core::core::slurm;
 use probing_proto::prelude::Node;
 
+/// Registry this worker reported to and the key it reported under, removed
+/// by [`deregister`] when the process exits
+static REGISTRATION: Mutex<Option<(String, String, String)>> = Mutex::new(None);
+
 pub fn get_hostname() -> Result<String> {
     let uname = nix::sys::utsname::uname()?;
     let hostname = uname.nodename().to_string_lossy().to_string();
@@ -18,11 +27,16 @@ pub fn start_report_worker(report_addr: String, local_addr: String) {
 }
 
 async fn report_worker(report_addr: String, local_addr: String) {
+    if mpi::rank() == Some(0) {
+        expect_slurm_nodes().await;
+    }
+
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(10));
 
     loop {
         interval.tick().await;
 
+        let clock_addr = format!("http://{report_addr}/apis/clock");
         let report_addr = format!("http://{report_addr}/apis/nodes");
         let hostname = get_hostname().unwrap_or("localhost".to_string());
         let address = {
@@ -39,9 +53,9 @@ async fn report_worker(report_addr: String, local_addr: String) {
         let node = Node {
             host: hostname,
             addr: address,
-            local_rank: get_i32_env("LOCAL_RANK"),
-            rank: get_i32_env("RANK"),
-            world_size: get_i32_env("WORLD_SIZE"),
+            local_rank: mpi::local_rank(),
+            rank: mpi::rank(),
+            world_size: mpi::world_size(),
             group_rank: get_i32_env("GROUP_RANK"),
             group_world_size: get_i32_env("GROUP_WORLD_SIZE"),
             role_name: std::env::var("ROLE_NAME").ok(),
@@ -49,16 +63,29 @@ async fn report_worker(report_addr: String, local_addr: String) {
             role_world_size: get_i32_env("ROLE_WORLD_SIZE"),
             status: Some("running".to_string()),
             timestamp: 0,
+            clock_offset: None,
+            job_id: job::job_id(),
         };
 
         log::debug!("reporting node status to {report_addr}: {node:?}");
         if node.rank == Some(0) {
-            probing_core::core::cluster::update_node(node);
+            probing_core::core::cluster::update_node(Node {
+                clock_offset: Some(0),
+                ..node
+            });
         } else {
+            let node = Node {
+                clock_offset: sync_clock(&clock_addr).await,
+                ..node
+            };
             let node_display = format!("{node}");
+            let key = (report_addr.clone(), node.host.clone(), node.addr.clone());
             match request_remote(&report_addr, node).await {
                 Ok(reply) => {
                     log::debug!("node status reported to {report_addr}: {reply:?}");
+                    if let Ok(mut registration) = REGISTRATION.lock() {
+                        *registration = Some(key);
+                    }
                 }
                 Err(err) => {
                     log::error!("failed to report {node_display} to {report_addr}, {err}");
@@ -68,6 +95,61 @@ async fn report_worker(report_addr: String, local_addr: String) {
     }
 }
 
+/// Remove this worker from the registry it reported to, so the master does
+/// not wait for its lease to expire. Blocking, called when the process exits.
+pub fn deregister() {
+    let Some((url, host, addr)) = REGISTRATION.lock().ok().and_then(|mut r| r.take()) else {
+        return;
+    };
+    let reply = ureq::delete(&url)
+        .query("host", &host)
+        .query("addr", &addr)
+        .config()
+        .no_delay(true)
+        .timeout_global(Some(Duration::from_millis(100)))
+        .build()
+        .call();
+    match reply {
+        Ok(_) => log::debug!("node {host}:{addr} deregistered from {url}"),
+        Err(err) => log::debug!("failed to deregister node {host}:{addr} from {url}, {err}"),
+    }
+}
+
+/// Record the nodes of the Slurm job as expected on the master, so those
+/// whose ranks never report show up in `cluster.nodes`
+async fn expect_slurm_nodes() {
+    let Some(slurm_job) = slurm::job() else {
+        return;
+    };
+    // `scontrol` is a blocking subprocess
+    let expand = move || slurm::expected_nodes(&slurm_job, job::job_id());
+    match tokio::task::spawn_blocking(expand).await {
+        Ok(nodes) => {
+            log::debug!("expecting {} nodes of the Slurm job", nodes.len());
+            probing_core::core::cluster::expect_nodes(nodes);
+        }
+        Err(err) => log::warn!("failed to expand the Slurm nodelist: {err}"),
+    }
+}
+
+/// Probe the master clock and return the updated offset estimate
+async fn sync_clock(url: &str) -> Option<i64> {
+    let t0 = clock::now_micros();
+    let master = match request_clock(url).await {
+        Ok(master) => master,
+        Err(err) => {
+            log::debug!("failed to probe master clock at {url}, {err}");
+            return clock::local_offset();
+        }
+    };
+    let t3 = clock::now_micros();
+
+    let sample = ClockSample::estimate(t0, master, t3);
+    let offset = clock::record_sample(sample);
+    log::debug!("clock sample from {url}: {sample:?}, offset estimate {offset}us");
+    Some(offset)
+}
+
 fn get_i32_env(name: &str) -> Option<i32> {
     std::env::var(name).unwrap_or_default().parse().ok()
 }
@@ -82,3 +164,14 @@ async fn request_remote(url: &str, node: Node) -> Result<String> {
         .body_mut()
         .read_to_string()?)
 }
+
+async fn request_clock(url: &str) -> Result<u64> {
+    Ok(ureq::get(url)
+        .config()
+        .no_delay(true)
+        .timeout_global(Some(Duration::from_millis(100)))
+        .build()
+        .call()?
+        .body_mut()
+        .read_json()?)
+}
@@ -0,0 +1,159 @@
+//! Tokio runtimes of the probe.
+//!
+//! The probe shares the machine with the training loop, so its runtimes
+//! run on a few threads at a lowered priority: `server.worker_threads`
+//! workers, by default a quarter of the CPUs available to the process (as
+//! limited by affinity and cgroup quotas) and at most
+//! [`MAX_DEFAULT_WORKER_THREADS`], and every thread niced to `server.nice`.
+//! Both are read from `PROBING_SERVER_WORKER_THREADS` and
+//! `PROBING_SERVER_NICE` when the first runtime starts; the nice value can be
+//! changed later on.
+
+use std::io;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use tokio::runtime::Runtime;
+
+/// Default of `server.nice`
+pub const DEFAULT_NICE: i32 = 10;
+
+/// Upper bound of the default `server.worker_threads`
+pub const MAX_DEFAULT_WORKER_THREADS: usize = 4;
+
+static WORKER_THREADS: Lazy<usize> = Lazy::new(|| {
+    match env_or("PROBING_SERVER_WORKER_THREADS", 0) {
+        n if n > 0 => n as usize,
+        _ => {
+            // affinity and cgroup quotas are taken into account
+            let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+            default_worker_threads(cpus)
+        }
+    }
+});
+
+static NICE: Lazy<AtomicI32> =
+    Lazy::new(|| AtomicI32::new(env_or("PROBING_SERVER_NICE", DEFAULT_NICE as i64) as i32));
+
+/// Kernel thread ids of the runtime threads alive
+static THREADS: Lazy<Mutex<Vec<i64>>> = Lazy::new(Default::default);
+
+fn env_or(name: &str, default: i64) -> i64 {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            log::warn!("invalid {name}={value}, using {default}");
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Workers for `cpus` available CPUs, a quarter of them between 1 and
+/// [`MAX_DEFAULT_WORKER_THREADS`]
+pub fn default_worker_threads(cpus: usize) -> usize {
+    (cpus / 4).clamp(1, MAX_DEFAULT_WORKER_THREADS)
+}
+
+/// Worker threads of the server runtime
+pub fn worker_threads() -> usize {
+    *WORKER_THREADS
+}
+
+/// Nice value of the runtime threads
+pub fn nice() -> i32 {
+    NICE.load(Ordering::Relaxed)
+}
+
+/// Renice the runtime threads; raising their priority back usually needs
+/// `CAP_SYS_NICE`
+pub fn set_nice(nice: i32) -> io::Result<()> {
+    if !(-20..=19).contains(&nice) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("nice must be between -20 and 19: {nice}"),
+        ));
+    }
+    for &tid in THREADS.lock().unwrap().iter() {
+        renice(tid, nice)?;
+    }
+    NICE.store(nice, Ordering::Relaxed);
+    Ok(())
+}
+
+/// A multi-threaded runtime whose threads are niced to [`nice`]
+pub fn build(name: &str, worker_threads: usize) -> io::Result<Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .worker_threads(worker_threads.max(1))
+        .thread_name(name)
+        .on_thread_start(|| {
+            let tid = current_tid();
+            log::debug!("start runtime thread {tid}");
+            if let Err(e) = renice(tid, nice()) {
+                log::warn!("failed to renice runtime thread {tid}: {e}");
+            }
+            THREADS.lock().unwrap().push(tid);
+        })
+        .on_thread_stop(|| {
+            let tid = current_tid();
+            THREADS.lock().unwrap().retain(|&t| t != tid);
+        })
+        .build()
+}
+
+#[cfg(target_os = "linux")]
+fn current_tid() -> i64 {
+    unsafe { libc::syscall(libc::SYS_gettid) }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_tid() -> i64 {
+    0
+}
+
+/// On Linux the nice value is per thread, `PRIO_PROCESS` with a thread id
+/// only changes that thread
+#[cfg(target_os = "linux")]
+fn renice(tid: i64, nice: i32) -> io::Result<()> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn renice(_tid: i64, _nice: i32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_worker_threads() {
+        assert_eq!(default_worker_threads(1), 1);
+        assert_eq!(default_worker_threads(8), 2);
+        assert_eq!(default_worker_threads(128), MAX_DEFAULT_WORKER_THREADS);
+    }
+
+    #[test]
+    fn test_set_nice_range() {
+        assert!(set_nice(20).is_err());
+        assert!(set_nice(-21).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_runtime_threads_are_niced() {
+        let runtime = build("test runtime", 1).unwrap();
+        let value = runtime.block_on(async {
+            tokio::spawn(async { unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) } })
+                .await
+                .unwrap()
+        });
+        // the test may already run at a lower priority, which is kept
+        assert!(value >= nice());
+    }
+}
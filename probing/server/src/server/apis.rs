@@ -1,14 +1,78 @@
-use axum::{routing::get, Router};
+use axum::{
+    routing::{delete, get, post},
+    Router,
+};
 
-use super::{cluster, extension_handler, file_api, profiling, system};
+#[cfg(feature = "analytics")]
+use super::html_report;
+use super::{cluster, extension_handler, file_api, profiling, schedule, system, waterfall};
 
 /// Main router for all API endpoints
 pub fn apis_route() -> Router {
-    Router::new()
+    let router = Router::new()
         .route("/overview", get(system::get_overview_json))
+        .route(
+            "/capabilities",
+            get(|| async { axum::Json(system::get_capabilities()) }),
+        )
+        .route(
+            "/version",
+            get(|| async { axum::Json(system::get_build_info()) }),
+        )
         .route("/files", get(file_api::read_file))
-        .route("/nodes", get(cluster::get_nodes).put(cluster::put_node))
+        .route("/files/download", get(file_api::download_file))
+        .route(
+            "/nodes",
+            get(cluster::get_nodes)
+                .put(cluster::put_node)
+                .delete(cluster::delete_node),
+        )
+        .route("/jobs", get(cluster::get_jobs))
+        .route("/jobs/{job_id}/nodes", get(cluster::get_job_nodes))
+        .route("/clock", get(cluster::get_clock))
+        .route(
+            "/config",
+            get(|| async { axum::Json(probing_core::config::dump().await) }),
+        )
+        .route(
+            "/config/schema",
+            get(|| async { axum::Json(probing_core::core::schema::all()) }),
+        )
+        .route(
+            "/config/schedule",
+            get(schedule::get_schedule).post(schedule::post_schedule),
+        )
+        .route(
+            "/config/schedule/{id}/cancel",
+            post(schedule::cancel_schedule),
+        )
+        .route("/snapshot", post(crate::engine::refresh_snapshot))
+        .route("/sessions/{session}", delete(crate::engine::close_session))
         .route("/flamegraph/torch", get(profiling::get_torch_flamegraph))
         .route("/flamegraph/pprof", get(profiling::get_pprof_flamegraph))
-        .fallback(extension_handler::handle_extension_call)
+        .route(
+            "/flamegraph/{profiler}/folded",
+            get(profiling::get_flamegraph_folded),
+        )
+        .route(
+            "/flamegraph/{profiler}/tree",
+            get(profiling::get_flamegraph_tree),
+        )
+        .route(
+            "/flamegraph/{profiler}/search",
+            get(profiling::search_flamegraph),
+        )
+        .route("/waterfall", get(waterfall::get_waterfall));
+
+    #[cfg(feature = "analytics")]
+    let router = router.route("/report", get(html_report::get_report));
+
+    #[cfg(feature = "wasm")]
+    let router = router.route(
+        "/wasm/{module}",
+        axum::routing::put(crate::engine::load_wasm_module)
+            .delete(crate::engine::unload_wasm_module),
+    );
+
+    router.fallback(extension_handler::handle_extension_call)
 }
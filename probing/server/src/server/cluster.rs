@@ -1,5 +1,8 @@
-use probing_core::core::cluster::{get_nodes as core_get_nodes, update_node};
+use axum::extract::{Path, Query};
+use probing_core::core::clock;
+use probing_core::core::cluster::{self as core_cluster, get_nodes as core_get_nodes, update_node};
 use probing_proto::prelude::*;
+use serde::Deserialize;
 
 use super::error::ApiResult;
 
@@ -9,7 +12,34 @@ pub async fn put_node(axum::Json(node): axum::Json<Node>) -> ApiResult<()> {
     Ok(())
 }
 
+#[derive(Debug, Deserialize)]
+pub struct NodeKey {
+    host: String,
+    addr: String,
+}
+
+/// Remove a node from the cluster, sent by workers when they exit
+pub async fn delete_node(Query(key): Query<NodeKey>) -> ApiResult<()> {
+    core_cluster::remove_node(&key.host, &key.addr);
+    Ok(())
+}
+
 /// Get all nodes in the cluster as JSON
 pub async fn get_nodes() -> ApiResult<axum::Json<Vec<Node>>> {
     Ok(axum::Json(core_get_nodes()))
 }
+
+/// Jobs reported by the nodes, with per-job aggregates
+pub async fn get_jobs() -> ApiResult<axum::Json<Vec<Job>>> {
+    Ok(axum::Json(core_cluster::get_jobs()))
+}
+
+/// Nodes of one job, empty if the job is unknown
+pub async fn get_job_nodes(Path(job_id): Path<String>) -> ApiResult<axum::Json<Vec<Node>>> {
+    Ok(axum::Json(core_cluster::get_job_nodes(&job_id)))
+}
+
+/// Current master time in microseconds, probed by workers for clock sync
+pub async fn get_clock() -> ApiResult<axum::Json<u64>> {
+    Ok(axum::Json(clock::now_micros()))
+}
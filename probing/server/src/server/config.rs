@@ -4,11 +4,9 @@ pub const MAX_REQUEST_BODY_SIZE: usize = 5 * 1024 * 1024;
 /// Maximum file size allowed for file API reading (10MB)
 pub const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
 
-/// Allowed base directories for file access
-pub const ALLOWED_FILE_DIRS: &[&str] = &[
-    "./logs", "./data", "./config",
-    // Add more allowed directories as needed
-];
+/// Allowed base directories for file access, set at runtime with
+/// `files.allowed_dirs`
+pub use probing_cc::extensions::files::allowed_dirs as allowed_file_dirs;
 
 /// Get maximum request body size from environment or use default
 pub fn get_max_request_body_size() -> usize {
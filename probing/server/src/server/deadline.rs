@@ -0,0 +1,90 @@
+//! Deadlines of requests.
+//!
+//! Clients send the milliseconds they still wait for a reply in the
+//! [`TIMEOUT_HEADER`]. Queries and extension calls run until then: their
+//! futures are dropped once the deadline is over, which cancels the
+//! DataFusion tasks behind them, and the client gets a timeout error
+//! instead of a reply nobody reads. Extension calls block the thread they
+//! run on and cannot be stopped, they are left to finish in the background.
+
+use std::future::Future;
+use std::time::Duration;
+
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, StatusCode};
+use probing_proto::prelude::TIMEOUT_HEADER;
+use tokio::time::Instant;
+
+/// When the client of a request stops waiting, if it does
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Deadline {
+    at: Option<Instant>,
+    timeout: Duration,
+}
+
+impl Deadline {
+    /// Deadline `timeout` from now
+    pub fn after(timeout: Duration) -> Self {
+        Deadline {
+            at: Some(Instant::now() + timeout),
+            timeout,
+        }
+    }
+
+    /// Deadline of the [`TIMEOUT_HEADER`] of a request, none without it
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, String> {
+        let Some(value) = headers.get(TIMEOUT_HEADER) else {
+            return Ok(Deadline::default());
+        };
+        value
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|ms| Deadline::after(Duration::from_millis(ms)))
+            .ok_or_else(|| format!("invalid {TIMEOUT_HEADER}: expected milliseconds"))
+    }
+
+    /// Timeout the deadline was set with
+    pub fn timeout(&self) -> Option<Duration> {
+        self.at.map(|_| self.timeout)
+    }
+
+    /// Run `fut` until the deadline, `None` if it is over first
+    pub async fn run<F: Future>(self, fut: F) -> Option<F::Output> {
+        match self.at {
+            Some(at) => tokio::time::timeout_at(at, fut).await.ok(),
+            None => Some(fut.await),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Deadline {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Deadline::from_headers(&parts.headers).map_err(|e| (StatusCode::BAD_REQUEST, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(Deadline::from_headers(&headers).unwrap().timeout(), None);
+        assert_eq!(Deadline::default().run(async { 1 }).await, Some(1));
+
+        headers.insert(TIMEOUT_HEADER, "50".parse().unwrap());
+        let deadline = Deadline::from_headers(&headers).unwrap();
+        assert_eq!(deadline.timeout(), Some(Duration::from_millis(50)));
+        assert_eq!(deadline.run(async { 1 }).await, Some(1));
+        let slow = tokio::time::sleep(Duration::from_secs(10));
+        assert_eq!(deadline.run(slow).await, None);
+
+        headers.insert(TIMEOUT_HEADER, "soon".parse().unwrap());
+        assert!(Deadline::from_headers(&headers).is_err());
+    }
+}
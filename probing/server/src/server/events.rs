@@ -0,0 +1,47 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
+use tokio::sync::broadcast::error::RecvError;
+
+use probing_core::journal;
+use probing_proto::prelude::*;
+
+/// Interval between SSE keep-alive comments
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Stream agent notifications as server-sent events
+///
+/// Each event is sent with the event kind as the SSE `event` name and a
+/// `Message<AgentEvent>` JSON document as `data`. Subscribers that fall
+/// behind receive a `lagged` event telling how many notifications were lost.
+pub async fn events_handler() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = probing_core::events::subscribe();
+
+    let stream = stream::unfold(rx, |mut rx| async move {
+        let event = match rx.recv().await {
+            Ok(event) => to_sse_event(event),
+            Err(RecvError::Lagged(skipped)) => {
+                log::warn!("event subscriber lagged, {skipped} events dropped");
+                journal::dropped("events", skipped, "event subscriber lagged");
+                Event::default().event("lagged").data(skipped.to_string())
+            }
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok(event), rx))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL))
+}
+
+fn to_sse_event(event: AgentEvent) -> Event {
+    let name = event.kind.as_str();
+    match serde_json::to_string(&Message::new(event.clone())) {
+        Ok(data) => Event::default().event(name).data(data),
+        Err(err) => {
+            log::error!("Failed to serialize agent event {event}: {err}");
+            Event::default().event(name).data(event.to_string())
+        }
+    }
+}
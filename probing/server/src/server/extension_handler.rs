@@ -1,13 +1,15 @@
 use std::collections::HashMap;
 
 use axum::{
-    http::{HeaderMap, HeaderValue, StatusCode},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use http_body_util::BodyExt;
 
 use probing_core::core::EngineExtensionManager;
+use probing_core::latency::{self, CallKind};
 
+use super::deadline::Deadline;
 use super::error::ApiResult;
 use crate::engine::ENGINE;
 
@@ -46,6 +48,11 @@ pub async fn handle_extension_call(req: axum::extract::Request) -> ApiResult<Res
         return Ok((StatusCode::OK, headers, "").into_response());
     }
 
+    let deadline = match Deadline::from_headers(&parts.headers) {
+        Ok(deadline) => deadline,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, e).into_response()),
+    };
+
     let params_str = parts.uri.query().unwrap_or_default();
     let params: HashMap<String, String> =
         serde_urlencoded::from_str(params_str).unwrap_or_default();
@@ -73,10 +80,28 @@ pub async fn handle_extension_call(req: axum::extract::Request) -> ApiResult<Res
     };
 
     if let Some(eem) = eem {
-        match eem.call(path, &params, &body_bytes).await {
+        let started = std::time::Instant::now();
+        let call = eem.call_with_metadata(path, &params, &body_bytes);
+        let reply = deadline.run(call).await;
+        let failed = !matches!(reply, Some((Ok(_), _)));
+        latency::record(CallKind::Extension, path, started.elapsed(), failed, || {
+            params_str.to_string()
+        });
+        let Some((result, metadata)) = reply else {
+            log::warn!("Extension call for path '{path}' cancelled at its deadline");
+            return Ok((
+                StatusCode::GATEWAY_TIMEOUT,
+                format!("Extension call for '{path}' did not complete in time"),
+            )
+                .into_response());
+        };
+        match result {
             Ok(response) => {
                 // Determine content type based on path
-                let content_type = if path.contains("timeline") || path.contains("chrome-tracing") {
+                let content_type = if path.contains("timeline")
+                    || path.contains("chrome-tracing")
+                    || path.contains("span-tree")
+                {
                     "application/json"
                 } else {
                     "text/plain"
@@ -116,6 +141,16 @@ pub async fn handle_extension_call(req: axum::extract::Request) -> ApiResult<Res
                     // );
                 }
 
+                // e.g. how long the call waited for the GIL
+                for (key, value) in metadata {
+                    if let (Ok(name), Ok(value)) = (
+                        HeaderName::try_from(format!("x-probing-{key}")),
+                        HeaderValue::try_from(value),
+                    ) {
+                        headers.insert(name, value);
+                    }
+                }
+
                 return Ok((StatusCode::OK, headers, response).into_response());
             }
             Err(e) => {
@@ -1,5 +1,7 @@
-use super::config::{get_max_file_size, ALLOWED_FILE_DIRS};
+use super::config::{allowed_file_dirs, get_max_file_size};
 use super::error::ApiResult;
+use axum::http::header;
+use axum::response::IntoResponse;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
@@ -25,8 +27,8 @@ pub fn validate_path(path: &str) -> Result<PathBuf, String> {
 
     // Check if the canonical path is within any allowed base directory
     let mut is_allowed = false;
-    for base_dir in ALLOWED_FILE_DIRS {
-        let base_path = match Path::new(base_dir).canonicalize() {
+    for base_dir in allowed_file_dirs() {
+        let base_path = match base_dir.canonicalize() {
             Ok(path) => path,
             Err(_) => continue, // Skip non-existent base directories
         };
@@ -79,6 +81,50 @@ pub async fn read_file(
     Ok(content)
 }
 
+/// Download a file as an attachment, binary files such as Parquet dumps included
+pub async fn download_file(
+    axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
+) -> ApiResult<impl IntoResponse> {
+    let path = params
+        .get("path")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'path' parameter"))?;
+
+    let safe_path = validate_path(path).map_err(|e| {
+        log::warn!("Path validation failed for '{path}': {e}");
+        anyhow::anyhow!("Invalid path: {}", e)
+    })?;
+
+    let metadata = tokio::fs::metadata(&safe_path).await.map_err(|e| {
+        log::warn!("Failed to get metadata for {safe_path:?}: {e}");
+        anyhow::anyhow!("Cannot access file")
+    })?;
+
+    let max_file_size = get_max_file_size();
+    if metadata.len() > max_file_size {
+        return Err(anyhow::anyhow!("File too large (max {} bytes allowed)", max_file_size).into());
+    }
+
+    let content = tokio::fs::read(&safe_path).await.map_err(|e| {
+        log::warn!("Failed to read file {safe_path:?}: {e}");
+        anyhow::anyhow!("Cannot read file")
+    })?;
+
+    let filename = safe_path
+        .file_name()
+        .map(|name| name.to_string_lossy().replace('"', ""))
+        .unwrap_or_default();
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        content,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,6 +159,13 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_download_file_missing_path_param() {
+        let params = HashMap::new();
+        let result = download_file(axum::extract::Query(params)).await;
+        assert!(result.is_err());
+    }
+
     #[tokio::test]
     async fn test_read_file_nonexistent() {
         let mut params = HashMap::new();
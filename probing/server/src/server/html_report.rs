@@ -0,0 +1,387 @@
+//! Self-contained HTML report of the process.
+//!
+//! `GET /apis/report` renders `templates/report.html` with the process
+//! overview, a flamegraph, a waterfall of the latest spans, key tables and
+//! the configuration. Everything is inlined, the flamegraph as a data URL, so
+//! the page can be attached to an issue or mailed to people who cannot reach
+//! the live UI. The command line and configuration values go through the
+//! redaction rules of `privacy.redact`, tokens, passwords and remote tables
+//! are masked whatever those rules say; the environment is left out.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use axum::extract::Query as Params;
+use axum::response::IntoResponse;
+use base64::Engine as _;
+use probing_core::config;
+use probing_proto::prelude::{ConfigDump, DataFrame, Ele, EleExt, Query, QueryDataFormat};
+use probing_python::features::privacy;
+
+use super::error::ApiResult;
+
+const TEMPLATE: &str = include_str!("../../templates/report.html");
+
+/// Spans drawn in the waterfall without `spans`
+const DEFAULT_SPANS: usize = 200;
+
+/// Rows of each table without `rows`
+const DEFAULT_ROWS: usize = 20;
+
+/// Latest spans with their duration, `{limit}` replaced by the span limit
+const SPANS_QUERY: &str = "SELECT s.span_id, s.parent_id, s.name, s.time, e.time - s.time \
+     FROM python.trace_event s JOIN python.trace_event e \
+     ON s.trace_id = e.trace_id AND s.span_id = e.span_id \
+     WHERE s.record_type = 'span_start' AND e.record_type = 'span_end' \
+     ORDER BY s.time DESC LIMIT {limit}";
+
+/// Title and query of the key tables, `{limit}` replaced by the row limit;
+/// tables that are missing or empty are left out
+const TABLES: &[(&str, &str)] = &[
+    (
+        "Slowest modules",
+        "SELECT module, stage, count, mean, p50, p99 FROM torch.op_summary \
+         ORDER BY mean DESC LIMIT {limit}",
+    ),
+    (
+        "Out of memory errors",
+        "SELECT time, step, device, requested, allocated, reserved, free, spans \
+         FROM oom.reports ORDER BY time DESC LIMIT {limit}",
+    ),
+    (
+        "Anomalies",
+        "SELECT * FROM alerts.anomalies ORDER BY time DESC LIMIT {limit}",
+    ),
+    (
+        "Longest GC pauses",
+        "SELECT * FROM python.gc ORDER BY duration DESC LIMIT {limit}",
+    ),
+    (
+        "Agent errors",
+        "SELECT * FROM agent.errors ORDER BY time DESC LIMIT {limit}",
+    ),
+];
+
+/// Render the report; `spans` and `rows` bound the waterfall and the tables
+pub async fn get_report(
+    Params(params): Params<HashMap<String, String>>,
+) -> ApiResult<impl IntoResponse> {
+    let limit = |key: &str, default: usize| {
+        params
+            .get(key)
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(default)
+    };
+    let rows = limit("rows", DEFAULT_ROWS);
+
+    let process = super::system::get_overview()?;
+    let overview = render_pairs(&[
+        ("pid".to_string(), process.pid.to_string()),
+        ("exe".to_string(), process.exe.clone()),
+        ("cmd".to_string(), privacy::redact(&process.cmd)),
+        ("cwd".to_string(), process.cwd.clone()),
+        ("threads".to_string(), process.threads.len().to_string()),
+    ]);
+
+    let waterfall = match query(SPANS_QUERY, limit("spans", DEFAULT_SPANS)).await {
+        Some(df) if !df.is_empty() => render_waterfall(&spans_of(&df)),
+        _ => "<p class=\"muted\">No spans recorded.</p>".to_string(),
+    };
+
+    let mut tables = String::new();
+    for (title, sql) in TABLES {
+        if let Some(df) = query(sql, rows).await.filter(|df| !df.is_empty()) {
+            let _ = write!(tables, "<h2>{}</h2>{}", escape(title), render_table(&df));
+        }
+    }
+
+    let config = render_pairs(&config_pairs(&probing_core::config::dump().await));
+
+    let title = format!("probing report of pid {}", process.pid);
+    let page = render(
+        TEMPLATE,
+        &[
+            ("title", escape(&title)),
+            (
+                "generated",
+                chrono::Local::now()
+                    .format("%Y-%m-%d %H:%M:%S %:z")
+                    .to_string(),
+            ),
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ("overview", overview),
+            ("flamegraph", flamegraph()),
+            ("waterfall", waterfall),
+            ("tables", tables),
+            ("config", config),
+        ],
+    );
+    Ok((
+        [
+            ("Content-Type", "text/html; charset=utf-8"),
+            ("Content-Disposition", "attachment; filename=report.html"),
+        ],
+        page,
+    ))
+}
+
+async fn query(sql: &str, limit: usize) -> Option<DataFrame> {
+    let expr = sql.replace("{limit}", &limit.to_string());
+    match crate::engine::handle_query(Query::new(expr), Default::default()).await {
+        Ok(QueryDataFormat::DataFrame(df)) => Some(df),
+        Ok(_) => None,
+        Err(err) => {
+            log::debug!("report left out `{sql}`: {err}");
+            None
+        }
+    }
+}
+
+/// Flamegraph of pprof samples, of the torch profiler when there are none
+fn flamegraph() -> String {
+    let has_samples =
+        |profiler| super::profiling::folded_lines(profiler).is_ok_and(|x| !x.is_empty());
+    let svg = if has_samples("pprof") {
+        probing_python::features::pprof::flamegraph().ok()
+    } else if has_samples("torch") {
+        Some(probing_python::features::torch::flamegraph())
+    } else {
+        None
+    };
+    match svg {
+        Some(svg) => format!(
+            "<img alt=\"flamegraph\" src=\"data:image/svg+xml;base64,{}\">",
+            base64::engine::general_purpose::STANDARD.encode(svg)
+        ),
+        None => "<p class=\"muted\">No profiling samples.</p>".to_string(),
+    }
+}
+
+/// Substitute the `{{name}}` placeholders of `template` in one pass, so
+/// values containing braces are left alone; unknown names are kept
+fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let end = start + 2 + len + 2;
+        let name = &rest[start + 2..end - 2];
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn render_pairs(pairs: &[(String, String)]) -> String {
+    if pairs.is_empty() {
+        return "<p class=\"muted\">Nothing to show.</p>".to_string();
+    }
+    let mut out = String::from("<table>");
+    for (key, value) in pairs {
+        let _ = write!(
+            out,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            escape(key),
+            escape(value)
+        );
+    }
+    out.push_str("</table>");
+    out
+}
+
+fn render_table(df: &DataFrame) -> String {
+    let mut out = String::from("<table><tr>");
+    for name in &df.names {
+        let _ = write!(out, "<th>{}</th>", escape(name));
+    }
+    out.push_str("</tr>");
+    for row in df.iter() {
+        out.push_str("<tr>");
+        for value in row {
+            let text = match value {
+                Ele::Nil => String::new(),
+                value => value.to_string(),
+            };
+            let _ = write!(out, "<td>{}</td>", escape(&text));
+        }
+        out.push_str("</tr>");
+    }
+    out.push_str("</table>");
+    out
+}
+
+/// A span of the waterfall, times in nanoseconds
+#[derive(Debug, Clone, PartialEq)]
+struct WaterfallSpan {
+    id: i64,
+    parent: Option<i64>,
+    name: String,
+    start: i64,
+    duration: i64,
+}
+
+fn spans_of(df: &DataFrame) -> Vec<WaterfallSpan> {
+    df.iter()
+        .filter_map(|row| {
+            Some(WaterfallSpan {
+                id: row.first()?.as_i64()?,
+                parent: row.get(1).and_then(|x| x.as_i64()).filter(|id| *id >= 0),
+                name: row.get(2)?.to_string_lossy(),
+                start: row.get(3)?.as_i64()?,
+                duration: row.get(4)?.as_i64()?.max(0),
+            })
+        })
+        .collect()
+}
+
+/// Spans as bars on a common time axis, nested under their parents
+fn render_waterfall(spans: &[WaterfallSpan]) -> String {
+    let mut spans = spans.to_vec();
+    spans.sort_by_key(|s| (s.start, -s.duration));
+    let (Some(t0), Some(t1)) = (
+        spans.iter().map(|s| s.start).min(),
+        spans.iter().map(|s| s.start + s.duration).max(),
+    ) else {
+        return String::new();
+    };
+    let range = (t1 - t0).max(1) as f64;
+    let parents = spans
+        .iter()
+        .map(|s| (s.id, s.parent))
+        .collect::<HashMap<_, _>>();
+    let depth = |span: &WaterfallSpan| {
+        let mut depth = 0;
+        let mut parent = span.parent;
+        // only parents among the drawn spans count, bounded against loops
+        while let Some(Some(id)) = parent.map(|id| parents.get(&id)) {
+            depth += 1;
+            if depth > parents.len() {
+                break;
+            }
+            parent = *id;
+        }
+        depth
+    };
+
+    let mut out = format!(
+        "<p class=\"muted\">{} spans over {:.3} ms</p>",
+        spans.len(),
+        range / 1e6
+    );
+    for span in &spans {
+        let _ = write!(
+            out,
+            "<div class=\"row\"><div class=\"name\" style=\"padding-left:{}em\" \
+             title=\"{} ({:.3} ms)\">{}</div><div class=\"lane\">\
+             <div class=\"bar\" style=\"left:{:.3}%;width:{:.3}%\"></div></div></div>",
+            depth(span),
+            escape(&span.name),
+            span.duration as f64 / 1e6,
+            escape(&span.name),
+            (span.start - t0) as f64 * 100.0 / range,
+            span.duration as f64 * 100.0 / range,
+        );
+    }
+    out
+}
+
+/// Options and store entries of `dump`, secrets masked and values redacted
+fn config_pairs(dump: &ConfigDump) -> Vec<(String, String)> {
+    dump.options
+        .iter()
+        .chain(dump.store.iter())
+        .map(|(key, value)| {
+            let value = if config::is_secret_key(key) {
+                config::MASKED.to_string()
+            } else {
+                privacy::redact_value(key, value)
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let page = render(
+            "<h1>{{title}}</h1>{{body}}{{missing}}",
+            &[
+                ("title", escape("a < b")),
+                ("body", "{{title}}".to_string()),
+            ],
+        );
+        assert_eq!(page, "<h1>a &lt; b</h1>{{title}}{{missing}}");
+        assert_eq!(render("{{open", &[]), "{{open");
+    }
+
+    #[test]
+    fn test_config_masks_secrets() {
+        let mut dump = ConfigDump::default();
+        dump.options.insert(
+            "probing.server.auth_token".to_string(),
+            "s3cret".to_string(),
+        );
+        dump.options.insert(
+            "probing.federation.tables".to_string(),
+            r#"{"url": "postgres://u:pw@db/x"}"#.to_string(),
+        );
+        dump.store
+            .insert("probing.torch.profiling".to_string(), "on".to_string());
+
+        let pairs = config_pairs(&dump);
+        assert!(pairs
+            .iter()
+            .all(|(_, v)| !v.contains("s3cret") && !v.contains("pw@")));
+        assert!(pairs.contains(&("probing.torch.profiling".to_string(), "on".to_string())));
+    }
+
+    #[test]
+    fn test_waterfall_nests_spans() {
+        let span = |id, parent, start, duration| WaterfallSpan {
+            id,
+            parent,
+            name: format!("span{id}"),
+            start,
+            duration,
+        };
+        let html = render_waterfall(&[
+            span(2, Some(1), 500, 500),
+            span(1, None, 0, 2_000_000),
+            span(3, Some(99), 1_000_000, 1_000_000),
+        ]);
+        assert!(html.starts_with("<p class=\"muted\">3 spans over 2.000 ms</p>"));
+        let first = html.find("span1").unwrap();
+        let second = html.find("span2").unwrap();
+        assert!(first < second);
+        assert!(html.contains("padding-left:1em\" title=\"span2"));
+        // the parent of span3 is not drawn, it starts a new tree
+        assert!(html.contains("padding-left:0em\" title=\"span3"));
+        assert!(html.contains("left:50.000%;width:50.000%"));
+    }
+}
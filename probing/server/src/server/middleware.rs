@@ -1,13 +1,14 @@
 use super::config::get_max_request_body_size;
 use axum::{
     body::Body,
-    extract::Request,
+    extract::{MatchedPath, Request},
     http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use bytes::Bytes;
 use http_body_util::BodyExt;
+use probing_core::latency::{self, CallKind};
 
 /// Middleware to limit request body size
 pub async fn request_size_limit_middleware(
@@ -75,6 +76,25 @@ async fn collect_body_with_limit(body: Body, limit: usize) -> Result<Bytes, &'st
     Ok(bytes)
 }
 
+/// Middleware timing every request into the histogram of its route, see
+/// [`probing_core::latency`]
+pub async fn latency_middleware(request: Request, next: Next) -> Response {
+    // routes rather than paths keep the number of histograms bounded
+    let endpoint = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| format!("{} {}", request.method(), path.as_str()))
+        .unwrap_or_else(|| format!("{} {}", request.method(), latency::OTHER));
+    let query = request.uri().query().unwrap_or_default().to_string();
+    let start = std::time::Instant::now();
+
+    let response = next.run(request).await;
+
+    let failed = response.status().is_server_error();
+    latency::record(CallKind::Http, &endpoint, start.elapsed(), failed, || query);
+    response
+}
+
 /// Middleware for logging requests (optional - for debugging)
 pub async fn request_logging_middleware(request: Request, next: Next) -> Response {
     let method = request.method().to_string();
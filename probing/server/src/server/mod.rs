@@ -1,16 +1,22 @@
 mod apis;
 mod query_dto;
-mod repl;
 
 pub mod cluster;
 pub mod config;
+pub mod deadline;
 pub mod error;
+pub mod events;
 pub mod extension_handler;
 pub mod file_api;
+#[cfg(feature = "analytics")]
+pub mod html_report;
 
 pub mod middleware;
 pub mod profiling;
+pub mod repl;
+pub mod schedule;
 pub mod system;
+pub mod waterfall;
 
 use anyhow::Result;
 use apis::apis_route;
@@ -22,8 +28,9 @@ use crate::engine::{handle_query, initialize_engine};
 use crate::server::repl::ws_handler;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use middleware::{request_logging_middleware, request_size_limit_middleware};
-use probing_proto::prelude::Query;
+use deadline::Deadline;
+use middleware::{latency_middleware, request_logging_middleware, request_size_limit_middleware};
+use probing_proto::prelude::{Message, Query};
 
 async fn get_config_value_handler(
     axum::extract::Path(config_key): axum::extract::Path<String>,
@@ -39,21 +46,7 @@ async fn get_config_value_handler(
 }
 
 pub static SERVER_RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
-    let worker_threads = std::env::var("PROBING_SERVER_WORKER_THREADS")
-        .unwrap_or("4".to_string())
-        .parse::<usize>()
-        .unwrap_or(4);
-    tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .worker_threads(worker_threads)
-        .thread_name("server runtime")
-        .on_thread_start(|| {
-            log::debug!(
-                "start server runtime thread: {:?}",
-                std::thread::current().id()
-            );
-        })
-        .build()
+    crate::runtime::build("server runtime", crate::runtime::worker_threads())
         .unwrap_or_else(|e| panic!("Failed to create server runtime: {e}"))
 });
 
@@ -67,19 +60,30 @@ fn build_app(auth: bool) -> axum::Router {
         .route("/analytics", axum::routing::get(index))
         .route("/python", axum::routing::get(index))
         .route("/traces", axum::routing::get(index))
+        .route("/waterfall", axum::routing::get(index))
         .route("/chrome-tracing", axum::routing::get(index))
         .route("/index.html", axum::routing::get(index))
         .route("/query", axum::routing::post(query))
         .route("/query/dto", axum::routing::post(query_dto::query_dto))
+        .route("/query/protobuf", axum::routing::post(query_protobuf))
+        .route(
+            "/query/protobuf/schema",
+            axum::routing::get(|| async { probing_proto::protobuf::SCHEMA }),
+        )
         .route(
             "/config/{config_key}",
             axum::routing::get(get_config_value_handler),
         )
         .nest_service("/apis", apis_route())
         .route("/ws", axum::routing::get(ws_handler))
+        .route("/events", axum::routing::get(events::events_handler))
         .fallback(static_files)
         .layer(axum::middleware::from_fn(request_size_limit_middleware))
-        .layer(axum::middleware::from_fn(request_logging_middleware));
+        .layer(axum::middleware::from_fn(
+            crate::janitor::activity_middleware,
+        ))
+        .layer(axum::middleware::from_fn(request_logging_middleware))
+        .layer(axum::middleware::from_fn(latency_middleware));
 
     if auth {
         app = app.layer(axum::middleware::from_fn(
@@ -91,13 +95,38 @@ fn build_app(auth: bool) -> axum::Router {
 }
 
 /// HTTP handler wrapper for query endpoint
-async fn query(body: String) -> impl IntoResponse {
-    match crate::engine::query(body).await {
+async fn query(deadline: Deadline, body: String) -> impl IntoResponse {
+    match crate::engine::query(body, deadline).await {
         Ok(response) => (StatusCode::OK, response).into_response(),
         Err(api_error) => api_error.into_response(),
     }
 }
 
+/// HTTP handler for protobuf encoded queries, see `probing.proto`
+async fn query_protobuf(deadline: Deadline, body: bytes::Bytes) -> impl IntoResponse {
+    let request = match Message::<Query>::from_protobuf(&body) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    match crate::engine::execute(request.payload, deadline)
+        .await
+        .try_to_protobuf()
+    {
+        Ok(reply) => (
+            [(
+                axum::http::header::CONTENT_TYPE,
+                probing_proto::protobuf::CONTENT_TYPE,
+            )],
+            reply,
+        )
+            .into_response(),
+        Err(err) => {
+            error!("Failed to encode protobuf response: {err}");
+            (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+        }
+    }
+}
+
 pub async fn local_server() -> Result<()> {
     #[cfg(target_os = "linux")]
     let socket_path = format!("\0probing-{}", std::process::id());
@@ -119,11 +148,15 @@ pub async fn local_server() -> Result<()> {
     );
 
     let app = build_app(false);
-    axum::serve(tokio::net::UnixListener::bind(socket_path)?, app).await?;
+    let listener = tokio::net::UnixListener::bind(&socket_path)?;
+    crate::registry::register(&socket_path);
+    axum::serve(listener, app).await?;
     Ok(())
 }
 
 pub fn start_local() {
+    // journal panics of the agent, readable even when the server is down
+    probing_core::journal::install_panic_hook();
     SERVER_RUNTIME.block_on(async move {
         initialize_engine()
             .await
@@ -132,6 +165,8 @@ pub fn start_local() {
     SERVER_RUNTIME.spawn(async move {
         let _ = local_server().await;
     });
+    crate::janitor::start();
+    schedule::start();
 }
 
 pub async fn remote_server(addr: Option<String>) -> Result<()> {
@@ -156,6 +191,8 @@ pub async fn remote_server(addr: Option<String>) -> Result<()> {
             eprintln!("{}", Red.bold().paint("probing server is available on:"));
             eprintln!("\t{}", Green.bold().underline().paint(addr.to_string()));
             probing_core::config::write("server.address", &addr.to_string()).await?;
+            let token = probing_core::config::get_str("server.auth_token").await;
+            crate::registry::register_address(&addr.to_string(), token.as_deref());
         }
         Err(err) => {
             eprintln!(
@@ -196,14 +233,18 @@ pub fn sync_env_settings() {
     SERVER_RUNTIME.spawn(async move {
         for (k, v) in env_vars {
             let k = k.replace("_", ".").to_lowercase();
-            let setting = format!("set {k}={v}");
+            // quoted so that values such as `dataloader-debug` are not parsed as expressions
+            let setting = format!("set {k}='{}'", v.replace('\'', "''"));
             // Since handle_query might not be async itself, but interacts with
             // components managed by the runtime, it's safer to run it within
             // the runtime's context. If handle_query becomes async, add .await
-            match handle_query(Query {
-                expr: setting,
-                opts: None,
-            })
+            match handle_query(
+                Query {
+                    expr: setting,
+                    opts: None,
+                },
+                Deadline::default(),
+            )
             .await
             {
                 Ok(_) => {
@@ -1,7 +1,18 @@
+use std::collections::HashMap;
+
+use axum::extract::{Path, Query};
 use axum::response::IntoResponse;
+use axum::Json;
+use probing_proto::prelude::{FlameMatch, FlameNode};
 
 use super::error::ApiResult;
 
+/// Levels of callees returned by a tree request without `depth`
+const DEFAULT_TREE_DEPTH: usize = 3;
+
+/// Matches returned by a search request without `limit`
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+
 /// Generate flamegraph using torch profiler
 pub async fn get_torch_flamegraph() -> ApiResult<impl IntoResponse> {
     let graph = probing_python::features::torch::flamegraph();
@@ -27,3 +38,61 @@ pub async fn get_pprof_flamegraph() -> ApiResult<impl IntoResponse> {
         Err(err) => Err(anyhow::anyhow!(err).into()),
     }
 }
+
+/// Folded samples (`a;b;c count`) of `profiler`, `torch` or `pprof`
+pub(super) fn folded_lines(profiler: &str) -> anyhow::Result<Vec<String>> {
+    match profiler {
+        "torch" => probing_python::features::torch::query_profiling(),
+        "pprof" => probing_python::features::pprof::folded(),
+        _ => Err(anyhow::anyhow!("unknown profiler: {profiler}")),
+    }
+}
+
+/// Flamegraph in folded format
+pub async fn get_flamegraph_folded(Path(profiler): Path<String>) -> ApiResult<String> {
+    let mut lines = folded_lines(&profiler)?;
+    lines.sort();
+    Ok(lines.join("\n"))
+}
+
+/// Flamegraph as a JSON tree
+///
+/// Returns the node at `path` (frame names joined by `;`, root if empty) with
+/// `depth` levels of callees; deeper nodes are marked `truncated` and can be
+/// fetched by a further request with their path.
+pub async fn get_flamegraph_tree(
+    Path(profiler): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> ApiResult<Json<FlameNode>> {
+    let root = FlameNode::from_folded(folded_lines(&profiler)?);
+    let path: Vec<&str> = params
+        .get("path")
+        .map(|path| path.split(';').filter(|x| !x.is_empty()).collect())
+        .unwrap_or_default();
+    let depth = params
+        .get("depth")
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_TREE_DEPTH);
+
+    let node = root
+        .subtree(&path, depth)
+        .ok_or_else(|| anyhow::anyhow!("no frame at path: {}", path.join(";")))?;
+    Ok(Json(node))
+}
+
+/// Frames of the flamegraph whose name contains `q`, largest first
+pub async fn search_flamegraph(
+    Path(profiler): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> ApiResult<Json<Vec<FlameMatch>>> {
+    let pattern = params
+        .get("q")
+        .ok_or_else(|| anyhow::anyhow!("Missing 'q' parameter"))?;
+    let limit = params
+        .get("limit")
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_SEARCH_LIMIT);
+
+    let root = FlameNode::from_folded(folded_lines(&profiler)?);
+    Ok(Json(root.search(pattern, limit)))
+}
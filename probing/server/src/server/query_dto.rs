@@ -13,16 +13,18 @@ use serde_json;
 /// This provides a stable external API while keeping the internal implementation unchanged
 #[axum::debug_handler]
 pub async fn query_dto(
+    deadline: super::deadline::Deadline,
     axum::extract::Json(request_dto): axum::extract::Json<
         probing_proto::dto::query::QueryRequestDto,
     >,
 ) -> impl IntoResponse {
-    handle_query_dto(request_dto).await
+    handle_query_dto(request_dto, deadline).await
 }
 
 /// Handle query DTO processing and convert to internal format
 async fn handle_query_dto(
     request_dto: probing_proto::dto::query::QueryRequestDto,
+    deadline: super::deadline::Deadline,
 ) -> impl IntoResponse {
     // Convert DTO to internal Query structure
     let query: ProtoQuery = request_dto.into();
@@ -32,7 +34,7 @@ async fn handle_query_dto(
 
     // Serialize to JSON string for existing engine interface
     match serde_json::to_string(&message) {
-        Ok(json_request) => process_engine_query(json_request).await,
+        Ok(json_request) => process_engine_query(json_request, deadline).await,
         Err(e) => (
             StatusCode::BAD_REQUEST,
             format!("Failed to serialize request: {}", e),
@@ -42,8 +44,11 @@ async fn handle_query_dto(
 }
 
 /// Process the engine query and convert response to DTO format
-async fn process_engine_query(json_request: String) -> axum::response::Response {
-    match crate::engine::query(json_request).await {
+async fn process_engine_query(
+    json_request: String,
+    deadline: super::deadline::Deadline,
+) -> axum::response::Response {
+    match crate::engine::query(json_request, deadline).await {
         Ok(response_json) => convert_engine_response_to_dto(response_json).await,
         Err(api_error) => convert_engine_error_to_dto(api_error).await,
     }
@@ -1,27 +1,247 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
 use axum::extract::ws::Message;
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use futures_util::{SinkExt, StreamExt};
-use probing_python::repl::Repl;
+use probing_proto::prelude::ReplCompression;
+use probing_proto::protocol::repl::{
+    mark_truncated, split_output, COMPRESSION_MIN_BYTES, PARTIAL_STATUS,
+};
+use probing_python::repl::{OutputSink, PythonRepl, ReplOutput};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Default upper bound of the output of a single REPL command
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1 << 20;
+
+/// Default size of the frames long outputs are streamed in
+pub const DEFAULT_CHUNK_BYTES: usize = 64 << 10;
+
+/// Output beyond this many bytes is dropped, 0 disables the limit
+pub static MAX_OUTPUT_BYTES: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_OUTPUT_BYTES);
+
+/// Size of the frames streamed to clients asking for it, 0 disables chunking
+pub static CHUNK_BYTES: AtomicUsize = AtomicUsize::new(DEFAULT_CHUNK_BYTES);
+
+/// Options negotiated by the client when opening the REPL channel
+#[derive(Debug, Default, Deserialize)]
+pub struct ReplParams {
+    /// Compression of large frames, `none` or `deflate`; done here because
+    /// tungstenite has no permessage-deflate
+    #[serde(default)]
+    compress: Option<String>,
+    /// Send the output as `partial` frames while the command runs, ahead of
+    /// the final response
+    #[serde(default)]
+    stream: bool,
+    /// Named session to attach to, its namespace survives disconnects;
+    /// without one the connection gets a console of its own
+    #[serde(default)]
+    session: Option<String>,
+}
 
 pub async fn ws_handler(
+    Query(params): Query<ReplParams>,
     ws: axum::extract::ws::WebSocketUpgrade,
-) -> impl axum::response::IntoResponse {
-    ws.on_upgrade(|ws| async move {
-        // Handle WebSocket connection
-        log::info!("WebSocket connection established");
-        let (mut write, mut read) = ws.split();
+) -> Response {
+    let compression = match params
+        .compress
+        .as_deref()
+        .unwrap_or_default()
+        .parse::<ReplCompression>()
+    {
+        Ok(compression) => compression,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let stream = params.stream;
+    let session = params.session;
+    let mut repl = match session.as_deref().map(PythonRepl::attach) {
+        Some(Ok(repl)) => repl,
+        Some(Err(err)) => return (StatusCode::BAD_REQUEST, err).into_response(),
+        None => PythonRepl::private(),
+    };
 
-        let mut repl = probing_python::repl::PythonRepl::default();
+    ws.on_upgrade(move |ws| async move {
+        log::info!(
+            "WebSocket connection established, session: {}, compression: {compression}, stream: {stream}",
+            session.as_deref().unwrap_or("-")
+        );
+        let (mut write, mut read) = ws.split();
 
         while let Some(Ok(msg)) = read.next().await {
-            if let Message::Text(msg) = msg {
-                let rsp = repl.feed(msg.to_string()).unwrap_or("{}".to_string());
+            let Message::Text(msg) = msg else {
+                continue;
+            };
+            let chunk_bytes = if stream {
+                CHUNK_BYTES.load(Ordering::Relaxed)
+            } else {
+                0
+            };
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+            let output = ReplOutput {
+                max_bytes: MAX_OUTPUT_BYTES.load(Ordering::Relaxed),
+                on_output: stream.then(|| {
+                    Arc::new(move |text: &str| {
+                        let _ = tx.send(text.to_string());
+                    }) as OutputSink
+                }),
+            };
 
-                if write.send(Message::Text(rsp.into())).await.is_err() {
-                    break;
+            // the command runs on a blocking thread, its output is sent
+            // while it runs
+            let mut task = tokio::task::spawn_blocking(move || {
+                let rsp = repl.feed_with(msg.to_string(), &output);
+                (repl, rsp)
+            });
+            let mut frames = vec![];
+            let (back, rsp) = loop {
+                tokio::select! {
+                    Some(text) = rx.recv() => {
+                        frames.extend(partial_frames(&text, chunk_bytes));
+                    }
+                    done = &mut task => match done {
+                        Ok(done) => break done,
+                        Err(err) => {
+                            log::error!("REPL command failed: {err}");
+                            return;
+                        }
+                    },
+                }
+                for frame in frames.drain(..) {
+                    if send(&mut write, frame, compression).await.is_err() {
+                        return;
+                    }
+                }
+            };
+            repl = back;
+            while let Ok(text) = rx.try_recv() {
+                frames.extend(partial_frames(&text, chunk_bytes));
+            }
+            frames.extend(response_frames(
+                &rsp.unwrap_or("{}".to_string()),
+                chunk_bytes,
+            ));
+            for frame in frames {
+                if send(&mut write, frame, compression).await.is_err() {
+                    return;
                 }
             }
         }
-        // Here you would handle the WebSocket messages
-        // For now, just close the connection
     })
+    .into_response()
+}
+
+async fn send<S>(write: &mut S, frame: String, compression: ReplCompression) -> Result<(), ()>
+where
+    S: SinkExt<Message> + Unpin,
+{
+    let msg = encode(frame, compression).map_err(|err| {
+        log::error!("Failed to compress REPL frame: {err}");
+    })?;
+    write.send(msg).await.map_err(|_| ())
+}
+
+/// Output streamed while the command runs, as `partial` frames of at most
+/// `chunk_bytes`
+fn partial_frames(output: &str, chunk_bytes: usize) -> Vec<String> {
+    split_output(output, chunk_bytes)
+        .into_iter()
+        .filter(|chunk| !chunk.is_empty())
+        .map(partial_frame)
+        .collect()
+}
+
+fn partial_frame(chunk: &str) -> String {
+    serde_json::json!({"status": PARTIAL_STATUS, "output": chunk}).to_string()
+}
+
+/// Split a REPL response into the frames sent to the client
+///
+/// The output was already capped where it was captured; a `truncated` field
+/// tells how many bytes were dropped, and is marked at the end of the output.
+/// Unless `chunk_bytes` is 0, all but the last piece of the output go out as
+/// `partial` frames and the last one carries the rest of the response.
+fn response_frames(rsp: &str, chunk_bytes: usize) -> Vec<String> {
+    let mut rsp = match serde_json::from_str::<Value>(rsp) {
+        Ok(Value::Object(rsp)) => rsp,
+        _ => return vec![rsp.to_string()],
+    };
+    let Some(Value::String(mut output)) = rsp.remove("output") else {
+        return vec![Value::Object(rsp).to_string()];
+    };
+    let dropped = rsp.get("truncated").and_then(Value::as_u64).unwrap_or(0);
+    mark_truncated(&mut output, dropped as usize);
+
+    let mut chunks = split_output(&output, chunk_bytes);
+    let last = chunks.pop().unwrap_or_default();
+    let mut frames = chunks.into_iter().map(partial_frame).collect::<Vec<_>>();
+    rsp.insert("output".to_string(), last.into());
+    frames.push(Value::Object(rsp).to_string());
+    frames
+}
+
+fn encode(frame: String, compression: ReplCompression) -> Result<Message, anyhow::Error> {
+    if compression == ReplCompression::None || frame.len() < COMPRESSION_MIN_BYTES {
+        return Ok(Message::Text(frame.into()));
+    }
+    Ok(Message::Binary(
+        compression.encode(frame.as_bytes())?.into(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_repl_frames() {
+        let rsp = serde_json::json!({
+            "status": "ok",
+            "output": "x".repeat(DEFAULT_CHUNK_BYTES * 2 + 10),
+            "traceback": [],
+        })
+        .to_string();
+
+        let whole = response_frames(&rsp, 0);
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0].len(), rsp.len());
+
+        let streamed = response_frames(&rsp, DEFAULT_CHUNK_BYTES);
+        assert_eq!(streamed.len(), 3);
+        let first: Value = serde_json::from_str(&streamed[0]).unwrap();
+        assert_eq!(first["status"], PARTIAL_STATUS);
+        assert_eq!(first["output"].as_str().unwrap().len(), DEFAULT_CHUNK_BYTES);
+        let last: Value = serde_json::from_str(&streamed[2]).unwrap();
+        assert_eq!(last["status"], "ok");
+        assert_eq!(last["output"].as_str().unwrap().len(), 10);
+
+        let rsp = serde_json::json!({
+            "status": "ok",
+            "output": "y".repeat(100),
+            "truncated": 5,
+        })
+        .to_string();
+        let last: Value = serde_json::from_str(&response_frames(&rsp, 0)[0]).unwrap();
+        assert_eq!(last["truncated"], 5);
+        assert!(last["output"]
+            .as_str()
+            .unwrap()
+            .ends_with("... [5 bytes truncated, see repl.max_output_bytes]\n"));
+
+        assert_eq!(response_frames("not json", 10), vec!["not json"]);
+    }
+
+    #[test]
+    fn test_partial_frames() {
+        let frames = partial_frames("abcdefg", 3);
+        assert_eq!(frames.len(), 3);
+        let last: Value = serde_json::from_str(&frames[2]).unwrap();
+        assert_eq!(last["status"], PARTIAL_STATUS);
+        assert_eq!(last["output"], "g");
+        assert_eq!(partial_frames("abc", 0).len(), 1);
+        assert!(partial_frames("", 3).is_empty());
+    }
 }
@@ -0,0 +1,50 @@
+//! Scheduled configuration changes, see [`probing_core::schedule`].
+//!
+//! `GET /apis/config/schedule` lists the changes, `POST` queues the one in
+//! the body and `POST /apis/config/schedule/{id}/cancel` cancels one.
+
+use std::time::Duration;
+
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use probing_core::schedule;
+use probing_proto::prelude::ScheduledChange;
+
+use super::SERVER_RUNTIME;
+
+/// Interval between two checks of the time triggers
+const TICK: Duration = Duration::from_secs(1);
+
+pub async fn get_schedule() -> Json<Vec<ScheduledChange>> {
+    Json(schedule::list())
+}
+
+/// Queue a change, rejected with `400` if its value does not fit its option
+pub async fn post_schedule(Json(change): Json<ScheduledChange>) -> Response {
+    match schedule::add(change) {
+        Ok(change) => Json(change).into_response(),
+        Err(err) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+pub async fn cancel_schedule(Path(id): Path<u64>) -> Response {
+    match schedule::cancel(id) {
+        Some(change) => Json(change).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("no scheduled change {id}")).into_response(),
+    }
+}
+
+/// Apply the changes that are due in the background
+pub(crate) fn start() {
+    SERVER_RUNTIME.spawn(async {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            if schedule::is_due(schedule::now(), schedule::step()) {
+                schedule::apply_due().await;
+            }
+        }
+    });
+}
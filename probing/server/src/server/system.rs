@@ -39,6 +39,50 @@ pub fn get_overview() -> Result<Process> {
     Ok(info)
 }
 
+/// Optional features compiled into this probe
+pub fn get_capabilities() -> Capabilities {
+    let mut features = vec![];
+    if cfg!(feature = "analytics") {
+        features.push(FEATURE_ANALYTICS.to_string());
+    }
+    if cfg!(feature = "wasm") {
+        features.push(FEATURE_WASM.to_string());
+    }
+    if cfg!(feature = "postgres") {
+        features.push(FEATURE_POSTGRES.to_string());
+    }
+    if cfg!(feature = "clickhouse") {
+        features.push(FEATURE_CLICKHOUSE.to_string());
+    }
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        features,
+    }
+}
+
+/// Build of this probe, with the versions a client checks compatibility against
+pub fn get_build_info() -> BuildInfo {
+    let crates = [
+        ("probing-server", env!("CARGO_PKG_VERSION")),
+        ("probing-core", probing_core::VERSION),
+        ("probing-proto", probing_proto::VERSION),
+        ("probing-python", probing_python::VERSION),
+    ];
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: option_env!("PROBING_GIT_HASH")
+            .unwrap_or_default()
+            .to_string(),
+        protocol: ProtocolVersion::current(),
+        features: get_capabilities().features,
+        python_abi: probing_python::python::abi_tag(),
+        crates: crates
+            .into_iter()
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect(),
+    }
+}
+
 /// Get system overview information as JSON for API
 pub async fn get_overview_json() -> ApiResult<axum::Json<Process>> {
     let overview = get_overview()?;
@@ -0,0 +1,504 @@
+//! Step-time waterfall of training steps.
+//!
+//! `GET /apis/waterfall?from=&to=&limit=` breaks the steps `from..=to`, the
+//! last `limit` of them, into data loading, host to device copies, forward,
+//! backward, optimizer and communication, aligned on the start of each step:
+//!
+//! * steps are marked by the `trainer.step` spans of `probing.ext.trainer`
+//!   or, failing that, by the `ProfilerStep#N` events of `kineto.events`;
+//! * forward, backward and optimizer are the outermost modules of
+//!   `python.torch_trace`, placed by their offset from the first module hook
+//!   of the step, so steps that are not marked still get them;
+//! * data loading are the spans named `dataloader*`, `data_load*` or
+//!   `data.load*` starting in or right before a marked step;
+//! * copies and collectives are the `HtoD` memory copies and NCCL kernels of
+//!   `kineto.events` in a marked step; `comm_overlap` is the part of the
+//!   collectives during which compute kernels ran as well.
+//!
+//! Sources that are missing are left out, a step has the phases that were
+//! recorded for it.
+
+use std::collections::{BTreeMap, HashMap};
+
+use axum::extract::Query as Params;
+use axum::Json;
+use probing_proto::prelude::{
+    DataFrame, Ele, EleExt, PhaseSegment, Query, QueryDataFormat, StepPhase, StepWaterfall,
+};
+
+use super::error::ApiResult;
+
+/// Steps returned without `limit`
+const DEFAULT_STEPS: usize = 20;
+
+/// Timed module rows, `{range}` replaced by the step filter
+const TORCH_QUERY: &str = "SELECT step, module, stage, time_offset, duration \
+     FROM python.torch_trace WHERE step IS NOT NULL AND stage NOT LIKE 'pre %'{range}";
+
+/// Start, duration and attributes of the `trainer.step` spans
+const STEP_SPANS_QUERY: &str = "SELECT s.time, e.time - s.time, s.attributes \
+     FROM python.trace_event s JOIN python.trace_event e ON s.span_id = e.span_id \
+     WHERE s.record_type = 'span_start' AND e.record_type = 'span_end' \
+     AND s.name = 'trainer.step'";
+
+/// Steps of a scheduled `torch.profiler`
+const PROFILER_STEPS_QUERY: &str =
+    "SELECT name, start, duration FROM kineto.events WHERE name LIKE 'ProfilerStep#%'";
+
+/// Data loading spans, `{t0}` and `{t1}` replaced by the time range
+const DATA_SPANS_QUERY: &str = "SELECT s.time, e.time \
+     FROM python.trace_event s JOIN python.trace_event e ON s.span_id = e.span_id \
+     WHERE s.record_type = 'span_start' AND e.record_type = 'span_end' \
+     AND (lower(s.name) LIKE 'dataloader%' OR lower(s.name) LIKE 'data_load%' \
+     OR lower(s.name) LIKE 'data.load%') AND e.time >= {t0} AND s.time < {t1}";
+
+/// Copies and kernels, `{t0}` and `{t1}` replaced by the time range
+const KINETO_QUERY: &str = "SELECT cat, name, start, duration FROM kineto.events \
+     WHERE cat IN ('kernel', 'gpu_memcpy') AND duration IS NOT NULL \
+     AND start + duration >= {t0} AND start < {t1}";
+
+/// Waterfall of the steps between `from` and `to`, both inclusive
+pub async fn get_waterfall(
+    Params(params): Params<HashMap<String, String>>,
+) -> ApiResult<Json<Vec<StepWaterfall>>> {
+    let bound = |key: &str| params.get(key).and_then(|x| x.parse::<i64>().ok());
+    let (from, to) = (bound("from"), bound("to"));
+    let limit = params
+        .get("limit")
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(DEFAULT_STEPS);
+    let in_range = |step: i64| from.is_none_or(|x| step >= x) && to.is_none_or(|x| step <= x);
+
+    let mut range = String::new();
+    if let Some(from) = from {
+        range.push_str(&format!(" AND step >= {from}"));
+    }
+    if let Some(to) = to {
+        range.push_str(&format!(" AND step <= {to}"));
+    }
+    let modules = query(&TORCH_QUERY.replace("{range}", &range))
+        .await
+        .map(|df| modules_of(&df))
+        .unwrap_or_default();
+
+    let mut anchors = match query(STEP_SPANS_QUERY).await {
+        Some(df) if !df.is_empty() => step_spans_of(&df),
+        _ => query(PROFILER_STEPS_QUERY)
+            .await
+            .map(|df| profiler_steps_of(&df))
+            .unwrap_or_default(),
+    };
+    anchors.retain(|step, _| in_range(*step));
+
+    let mut steps: BTreeMap<i64, StepTimes> = BTreeMap::new();
+    for module in modules {
+        let times = steps.entry(module.step).or_default();
+        times
+            .relative
+            .entry(module.phase)
+            .or_default()
+            .push((module.end - module.duration, module.end));
+    }
+    for (step, anchor) in anchors {
+        steps.entry(step).or_default().anchor = Some(anchor);
+    }
+    while steps.len() > limit {
+        steps.pop_first();
+    }
+
+    let marked: Vec<(i64, i64)> = steps.values().filter_map(|x| x.anchor).collect();
+    if let (Some(t0), Some(t1)) = (
+        marked.iter().map(|x| x.0).min(),
+        marked.iter().map(|x| x.1).max(),
+    ) {
+        let bounds = |sql: &str| {
+            sql.replace("{t0}", &t0.to_string())
+                .replace("{t1}", &t1.to_string())
+        };
+        if let Some(df) = query(&bounds(DATA_SPANS_QUERY)).await {
+            for interval in intervals_of(&df) {
+                // batches are usually fetched right before the step using them
+                if let Some(times) = step_after(&mut steps, interval.0) {
+                    times.absolute(StepPhase::DataLoad).push(interval);
+                }
+            }
+        }
+        if let Some(df) = query(&bounds(KINETO_QUERY)).await {
+            for (kind, interval) in kernels_of(&df) {
+                let Some(times) = step_at(&mut steps, interval.0) else {
+                    continue;
+                };
+                match kind {
+                    Some(phase) => times.absolute(phase).push(interval),
+                    None => times.compute.push(interval),
+                }
+            }
+        }
+    }
+
+    Ok(Json(
+        steps
+            .into_iter()
+            .map(|(step, times)| times.build(step))
+            .collect(),
+    ))
+}
+
+async fn query(sql: &str) -> Option<DataFrame> {
+    match crate::engine::handle_query(Query::new(sql.to_string()), Default::default()).await {
+        Ok(QueryDataFormat::DataFrame(df)) => Some(df),
+        Ok(_) => None,
+        Err(err) => {
+            log::debug!("waterfall left out `{sql}`: {err}");
+            None
+        }
+    }
+}
+
+/// Start and end of a time range, in nanoseconds
+type Interval = (i64, i64);
+
+/// What was recorded for a step
+#[derive(Debug, Default)]
+struct StepTimes {
+    /// Start and end of the step since the unix epoch, if it is marked
+    anchor: Option<Interval>,
+
+    /// Phases from the first module hook of the step
+    relative: BTreeMap<StepPhase, Vec<Interval>>,
+
+    /// Phases since the unix epoch
+    absolute_phases: BTreeMap<StepPhase, Vec<Interval>>,
+
+    /// Compute kernels since the unix epoch, overlapping the collectives
+    compute: Vec<Interval>,
+}
+
+impl StepTimes {
+    fn absolute(&mut self, phase: StepPhase) -> &mut Vec<Interval> {
+        self.absolute_phases.entry(phase).or_default()
+    }
+
+    /// Align the phases on the start of the step
+    fn build(self, step: i64) -> StepWaterfall {
+        let base = self.anchor.map(|x| x.0).unwrap_or_default();
+        let mut phases = self.absolute_phases;
+        for (phase, intervals) in self.relative {
+            phases
+                .entry(phase)
+                .or_default()
+                .extend(intervals.into_iter().map(|(s, e)| (s + base, e + base)));
+        }
+        let phases: BTreeMap<_, _> = phases.into_iter().map(|(k, v)| (k, merge(v))).collect();
+
+        let origin = phases
+            .values()
+            .filter_map(|x| x.first().map(|x| x.0))
+            .chain(self.anchor.map(|x| x.0))
+            .min()
+            .unwrap_or(base);
+        let end = phases
+            .values()
+            .filter_map(|x| x.last().map(|x| x.1))
+            .chain(self.anchor.map(|x| x.1))
+            .max()
+            .unwrap_or(origin);
+
+        let comm_overlap = phases
+            .get(&StepPhase::Comm)
+            .map(|comm| overlap(comm, &merge(self.compute)))
+            .unwrap_or_default();
+        let segments = StepPhase::ALL
+            .iter()
+            .filter_map(|phase| {
+                let intervals = phases.get(phase)?;
+                Some(PhaseSegment {
+                    phase: *phase,
+                    start: intervals.first()?.0 - origin,
+                    end: intervals.last()?.1 - origin,
+                    busy: intervals.iter().map(|(s, e)| e - s).sum(),
+                })
+            })
+            .collect();
+        StepWaterfall {
+            step,
+            start: self.anchor.map(|_| origin),
+            duration: end - origin,
+            segments,
+            comm_overlap,
+        }
+    }
+}
+
+/// Marked step running at `time`
+fn step_at(steps: &mut BTreeMap<i64, StepTimes>, time: i64) -> Option<&mut StepTimes> {
+    steps
+        .values_mut()
+        .find(|x| x.anchor.is_some_and(|(s, e)| s <= time && time < e))
+}
+
+/// Marked step running at `time` or, between steps, the next one
+fn step_after(steps: &mut BTreeMap<i64, StepTimes>, time: i64) -> Option<&mut StepTimes> {
+    steps
+        .values_mut()
+        .filter(|x| x.anchor.is_some_and(|(_, e)| time < e))
+        .min_by_key(|x| x.anchor.map(|x| x.0))
+}
+
+/// Sort `intervals` and merge those that overlap
+fn merge(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort_unstable();
+    let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end.max(start))),
+        }
+    }
+    merged
+}
+
+/// Time covered by both `a` and `b`, both merged
+fn overlap(a: &[Interval], b: &[Interval]) -> i64 {
+    let (mut i, mut j, mut total) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].0.max(b[j].0);
+        let end = a[i].1.min(b[j].1);
+        total += (end - start).max(0);
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    total
+}
+
+/// Seconds or nanoseconds as nanoseconds
+fn nanos(ele: &Ele, scale: f64) -> Option<i64> {
+    ele.as_f64()
+        .map(|x| (x * scale) as i64)
+        .or_else(|| ele.as_i64().map(|x| (x as f64 * scale) as i64))
+}
+
+/// Outermost module of a stage, times in nanoseconds from the step start
+#[derive(Debug, Clone, PartialEq)]
+struct StageModule {
+    step: i64,
+    phase: StepPhase,
+    end: i64,
+    duration: i64,
+}
+
+fn modules_of(df: &DataFrame) -> Vec<StageModule> {
+    // the root module has no name and is recorded as `None`
+    let depth = |module: &str| match module {
+        "None" => -1,
+        module => module.matches('.').count() as i64,
+    };
+    let rows: Vec<(StageModule, i64)> = df
+        .iter()
+        .filter_map(|row| {
+            let phase = match row.get(2)?.as_str()?.trim_start_matches("post ") {
+                "forward" => StepPhase::Forward,
+                "backward" => StepPhase::Backward,
+                "step" => StepPhase::Optimizer,
+                _ => return None,
+            };
+            let module = StageModule {
+                step: row.first()?.as_i64()?,
+                phase,
+                end: nanos(row.get(3)?, 1e9)?,
+                duration: nanos(row.get(4)?, 1e9)?.max(0),
+            };
+            Some((module, depth(row.get(1)?.as_str()?)))
+        })
+        .collect();
+
+    let mut outermost: HashMap<(i64, StepPhase), i64> = HashMap::new();
+    for (module, depth) in &rows {
+        let min = outermost
+            .entry((module.step, module.phase))
+            .or_insert(*depth);
+        *min = (*min).min(*depth);
+    }
+    rows.into_iter()
+        .filter(|(module, depth)| outermost[&(module.step, module.phase)] == *depth)
+        .map(|(module, _)| module)
+        .collect()
+}
+
+fn step_spans_of(df: &DataFrame) -> BTreeMap<i64, Interval> {
+    df.iter()
+        .filter_map(|row| {
+            let start = row.first()?.as_i64()?;
+            let duration = row.get(1)?.as_i64()?.max(0);
+            let attributes: serde_json::Value = serde_json::from_str(row.get(2)?.as_str()?).ok()?;
+            let step = attributes.get("step")?.as_i64()?;
+            Some((step, (start, start + duration)))
+        })
+        .collect()
+}
+
+fn profiler_steps_of(df: &DataFrame) -> BTreeMap<i64, Interval> {
+    df.iter()
+        .filter_map(|row| {
+            let step = row.first()?.as_str()?.strip_prefix("ProfilerStep#")?;
+            let start = row.get(1)?.as_i64()?;
+            let duration = row.get(2)?.as_i64()?.max(0);
+            Some((step.parse().ok()?, (start, start + duration)))
+        })
+        .collect()
+}
+
+fn intervals_of(df: &DataFrame) -> Vec<Interval> {
+    df.iter()
+        .filter_map(|row| Some((row.first()?.as_i64()?, row.get(1)?.as_i64()?)))
+        .collect()
+}
+
+/// Copies and kernels, with their phase or `None` for compute kernels
+fn kernels_of(df: &DataFrame) -> Vec<(Option<StepPhase>, Interval)> {
+    df.iter()
+        .filter_map(|row| {
+            let name = row.get(1)?.as_str()?;
+            let phase = match row.first()?.as_str()? {
+                "gpu_memcpy" if name.contains("HtoD") => Some(StepPhase::H2d),
+                "gpu_memcpy" => return None,
+                _ if name.to_lowercase().contains("nccl") => Some(StepPhase::Comm),
+                _ => None,
+            };
+            let start = row.get(2)?.as_i64()?;
+            Some((phase, (start, start + row.get(3)?.as_i64()?.max(0))))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use probing_proto::prelude::Seq;
+
+    #[test]
+    fn test_merge_and_overlap() {
+        let comm = merge(vec![(10, 20), (0, 5), (15, 30)]);
+        assert_eq!(comm, vec![(0, 5), (10, 30)]);
+        let compute = merge(vec![(3, 12), (25, 40)]);
+        assert_eq!(overlap(&comm, &compute), 2 + 2 + 5);
+        assert_eq!(overlap(&comm, &[]), 0);
+    }
+
+    #[test]
+    fn test_outermost_modules() {
+        let df = DataFrame::new(
+            vec![
+                "step".into(),
+                "module".into(),
+                "stage".into(),
+                "time_offset".into(),
+                "duration".into(),
+            ],
+            vec![
+                Seq::SeqI64(vec![1, 1, 1, 2]),
+                Seq::SeqText(vec![
+                    "None".into(),
+                    "encoder.layer".into(),
+                    "None".into(),
+                    "encoder".into(),
+                ]),
+                Seq::SeqText(vec![
+                    "post forward".into(),
+                    "post forward".into(),
+                    "post backward".into(),
+                    "post step".into(),
+                ]),
+                Seq::SeqF64(vec![0.5, 0.2, 1.5, 0.1]),
+                Seq::SeqF64(vec![0.5, 0.1, 1.0, 0.1]),
+            ],
+        );
+        let modules = modules_of(&df);
+        assert_eq!(modules.len(), 3);
+        assert_eq!(
+            modules[0],
+            StageModule {
+                step: 1,
+                phase: StepPhase::Forward,
+                end: 500_000_000,
+                duration: 500_000_000,
+            }
+        );
+        assert_eq!(modules[2].phase, StepPhase::Optimizer);
+    }
+
+    #[test]
+    fn test_build_aligns_on_step_start() {
+        let mut times = StepTimes {
+            anchor: Some((1_000, 2_000)),
+            ..Default::default()
+        };
+        times
+            .relative
+            .insert(StepPhase::Forward, vec![(100, 400), (0, 50)]);
+        times.absolute(StepPhase::DataLoad).push((900, 1_000));
+        times.absolute(StepPhase::Comm).push((1_500, 1_700));
+        times.compute.push((1_600, 1_800));
+
+        let step = times.build(7);
+        assert_eq!(step.start, Some(900));
+        assert_eq!(step.duration, 1_100);
+        assert_eq!(step.comm_overlap, 100);
+        let phases: Vec<_> = step.segments.iter().map(|x| x.phase).collect();
+        assert_eq!(
+            phases,
+            vec![StepPhase::DataLoad, StepPhase::Forward, StepPhase::Comm]
+        );
+        let forward = step.segment(StepPhase::Forward).unwrap();
+        assert_eq!((forward.start, forward.end, forward.busy), (100, 500, 350));
+    }
+
+    #[test]
+    fn test_unmarked_step_keeps_offsets() {
+        let mut times = StepTimes::default();
+        times.relative.insert(StepPhase::Backward, vec![(200, 600)]);
+        let step = times.build(3);
+        assert_eq!(step.start, None);
+        assert_eq!(step.duration, 400);
+        assert_eq!(step.segments[0].start, 0);
+    }
+
+    #[test]
+    fn test_classify_kernels() {
+        let df = DataFrame::new(
+            vec![
+                "cat".into(),
+                "name".into(),
+                "start".into(),
+                "duration".into(),
+            ],
+            vec![
+                Seq::SeqText(vec![
+                    "gpu_memcpy".into(),
+                    "gpu_memcpy".into(),
+                    "kernel".into(),
+                    "kernel".into(),
+                ]),
+                Seq::SeqText(vec![
+                    "Memcpy HtoD (Pageable -> Device)".into(),
+                    "Memcpy DtoH (Device -> Pageable)".into(),
+                    "ncclDevKernel_AllReduce_Sum_f32_RING_LL".into(),
+                    "ampere_sgemm_128x64_tn".into(),
+                ]),
+                Seq::SeqI64(vec![0, 10, 20, 30]),
+                Seq::SeqI64(vec![5, 5, 5, 5]),
+            ],
+        );
+        assert_eq!(
+            kernels_of(&df),
+            vec![
+                (Some(StepPhase::H2d), (0, 5)),
+                (Some(StepPhase::Comm), (20, 25)),
+                (None, (30, 35)),
+            ]
+        );
+    }
+}
@@ -15,7 +15,7 @@ Responsibilities:
 Public Interfaces:
 - Engine: `query`, `load_extension`
 - Control: `cli_main`, `enable_tracer`, `disable_tracer`, `is_enabled`
-- Tracing: `span`, `event`
+- Tracing: `span`, `event`, `watch`, `unwatch`, `snapshot_on`
 - Engine: `query`, `load_extension`
 """
 
@@ -26,6 +26,7 @@ VERSION = "0.2.3"
 
 # Core Primitives
 ExternalTable = _core.ExternalTable
+SchemaError = _core.SchemaError
 TCPStore = _core.TCPStore
 
 # Control Functions
@@ -44,11 +45,14 @@ _get_python_frames = _core._get_python_frames
 
 # Submodules with side effects (must be imported after Core Primitives)
 from probing.core.engine import load_extension, query
+from probing.inspect.snapshot import snapshot_on
+from probing.inspect.watch import unwatch, watch
 from probing.tracing import event, span
 
 __all__ = [
     "VERSION",
     "ExternalTable",
+    "SchemaError",
     "TCPStore",
     "config",
     "cli_main",
@@ -59,4 +63,7 @@ __all__ = [
     "load_extension",
     "span",
     "event",
+    "watch",
+    "unwatch",
+    "snapshot_on",
 ]
@@ -0,0 +1,41 @@
+"""Offline analysis of exported probing archives.
+
+An archive is a directory, or a zip of one, holding a parquet (or csv) file
+per table, named after the table: ``python.torch_trace.parquet``. Tables of
+several ranks are kept in ``rank<N>/`` subdirectories, or carry a ``rank``
+column; :func:`export` writes the tables of the current process in that
+layout. The computations need `polars <https://pola.rs>`_, installed with
+``pip install "probing[analysis]"``:
+
+* :func:`step_breakdown`: forward, backward and optimizer time of each step;
+* :func:`stragglers`: ranks whose steps are slower than those of the others;
+* :func:`regressions`: operators that got slower than in a baseline archive;
+* :func:`render_report`: all of the above as a self-contained HTML page;
+* :func:`diff`: configuration, profiles and key metrics that changed
+  between two archives.
+
+``probing analyze <archive>`` prints the tables and writes the report,
+``probing bundle diff <before> <after>`` prints what changed.
+
+Examples
+--------
+>>> from probing import analysis
+>>> archive = analysis.Archive("run-42/")  # doctest: +SKIP
+>>> analysis.stragglers(archive)  # doctest: +SKIP
+"""
+
+from .archive import Archive, export
+from .diff import diff, format_diff
+from .metrics import regressions, step_breakdown, stragglers
+from .report import render_report
+
+__all__ = [
+    "Archive",
+    "diff",
+    "export",
+    "format_diff",
+    "regressions",
+    "render_report",
+    "step_breakdown",
+    "stragglers",
+]
@@ -0,0 +1,60 @@
+"""``probing analyze <archive>``: print the analyses of an archive."""
+
+import argparse
+import sys
+from typing import List, Optional
+
+from .archive import Archive
+from .metrics import regressions, step_breakdown, stragglers
+from .report import render_report
+
+
+def main(argv: Optional[List[str]] = None) -> int:
+    parser = argparse.ArgumentParser(
+        prog="probing analyze",
+        description="Analyze an archive of probing tables exported by "
+        "probing.analysis.export",
+    )
+    parser.add_argument("archive", help="directory or zip of the archive")
+    parser.add_argument("--baseline", help="archive to look for regressions against")
+    parser.add_argument("--html", metavar="PATH", help="write an HTML report")
+    parser.add_argument(
+        "--top", type=int, default=20, help="regressed operators listed (20)"
+    )
+    parser.add_argument(
+        "--threshold",
+        type=float,
+        default=1.1,
+        help="step time over the median that makes a straggler (1.1)",
+    )
+    args = parser.parse_args(argv)
+
+    try:
+        archive = Archive(args.archive)
+        baseline = Archive(args.baseline) if args.baseline else None
+    except (FileNotFoundError, ImportError) as e:
+        print(f"error: {e}", file=sys.stderr)
+        return 1
+
+    if "python.torch_trace" in archive:
+        print("Step time breakdown (seconds)")
+        print(step_breakdown(archive))
+        print(f"\nStragglers (ratio above {args.threshold})")
+        print(stragglers(archive, args.threshold))
+    else:
+        print(f"no python.torch_trace in archive, found {archive.tables}")
+    if baseline is not None:
+        print(f"\nTop {args.top} regressed operators vs {baseline.path}")
+        try:
+            print(regressions(archive, baseline, top=args.top))
+        except KeyError as e:
+            print(f"no operator timings: {e}")
+    if args.html:
+        with open(args.html, "w", encoding="utf-8") as f:
+            f.write(render_report(archive, baseline, args.top, args.threshold))
+        print(f"\nreport written to {args.html}")
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
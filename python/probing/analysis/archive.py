@@ -0,0 +1,193 @@
+"""Reading and writing archives of probing tables."""
+
+import json
+import os
+import re
+import tempfile
+import zipfile
+from typing import Dict, Iterable, List, Optional, Tuple
+
+#: Tables written by :func:`export` unless told otherwise
+DEFAULT_TABLES = ("python.torch_trace", "python.spans", "kineto.events")
+
+_RANK_DIR = re.compile(r"^rank[-_]?(\d+)$")
+_SHUTDOWN = re.compile(r"^shutdown-\d+\.json$")
+_CONFIG = "config.json"
+_SUFFIXES = (".parquet", ".csv")
+
+
+def polars():
+    """Import polars, with a hint on how to install it."""
+    try:
+        import polars
+    except ImportError as e:
+        raise ImportError(
+            'probing.analysis requires polars: pip install "probing[analysis]"'
+        ) from e
+    return polars
+
+
+class Archive:
+    """Tables of an exported archive, merged across ranks.
+
+    Parameters
+    ----------
+    path : str
+        Directory or zip file of the archive.
+    """
+
+    def __init__(self, path: str):
+        self.path = path
+        self._tmp = None
+        root = path
+        if os.path.isfile(path) and zipfile.is_zipfile(path):
+            self._tmp = tempfile.TemporaryDirectory(prefix="probing-archive-")
+            with zipfile.ZipFile(path) as zf:
+                zf.extractall(self._tmp.name)
+            root = self._tmp.name
+        elif not os.path.isdir(path):
+            raise FileNotFoundError(f"no archive at {path}")
+        self._root = root
+        self._files = _discover(root)
+        self._cache = {}
+
+    @property
+    def tables(self) -> List[str]:
+        """Names of the tables in the archive."""
+        return sorted(self._files)
+
+    @property
+    def ranks(self) -> List[int]:
+        """Ranks found in the archive, ``[0]`` for single process ones."""
+        ranks = set()
+        for name in self._files:
+            table = self.table(name)
+            ranks.update(table.get_column("rank").unique().to_list())
+        return sorted(r for r in ranks if r is not None) or [0]
+
+    @property
+    def shutdowns(self) -> List[Dict]:
+        """Snapshots written on SIGTERM or SIGINT with ``shutdown.capture=on``.
+
+        One dict per process, ordered by rank, see
+        :mod:`probing.inspect.shutdown`.
+        """
+        snapshots = []
+        for dirpath, _, filenames in os.walk(self._root):
+            for filename in filenames:
+                if not _SHUTDOWN.match(filename):
+                    continue
+                try:
+                    with open(os.path.join(dirpath, filename)) as f:
+                        snapshots.append(json.load(f))
+                except (OSError, ValueError):
+                    continue
+        return sorted(snapshots, key=lambda s: (s.get("rank", 0), s.get("time", 0)))
+
+    @property
+    def configs(self) -> Dict[int, Dict]:
+        """Configuration dumps written by :func:`export`, by rank.
+
+        Each is ``{"options": {...}, "store": {...}}`` as served at
+        ``/apis/config``, the active profile being ``probing.profile``.
+        """
+        configs = {}
+        for dirpath, _, filenames in os.walk(self._root):
+            if _CONFIG not in filenames:
+                continue
+            try:
+                with open(os.path.join(dirpath, _CONFIG)) as f:
+                    config = json.load(f)
+            except (OSError, ValueError):
+                continue
+            configs[_rank_of(self._root, dirpath) or 0] = config
+        return dict(sorted(configs.items()))
+
+    def __contains__(self, name: str) -> bool:
+        return name in self._files
+
+    def table(self, name: str):
+        """A table as a polars DataFrame, with a ``rank`` column."""
+        if name not in self._files:
+            raise KeyError(f"no table {name} in archive, found {self.tables}")
+        if name not in self._cache:
+            pl = polars()
+            frames = []
+            for rank, path in self._files[name]:
+                if path.endswith(".csv"):
+                    frame = pl.read_csv(path)
+                else:
+                    frame = pl.read_parquet(path)
+                if "rank" not in frame.columns:
+                    frame = frame.with_columns(
+                        pl.lit(0 if rank is None else rank, dtype=pl.Int64).alias(
+                            "rank"
+                        )
+                    )
+                frames.append(frame)
+            self._cache[name] = pl.concat(frames, how="diagonal_relaxed")
+        return self._cache[name]
+
+
+def _rank_of(root: str, dirpath: str) -> Optional[int]:
+    rank = None
+    for part in os.path.relpath(dirpath, root).split(os.sep):
+        match = _RANK_DIR.match(part)
+        if match:
+            rank = int(match.group(1))
+    return rank
+
+
+def _discover(root: str) -> Dict[str, List[Tuple[Optional[int], str]]]:
+    files = {}
+    for dirpath, _, filenames in os.walk(root):
+        rank = _rank_of(root, dirpath)
+        for filename in sorted(filenames):
+            for suffix in _SUFFIXES:
+                if filename.endswith(suffix):
+                    name = filename[: -len(suffix)]
+                    path = os.path.join(dirpath, filename)
+                    files.setdefault(name, []).append((rank, path))
+    return files
+
+
+def export(
+    path: str, tables: Iterable[str] = DEFAULT_TABLES, rank: Optional[int] = None
+) -> List[str]:
+    """Write tables of this process below ``path/rank<rank>/``.
+
+    ``rank`` defaults to the ``RANK`` environment variable. Tables that fail
+    to query or are empty are skipped. The configuration, secrets masked, is
+    written next to them as ``config.json``. Returns the files written.
+    """
+    import probing
+
+    pl = polars()
+    if rank is None:
+        rank = int(os.environ.get("RANK", "0"))
+    directory = os.path.join(path, f"rank{rank}")
+    os.makedirs(directory, exist_ok=True)
+    written = []
+    for name in tables:
+        try:
+            df = probing.query(f"SELECT * FROM {name}")
+        except Exception:
+            continue
+        if not hasattr(df, "columns") or len(df) == 0:
+            continue
+        # column by column, so that pandas to polars does not need pyarrow
+        frame = pl.DataFrame({col: df[col].tolist() for col in df.columns})
+        target = os.path.join(directory, f"{name}.parquet")
+        frame.write_parquet(target)
+        written.append(target)
+
+    try:
+        config = probing.config.dump()
+    except Exception:
+        config = None
+    if config is not None:
+        target = os.path.join(directory, _CONFIG)
+        with open(target, "w") as f:
+            json.dump(config, f, indent=2, sort_keys=True)
+        written.append(target)
+    return written
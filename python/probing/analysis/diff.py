@@ -0,0 +1,131 @@
+"""``probing bundle diff``: what changed between two archives."""
+
+import argparse
+import sys
+from typing import Dict, List, Optional, Tuple
+
+from .archive import Archive
+
+#: Options of the instrumentation profiles, reported apart from the others
+_PROFILE_KEYS = ("probing.profile", "probing.profiles.")
+
+#: ``(key, before, after)``, ``None`` on the side missing the key
+Change = Tuple[str, object, object]
+
+
+def _config(archive: Archive) -> Dict[str, str]:
+    """Options and entries of the lowest rank that wrote its configuration."""
+    configs = archive.configs
+    if not configs:
+        return {}
+    config = next(iter(configs.values()))
+    flat = dict(config.get("store", {}))
+    flat.update(config.get("options", {}))
+    return flat
+
+
+def _metrics(archive: Archive) -> Dict[str, object]:
+    """Key summary metrics, those of missing tables left out."""
+    from .metrics import step_breakdown, stragglers
+
+    metrics: Dict[str, object] = {"ranks": len(archive.ranks)}
+    if "python.torch_trace" in archive:
+        steps = step_breakdown(archive)
+        metrics["steps"] = steps.group_by("rank").len().get_column("len").max()
+        for column in ("total", "forward", "backward", "optimizer"):
+            metrics[f"mean {column} (s)"] = steps.get_column(column).mean()
+        metrics["stragglers"] = stragglers(archive).get_column("straggler").sum()
+    shutdowns = archive.shutdowns
+    if shutdowns:
+        metrics["shutdown signals"] = ", ".join(
+            sorted({str(s.get("signal")) for s in shutdowns})
+        )
+        steps = [s["step"] for s in shutdowns if s.get("step") is not None]
+        if steps:
+            metrics["shutdown step"] = max(steps)
+    return metrics
+
+
+def _changes(before: Dict, after: Dict) -> List[Change]:
+    return [
+        (key, before.get(key), after.get(key))
+        for key in sorted(set(before) | set(after))
+        if before.get(key) != after.get(key)
+    ]
+
+
+def diff(before: Archive, after: Archive) -> Dict[str, List[Change]]:
+    """Differences between two archives, by section.
+
+    * ``config``: options and configuration entries, of the lowest rank;
+    * ``profile``: the active profile and the profiles defined;
+    * ``metrics``: ranks, steps, mean step and stage times, stragglers and
+      the shutdown snapshots.
+
+    Each section lists the ``(key, before, after)`` that differ.
+    """
+    config_before, config_after = _config(before), _config(after)
+
+    def is_profile(key: str) -> bool:
+        return key == _PROFILE_KEYS[0] or key.startswith(_PROFILE_KEYS[1])
+
+    def split(config: Dict[str, str], profile: bool) -> Dict[str, str]:
+        return {k: v for k, v in config.items() if is_profile(k) == profile}
+
+    return {
+        "config": _changes(split(config_before, False), split(config_after, False)),
+        "profile": _changes(split(config_before, True), split(config_after, True)),
+        "metrics": _changes(_metrics(before), _metrics(after)),
+    }
+
+
+def _value(value) -> str:
+    if value is None:
+        return "-"
+    if isinstance(value, float):
+        return f"{value:.6g}"
+    return str(value)
+
+
+def format_diff(changes: Dict[str, List[Change]]) -> str:
+    """The differences as text, numbers with their relative change."""
+    lines = []
+    for section, rows in changes.items():
+        if not rows:
+            continue
+        lines.append(f"{section}:")
+        for key, before, after in rows:
+            line = f"  {key}: {_value(before)} -> {_value(after)}"
+            numbers = (int, float)
+            if (
+                isinstance(before, numbers)
+                and isinstance(after, numbers)
+                and not isinstance(before, bool)
+                and before
+            ):
+                line += f" ({(after - before) / before:+.1%})"
+            lines.append(line)
+    return "\n".join(lines) if lines else "no differences"
+
+
+def main(argv: Optional[List[str]] = None) -> int:
+    parser = argparse.ArgumentParser(
+        prog="probing bundle diff",
+        description="Compare the configuration, profiles and key metrics of "
+        "two archives exported by probing.analysis.export",
+    )
+    parser.add_argument("before", help="directory or zip of the good run")
+    parser.add_argument("after", help="directory or zip of the run to compare")
+    args = parser.parse_args(argv)
+
+    try:
+        before, after = Archive(args.before), Archive(args.after)
+        print(format_diff(diff(before, after)))
+    except (FileNotFoundError, ImportError) as e:
+        print(f"error: {e}", file=sys.stderr)
+        return 1
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
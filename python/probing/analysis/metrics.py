@@ -0,0 +1,138 @@
+"""Canned computations over an :class:`~probing.analysis.Archive`."""
+
+from typing import Optional
+
+from .archive import Archive, polars
+
+#: Stages of ``python.torch_trace`` and the columns they are summed into
+STAGES = (("forward", "forward"), ("backward", "backward"), ("step", "optimizer"))
+
+
+def _torch_trace(archive: Archive):
+    """Timed rows of ``python.torch_trace``, stages without pre/post."""
+    pl = polars()
+    trace = archive.table("python.torch_trace")
+    # pre hooks only mark the start of a stage, their duration is zero
+    return trace.filter(
+        pl.col("step").is_not_null() & ~pl.col("stage").str.starts_with("pre ")
+    ).with_columns(pl.col("stage").str.replace(r"^post ", ""))
+
+
+def step_breakdown(archive: Archive):
+    """Seconds spent in each stage of every step of every rank.
+
+    Nested modules are traced as well as the modules containing them, so
+    only the outermost modules traced in a step and stage are summed; the
+    root module, which has no name, is traced as ``None``.
+
+    Returns a DataFrame of ``rank, step, forward, backward, optimizer,
+    total``.
+    """
+    pl = polars()
+    depth = (
+        pl.when(pl.col("module") == "None")
+        .then(-1)
+        .otherwise(pl.col("module").str.count_matches(r"\."))
+    )
+    trace = _torch_trace(archive).with_columns(depth.alias("depth"))
+    outermost = trace.filter(
+        pl.col("depth") == pl.col("depth").min().over("rank", "step", "stage")
+    )
+    columns = [name for _, name in STAGES]
+    return (
+        outermost.group_by("rank", "step")
+        .agg(
+            pl.col("duration").filter(pl.col("stage") == stage).sum().alias(name)
+            for stage, name in STAGES
+        )
+        .with_columns(pl.sum_horizontal(columns).alias("total"))
+        .sort("rank", "step")
+    )
+
+
+def stragglers(archive: Archive, threshold: float = 1.1):
+    """Ranks whose steps take longer than those of the other ranks.
+
+    ``ratio`` is the mean step time of a rank over the median of the means
+    of all ranks; ranks above ``threshold`` are flagged as stragglers.
+    ``slowest`` counts the steps in which the rank was the slowest.
+
+    Returns a DataFrame of ``rank, steps, mean, p50, max, slowest, ratio,
+    straggler``, slowest ranks first.
+    """
+    pl = polars()
+    steps = step_breakdown(archive).with_columns(
+        (pl.col("total") == pl.col("total").max().over("step")).alias("is_slowest")
+    )
+    per_rank = steps.group_by("rank").agg(
+        pl.len().alias("steps"),
+        pl.col("total").mean().alias("mean"),
+        pl.col("total").median().alias("p50"),
+        pl.col("total").max().alias("max"),
+        pl.col("is_slowest").sum().alias("slowest"),
+    )
+    return (
+        per_rank.with_columns(
+            (pl.col("mean") / pl.col("mean").median()).alias("ratio")
+        )
+        .with_columns((pl.col("ratio") > threshold).alias("straggler"))
+        .sort("ratio", descending=True)
+    )
+
+
+def _op_stats(archive: Archive, source: str):
+    pl = polars()
+    if source == "kineto":
+        events = archive.table("kineto.events").filter(
+            pl.col("cat").is_in(["cpu_op", "kernel"])
+            & pl.col("duration").is_not_null()
+        )
+        ops = events.select(
+            pl.col("cat").alias("kind"),
+            pl.col("name").alias("op"),
+            (pl.col("duration") / 1e9).alias("duration"),
+        )
+    else:
+        ops = _torch_trace(archive).select(
+            pl.col("stage").alias("kind"), pl.col("module").alias("op"), "duration"
+        )
+    return ops.group_by("kind", "op").agg(
+        pl.len().alias("count"), pl.col("duration").mean().alias("mean")
+    )
+
+
+def regressions(
+    archive: Archive,
+    baseline: Archive,
+    top: int = 20,
+    source: Optional[str] = None,
+):
+    """Operators whose mean duration grew the most since ``baseline``.
+
+    Operators are the CPU ops and kernels of ``kineto.events`` when both
+    archives have profiler traces, the modules of ``python.torch_trace``
+    otherwise; ``source`` forces ``"kineto"`` or ``"torch"``.
+
+    Returns a DataFrame of ``kind, op, count, mean, baseline, delta,
+    ratio``, with durations in seconds, largest delta first.
+    """
+    pl = polars()
+    if source is None:
+        both = "kineto.events" in archive and "kineto.events" in baseline
+        source = "kineto" if both else "torch"
+    if source not in ("kineto", "torch"):
+        raise ValueError(f"unknown source {source}, expected kineto or torch")
+    current = _op_stats(archive, source)
+    before = _op_stats(baseline, source).select(
+        "kind", "op", pl.col("mean").alias("baseline")
+    )
+    return (
+        current.join(before, on=["kind", "op"])
+        .with_columns(
+            (pl.col("mean") - pl.col("baseline")).alias("delta"),
+            (pl.col("mean") / pl.col("baseline")).alias("ratio"),
+        )
+        .filter(pl.col("delta") > 0)
+        .sort("delta", descending=True)
+        .head(top)
+    )
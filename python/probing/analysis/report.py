@@ -0,0 +1,82 @@
+"""Self-contained HTML reports of an archive."""
+
+import html
+from typing import List, Optional, Tuple
+
+from .archive import Archive
+from .metrics import regressions, step_breakdown, stragglers
+
+_STYLE = """
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; }
+th { background: #f0f0f0; }
+td.text { text-align: left; }
+tr.flagged td { background: #fde2e2; }
+"""
+
+
+def _cell(value) -> str:
+    if isinstance(value, float):
+        return f"<td>{value:.6g}</td>"
+    if isinstance(value, (bool, int)) or value is None:
+        return f"<td>{html.escape(str(value))}</td>"
+    return f'<td class="text">{html.escape(str(value))}</td>'
+
+
+def render_table(df, flag: Optional[str] = None) -> str:
+    """A DataFrame as an HTML table; rows where ``flag`` is true stand out."""
+    head = "".join(f"<th>{html.escape(c)}</th>" for c in df.columns)
+    rows = []
+    for row in df.iter_rows(named=True):
+        attr = ' class="flagged"' if flag and row.get(flag) else ""
+        cells = "".join(_cell(v) for v in row.values())
+        rows.append(f"<tr{attr}>{cells}</tr>")
+    return f"<table><tr>{head}</tr>{''.join(rows)}</table>"
+
+
+def render_report(
+    archive: Archive,
+    baseline: Optional[Archive] = None,
+    top: int = 20,
+    threshold: float = 1.1,
+    title: Optional[str] = None,
+) -> str:
+    """Step breakdown, stragglers and regressions as a single HTML page.
+
+    Sections whose tables are missing from the archive are left out.
+    """
+    sections: List[Tuple[str, str]] = []
+    if "python.torch_trace" in archive:
+        steps = step_breakdown(archive)
+        per_step = (
+            steps.drop("rank").group_by("step").mean().sort("step")
+            if len(archive.ranks) > 1
+            else steps.drop("rank")
+        )
+        sections.append(("Step time breakdown (seconds)", render_table(per_step)))
+        sections.append(
+            (
+                f"Stragglers (ratio above {threshold})",
+                render_table(stragglers(archive, threshold), flag="straggler"),
+            )
+        )
+    if baseline is not None:
+        try:
+            table = render_table(regressions(archive, baseline, top=top))
+        except KeyError:
+            table = "<p>No operator timings in both archives.</p>"
+        sections.append((f"Top {top} regressed operators vs {baseline.path}", table))
+
+    title = title or f"probing analysis of {archive.path}"
+    body = "".join(
+        f"<h2>{html.escape(name)}</h2>{content}" for name, content in sections
+    )
+    if not sections:
+        found = html.escape(", ".join(archive.tables))
+        body = f"<p>No known tables in the archive, found: {found}</p>"
+    return (
+        '<!DOCTYPE html><html><head><meta charset="utf-8">'
+        f"<title>{html.escape(title)}</title><style>{_STYLE}</style></head>"
+        f"<body><h1>{html.escape(title)}</h1>{body}</body></html>"
+    )
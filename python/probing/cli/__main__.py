@@ -7,6 +7,16 @@ os.environ["PROBING_CLI_MODE"] = "1"
 
 def main():
     """Entry point for the probing CLI command."""
+    # offline analysis runs in Python, without a target
+    if sys.argv[1:2] == ["analyze"]:
+        from probing.analysis.__main__ import main as analyze
+
+        sys.exit(analyze(sys.argv[2:]))
+    if sys.argv[1:3] == ["bundle", "diff"]:
+        from probing.analysis.diff import main as bundle_diff
+
+        sys.exit(bundle_diff(sys.argv[3:]))
+
     import probing
 
     probing.cli_main(["probing"] + sys.argv[1:])
@@ -37,3 +37,40 @@ def len():
 
 def is_empty():
     return _core.config_is_empty()
+
+
+def dump():
+    """Extension options and configuration entries, secrets masked.
+
+    Returns ``{"options": {...}, "store": {...}}`` with sorted keys, as
+    served at ``/apis/config``.
+    """
+    import json
+
+    return json.loads(_core.config_dump())
+
+
+def allowed_dirs():
+    """Directories the file API serves, see ``files.allowed_dirs``."""
+    return _core.config_allowed_dirs()
+
+
+# whether the training reports its own steps, see `report_step`
+_reported = False
+
+
+def report_step(step):
+    """Report the training step to the scheduler of configuration changes.
+
+    The optimizer hooks of probing count the steps of the first optimizer;
+    loops that know their step better, e.g. when resumed from a checkpoint,
+    report it themselves and the count is no longer reported.
+    """
+    global _reported
+    _reported = True
+    _core.config_report_step(int(step))
+
+
+def _count_step(step):
+    if not _reported:
+        _core.config_report_step(step)
@@ -23,7 +23,7 @@ Examples:
 """
 
 
-def query(sql: str) -> "DataFrame":  # noqa: F821
+def query(sql: str, session: str = None) -> "DataFrame":  # noqa: F821
     """
     Execute a SQL query and return the result as a pandas DataFrame.
 
@@ -34,6 +34,10 @@ def query(sql: str) -> "DataFrame":  # noqa: F821
 
     Args:
         sql (str): The SQL query string to execute.
+        session (str, optional): Query session to run in. Tables created with
+            `CREATE TEMP TABLE` are visible to later queries of the same
+            session only, and dropped when the REPL session of the same name
+            is closed or the session has been idle for 30 minutes.
 
     Returns:
         pandas.DataFrame: The query results as a DataFrame. If conversion fails,
@@ -54,7 +58,7 @@ def query(sql: str) -> "DataFrame":  # noqa: F821
     # Import query_json from _core module
     from probing import _core
 
-    ret = _core.query_json(sql)
+    ret = _core.query_json(sql, session)
     try:
         import json
 
@@ -49,6 +49,8 @@ def table(name_or_class: Optional[Union[str, Type[Any]]] = None):
         Adds multiple instances to the table.
     take(n) : classmethod
         Retrieves n rows from the table.
+    flush() : classmethod
+        Stores the rows queued by all threads.
     drop() : classmethod
         Deletes the table.
     save() : instancemethod
@@ -58,8 +60,11 @@ def table(name_or_class: Optional[Union[str, Type[Any]]] = None):
     ------
     TypeError
         If the decorated class is not a dataclass.
-    ValueError
-        If a table with the same name but different fields already exists.
+    probing.SchemaError
+        From `save` or `append`, when the row does not fit the types of the
+        table, or from `flush` for a queued row rejected since the previous
+        flush. A table that already exists with other fields is reused,
+        fields it lacks are added as nullable columns.
 
     Examples
     --------
@@ -96,11 +101,7 @@ def table(name_or_class: Optional[Union[str, Type[Any]]] = None):
         def init_table():
             try:
                 table = probing.ExternalTable.get(table_name)
-                if table.names() != fields:
-                    raise ValueError(
-                        f"Table {table_name} already exists with different fields"
-                    )
-            except Exception:
+            except ValueError:
                 table = probing.ExternalTable(table_name, fields)
             cache[cls] = table
             return table
@@ -108,7 +109,12 @@ def table(name_or_class: Optional[Union[str, Type[Any]]] = None):
         @classmethod
         def append(cls, self):
             table = cache[cls]
-            table.append(dataclasses.astuple(self))
+            if table.names() == fields:
+                table.append(dataclasses.astuple(self))
+            else:
+                # the table was created with other fields, rows are matched by
+                # name and the schema evolves, see ExternalTable.append_dict
+                table.append_dict({f: getattr(self, f) for f in fields})
 
         @classmethod
         def append_many(cls, self):
@@ -120,6 +126,10 @@ def table(name_or_class: Optional[Union[str, Type[Any]]] = None):
             table = cache[cls]
             return table.take(n)
 
+        @classmethod
+        def flush(cls):
+            cache[cls].flush()
+
         @classmethod
         def drop(cls):
             table = cache[cls]
@@ -133,6 +143,7 @@ def table(name_or_class: Optional[Union[str, Type[Any]]] = None):
         setattr(cls, "append", append)
         setattr(cls, "append_many", append_many)
         setattr(cls, "take", take)
+        setattr(cls, "flush", flush)
         setattr(cls, "drop", drop)
         setattr(cls, "save", save)
         init_table()
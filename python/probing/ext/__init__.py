@@ -11,6 +11,10 @@ Responsibilities:
 3.  Normalize framework-specific events into Probing spans.
 
 Submodules:
+- `dask`: Dask task tracing.
+- `inference`: KV cache and request metrics of inference servers (vLLM).
 - `ray`: Ray task and actor tracing.
+- `tasks`: `ray.tasks` / `dask.tasks` and trace propagation into task workers.
+- `trainer`: Spans and step metrics of Hugging Face and Lightning trainers.
 - `torch`: PyTorch profiling hooks and utilities.
 """
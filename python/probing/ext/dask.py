@@ -0,0 +1,13 @@
+"""Dask integration.
+
+Functions submitted with ``distributed.Client.submit`` and ``Client.map`` are
+recorded in ``dask.tasks`` by the workers running them, in a span continuing
+the trace of the span submitting them, see :mod:`probing.ext.tasks`.
+"""
+
+
+def init():
+    """Initialize Dask integration (called by import hook)."""
+    from probing.ext.tasks import instrument_dask
+
+    instrument_dask()
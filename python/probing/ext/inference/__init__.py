@@ -0,0 +1,148 @@
+"""Inference server integrations.
+
+Serving engines embedded in the probed process keep their own stats about
+the KV cache and the requests they serve. An integration scrapes them into
+``inference.kv_cache`` and ``inference.requests``, so that a latency spike
+can be matched with the cache pressure and the preemptions around it.
+
+Integrations are keyed by the module that enables them, and initialized by
+the import hook when the program imports that module::
+
+    from probing.ext import inference
+
+    def init(*args):
+        ...  # patch the engine, then call inference.record_kv_cache(...)
+
+    inference.register_integration("my_server", init)
+
+``vllm`` is supported out of the box, see :mod:`probing.ext.inference.vllm`.
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT time, gpu_usage, preemptions FROM inference.kv_cache"
+... )  # doctest: +SKIP
+"""
+
+import time
+from typing import Callable, Dict, Optional
+
+#: Seconds between two KV cache samples of an engine
+SAMPLE_INTERVAL = 1.0
+
+
+def _core():
+    from probing import _core
+
+    return _core
+
+
+def record_kv_cache(
+    source: str,
+    engine: str,
+    gpu_blocks: Optional[int] = None,
+    free_gpu_blocks: Optional[int] = None,
+    gpu_usage: Optional[float] = None,
+    cpu_usage: Optional[float] = None,
+    prefix_hit_rate: Optional[float] = None,
+    running: Optional[int] = None,
+    waiting: Optional[int] = None,
+    swapped: Optional[int] = None,
+    preemptions: Optional[int] = None,
+    time_ns: Optional[int] = None,
+) -> None:
+    """Record a sample of the KV cache of ``engine``."""
+    _core()._record_kv_cache(
+        [
+            dict(
+                time=time_ns if time_ns is not None else time.time_ns(),
+                source=source,
+                engine=engine,
+                gpu_blocks=gpu_blocks,
+                free_gpu_blocks=free_gpu_blocks,
+                gpu_usage=gpu_usage,
+                cpu_usage=cpu_usage,
+                prefix_hit_rate=prefix_hit_rate,
+                running=running,
+                waiting=waiting,
+                swapped=swapped,
+                preemptions=preemptions,
+            )
+        ]
+    )
+
+
+def record_request(
+    source: str,
+    engine: str,
+    request_id: str,
+    prompt_tokens: Optional[int] = None,
+    output_tokens: Optional[int] = None,
+    queue: Optional[float] = None,
+    ttft: Optional[float] = None,
+    e2e: Optional[float] = None,
+    finish_reason: Optional[str] = None,
+    time_ns: Optional[int] = None,
+) -> None:
+    """Record a finished request, latencies are in seconds."""
+    _core()._record_inference_requests(
+        [
+            dict(
+                time=time_ns if time_ns is not None else time.time_ns(),
+                source=source,
+                engine=engine,
+                request_id=str(request_id),
+                prompt_tokens=prompt_tokens,
+                output_tokens=output_tokens,
+                queue=queue,
+                ttft=ttft,
+                e2e=e2e,
+                finish_reason=finish_reason,
+            )
+        ]
+    )
+
+
+class Sampler:
+    """Rate limit the KV cache samples of each engine.
+
+    Engines are scraped from their step, which runs far more often than a
+    sample is worth taking.
+    """
+
+    def __init__(
+        self,
+        interval: float = SAMPLE_INTERVAL,
+        clock: Callable[[], float] = time.monotonic,
+    ):
+        self.interval = interval
+        self.clock = clock
+        self.last: Dict[str, float] = {}
+
+    def due(self, engine: str) -> bool:
+        now = self.clock()
+        last = self.last.get(engine)
+        if last is not None and now - last < self.interval:
+            return False
+        self.last[engine] = now
+        return True
+
+
+def register_integration(module: str, init: Callable) -> None:
+    """Run ``init`` once ``module`` is imported, or now if it already is."""
+    from probing.hooks.import_hook import register_module_callback
+
+    register_module_callback(module, init)
+
+
+def _vllm_init(*args):
+    from probing.ext.inference import vllm
+
+    vllm.init()
+
+
+#: Integrations shipped with probing
+integrations = {
+    "vllm": _vllm_init,
+}
@@ -0,0 +1,184 @@
+"""vLLM integration.
+
+``LLMEngine.step`` (and ``step_async`` of the engine behind
+``AsyncLLMEngine``) is wrapped: the request outputs it returns give the
+finished requests and their metrics, and the schedulers of the engine are
+sampled for the KV cache occupancy at most every
+:data:`~probing.ext.inference.SAMPLE_INTERVAL` seconds.
+
+Engines running their scheduler in another process, as the V1 engine core
+does by default, only report their requests.
+"""
+
+import functools
+import sys
+from typing import Any, Dict, Iterable, Optional
+
+from probing.ext import inference
+
+SOURCE = "vllm"
+
+ENGINE_CLASSES = (
+    ("vllm.engine.llm_engine", "LLMEngine"),
+    ("vllm.engine.async_llm_engine", "_AsyncLLMEngine"),
+    ("vllm.v1.engine.llm_engine", "LLMEngine"),
+)
+
+
+class EngineMonitor:
+    """Turn the state of vLLM engines into rows of the inference tables.
+
+    Parameters
+    ----------
+    recorder : object
+        Provides ``record_kv_cache`` and ``record_request``, defaults to
+        :mod:`probing.ext.inference`.
+    """
+
+    def __init__(
+        self,
+        sampler: Optional[inference.Sampler] = None,
+        recorder: Any = inference,
+    ):
+        self.sampler = sampler or inference.Sampler()
+        self.recorder = recorder
+        self.names: Dict[int, str] = {}
+        self.preemptions: Dict[str, int] = {}
+
+    def name(self, engine: Any) -> str:
+        return self.names.setdefault(id(engine), str(len(self.names)))
+
+    def after_step(self, engine: Any, outputs: Any) -> None:
+        name = self.name(engine)
+        for output in outputs or ():
+            if getattr(output, "finished", False):
+                self.finished(name, output)
+        if self.sampler.due(name):
+            self.sample(name, engine)
+
+    def finished(self, name: str, output: Any) -> None:
+        completions = getattr(output, "outputs", None) or ()
+        first = completions[0] if completions else None
+        prompt = getattr(output, "prompt_token_ids", None)
+        metrics = getattr(output, "metrics", None)
+        arrival = getattr(metrics, "arrival_time", None)
+        first_token = getattr(metrics, "first_token_time", None)
+        finished = getattr(metrics, "finished_time", None) or getattr(
+            metrics, "last_token_time", None
+        )
+        self.recorder.record_request(
+            SOURCE,
+            name,
+            getattr(output, "request_id", ""),
+            prompt_tokens=len(prompt) if prompt is not None else None,
+            output_tokens=sum(len(getattr(c, "token_ids", ())) for c in completions)
+            if completions
+            else None,
+            queue=getattr(metrics, "time_in_queue", None),
+            ttft=_elapsed(arrival, first_token),
+            e2e=_elapsed(arrival, finished),
+            finish_reason=getattr(first, "finish_reason", None),
+            time_ns=int(finished * 1e9) if finished else None,
+        )
+
+    def sample(self, name: str, engine: Any) -> None:
+        schedulers = getattr(engine, "scheduler", None)
+        if not schedulers:
+            return
+        if not isinstance(schedulers, (list, tuple)):
+            schedulers = [schedulers]
+        cache_config = getattr(engine, "cache_config", None)
+        gpu_blocks = getattr(cache_config, "num_gpu_blocks", None)
+        cpu_blocks = getattr(cache_config, "num_cpu_blocks", None)
+
+        free_gpu = _sum(s.block_manager.get_num_free_gpu_blocks() for s in schedulers)
+        free_cpu = _sum(s.block_manager.get_num_free_cpu_blocks() for s in schedulers)
+        preempted = _sum(getattr(s, "num_cumulative_preemption", 0) for s in schedulers)
+        previous = self.preemptions.get(name)
+        self.preemptions[name] = preempted
+
+        self.recorder.record_kv_cache(
+            SOURCE,
+            name,
+            gpu_blocks=gpu_blocks,
+            free_gpu_blocks=free_gpu,
+            gpu_usage=_usage(gpu_blocks, free_gpu),
+            cpu_usage=_usage(cpu_blocks, free_cpu),
+            prefix_hit_rate=_prefix_hit_rate(schedulers),
+            running=_sum(len(s.running) for s in schedulers),
+            waiting=_sum(len(s.waiting) for s in schedulers),
+            swapped=_sum(len(getattr(s, "swapped", ())) for s in schedulers),
+            preemptions=preempted - previous if previous is not None else 0,
+        )
+
+
+def _sum(values: Iterable[int]) -> int:
+    return sum(int(v) for v in values)
+
+
+def _elapsed(start: Optional[float], end: Optional[float]) -> Optional[float]:
+    if start is None or end is None:
+        return None
+    return float(end - start)
+
+
+def _usage(total: Optional[int], free: int) -> Optional[float]:
+    if not total:
+        return None
+    return 1.0 - free / (total * 1.0)
+
+
+def _prefix_hit_rate(schedulers) -> Optional[float]:
+    device = getattr(sys.modules.get("vllm.utils"), "Device", None)
+    if device is None:
+        return None
+    try:
+        rates = [s.get_prefix_cache_hit_rate(device.GPU) for s in schedulers]
+    except Exception:
+        return None
+    rates = [r for r in rates if r is not None and r >= 0]
+    return sum(rates) / len(rates) if rates else None
+
+
+_monitor = EngineMonitor()
+
+
+def _after_step(monitor: EngineMonitor, engine: Any, outputs: Any) -> None:
+    try:
+        monitor.after_step(engine, outputs)
+    except Exception:
+        # never let bookkeeping break the serving loop
+        pass
+
+
+def instrument(cls: Any, monitor: Optional[EngineMonitor] = None) -> None:
+    """Wrap ``step`` and ``step_async`` of an engine class."""
+    monitor = monitor or _monitor
+    step = cls.__dict__.get("step")
+    if step is not None and not hasattr(step, "__wrapped__"):
+
+        @functools.wraps(step)
+        def wrapped_step(self, *args, **kwargs):
+            outputs = step(self, *args, **kwargs)
+            _after_step(monitor, self, outputs)
+            return outputs
+
+        cls.step = wrapped_step
+
+    step_async = cls.__dict__.get("step_async")
+    if step_async is not None and not hasattr(step_async, "__wrapped__"):
+
+        @functools.wraps(step_async)
+        async def wrapped_step_async(self, *args, **kwargs):
+            outputs = await step_async(self, *args, **kwargs)
+            _after_step(monitor, self, outputs)
+            return outputs
+
+        cls.step_async = wrapped_step_async
+
+
+def init() -> None:
+    for module, name in ENGINE_CLASSES:
+        cls = getattr(sys.modules.get(module), name, None)
+        if cls is not None:
+            instrument(cls)
@@ -149,9 +149,16 @@ def _wrap_task_execution_in_worker():
 
 
 def init():
-    """Initialize Ray tracing integration (called by import hook)."""
+    """Initialize Ray tracing integration (called by import hook).
+
+    Remote functions are recorded in ``ray.tasks`` and continue the trace of
+    the span submitting them, see :mod:`probing.ext.tasks`.
+    """
     # Users should explicitly use: ray.init(_tracing_startup_hook="probing.ext.ray:setup_tracing")
-    pass
+    # for the OpenTelemetry spans of Ray itself
+    from probing.ext.tasks import instrument_ray
+
+    instrument_ray()
 
 
 def setup_tracing() -> None:
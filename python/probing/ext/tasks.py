@@ -0,0 +1,293 @@
+"""Tasks of distributed task frameworks.
+
+Ray and Dask run the functions submitted as tasks in worker processes, out of
+sight of the span that submitted them. The integrations wrap these functions
+in a :class:`TaskFunction` on the submitting side, so that in the worker each
+run of a task:
+
+* is recorded in ``ray.tasks`` or ``dask.tasks`` when it starts and when it
+  ends, with the ids the framework gave the task and the worker;
+* runs in a span tagged with these ids (``ray.task_id``, ``ray.worker_id``,
+  ...), so that the spans and events of the task carry them as well;
+* continues the trace of the span that submitted it, see
+  :func:`probing.tracing.continue_trace`.
+
+A worker only records the tasks submitted from a process running probing;
+the ``probing`` package has to be importable in the workers.
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT name, state, duration FROM ray.tasks ORDER BY start DESC"
+... )  # doctest: +SKIP
+"""
+
+import functools
+import inspect
+import time
+from typing import Any, Callable, Dict, Optional
+
+from probing import tracing
+
+#: Keyword argument carrying the trace context to a task, removed before the
+#: task function is called
+CONTEXT_KWARG = "_probing_context"
+
+
+def _core():
+    from probing import _core
+
+    return _core
+
+
+def record_task(framework: str, **task) -> None:
+    """Record a task of ``framework``, replacing the row of the same task
+    recorded while it was running."""
+    row = dict.fromkeys(
+        (
+            "node_id",
+            "job_id",
+            "actor_id",
+            "end",
+            "duration",
+            "error",
+            "trace_id",
+            "span_id",
+            "parent_span_id",
+        )
+    )
+    row.update(task)
+    try:
+        _core()._record_task(framework, row)
+    except Exception:
+        # built without the `analytics` feature
+        pass
+
+
+def _ray_ids() -> Dict[str, Optional[str]]:
+    import ray
+
+    context = ray.get_runtime_context()
+
+    def get(name: str) -> Optional[str]:
+        try:
+            value = getattr(context, name)()
+        except Exception:
+            return None
+        return None if value is None else str(value)
+
+    return dict(
+        task_id=get("get_task_id"),
+        worker_id=get("get_worker_id"),
+        node_id=get("get_node_id"),
+        job_id=get("get_job_id"),
+        actor_id=get("get_actor_id"),
+    )
+
+
+def _dask_ids() -> Dict[str, Optional[str]]:
+    from distributed import get_worker
+    from distributed.worker import thread_state
+
+    try:
+        worker = get_worker()
+        worker_id, node_id = worker.address, getattr(worker, "ip", None)
+    except ValueError:
+        worker_id, node_id = None, None
+    key = getattr(thread_state, "key", None)
+    return dict(
+        task_id=None if key is None else str(key),
+        worker_id=worker_id,
+        node_id=node_id,
+    )
+
+
+#: Ids of the task running in the current thread, by framework
+IDS: Dict[str, Callable[[], Dict[str, Optional[str]]]] = {
+    "ray": _ray_ids,
+    "dask": _dask_ids,
+}
+
+
+def _task_ids(framework: str) -> Dict[str, Optional[str]]:
+    try:
+        return IDS[framework]()
+    except Exception:
+        return {}
+
+
+def run_task(
+    framework: str,
+    func: Callable,
+    args: tuple,
+    kwargs: dict,
+    context: Optional[dict] = None,
+) -> Any:
+    """Run ``func`` as a task of ``framework``: record it and run it in a
+    span continuing the trace of ``context``."""
+    ids = _task_ids(framework)
+    name = getattr(func, "__qualname__", None) or repr(func)
+    task_id = ids.get("task_id") or f"{name}-{time.time_ns()}"
+    task = dict(
+        (key, value) for key, value in ids.items() if key != "task_id" and value
+    )
+    task.update(task_id=task_id, name=name, worker_id=ids.get("worker_id") or "")
+    tags = {f"{framework}.{key}": value for key, value in ids.items() if value}
+    context = context or {}
+
+    with tracing.continue_trace(
+        context, f"{framework}.task", kind="consumer", task=name, **tags
+    ) as span:
+        start = time.time_ns()
+        ids = dict(
+            trace_id=getattr(span, "trace_id", None),
+            span_id=getattr(span, "span_id", None),
+            parent_span_id=context.get("span_id"),
+        )
+        record_task(framework, state="running", start=start, **task, **ids)
+        state, error = "failed", None
+        try:
+            result = func(*args, **kwargs)
+            state = "finished"
+            return result
+        except BaseException as exc:
+            error = f"{type(exc).__name__}: {exc}"
+            raise
+        finally:
+            end = time.time_ns()
+            record_task(
+                framework,
+                state=state,
+                start=start,
+                end=end,
+                duration=(end - start) / 1e9,
+                error=error,
+                **task,
+                **ids,
+            )
+
+
+class TaskFunction:
+    """A task function run with :func:`run_task`.
+
+    The context of the submitting span is taken from the ``_probing_context``
+    keyword argument of each call, or else from ``context``. The signature
+    accepts that argument, so frameworks checking the arguments of a task
+    against the signature of its function let it through.
+    """
+
+    def __init__(self, func: Callable, framework: str, context: Optional[dict] = None):
+        functools.update_wrapper(self, func)
+        self.func = func
+        self.framework = framework
+        self.context = context
+        try:
+            signature = inspect.signature(func)
+            if CONTEXT_KWARG not in signature.parameters:
+                signature = _with_context_parameter(signature)
+            self.__signature__ = signature
+        except (TypeError, ValueError):
+            pass
+
+    def __call__(self, *args, **kwargs):
+        context = kwargs.pop(CONTEXT_KWARG, None) or self.context
+        return run_task(self.framework, self.func, args, kwargs, context)
+
+    def __dask_tokenize__(self):
+        # the context changes with every submission, the task stays the same
+        return (TaskFunction.__qualname__, self.framework, self.func)
+
+    def __reduce__(self):
+        return (TaskFunction, (self.func, self.framework, self.context))
+
+
+def _with_context_parameter(signature: inspect.Signature) -> inspect.Signature:
+    params = list(signature.parameters.values())
+    context = inspect.Parameter(
+        CONTEXT_KWARG, inspect.Parameter.KEYWORD_ONLY, default=None
+    )
+    # keyword-only parameters go before `**kwargs`
+    if params and params[-1].kind == inspect.Parameter.VAR_KEYWORD:
+        params.insert(len(params) - 1, context)
+    else:
+        params.append(context)
+    return signature.replace(parameters=params)
+
+
+def _wrappable(func: Any) -> bool:
+    return (
+        callable(func)
+        and not isinstance(func, TaskFunction)
+        and not inspect.iscoroutinefunction(func)
+        and not inspect.isclass(func)
+    )
+
+
+def instrument_ray() -> bool:
+    """Run the remote functions of Ray as tasks recorded in ``ray.tasks``,
+    passing the trace context along with the arguments of each call.
+
+    Returns whether Ray could be instrumented.
+    """
+    try:
+        from ray.remote_function import RemoteFunction
+    except ImportError:
+        return False
+    if getattr(RemoteFunction, "_probing_instrumented", False):
+        return True
+
+    original_init = RemoteFunction.__init__
+    original_remote = RemoteFunction._remote
+
+    @functools.wraps(original_init)
+    def __init__(self, language, function, *args, **kwargs):
+        if _wrappable(function):
+            function = TaskFunction(function, "ray")
+        original_init(self, language, function, *args, **kwargs)
+
+    @functools.wraps(original_remote)
+    def _remote(self, args=None, kwargs=None, **options):
+        context = tracing.inject()
+        if context and isinstance(getattr(self, "_function", None), TaskFunction):
+            kwargs = dict(kwargs or {}, **{CONTEXT_KWARG: context})
+        return original_remote(self, args, kwargs, **options)
+
+    RemoteFunction.__init__ = __init__
+    RemoteFunction._remote = _remote
+    RemoteFunction._probing_instrumented = True
+    return True
+
+
+def instrument_dask() -> bool:
+    """Run the functions submitted to a Dask ``Client`` as tasks recorded in
+    ``dask.tasks``, in the trace of the span submitting them.
+
+    Returns whether Dask could be instrumented.
+    """
+    try:
+        from distributed import Client
+    except ImportError:
+        return False
+    if getattr(Client, "_probing_instrumented", False):
+        return True
+
+    original_submit = Client.submit
+    original_map = Client.map
+
+    @functools.wraps(original_submit)
+    def submit(self, func, *args, **kwargs):
+        if _wrappable(func):
+            func = TaskFunction(func, "dask", tracing.inject() or None)
+        return original_submit(self, func, *args, **kwargs)
+
+    @functools.wraps(original_map)
+    def map(self, func, *iterables, **kwargs):
+        if _wrappable(func):
+            func = TaskFunction(func, "dask", tracing.inject() or None)
+        return original_map(self, func, *iterables, **kwargs)
+
+    Client.submit = submit
+    Client.map = map
+    Client._probing_instrumented = True
+    return True
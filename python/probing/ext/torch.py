@@ -4,6 +4,12 @@ import probing
 
 hooks = {}
 
+# `torch_probe.GENERATION` each optimizer was set up with
+generations = {}
+
+# optimizer steps, for the steps of `config schedule`
+steps = {}
+
 
 def is_true(value):
     if value in ["TRUE", "True", "true", "1", "YES", "Yes", "yes", "ON", "On", "on"]:
@@ -13,11 +19,31 @@ def is_true(value):
 
 def optimizer_step_post_hook(optimizer, *args, **kwargs):
     global hooks
+    from probing.profiling import dynamo, fsdp, grad_stats
+
+    dynamo.collect()
+    fsdp.collect()
+    grad_stats.collect(optimizer)
+    count_step(optimizer)
+
+    from probing.profiling import torch_probe
+
+    if optimizer in hooks and generations.get(optimizer) != torch_probe.GENERATION:
+        # reconfigured since, e.g. by `config schedule`: set up with the new spec
+        from probing.profiling.torch import uninstall_hooks
+
+        tracer = hooks.pop(optimizer)
+        if tracer is not None:
+            tracer.enabled = False
+        uninstall_hooks()
+
     if optimizer not in hooks:
         from probing.profiling.torch import install_hooks
         from probing.profiling.torch.module_utils import get_toplevel_module
         from probing.profiling.torch_probe import TorchProbe, TorchProbeConfig
 
+        generations[optimizer] = torch_probe.GENERATION
+
         # Get config directly from probing.config
         # Rust sync_env_settings() converts PROBING_TORCH_PROFILING to probing.torch.profiling
         spec = probing.config.get_str("probing.torch.profiling")
@@ -52,6 +78,15 @@ def optimizer_step_post_hook(optimizer, *args, **kwargs):
         next_step()
 
 
+def count_step(optimizer):
+    """Count the steps of the first optimizer, so that training with several
+    optimizers still reports one step per iteration."""
+    if steps and optimizer not in steps:
+        return
+    steps[optimizer] = steps.get(optimizer, 0) + 1
+    probing.config._count_step(steps[optimizer])
+
+
 def collective_hook():
     """Initialize collective profiling if enabled."""
     # Get config directly from probing.config
@@ -72,6 +107,10 @@ def init():
 
     collective_hook()
 
+    from probing.profiling import oom
+
+    oom.install()
+
 
 def deinit():
     from probing.profiling.torch import uninstall_hooks
@@ -0,0 +1,314 @@
+"""Trainer integrations.
+
+The training loops of ``transformers.Trainer`` and PyTorch Lightning call
+back into user code at the start and end of training and of every step. With
+``integrations.hf=on`` (``PROBING_INTEGRATIONS_HF=on``), a callback is added
+to every trainer built afterwards, so that without changing the script:
+
+* training runs in a ``trainer.train`` span and every optimizer step in a
+  ``trainer.step`` span below it, tagged with the step and the epoch;
+* every step appends a row to ``python.trainer_steps`` with its duration and
+  the metrics the trainer logged for it, such as the loss and the learning
+  rate.
+
+The trainer classes are patched when ``transformers.trainer``,
+``lightning.pytorch`` or ``pytorch_lightning`` is imported, and the setting is
+read when a trainer is built, so it can be changed at runtime. Callbacks
+passed to a trainer explicitly are used as they are, see
+:func:`transformers_callback` and :func:`lightning_callback`.
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT step, duration, loss FROM python.trainer_steps ORDER BY step"
+... )  # doctest: +SKIP
+"""
+
+import functools
+import logging
+import math
+import os
+import sys
+import time
+from typing import Any, Dict, Optional
+
+from probing import tracing
+
+logger = logging.getLogger(__name__)
+
+#: Table the steps are appended to, served as ``python.trainer_steps``
+TABLE = "trainer_steps"
+
+#: Config key enabling the callbacks, also set by ``PROBING_INTEGRATIONS_HF``
+CONFIG_KEY = "probing.integrations.hf"
+
+
+def enabled() -> bool:
+    """Whether callbacks are added to the trainers being built."""
+    value = None
+    try:
+        import probing
+
+        value = probing.config.get_str(CONFIG_KEY)
+    except Exception:
+        pass
+    # the config store is filled from the environment in the background
+    if not value:
+        value = os.environ.get("PROBING_INTEGRATIONS_HF", "")
+    return value.lower() in ("1", "true", "yes", "on")
+
+
+def _scalar(value: Any) -> Optional[float]:
+    """A finite or non-finite float from a number or a one element tensor."""
+    if isinstance(value, bool):
+        return None
+    if hasattr(value, "numel"):
+        try:
+            if value.numel() != 1:
+                return None
+            value = value.item()
+        except Exception:
+            return None
+    if isinstance(value, (int, float)):
+        return float(value)
+    return None
+
+
+def _metrics(values: Optional[Dict[str, Any]]) -> Dict[str, float]:
+    metrics = {}
+    for name, value in (values or {}).items():
+        value = _scalar(value)
+        if value is not None:
+            # column names of the table, e.g. `train/loss` -> `train_loss`
+            metrics[str(name).replace("/", "_").replace(".", "_")] = value
+    return metrics
+
+
+class StepRecorder:
+    """Spans and step rows of one training run of ``framework``.
+
+    The step row is written when the next step starts or training ends, so
+    metrics logged right after a step, as ``transformers`` does, end up in
+    the row of that step.
+    """
+
+    def __init__(self, framework: str, table: Any = None):
+        self.framework = framework
+        self.table = table
+        self.train_span = None
+        self.step_span = None
+        self.step_started: Optional[float] = None
+        self.pending: Optional[Dict[str, Any]] = None
+
+    def _table(self):
+        if self.table is None:
+            import probing
+
+            try:
+                self.table = probing.ExternalTable.get(TABLE)
+            except ValueError:
+                self.table = probing.ExternalTable(
+                    TABLE, ["framework", "step", "epoch", "duration"]
+                )
+        return self.table
+
+    def _flush(self) -> None:
+        row, self.pending = self.pending, None
+        if row is None:
+            return
+        try:
+            self._table().append_dict(row)
+        except Exception as exc:
+            logger.debug("Failed to record %s step: %s", self.framework, exc)
+
+    def train_begin(self, **attrs) -> None:
+        if self.train_span is not None:
+            return
+        attrs = {key: value for key, value in attrs.items() if value is not None}
+        self.train_span = tracing.span(
+            "trainer.train", framework=self.framework, **attrs
+        )
+        self.train_span.__enter__()
+
+    def step_begin(self, step: int, epoch: Optional[float] = None) -> None:
+        self._flush()
+        self._close_step()
+        attrs = {"framework": self.framework, "step": step}
+        if epoch is not None:
+            attrs["epoch"] = epoch
+        self.step_span = tracing.span("trainer.step", **attrs)
+        self.step_span.__enter__()
+        self.step_started = time.perf_counter()
+
+    def step_end(
+        self,
+        step: int,
+        epoch: Optional[float] = None,
+        metrics: Optional[Dict[str, Any]] = None,
+    ) -> None:
+        duration = None
+        if self.step_started is not None:
+            duration = time.perf_counter() - self.step_started
+            self.step_started = None
+        self._close_step()
+        self._flush()
+        self.pending = dict(
+            framework=self.framework,
+            step=int(step),
+            epoch=None if epoch is None else float(epoch),
+            duration=duration,
+        )
+        self.pending.update(_metrics(metrics))
+
+    def log(self, step: int, metrics: Optional[Dict[str, Any]]) -> None:
+        """Metrics logged for ``step``, merged into its row when it is not
+        written yet."""
+        metrics = _metrics(metrics)
+        if not metrics:
+            return
+        if self.pending is not None and self.pending["step"] == step:
+            self.pending.update(metrics)
+            return
+        self._flush()
+        self.pending = dict(framework=self.framework, step=int(step), **metrics)
+
+    def train_end(self) -> None:
+        self._flush()
+        self._close_step()
+        span, self.train_span = self.train_span, None
+        if span is not None:
+            span.__exit__(None, None, None)
+
+    def _close_step(self) -> None:
+        span, self.step_span = self.step_span, None
+        if span is not None:
+            span.__exit__(None, None, None)
+
+
+def _finite(value: Any) -> Optional[float]:
+    value = _scalar(value)
+    return value if value is not None and math.isfinite(value) else None
+
+
+def transformers_callback(recorder: Optional[StepRecorder] = None):
+    """A ``transformers.TrainerCallback`` recording the spans and steps of a
+    ``Trainer``."""
+    from transformers import TrainerCallback
+
+    class ProbingCallback(TrainerCallback):
+        def __init__(self):
+            self.recorder = recorder or StepRecorder("transformers")
+
+        def on_train_begin(self, args, state, control, **kwargs):
+            self.recorder.train_begin(max_steps=state.max_steps)
+
+        def on_step_begin(self, args, state, control, **kwargs):
+            # `global_step` counts the steps done, this one ends as the next
+            self.recorder.step_begin(state.global_step + 1, _finite(state.epoch))
+
+        def on_step_end(self, args, state, control, **kwargs):
+            self.recorder.step_end(state.global_step, _finite(state.epoch))
+
+        def on_log(self, args, state, control, logs=None, **kwargs):
+            self.recorder.log(state.global_step, logs)
+
+        def on_train_end(self, args, state, control, **kwargs):
+            self.recorder.train_end()
+
+    return ProbingCallback()
+
+
+def lightning_callback(base: Any, recorder: Optional[StepRecorder] = None):
+    """A callback of class ``base``, ``lightning.pytorch.Callback`` or its
+    ``pytorch_lightning`` twin, recording the spans and steps of a
+    ``Trainer``."""
+
+    class ProbingCallback(base):
+        def __init__(self):
+            self.recorder = recorder or StepRecorder("lightning")
+
+        def on_train_start(self, trainer, pl_module):
+            self.recorder.train_begin(max_steps=getattr(trainer, "max_steps", None))
+
+        def on_train_batch_start(self, trainer, pl_module, batch, batch_idx):
+            self.recorder.step_begin(trainer.global_step + 1, trainer.current_epoch)
+
+        def on_train_batch_end(self, trainer, pl_module, outputs, batch, batch_idx):
+            metrics = dict(getattr(trainer, "callback_metrics", None) or {})
+            loss = outputs.get("loss") if isinstance(outputs, dict) else outputs
+            if _scalar(loss) is not None:
+                metrics.setdefault("loss", loss)
+            self.recorder.step_end(trainer.global_step, trainer.current_epoch, metrics)
+
+        def on_train_end(self, trainer, pl_module):
+            self.recorder.train_end()
+
+    return ProbingCallback()
+
+
+def _has_probing_callback(callbacks) -> bool:
+    return any(
+        type(cb).__name__ == "ProbingCallback" and type(cb).__module__ == __name__
+        for cb in callbacks
+    )
+
+
+def instrument_transformers(trainer_cls: Any) -> None:
+    """Add the callback to the ``Trainer`` instances built while enabled."""
+    init = trainer_cls.__dict__.get("__init__")
+    if init is None or hasattr(init, "__wrapped__"):
+        return
+
+    @functools.wraps(init)
+    def __init__(self, *args, **kwargs):
+        init(self, *args, **kwargs)
+        try:
+            handler = self.callback_handler
+            if enabled() and not _has_probing_callback(handler.callbacks):
+                self.add_callback(transformers_callback())
+        except Exception as exc:
+            logger.debug("Failed to add the transformers callback: %s", exc)
+
+    trainer_cls.__init__ = __init__
+
+
+def instrument_lightning(trainer_cls: Any, callback_base: Any) -> None:
+    """Pass the callback to the ``Trainer`` instances built while enabled."""
+    init = trainer_cls.__dict__.get("__init__")
+    if init is None or hasattr(init, "__wrapped__"):
+        return
+
+    @functools.wraps(init)
+    def __init__(self, *args, **kwargs):
+        try:
+            if enabled():
+                callbacks = kwargs.get("callbacks") or []
+                if not isinstance(callbacks, (list, tuple)):
+                    callbacks = [callbacks]
+                if not _has_probing_callback(callbacks):
+                    kwargs["callbacks"] = list(callbacks) + [
+                        lightning_callback(callback_base)
+                    ]
+        except Exception as exc:
+            logger.debug("Failed to add the lightning callback: %s", exc)
+        init(self, *args, **kwargs)
+
+    trainer_cls.__init__ = __init__
+
+
+def init_transformers(*args) -> None:
+    """Patch ``transformers.Trainer`` (called by import hook)."""
+    cls = getattr(sys.modules.get("transformers.trainer"), "Trainer", None)
+    if cls is not None:
+        instrument_transformers(cls)
+
+
+def init_lightning(*args) -> None:
+    """Patch the Lightning ``Trainer`` (called by import hook)."""
+    for package in ("lightning.pytorch", "pytorch_lightning"):
+        module = sys.modules.get(package)
+        trainer = getattr(module, "Trainer", None)
+        callback = getattr(module, "Callback", None)
+        if trainer is not None and callback is not None:
+            instrument_lightning(trainer, callback)
@@ -126,7 +126,8 @@ def get_chrome_tracing(limit: int = 1000) -> str:
                 kind,
                 location,
                 attributes,
-                event_attributes
+                event_attributes,
+                cpu_time_ns
             FROM python.trace_event
             ORDER BY timestamp ASC
             {limit_clause}
@@ -202,6 +203,13 @@ def get_chrome_tracing(limit: int = 1000) -> str:
                         chrome_event["args"] = {"location": row.get("location")}
                     trace_events.append(chrome_event)
                 elif record_type == "span_end":
+                    # CPU time of the thread in the span, -1 when not measured
+                    cpu_time_ns = row.get("cpu_time_ns")
+                    cpu_args = (
+                        {"cpu_time_ns": int(cpu_time_ns)}
+                        if cpu_time_ns is not None and cpu_time_ns >= 0
+                        else None
+                    )
                     # Try to find matching span_start
                     key = (span_id, thread_id)
                     start_info = span_starts.get(key)
@@ -224,6 +232,8 @@ def get_chrome_tracing(limit: int = 1000) -> str:
                         dur = ts_micros - start_ts
                         if dur > 0:
                             chrome_event["dur"] = dur
+                        if cpu_args:
+                            chrome_event["args"] = cpu_args
                         trace_events.append(chrome_event)
                         # Remove from span_starts to avoid duplicate matches
                         del span_starts[key]
@@ -251,6 +261,8 @@ def get_chrome_tracing(limit: int = 1000) -> str:
                             dur = ts_micros - start_ts
                             if dur > 0:
                                 chrome_event["dur"] = dur
+                            if cpu_args:
+                                chrome_event["args"] = cpu_args
                             trace_events.append(chrome_event)
                         else:
                             # No matching span_start found at all
@@ -294,6 +306,120 @@ def get_chrome_tracing(limit: int = 1000) -> str:
         )
 
 
+def _overlap(intervals: List[tuple], start: int, end: int) -> int:
+    """Length of the union of ``intervals`` within ``[start, end]``."""
+    total = 0
+    cursor = start
+    for lo, hi in sorted(intervals):
+        lo, hi = max(lo, cursor), min(hi, end)
+        if hi > lo:
+            total += hi - lo
+            cursor = hi
+    return total
+
+
+def _text(value) -> Optional[str]:
+    """Non-empty string column value, None for empty or missing values."""
+    return value if isinstance(value, str) and value else None
+
+
+def build_span_tree(rows: List[Dict]) -> List[Dict]:
+    """Nest the spans of ``python.trace_event`` rows under their parents.
+
+    Each span carries its ``duration`` and ``self_time`` in nanoseconds, None
+    while it runs. The self time is the duration minus the time covered by
+    children on the same thread; children on other threads ran concurrently
+    and are not subtracted, and overlapping children are counted once.
+    """
+    spans: Dict[int, Dict] = {}
+    ends: Dict[int, int] = {}
+    events: Dict[int, List[Dict]] = {}
+    for row in rows:
+        record_type = row.get("record_type")
+        span_id = row.get("span_id")
+        if record_type == "span_start":
+            parent_id = row.get("parent_id")
+            # -1 or NULL (NaN in the frame) for roots
+            if parent_id is None or parent_id != parent_id or parent_id < 0:
+                parent_id = None
+            spans[span_id] = {
+                "span_id": span_id,
+                "trace_id": row.get("trace_id", 0),
+                "parent_id": parent_id,
+                "name": row.get("name", ""),
+                "start_timestamp": row.get("time", 0),
+                "end_timestamp": None,
+                "duration": None,
+                "self_time": None,
+                "thread_id": row.get("thread_id", 0),
+                "kind": _text(row.get("kind")),
+                "location": _text(row.get("location")),
+                "attributes": _text(row.get("attributes")),
+                "children": [],
+                "events": [],
+            }
+        elif record_type == "span_end":
+            ends[span_id] = row.get("time", 0)
+        elif record_type == "event":
+            events.setdefault(span_id, []).append(
+                {
+                    "name": row.get("name", ""),
+                    "timestamp": row.get("time", 0),
+                    "attributes": _text(row.get("event_attributes")),
+                }
+            )
+
+    roots = []
+    for span_id, span in spans.items():
+        span["end_timestamp"] = ends.get(span_id)
+        span["events"] = sorted(events.get(span_id, []), key=lambda e: e["timestamp"])
+        parent = spans.get(span["parent_id"])
+        # spans whose parent is not among the rows start a tree of their own
+        (parent["children"] if parent is not None else roots).append(span)
+
+    for span in spans.values():
+        span["children"].sort(key=lambda s: s["start_timestamp"])
+        start, end = span["start_timestamp"], span["end_timestamp"]
+        if end is None:
+            continue
+        span["duration"] = end - start
+        busy = [
+            (child["start_timestamp"], child["end_timestamp"] or end)
+            for child in span["children"]
+            if child["thread_id"] == span["thread_id"]
+        ]
+        span["self_time"] = span["duration"] - _overlap(busy, start, end)
+
+    roots.sort(key=lambda s: s["start_timestamp"])
+    return roots
+
+
+@ext_handler("pythonext", "trace/span-tree")
+def get_span_tree(limit: int = 1000) -> str:
+    """Spans of the latest ``limit`` trace events as trees, with self times.
+
+    Args:
+        limit: Maximum number of events to read (0 for no limit)
+
+    Returns:
+        JSON list of root spans, see `build_span_tree`
+    """
+    import probing.core.engine as engine
+
+    limit_clause = f" LIMIT {limit}" if limit > 0 else ""
+    df = engine.query(
+        f"""
+        SELECT record_type, trace_id, span_id, parent_id, name, time, thread_id,
+            kind, location, attributes, event_attributes
+        FROM python.trace_event
+        ORDER BY time DESC
+        {limit_clause}
+        """
+    )
+    rows = df.to_dict("records") if df is not None and not df.empty else []
+    return json.dumps(build_span_tree(rows))
+
+
 @ext_handler("pythonext", "pytorch/timeline")
 def get_pytorch_timeline() -> str:
     """Get PyTorch profiler timeline.
@@ -424,6 +550,25 @@ def get_pytorch_profile() -> str:
     return json.dumps({"error": "Not implemented"})
 
 
+@ext_handler("pythonext", "fsdp/units")
+def fsdp_units() -> str:
+    """Inspect the FSDP units again and return their sharding layout.
+
+    Returns:
+        JSON string containing one object per unit, the layout of the
+        parameters is refreshed in ``torch.fsdp_params``
+    """
+    try:
+        from probing.profiling import fsdp
+
+        monitor = fsdp.monitor()
+        if monitor is None:
+            return "[]"
+        return json.dumps(monitor.refresh())
+    except Exception as e:
+        return json.dumps({"error": str(e)})
+
+
 @ext_handler("pythonext", "trace/list")
 def list_trace(prefix: Optional[str] = None) -> str:
     """List traceable functions.
@@ -516,6 +661,270 @@ def stop_trace(function: str) -> str:
         return json.dumps({"success": False, "error": str(e)})
 
 
+@ext_handler("pythonext", "trace/watch", required_params=["expression"])
+def start_watch(
+    expression: str,
+    interval: Optional[str] = None,
+    threshold: Optional[str] = None,
+) -> str:
+    """Start sampling an expression into python.watches.
+
+    Args:
+        expression: Python expression evaluated in __main__
+        interval: Sampling interval such as "1s" or "500ms" (default 1s)
+        threshold: Minimal numeric change that emits a trace event (default 0)
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.watch import watch
+
+        watch(
+            expression,
+            interval=interval or 1.0,
+            threshold=float(threshold) if threshold else 0.0,
+        )
+        return json.dumps({"success": True, "message": f"Watching {expression}"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "trace/unwatch", required_params=["expression"])
+def stop_watch(expression: str) -> str:
+    """Stop sampling an expression.
+
+    Args:
+        expression: Expression passed to trace/watch
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.watch import unwatch
+
+        if not unwatch(expression):
+            return json.dumps({"success": False, "error": f"{expression} is not watched"})
+        return json.dumps({"success": True, "message": f"Stopped watching {expression}"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "trace/watches")
+def get_watches() -> str:
+    """List watched expressions with their last value.
+
+    Returns:
+        JSON string containing the watches
+    """
+    try:
+        from probing.inspect.watch import list_watches
+
+        return json.dumps(list_watches())
+    except Exception as e:
+        return json.dumps({"error": str(e)})
+
+
+@ext_handler(
+    "pythonext", "trace/snapshot_on", required_params=["label", "condition"]
+)
+def start_snapshot_rule(
+    label: str,
+    condition: str,
+    kind: Optional[str] = None,
+    variables: Optional[str] = None,
+    interval: Optional[str] = None,
+) -> str:
+    """Capture a labeled snapshot whenever a condition becomes true.
+
+    Args:
+        label: Name of the rule, stored with its snapshots
+        condition: Python expression, or SQL query when kind is "sql"
+        kind: "python" (default) or "sql"
+        variables: Comma separated expressions stored with the snapshot
+        interval: Check interval such as "1s" or "500ms" (default 1s)
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.snapshot import snapshot_on
+
+        snapshot_on(
+            label,
+            condition,
+            kind=kind or "python",
+            variables=[v.strip() for v in (variables or "").split(",") if v.strip()],
+            interval=interval or 1.0,
+        )
+        return json.dumps({"success": True, "message": f"Snapshot rule {label} added"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "trace/snapshot_off", required_params=["label"])
+def stop_snapshot_rule(label: str) -> str:
+    """Remove a snapshot rule; snapshots it captured are kept.
+
+    Args:
+        label: Label passed to trace/snapshot_on
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.snapshot import remove_rule
+
+        if not remove_rule(label):
+            return json.dumps({"success": False, "error": f"no snapshot rule {label}"})
+        return json.dumps({"success": True, "message": f"Snapshot rule {label} removed"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "trace/snapshot_rules")
+def get_snapshot_rules() -> str:
+    """List snapshot rules and how often they fired.
+
+    Returns:
+        JSON string containing the rules
+    """
+    try:
+        from probing.inspect.snapshot import list_rules
+
+        return json.dumps(list_rules())
+    except Exception as e:
+        return json.dumps({"error": str(e)})
+
+
+@ext_handler("pythonext", "trace/snapshots")
+def get_snapshots(label: Optional[str] = None, limit: Optional[int] = None) -> str:
+    """Get captured snapshots, newest first.
+
+    Args:
+        label: Only return snapshots of this rule
+        limit: Maximum number of snapshots (default 10)
+
+    Returns:
+        JSON string containing the snapshots
+    """
+    try:
+        from probing.inspect.snapshot import get_snapshots as _get_snapshots
+
+        return json.dumps(_get_snapshots(label, limit=int(limit or 10)))
+    except Exception as e:
+        return json.dumps({"error": str(e)})
+
+
+@ext_handler("pythonext", "gc/track")
+def start_gc_tracking(threshold: Optional[str] = None) -> str:
+    """Record garbage collections into python.gc.
+
+    Args:
+        threshold: Pauses at least this long, such as "10ms", are also
+            recorded as spans (default 10ms)
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.gctrace import track_gc
+
+        track_gc(threshold or 0.01)
+        return json.dumps({"success": True, "message": "Tracking gc collections"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "gc/untrack")
+def stop_gc_tracking() -> str:
+    """Stop recording garbage collections.
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.gctrace import untrack_gc
+
+        if not untrack_gc():
+            return json.dumps({"success": False, "error": "gc tracking is not enabled"})
+        return json.dumps({"success": True, "message": "Stopped tracking gc"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "gc/status")
+def get_gc_status() -> str:
+    """Get the gc tracking state and collector counters.
+
+    Returns:
+        JSON string containing the status
+    """
+    try:
+        from probing.inspect.gctrace import gc_status
+
+        return json.dumps(gc_status())
+    except Exception as e:
+        return json.dumps({"error": str(e)})
+
+
+@ext_handler("pythonext", "locks/track")
+def start_lock_tracking(threshold: Optional[str] = None) -> str:
+    """Time contended acquisitions of the locks created from now on.
+
+    Args:
+        threshold: Waits at least this long, such as "1ms", are recorded in
+            python.lock_contention (default 1ms)
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.locks import track_locks
+
+        track_locks(threshold or 0.001)
+        return json.dumps({"success": True, "message": "Tracking lock contention"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "locks/untrack")
+def stop_lock_tracking() -> str:
+    """Stop timing lock acquisitions.
+
+    Returns:
+        JSON string with success status
+    """
+    try:
+        from probing.inspect.locks import untrack_locks
+
+        if not untrack_locks():
+            return json.dumps(
+                {"success": False, "error": "lock tracking is not enabled"}
+            )
+        return json.dumps({"success": True, "message": "Stopped tracking locks"})
+    except Exception as e:
+        return json.dumps({"success": False, "error": str(e)})
+
+
+@ext_handler("pythonext", "locks/status")
+def get_lock_status(limit: int = 10) -> str:
+    """Get the lock tracking state and the most contended locks.
+
+    Args:
+        limit: Number of locks listed, by total wait
+
+    Returns:
+        JSON string containing the status
+    """
+    try:
+        from probing.inspect.locks import lock_status
+
+        return json.dumps(lock_status(limit))
+    except Exception as e:
+        return json.dumps({"error": str(e)})
+
+
 @ext_handler("pythonext", "trace/variables")
 def get_trace_variables(function: Optional[str] = None, limit: int = 100) -> str:
     """Get trace variables from database.
@@ -559,6 +968,84 @@ def get_trace_variables(function: Optional[str] = None, limit: int = 100) -> str
         return json.dumps({"error": str(e)})
 
 
+@ext_handler(
+    "pythonext",
+    ["python/dump_objects", "dump_objects", "pythonext/dump_objects"],
+    required_params=["type"],
+)
+def dump_objects(
+    type: str,
+    limit: Optional[int] = None,
+    format: Optional[str] = None,
+    referrers: Optional[int] = None,
+    directory: Optional[str] = None,
+) -> str:
+    """Dump metadata of live objects of a type to a file.
+
+    Args:
+        type: Type name, qualified (e.g. torch.Tensor) or not (e.g. Tensor)
+        limit: Maximum number of objects to dump (default 1000)
+        format: Output format, json (default) or parquet
+        referrers: Number of objects whose referrer chain is sampled (default 10)
+        directory: Directory of the dump, one of files.allowed_dirs or below
+            (default PROBING_DUMP_DIR or ./data/dumps)
+
+    Returns:
+        JSON string with the path of the dump and its download URL
+    """
+    try:
+        from urllib.parse import quote
+
+        import probing
+        from probing.inspect.objects import dump_objects as _dump_objects
+
+        result = _dump_objects(
+            type,
+            limit=1000 if limit is None else limit,
+            format=format or "json",
+            referrers=10 if referrers is None else referrers,
+            directory=directory,
+            allowed_dirs=probing.config.allowed_dirs() if directory else None,
+        )
+        result["download"] = f"/apis/files/download?path={quote(result['path'])}"
+        return json.dumps(result)
+    except Exception as e:
+        return json.dumps({"error": str(e), "traceback": traceback.format_exc()})
+
+
+@ext_handler(
+    "pythonext",
+    ["python/tensor", "tensor", "pythonext/tensor"],
+    required_params=["expr"],
+)
+def inspect_tensor(
+    expr: str, bins: Optional[int] = None, sample: Optional[int] = None
+) -> str:
+    """Shape, dtype, device and value statistics of a live tensor.
+
+    Args:
+        expr: Dotted path of the tensor, e.g. model.layers.0.weight.grad
+        bins: Buckets of a histogram of the finite values (default none)
+        sample: Elements read from large tensors (default 4194304)
+
+    Returns:
+        JSON string with the statistics
+    """
+    try:
+        from probing.inspect.tensor import DEFAULT_SAMPLE
+        from probing.inspect.tensor import inspect_tensor as _inspect_tensor
+
+        return json.dumps(
+            _inspect_tensor(
+                expr,
+                bins=bins or 0,
+                sample=DEFAULT_SAMPLE if sample is None else sample,
+            )
+        )
+    except Exception as e:
+        return json.dumps({"error": str(e)})
+
+
 # Unified entry point for all handlers
 def handle_api_request(path: str, params: Dict[str, str]) -> str:
     """Unified entry point for handling API requests.
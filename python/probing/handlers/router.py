@@ -9,6 +9,8 @@ import json
 import traceback
 from typing import Any, Callable, Dict, List, Optional, Tuple, Union
 
+from probing.privacy import redact
+
 # Global router state
 _handlers: Dict[str, Dict[str, Any]] = (
     {}
@@ -230,8 +232,8 @@ def handle_request(path: str, params: Dict[str, str]) -> str:
         except Exception as e:
             return json.dumps(
                 {
-                    "error": str(e),
-                    "traceback": traceback.format_exc(),
+                    "error": redact(str(e)),
+                    "traceback": redact(traceback.format_exc()),
                 }
             )
     except Exception as e:
@@ -24,13 +24,53 @@ def _get_ray_init():
         return lambda: None
 
 
+def _get_dask_init():
+    """Lazy import of dask init function."""
+    try:
+        from probing.ext.dask import init as dask_init
+
+        return dask_init
+    except ImportError:
+        return lambda: None
+
+
+def _get_trainer_inits():
+    """Lazy import of the trainer integration init functions."""
+    try:
+        from probing.ext.trainer import init_lightning, init_transformers
+
+        return init_transformers, init_lightning
+    except ImportError:
+        return (lambda: None), (lambda: None)
+
+
+def _get_vllm_init():
+    """Lazy import of the vLLM integration init function."""
+    try:
+        from probing.ext.inference import integrations
+
+        return integrations["vllm"]
+    except ImportError:
+        return lambda: None
+
+
 # Mapping from module names to callback functions
 # Callbacks are called when the module is imported
 # Use lazy loading to avoid import errors
 register = {
     "torch": _get_torch_init(),
     "ray": _get_ray_init(),
+    "distributed": _get_dask_init(),
+    "vllm": _get_vllm_init(),
 }
+_init_transformers, _init_lightning = _get_trainer_inits()
+register.update(
+    {
+        "transformers.trainer": _init_transformers,
+        "lightning.pytorch": _init_lightning,
+        "pytorch_lightning": _init_lightning,
+    }
+)
 
 # Record modules that have been triggered
 triggered = {}
@@ -0,0 +1,177 @@
+"""Garbage collection pause tracking.
+
+A ``gc.callbacks`` hook times every collection and records its generation,
+duration and collected/uncollectable counts in ``python.gc``. Collections
+longer than a threshold are also written to ``python.trace_event`` as spans of
+kind ``gc``, nested in the span active on the collecting thread, so pauses
+show up next to the requests or steps they delayed.
+
+Examples
+--------
+>>> from probing.inspect.gctrace import track_gc, untrack_gc
+>>> track_gc(threshold="10ms")  # doctest: +SKIP
+>>> untrack_gc()  # doctest: +SKIP
+"""
+
+import gc
+import itertools
+import json
+import threading
+import time
+from dataclasses import dataclass
+from typing import Any, Dict, Union
+
+from probing.core.table import table
+from probing.inspect.watch import parse_interval
+
+# Span ids of gc pauses, kept apart from the ids allocated by the tracer
+_span_ids = itertools.count(1 << 62)
+
+
+@table("gc")
+@dataclass
+class GcCollection:
+    """Row model for garbage collections.
+
+    Parameters
+    ----------
+    generation : int
+        Oldest generation collected.
+    duration : float
+        Pause in seconds.
+    collected : int
+        Number of unreachable objects freed.
+    uncollectable : int
+        Number of unreachable objects that could not be freed.
+    thread_id : int
+        Native id of the thread that ran the collection.
+    time : int
+        Start of the collection, nanoseconds since epoch.
+    """
+
+    generation: int
+    duration: float
+    collected: int
+    uncollectable: int
+    thread_id: int
+    time: int
+
+
+class _GcTracker:
+    """The ``gc.callbacks`` hook and its settings."""
+
+    def __init__(self):
+        self.threshold = 0.01
+        self.enabled = False
+        self.collections = 0
+        self.pauses = 0
+        # (wall clock, monotonic) start of the running collection; collections
+        # cannot nest, and run with the GIL held, so one slot is enough
+        self.start = None
+
+    def __call__(self, phase: str, info: Dict[str, Any]):
+        if phase == "start":
+            self.start = (time.time_ns(), time.perf_counter_ns())
+            return
+        if self.start is None:
+            # installed while a collection was running
+            return
+        start, begin = self.start
+        self.start = None
+        duration = (time.perf_counter_ns() - begin) / 1e9
+        try:
+            self.record(info, start, duration)
+        except Exception:
+            # never let bookkeeping break the collection
+            pass
+
+    def record(self, info: Dict[str, Any], start: int, duration: float):
+        self.collections += 1
+        thread_id = threading.get_native_id()
+        GcCollection(
+            generation=info.get("generation", -1),
+            duration=duration,
+            collected=info.get("collected", 0),
+            uncollectable=info.get("uncollectable", 0),
+            thread_id=thread_id,
+            time=start,
+        ).save()
+        if duration >= self.threshold:
+            self.pauses += 1
+            _record_pause(info, start, duration, thread_id)
+
+
+def _record_pause(info: Dict[str, Any], start: int, duration: float, thread_id: int):
+    from probing.tracing import TraceEvent, current_span
+
+    parent = current_span()
+    trace_id = getattr(parent, "trace_id", 0) if parent is not None else 0
+    parent_id = getattr(parent, "span_id", -1) if parent is not None else -1
+    span_id = next(_span_ids)
+    generation = info.get("generation", -1)
+
+    TraceEvent(
+        record_type="span_start",
+        trace_id=trace_id,
+        span_id=span_id,
+        name=f"gc.collect[gen{generation}]",
+        time=start,
+        thread_id=thread_id,
+        parent_id=parent_id,
+        kind="gc",
+        attributes=json.dumps(
+            {
+                "generation": generation,
+                "collected": info.get("collected", 0),
+                "uncollectable": info.get("uncollectable", 0),
+            }
+        ),
+    ).save()
+    TraceEvent(
+        record_type="span_end",
+        trace_id=0,
+        span_id=span_id,
+        name="",
+        time=start + int(duration * 1e9),
+        thread_id=thread_id,
+    ).save()
+
+
+_tracker = _GcTracker()
+
+
+def track_gc(threshold: Union[int, float, str] = 0.01):
+    """Record every garbage collection in ``python.gc``.
+
+    Parameters
+    ----------
+    threshold : int, float or str
+        Pauses at least this long, in seconds or as a string such as
+        ``"10ms"``, are also recorded as spans in ``python.trace_event``.
+    """
+    _tracker.threshold = parse_interval(threshold)
+    if not _tracker.enabled:
+        _tracker.start = None
+        gc.callbacks.append(_tracker)
+        _tracker.enabled = True
+
+
+def untrack_gc() -> bool:
+    """Stop recording collections, returning whether tracking was enabled."""
+    if not _tracker.enabled:
+        return False
+    gc.callbacks.remove(_tracker)
+    _tracker.enabled = False
+    return True
+
+
+def gc_status() -> Dict[str, Any]:
+    """Tracking state with the number of collections and long pauses seen."""
+    return {
+        "enabled": _tracker.enabled,
+        "threshold": _tracker.threshold,
+        "collections": _tracker.collections,
+        "pauses": _tracker.pauses,
+        "counts": list(gc.get_count()),
+        "thresholds": list(gc.get_threshold()),
+    }
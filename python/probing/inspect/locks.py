@@ -0,0 +1,267 @@
+"""Contention of ``threading`` locks.
+
+While tracking is on, ``threading.Lock`` and ``threading.RLock`` create
+wrapped locks, and so do the ``Condition``, ``Event``, ``Semaphore`` and
+``queue.Queue`` objects built on them. An acquire first tries the lock
+without blocking, so uncontended locks only pay for that call; when the lock
+is held by another thread, the wait is timed and, when at least
+``threshold`` long, written to ``python.lock_contention`` with the site that
+created the lock, the thread holding it and the stack of the waiting thread.
+
+Locks created before tracking started are not instrumented, so tracking is
+best enabled early, e.g. before the data loader and worker pools start.
+Wrapped locks keep working after `untrack_locks`, without timing. While
+tracking, ``threading.Lock`` is a function, so ``isinstance`` checks against
+it fail.
+
+Examples
+--------
+>>> from probing.inspect.locks import track_locks, untrack_locks
+>>> track_locks(threshold="1ms")  # doctest: +SKIP
+>>> probing.query(
+...     "SELECT lock, count(*), sum(wait) FROM python.lock_contention "
+...     "GROUP BY lock ORDER BY 3 DESC LIMIT 10"
+... )  # doctest: +SKIP
+>>> untrack_locks()  # doctest: +SKIP
+"""
+
+import _thread
+import os
+import re
+import sys
+import threading
+import time
+from dataclasses import dataclass
+from typing import Any, Dict, List, Optional, Union
+
+from probing.core.table import table
+from probing.inspect.watch import parse_interval
+
+_OWNER = re.compile(r"owner=(\d+)")
+
+# modules whose frames do not name the creation site of a lock
+_INTERNAL = (
+    os.path.normcase(os.path.abspath(__file__)),
+    os.path.normcase(threading.__file__),
+)
+
+
+@table("lock_contention")
+@dataclass
+class LockContention:
+    """Row model for contended lock acquisitions.
+
+    Parameters
+    ----------
+    lock : str
+        ``file:lineno`` where the lock was created.
+    kind : str
+        ``Lock`` or ``RLock``.
+    wait : float
+        Time spent waiting, in seconds.
+    acquired : bool
+        False if the acquire timed out.
+    owner : int
+        ``threading`` ident of the thread that held the lock, -1 if unknown.
+    thread_id : int
+        ``threading`` ident of the waiting thread.
+    stack_id : int
+        Stack of the waiting thread in ``stacks.dictionary``.
+    time : int
+        Start of the wait, nanoseconds since epoch.
+    """
+
+    lock: str
+    kind: str
+    wait: float
+    acquired: bool
+    owner: int
+    thread_id: int
+    stack_id: int
+    time: int
+
+
+def _internal(filename: str) -> bool:
+    filename = os.path.normcase(filename)
+    return filename in _INTERNAL or filename.endswith(os.sep + "queue.py")
+
+
+def _creation_site() -> str:
+    frame = sys._getframe(2)
+    while frame is not None and _internal(frame.f_code.co_filename):
+        frame = frame.f_back
+    if frame is None:
+        return "<unknown>"
+    return f"{frame.f_code.co_filename}:{frame.f_lineno}"
+
+
+def _waiting_stack() -> int:
+    from probing.inspect.stacks import format_frame, intern_stack
+
+    frames = []
+    # skip the frames of the tracking itself
+    frame = sys._getframe(3)
+    while frame is not None:
+        code = frame.f_code
+        frames.append(format_frame(code.co_filename, code.co_name, frame.f_lineno))
+        frame = frame.f_back
+    return intern_stack(frames[::-1])
+
+
+class _Tracker:
+    """Settings and per-lock totals of the tracking."""
+
+    def __init__(self):
+        self.enabled = False
+        self.threshold = 0.001
+        self.contended = 0
+        self.recorded = 0
+        # lock -> [contended acquires, total wait, longest wait]
+        self.totals: Dict[str, List[float]] = {}
+        self.mutex = _thread.allocate_lock()
+        self.local = threading.local()
+
+    def record(self, lock: "_TrackedLock", wait: float, acquired: bool, owner, start):
+        with self.mutex:
+            self.contended += 1
+            totals = self.totals.setdefault(lock._site, [0, 0.0, 0.0])
+            totals[0] += 1
+            totals[1] += wait
+            totals[2] = max(totals[2], wait)
+        if wait < self.threshold or getattr(self.local, "recording", False):
+            return
+        # saving a row may take tracked locks itself
+        self.local.recording = True
+        try:
+            LockContention(
+                lock=lock._site,
+                kind=lock._kind,
+                wait=wait,
+                acquired=acquired,
+                owner=owner if owner is not None else -1,
+                thread_id=threading.get_ident(),
+                stack_id=_waiting_stack(),
+                time=start,
+            ).save()
+            self.recorded += 1
+        except Exception:
+            # never let bookkeeping break the application
+            pass
+        finally:
+            self.local.recording = False
+
+
+_tracker = _Tracker()
+
+
+class _TrackedLock:
+    """A ``Lock`` or ``RLock`` timing the acquisitions that have to wait."""
+
+    __slots__ = ("_lock", "_kind", "_site", "_owner", "__weakref__")
+
+    def __init__(self, lock: Any, kind: str, site: str):
+        self._lock = lock
+        self._kind = kind
+        self._site = site
+        self._owner: Optional[int] = None
+
+    def acquire(self, blocking: bool = True, timeout: float = -1) -> bool:
+        if not _tracker.enabled or not blocking:
+            acquired = self._lock.acquire(blocking, timeout)
+        elif self._lock.acquire(False):
+            acquired = True
+        else:
+            owner = self._holder()
+            start = time.time_ns()
+            begin = time.perf_counter()
+            acquired = self._lock.acquire(True, timeout)
+            _tracker.record(self, time.perf_counter() - begin, acquired, owner, start)
+        if acquired:
+            self._owner = threading.get_ident()
+        return acquired
+
+    def release(self):
+        self._lock.release()
+        if self._kind == "Lock" or not self._is_owned():
+            self._owner = None
+
+    __enter__ = acquire
+
+    def __exit__(self, *args):
+        self.release()
+
+    def _holder(self) -> Optional[int]:
+        if self._kind == "Lock":
+            return self._owner
+        # a Condition releases and restores an RLock behind our back
+        match = _OWNER.search(repr(self._lock))
+        return int(match.group(1)) if match and match.group(1) != "0" else None
+
+    def locked(self) -> bool:
+        return self._lock.locked()
+
+    def __getattr__(self, name: str) -> Any:
+        # _is_owned, _release_save and _acquire_restore of an RLock, used by
+        # Condition, and _at_fork_reinit
+        return getattr(self._lock, name)
+
+    def __repr__(self) -> str:
+        text = repr(self._lock)
+        if "owner=" not in text and self._owner is not None:
+            text = f"{text[:-1]} owner={self._owner}>"
+        return text
+
+
+def _tracked_lock() -> _TrackedLock:
+    return _TrackedLock(_thread.allocate_lock(), "Lock", _creation_site())
+
+
+def _tracked_rlock(*args, **kwargs) -> _TrackedLock:
+    return _TrackedLock(_rlock(*args, **kwargs), "RLock", _creation_site())
+
+
+_lock = threading.Lock
+_rlock = threading.RLock
+
+
+def track_locks(threshold: Union[int, float, str] = 0.001):
+    """Time contended acquisitions of the locks created from now on.
+
+    Parameters
+    ----------
+    threshold : int, float or str
+        Waits at least this long, in seconds or as a string such as
+        ``"1ms"``, are recorded in ``python.lock_contention``; shorter ones
+        are only counted.
+    """
+    _tracker.threshold = parse_interval(threshold)
+    if not _tracker.enabled:
+        threading.Lock = _tracked_lock
+        threading.RLock = _tracked_rlock
+        _tracker.enabled = True
+
+
+def untrack_locks() -> bool:
+    """Stop tracking, returning whether tracking was enabled."""
+    if not _tracker.enabled:
+        return False
+    threading.Lock = _lock
+    threading.RLock = _rlock
+    _tracker.enabled = False
+    return True
+
+
+def lock_status(limit: int = 10) -> Dict[str, Any]:
+    """Tracking state and the ``limit`` locks with the longest total wait."""
+    with _tracker.mutex:
+        totals = sorted(_tracker.totals.items(), key=lambda kv: kv[1][1], reverse=True)
+    return {
+        "enabled": _tracker.enabled,
+        "threshold": _tracker.threshold,
+        "contended": _tracker.contended,
+        "recorded": _tracker.recorded,
+        "top": [
+            {"lock": site, "contended": int(count), "wait": wait, "max_wait": longest}
+            for site, (count, wait, longest) in totals[:limit]
+        ],
+    }
@@ -0,0 +1,193 @@
+"""Dump metadata of live Python objects for offline inspection.
+
+Objects are found through the garbage collector and matched by the qualified
+name of any class in their MRO, so ``torch.Tensor`` also matches parameters.
+The dump is written to ``PROBING_DUMP_DIR``, ``./data/dumps`` by default,
+and can be downloaded from ``/apis/files/download`` as long as the directory
+is one of ``files.allowed_dirs``.
+"""
+
+import gc
+import inspect
+import json
+import os
+import sys
+import time
+from typing import Any, Dict, List, Optional
+
+# Default directory of the dumps, below ``./data`` served by the file API
+DUMP_DIR = os.path.join(".", "data", "dumps")
+
+# Depth of the referrer chain recorded for sampled objects
+REFERRER_DEPTH = 3
+
+
+def _matches(obj: Any, type_name: str) -> bool:
+    try:
+        mro = type(obj).__mro__
+    except AttributeError:
+        return False
+    for cls in mro:
+        if cls.__qualname__ == type_name:
+            return True
+        if f"{cls.__module__}.{cls.__qualname__}" == type_name:
+            return True
+    return False
+
+
+def _qualname(obj: Any) -> str:
+    cls = type(obj)
+    return f"{cls.__module__}.{cls.__qualname__}"
+
+
+def _describe(obj: Any) -> Dict[str, Any]:
+    """Metadata of an object; shape, dtype and device when it has them."""
+    record = {"id": id(obj), "type": _qualname(obj), "size": sys.getsizeof(obj)}
+
+    shape = getattr(obj, "shape", None)
+    if shape is not None:
+        try:
+            record["shape"] = [int(x) for x in shape]
+        except (TypeError, ValueError):
+            record["shape"] = None
+    for attr in ("dtype", "device"):
+        value = getattr(obj, attr, None)
+        if value is not None:
+            record[attr] = str(value)
+
+    # Tensors and arrays report the size of their data rather than the wrapper
+    nbytes = getattr(obj, "nbytes", None)
+    if isinstance(nbytes, int):
+        record["size"] = nbytes
+    elif callable(getattr(obj, "element_size", None)) and callable(
+        getattr(obj, "numel", None)
+    ):
+        try:
+            record["size"] = obj.element_size() * obj.numel()
+        except Exception:
+            pass
+    return record
+
+
+def _describe_referrer(parent: Any, child: Any) -> str:
+    if isinstance(parent, dict):
+        for key, value in parent.items():
+            if value is child:
+                return f"dict[{key!r}]"
+        return "dict"
+    if isinstance(parent, (list, tuple)):
+        for idx, value in enumerate(parent):
+            if value is child:
+                return f"{type(parent).__name__}[{idx}]"
+    # instances reference their attributes directly when the dict is inlined
+    attrs = getattr(parent, "__dict__", None)
+    if isinstance(attrs, dict):
+        for key, value in attrs.items():
+            if value is child:
+                return f"{_qualname(parent)}.{key}"
+    return _qualname(parent)
+
+
+def referrer_chain(obj: Any, ignore: set, depth: int = REFERRER_DEPTH) -> List[str]:
+    """Follow the first referrer of ``obj`` up to ``depth`` levels.
+
+    Frames and the containers in ``ignore`` are skipped, as are the lists
+    holding intermediate results of the walk itself.
+    """
+    chain = []
+    current = obj
+    for _ in range(depth):
+        referrers = [
+            r
+            for r in gc.get_referrers(current)
+            if id(r) not in ignore and not inspect.isframe(r)
+        ]
+        ignore.add(id(referrers))
+        if not referrers:
+            break
+        parent = referrers[0]
+        chain.append(_describe_referrer(parent, current))
+        current = parent
+    return chain
+
+
+def collect_objects(
+    type_name: str, limit: int = 1000, referrers: int = 10
+) -> List[Dict[str, Any]]:
+    """Describe up to ``limit`` live objects of ``type_name``.
+
+    The referrer chain is sampled for the first ``referrers`` objects only, as
+    each chain scans the whole heap.
+    """
+    matched = []
+    for obj in gc.get_objects():
+        if _matches(obj, type_name):
+            matched.append(obj)
+            if len(matched) >= limit:
+                break
+
+    ignore = {id(matched)}
+    records = []
+    for idx, obj in enumerate(matched):
+        record = _describe(obj)
+        if idx < referrers:
+            record["referrers"] = referrer_chain(obj, ignore)
+        records.append(record)
+    return records
+
+
+def _check_allowed(directory: str, allowed_dirs: List[str]) -> None:
+    resolved = os.path.realpath(directory)
+    for base in allowed_dirs:
+        base = os.path.realpath(base)
+        if resolved == base or resolved.startswith(base.rstrip(os.sep) + os.sep):
+            return
+    raise PermissionError(f"{directory} is outside files.allowed_dirs")
+
+
+def dump_objects(
+    type_name: str,
+    limit: int = 1000,
+    format: str = "json",
+    referrers: int = 10,
+    directory: Optional[str] = None,
+    allowed_dirs: Optional[List[str]] = None,
+) -> Dict[str, Any]:
+    """Write the metadata of matching objects to a JSON or Parquet file.
+
+    The file goes to ``directory``, else ``PROBING_DUMP_DIR``, else
+    ``./data/dumps``. With ``allowed_dirs``, a ``directory`` that does not
+    resolve to one of them, or below, is refused with ``PermissionError``.
+    Returns the path of the dump together with summary statistics.
+    """
+    if format not in ("json", "parquet"):
+        raise ValueError(f"unsupported format: {format}, expected json or parquet")
+    if directory and allowed_dirs is not None:
+        _check_allowed(directory, allowed_dirs)
+
+    records = collect_objects(type_name, limit=limit, referrers=referrers)
+
+    directory = directory or os.environ.get("PROBING_DUMP_DIR") or DUMP_DIR
+    os.makedirs(directory, exist_ok=True)
+    stamp = time.strftime("%Y%m%d-%H%M%S")
+    path = os.path.abspath(
+        os.path.join(directory, f"objects-{os.getpid()}-{stamp}.{format}")
+    )
+
+    if format == "json":
+        with open(path, "w") as f:
+            json.dump(records, f)
+    else:
+        try:
+            import pyarrow as pa
+            import pyarrow.parquet as pq
+        except ImportError as e:
+            raise RuntimeError("parquet dumps require pyarrow") from e
+        pq.write_table(pa.Table.from_pylist(records), path)
+
+    return {
+        "path": path,
+        "format": format,
+        "count": len(records),
+        "total_size": sum(r["size"] for r in records),
+    }
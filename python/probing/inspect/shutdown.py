@@ -0,0 +1,78 @@
+"""Final snapshot written when the process is asked to terminate.
+
+With ``shutdown.capture=on``, probing takes over SIGTERM and SIGINT. On either
+signal a background thread calls :func:`capture`, which writes the stacks of
+all threads, the most recent trace records and the step reached as JSON to
+``<shutdown.dir>/rank<N>/shutdown-<pid>.json``, and the signal is then handed
+to the handler it would have reached, so a preempted job keeps what it was
+doing when it was stopped.
+
+Examples
+--------
+>>> import probing
+>>> probing.config.set("probing.shutdown.capture", "on")  # doctest: +SKIP
+>>> from probing.inspect.shutdown import load
+>>> load("logs/rank0/shutdown-4242.json")["step"]  # doctest: +SKIP
+1200
+"""
+
+import json
+import os
+import threading
+import time
+from typing import Any, Dict, Optional
+
+from probing.inspect.snapshot import capture_stacks, recent_spans
+
+
+def _current_step() -> Optional[int]:
+    from probing.profiling.oom import _current_step
+
+    return _current_step()
+
+
+def _rank() -> int:
+    try:
+        return int(os.environ.get("RANK", "0"))
+    except ValueError:
+        return 0
+
+
+def snapshot(signal: str, step: Optional[int] = None) -> Dict[str, Any]:
+    """What the process was doing, as written by :func:`capture`.
+
+    ``step`` is the step reported to ``probing.config``, the step counted by
+    the torch profiler is used when no step was reported.
+    """
+    if step is None:
+        step = _current_step()
+    return {
+        "signal": signal,
+        "time": time.time_ns(),
+        "pid": os.getpid(),
+        "rank": _rank(),
+        "step": step,
+        "stacks": capture_stacks(threading.get_ident()),
+        "spans": recent_spans(),
+    }
+
+
+def capture(path: str, signal: str, step: Optional[int] = None) -> str:
+    """Write the snapshot to ``path`` and return it.
+
+    The file is renamed into place once written, so a process killed during
+    the capture leaves no truncated snapshot.
+    """
+    data = snapshot(signal, step)
+    os.makedirs(os.path.dirname(path) or ".", exist_ok=True)
+    tmp = f"{path}.tmp"
+    with open(tmp, "w") as f:
+        json.dump(data, f, default=str)
+    os.replace(tmp, path)
+    return path
+
+
+def load(path: str) -> Dict[str, Any]:
+    """Read a snapshot written by :func:`capture`."""
+    with open(path) as f:
+        return json.load(f)
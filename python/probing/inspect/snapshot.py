@@ -0,0 +1,302 @@
+"""Conditional snapshots, captured when a rule's condition becomes true.
+
+A snapshot rule checks a Python expression or a SQL predicate in the
+background. When the condition turns from false to true, for example when the
+loss becomes NaN, the stacks of all threads, the most recent trace records and
+a set of selected variables are stored as one row of ``python.snapshots``
+under the rule's label. The rule fires again only after the condition has
+become false in between. Stacks are stored once in ``stacks.dictionary`` and
+snapshots only keep their ids, `get_snapshots` expands them back to frames.
+
+Examples
+--------
+>>> from probing.inspect.snapshot import snapshot_on
+>>> snapshot_on("nan-loss", "math.isnan(loss)", variables=["step"])  # doctest: +SKIP
+>>> snapshot_on(
+...     "loss-spike",
+...     "SELECT numeric > 10 FROM python.watches WHERE expression = 'loss.item()' "
+...     "ORDER BY time DESC LIMIT 1",
+...     kind="sql",
+... )  # doctest: +SKIP
+"""
+
+import json
+import math
+import sys
+import threading
+import time
+import traceback
+from dataclasses import dataclass
+from typing import Any, Dict, List, Optional, Union
+
+from probing.core.table import table
+from probing.inspect.stacks import (
+    format_frame,
+    intern_stack,
+    parse_frame,
+    resolve_stack,
+)
+from probing.inspect.watch import _Sampler, parse_interval
+from probing.privacy import redact, redact_value
+
+# Number of trace records captured with each snapshot
+RECENT_SPANS = 50
+
+
+@table("snapshots")
+@dataclass
+class Snapshot:
+    """Row model for captured snapshots.
+
+    Parameters
+    ----------
+    label : str
+        Label of the rule that captured the snapshot.
+    condition : str
+        The condition that became true.
+    stacks : str
+        JSON list of the threads with the id of their Python stack in
+        ``stacks.dictionary``.
+    spans : str
+        JSON list of the most recent rows of ``python.trace_event``.
+    variables : str
+        JSON object mapping the selected expressions to their values.
+    time : int
+        Nanoseconds since epoch.
+    """
+
+    label: str
+    condition: str
+    stacks: str
+    spans: str
+    variables: str
+    time: int
+
+
+def _evaluate(expr: str, code, namespace: Dict[str, Any]) -> str:
+    try:
+        return redact_value(expr, repr(eval(code, namespace)))
+    except Exception as e:
+        return redact(f"<error: {type(e).__name__}: {e}>")
+
+
+def _truthy(value: Any) -> bool:
+    if isinstance(value, float) and math.isnan(value):
+        return False
+    return bool(value)
+
+
+def capture_stacks(skip: Optional[int] = None) -> List[Dict[str, Any]]:
+    """Python stacks of all threads except ``skip``, innermost frame last."""
+    names = {t.ident: t.name for t in threading.enumerate()}
+    stacks = []
+    for ident, frame in sys._current_frames().items():
+        if ident == skip:
+            continue
+        stacks.append(
+            {
+                "thread_id": ident,
+                "thread_name": names.get(ident, ""),
+                "frames": [
+                    {"file": f.filename, "func": f.name, "lineno": f.lineno}
+                    for f in traceback.extract_stack(frame)
+                ],
+            }
+        )
+    return stacks
+
+
+def intern_stacks(stacks: List[Dict[str, Any]]) -> List[Dict[str, Any]]:
+    """Replace the frames of ``stacks`` with the id of the stored stack."""
+    return [
+        {
+            "thread_id": s["thread_id"],
+            "thread_name": s["thread_name"],
+            "stack_id": intern_stack(
+                [format_frame(f["file"], f["func"], f["lineno"]) for f in s["frames"]]
+            ),
+        }
+        for s in stacks
+    ]
+
+
+def expand_stacks(stacks: List[Dict[str, Any]]) -> List[Dict[str, Any]]:
+    """Frames of stacks stored by `intern_stacks`, empty if no longer known."""
+    expanded = []
+    for s in stacks:
+        if "stack_id" in s:
+            frames = resolve_stack(s["stack_id"]) or []
+            s = dict(s, frames=[parse_frame(f) for f in frames])
+        expanded.append(s)
+    return expanded
+
+
+def recent_spans(limit: int = RECENT_SPANS) -> List[Dict[str, Any]]:
+    """The last ``limit`` rows of ``python.trace_event``, oldest first."""
+    try:
+        from probing.core.engine import query
+
+        df = query(
+            f"""
+            SELECT record_type, trace_id, span_id, parent_id, name, time, thread_id
+            FROM python.trace_event
+            ORDER BY time DESC
+            LIMIT {limit}
+            """
+        )
+    except Exception:
+        return []
+    if df is None or df.empty:
+        return []
+    return df.to_dict("records")[::-1]
+
+
+class SnapshotRule:
+    """A condition checked at a fixed interval and the data captured on it."""
+
+    def __init__(
+        self,
+        label: str,
+        condition: str,
+        kind: str = "python",
+        variables: Optional[List[str]] = None,
+        interval: float = 1.0,
+        namespace: Optional[Dict[str, Any]] = None,
+    ):
+        if kind not in ("python", "sql"):
+            raise ValueError(
+                f"unsupported condition kind: {kind}, expected python or sql"
+            )
+        self.label = label
+        self.condition = condition
+        self.kind = kind
+        self.interval = interval
+        self.namespace = namespace
+        self.code = None
+        if kind == "python":
+            self.code = compile(condition, f"<snapshot {label}>", "eval")
+        self.variables = {
+            expr: compile(expr, f"<snapshot {label}: {expr}>", "eval")
+            for expr in variables or []
+        }
+        self.next_due = 0.0
+        self.active = False
+        self.fired = 0
+
+    def _globals(self) -> Dict[str, Any]:
+        if self.namespace is not None:
+            return self.namespace
+        import __main__
+
+        return __main__.__dict__
+
+    def check(self) -> bool:
+        """Evaluate the condition; errors count as false."""
+        try:
+            if self.code is not None:
+                return _truthy(eval(self.code, self._globals()))
+
+            from probing.core.engine import query
+
+            df = query(self.condition)
+            if df is None or df.empty:
+                return False
+            return _truthy(df.iloc[0, 0])
+        except Exception:
+            return False
+
+    def capture(self) -> Snapshot:
+        """Capture and store a snapshot, regardless of the condition."""
+        namespace = self._globals()
+        variables = {
+            expr: _evaluate(expr, code, namespace)
+            for expr, code in self.variables.items()
+        }
+        snapshot = Snapshot(
+            label=self.label,
+            condition=self.condition,
+            stacks=json.dumps(intern_stacks(capture_stacks(threading.get_ident()))),
+            spans=json.dumps(recent_spans(), default=str),
+            variables=json.dumps(variables),
+            time=time.time_ns(),
+        )
+        snapshot.save()
+        self.fired += 1
+        return snapshot
+
+    def sample(self) -> Optional[Snapshot]:
+        """Capture a snapshot if the condition has just become true."""
+        active = self.check()
+        rising = active and not self.active
+        self.active = active
+        return self.capture() if rising else None
+
+
+_rules = _Sampler(name="probing-snapshot")
+
+
+def snapshot_on(
+    label: str,
+    condition: str,
+    kind: str = "python",
+    variables: Optional[List[str]] = None,
+    interval: Union[int, float, str] = 1.0,
+    namespace: Optional[Dict[str, Any]] = None,
+):
+    """Capture a snapshot labeled ``label`` whenever ``condition`` becomes true.
+
+    Parameters
+    ----------
+    label : str
+        Name of the rule, stored with every snapshot it captures.
+    condition : str
+        Python expression, or with ``kind="sql"`` a query whose first value
+        is the condition; a query returning no rows is false.
+    kind : str
+        ``"python"`` or ``"sql"``.
+    variables : list of str, optional
+        Python expressions whose values are stored with the snapshot.
+    interval : int, float or str
+        Seconds between checks, or a string such as ``"500ms"``.
+    namespace : dict, optional
+        Globals used for Python expressions, ``__main__`` by default.
+    """
+    rule = SnapshotRule(
+        label, condition, kind, variables, parse_interval(interval), namespace
+    )
+    _rules.add(label, rule)
+
+
+def remove_rule(label: str) -> bool:
+    """Stop checking the rule ``label``, returning whether it existed."""
+    return _rules.remove(label)
+
+
+def list_rules() -> List[Dict[str, Any]]:
+    """Registered snapshot rules and how often they fired."""
+    with _rules.lock:
+        return [
+            {
+                "label": r.label,
+                "condition": r.condition,
+                "kind": r.kind,
+                "variables": list(r.variables),
+                "interval": r.interval,
+                "fired": r.fired,
+            }
+            for r in _rules.watches.values()
+        ]
+
+
+def get_snapshots(label: Optional[str] = None, limit: int = 10) -> List[Dict[str, Any]]:
+    """The latest ``limit`` snapshots, optionally of one label, newest first."""
+    names = list(Snapshot.__dataclass_fields__)
+    rows = [dict(zip(names, values)) for _, values in Snapshot.take(None)]
+    if label is not None:
+        rows = [r for r in rows if r["label"] == label]
+    rows.sort(key=lambda r: r["time"], reverse=True)
+    for row in rows[:limit]:
+        for field in ("stacks", "spans", "variables"):
+            row[field] = json.loads(row[field])
+        row["stacks"] = expand_stacks(row["stacks"])
+    return rows[:limit]
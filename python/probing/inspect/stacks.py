@@ -0,0 +1,79 @@
+"""Call stacks stored once and referenced by id.
+
+Each distinct stack is kept once in ``stacks.dictionary`` and records keep its
+id, a stable 64-bit hash of the frames. Frames are formatted as
+``func (file:lineno)``, outermost first.
+
+Examples
+--------
+>>> from probing.inspect.stacks import format_frame, intern_stack, resolve_stack
+>>> frames = [format_frame("train.py", "main", 10), format_frame("train.py", "step", 3)]
+>>> stack_id = intern_stack(frames)
+>>> resolve_stack(stack_id) == frames
+True
+"""
+
+import re
+from typing import Dict, List, Optional, Sequence
+
+_FNV_OFFSET = 0xCBF29CE484222325
+_FNV_PRIME = 0x100000001B3
+_MASK = (1 << 64) - 1
+
+_FRAME = re.compile(r"^(?P<func>.*) \((?P<file>.*):(?P<lineno>\d+)\)$")
+
+# stacks interned without the native module, e.g. in tests
+_local: Dict[int, List[str]] = {}
+
+
+def fingerprint(frames: Sequence[str]) -> int:
+    """Id of a stack, the same as computed by the probe.
+
+    >>> fingerprint(["main (a.py:1)"])
+    -456493945050715160
+    """
+    value = _FNV_OFFSET
+    for frame in frames:
+        for byte in frame.encode() + b"\0":
+            value = ((value ^ byte) * _FNV_PRIME) & _MASK
+    return value - (1 << 64) if value >= 1 << 63 else value
+
+
+def format_frame(file: str, func: str, lineno: int) -> str:
+    return f"{func} ({file}:{lineno})"
+
+
+def parse_frame(frame: str) -> Dict[str, object]:
+    """``file``, ``func`` and ``lineno`` of a frame built by `format_frame`."""
+    match = _FRAME.match(frame)
+    if match is None:
+        return {"file": "", "func": frame, "lineno": 0}
+    return {
+        "file": match["file"],
+        "func": match["func"],
+        "lineno": int(match["lineno"]),
+    }
+
+
+def intern_stack(frames: Sequence[str]) -> int:
+    """Id of the stack ``frames``, outermost first, storing it on first use."""
+    frames = list(frames)
+    try:
+        from probing import _core
+
+        return _core._intern_stack(frames)
+    except (ImportError, AttributeError):
+        stack_id = fingerprint(frames)
+        _local.setdefault(stack_id, frames)
+        return stack_id
+
+
+def resolve_stack(stack_id: int) -> Optional[List[str]]:
+    """Frames of the stack ``stack_id``, None if it is not stored."""
+    try:
+        from probing import _core
+
+        frames = _core._resolve_stack(stack_id)
+    except (ImportError, AttributeError):
+        frames = None
+    return frames if frames is not None else _local.get(stack_id)
@@ -0,0 +1,163 @@
+"""Statistics of a live tensor, named by a dotted path.
+
+Routine checks of weights or gradients do not need free-form ``eval``:
+:func:`resolve` only accepts names, attributes and constant subscripts, such
+as ``model.layers.0.mlp.weight.grad`` or ``opt.param_groups[0]["params"][1]``,
+so a request cannot call functions or reach dunder attributes. Names are
+looked up in ``__main__``, then among the imported modules.
+
+:func:`tensor_stats` computes the statistics under ``torch.no_grad``, on a
+strided sample of at most ``sample`` elements for large tensors, and
+optionally a histogram of the finite values.
+
+Examples
+--------
+>>> from probing.inspect.tensor import inspect_tensor
+>>> inspect_tensor("model.fc.weight", bins=16)  # doctest: +SKIP
+{'expr': 'model.fc.weight', 'shape': [10, 128], 'dtype': 'torch.float32', ...}
+"""
+
+import ast
+import re
+import sys
+from typing import Any, Dict, Optional
+
+#: Elements read to compute the statistics of large tensors
+DEFAULT_SAMPLE = 1 << 22
+
+#: Largest histogram
+MAX_BINS = 256
+
+# `layers.0` as written by named_parameters, turned into `layers[0]`
+_INDEX_SEGMENT = re.compile(r"\.(\d+)(?=\.|\[|$)")
+
+
+def _lookup(name: str, namespace: Optional[Dict[str, Any]]) -> Any:
+    if namespace is None:
+        main = sys.modules.get("__main__")
+        namespace = vars(main) if main is not None else {}
+    if name in namespace:
+        return namespace[name]
+    if name in sys.modules:
+        return sys.modules[name]
+    raise NameError(f"name {name!r} is not defined")
+
+
+def _index(node: ast.AST) -> Any:
+    if isinstance(node, ast.Constant) and isinstance(node.value, (int, str)):
+        return node.value
+    if (
+        isinstance(node, ast.UnaryOp)
+        and isinstance(node.op, ast.USub)
+        and isinstance(node.operand, ast.Constant)
+        and isinstance(node.operand.value, int)
+    ):
+        return -node.operand.value
+    raise ValueError("only integer and string constants can be used as index")
+
+
+def _walk(node: ast.AST, namespace: Optional[Dict[str, Any]]) -> Any:
+    if isinstance(node, ast.Name):
+        return _lookup(node.id, namespace)
+    if isinstance(node, ast.Attribute):
+        if node.attr.startswith("__"):
+            raise ValueError(f"attribute {node.attr!r} is not allowed")
+        return getattr(_walk(node.value, namespace), node.attr)
+    if isinstance(node, ast.Subscript):
+        value = _walk(node.value, namespace)
+        key = _index(node.slice)
+        # modules of a Sequential or a ModuleList are attributes named by index
+        if isinstance(key, int) and not hasattr(value, "__getitem__"):
+            return getattr(value, str(key))
+        return value[key]
+    raise ValueError(f"{type(node).__name__} is not allowed in a tensor path")
+
+
+def resolve(expr: str, namespace: Optional[Dict[str, Any]] = None) -> Any:
+    """Value of a dotted path with constant subscripts.
+
+    >>> class Layer:
+    ...     weight = [1.0, 2.0]
+    >>> resolve("layers.0.weight[-1]", {"layers": [Layer]})
+    2.0
+    >>> resolve("net.__class__", {"net": 1})
+    Traceback (most recent call last):
+    ...
+    ValueError: attribute '__class__' is not allowed
+    """
+    path = _INDEX_SEGMENT.sub(r"[\1]", expr.strip())
+    try:
+        tree = ast.parse(path, mode="eval")
+    except SyntaxError as e:
+        raise ValueError(f"invalid tensor path {expr!r}: {e.msg}") from None
+    return _walk(tree.body, namespace)
+
+
+def tensor_stats(
+    tensor: Any, bins: int = 0, sample: int = DEFAULT_SAMPLE
+) -> Dict[str, Any]:
+    """Shape, dtype, device and value statistics of a tensor.
+
+    ``min``, ``max``, ``mean`` and ``std`` are those of the finite values;
+    ``sampled`` tells whether they were computed on a strided sample.
+    """
+    import torch
+
+    if not isinstance(tensor, torch.Tensor):
+        kind = type(tensor)
+        raise TypeError(f"not a tensor: {kind.__module__}.{kind.__qualname__}")
+
+    stats = {
+        "shape": list(tensor.shape),
+        "dtype": str(tensor.dtype),
+        "device": str(tensor.device),
+        "numel": tensor.numel(),
+        "requires_grad": tensor.requires_grad,
+        "has_grad": getattr(tensor, "grad", None) is not None,
+        "sampled": False,
+    }
+    if tensor.numel() == 0 or tensor.is_complex() or tensor.layout != torch.strided:
+        return stats
+
+    with torch.no_grad():
+        flat = tensor.detach().reshape(-1)
+        if sample > 0 and flat.numel() > sample:
+            flat = flat[:: -(-flat.numel() // sample)]
+            stats["sampled"] = True
+        if flat.dtype == torch.bool:
+            flat = flat.to(torch.uint8)
+        values = flat if flat.dtype == torch.float64 else flat.float()
+        finite_mask = torch.isfinite(values)
+        stats["nan_count"] = int(torch.isnan(values).sum())
+        stats["inf_count"] = int(torch.isinf(values).sum())
+        finite = values[finite_mask]
+        if finite.numel() == 0:
+            return stats
+        lo, hi = float(finite.min()), float(finite.max())
+        stats.update(
+            min=lo,
+            max=hi,
+            mean=float(finite.mean()),
+            std=float(finite.std()) if finite.numel() > 1 else 0.0,
+            zero_fraction=float((finite == 0).sum()) / finite.numel(),
+        )
+        bins = min(int(bins), MAX_BINS)
+        if bins > 0:
+            counts = torch.histc(finite.float(), bins=bins, min=lo, max=hi)
+            stats["histogram"] = {
+                "min": lo,
+                "max": hi,
+                "counts": [int(c) for c in counts.tolist()],
+            }
+    return stats
+
+
+def inspect_tensor(
+    expr: str,
+    bins: int = 0,
+    sample: int = DEFAULT_SAMPLE,
+    namespace: Optional[Dict[str, Any]] = None,
+) -> Dict[str, Any]:
+    """Statistics of the tensor at ``expr``, see :func:`resolve`."""
+    stats = tensor_stats(resolve(expr, namespace), bins=bins, sample=sample)
+    return {"expr": expr, **stats}
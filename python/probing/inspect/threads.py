@@ -0,0 +1,227 @@
+"""What each Python thread is doing, served as ``python.threads``.
+
+Each thread is classified from its innermost Python frames, and on Linux from
+the scheduler state and wait channel of its native thread in ``/proc``:
+
+* ``waiting_lock``: in ``Lock.acquire``, ``with lock:``, ``Condition.wait``,
+  ``Event.wait``, ``Queue.get``, ``Thread.join`` and the like. ``lock_owner``
+  is the ident of the thread holding the lock when the lock records it, as
+  ``RLock`` does;
+* ``sleeping``: in ``time.sleep``;
+* ``waiting_io``: in a socket, pipe or selector call;
+* ``native``: in a call of Python code that blocks in the kernel on
+  something else than a futex, e.g. a C extension waiting on a device;
+* ``running``: anything else. Threads are inspected while the GIL is held,
+  so the other threads running Python code are waiting for it.
+
+The classification is a first answer to "why is nothing happening", not a
+proof: ``detail`` tells what it is based on.
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT name, state, detail, lock_owner FROM python.threads"
+... )  # doctest: +SKIP
+"""
+
+import linecache
+import re
+import sys
+import threading
+from typing import Any, Dict, List, Optional, Tuple
+
+from probing.inspect.tensor import resolve
+
+# innermost frame of stdlib waits: (file suffix, function) -> description
+_WAITS = {
+    ("threading.py", "wait"): "Condition.wait",
+    ("threading.py", "_wait_for_tstate_lock"): "Thread.join",
+    ("threading.py", "join"): "Thread.join",
+    ("queue.py", "get"): "Queue.get",
+    ("queue.py", "put"): "Queue.put",
+    ("concurrent/futures/_base.py", "result"): "Future.result",
+}
+
+# callers refining a Condition.wait
+_CALLERS = {
+    ("threading.py", "wait"): "Event.wait",
+    ("threading.py", "acquire"): "Semaphore.acquire",
+    ("threading.py", "_wait"): "Barrier.wait",
+    ("queue.py", "get"): "Queue.get",
+    ("queue.py", "put"): "Queue.put",
+    ("concurrent/futures/_base.py", "result"): "Future.result",
+}
+
+_IO_CALLS = {
+    "accept",
+    "connect",
+    "poll",
+    "read",
+    "readinto",
+    "readline",
+    "recv",
+    "recv_into",
+    "recvfrom",
+    "select",
+    "send",
+    "sendall",
+    "urlopen",
+    "write",
+}
+
+# wait channels of threads blocked in the kernel
+_SLEEP_WCHANS = ("nanosleep",)
+_IO_WCHANS = ("ep_poll", "select", "poll", "sk_wait", "sock", "pipe", "tcp", "unix")
+
+_CALL = re.compile(r"([A-Za-z_][\w.]*)\s*\(")
+_WITH = re.compile(r"^\s*(?:async\s+)?with\s+([A-Za-z_][\w.]*)\s*(?::|,|\sas\s)")
+_OWNER = re.compile(r"owner=(\d+)")
+
+
+def _matches(frame, table: Dict[Tuple[str, str], str]) -> Optional[str]:
+    file = frame.f_code.co_filename.replace("\\", "/")
+    for (suffix, func), description in table.items():
+        if frame.f_code.co_name == func and file.endswith("/" + suffix):
+            return description
+    return None
+
+
+def _calls(frame) -> List[str]:
+    """Dotted callees on the current line of ``frame``, e.g. ``lock.acquire``."""
+    line = linecache.getline(frame.f_code.co_filename, frame.f_lineno)
+    return _CALL.findall(line)
+
+
+def _lock(expr: str, frame) -> Any:
+    """The lock named ``expr`` in ``frame``, None if it is not a lock."""
+    try:
+        lock = resolve(expr, {**frame.f_globals, **frame.f_locals})
+    except Exception:
+        return None
+    # a Semaphore waits on a Condition, a Condition on its lock
+    if isinstance(lock, threading.Semaphore):
+        lock = lock._cond
+    if isinstance(lock, threading.Condition):
+        lock = lock._lock
+    return lock if "lock" in type(lock).__name__.lower() else None
+
+
+def _owner(lock: Any) -> Optional[int]:
+    """Ident of the thread holding ``lock``, if the lock records it."""
+    match = _OWNER.search(repr(lock))
+    return int(match.group(1)) if match and match.group(1) != "0" else None
+
+
+def _kernel(native_id: Optional[int]) -> Tuple[Optional[str], Optional[str]]:
+    """Scheduler state and wait channel of a native thread, Linux only."""
+    if native_id is None:
+        return None, None
+    base = f"/proc/self/task/{native_id}"
+    try:
+        with open(f"{base}/stat") as f:
+            # the command may contain spaces, the state follows its `)`
+            state = f.read().rsplit(")", 1)[1].split()[0]
+    except (OSError, IndexError):
+        return None, None
+    try:
+        with open(f"{base}/wchan") as f:
+            wchan = f.read().strip()
+    except OSError:
+        wchan = ""
+    return state, wchan if wchan not in ("", "0") else None
+
+
+def classify(
+    frame, kernel_state: Optional[str] = None, wchan: Optional[str] = None
+) -> Tuple[str, str, Optional[int]]:
+    """State, detail and lock owner of a thread whose innermost frame is
+    ``frame``."""
+    if frame is None:
+        return "running", "no Python frame", None
+
+    # waiting in a lock wrapped by probing.inspect.locks
+    tracked = frame.f_locals.get("self") if frame.f_code.co_name == "acquire" else None
+    if type(tracked).__name__ == "_TrackedLock":
+        state, detail, _ = classify(frame.f_back, kernel_state, wchan)
+        if state != "waiting_lock":
+            detail = f"{tracked._kind}.acquire"
+        return "waiting_lock", detail, _owner(tracked)
+
+    wait = _matches(frame, _WAITS)
+    if wait is not None:
+        if wait == "Condition.wait" and frame.f_back is not None:
+            wait = _matches(frame.f_back, _CALLERS) or wait
+        if wait == "Thread.join":
+            target = frame.f_locals.get("self")
+            if isinstance(target, threading.Thread):
+                wait = f"Thread.join({target.name})"
+        return "waiting_lock", wait, None
+
+    for call in reversed(_calls(frame)):
+        receiver, _, name = call.rpartition(".")
+        if name == "sleep":
+            return "sleeping", call, None
+        if name in ("acquire", "wait") and receiver:
+            lock = _lock(receiver, frame)
+            return "waiting_lock", call, _owner(lock) if lock is not None else None
+        if name in _IO_CALLS:
+            return "waiting_io", call, None
+    line = linecache.getline(frame.f_code.co_filename, frame.f_lineno)
+    with_lock = _WITH.match(line)
+    if with_lock is not None:
+        lock = _lock(with_lock.group(1), frame)
+        if lock is not None:
+            return "waiting_lock", f"with {with_lock.group(1)}", _owner(lock)
+
+    if kernel_state in ("S", "D") and wchan and "futex" not in wchan:
+        if any(w in wchan for w in _SLEEP_WCHANS):
+            return "sleeping", wchan, None
+        if any(w in wchan for w in _IO_WCHANS):
+            return "waiting_io", wchan, None
+        return "native", wchan, None
+    return "running", f"kernel state {kernel_state}" if kernel_state else "", None
+
+
+def thread_states() -> List[Dict[str, Any]]:
+    """One row of ``python.threads`` per live Python thread."""
+    from probing.inspect.stacks import format_frame, intern_stack
+
+    frames = sys._current_frames()
+    current = threading.get_ident()
+    main = threading.main_thread().ident
+    rows = []
+    for thread in threading.enumerate():
+        frame = frames.get(thread.ident)
+        native_id = getattr(thread, "native_id", None)
+        kernel_state, wchan = _kernel(native_id)
+        if thread.ident == current:
+            state, detail, owner = "running", "inspecting threads", None
+        else:
+            state, detail, owner = classify(frame, kernel_state, wchan)
+
+        stack = []
+        walk = frame
+        while walk is not None:
+            code = walk.f_code
+            stack.append(format_frame(code.co_filename, code.co_name, walk.f_lineno))
+            walk = walk.f_back
+        rows.append(
+            {
+                "thread_id": thread.ident,
+                "native_id": native_id,
+                "name": thread.name,
+                "daemon": thread.daemon,
+                "is_main": thread.ident == main,
+                "state": state,
+                "detail": detail,
+                "lock_owner": owner,
+                "kernel_state": kernel_state,
+                "wchan": wchan,
+                "func": frame.f_code.co_name if frame is not None else None,
+                "file": frame.f_code.co_filename if frame is not None else None,
+                "lineno": frame.f_lineno if frame is not None else None,
+                "stack_id": intern_stack(stack[::-1]) if stack else None,
+            }
+        )
+    return rows
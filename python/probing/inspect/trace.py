@@ -24,6 +24,8 @@ _probe_attrs = {}
 # Global dictionary to store module references needed by wrapper functions
 _probe_modules = {"sys": sys}
 
+# Modules traced by ProbingTracer, see configure_filter
+
 
 @table("trace_variables")
 @dataclass
@@ -658,6 +660,14 @@ def probe(func, watch=None, silent_watch=None, depth=1):
     return wrapper
 
 
+def module_allowed(module: str) -> bool:
+    """Whether calls of ``module`` pass the ``tracing.include`` and
+    ``tracing.exclude`` filter, the one the eval frame hook applies."""
+    from probing import _core
+
+    return _core._trace_module_allowed(module)
+
+
 class ProbingTracer:
     def __init__(self, depth=1, watch=[], silent_watch=[]):
         self.depth = depth
@@ -693,6 +703,15 @@ class ProbingTracer:
         sys.settrace(tracer_stack.pop())
 
     def trace(self, frame: FrameType, event: AnyStr, arg: Any):
+        # calls of filtered modules are neither traced nor counted in the
+        # depth; the traced function itself is always kept
+        if (
+            event == "call"
+            and self.count_calls
+            and not module_allowed(frame.f_globals.get("__name__", ""))
+        ):
+            return None
+
         import torch
 
         # print(
@@ -0,0 +1,281 @@
+"""Watch expressions sampled in the background.
+
+A watch evaluates a Python expression at a fixed interval, records every
+sample in the ``python.watches`` table and emits a trace event into
+``python.trace_event`` whenever the value changes by more than a threshold,
+so values such as the learning rate can be followed without editing the
+training script.
+
+Examples
+--------
+>>> import probing
+>>> probing.watch("model.lr_scheduler.get_last_lr()[0]", interval="1s")  # doctest: +SKIP
+>>> probing.unwatch("model.lr_scheduler.get_last_lr()[0]")  # doctest: +SKIP
+"""
+
+import json
+import math
+import re
+import threading
+import time
+from dataclasses import dataclass
+from typing import Any, Dict, List, Optional, Union
+
+from probing.core.table import table
+from probing.privacy import redact, redact_value
+
+
+@table("watches")
+@dataclass
+class WatchSample:
+    """Row model for watch samples.
+
+    Parameters
+    ----------
+    expression : str
+        The watched expression.
+    value : str
+        String representation of the value, or the error raised by the expression.
+    numeric : float
+        The value as a number, NaN if it is not numeric.
+    changed : bool
+        Whether the value changed beyond the threshold since the previous sample.
+    time : int
+        Nanoseconds since epoch.
+    """
+
+    expression: str
+    value: str
+    numeric: float
+    changed: bool
+    time: int
+
+
+def parse_interval(interval: Union[int, float, str]) -> float:
+    """Interval in seconds, given as a number of seconds or with a unit.
+
+    >>> parse_interval(2)
+    2.0
+    >>> parse_interval("500ms")
+    0.5
+    >>> parse_interval("1s")
+    1.0
+    >>> parse_interval("2m")
+    120.0
+    """
+    if isinstance(interval, (int, float)):
+        seconds = float(interval)
+    else:
+        match = re.fullmatch(r"\s*([0-9.]+)\s*(ms|s|m|h)?\s*", interval)
+        if not match:
+            raise ValueError(f"invalid interval: {interval!r}")
+        scale = {"ms": 0.001, "s": 1, "m": 60, "h": 3600}[match.group(2) or "s"]
+        seconds = float(match.group(1)) * scale
+    if seconds <= 0:
+        raise ValueError(f"interval must be positive: {interval!r}")
+    return seconds
+
+
+def _as_number(value: Any) -> Optional[float]:
+    if isinstance(value, bool):
+        return float(value)
+    try:
+        return float(value)
+    except (TypeError, ValueError, OverflowError):
+        return None
+
+
+class Watch:
+    """A watched expression and its last sampled value."""
+
+    def __init__(
+        self,
+        expression: str,
+        interval: float,
+        threshold: float = 0.0,
+        namespace: Optional[Dict[str, Any]] = None,
+    ):
+        self.expression = expression
+        self.interval = interval
+        self.threshold = threshold
+        self.namespace = namespace
+        self.code = compile(expression, f"<watch {expression}>", "eval")
+        self.next_due = 0.0
+        self.value: Optional[str] = None
+        self.numeric: Optional[float] = None
+        # reference value before the last update, reported as `old` in events
+        self.previous: Optional[str] = None
+
+    def _globals(self) -> Dict[str, Any]:
+        if self.namespace is not None:
+            return self.namespace
+        import __main__
+
+        return __main__.__dict__
+
+    def evaluate(self):
+        """Evaluate the expression, returning ``(text, number)``.
+
+        The text is redacted with ``privacy.redact_patterns``, the number is
+        dropped when anything was redacted.
+        """
+        try:
+            value = eval(self.code, self._globals())
+        except Exception as e:
+            return redact(f"<error: {type(e).__name__}: {e}>"), None
+        text = repr(value)
+        redacted = redact_value(self.expression, text)
+        return redacted, _as_number(value) if redacted == text else None
+
+    def sample(self):
+        """Evaluate the expression and record the sample."""
+        text, numeric = self.evaluate()
+        changed = self.update(text, numeric)
+        _record(self, text, numeric, changed)
+
+    def update(self, text: str, numeric: Optional[float]) -> bool:
+        """Store a new sample, returning whether it counts as a change.
+
+        Numbers change when they move by more than the threshold (NaN
+        appearing or disappearing always counts), other values when their
+        representation differs. The first sample is not a change.
+        """
+        if self.value is None:
+            changed = False
+        elif numeric is not None and self.numeric is not None:
+            if math.isnan(numeric) or math.isnan(self.numeric):
+                changed = math.isnan(numeric) != math.isnan(self.numeric)
+            else:
+                changed = abs(numeric - self.numeric) > self.threshold
+        else:
+            changed = text != self.value
+
+        previous = self.value
+        # keep the reference value while changes stay below the threshold,
+        # so slow drifts are still reported once they add up
+        if changed or self.value is None or numeric is None:
+            self.value, self.numeric = text, numeric
+        self.previous = previous
+        return changed
+
+
+class _Sampler:
+    """Background thread sampling all registered watches.
+
+    Anything with ``interval``, ``next_due`` and a ``sample()`` method can be
+    registered, which snapshot rules rely on.
+    """
+
+    def __init__(self, name: str = "probing-watch"):
+        self.name = name
+        self.watches: Dict[str, Any] = {}
+        self.lock = threading.Lock()
+        self.wakeup = threading.Event()
+        self.thread: Optional[threading.Thread] = None
+
+    def add(self, key: str, watch: Any):
+        with self.lock:
+            self.watches[key] = watch
+            if self.thread is None or not self.thread.is_alive():
+                self.thread = threading.Thread(
+                    target=self._run, name=self.name, daemon=True
+                )
+                self.thread.start()
+        self.wakeup.set()
+
+    def remove(self, key: str) -> bool:
+        with self.lock:
+            return self.watches.pop(key, None) is not None
+
+    def sample_due(self, now: float) -> float:
+        """Sample the watches that are due, returning the next due time."""
+        with self.lock:
+            due = [w for w in self.watches.values() if w.next_due <= now]
+        for watch in due:
+            watch.sample()
+            watch.next_due = now + watch.interval
+        with self.lock:
+            return min((w.next_due for w in self.watches.values()), default=now + 1)
+
+    def _run(self):
+        while True:
+            with self.lock:
+                if not self.watches:
+                    self.thread = None
+                    return
+            next_due = self.sample_due(time.monotonic())
+            self.wakeup.wait(max(0.0, next_due - time.monotonic()))
+            self.wakeup.clear()
+
+
+_sampler = _Sampler()
+
+
+def _record(watch: Watch, text: str, numeric: Optional[float], changed: bool):
+    now = time.time_ns()
+    WatchSample(
+        expression=watch.expression,
+        value=text,
+        numeric=float("nan") if numeric is None else numeric,
+        changed=changed,
+        time=now,
+    ).save()
+    if not changed:
+        return
+
+    from probing.tracing import TraceEvent
+
+    TraceEvent(
+        record_type="event",
+        trace_id=0,
+        span_id=0,
+        name=f"watch:{watch.expression}",
+        time=now,
+        thread_id=threading.get_native_id(),
+        event_attributes=json.dumps(
+            {"expression": watch.expression, "old": watch.previous, "new": text}
+        ),
+    ).save()
+
+
+def watch(
+    expression: str,
+    interval: Union[int, float, str] = 1.0,
+    threshold: float = 0.0,
+    namespace: Optional[Dict[str, Any]] = None,
+):
+    """Sample ``expression`` every ``interval`` in the background.
+
+    Parameters
+    ----------
+    expression : str
+        Python expression, evaluated in ``namespace`` (``__main__`` by default).
+    interval : int, float or str
+        Seconds between samples, or a string such as ``"500ms"`` or ``"1s"``.
+    threshold : float
+        Minimal change of a numeric value that emits a trace event.
+    namespace : dict, optional
+        Globals used to evaluate the expression.
+    """
+    _sampler.add(
+        expression, Watch(expression, parse_interval(interval), threshold, namespace)
+    )
+
+
+def unwatch(expression: str) -> bool:
+    """Stop sampling ``expression``, returning whether it was watched."""
+    return _sampler.remove(expression)
+
+
+def list_watches() -> List[Dict[str, Any]]:
+    """Registered watches with their last sampled value."""
+    with _sampler.lock:
+        return [
+            {
+                "expression": w.expression,
+                "interval": w.interval,
+                "threshold": w.threshold,
+                "value": w.value,
+            }
+            for w in _sampler.watches.values()
+        ]
@@ -0,0 +1,176 @@
+"""
+Jupyter Kernel Bridge
+
+Spec
+----
+This module exposes the in-process REPL of a running process as a Jupyter kernel.
+
+Responsibilities:
+1.  Run notebook cells in the target process through its probing server.
+2.  Keep each kernel in a REPL session of its own, so its variables live on
+    in the target and `probing.query` is available next to the program globals.
+3.  Install kernelspecs that connect to a given target.
+
+Public Interfaces:
+- `ProbingKernel`: A Jupyter wrapper kernel forwarding cells to the target.
+- `install`: Install a kernelspec for a target.
+- `TargetClient`: HTTP client for the probing server of a target.
+"""
+
+import codeop
+import os
+import sys
+from typing import Optional
+
+from probing import VERSION
+from probing.kernel.client import TargetClient
+
+# Environment variable holding the target of a kernel, a pid or host:port
+TARGET_ENV = "PROBING_KERNEL_TARGET"
+
+try:
+    from ipykernel.kernelbase import Kernel
+except ImportError:  # pragma: no cover - ipykernel is optional
+    Kernel = object
+
+
+def is_complete(code: str) -> str:
+    """Tell whether a cell can run as is, as `do_is_complete` reports it.
+
+    >>> is_complete("for i in range(3):")
+    'incomplete'
+    >>> is_complete("x = 1")
+    'complete'
+    >>> is_complete("x = (")
+    'incomplete'
+    >>> is_complete("x = )")
+    'invalid'
+    """
+    try:
+        compiled = codeop.compile_command(code, "<cell>", "exec")
+    except (SyntaxError, OverflowError, ValueError):
+        return "invalid"
+    return "incomplete" if compiled is None else "complete"
+
+
+class ProbingKernel(Kernel):
+    """Jupyter kernel running cells in the target process.
+
+    Each kernel works in the REPL session `jupyter-<kernel pid>` of the
+    target, which is closed on shutdown, so a restart starts afresh.
+    """
+
+    implementation = "probing"
+    implementation_version = VERSION
+    language = "python"
+    language_version = sys.version.split()[0]
+    language_info = {
+        "name": "python",
+        "mimetype": "text/x-python",
+        "file_extension": ".py",
+        "codemirror_mode": {"name": "ipython", "version": 3},
+        "pygments_lexer": "ipython3",
+    }
+
+    def __init__(self, **kwargs):
+        super().__init__(**kwargs)
+        self.client = TargetClient(os.environ.get(TARGET_ENV, ""))
+        self.session = f"jupyter-{os.getpid()}"
+
+    @property
+    def banner(self):
+        return f"probing kernel attached to {self.client}, session {self.session}"
+
+    def do_execute(
+        self,
+        code,
+        silent,
+        store_history=True,
+        user_expressions=None,
+        allow_stdin=False,
+    ):
+        if not code.strip():
+            return self._ok()
+        try:
+            # the REPL compiles line by line, a final newline closes blocks
+            reply = self.client.eval(code.rstrip() + "\n", session=self.session)
+        except Exception as e:
+            reply = {"status": "error", "traceback": [f"{type(e).__name__}: {e}"]}
+        if not reply:
+            reply = {"status": "error", "traceback": ["SyntaxError: incomplete input"]}
+
+        output = reply.get("output")
+        if output and not silent:
+            self.send_response(
+                self.iopub_socket, "stream", {"name": "stdout", "text": output}
+            )
+        if reply.get("status") != "error":
+            return self._ok()
+
+        traceback = reply.get("traceback") or []
+        ename, _, evalue = (traceback[-1] if traceback else "Error").partition(": ")
+        error = {"ename": ename, "evalue": evalue, "traceback": traceback}
+        if not silent:
+            self.send_response(self.iopub_socket, "error", error)
+        return {"status": "error", "execution_count": self.execution_count, **error}
+
+    def do_is_complete(self, code):
+        status = is_complete(code)
+        if status == "incomplete":
+            return {"status": status, "indent": "    "}
+        return {"status": status}
+
+    def do_shutdown(self, restart):
+        try:
+            self.client.close_session(self.session)
+        except Exception:
+            pass
+        return {"status": "ok", "restart": restart}
+
+    def _ok(self):
+        return {
+            "status": "ok",
+            "execution_count": self.execution_count,
+            "payload": [],
+            "user_expressions": {},
+        }
+
+
+def install(target: str, name: Optional[str] = None, user: bool = True) -> str:
+    """Install a kernelspec connecting to `target`, return its directory.
+
+    Parameters
+    ----------
+    target : str
+        Pid of a local process or `host:port` of its probing server.
+    name : str, optional
+        Kernel name, `probing-<target>` by default.
+    user : bool
+        Install for the current user rather than into `sys.prefix`.
+    """
+    import json
+    import tempfile
+
+    from jupyter_client.kernelspec import KernelSpecManager
+
+    TargetClient(target)  # validate
+    name = name or "probing-" + "".join(c if c.isalnum() else "-" for c in target)
+    spec = {
+        "argv": [sys.executable, "-m", "probing.kernel", "-f", "{connection_file}"],
+        "display_name": f"Probing ({target})",
+        "language": "python",
+        "env": {TARGET_ENV: target},
+    }
+    with tempfile.TemporaryDirectory() as tmp:
+        with open(os.path.join(tmp, "kernel.json"), "w") as f:
+            json.dump(spec, f, indent=2)
+        return KernelSpecManager().install_kernel_spec(
+            tmp,
+            kernel_name=name,
+            user=user,
+            replace=True,
+            prefix=None if user else sys.prefix,
+        )
+
+
+__all__ = ["ProbingKernel", "TargetClient", "install", "is_complete", "TARGET_ENV"]
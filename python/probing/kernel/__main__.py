@@ -0,0 +1,40 @@
+"""Launch or install the probing Jupyter kernel.
+
+    python -m probing.kernel install <pid|host:port> [--name NAME] [--sys-prefix]
+    python -m probing.kernel -f <connection_file>   # started by Jupyter
+"""
+
+import os
+import sys
+
+# The kernel only talks to the target, do not probe the kernel process itself
+os.environ["PROBING_CLI_MODE"] = "1"
+
+
+def main(argv=None):
+    argv = sys.argv[1:] if argv is None else argv
+    if argv and argv[0] == "install":
+        import argparse
+
+        from probing.kernel import install
+
+        parser = argparse.ArgumentParser(prog="python -m probing.kernel install")
+        parser.add_argument("target", help="pid or host:port of the target")
+        parser.add_argument("--name", help="kernel name, probing-<target> by default")
+        parser.add_argument(
+            "--sys-prefix", action="store_true", help="install into sys.prefix"
+        )
+        args = parser.parse_args(argv[1:])
+        path = install(args.target, name=args.name, user=not args.sys_prefix)
+        print(f"Installed kernelspec in {path}")
+        return
+
+    from ipykernel.kernelapp import IPKernelApp
+
+    from probing.kernel import ProbingKernel
+
+    IPKernelApp.launch_instance(argv=argv, kernel_class=ProbingKernel)
+
+
+if __name__ == "__main__":
+    main()
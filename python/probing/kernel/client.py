@@ -0,0 +1,107 @@
+"""HTTP client for the probing server of a target process.
+
+A target is a pid, reached through the unix socket the server listens on, or
+a `host:port` address. Requests carry `PROBING_AUTH_TOKEN` as a bearer token
+when it is set.
+"""
+
+import http.client
+import json
+import os
+import socket
+import sys
+import tempfile
+import urllib.parse
+from typing import Optional
+
+
+def socket_path(pid: int) -> str:
+    """Path of the unix socket the probing server of `pid` listens on."""
+    if sys.platform.startswith("linux"):
+        return f"\0probing-{pid}"
+    return os.path.join(tempfile.gettempdir(), f"probing-{pid}.sock")
+
+
+class UnixHTTPConnection(http.client.HTTPConnection):
+    def __init__(self, path: str, timeout: Optional[float] = None):
+        super().__init__("localhost", timeout=timeout)
+        self.socket_path = path
+
+    def connect(self):
+        sock = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+        sock.settimeout(self.timeout)
+        sock.connect(self.socket_path)
+        self.sock = sock
+
+
+class TargetClient:
+    """Send requests to the probing server of a target process.
+
+    >>> TargetClient("1234").pid
+    1234
+    >>> TargetClient("node1:8080").address
+    'node1:8080'
+    """
+
+    def __init__(self, target: str, timeout: Optional[float] = None):
+        target = str(target).strip()
+        if not target:
+            raise ValueError("no target given, use a pid or host:port")
+        self.pid = int(target) if target.isdigit() else None
+        self.address = None if target.isdigit() else target
+        self.timeout = timeout
+        self.token = os.environ.get("PROBING_AUTH_TOKEN")
+
+    def __repr__(self):
+        return f"TargetClient({self.pid or self.address})"
+
+    def _connection(self) -> http.client.HTTPConnection:
+        if self.pid is not None:
+            return UnixHTTPConnection(socket_path(self.pid), timeout=self.timeout)
+        return http.client.HTTPConnection(self.address, timeout=self.timeout)
+
+    def request(
+        self, path: str, body: Optional[str] = None, params: Optional[dict] = None
+    ) -> bytes:
+        """Send a request, POST when there is a body, and return the reply."""
+        if params:
+            path = f"{path}?{urllib.parse.urlencode(params)}"
+        headers = {}
+        if self.token:
+            headers["Authorization"] = f"Bearer {self.token}"
+        conn = self._connection()
+        try:
+            if body is None:
+                conn.request("GET", path, headers=headers)
+            else:
+                conn.request("POST", path, body=body.encode(), headers=headers)
+            response = conn.getresponse()
+            data = response.read()
+        finally:
+            conn.close()
+        if response.status >= 400:
+            raise RuntimeError(
+                f"{path} failed with {response.status}: {data.decode(errors='replace')}"
+            )
+        return data
+
+    def eval(self, code: str, session: Optional[str] = None) -> dict:
+        """Run `code` in the target, in the given REPL session if any.
+
+        Returns the result of the REPL, a dict with `status`, `output` and
+        `traceback`.
+        """
+        params = {"session": session} if session else None
+        reply = self.request("/apis/pythonext/eval", body=code, params=params)
+        text = reply.decode(errors="replace")
+        try:
+            result = json.loads(text)
+        except ValueError:
+            return {"status": "ok", "output": text, "traceback": []}
+        return result if isinstance(result, dict) else {"status": "ok", "output": text}
+
+    def close_session(self, name: str) -> dict:
+        """Close a REPL session of the target and release its namespace."""
+        path = "/apis/pythonext/repl/sessions/close"
+        reply = self.request(path, params={"name": name})
+        return json.loads(reply)
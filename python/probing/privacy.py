@@ -0,0 +1,24 @@
+"""Python wrapper for the redaction functions in _core.
+
+Values are redacted with the rules set in `privacy.redact_patterns` before
+they are stored in tables or sent to clients. Without the native module, for
+instance in the CLI, values are returned unchanged.
+"""
+
+
+def redact(text: str) -> str:
+    """Replace the matches of the redact patterns in `text`."""
+    try:
+        from probing import _core
+    except ImportError:
+        return text
+    return _core._redact(text)
+
+
+def redact_value(name: str, text: str) -> str:
+    """Redact the value of `name`, hiding it whole if the name is sensitive."""
+    try:
+        from probing import _core
+    except ImportError:
+        return text
+    return _core._redact_value(name, text)
@@ -0,0 +1,182 @@
+"""torch.compile diagnostics.
+
+``torch._dynamo`` keeps process wide records of the graph breaks, guard
+failures and compilations it went through. :class:`DynamoMonitor` turns what
+changed since its previous :meth:`~DynamoMonitor.collect` into rows of
+``torch.dynamo``, tagged with the current optimizer step, so that a step time
+regression after enabling ``torch.compile`` can be matched with the frames
+dynamo broke or recompiled in that step.
+
+:func:`collect` runs after every optimizer step, and after every compilation
+when ``torch._dynamo.callback`` is available. Nothing is imported from torch:
+programs that never use ``torch.compile`` do not pay for the monitor.
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT step, kind, code, reason FROM torch.dynamo WHERE kind != 'compile'"
+... )  # doctest: +SKIP
+"""
+
+import sys
+from typing import Any, Callable, Dict, Optional, Tuple
+
+#: Kinds of rows in ``torch.dynamo``
+COMPILE = "compile"
+RECOMPILE = "recompile"
+GRAPH_BREAK = "graph_break"
+GUARD_FAILURE = "guard_failure"
+
+
+def _record(kind, code, reason, count=1, duration=0.0, step=None, time=None):
+    from probing import _core
+
+    _core._record_dynamo_event(kind, code, reason, count, duration, step, time)
+
+
+def _current_step() -> Optional[int]:
+    # only read the step if the torch profiler is loaded, it imports torch
+    module = sys.modules.get("probing.profiling.torch.step")
+    return module.step() if module is not None else None
+
+
+def _code_name(code: Any) -> str:
+    name = getattr(code, "co_name", None)
+    if name is None:
+        return str(code)
+    return f"{name} ({code.co_filename}:{code.co_firstlineno})"
+
+
+class DynamoMonitor:
+    """Record the changes of the state kept by ``torch._dynamo.utils``.
+
+    Parameters
+    ----------
+    utils : module
+        ``torch._dynamo.utils``, or an object with the same attributes.
+    record : callable
+        Receives ``kind, code, reason, count, duration, step, time``.
+    """
+
+    def __init__(self, utils: Any, record: Callable = _record):
+        self.utils = utils
+        self.record = record
+        self.graph_breaks: Dict[str, int] = {}
+        self.guard_failures: Dict[Any, int] = {}
+        self.compilations: set = set()
+
+    def collect(self) -> int:
+        """Record what changed since the previous call, returns the row count."""
+        step = _current_step()
+        rows = 0
+        rows += self._collect_compilations(step)
+        rows += self._collect_graph_breaks(step)
+        rows += self._collect_guard_failures(step)
+        return rows
+
+    def _collect_graph_breaks(self, step: Optional[int]) -> int:
+        counters = getattr(self.utils, "counters", {})
+        rows = 0
+        for reason, count in list(counters.get(GRAPH_BREAK, {}).items()):
+            new = count - self.graph_breaks.get(reason, 0)
+            if new > 0:
+                self.record(GRAPH_BREAK, "", str(reason), new, 0.0, step, None)
+                rows += 1
+            self.graph_breaks[reason] = count
+        return rows
+
+    def _collect_guard_failures(self, step: Optional[int]) -> int:
+        failures = getattr(self.utils, "guard_failures", {})
+        rows = 0
+        for code, reasons in list(failures.items()):
+            seen = self.guard_failures.get(code, 0)
+            name = _code_name(code)
+            for reason in list(reasons)[seen:]:
+                self.record(GUARD_FAILURE, name, str(reason), 1, 0.0, step, None)
+                rows += 1
+            self.guard_failures[code] = len(reasons)
+        return rows
+
+    def _collect_compilations(self, step: Optional[int]) -> int:
+        get_metrics = getattr(self.utils, "get_compilation_metrics", None)
+        if get_metrics is None:
+            return 0
+        metrics = list(get_metrics())
+        rows = 0
+        keys = set()
+        for m in metrics:
+            key = _metrics_key(m)
+            keys.add(key)
+            if key in self.compilations:
+                continue
+            kind, reason = _classify(m)
+            code = "{} ({}:{})".format(
+                getattr(m, "co_name", None),
+                getattr(m, "co_filename", None),
+                getattr(m, "co_firstlineno", None),
+            )
+            duration = getattr(m, "entire_frame_compile_time_s", None) or 0.0
+            self.record(kind, code, reason, 1, float(duration), step, _start_ns(m))
+            rows += 1
+        # dynamo only keeps the last metrics, forget the evicted ones
+        self.compilations = keys
+        return rows
+
+
+def _metrics_key(m: Any) -> Tuple:
+    return (
+        str(getattr(m, "compile_id", None)),
+        getattr(m, "frame_key", None),
+        getattr(m, "start_time", None) or getattr(m, "start_time_us", None),
+    )
+
+
+def _classify(m: Any) -> Tuple[str, str]:
+    reason = getattr(m, "recompile_reason", None)
+    if reason or (getattr(m, "cache_size", 0) or 0) > 0:
+        return RECOMPILE, str(reason or "")
+    return COMPILE, str(getattr(m, "fail_reason", None) or "")
+
+
+def _start_ns(m: Any) -> Optional[int]:
+    start = getattr(m, "start_time", None)
+    if start is not None:
+        return int(start * 1e9)
+    start_us = getattr(m, "start_time_us", None)
+    if start_us is not None:
+        return int(start_us * 1000)
+    return None
+
+
+_monitor: Optional[DynamoMonitor] = None
+
+
+def collect() -> int:
+    """Record the dynamo diagnostics since the previous call.
+
+    Does nothing until the program imports ``torch._dynamo``.
+    """
+    global _monitor
+    if _monitor is None:
+        utils = sys.modules.get("torch._dynamo.utils")
+        if utils is None:
+            return 0
+        _monitor = DynamoMonitor(utils)
+        _register_compile_callback()
+    try:
+        return _monitor.collect()
+    except Exception:
+        # never let bookkeeping break the training step
+        return 0
+
+
+def _register_compile_callback():
+    callback = sys.modules.get("torch._dynamo.callback")
+    register = getattr(callback, "on_compile_end", None)
+    if register is None:
+        return
+    try:
+        register(lambda *args, **kwargs: collect())
+    except Exception:
+        pass
@@ -0,0 +1,478 @@
+"""Sharding state of FSDP and ``fully_shard`` models.
+
+With FSDP every rank holds a shard of the flat parameter of each unit, and
+an unlucky wrapping policy can leave a few units holding most of the memory.
+:func:`inspect_modules` walks the units of the models and reports, for each
+of them, how much this rank holds, and where each original parameter lives
+in the flat parameter. Both ``FullyShardedDataParallel`` (FSDP1) and
+``fully_shard`` (FSDP2) are supported; only private attributes describe the
+layout, so every lookup tolerates their absence.
+
+:class:`CollectiveTimer` wraps the functions FSDP issues its all-gathers and
+reduce-scatters from. They run on side streams, so CUDA events are recorded
+on those streams and resolved by later calls to :func:`collect`, which runs
+after every optimizer step.
+
+Nothing is imported from torch until the program imports
+``torch.distributed.fsdp``.
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT unit, shard_bytes FROM torch.fsdp_units ORDER BY shard_bytes DESC"
+... )  # doctest: +SKIP
+"""
+
+import sys
+import time
+from typing import Any, Callable, Dict, Iterable, List, Optional, Tuple
+
+FSDP1 = "FSDP"
+FSDP2 = "fully_shard"
+
+ALL_GATHER = "all_gather"
+REDUCE_SCATTER = "reduce_scatter"
+
+#: Optimizer steps between two layout inspections while no unit is found
+INSPECT_INTERVAL = 100
+
+
+def _current_step() -> Optional[int]:
+    module = sys.modules.get("probing.profiling.torch.step")
+    return module.step() if module is not None else None
+
+
+def _numel(shape: Any) -> int:
+    n = 1
+    for d in shape:
+        n *= int(d)
+    return n
+
+
+def _element_size(tensor: Any) -> int:
+    try:
+        return int(tensor.element_size())
+    except Exception:
+        return 0
+
+
+def _unit(
+    fqn: str,
+    api: str,
+    rank: int,
+    world_size: int,
+    params: int,
+    numel: int,
+    shard_numel: int,
+    padding: int,
+    element_size: int,
+    dtype: Any,
+) -> Dict[str, Any]:
+    return dict(
+        unit=fqn,
+        api=api,
+        rank=int(rank),
+        world_size=int(world_size),
+        params=int(params),
+        numel=int(numel),
+        shard_numel=int(shard_numel),
+        padding=int(padding),
+        shard_bytes=int(shard_numel) * element_size,
+        dtype=str(dtype),
+    )
+
+
+def _inspect_flat_param(fqn: str, module: Any) -> Optional[Tuple[Dict, List[Dict]]]:
+    """Layout of an FSDP1 unit, from its ``FlatParameter``."""
+    flat = getattr(getattr(module, "_handle", None), "flat_param", None)
+    if flat is None:
+        return None
+    names = list(getattr(flat, "_fqns", ()))
+    shapes = list(getattr(flat, "_shapes", ()))
+    numels = list(getattr(flat, "_numels", ()))
+    shard_infos = list(getattr(flat, "_shard_param_infos", ()))
+
+    # with use_orig_params=True, alignment padding sits between parameters
+    offsets = []
+    offset = 0
+    padding_mask = getattr(flat, "_is_padding_mask", None)
+    with_padding = getattr(flat, "_numels_with_padding", None)
+    if padding_mask is not None and with_padding is not None:
+        for is_padding, n in zip(padding_mask, with_padding):
+            if not is_padding:
+                offsets.append(offset)
+            offset += int(n)
+    else:
+        for n in numels:
+            offsets.append(offset)
+            offset += int(n)
+
+    local = getattr(flat, "_local_shard", None)
+    shard_numel = local.numel() if local is not None else flat.numel()
+    padding = int(getattr(flat, "_shard_numel_padded", 0) or 0)
+
+    params = []
+    for i, (name, shape, n) in enumerate(zip(names, shapes, numels)):
+        info = shard_infos[i] if i < len(shard_infos) else None
+        in_shard = info is not None and getattr(info, "in_shard", False)
+        params.append(
+            dict(
+                unit=fqn,
+                param=str(name),
+                shape=str(list(shape)),
+                numel=int(n),
+                offset=offsets[i] if i < len(offsets) else None,
+                shard_numel=int(info.numel_in_shard or 0) if in_shard else 0,
+            )
+        )
+    unit = _unit(
+        fqn,
+        FSDP1,
+        getattr(module, "rank", 0),
+        getattr(module, "world_size", 1),
+        len(params),
+        sum(numels),
+        shard_numel,
+        padding,
+        _element_size(flat),
+        getattr(flat, "dtype", ""),
+    )
+    return unit, params
+
+
+def _param_group(module: Any) -> Any:
+    try:
+        state = module._get_fsdp_state()
+    except Exception:
+        return None
+    return getattr(state, "_fsdp_param_group", None)
+
+
+def _inspect_param_group(fqn: str, module: Any) -> Optional[Tuple[Dict, List[Dict]]]:
+    """Layout of an FSDP2 unit, each parameter is sharded on its own."""
+    group = _param_group(module)
+    if group is None:
+        return None
+    mesh_info = getattr(group, "mesh_info", None)
+    params = []
+    numel = shard_numel = padding = element_size = 0
+    dtype = ""
+    for p in getattr(group, "fsdp_params", ()):
+        name = getattr(p, "_param_fqn", None) or getattr(
+            getattr(p, "_module_info", None), "param_name", ""
+        )
+        shape = getattr(p, "_orig_size", ())
+        local = _numel(getattr(p, "sharded_size", ()))
+        padded = getattr(p, "_sharded_param_data", None)
+        padded_numel = padded.numel() if padded is not None else local
+        numel += _numel(shape)
+        shard_numel += padded_numel
+        padding += padded_numel - local
+        sharded = getattr(p, "sharded_param", None)
+        if sharded is not None:
+            element_size = _element_size(sharded)
+            dtype = getattr(sharded, "dtype", dtype)
+        params.append(
+            dict(
+                unit=fqn,
+                param=str(name),
+                shape=str(list(shape)),
+                numel=_numel(shape),
+                offset=None,
+                shard_numel=local,
+            )
+        )
+    unit = _unit(
+        fqn,
+        FSDP2,
+        getattr(mesh_info, "shard_mesh_rank", 0),
+        getattr(mesh_info, "shard_mesh_size", 1),
+        len(params),
+        numel,
+        shard_numel,
+        padding,
+        element_size,
+        dtype,
+    )
+    return unit, params
+
+
+def inspect_modules(roots: Iterable[Any]) -> Tuple[List[Dict], List[Dict]]:
+    """Return the ``(units, params)`` rows of the FSDP units under ``roots``."""
+    units: List[Dict] = []
+    params: List[Dict] = []
+    seen = set()
+    for root in roots:
+        for fqn, module in root.named_modules():
+            if id(module) in seen:
+                continue
+            seen.add(id(module))
+            if type(module).__name__ == "FullyShardedDataParallel":
+                layout = _inspect_flat_param(fqn, module)
+            elif hasattr(module, "_get_fsdp_state"):
+                layout = _inspect_param_group(fqn, module)
+            else:
+                continue
+            if layout is not None:
+                units.append(layout[0])
+                params.extend(layout[1])
+    return units, params
+
+
+class CollectiveTimer:
+    """Time the collectives issued by wrapped functions.
+
+    ``unit_of`` maps the arguments of a wrapped function to ``(unit, bytes)``
+    and ``stream_of`` to the CUDA stream the collective runs on, ``None``
+    falls back to the host time of the call.
+    """
+
+    def __init__(self, clock: Callable[[], int] = time.time_ns):
+        self.clock = clock
+        self.pending: List[Tuple] = []
+
+    def wrap(
+        self,
+        func: Callable,
+        op: str,
+        unit_of: Callable[..., Tuple[str, int]],
+        stream_of: Callable[..., Any] = lambda *args, **kwargs: None,
+    ) -> Callable:
+        timer = self
+
+        def wrapper(*args, **kwargs):
+            try:
+                unit, nbytes = unit_of(*args, **kwargs)
+                stream = stream_of(*args, **kwargs)
+                start = timer._start(stream)
+            except Exception:
+                return func(*args, **kwargs)
+            now = timer.clock()
+            try:
+                return func(*args, **kwargs)
+            finally:
+                timer.pending.append(
+                    (now, _current_step(), unit, op, nbytes, start, timer._stop(stream))
+                )
+
+        wrapper.__wrapped__ = func
+        return wrapper
+
+    def _start(self, stream: Any) -> Any:
+        if stream is None:
+            return time.perf_counter()
+        import torch
+
+        event = torch.cuda.Event(enable_timing=True)
+        event.record(stream)
+        return event
+
+    def _stop(self, stream: Any) -> Any:
+        return self._start(stream)
+
+    def resolve(self) -> List[Dict]:
+        """Return the collectives that completed since the previous call."""
+        pending = []
+        done = []
+        for now, step, unit, op, nbytes, start, stop in self.pending:
+            if isinstance(start, float):
+                duration = stop - start
+            elif stop.query():
+                duration = start.elapsed_time(stop) / 1000.0
+            else:
+                pending.append((now, step, unit, op, nbytes, start, stop))
+                continue
+            done.append(
+                dict(
+                    time=now,
+                    step=step,
+                    unit=unit,
+                    op=op,
+                    duration=float(duration),
+                    bytes=int(nbytes),
+                )
+            )
+        self.pending = pending
+        return done
+
+
+class FsdpMonitor:
+    """Keep the FSDP tables up to date."""
+
+    def __init__(
+        self,
+        roots: Callable[[], Iterable[Any]],
+        set_layout: Optional[Callable] = None,
+        record: Optional[Callable] = None,
+    ):
+        if set_layout is None or record is None:
+            from probing import _core
+
+            set_layout = set_layout or _core._set_fsdp_layout
+            record = record or _core._record_fsdp_collectives
+        self.roots = roots
+        self.set_layout = set_layout
+        self.record = record
+        self.timer = CollectiveTimer()
+        self.units: List[Dict] = []
+        # keyed by id() of the FSDP1 handle or the first FSDP2 parameter
+        self.owners: Dict[int, Tuple[str, int]] = {}
+        self.calls = 0
+
+    def refresh(self) -> List[Dict]:
+        """Inspect the layout again, returns the unit rows."""
+        roots = list(self.roots())
+        units, params = inspect_modules(roots)
+        owners = {}
+        for root in roots:
+            for fqn, module in root.named_modules():
+                owner = getattr(module, "_handle", None)
+                if owner is None:
+                    group = _param_group(module)
+                    owner = next(iter(getattr(group, "fsdp_params", ())), None)
+                if owner is not None:
+                    owners[id(owner)] = fqn
+        by_unit = {u["unit"]: u for u in units}
+        self.owners = {
+            key: (fqn, by_unit[fqn]["shard_bytes"] * by_unit[fqn]["world_size"])
+            for key, fqn in owners.items()
+            if fqn in by_unit
+        }
+        self.units = units
+        self.set_layout(units, params)
+        return units
+
+    def owner(self, obj: Any) -> Tuple[str, int]:
+        return self.owners.get(id(obj), ("", 0))
+
+    def collect(self) -> int:
+        """Record the finished collectives, returns how many."""
+        if not self.units and self.calls % INSPECT_INTERVAL == 0:
+            self.refresh()
+        self.calls += 1
+        done = self.timer.resolve()
+        if done:
+            self.record(done)
+        return len(done)
+
+
+def _patch(module: Any, name: str, wrapper: Callable[[Callable], Callable]) -> None:
+    func = getattr(module, name, None) if module is not None else None
+    if func is None or hasattr(func, "__wrapped__"):
+        return
+    setattr(module, name, wrapper(func))
+
+
+def _arg(args: Tuple, kwargs: Dict, index: int, name: str) -> Any:
+    if name in kwargs:
+        return kwargs[name]
+    return args[index] if len(args) > index else None
+
+
+def _install(monitor: FsdpMonitor) -> None:
+    import torch
+
+    timer = monitor.timer
+    cuda = torch.cuda.is_available()
+
+    def stream(index, name):
+        return lambda *a, **kw: _arg(a, kw, index, name) if cuda else None
+
+    # FSDP1: _unshard(state, handle, unshard_stream, pre_unshard_stream) and
+    # _reduce_grad(state, handle), looked up through the module globals
+    runtime = sys.modules.get("torch.distributed.fsdp._runtime_utils")
+    _patch(
+        runtime,
+        "_unshard",
+        lambda f: timer.wrap(
+            f,
+            ALL_GATHER,
+            lambda *a, **kw: monitor.owner(_arg(a, kw, 1, "handle")),
+            stream(2, "unshard_stream"),
+        ),
+    )
+    _patch(
+        runtime,
+        "_reduce_grad",
+        lambda f: timer.wrap(
+            f,
+            REDUCE_SCATTER,
+            lambda *a, **kw: monitor.owner(_arg(a, kw, 1, "handle")),
+            lambda *a, **kw: (
+                getattr(_arg(a, kw, 0, "state"), "_post_backward_stream", None)
+                if cuda
+                else None
+            ),
+        ),
+    )
+
+    # FSDP2: the parameter group calls the collectives imported in its module
+    for name in (
+        "torch.distributed.fsdp._fully_shard._fsdp_param_group",
+        "torch.distributed._composable.fsdp._fsdp_param_group",
+    ):
+        group_module = sys.modules.get(name)
+        if group_module is None:
+            continue
+        _patch(
+            group_module,
+            "foreach_all_gather",
+            lambda f: timer.wrap(
+                f, ALL_GATHER, _fsdp2_owner(monitor), stream(4, "all_gather_stream")
+            ),
+        )
+        _patch(
+            group_module,
+            "foreach_reduce",
+            lambda f: timer.wrap(
+                f,
+                REDUCE_SCATTER,
+                _fsdp2_owner(monitor),
+                stream(3, "reduce_scatter_stream"),
+            ),
+        )
+
+
+def _fsdp2_owner(monitor: FsdpMonitor) -> Callable[..., Tuple[str, int]]:
+    # the collectives only receive the FSDPParams of the group
+    def owner(*args, **kwargs):
+        fsdp_params = _arg(args, kwargs, 0, "fsdp_params") or ()
+        return monitor.owner(next(iter(fsdp_params), None))
+
+    return owner
+
+
+_monitor: Optional[FsdpMonitor] = None
+
+
+def _toplevel_modules() -> List[Any]:
+    from probing.profiling.torch.module_utils import get_toplevel_module
+
+    return get_toplevel_module()
+
+
+def monitor() -> Optional[FsdpMonitor]:
+    """The monitor of the process, once ``torch.distributed.fsdp`` is loaded."""
+    global _monitor
+    if _monitor is None:
+        if "torch.distributed.fsdp" not in sys.modules:
+            return None
+        _monitor = FsdpMonitor(_toplevel_modules)
+        try:
+            _install(_monitor)
+        except Exception:
+            pass
+    return _monitor
+
+
+def collect() -> int:
+    """Record the FSDP layout and collectives since the previous call."""
+    m = monitor()
+    if m is None:
+        return 0
+    try:
+        return m.collect()
+    except Exception:
+        # never let bookkeeping break the training step
+        return 0
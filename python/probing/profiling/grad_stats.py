@@ -0,0 +1,247 @@
+"""Gradient statistics of each parameter group, recorded every few steps.
+
+Divergence is usually noticed long after it started, so the gradients are
+recorded all along rather than when someone attaches. With
+``torch.grad_stats`` set, the first optimizer step of each optimizer
+installs a hook on its parameters, called when backward has accumulated
+their gradient. On sampled steps the hook reduces the gradient on its device
+to four numbers (sum of squares, largest finite magnitude, NaN and infinite
+counts) without synchronizing; the next optimizer step adds them up per
+parameter group and records a row of ``torch.grad_stats``.
+
+Gradients are taken as produced by backward: before clipping, and including
+the loss scale of a ``GradScaler``. On torch versions without
+``register_post_accumulate_grad_hook`` they are read at the optimizer step.
+
+The option takes ``on`` (every step), ``every=<steps>`` or ``off``::
+
+    SET probing.torch.grad_stats='every=10';
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT step, \\"group\\", norm, nan_count FROM torch.grad_stats"
+... )  # doctest: +SKIP
+"""
+
+import math
+import sys
+import weakref
+from dataclasses import dataclass
+from typing import Any, Callable, Dict, List, Optional
+
+
+@dataclass
+class GradStatsConfig:
+    """Parsed value of the ``torch.grad_stats`` option."""
+
+    enabled: bool = False
+    every: int = 1
+
+    @staticmethod
+    def parse(spec: Optional[str]) -> "GradStatsConfig":
+        """Parse ``on``, ``off``, ``every=<steps>`` or ``on,every=<steps>``.
+
+        >>> GradStatsConfig.parse("every=10")
+        GradStatsConfig(enabled=True, every=10)
+        >>> GradStatsConfig.parse("off")
+        GradStatsConfig(enabled=False, every=1)
+        >>> GradStatsConfig.parse("")
+        GradStatsConfig(enabled=False, every=1)
+        """
+        config = GradStatsConfig()
+        for token in (spec or "").replace(",", " ").split():
+            token = token.strip().lower()
+            if token in ("on", "true", "1"):
+                config.enabled = True
+            elif token in ("off", "false", "0"):
+                config.enabled = False
+            elif token.startswith("every="):
+                every = int(token[len("every=") :])
+                if every <= 0:
+                    raise ValueError(f"every must be positive: {token}")
+                config.every = every
+                config.enabled = True
+            else:
+                raise ValueError(f"unknown grad_stats option: {token}")
+        return config
+
+
+def _record(step, optimizer, group, name, params, numel, norm, max_abs, nan, inf):
+    from probing import _core
+
+    _core._record_grad_stats(
+        step, optimizer, group, name, params, numel, norm, max_abs, nan, inf
+    )
+
+
+def reduce_grad(torch: Any, grad: Any) -> Any:
+    """Sum of squares, largest finite magnitude, NaN and inf counts of
+    ``grad``, as a float32 tensor on its device."""
+    grad = grad.detach()
+    if grad.is_sparse:
+        grad = grad.coalesce().values()
+    values = grad.float()
+    finite = torch.isfinite(values)
+    magnitudes = torch.where(finite, values.abs(), torch.zeros_like(values))
+    return torch.stack(
+        [
+            magnitudes.pow(2).sum(),
+            magnitudes.max() if values.numel() else values.new_zeros(()),
+            torch.isnan(values).sum().float(),
+            torch.isinf(values).sum().float(),
+        ]
+    )
+
+
+class GradStatsRecorder:
+    """Record the gradients of the parameter groups of optimizers.
+
+    Parameters
+    ----------
+    torch : module
+        ``torch``, or an object with the same attributes.
+    record : callable
+        Receives the columns of ``torch.grad_stats``.
+    """
+
+    def __init__(self, torch: Any, record: Callable = _record):
+        self.torch = torch
+        self.record = record
+        self.config = GradStatsConfig()
+        # per optimizer: name, steps seen, hook handles, reduced gradients
+        self._optimizers: "weakref.WeakKeyDictionary" = weakref.WeakKeyDictionary()
+
+    def configure(self, config: GradStatsConfig):
+        self.config = config
+        if not config.enabled:
+            self.remove()
+
+    def remove(self):
+        """Remove the hooks of all optimizers."""
+        for state in self._optimizers.values():
+            for handle in state["handles"]:
+                handle.remove()
+        self._optimizers = weakref.WeakKeyDictionary()
+
+    def _sampled(self, steps: int) -> bool:
+        return self.config.enabled and steps % self.config.every == 0
+
+    def _install(self, optimizer: Any) -> Dict:
+        index = len(self._optimizers)
+        name = type(optimizer).__name__
+        state = {
+            "name": name if index == 0 else f"{name}.{index}",
+            "steps": 0,
+            "handles": [],
+            "pending": {},
+            "hooked": False,
+        }
+        pending = state["pending"]
+
+        def hook(param):
+            if param.grad is not None and self._sampled(state["steps"]):
+                pending[id(param)] = reduce_grad(self.torch, param.grad)
+
+        for group in optimizer.param_groups:
+            for param in group["params"]:
+                register = getattr(param, "register_post_accumulate_grad_hook", None)
+                if register is None or not param.requires_grad:
+                    continue
+                state["handles"].append(register(hook))
+                state["hooked"] = True
+        self._optimizers[optimizer] = state
+        return state
+
+    def step(self, optimizer: Any):
+        """Record the sampled step that just ended; called after each step."""
+        if not self.config.enabled:
+            return
+        state = self._optimizers.get(optimizer)
+        installed = state is None
+        if installed:
+            state = self._install(optimizer)
+        try:
+            if self._sampled(state["steps"]):
+                # hooks only see the gradients of the next steps
+                self._flush(optimizer, state, installed or not state["hooked"])
+        finally:
+            state["pending"].clear()
+            state["steps"] += 1
+
+    def _flush(self, optimizer: Any, state: Dict, read_grads: bool):
+        torch = self.torch
+        pending = state["pending"]
+        for index, group in enumerate(optimizer.param_groups):
+            reduced: List[Any] = []
+            numel = 0
+            for param in group["params"]:
+                if param.grad is None:
+                    continue
+                stats = pending.get(id(param))
+                if stats is None and read_grads:
+                    stats = reduce_grad(torch, param.grad)
+                if stats is None:
+                    continue
+                reduced.append(stats.to(reduced[0].device) if reduced else stats)
+                numel += param.grad.numel()
+            if not reduced:
+                continue
+            stacked = torch.stack(reduced)
+            # a single synchronization per group
+            sumsq, max_abs, nan, inf = (
+                torch.stack(
+                    [
+                        stacked[:, 0].sum(),
+                        stacked[:, 1].max(),
+                        stacked[:, 2].sum(),
+                        stacked[:, 3].sum(),
+                    ]
+                )
+                .cpu()
+                .tolist()
+            )
+            self.record(
+                state["steps"],
+                state["name"],
+                index,
+                str(group.get("name", "")),
+                len(reduced),
+                numel,
+                math.sqrt(sumsq),
+                max_abs,
+                int(nan),
+                int(inf),
+            )
+
+
+_recorder: Optional[GradStatsRecorder] = None
+_config = GradStatsConfig()
+
+
+def configure(spec: Optional[str]) -> GradStatsConfig:
+    """Apply the ``torch.grad_stats`` option."""
+    global _config
+    _config = GradStatsConfig.parse(spec)
+    if _recorder is not None:
+        _recorder.configure(_config)
+    return _config
+
+
+def collect(optimizer: Any):
+    """Record the step of ``optimizer`` if it is sampled."""
+    global _recorder
+    if not _config.enabled:
+        return
+    if _recorder is None:
+        torch = sys.modules.get("torch")
+        if torch is None:
+            return
+        _recorder = GradStatsRecorder(torch)
+        _recorder.configure(_config)
+    try:
+        _recorder.step(optimizer)
+    except Exception:
+        # statistics must never break training
+        pass
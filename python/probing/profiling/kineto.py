@@ -0,0 +1,111 @@
+"""Kineto traces of ``torch.profiler`` as tables.
+
+``torch.profiler`` records GPU kernels, memory copies and CUDA runtime calls
+through Kineto and exports them as Chrome trace JSON, the timeline shown in
+Perfetto. :func:`load` parses such a trace into ``kineto.events``, with
+timestamps in nanoseconds since the epoch like the probing spans, so kernel
+time can be attributed with SQL instead of by eye.
+
+``%pytorch timeline`` loads the timeline it exports as the ``timeline`` trace,
+and :func:`trace_handler` loads every trace of a scheduled profiler.
+
+Examples
+--------
+>>> from probing.profiling import kineto
+>>> kineto.load("trace.json")  # doctest: +SKIP
+>>> probing.query(
+...     "SELECT name, count(*), sum(duration) FROM kineto.events "
+...     "WHERE cat = 'kernel' GROUP BY name"
+... )  # doctest: +SKIP
+"""
+
+import gzip
+import json
+import os
+import tempfile
+from typing import Any, Callable, Optional, Union
+
+Trace = Union[str, bytes, dict, os.PathLike]
+
+
+def _read(trace: Trace) -> str:
+    if isinstance(trace, dict):
+        return json.dumps(trace)
+    if isinstance(trace, bytes):
+        trace = trace.decode()
+    if isinstance(trace, str) and trace.lstrip()[:1] in ("{", "["):
+        return trace
+    path = os.fspath(trace)
+    opener = gzip.open if path.endswith(".gz") else open
+    with opener(path, "rt") as f:
+        return f.read()
+
+
+def _name(trace: Trace) -> str:
+    if isinstance(trace, (str, os.PathLike)):
+        path = os.fspath(trace)
+        if path.lstrip()[:1] not in ("{", "["):
+            name = os.path.basename(path)
+            for suffix in (".gz", ".json"):
+                if name.endswith(suffix):
+                    name = name[: -len(suffix)]
+            return name
+    return "trace"
+
+
+def load(trace: Trace, name: Optional[str] = None) -> int:
+    """Load a Chrome trace exported by ``torch.profiler`` into ``kineto.events``.
+
+    Parameters
+    ----------
+    trace : str, bytes, dict or path
+        Path of a ``.json`` or ``.json.gz`` trace, or the trace itself.
+    name : str, optional
+        Value of the ``trace`` column, the file name without extension by
+        default. Loading a trace again under the same name replaces it.
+
+    Returns
+    -------
+    int
+        Number of events loaded.
+
+    Raises
+    ------
+    ValueError
+        If the trace is not a Chrome trace.
+    """
+    from probing import _core
+
+    return _core._load_kineto_trace(name or _name(trace), _read(trace))
+
+
+def clear(name: Optional[str] = None) -> None:
+    """Drop the events of the trace ``name``, or of every trace."""
+    from probing import _core
+
+    _core._clear_kineto_traces(name)
+
+
+def trace_handler(name: str = "profiler") -> Callable[[Any], None]:
+    """Return an ``on_trace_ready`` callback loading every trace of a profiler.
+
+    Each cycle of the profiler schedule is loaded as ``<name>-<step>``.
+
+    Examples
+    --------
+    >>> torch.profiler.profile(
+    ...     schedule=torch.profiler.schedule(wait=1, warmup=1, active=2),
+    ...     on_trace_ready=kineto.trace_handler(),
+    ... )  # doctest: +SKIP
+    """
+
+    def handler(prof) -> None:
+        fd, path = tempfile.mkstemp(suffix=".json")
+        os.close(fd)
+        try:
+            prof.export_chrome_trace(path)
+            load(path, f"{name}-{getattr(prof, 'step_num', 0)}")
+        finally:
+            os.unlink(path)
+
+    return handler
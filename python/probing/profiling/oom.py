@@ -0,0 +1,264 @@
+"""CUDA out of memory forensics.
+
+By the time ``torch.cuda.OutOfMemoryError`` reaches a handler, the frames and
+tensors that filled the device are gone. :func:`install` attaches an observer
+to the CUDA caching allocator, called when an allocation fails and before the
+exception is raised, that records a row of ``oom.reports`` with:
+
+* the allocator statistics of ``torch.cuda.memory_stats``;
+* a summary of ``torch.cuda.memory_snapshot``, with the largest segments and
+  the largest free block, to tell fragmentation from exhaustion;
+* the probing spans active in the failing thread;
+* the largest live CUDA tensors, each storage counted once.
+
+The observer is attached once CUDA is initialized. Code catching the error
+can also call :func:`report` itself, e.g. on torch versions without
+allocator observers.
+
+Examples
+--------
+>>> import probing
+>>> probing.query(
+...     "SELECT time, device, requested, free, spans, tensors FROM oom.reports"
+... )  # doctest: +SKIP
+"""
+
+import gc
+import json
+import sys
+import threading
+from typing import Any, Callable, Dict, List, Optional
+
+#: Live tensors listed in a report
+MAX_TENSORS = 10
+
+#: Segments listed in the snapshot summary
+MAX_SEGMENTS = 16
+
+
+def _record(
+    device,
+    requested,
+    allocated,
+    reserved,
+    free,
+    message,
+    spans,
+    tensors,
+    stats,
+    snapshot,
+    step=None,
+    time=None,
+):
+    from probing import _core
+
+    _core._record_oom_report(
+        device,
+        requested,
+        allocated,
+        reserved,
+        free,
+        message,
+        spans,
+        tensors,
+        stats,
+        snapshot,
+        step,
+        time,
+    )
+
+
+def _current_step() -> Optional[int]:
+    # only read the step if the torch profiler is loaded, it imports torch
+    module = sys.modules.get("probing.profiling.torch.step")
+    return module.step() if module is not None else None
+
+
+def span_stack() -> str:
+    """Names of the active spans of the calling thread, outermost first."""
+    try:
+        from probing.tracing import _capture_span_stack
+
+        return " > ".join(span.name for span in _capture_span_stack())
+    except Exception:
+        return ""
+
+
+def largest_tensors(
+    torch: Any, limit: int = MAX_TENSORS, objects: Optional[List[Any]] = None
+) -> List[Dict]:
+    """The ``limit`` largest live CUDA tensors, by storage size.
+
+    Views share the storage of their base, which is only counted once.
+    """
+    seen = {}
+    for obj in gc.get_objects() if objects is None else objects:
+        try:
+            if not isinstance(obj, torch.Tensor) or not obj.is_cuda:
+                continue
+            storage = obj.untyped_storage()
+            key = (str(obj.device), storage.data_ptr())
+            nbytes = storage.nbytes()
+        except Exception:
+            continue
+        if key in seen and seen[key]["bytes"] >= nbytes:
+            continue
+        seen[key] = {
+            "shape": list(obj.shape),
+            "dtype": str(obj.dtype),
+            "device": str(obj.device),
+            "bytes": int(nbytes),
+            "requires_grad": bool(getattr(obj, "requires_grad", False)),
+        }
+    return sorted(seen.values(), key=lambda t: t["bytes"], reverse=True)[:limit]
+
+
+def summarize_snapshot(snapshot: Any, device: int) -> Dict:
+    """Summarize the segments of ``device`` in a ``memory_snapshot``."""
+    if isinstance(snapshot, dict):
+        snapshot = snapshot.get("segments", [])
+    summary = {
+        "segments": 0,
+        "total": 0,
+        "allocated": 0,
+        "active": 0,
+        "largest_free_block": 0,
+        "largest_segments": [],
+    }
+    rows = []
+    for segment in snapshot or []:
+        if segment.get("device", device) != device:
+            continue
+        summary["segments"] += 1
+        summary["total"] += segment.get("total_size", 0)
+        summary["allocated"] += segment.get("allocated_size", 0)
+        summary["active"] += segment.get("active_size", 0)
+        for block in segment.get("blocks", []):
+            if block.get("state") == "inactive":
+                summary["largest_free_block"] = max(
+                    summary["largest_free_block"], block.get("size", 0)
+                )
+        rows.append(
+            {
+                "address": segment.get("address", 0),
+                "size": segment.get("total_size", 0),
+                "allocated": segment.get("allocated_size", 0),
+                "pool": segment.get("segment_type", ""),
+                "stream": segment.get("stream", 0),
+            }
+        )
+    rows.sort(key=lambda s: s["size"], reverse=True)
+    summary["largest_segments"] = rows[:MAX_SEGMENTS]
+    return summary
+
+
+class OomRecorder:
+    """Record the state of a device when an allocation fails on it.
+
+    Parameters
+    ----------
+    torch : module
+        ``torch``, or an object with the same attributes.
+    record : callable
+        Receives the columns of ``oom.reports``.
+    """
+
+    def __init__(self, torch: Any, record: Callable = _record):
+        self.torch = torch
+        self.record = record
+        self._busy = threading.local()
+
+    def capture(
+        self, device: int, requested: int = 0, free: int = 0, message: str = ""
+    ) -> bool:
+        """Record a report of ``device``, returns False when re-entered."""
+        if getattr(self._busy, "active", False):
+            return False
+        self._busy.active = True
+        try:
+            cuda = self.torch.cuda
+            stats = _safe(lambda: dict(cuda.memory_stats(device)), {})
+            snapshot = _safe(
+                lambda: summarize_snapshot(cuda.memory_snapshot(), device), {}
+            )
+            tensors = _safe(lambda: largest_tensors(self.torch), [])
+            self.record(
+                int(device),
+                int(requested),
+                int(stats.get("allocated_bytes.all.current", 0)),
+                int(stats.get("reserved_bytes.all.current", 0)),
+                int(free),
+                str(message),
+                span_stack(),
+                json.dumps(tensors),
+                json.dumps(stats, default=str),
+                json.dumps(snapshot),
+                _current_step(),
+                None,
+            )
+            return True
+        finally:
+            self._busy.active = False
+
+    def observer(self, device, alloc, device_total, device_free):
+        """Allocator callback, called before the error is raised."""
+        try:
+            self.capture(device, alloc, device_free)
+        except Exception:
+            # never turn an out of memory error into a different one
+            pass
+
+
+def _safe(fn: Callable, default: Any) -> Any:
+    try:
+        return fn()
+    except Exception:
+        return default
+
+
+_recorder: Optional[OomRecorder] = None
+
+
+def _get_recorder() -> Optional[OomRecorder]:
+    global _recorder
+    if _recorder is None:
+        torch = sys.modules.get("torch")
+        if torch is None:
+            return None
+        _recorder = OomRecorder(torch)
+    return _recorder
+
+
+def install() -> bool:
+    """Attach the allocator observer once CUDA is initialized.
+
+    Returns False when torch has no CUDA or no allocator observers.
+    """
+    recorder = _get_recorder()
+    if recorder is None:
+        return False
+    torch = recorder.torch
+    attach = getattr(torch._C, "_cuda_attach_out_of_memory_observer", None)
+    if attach is None or not torch.cuda.is_available():
+        return False
+    # the observer is attached to the allocators of initialized devices only
+    torch.cuda._lazy_call(lambda: attach(recorder.observer))
+    return True
+
+
+def report(error: BaseException, device: Optional[int] = None) -> bool:
+    """Record a report for a caught ``torch.cuda.OutOfMemoryError``.
+
+    Use it where the allocator observer is not available; the report is
+    taken after the stack unwound to the handler.
+    """
+    recorder = _get_recorder()
+    if recorder is None:
+        return False
+    try:
+        if device is None:
+            device = recorder.torch.cuda.current_device()
+        free = _safe(lambda: recorder.torch.cuda.mem_get_info(device)[0], 0)
+        return recorder.capture(device, 0, free, str(error))
+    except Exception:
+        return False
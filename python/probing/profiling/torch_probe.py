@@ -167,6 +167,9 @@ class TorchProbeConfig:
 # Rust sync_env_settings() converts PROBING_TORCH_PROFILING to probing.torch.profiling
 _CONFIG_KEY = "probing.torch.profiling"
 
+# bumped by every `configure`, so the optimizer hooks notice new specs
+GENERATION = 0
+
 
 def configure(spec: Optional[str] = None) -> TorchProbeConfig:
     """Set a process-wide Torch profiling configuration.
@@ -199,6 +202,9 @@ def configure(spec: Optional[str] = None) -> TorchProbeConfig:
     >>> config.mode
     'random'
     """
+    global GENERATION
+    GENERATION += 1
+
     # Store the configuration spec in probing.config
     # Check if config module is available before using it
     if hasattr(probing, "config") and hasattr(probing.config, "set"):
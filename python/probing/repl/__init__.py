@@ -8,18 +8,21 @@ This module provides an in-process REPL (Read-Eval-Print Loop) for interactive d
 Responsibilities:
 1.  Embed an IPython kernel within the application process.
 2.  Execute Python code and custom magic commands dynamically.
-3.  Capture and return execution results (stdout, stderr, errors).
+3.  Capture and return execution results (stdout, stderr, errors), redacted
+    with the rules of `privacy.redact_patterns`.
 
 Public Interfaces:
 - `CodeExecutor`: Manages the lifecycle of the embedded IPython kernel.
 - `DebugConsole`: A wrapper around `CodeExecutor` compatible with `code.InteractiveConsole`.
+- `session_console`: The console of a named REPL session.
+- `private_console`: The console of a REPL connection naming no session.
 - `register_magic`: Decorator to define custom magic commands.
 """
 
 # from jupyter_client.session import Session
 import json
 from dataclasses import asdict, dataclass, field
-from typing import Dict, List, Optional, Type, Union
+from typing import Callable, Dict, List, Optional, Type, Union
 
 # Magic class registry
 _MAGIC_REGISTRY: Dict[str, Type] = {}
@@ -93,10 +96,14 @@ class ExecutionResult:
     status: str  # 'ok' or 'error'
     output: str = ""
     traceback: Optional[List[str]] = field(default_factory=list)
+    truncated: int = 0  # bytes of output dropped over the limit
 
     def to_json(self, indent: Optional[int] = None) -> str:
         """Serializes the result to a JSON string."""
-        return json.dumps(asdict(self), indent=indent)
+        result = asdict(self)
+        if not self.truncated:
+            del result["truncated"]
+        return json.dumps(result, indent=indent)
 
     def display(self):
         """Prints the execution result to the console."""
@@ -109,6 +116,52 @@ class ExecutionResult:
                 print(line)
 
 
+class OutputCapture:
+    """Output of one execution, capped as it is written.
+
+    Text past ``limit`` bytes is dropped and counted, a ``limit`` of 0 keeps
+    everything. With ``on_output``, text is handed over as soon as it is
+    written instead of kept, so a client sees it while the code runs.
+
+    >>> capture = OutputCapture(limit=8)
+    >>> capture.write("hello ")
+    >>> capture.write("wörld")
+    >>> capture.text(), capture.dropped
+    ('hello w', 5)
+    >>> sent = []
+    >>> capture = OutputCapture(on_output=sent.append)
+    >>> capture.write("step 1\\n")
+    >>> sent, capture.text()
+    (['step 1\\n'], '')
+    """
+
+    def __init__(self, limit: int = 0, on_output: Optional[Callable] = None):
+        self.limit = limit
+        self.on_output = on_output
+        self.size = 0
+        self.dropped = 0
+        self.parts: List[str] = []
+
+    def write(self, text: str):
+        data = text.encode("utf-8")
+        if self.limit:
+            room = max(self.limit - self.size, 0)
+            if len(data) > room:
+                text = data[:room].decode("utf-8", errors="ignore")
+                self.dropped += len(data) - len(text.encode("utf-8"))
+                data = text.encode("utf-8")
+        if not text:
+            return
+        self.size += len(data)
+        if self.on_output is not None:
+            self.on_output(text)
+        else:
+            self.parts.append(text)
+
+    def text(self) -> str:
+        return "".join(self.parts)
+
+
 class CodeExecutor:
     """A class that encapsulates an in-process IPython kernel for code execution.
 
@@ -201,7 +254,11 @@ class CodeExecutor:
 
                 warnings.warn(f"Failed to register {magic_name}: {e}", ImportWarning)
 
-    def execute(self, code_or_request: Union[str, dict]) -> ExecutionResult:
+    def execute(
+        self,
+        code_or_request: Union[str, dict],
+        capture: Optional[OutputCapture] = None,
+    ) -> ExecutionResult:
         """Executes a string of code or a request dictionary in the kernel.
 
         This method sends the code to the IPython kernel for execution and waits
@@ -215,6 +272,9 @@ class CodeExecutor:
         code_or_request : str or dict
             The code to execute as a string, or a dictionary conforming to the
             format `{'code': '...'}`.
+        capture : OutputCapture, optional
+            Receives the streamed output as the kernel publishes it, capped
+            at its limit; the result then holds what it kept.
 
         Returns
         -------
@@ -253,34 +313,62 @@ class CodeExecutor:
         else:
             request = code_or_request
 
-        # Execute the code, this is a non-blocking call
-        self.kc.execute(request["code"], silent=False)
+        # Hand stream messages to the capture as the kernel publishes them,
+        # so long running code shows its output while it runs
+        channel = self.kc.iopub_channel
+        call_handlers = channel.call_handlers
+        captured = capture is not None
+
+        def on_iopub(msg):
+            if msg["header"]["msg_type"] == "stream":
+                capture.write(redact(msg["content"]["text"]))
+            call_handlers(msg)
 
-        # Wait for and get the execution result
-        # For InProcessKernelClient, we can call get_shell_msg directly
-        reply = self.kc.get_shell_msg(timeout=5)
+        if captured:
+            channel.call_handlers = on_iopub
+        try:
+            # Execute the code, this is a non-blocking call
+            self.kc.execute(request["code"], silent=False)
+
+            # Wait for and get the execution result
+            # For InProcessKernelClient, we can call get_shell_msg directly
+            reply = self.kc.get_shell_msg(timeout=5)
+        finally:
+            if captured:
+                channel.call_handlers = call_handlers
 
         # Check execution status
         content = reply["content"]
         status = content["status"]
 
-        if status == "error":
-            traceback = content["traceback"]
-            return ExecutionResult(status="error", traceback=traceback)
-
         # Get all stdout/stderr output from the IOPub channel
         output = []
         while self.kc.iopub_channel.msg_ready():
             sub_msg = self.kc.get_iopub_msg(timeout=5)
             msg_type = sub_msg["header"]["msg_type"]
 
-            if msg_type == "stream":
+            if msg_type == "stream" and not captured:
                 output.append(sub_msg["content"]["text"])
             elif msg_type == "execute_result":
-                output.append(sub_msg["content"]["data"].get("text/plain", ""))
+                text = sub_msg["content"]["data"].get("text/plain", "")
+                if captured:
+                    capture.write(redact(text))
+                else:
+                    output.append(text)
+
+        if status == "error":
+            traceback = content["traceback"]
+            return ExecutionResult(status="error", traceback=traceback)
 
-        result_text = "".join(output).strip()
-        return ExecutionResult(status="ok", output=result_text)
+        if not captured:
+            return ExecutionResult(status="ok", output="".join(output).strip())
+        # what was streamed already went out, keep the rest as it is
+        text = capture.text()
+        return ExecutionResult(
+            status="ok",
+            output=text if capture.on_output else text.strip(),
+            truncated=capture.dropped,
+        )
 
     def shutdown(self):
         """Shuts down the kernel and its communication channels.
@@ -297,8 +385,12 @@ class CodeExecutor:
 
 import code
 
+from probing.privacy import redact
+
 
 class DebugConsole(code.InteractiveConsole):
+    _capture: Optional[OutputCapture] = None
+
     def __init__(self):
         try:
             self.code_executor = CodeExecutor()
@@ -341,20 +433,29 @@ class DebugConsole(code.InteractiveConsole):
         except (OverflowError, SyntaxError, ValueError):
             # Compilation failed - might be incomplete code or magic command
             # Let IPython kernel handle it (it understands magic commands)
-            retval = self.code_executor.execute(source)
+            retval = self.code_executor.execute(source, self._capture)
             self.resetbuffer()
             return retval
 
         if code is None:  # incomplete code
             return None
 
-        retval = self.code_executor.execute(source)
+        retval = self.code_executor.execute(source, self._capture)
         self.resetbuffer()
         return retval
 
-    def push(self, code: str):
+    def push(
+        self,
+        code: str,
+        max_output: int = 0,
+        on_output: Optional[Callable] = None,
+    ):
         """Pushes code to the executor and executes it.
 
+        Output past ``max_output`` bytes is dropped, and counted in the
+        ``truncated`` field of the result. With ``on_output``, output is
+        handed to it while the code runs instead of returned at the end.
+
         Examples
         --------
         >>> console = DebugConsole()
@@ -389,8 +490,14 @@ class DebugConsole(code.InteractiveConsole):
         try:
             self.buffer.append(code)
             source = "\n".join(self.buffer)
-            retval = self.runsource(source)
+            self._capture = OutputCapture(max_output, on_output)
+            try:
+                retval = self.runsource(source)
+            finally:
+                self._capture = None
             if retval is not None:
+                retval.output = redact(retval.output or "")
+                retval.traceback = [redact(line) for line in retval.traceback or []]
                 return retval.to_json()
             return json.dumps({})
         except Exception:
@@ -400,3 +507,64 @@ class DebugConsole(code.InteractiveConsole):
 
 
 debug_console = DebugConsole()
+
+
+class SessionConsole(DebugConsole):
+    """A console whose variables live in a namespace of its own.
+
+    The in-process kernel shell is a singleton, so sessions share the kernel
+    of `debug_console` and swap its namespace for their own while their code
+    runs. A session starts from a copy of the main namespace, so the globals
+    of the program stay reachable while its own variables stay private, and
+    `probing` is always importable from it, e.g. for `probing.query`.
+    """
+
+    def __init__(self, parent: DebugConsole):
+        code.InteractiveConsole.__init__(self)
+        self.code_executor = parent.code_executor
+        self.user_ns = None
+        if self.code_executor is not None:
+            import sys
+
+            self.user_ns = dict(self.code_executor.km.kernel.shell.user_ns)
+            if "probing" in sys.modules:
+                self.user_ns.setdefault("probing", sys.modules["probing"])
+
+    def runsource(self, source):
+        if self.code_executor is None:
+            return super().runsource(source)
+
+        from IPython.core.interactiveshell import DummyMod
+
+        shell = self.code_executor.km.kernel.shell
+        saved = shell.user_module, shell.user_ns
+        module = DummyMod()
+        module.__dict__ = self.user_ns
+        shell.user_module, shell.user_ns = module, self.user_ns
+        try:
+            return super().runsource(source)
+        finally:
+            shell.user_module, shell.user_ns = saved
+
+
+DEFAULT_SESSION = "default"
+
+
+def session_console(name: str) -> DebugConsole:
+    """Return a console for the named REPL session.
+
+    The default session is `debug_console` and works in the main namespace,
+    other sessions get a `SessionConsole`.
+    """
+    if name == DEFAULT_SESSION:
+        return debug_console
+    return SessionConsole(debug_console)
+
+
+def private_console() -> DebugConsole:
+    """Return a console of its own for a REPL connection naming no session.
+
+    It starts from a copy of the main namespace like a named session, but is
+    not registered anywhere and goes away with the connection.
+    """
+    return SessionConsole(debug_console)
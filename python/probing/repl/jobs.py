@@ -0,0 +1,108 @@
+"""
+Eval jobs
+
+Spec
+----
+Code evaluated as a job runs in a thread of its own so that a long running
+diagnostic does not hold up the HTTP worker that started it. The agent keeps
+the job registry; this module only runs the code of one job.
+
+Responsibilities:
+1.  Route what the job thread writes to `sys.stdout` and `sys.stderr` to the
+    writer of its job, redacted with the rules of `privacy.redact_patterns`,
+    while other threads keep writing to the original streams.
+2.  Run the code in the namespace of the REPL session of the job, and return
+    the value of a trailing expression like the REPL prints it.
+
+Public Interfaces:
+- `run`: Run the code of a job in the current thread.
+"""
+
+import ast
+import sys
+import threading
+from typing import Any, Dict, Optional
+
+from probing.privacy import redact
+
+# writers of the threads running a job, by thread ident
+_writers: Dict[int, Any] = {}
+_lock = threading.Lock()
+
+
+class _Router:
+    """A stream sending the writes of job threads to their job."""
+
+    def __init__(self, stream):
+        self.stream = stream
+
+    def write(self, text):
+        writer = _writers.get(threading.get_ident())
+        if writer is None:
+            return self.stream.write(text)
+        writer.write(redact(text))
+        return len(text)
+
+    def flush(self):
+        if threading.get_ident() not in _writers:
+            self.stream.flush()
+
+    def __getattr__(self, name):
+        return getattr(self.stream, name)
+
+
+def _install():
+    for name in ("stdout", "stderr"):
+        stream = getattr(sys, name)
+        if not isinstance(stream, _Router):
+            setattr(sys, name, _Router(stream))
+
+
+def namespace(console) -> dict:
+    """The namespace code of `console` runs in, the main one for `None`."""
+    if console is None:
+        from probing.repl import debug_console
+
+        console = debug_console
+    user_ns = getattr(console, "user_ns", None)
+    if user_ns is not None:
+        return user_ns
+    executor = getattr(console, "code_executor", None)
+    if executor is not None:
+        return executor.km.kernel.shell.user_ns
+    return sys.modules["__main__"].__dict__
+
+
+def run(code: str, console, writer) -> Optional[str]:
+    """Run `code` in the namespace of `console`, the main namespace for `None`,
+    writing its output to `writer`.
+
+    Returns the repr of the value of a trailing expression, if any.
+
+    >>> import io
+    >>> class Console: user_ns = {}
+    >>> out = io.StringIO()
+    >>> run("x = 6\\nprint('hello')\\nx * 7", Console(), out)
+    '42'
+    >>> out.getvalue()
+    'hello\\n'
+    """
+    with _lock:
+        _install()
+    ns = namespace(console)
+    tree = ast.parse(code, "<eval-job>", "exec")
+    last = None
+    if tree.body and isinstance(tree.body[-1], ast.Expr):
+        last = ast.Expression(tree.body.pop().value)
+
+    ident = threading.get_ident()
+    _writers[ident] = writer
+    try:
+        exec(compile(tree, "<eval-job>", "exec"), ns)
+        if last is not None:
+            value = eval(compile(last, "<eval-job>", "eval"), ns)
+            if value is not None:
+                return redact(repr(value))
+        return None
+    finally:
+        _writers.pop(ident, None)
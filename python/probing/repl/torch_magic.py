@@ -437,6 +437,14 @@ Examples:
                         self._cached_timeline = trace_json
                         self._timeline_exported = True
 
+                        # Make it queryable as kineto.events
+                        try:
+                            from probing.profiling import kineto
+
+                            kineto.load(trace_json, "timeline")
+                        except Exception:
+                            pass
+
                         return trace_json
                     finally:
                         try:
@@ -11,6 +11,18 @@ Notes
   (parent_id = -1, text fields = empty string) to avoid `None` persistence issues.
 * The public surface stays minimal: `span`, `Span.with_`, `Span.decorator`, `add_event`,
   and the `TraceEvent` dataclass table.
+* With ``PROBING_TRACING_CPU_TIME=1`` or `enable_cpu_time`, spans also measure the
+  CPU time of their thread (``CLOCK_THREAD_CPUTIME_ID``), recorded as ``cpu_time_ns``
+  in span_end rows: a span much longer than its CPU time was blocked.
+* Active spans are kept per thread. `capture_context` and `propagate` carry them over
+  to other threads, and `enable_propagation` (or ``PROBING_TRACING_PROPAGATE=1``) does
+  it for every task submitted to a `ThreadPoolExecutor`.
+* `inject` and `continue_trace` carry a trace over to another process, e.g. to the
+  worker running a Ray or Dask task.
+* Attribute values other than scalars go through serializers: tensors and numpy
+  arrays become their shape, dtype and a hash of small CPU buffers, dataclasses
+  their fields. `register_serializer` adds one for other types, anything else is
+  kept as a truncated ``repr``.
 
 Examples
 --------
@@ -34,12 +46,24 @@ Implicit name decorator::
     @probing.span
     def compute():
         return 42
+
+Thread pools::
+
+    from probing.tracing import propagate
+    with probing.span("step"):
+        pool.submit(propagate(load_batch), i)  # load_batch spans are children of step
 """
 
+import contextlib
+import dataclasses
+import datetime
 import functools
+import hashlib
 import inspect
+import json
+import os
 from dataclasses import dataclass
-from typing import Callable, Optional
+from typing import Any, Callable, Optional, Union
 
 # Import from the internal Rust module
 from probing import _core
@@ -48,10 +72,18 @@ try:
     Span = _core.Span
     span_raw = _core._span_raw
     current_span = _core.current_span
+    _capture_span_stack = _core._capture_span_stack
+    _restore_span_stack = _core._restore_span_stack
+    _set_span_cpu_time = _core._set_span_cpu_time
+    _span_cpu_time_enabled = _core._span_cpu_time_enabled
 except AttributeError:
     Span = None
     span_raw = None
     current_span = lambda: None
+    _capture_span_stack = lambda: []
+    _restore_span_stack = lambda stack: []
+    _set_span_cpu_time = lambda enabled: None
+    _span_cpu_time_enabled = lambda: False
 from probing.core.table import table
 
 
@@ -113,6 +145,9 @@ class TraceEvent:
         JSON string of span attributes (only in span rows).
     event_attributes : str, default ""
         JSON string of event attributes (only in event rows).
+    cpu_time_ns : int, default -1
+        CPU time of the thread in the span (only in span_end rows), -1 if not
+        measured or if the span ended on another thread.
     """
 
     # Required fields
@@ -129,6 +164,147 @@ class TraceEvent:
     location: Optional[str] = ""
     attributes: Optional[str] = ""
     event_attributes: Optional[str] = ""
+    cpu_time_ns: Optional[int] = -1
+
+
+# Serializers of attribute values as (class or qualified name, function), the
+# most recently registered first
+_serializers: list = []
+
+# Buffers hashed by the tensor and array serializers, larger ones are not
+_HASH_MAX_BYTES = 1 << 20
+
+# Nesting of containers and dataclasses serialized before falling back to repr
+_MAX_DEPTH = 3
+
+# Items of a list or dict kept in a serialized attribute
+_MAX_ITEMS = 32
+
+# Characters of the repr kept for values without a serializer
+_MAX_REPR = 256
+
+
+def register_serializer(type_: Union[type, str], func: Callable[[Any], Any]) -> None:
+    """Serialize span and event attributes of ``type_`` with ``func``.
+
+    Parameters
+    ----------
+    type_ : type or str
+        Class whose instances, subclasses included, ``func`` serializes, or
+        its qualified name such as ``"torch.Tensor"``, which matches without
+        importing the module.
+    func : Callable
+        Returns a JSON-compatible value, usually a small dict.
+
+    Later registrations take precedence, so a built-in serializer can be
+    replaced.
+
+    Examples
+    --------
+    >>> register_serializer("PIL.Image.Image", lambda im: {"size": list(im.size)})
+    """
+    _serializers.insert(0, (type_, func))
+
+
+def unregister_serializer(type_: Union[type, str]) -> None:
+    """Remove the serializers registered for ``type_``."""
+    _serializers[:] = [(t, f) for t, f in _serializers if t != type_]
+
+
+def _find_serializer(value) -> Optional[Callable[[Any], Any]]:
+    mro = type(value).__mro__
+    names = {f"{cls.__module__}.{cls.__qualname__}" for cls in mro}
+    for type_, func in _serializers:
+        matched = type_ in mro if isinstance(type_, type) else type_ in names
+        if matched:
+            return func
+    return None
+
+
+def _digest(data: bytes) -> str:
+    return hashlib.blake2b(data, digest_size=8).hexdigest()
+
+
+def _serialize_tensor(tensor) -> dict:
+    """Shape, dtype and device of a tensor, and a hash of CPU tensors up to 1 MiB.
+
+    Device tensors are not hashed, copying them would synchronize the device.
+    """
+    out = {
+        "type": "tensor",
+        "shape": list(tensor.shape),
+        "dtype": str(tensor.dtype).replace("torch.", ""),
+        "device": str(tensor.device),
+    }
+    nbytes = tensor.numel() * tensor.element_size()
+    if tensor.device.type == "cpu" and nbytes <= _HASH_MAX_BYTES:
+        import torch
+
+        try:
+            data = tensor.detach().contiguous().reshape(-1).view(torch.uint8)
+            out["hash"] = _digest(data.numpy().tobytes())
+        except Exception:
+            pass
+    return out
+
+
+def _serialize_ndarray(array) -> dict:
+    """Shape and dtype of a numpy array, and a hash of arrays up to 1 MiB."""
+    out = {"type": "ndarray", "shape": list(array.shape), "dtype": str(array.dtype)}
+    if array.nbytes <= _HASH_MAX_BYTES and not array.dtype.hasobject:
+        out["hash"] = _digest(array.tobytes())
+    return out
+
+
+register_serializer("torch.Tensor", _serialize_tensor)
+register_serializer("numpy.ndarray", _serialize_ndarray)
+
+
+def serialize_attribute(value, _depth: int = 0):
+    """JSON-compatible form of an attribute value.
+
+    Scalars are kept, registered types go through their serializer,
+    dataclasses, lists and dicts are serialized item by item, and anything
+    else becomes its ``repr``, truncated.
+    """
+    if value is None or isinstance(value, (bool, int, float, str)):
+        return value
+    if isinstance(value, (datetime.datetime, datetime.date)):
+        return value.isoformat()
+    func = _find_serializer(value)
+    if func is not None:
+        try:
+            return func(value)
+        except Exception as exc:
+            return f"<{type(value).__qualname__}: serializer failed: {exc}>"
+    if _depth < _MAX_DEPTH:
+        if dataclasses.is_dataclass(value) and not isinstance(value, type):
+            out = {"type": type(value).__qualname__}
+            for field in dataclasses.fields(value)[:_MAX_ITEMS]:
+                out[field.name] = serialize_attribute(
+                    getattr(value, field.name, None), _depth + 1
+                )
+            return out
+        if isinstance(value, (list, tuple)):
+            return [serialize_attribute(v, _depth + 1) for v in value[:_MAX_ITEMS]]
+        if isinstance(value, dict):
+            items = list(value.items())[:_MAX_ITEMS]
+            return {str(k): serialize_attribute(v, _depth + 1) for k, v in items}
+    text = repr(value)
+    return text if len(text) <= _MAX_REPR else text[: _MAX_REPR - 3] + "..."
+
+
+def serialize_attributes(attrs: dict) -> dict:
+    """Serialize every value of ``attrs`` with :func:`serialize_attribute`."""
+    return {str(k): serialize_attribute(v) for k, v in attrs.items()}
+
+
+def _attribute_json(value) -> Optional[str]:
+    """Compact JSON of a value that is not a scalar, for the attributes kept
+    on spans and events by the native module; ``None`` for scalars."""
+    if value is None or isinstance(value, (bool, int, float, str, datetime.datetime)):
+        return None
+    return json.dumps(serialize_attribute(value), separators=(",", ":"))
 
 
 def span(*args, **kwargs):
@@ -325,12 +501,10 @@ def _record_span_start(span: Span, attrs: dict):
     attrs : dict
         Creation-time attributes.
     """
-    import json
-
     # Convert attributes to JSON string
     attrs_json = None
     if attrs:
-        attrs_json = json.dumps(attrs)
+        attrs_json = json.dumps(serialize_attributes(attrs))
     # Sanitize None values to backend-friendly sentinels (tables reject Python None)
     parent_id = span.parent_id if span.parent_id is not None else -1
     kind = span.kind if span.kind is not None else ""
@@ -362,6 +536,7 @@ def _record_span_end(span: Span):
     import time
 
     end_ts = span.end_timestamp or int(time.time_ns())
+    cpu_time_ns = getattr(span, "cpu_time_ns", None)
     event = TraceEvent(
         record_type="span_end",
         trace_id=0,
@@ -374,6 +549,7 @@ def _record_span_end(span: Span):
         location="",
         attributes="",
         event_attributes="",
+        cpu_time_ns=cpu_time_ns if cpu_time_ns is not None else -1,
     )
     event.save()
 
@@ -390,7 +566,6 @@ def _record_event(span: Span, event_name: str, event_attributes: Optional[list]
     event_attributes : list, optional
         List of dicts or (key, value) tuples.
     """
-    import json
     import time
 
     # Get current timestamp (nanoseconds since epoch)
@@ -407,7 +582,7 @@ def _record_event(span: Span, event_name: str, event_attributes: Optional[list]
             elif isinstance(attr_item, (list, tuple)) and len(attr_item) == 2:
                 attrs_dict[attr_item[0]] = attr_item[1]
         if attrs_dict:
-            event_attrs_json = json.dumps(attrs_dict)
+            event_attrs_json = json.dumps(serialize_attributes(attrs_dict))
 
     parent_id = span.parent_id if span.parent_id is not None else -1
     kind = span.kind if span.kind is not None else ""
@@ -524,3 +699,160 @@ def add_event(name: str, *, attributes: Optional[list] = None):
 
 # Alias for add_event to match the top-level export
 event = add_event
+
+
+class SpanContext:
+    """Spans active in the thread that called :func:`capture_context`.
+
+    Running a function in the context makes the spans it opens children of
+    the innermost captured span, whatever thread it runs in. The active spans
+    of that thread are restored afterwards.
+    """
+
+    __slots__ = ("_stack",)
+
+    def __init__(self, stack: list):
+        self._stack = stack
+
+    def __bool__(self) -> bool:
+        return bool(self._stack)
+
+    def run(self, func: Callable, *args, **kwargs):
+        """Call ``func(*args, **kwargs)`` with the captured spans active."""
+        previous = _restore_span_stack(self._stack)
+        try:
+            return func(*args, **kwargs)
+        finally:
+            _restore_span_stack(previous)
+
+    def wrap(self, func: Callable) -> Callable:
+        """Return ``func`` bound to run in this context."""
+
+        @functools.wraps(func)
+        def wrapper(*args, **kwargs):
+            return self.run(func, *args, **kwargs)
+
+        return wrapper
+
+
+def capture_context() -> SpanContext:
+    """Capture the active spans of the calling thread.
+
+    Examples
+    --------
+    >>> with span("step"):
+    ...     ctx = capture_context()
+    >>> threading.Thread(target=ctx.wrap(load_batch)).start()  # doctest: +SKIP
+    """
+    return SpanContext(_capture_span_stack())
+
+
+def propagate(func: Callable) -> Callable:
+    """Bind ``func`` to the spans active now, for running in another thread."""
+    return capture_context().wrap(func)
+
+
+def inject() -> dict:
+    """Context of the active span, for continuing its trace in another process
+    with :func:`continue_trace`; empty outside of any span.
+
+    The context is a plain dict, so it travels with the arguments of a task
+    sent to another process.
+    """
+    parent = current_span()
+    if parent is None:
+        return {}
+    return {"trace_id": parent.trace_id, "span_id": parent.span_id}
+
+
+@contextlib.contextmanager
+def continue_trace(context: Optional[dict], name: str, *, kind=None, **attrs):
+    """Open a span as a child of the span a context made by :func:`inject` was
+    taken from, in the same trace, e.g. in the process running a remote task.
+    Without a context the span starts a trace of its own.
+
+    Examples
+    --------
+    >>> context = inject()  # in the process submitting the task  # doctest: +SKIP
+    >>> with continue_trace(context, "task"):  # in the worker  # doctest: +SKIP
+    ...     run()
+    """
+    context = context or {}
+    trace_id, parent_id = context.get("trace_id"), context.get("span_id")
+    if Span is None or trace_id is None or parent_id is None:
+        with span(name, kind=kind, **attrs) as s:
+            yield s
+        return
+
+    s = Span.remote_child(
+        int(trace_id), int(parent_id), name, kind=kind, location=_get_location()
+    )
+    if attrs:
+        s._set_initial_attrs(dict(attrs))
+    s.__enter__()
+    _record_span_start(s, attrs)
+    try:
+        yield s
+    except BaseException as exc:
+        s.__exit__(type(exc), exc, exc.__traceback__)
+        _record_span_end(s)
+        raise
+    s.__exit__(None, None, None)
+    _record_span_end(s)
+
+
+_original_submit = None
+
+
+def enable_propagation() -> None:
+    """Run every task submitted to a ``ThreadPoolExecutor`` in the span context
+    of the submitting thread.
+
+    ``Executor.map`` and ``loop.run_in_executor`` submit through the patched
+    method as well. Tasks submitted outside of any span are left untouched.
+    """
+    global _original_submit
+    from concurrent.futures import ThreadPoolExecutor
+
+    if _original_submit is not None:
+        return
+    original = ThreadPoolExecutor.submit
+
+    @functools.wraps(original)
+    def submit(self, fn, *args, **kwargs):
+        context = capture_context()
+        if context:
+            fn = context.wrap(fn)
+        return original(self, fn, *args, **kwargs)
+
+    ThreadPoolExecutor.submit = submit
+    _original_submit = original
+
+
+def disable_propagation() -> None:
+    """Undo :func:`enable_propagation`."""
+    global _original_submit
+    from concurrent.futures import ThreadPoolExecutor
+
+    if _original_submit is not None:
+        ThreadPoolExecutor.submit = _original_submit
+        _original_submit = None
+
+
+def enable_cpu_time(enabled: bool = True) -> None:
+    """Measure the CPU time of the thread running each span started from now on.
+
+    Reading the thread clock costs a system call at the start and the end of
+    each span, so it is off unless enabled here or with
+    ``PROBING_TRACING_CPU_TIME=1``.
+    """
+    _set_span_cpu_time(enabled)
+
+
+def cpu_time_enabled() -> bool:
+    """Whether spans measure the CPU time of their thread."""
+    return _span_cpu_time_enabled()
+
+
+if os.environ.get("PROBING_TRACING_PROPAGATE", "").lower() in ("1", "true", "on"):
+    enable_propagation()
@@ -4,10 +4,14 @@ extern crate ctor;
 use anyhow::Result;
 use pyo3::prelude::*;
 
-use probing_python::extensions::python::ExternalTable;
+use probing_core::core::{mpi, slurm};
+use probing_python::extensions::python::{ExternalTable, SchemaError};
 use probing_python::features::config;
+use probing_python::features::privacy;
 use probing_python::features::python_api::{cli_main, query_json};
+use probing_python::features::stacks;
 use probing_python::features::tracing;
+use probing_python::features::vm_tracer;
 use probing_python::features::vm_tracer::{
     _get_python_frames, _get_python_stacks, disable_tracer, enable_tracer, initialize_globals,
 };
@@ -17,6 +21,8 @@ use probing_python::pkg::TCPStore;
 
 const ENV_PROBING_LOGLEVEL: &str = "PROBING_LOGLEVEL";
 const ENV_PROBING_PORT: &str = "PROBING_PORT";
+#[cfg(feature = "tracing-bridge")]
+const ENV_PROBING_TRACING_LEVEL: &str = "PROBING_TRACING_LEVEL";
 
 #[cfg(feature = "use-mimalloc")]
 #[global_allocator]
@@ -89,23 +95,22 @@ fn setup_env_settings() {
                         );
                         report_port_basis = Some(port_number);
 
-                        let local_rank: u16 = std::env::var("LOCAL_RANK")
-                            .unwrap_or_else(|_| "0".to_string())
-                            .parse()
+                        // `LOCAL_RANK` of torchrun, or the local rank of `mpirun`
+                        let local_rank: u16 = mpi::local_rank()
+                            .and_then(|rank| rank.try_into().ok())
                             .unwrap_or(0);
                         let serving_port = port_number.saturating_add(local_rank);
 
-                        let hostname =
-                            if std::env::var("RANK").unwrap_or_else(|_| "0".to_string()) == "0" {
-                                "0.0.0.0".to_string()
-                            } else {
-                                get_hostname().unwrap_or_else(|err| {
-                                    log::warn!(
-                                        "Failed to get hostname: {err}, defaulting to localhost"
-                                    );
-                                    "localhost".to_string()
-                                })
-                            };
+                        let hostname = if mpi::rank().unwrap_or(0) == 0 {
+                            "0.0.0.0".to_string()
+                        } else {
+                            get_hostname().unwrap_or_else(|err| {
+                                log::warn!(
+                                    "Failed to get hostname: {err}, defaulting to localhost"
+                                );
+                                "localhost".to_string()
+                            })
+                        };
                         std::env::set_var(
                             "PROBING_SERVER_ADDR",
                             format!("'{hostname}:{serving_port}'"),
@@ -131,7 +136,11 @@ fn setup_env_settings() {
 
     // Setup reporting address only if a base port was determined (specific port, not RANDOM)
     if let Some(base_port_for_reporting) = report_port_basis {
-        if let Ok(master_addr) = std::env::var("MASTER_ADDR") {
+        // under Slurm without `MASTER_ADDR`, rank 0 runs on the first node
+        let master_addr = std::env::var("MASTER_ADDR")
+            .ok()
+            .or_else(|| slurm::job().and_then(|job| job.first_host()));
+        if let Some(master_addr) = master_addr {
             if !master_addr.is_empty() {
                 // Ensure MASTER_ADDR is not empty
                 log::debug!("Configuring PROBING_SERVER_REPORT_ADDR to {master_addr}:{base_port_for_reporting} based on MASTER_ADDR and base port");
@@ -164,6 +173,18 @@ fn setup() {
     // This needs to happen early, even if Python module is not imported
     probing_server::start_local();
 
+    // Return the pages mimalloc keeps for reuse once the probe is idle
+    #[cfg(feature = "use-mimalloc")]
+    probing_server::janitor::on_reclaim(|| unsafe { libmimalloc_sys::mi_collect(true) });
+
+    // Forward spans from the `tracing` crate into the trace tables
+    #[cfg(feature = "tracing-bridge")]
+    if let Ok(level) = std::env::var(ENV_PROBING_TRACING_LEVEL) {
+        if let Err(e) = probing_core::trace::install_tracing_bridge(&level) {
+            log::warn!("Failed to install tracing bridge: {e}");
+        }
+    }
+
     // Setup environment variables
     setup_env_settings();
     sync_env_settings();
@@ -196,6 +217,7 @@ fn _core(m: &Bound<'_, PyModule>) -> PyResult<()> {
 
     // Register all classes
     m.add_class::<ExternalTable>()?;
+    m.add("SchemaError", m.py().get_type::<SchemaError>())?;
     m.add_class::<TCPStore>()?;
 
     // Register all functions
@@ -217,5 +239,47 @@ fn _core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     // Register tracing classes and functions directly to the module (flattened)
     tracing::register_tracing_functions(m)?;
 
+    // Register recording of the framework tables
+    #[cfg(feature = "analytics")]
+    register_analytics_functions(m)?;
+
+    // Register redaction of captured values
+    privacy::register_privacy_functions(m)?;
+
+    // Register the stack dictionary
+    stacks::register_stacks_functions(m)?;
+
+    // Register the module filter shared with the eval frame hook
+    vm_tracer::register_tracer_functions(m)?;
+
+    Ok(())
+}
+
+/// Recording functions of the tables built with the `analytics` feature
+#[cfg(feature = "analytics")]
+fn register_analytics_functions(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    use probing_python::features::{dynamo, fsdp, grad_stats, inference, kineto, oom, tasks};
+
+    // Register torch.compile diagnostics recording
+    dynamo::register_dynamo_functions(m)?;
+
+    // Register CUDA out of memory reports
+    oom::register_oom_functions(m)?;
+
+    // Register gradient statistics of parameter groups
+    grad_stats::register_grad_stats_functions(m)?;
+
+    // Register FSDP sharding state recording
+    fsdp::register_fsdp_functions(m)?;
+
+    // Register inference server metrics recording
+    inference::register_inference_functions(m)?;
+
+    // Register loading of torch.profiler traces
+    kineto::register_kineto_functions(m)?;
+
+    // Register tasks of Ray and Dask workers
+    tasks::register_tasks_functions(m)?;
+
     Ok(())
 }
@@ -0,0 +1,153 @@
+"""Tests for the offline analysis of archives."""
+
+import pytest
+
+pl = pytest.importorskip("polars")
+
+from probing.analysis import (  # noqa: E402
+    Archive,
+    regressions,
+    render_report,
+    step_breakdown,
+    stragglers,
+)
+
+
+def trace_rows(step_time, steps=3, layer_time=None):
+    """Rows of python.torch_trace for a model `None` with one child `layer`."""
+    rows = []
+    for step in range(steps):
+        for stage, seconds in (
+            ("forward", step_time * 0.3),
+            ("backward", step_time * 0.6),
+        ):
+            rows.append((step, "None", "pre " + stage, 0.0))
+            rows.append((step, "None", "post " + stage, seconds))
+            rows.append((step, "layer", "post " + stage, layer_time or seconds / 2))
+        rows.append((step, "SGD", "post step", step_time * 0.1))
+    return pl.DataFrame(
+        rows, schema=["step", "module", "stage", "duration"], orient="row"
+    )
+
+
+def write_archive(root, ranks):
+    for rank, step_time in enumerate(ranks):
+        directory = root / f"rank{rank}"
+        directory.mkdir(parents=True)
+        trace_rows(step_time).write_parquet(directory / "python.torch_trace.parquet")
+    return Archive(str(root))
+
+
+def test_step_breakdown_sums_outermost_modules(tmp_path):
+    archive = write_archive(tmp_path, [1.0])
+    assert archive.tables == ["python.torch_trace"]
+    assert archive.ranks == [0]
+
+    steps = step_breakdown(archive)
+    assert steps.columns == [
+        "rank",
+        "step",
+        "forward",
+        "backward",
+        "optimizer",
+        "total",
+    ]
+    assert steps.height == 3
+    row = steps.row(0, named=True)
+    # the child module is part of the forward of its parent
+    assert row["forward"] == pytest.approx(0.3)
+    assert row["backward"] == pytest.approx(0.6)
+    assert row["total"] == pytest.approx(1.0)
+
+
+def test_stragglers(tmp_path):
+    archive = write_archive(tmp_path, [1.0, 1.0, 1.5, 1.0])
+    assert archive.ranks == [0, 1, 2, 3]
+
+    result = stragglers(archive)
+    first = result.row(0, named=True)
+    assert first["rank"] == 2
+    assert first["straggler"]
+    assert first["slowest"] == 3
+    assert first["ratio"] == pytest.approx(1.5)
+    assert result.get_column("straggler").sum() == 1
+
+
+def test_regressions_against_baseline(tmp_path):
+    baseline = tmp_path / "baseline"
+    baseline.mkdir()
+    trace_rows(1.0).write_csv(baseline / "python.torch_trace.csv")
+    current = tmp_path / "current"
+    current.mkdir()
+    trace_rows(1.0, layer_time=0.5).write_csv(current / "python.torch_trace.csv")
+
+    result = regressions(Archive(str(current)), Archive(str(baseline)))
+    ops = list(zip(result.get_column("kind"), result.get_column("op")))
+    assert ops == [("forward", "layer"), ("backward", "layer")]
+    assert result.row(0, named=True)["delta"] == pytest.approx(0.5 - 0.15)
+
+
+def test_render_report(tmp_path):
+    archive = write_archive(tmp_path, [1.0, 2.0])
+    page = render_report(archive, baseline=archive)
+    assert page.startswith("<!DOCTYPE html>")
+    assert "Step time breakdown" in page
+    assert 'class="flagged"' in page
+    assert "regressed operators" in page
+
+
+def test_missing_table(tmp_path):
+    with pytest.raises(KeyError):
+        Archive(str(tmp_path)).table("python.torch_trace")
+    with pytest.raises(FileNotFoundError):
+        Archive(str(tmp_path / "missing"))
+
+
+def test_shutdown_snapshots(tmp_path):
+    import json
+
+    archive = write_archive(tmp_path, [1.0, 1.0])
+    assert archive.shutdowns == []
+    for rank in (1, 0):
+        snapshot = {"signal": "SIGTERM", "rank": rank, "step": 10 + rank}
+        (tmp_path / f"rank{rank}" / f"shutdown-{100 + rank}.json").write_text(
+            json.dumps(snapshot)
+        )
+    assert [s["step"] for s in archive.shutdowns] == [10, 11]
+    # not mistaken for a table
+    assert "shutdown-100" not in archive
+
+
+def test_bundle_diff(tmp_path):
+    import json
+
+    from probing.analysis import diff, format_diff
+
+    def with_config(root, options):
+        archive = write_archive(root, [1.0, 1.0])
+        (root / "rank0" / "config.json").write_text(
+            json.dumps({"options": options, "store": {}})
+        )
+        return Archive(str(root))
+
+    good = with_config(
+        tmp_path / "good",
+        {"probing.profile": "minimal", "probing.torch.profiling": "off"},
+    )
+    bad = with_config(
+        tmp_path / "bad",
+        {"probing.profile": "dataloader-debug", "probing.pprof.sample_freq": "10"},
+    )
+    assert good.configs[0]["options"]["probing.profile"] == "minimal"
+
+    changes = diff(good, bad)
+    assert changes["profile"] == [
+        ("probing.profile", "minimal", "dataloader-debug")
+    ]
+    assert changes["config"] == [
+        ("probing.pprof.sample_freq", None, "10"),
+        ("probing.torch.profiling", "off", None),
+    ]
+    assert changes["metrics"] == []
+    assert "probing.profile: minimal -> dataloader-debug" in format_diff(changes)
+    assert format_diff(diff(good, good)) == "no differences"
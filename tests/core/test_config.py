@@ -214,3 +214,32 @@ def test_mixed_types():
     assert abs(probing.config.get("float") - 3.14) < 1e-5
     assert probing.config.get("bool") is True
     assert probing.config.get("none") is None
+
+
+@pytest.mark.skipif(not config_available, reason="probing.config module not available")
+def test_report_step_stops_the_count(monkeypatch):
+    """Steps reported by the training replace those counted by the hooks."""
+    reported = []
+    monkeypatch.setattr(probing._core, "config_report_step", reported.append)
+    monkeypatch.setattr(probing.config, "_reported", False)
+
+    probing.config._count_step(1)
+    probing.config.report_step(10)
+    probing.config._count_step(2)
+    assert reported == [1, 10]
+
+
+@pytest.mark.skipif(not config_available, reason="probing.config module not available")
+def test_count_steps_of_first_optimizer(monkeypatch):
+    """Several optimizers still count one step per iteration."""
+    from probing.ext import torch as ext
+
+    counted = []
+    monkeypatch.setattr(ext, "steps", {})
+    monkeypatch.setattr(probing.config, "_count_step", counted.append)
+
+    first, second = object(), object()
+    for _ in range(2):
+        ext.count_step(first)
+        ext.count_step(second)
+    assert counted == [1, 2]
@@ -0,0 +1,79 @@
+"""Tests for the torch.compile diagnostics monitor."""
+
+from collections import Counter, defaultdict
+from types import SimpleNamespace
+
+
+def fake_utils():
+    return SimpleNamespace(
+        counters=defaultdict(Counter),
+        guard_failures=defaultdict(list),
+        metrics=[],
+        get_compilation_metrics=None,
+    )
+
+
+def test_records_changes_only():
+    from probing.profiling.dynamo import DynamoMonitor
+
+    utils = fake_utils()
+    utils.get_compilation_metrics = lambda: list(utils.metrics)
+    rows = []
+    monitor = DynamoMonitor(utils, record=lambda *row: rows.append(row))
+
+    def forward():
+        pass
+
+    utils.metrics.append(
+        SimpleNamespace(
+            compile_id="0/0",
+            co_name="forward",
+            co_filename="model.py",
+            co_firstlineno=10,
+            cache_size=0,
+            start_time=1.5,
+            entire_frame_compile_time_s=2.0,
+        )
+    )
+    utils.counters["graph_break"]["call_function print"] += 2
+    assert monitor.collect() == 2
+    kind, code, reason, count, duration, _, time = rows[0]
+    assert (kind, code, duration, time) == (
+        "compile",
+        "forward (model.py:10)",
+        2.0,
+        1_500_000_000,
+    )
+    assert rows[1][:4] == ("graph_break", "", "call_function print", 2)
+
+    # nothing new
+    assert monitor.collect() == 0
+
+    utils.metrics.append(
+        SimpleNamespace(
+            compile_id="0/1",
+            co_name="forward",
+            co_filename="model.py",
+            co_firstlineno=10,
+            cache_size=1,
+            recompile_reason="tensor 'x' size mismatch at index 0",
+        )
+    )
+    utils.counters["graph_break"]["call_function print"] += 1
+    utils.guard_failures[forward.__code__].append("x.size()[0] == 8")
+    assert monitor.collect() == 3
+    assert rows[2][:3] == (
+        "recompile",
+        "forward (model.py:10)",
+        "tensor 'x' size mismatch at index 0",
+    )
+    assert rows[3][:4] == ("graph_break", "", "call_function print", 1)
+    assert rows[4][0] == "guard_failure"
+    assert rows[4][1].startswith("forward (")
+    assert rows[4][2] == "x.size()[0] == 8"
+
+
+def test_collect_without_dynamo():
+    from probing.profiling import dynamo
+
+    assert dynamo.collect() == 0
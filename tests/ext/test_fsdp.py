@@ -0,0 +1,120 @@
+"""Tests for the FSDP sharding state inspection."""
+
+from types import SimpleNamespace
+
+
+class FakeTensor(SimpleNamespace):
+    def numel(self):
+        return self.n
+
+    def element_size(self):
+        return 4
+
+
+class FullyShardedDataParallel(SimpleNamespace):
+    pass
+
+
+class Module(SimpleNamespace):
+    def named_modules(self):
+        return self.children
+
+
+def fsdp1_unit():
+    flat = FakeTensor(
+        n=6,
+        dtype="torch.float32",
+        _fqns=("weight", "bias"),
+        _shapes=((2, 4), (2,)),
+        _numels=(8, 2),
+        _shard_numel_padded=2,
+        _shard_param_infos=(
+            SimpleNamespace(in_shard=True, numel_in_shard=6),
+            SimpleNamespace(in_shard=False, numel_in_shard=None),
+        ),
+    )
+    return FullyShardedDataParallel(
+        _handle=SimpleNamespace(flat_param=flat), rank=0, world_size=2
+    )
+
+
+def fsdp2_unit():
+    param = SimpleNamespace(
+        _param_fqn="proj.weight",
+        _orig_size=(3, 4),
+        sharded_size=(2, 4),
+        _sharded_param_data=FakeTensor(n=8),
+        sharded_param=FakeTensor(n=8, dtype="torch.bfloat16"),
+    )
+    group = SimpleNamespace(
+        fsdp_params=[param],
+        mesh_info=SimpleNamespace(shard_mesh_rank=1, shard_mesh_size=2),
+    )
+    state = SimpleNamespace(_fsdp_param_group=group)
+    return Module(_get_fsdp_state=lambda: state), param
+
+
+def test_inspect_modules():
+    from probing.profiling.fsdp import inspect_modules
+
+    unit1 = fsdp1_unit()
+    unit2, _ = fsdp2_unit()
+    root = Module(children=[("", Module()), ("layers.0", unit1), ("proj", unit2)])
+    units, params = inspect_modules([root])
+
+    assert [(u["unit"], u["api"]) for u in units] == [
+        ("layers.0", "FSDP"),
+        ("proj", "fully_shard"),
+    ]
+    assert units[0]["numel"] == 10
+    assert units[0]["shard_bytes"] == 24
+    assert units[0]["padding"] == 2
+    assert units[1]["rank"] == 1
+    assert units[1]["shard_numel"] == 8
+    assert units[1]["dtype"] == "torch.bfloat16"
+
+    weight, bias, proj = params
+    assert (weight["offset"], weight["shard_numel"]) == (0, 6)
+    assert (bias["offset"], bias["shard_numel"]) == (8, 0)
+    assert (proj["shape"], proj["offset"], proj["shard_numel"]) == ("[3, 4]", None, 8)
+
+
+def test_collectives_are_attributed_to_units():
+    from probing.profiling.fsdp import ALL_GATHER, FsdpMonitor
+
+    unit1 = fsdp1_unit()
+    unit2, param = fsdp2_unit()
+    root = Module(children=[("layers.0", unit1), ("proj", unit2)])
+    layouts, batches = [], []
+    monitor = FsdpMonitor(
+        lambda: [root],
+        set_layout=lambda units, params: layouts.append(units),
+        record=batches.append,
+    )
+    assert monitor.collect() == 0
+    assert len(layouts) == 1
+
+    calls = []
+    unshard = monitor.timer.wrap(
+        lambda state, handle: calls.append(handle),
+        ALL_GATHER,
+        lambda state, handle: monitor.owner(handle),
+    )
+    unshard(None, unit1._handle)
+    unshard(None, object())
+    assert len(calls) == 2
+    assert monitor.collect() == 2
+    first, unknown = batches[0]
+    assert (first["unit"], first["op"], first["bytes"]) == ("layers.0", ALL_GATHER, 48)
+    assert unknown["unit"] == ""
+    assert monitor.owner(param) == ("proj", 64)
+
+    # the layout is not inspected again once units are known
+    monitor.collect()
+    assert len(layouts) == 1
+
+
+def test_collect_without_fsdp():
+    from probing.profiling import fsdp
+
+    assert fsdp.collect() == 0
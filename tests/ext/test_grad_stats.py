@@ -0,0 +1,62 @@
+"""Tests for the gradient statistics of parameter groups."""
+
+import math
+
+import pytest
+
+from probing.profiling.grad_stats import GradStatsConfig, GradStatsRecorder
+
+
+def test_parse_config():
+    assert GradStatsConfig.parse("on") == GradStatsConfig(enabled=True, every=1)
+    assert GradStatsConfig.parse("on,every=5") == GradStatsConfig(True, 5)
+    assert GradStatsConfig.parse("every=5 off") == GradStatsConfig(False, 5)
+    assert GradStatsConfig.parse(None) == GradStatsConfig()
+    for spec in ("every=0", "every=x", "sometimes"):
+        with pytest.raises(ValueError):
+            GradStatsConfig.parse(spec)
+
+
+def test_recorder():
+    torch = pytest.importorskip("torch")
+
+    rows = []
+    recorder = GradStatsRecorder(torch, record=lambda *row: rows.append(row))
+    recorder.configure(GradStatsConfig.parse("every=2"))
+
+    weight = torch.nn.Parameter(torch.ones(3))
+    bias = torch.nn.Parameter(torch.ones(2))
+    frozen = torch.nn.Parameter(torch.ones(2))
+    optimizer = torch.optim.SGD(
+        [{"params": [weight, bias], "name": "dense"}, {"params": [frozen]}], lr=0.0
+    )
+
+    def train_step(scale):
+        optimizer.zero_grad()
+        ((weight * scale).sum() + (bias * 2).sum()).backward()
+        optimizer.step()
+        recorder.step(optimizer)
+
+    # the first step installs the hooks, gradients are read at the step
+    train_step(1.0)
+    assert len(rows) == 1
+    step, name, group, group_name, params, numel, norm, max_abs, nan, inf = rows[0]
+    assert (step, name, group, group_name) == (0, "SGD", 0, "dense")
+    assert (params, numel, nan, inf) == (2, 5, 0, 0)
+    assert math.isclose(norm, math.sqrt(3 + 2 * 4))
+    assert max_abs == 2.0
+
+    # not sampled
+    train_step(1.0)
+    assert len(rows) == 1
+
+    train_step(float("nan"))
+    assert len(rows) == 2
+    assert rows[1][0] == 2
+    assert rows[1][-2:] == (3, 0)
+    assert math.isclose(rows[1][6], math.sqrt(8))
+
+    recorder.configure(GradStatsConfig.parse("off"))
+    train_step(1.0)
+    train_step(1.0)
+    assert len(rows) == 2
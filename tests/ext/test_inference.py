@@ -0,0 +1,109 @@
+"""Tests for the inference server integrations."""
+
+import asyncio
+from types import SimpleNamespace
+
+
+class Recorder:
+    def __init__(self):
+        self.kv_cache = []
+        self.requests = []
+
+    def record_kv_cache(self, source, engine, **fields):
+        self.kv_cache.append(dict(source=source, engine=engine, **fields))
+
+    def record_request(self, source, engine, request_id, **fields):
+        self.requests.append(dict(engine=engine, request_id=request_id, **fields))
+
+
+class BlockManager:
+    def __init__(self, free_gpu):
+        self.free_gpu = free_gpu
+
+    def get_num_free_gpu_blocks(self):
+        return self.free_gpu
+
+    def get_num_free_cpu_blocks(self):
+        return 10
+
+
+def finished_output(request_id):
+    return SimpleNamespace(
+        request_id=request_id,
+        finished=True,
+        prompt_token_ids=[1, 2, 3],
+        outputs=[SimpleNamespace(token_ids=[4, 5], finish_reason="stop")],
+        metrics=SimpleNamespace(
+            arrival_time=100.0,
+            first_token_time=100.25,
+            finished_time=101.0,
+            time_in_queue=0.125,
+        ),
+    )
+
+
+class FakeEngine:
+    def __init__(self):
+        self.scheduler = [
+            SimpleNamespace(
+                block_manager=BlockManager(25),
+                running=[1, 2],
+                waiting=[3],
+                swapped=[],
+                num_cumulative_preemption=0,
+            )
+        ]
+        self.cache_config = SimpleNamespace(num_gpu_blocks=100, num_cpu_blocks=10)
+        self.outputs = []
+
+    def step(self):
+        return self.outputs
+
+    async def step_async(self, virtual_engine):
+        return self.outputs
+
+
+def test_vllm_engine_steps():
+    from probing.ext.inference import Sampler
+    from probing.ext.inference.vllm import EngineMonitor, instrument
+
+    now = [0.0]
+    recorder = Recorder()
+    monitor = EngineMonitor(Sampler(1.0, clock=lambda: now[0]), recorder)
+    instrument(FakeEngine, monitor)
+    instrument(FakeEngine, monitor)  # idempotent
+
+    engine = FakeEngine()
+    engine.outputs = [finished_output("a"), SimpleNamespace(finished=False)]
+    assert engine.step() is engine.outputs
+
+    (request,) = recorder.requests
+    assert request["request_id"] == "a"
+    assert (request["prompt_tokens"], request["output_tokens"]) == (3, 2)
+    assert (request["queue"], request["ttft"], request["e2e"]) == (0.125, 0.25, 1.0)
+    assert request["finish_reason"] == "stop"
+    assert request["time_ns"] == 101_000_000_000
+
+    (sample,) = recorder.kv_cache
+    assert sample["gpu_usage"] == 0.75
+    assert sample["cpu_usage"] == 0.0
+    assert (sample["running"], sample["waiting"], sample["preemptions"]) == (2, 1, 0)
+
+    # samples are rate limited, preemptions are reported as deltas
+    engine.outputs = []
+    engine.scheduler[0].num_cumulative_preemption = 3
+    asyncio.run(engine.step_async(0))
+    assert len(recorder.kv_cache) == 1
+    now[0] = 1.5
+    engine.step()
+    assert recorder.kv_cache[-1]["preemptions"] == 3
+
+
+def test_engine_without_scheduler():
+    from probing.ext.inference.vllm import EngineMonitor
+
+    recorder = Recorder()
+    monitor = EngineMonitor(recorder=recorder)
+    monitor.after_step(SimpleNamespace(), [finished_output("b")])
+    assert recorder.kv_cache == []
+    assert recorder.requests[0]["engine"] == "0"
@@ -0,0 +1,62 @@
+"""Tests for loading torch.profiler traces."""
+
+import gzip
+import json
+from types import SimpleNamespace
+
+TRACE = {
+    "traceEvents": [
+        {"ph": "X", "cat": "kernel", "name": "gemm", "ts": 40, "dur": 5},
+    ]
+}
+
+
+def fake_core(monkeypatch):
+    import probing
+
+    loaded = {}
+
+    def load(name, text):
+        loaded[name] = json.loads(text)
+        return len(loaded[name]["traceEvents"])
+
+    core = SimpleNamespace(_load_kineto_trace=load)
+    monkeypatch.setattr(probing, "_core", core, raising=False)
+    return loaded
+
+
+def test_load_paths_and_text(monkeypatch, tmp_path):
+    from probing.profiling import kineto
+
+    loaded = fake_core(monkeypatch)
+
+    path = tmp_path / "step_12.json"
+    path.write_text(json.dumps(TRACE))
+    assert kineto.load(path) == 1
+    assert kineto.load(str(path), "warmup") == 1
+
+    with gzip.open(tmp_path / "rank0.pt.trace.json.gz", "wt") as f:
+        json.dump(TRACE, f)
+    assert kineto.load(tmp_path / "rank0.pt.trace.json.gz") == 1
+
+    assert kineto.load(json.dumps(TRACE)) == 1
+    assert kineto.load(TRACE, "dict") == 1
+
+    assert sorted(loaded) == ["dict", "rank0.pt.trace", "step_12", "trace", "warmup"]
+    assert all(trace == TRACE for trace in loaded.values())
+
+
+def test_trace_handler_names_cycles(monkeypatch):
+    from probing.profiling import kineto
+
+    loaded = fake_core(monkeypatch)
+
+    class Profiler:
+        step_num = 3
+
+        def export_chrome_trace(self, path):
+            with open(path, "w") as f:
+                json.dump(TRACE, f)
+
+    kineto.trace_handler("train")(Profiler())
+    assert list(loaded) == ["train-3"]
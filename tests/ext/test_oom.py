@@ -0,0 +1,82 @@
+"""Tests for the CUDA out of memory forensics."""
+
+import json
+from types import SimpleNamespace
+
+
+class Tensor(SimpleNamespace):
+    is_cuda = True
+    device = "cuda:0"
+    dtype = "torch.float32"
+    requires_grad = False
+
+    def untyped_storage(self):
+        return SimpleNamespace(data_ptr=lambda: self.ptr, nbytes=lambda: self.nbytes)
+
+
+def fake_torch(objects=()):
+    cuda = SimpleNamespace(
+        memory_stats=lambda device: {
+            "allocated_bytes.all.current": 300,
+            "reserved_bytes.all.current": 512,
+        },
+        memory_snapshot=lambda: [
+            {
+                "device": 0,
+                "address": 1,
+                "total_size": 512,
+                "allocated_size": 300,
+                "active_size": 300,
+                "segment_type": "large",
+                "stream": 0,
+                "blocks": [
+                    {"size": 300, "state": "active_allocated"},
+                    {"size": 212, "state": "inactive"},
+                ],
+            },
+            {"device": 1, "address": 2, "total_size": 1024, "blocks": []},
+        ],
+    )
+    return SimpleNamespace(Tensor=Tensor, cuda=cuda)
+
+
+def test_largest_tensors_count_storages_once():
+    from probing.profiling.oom import largest_tensors
+
+    base = Tensor(shape=(8, 8), ptr=1, nbytes=256)
+    view = Tensor(shape=(8,), ptr=1, nbytes=256)
+    small = Tensor(shape=(4,), ptr=2, nbytes=16)
+    host = Tensor(shape=(100,), ptr=3, nbytes=400, is_cuda=False)
+    tensors = largest_tensors(
+        fake_torch(), limit=5, objects=[small, base, view, host, "other"]
+    )
+    assert [t["bytes"] for t in tensors] == [256, 16]
+    (largest,) = largest_tensors(fake_torch(), limit=1, objects=[small, base])
+    assert largest["shape"] == [8, 8]
+
+
+def test_observer_records_report():
+    from probing.profiling.oom import OomRecorder
+
+    rows = []
+    recorder = OomRecorder(fake_torch(), record=lambda *row: rows.append(row))
+    recorder.observer(0, 1 << 20, 1 << 30, 64)
+
+    assert len(rows) == 1
+    device, requested, allocated, reserved, free, message = rows[0][:6]
+    assert (device, requested, allocated, reserved, free) == (0, 1 << 20, 300, 512, 64)
+    assert message == ""
+    snapshot = json.loads(rows[0][9])
+    assert snapshot["segments"] == 1
+    assert snapshot["largest_free_block"] == 212
+    assert json.loads(rows[0][8])["reserved_bytes.all.current"] == 512
+
+
+def test_observer_never_raises():
+    from probing.profiling.oom import OomRecorder
+
+    def fail(*row):
+        raise RuntimeError("recording failed")
+
+    recorder = OomRecorder(fake_torch(), record=fail)
+    recorder.observer(0, 1, 1, 1)
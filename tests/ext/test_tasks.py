@@ -0,0 +1,150 @@
+"""Tests for the Ray and Dask task integrations."""
+
+import contextlib
+import pickle
+import sys
+from types import ModuleType, SimpleNamespace
+
+import pytest
+
+
+class FakeTracing:
+    def __init__(self):
+        self.context = {}
+        self.spans = []
+
+    def inject(self):
+        return dict(self.context)
+
+    @contextlib.contextmanager
+    def continue_trace(self, context, name, *, kind=None, **attrs):
+        span = SimpleNamespace(
+            name=name,
+            attrs=attrs,
+            trace_id=(context or {}).get("trace_id", 7),
+            span_id=len(self.spans) + 100,
+        )
+        self.spans.append(span)
+        yield span
+
+
+@pytest.fixture
+def tasks(monkeypatch):
+    from probing.ext import tasks
+
+    recorded = []
+    monkeypatch.setattr(tasks, "tracing", FakeTracing())
+    monkeypatch.setattr(
+        tasks, "record_task", lambda framework, **row: recorded.append((framework, row))
+    )
+    tasks.recorded = recorded
+    return tasks
+
+
+def double(x, *, scale=2):
+    return x * scale
+
+
+def fail():
+    raise RuntimeError("boom")
+
+
+def test_task_function_records_runs(tasks, monkeypatch):
+    monkeypatch.setitem(
+        tasks.IDS,
+        "ray",
+        lambda: dict(task_id="t1", worker_id="w1", node_id="n1", job_id=None),
+    )
+    func = tasks.TaskFunction(double, "ray")
+    assert func.__name__ == "double"
+    assert tasks.CONTEXT_KWARG in str(func.__signature__)
+
+    context = {"trace_id": 42, "span_id": 5}
+    assert func(3, **{tasks.CONTEXT_KWARG: context}) == 6
+
+    (span,) = tasks.tracing.spans
+    assert span.name == "ray.task"
+    assert span.trace_id == 42
+    assert span.attrs["ray.task_id"] == "t1"
+    assert span.attrs["ray.worker_id"] == "w1"
+    assert "ray.job_id" not in span.attrs
+
+    (_, running), (framework, done) = tasks.recorded
+    assert framework == "ray"
+    assert running["state"] == "running"
+    assert done["state"] == "finished"
+    assert done["task_id"] == running["task_id"] == "t1"
+    assert done["node_id"] == "n1"
+    assert done["parent_span_id"] == 5
+    assert done["trace_id"] == 42
+    assert done["duration"] >= 0
+
+
+def test_task_function_records_failures(tasks, monkeypatch):
+    monkeypatch.setitem(tasks.IDS, "dask", lambda: dict(task_id="k", worker_id="w"))
+    func = tasks.TaskFunction(fail, "dask", {"trace_id": 1, "span_id": 2})
+    with pytest.raises(RuntimeError):
+        func()
+    _, failed = tasks.recorded[-1]
+    assert failed["state"] == "failed"
+    assert failed["error"] == "RuntimeError: boom"
+    assert failed["parent_span_id"] == 2
+
+
+def test_task_function_pickles(tasks):
+    func = tasks.TaskFunction(double, "dask", {"trace_id": 1, "span_id": 2})
+    copy = pickle.loads(pickle.dumps(func))
+    assert copy.context == func.context
+    assert copy(4, scale=3) == 12
+    # the context does not change the token of the task
+    other = tasks.TaskFunction(double, "dask", {"trace_id": 9, "span_id": 9})
+    assert func.__dask_tokenize__() == other.__dask_tokenize__()
+
+
+def test_instrument_ray(tasks, monkeypatch):
+    class RemoteFunction:
+        def __init__(self, language, function, *args, **kwargs):
+            self._function = function
+
+        def _remote(self, args=None, kwargs=None, **options):
+            return self._function(*(args or ()), **(kwargs or {}))
+
+    ray = ModuleType("ray")
+    remote_function = ModuleType("ray.remote_function")
+    remote_function.RemoteFunction = RemoteFunction
+    ray.remote_function = remote_function
+    monkeypatch.setitem(sys.modules, "ray", ray)
+    monkeypatch.setitem(sys.modules, "ray.remote_function", remote_function)
+    monkeypatch.setitem(tasks.IDS, "ray", lambda: dict(task_id="t", worker_id="w"))
+
+    assert tasks.instrument_ray()
+    assert tasks.instrument_ray()  # idempotent
+
+    remote = RemoteFunction("python", double)
+    assert isinstance(remote._function, tasks.TaskFunction)
+    tasks.tracing.context = {"trace_id": 3, "span_id": 4}
+    assert remote._remote((5,)) == 10
+    assert tasks.recorded[-1][1]["parent_span_id"] == 4
+
+
+def test_instrument_dask(tasks, monkeypatch):
+    class Client:
+        def submit(self, func, *args, **kwargs):
+            return func(*args, **kwargs)
+
+        def map(self, func, *iterables, **kwargs):
+            return [func(*args, **kwargs) for args in zip(*iterables)]
+
+    distributed = ModuleType("distributed")
+    distributed.Client = Client
+    monkeypatch.setitem(sys.modules, "distributed", distributed)
+    monkeypatch.setitem(tasks.IDS, "dask", lambda: dict(task_id="k", worker_id="w"))
+
+    assert tasks.instrument_dask()
+    tasks.tracing.context = {"trace_id": 3, "span_id": 4}
+    client = Client()
+    assert client.submit(double, 2) == 4
+    assert client.map(double, [1, 2]) == [2, 4]
+    finished = [row for _, row in tasks.recorded if row["state"] == "finished"]
+    assert len(finished) == 3
+    assert {row["parent_span_id"] for row in finished} == {4}
@@ -154,3 +154,119 @@ def test_add_event_no_active_span():
 
     with pytest.raises(RuntimeError, match="No active span"):
         probing.event("should_fail")
+
+
+def test_propagate_to_thread():
+    import threading
+
+    from probing.tracing import capture_context, current_span, propagate
+
+    seen = {}
+
+    def task():
+        seen["parent"] = current_span()
+        with probing.span("task") as s:
+            seen["task"] = s
+
+    with probing.span("submit") as parent:
+        thread = threading.Thread(target=propagate(task))
+        context = capture_context()
+    thread.start()
+    thread.join()
+
+    assert seen["parent"].span_id == parent.span_id
+    assert seen["task"].parent_id == parent.span_id
+    assert seen["task"].trace_id == parent.trace_id
+    assert context and not capture_context()
+
+    # the spans of the running thread are restored afterwards
+    with probing.span("other") as other:
+        context.run(lambda: None)
+        assert current_span().span_id == other.span_id
+
+
+def test_propagation_through_executor():
+    from concurrent.futures import ThreadPoolExecutor
+
+    from probing.tracing import disable_propagation, enable_propagation
+
+    def task():
+        with probing.span("task") as s:
+            return s.parent_id
+
+    enable_propagation()
+    try:
+        with ThreadPoolExecutor(max_workers=2) as pool:
+            with probing.span("step") as step:
+                parents = list(pool.map(lambda _: task(), range(4)))
+            assert pool.submit(task).result() is None
+    finally:
+        disable_propagation()
+
+    assert parents == [step.span_id] * 4
+
+    with ThreadPoolExecutor(max_workers=1) as pool:
+        with probing.span("step"):
+            assert pool.submit(task).result() is None
+
+
+def test_cpu_time():
+    from probing.tracing import cpu_time_enabled, enable_cpu_time
+
+    enable_cpu_time()
+    try:
+        assert cpu_time_enabled()
+        with probing.span("busy") as busy:
+            sum(i * i for i in range(200_000))
+        with probing.span("idle") as idle:
+            time.sleep(0.05)
+    finally:
+        enable_cpu_time(False)
+
+    assert busy.cpu_time_ns > 0
+    # a sleeping span is long but uses little CPU
+    assert idle.cpu_time_ns < idle.duration * 1e9 / 2
+
+    with probing.span("off") as off:
+        pass
+    assert off.cpu_time_ns is None
+
+
+def test_attribute_serializers():
+    import json
+    from dataclasses import dataclass
+
+    from probing.tracing import register_serializer, unregister_serializer
+
+    @dataclass
+    class Batch:
+        size: int
+        source: str
+
+    class Shard:
+        def __init__(self, index):
+            self.index = index
+
+    register_serializer(Shard, lambda shard: {"shard": shard.index})
+    try:
+        with probing.span("serialized", batch=Batch(32, "train"), shard=Shard(3)) as s:
+            probing.event("loaded", attributes=[{"shard": Shard(4)}])
+            attrs = s.get_attributes()
+            events = s.get_events()
+    finally:
+        unregister_serializer(Shard)
+
+    assert json.loads(attrs["batch"]) == {"type": "Batch", "size": 32, "source": "train"}
+    assert json.loads(attrs["shard"]) == {"shard": 3}
+    assert json.loads(events[0]["attributes"]["shard"]) == {"shard": 4}
+
+
+def test_ndarray_attribute():
+    np = pytest.importorskip("numpy")
+    from probing.tracing import serialize_attribute
+
+    out = serialize_attribute(np.zeros((2, 3), dtype=np.float32))
+    assert out["type"] == "ndarray"
+    assert out["shape"] == [2, 3]
+    assert out["dtype"] == "float32"
+    assert out["hash"] == serialize_attribute(np.zeros((2, 3), dtype=np.float32))["hash"]
@@ -0,0 +1,151 @@
+"""Tests for the Hugging Face and Lightning trainer integrations."""
+
+import sys
+from types import ModuleType, SimpleNamespace
+
+import pytest
+
+
+class Table:
+    def __init__(self):
+        self.rows = []
+
+    def append_dict(self, row):
+        self.rows.append(row)
+
+
+class FakeSpan:
+    def __init__(self, spans, name, attrs):
+        self.spans, self.name, self.attrs = spans, name, attrs
+        self.open = False
+
+    def __enter__(self):
+        self.open = True
+        self.spans.append(self)
+        return self
+
+    def __exit__(self, *args):
+        self.open = False
+        return False
+
+
+@pytest.fixture
+def trainer(monkeypatch):
+    from probing.ext import trainer
+
+    spans = []
+    monkeypatch.setattr(
+        trainer,
+        "tracing",
+        SimpleNamespace(span=lambda name, **attrs: FakeSpan(spans, name, attrs)),
+    )
+    monkeypatch.setenv("PROBING_INTEGRATIONS_HF", "on")
+    trainer.spans = spans
+    return trainer
+
+
+def test_step_recorder(trainer):
+    table = Table()
+    recorder = trainer.StepRecorder("transformers", table)
+    recorder.train_begin(max_steps=2, unset=None)
+    recorder.step_begin(1, 0.0)
+    recorder.step_end(1, 0.5)
+    recorder.log(1, {"loss": 2.5, "learning_rate": 1e-4, "note": "text"})
+    recorder.step_begin(2, 0.5)
+    recorder.step_end(2, 1.0, {"train/loss": 2.0})
+    recorder.log(2, {"eval_loss": 2.2})
+    recorder.train_end()
+
+    train, step1, step2 = trainer.spans
+    assert train.name == "trainer.train"
+    assert train.attrs == {"framework": "transformers", "max_steps": 2}
+    assert (step1.name, step1.attrs["step"], step2.attrs["epoch"]) == (
+        "trainer.step",
+        1,
+        0.5,
+    )
+    assert not any(span.open for span in trainer.spans)
+
+    first, second = table.rows
+    assert first["step"] == 1 and first["loss"] == 2.5
+    assert first["learning_rate"] == 1e-4
+    assert "note" not in first
+    assert first["duration"] >= 0
+    assert second["train_loss"] == 2.0 and second["eval_loss"] == 2.2
+    assert second["epoch"] == 1.0
+
+
+def test_transformers_trainer(trainer, monkeypatch):
+    class TrainerCallback:
+        pass
+
+    transformers = ModuleType("transformers")
+    transformers.TrainerCallback = TrainerCallback
+    monkeypatch.setitem(sys.modules, "transformers", transformers)
+
+    class Trainer:
+        def __init__(self, callbacks=None):
+            self.callback_handler = SimpleNamespace(callbacks=list(callbacks or []))
+
+        def add_callback(self, callback):
+            self.callback_handler.callbacks.append(callback)
+
+    trainer.instrument_transformers(Trainer)
+    trainer.instrument_transformers(Trainer)  # idempotent
+
+    (callback,) = Trainer().callback_handler.callbacks
+    assert isinstance(callback, TrainerCallback)
+    # a callback passed explicitly is not added twice
+    assert len(Trainer([callback]).callback_handler.callbacks) == 1
+
+    monkeypatch.setenv("PROBING_INTEGRATIONS_HF", "off")
+    assert Trainer().callback_handler.callbacks == []
+
+    table = Table()
+    callback.recorder.table = table
+    state = SimpleNamespace(max_steps=1, global_step=0, epoch=0.0)
+    callback.on_train_begin(None, state, None)
+    callback.on_step_begin(None, state, None)
+    state.global_step, state.epoch = 1, 1.0
+    callback.on_step_end(None, state, None)
+    callback.on_log(None, state, None, logs={"loss": 0.5})
+    callback.on_train_end(None, state, None)
+    assert table.rows == [
+        dict(
+            framework="transformers",
+            step=1,
+            epoch=1.0,
+            duration=table.rows[0]["duration"],
+            loss=0.5,
+        )
+    ]
+
+
+def test_lightning_trainer(trainer):
+    class Callback:
+        pass
+
+    class Trainer:
+        def __init__(self, max_epochs=1, callbacks=None):
+            self.callbacks = callbacks
+            self.global_step = 0
+            self.current_epoch = 0
+            self.callback_metrics = {}
+
+    trainer.instrument_lightning(Trainer, Callback)
+    pl = Trainer(callbacks=Callback())
+    user, callback = pl.callbacks
+    assert isinstance(callback, Callback)
+
+    table = Table()
+    callback.recorder.table = table
+    callback.on_train_start(pl, None)
+    callback.on_train_batch_start(pl, None, None, 0)
+    pl.global_step = 1
+    pl.callback_metrics = {"acc": 0.75}
+    callback.on_train_batch_end(pl, None, {"loss": 1.5}, None, 0)
+    callback.on_train_end(pl, None)
+
+    (row,) = table.rows
+    assert row["framework"] == "lightning"
+    assert (row["step"], row["loss"], row["acc"]) == (1, 1.5, 0.75)
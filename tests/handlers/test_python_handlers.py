@@ -196,6 +196,59 @@ class TestUnifiedEntryPoint:
             pass
 
 
+class TestSpanTree:
+    """Test the span trees served by trace/span-tree."""
+
+    @staticmethod
+    def rows(*spans):
+        rows = []
+        for span_id, parent_id, thread_id, start, end in spans:
+            span = {"span_id": span_id, "trace_id": 1, "thread_id": thread_id}
+            rows.append(
+                dict(span, record_type="span_start", parent_id=parent_id, time=start)
+            )
+            if end is not None:
+                rows.append(dict(span, record_type="span_end", time=end))
+        return rows
+
+    def test_self_time(self):
+        from probing.handlers.pythonext import build_span_tree
+
+        (root,) = build_span_tree(
+            self.rows(
+                (1, -1, 7, 0, 100),
+                # overlapping children of the same thread count once
+                (2, 1, 7, 10, 40),
+                (3, 1, 7, 30, 50),
+                # a child on another thread ran concurrently
+                (4, 1, 8, 0, 90),
+                (5, 2, 7, 15, 25),
+            )
+        )
+        assert root["duration"] == 100
+        assert root["self_time"] == 60
+        worker, first, second = root["children"]
+        assert [worker["span_id"], first["span_id"], second["span_id"]] == [4, 2, 3]
+        assert first["self_time"] == 20
+        assert worker["self_time"] == 90
+        assert first["children"][0]["self_time"] == 10
+
+    def test_running_spans_and_orphans(self):
+        from probing.handlers.pythonext import build_span_tree
+
+        roots = build_span_tree(
+            self.rows((1, -1, 7, 0, None), (2, 1, 7, 10, None), (3, 9, 7, 20, 30))
+        )
+        assert [root["span_id"] for root in roots] == [1, 3]
+        running, orphan = roots
+        assert running["duration"] is None and running["self_time"] is None
+        assert orphan["parent_id"] == 9 and orphan["self_time"] == 10
+
+        (parent,) = build_span_tree(self.rows((1, -1, 7, 0, 50), (2, 1, 7, 40, None)))
+        # a child still running covers the rest of its parent
+        assert parent["self_time"] == 40
+
+
 class TestHandlerRegistration:
     """Test that all handlers are properly registered."""
 
@@ -218,6 +271,7 @@ class TestHandlerRegistration:
             "ray/timeline",
             "ray/timeline/chrome",
             "trace/chrome-tracing",
+            "trace/span-tree",
             "pytorch/timeline",
             "pytorch/profile",
             "trace/list",
@@ -225,6 +279,10 @@ class TestHandlerRegistration:
             "trace/start",
             "trace/stop",
             "trace/variables",
+            "python/tensor",
+            "locks/track",
+            "locks/untrack",
+            "locks/status",
         ]
 
         # Debug: show current handlers if test fails
@@ -0,0 +1,45 @@
+"""Tests for gc pause tracking."""
+
+import gc
+
+
+class Cycle:
+    def __init__(self):
+        self.me = self
+
+
+def test_records_collections():
+    from probing.inspect.gctrace import GcCollection, track_gc, untrack_gc
+
+    before = len(GcCollection.take(100000))
+    track_gc(threshold=60)
+    try:
+        for _ in range(10):
+            Cycle()
+        gc.collect()
+    finally:
+        assert untrack_gc()
+
+    rows = GcCollection.take(100000)[before:]
+    assert rows
+    # generation, duration, collected, uncollectable, thread_id, time
+    generation, duration, collected = rows[-1][1][:3]
+    assert generation == 2
+    assert duration >= 0
+    assert collected >= 10
+    assert not untrack_gc()
+
+
+def test_long_pauses_become_spans():
+    from probing.inspect.gctrace import _GcTracker
+
+    tracker = _GcTracker()
+    tracker.threshold = 0.0
+    tracker("start", {"generation": 1})
+    tracker("stop", {"generation": 1, "collected": 3, "uncollectable": 0})
+    assert tracker.collections == 1
+    assert tracker.pauses == 1
+
+    # a stop without a start, e.g. when installed mid-collection, is ignored
+    tracker("stop", {"generation": 0, "collected": 0, "uncollectable": 0})
+    assert tracker.collections == 1
@@ -0,0 +1,73 @@
+"""Tests for lock contention tracking."""
+
+import threading
+import time
+
+
+def contend(lock, hold=0.05):
+    holding = threading.Event()
+
+    def holder():
+        with lock:
+            holding.set()
+            time.sleep(hold)
+
+    thread = threading.Thread(target=holder)
+    thread.start()
+    holding.wait()
+    with lock:
+        pass
+    thread.join()
+    return thread.ident
+
+
+def test_records_contended_waits():
+    from probing.inspect.locks import (
+        LockContention,
+        lock_status,
+        track_locks,
+        untrack_locks,
+    )
+
+    before = len(LockContention.take(100000))
+    track_locks(threshold="1ms")
+    try:
+        lock = threading.Lock()
+        rlock = threading.RLock()
+        owner = contend(lock)
+        contend(rlock)
+        # uncontended and reentrant acquisitions are not recorded
+        with rlock, rlock:
+            pass
+        # conditions work on tracked locks
+        condition = threading.Condition()
+        with condition:
+            assert not condition.wait(0.01)
+    finally:
+        assert untrack_locks()
+    assert not untrack_locks()
+    assert not isinstance(threading.Lock(), type(lock))
+
+    rows = [values for _, values in LockContention.take(100000)[before:]]
+    # lock, kind, wait, acquired, owner, thread_id, stack_id, time
+    assert [row[1] for row in rows] == ["Lock", "RLock"]
+    site, _, wait, acquired, held_by = rows[0][:5]
+    assert site.endswith("test_locks.py:35")
+    assert wait >= 0.01 and acquired
+    assert held_by == owner
+
+    status = lock_status()
+    assert status["enabled"] is False
+    assert status["top"][0]["contended"] >= 1
+
+
+def test_short_waits_are_only_counted():
+    from probing.inspect.locks import LockContention, track_locks, untrack_locks
+
+    before = len(LockContention.take(100000))
+    track_locks(threshold=60)
+    try:
+        contend(threading.Lock(), hold=0.01)
+    finally:
+        untrack_locks()
+    assert len(LockContention.take(100000)) == before
@@ -0,0 +1,99 @@
+"""Tests for dumping live objects."""
+
+import json
+
+import pytest
+
+
+class Payload:
+    def __init__(self, shape):
+        self.shape = shape
+        self.dtype = "float32"
+
+
+class Holder:
+    def __init__(self, payload):
+        self.payload = payload
+
+
+def test_collect_objects_by_type_name():
+    from probing.inspect.objects import collect_objects
+
+    payloads = [Payload((2, 3)), Payload((4,))]
+    records = collect_objects("Payload", limit=10)
+    assert len(records) == len(payloads)
+    assert {tuple(r["shape"]) for r in records} == {(2, 3), (4,)}
+    assert all(r["dtype"] == "float32" for r in records)
+    assert all(r["type"].endswith("Payload") for r in records)
+
+    assert len(collect_objects(f"{__name__}.Payload", limit=1)) == 1
+
+
+def test_referrer_chain():
+    from probing.inspect.objects import collect_objects
+
+    holder = Holder(Payload((1,)))
+    records = collect_objects("Payload", limit=1, referrers=1)
+    # the instance dict shows up as a separate referrer unless it is inlined
+    chain = " <- ".join(records[0]["referrers"])
+    assert "payload" in chain
+    assert f"{__name__}.Holder" in chain
+    del holder
+
+
+def test_dump_objects_to_json(tmp_path):
+    from probing.inspect.objects import dump_objects
+
+    payload = Payload((8, 8))
+    result = dump_objects("Payload", directory=str(tmp_path))
+    assert result["count"] == 1
+
+    with open(result["path"]) as f:
+        records = json.load(f)
+    assert records[0]["id"] == id(payload)
+    assert records[0]["shape"] == [8, 8]
+
+
+def test_dump_dir_from_environment(tmp_path, monkeypatch):
+    from probing.inspect.objects import dump_objects
+
+    monkeypatch.setenv("PROBING_DUMP_DIR", str(tmp_path / "dumps"))
+    payload = Payload((3,))
+    result = dump_objects("Payload")
+    assert result["path"].startswith(str(tmp_path / "dumps"))
+
+    with open(result["path"]) as f:
+        records = json.load(f)
+    assert [r["id"] for r in records] == [id(payload)]
+
+
+def test_dump_objects_to_parquet(tmp_path):
+    pq = pytest.importorskip("pyarrow.parquet")
+    from probing.inspect.objects import dump_objects
+
+    payload = Payload((5, 5))
+    result = dump_objects("Payload", format="parquet", directory=str(tmp_path))
+    rows = pq.read_table(result["path"]).to_pylist()
+    assert [r["id"] for r in rows] == [id(payload)]
+    assert rows[0]["shape"] == [5, 5]
+
+
+def test_dump_dir_outside_allowed_dirs_is_refused(tmp_path):
+    from probing.inspect.objects import dump_objects
+
+    allowed = tmp_path / "data"
+    allowed.mkdir()
+    payload = Payload((2,))
+    for directory in (tmp_path / "elsewhere", allowed / ".." / "escaped"):
+        with pytest.raises(PermissionError):
+            dump_objects(
+                "Payload", directory=str(directory), allowed_dirs=[str(allowed)]
+            )
+        assert not directory.exists()
+
+    result = dump_objects(
+        "Payload", directory=str(allowed / "dumps"), allowed_dirs=[str(allowed)]
+    )
+    assert result["path"].startswith(str(allowed / "dumps"))
+    assert result["count"] == 1
+    del payload
@@ -0,0 +1,44 @@
+"""Tests for the snapshot written on SIGTERM and SIGINT."""
+
+import os
+import threading
+
+from probing.inspect import shutdown
+
+
+def test_capture_writes_snapshot(tmp_path):
+    started, done = threading.Event(), threading.Event()
+
+    def worker():
+        started.set()
+        done.wait()
+
+    thread = threading.Thread(target=worker, name="shutdown-worker")
+    thread.start()
+    started.wait()
+    try:
+        path = str(tmp_path / "rank0" / "shutdown-1.json")
+        assert shutdown.capture(path, "SIGTERM", 7) == path
+    finally:
+        done.set()
+        thread.join()
+
+    snapshot = shutdown.load(path)
+    assert snapshot["signal"] == "SIGTERM"
+    assert snapshot["step"] == 7
+    assert snapshot["pid"] == os.getpid()
+    assert isinstance(snapshot["spans"], list)
+    worker_stack = next(
+        s for s in snapshot["stacks"] if s["thread_name"] == "shutdown-worker"
+    )
+    assert any(f["func"] == "worker" for f in worker_stack["frames"])
+    # written to a temporary file and renamed into place
+    assert os.listdir(tmp_path / "rank0") == ["shutdown-1.json"]
+
+
+def test_step_defaults_to_counted_step(monkeypatch):
+    from probing.profiling import oom
+
+    monkeypatch.setattr(oom, "_current_step", lambda: 41)
+    assert shutdown.snapshot("SIGINT")["step"] == 41
+    assert shutdown.snapshot("SIGINT", step=3)["step"] == 3
@@ -0,0 +1,64 @@
+"""Tests for conditional snapshots."""
+
+import json
+import math
+import threading
+
+import pytest
+
+
+def make_rule(namespace, **kwargs):
+    from probing.inspect.snapshot import SnapshotRule
+
+    return SnapshotRule("nan-loss", "math.isnan(loss)", namespace=namespace, **kwargs)
+
+
+def test_fires_on_rising_edge():
+    namespace = {"math": math, "loss": 1.0}
+    rule = make_rule(namespace)
+
+    assert rule.sample() is None
+    namespace["loss"] = math.nan
+    assert rule.sample() is not None
+    # stays true, no second snapshot
+    assert rule.sample() is None
+
+    namespace["loss"] = 0.5
+    assert rule.sample() is None
+    namespace["loss"] = math.nan
+    assert rule.sample() is not None
+    assert rule.fired == 2
+
+
+def test_errors_count_as_false():
+    rule = make_rule({"math": math})
+    assert not rule.check()
+
+
+def test_capture_contents():
+    from probing.inspect.snapshot import get_snapshots
+
+    namespace = {"math": math, "loss": math.nan, "step": 42}
+    rule = make_rule(namespace, variables=["step", "missing"])
+    # captured from another thread like the sampler, which skips itself
+    capture = threading.Thread(target=rule.capture)
+    capture.start()
+    capture.join()
+
+    snapshot = get_snapshots("nan-loss", limit=1)[0]
+    assert snapshot["condition"] == "math.isnan(loss)"
+    assert snapshot["variables"]["step"] == "42"
+    assert snapshot["variables"]["missing"].startswith("<error: NameError")
+
+    # stacks are stored by id and expanded when read
+    assert all("stack_id" in s for s in snapshot["stacks"])
+    funcs = [f["func"] for s in snapshot["stacks"] for f in s["frames"]]
+    assert "test_capture_contents" in funcs
+    json.dumps(snapshot)
+
+
+def test_invalid_kind():
+    from probing.inspect.snapshot import SnapshotRule
+
+    with pytest.raises(ValueError):
+        SnapshotRule("bad", "1", kind="lua")
@@ -0,0 +1,34 @@
+"""Tests for the stack dictionary."""
+
+from probing.inspect.stacks import (
+    fingerprint,
+    format_frame,
+    intern_stack,
+    parse_frame,
+    resolve_stack,
+)
+
+
+def test_fingerprint_matches_the_probe():
+    # same value as probing_core::stacks::fingerprint
+    assert fingerprint(["main (a.py:1)"]) == -456493945050715160
+    assert fingerprint(["ab", "c"]) != fingerprint(["a", "bc"])
+
+
+def test_intern_and_resolve():
+    frames = [format_frame("a.py", "main", 1), format_frame("b.py", "step", 20)]
+    stack_id = intern_stack(frames)
+    assert intern_stack(list(frames)) == stack_id
+    assert intern_stack(frames[:1]) != stack_id
+    assert resolve_stack(stack_id) == frames
+    assert resolve_stack(stack_id + 1) is None
+
+
+def test_parse_frame():
+    frame = format_frame("C:/src/train.py", "<lambda>", 7)
+    assert parse_frame(frame) == {
+        "file": "C:/src/train.py",
+        "func": "<lambda>",
+        "lineno": 7,
+    }
+    assert parse_frame("??")["func"] == "??"
@@ -0,0 +1,71 @@
+"""Tests for live tensor inspection."""
+
+import json
+import math
+
+import pytest
+
+from probing.inspect.tensor import resolve
+
+
+class Linear:
+    def __init__(self):
+        self.weight = [0.5, -0.5]
+
+
+class Model:
+    def __init__(self):
+        self.layers = [Linear(), Linear()]
+        self.heads = {"lm": Linear()}
+
+
+def test_resolve_paths():
+    namespace = {"model": Model()}
+    model = namespace["model"]
+    assert resolve("model.layers.1.weight", namespace) is model.layers[1].weight
+    assert resolve("model.layers[-1].weight[0]", namespace) == 0.5
+    assert resolve('model.heads["lm"].weight', namespace) is model.heads["lm"].weight
+    # modules are found among the imported ones
+    assert resolve("json.dumps", {}) is json.dumps
+
+
+def test_resolve_rejects_code():
+    namespace = {"model": Model(), "print": print}
+    for expr in (
+        "print('x')",
+        "model.layers[0 + 1]",
+        "model.__dict__",
+        "model.layers[::2]",
+        "[model]",
+        "model.layers.",
+    ):
+        with pytest.raises(ValueError):
+            resolve(expr, namespace)
+    with pytest.raises(NameError):
+        resolve("missing.weight", namespace)
+
+
+def test_tensor_stats():
+    torch = pytest.importorskip("torch")
+    from probing.inspect.tensor import inspect_tensor
+
+    weight = torch.tensor([[1.0, float("nan")], [3.0, float("inf")], [0.0, 5.0]])
+    stats = inspect_tensor("w", bins=4, namespace={"w": weight})
+    assert stats["shape"] == [3, 2]
+    assert stats["dtype"] == "torch.float32"
+    assert stats["nan_count"] == 1
+    assert stats["inf_count"] == 1
+    assert (stats["min"], stats["max"]) == (0.0, 5.0)
+    assert math.isclose(stats["mean"], 9.0 / 4)
+    assert stats["zero_fraction"] == 0.25
+    assert sum(stats["histogram"]["counts"]) == 4
+    assert not stats["sampled"]
+
+    big = torch.arange(1000, dtype=torch.int64)
+    stats = inspect_tensor("big", sample=100, namespace={"big": big})
+    assert stats["sampled"]
+    assert stats["min"] == 0.0 and stats["max"] < 1000
+    assert "histogram" not in stats
+
+    with pytest.raises(TypeError):
+        inspect_tensor("x", namespace={"x": [1.0]})
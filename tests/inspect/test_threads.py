@@ -0,0 +1,66 @@
+"""Tests for the classification of thread states."""
+
+import threading
+import time
+
+from probing.inspect.threads import thread_states
+
+
+def states_of(threads):
+    rows = {row["name"]: row for row in thread_states()}
+    return {t.name: rows[t.name] for t in threads}
+
+
+def test_thread_states():
+    lock = threading.RLock()
+    plain = threading.Lock()
+    release = threading.Event()
+    plain.acquire()
+
+    def holder():
+        with lock:
+            release.wait()
+
+    def waiter():
+        with lock:
+            pass
+
+    def acquirer():
+        if plain.acquire():
+            plain.release()
+
+    def sleeper():
+        time.sleep(0.5)
+
+    first = threading.Thread(target=holder, name="holder", daemon=True)
+    first.start()
+    time.sleep(0.05)
+    others = [
+        threading.Thread(target=f, name=f.__name__, daemon=True)
+        for f in (waiter, acquirer, sleeper)
+    ]
+    for thread in others:
+        thread.start()
+    time.sleep(0.1)
+
+    try:
+        states = states_of([first, *others])
+    finally:
+        plain.release()
+        release.set()
+
+    assert states["holder"]["state"] == "waiting_lock"
+    assert states["holder"]["detail"] == "Event.wait"
+    assert states["waiter"]["state"] == "waiting_lock"
+    # an RLock records its owner
+    assert states["waiter"]["lock_owner"] == first.ident
+    assert states["acquirer"]["detail"] == "plain.acquire"
+    assert states["acquirer"]["lock_owner"] is None
+    assert states["sleeper"]["state"] == "sleeping"
+    assert all(row["stack_id"] is not None for row in states.values())
+
+
+def test_current_thread_is_running():
+    (row,) = [r for r in thread_states() if r["thread_id"] == threading.get_ident()]
+    assert row["state"] == "running"
+    assert row["func"] == "thread_states"
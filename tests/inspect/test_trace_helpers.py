@@ -299,3 +299,50 @@ class TestFilterByPrefix:
 
 if __name__ == "__main__":
     pytest.main([__file__, "-v"])
+
+
+class TestModuleFilter:
+    """Test the module filter of ProbingTracer (tracing.include/exclude)."""
+
+    @staticmethod
+    def configure(include="", exclude=""):
+        import probing
+
+        probing.query(f"set probing.tracing.include=`{include}`")
+        probing.query(f"set probing.tracing.exclude=`{exclude}`")
+
+    def teardown_method(self):
+        self.configure()
+
+    def test_no_filter_allows_all(self):
+        from probing.inspect.trace import module_allowed
+
+        self.configure()
+        assert module_allowed("torch.nn.modules.linear")
+        assert module_allowed("__main__")
+
+    def test_include_and_exclude(self):
+        from probing.inspect.trace import module_allowed
+
+        self.configure("my_project.*,__main__", "torch.*, numpy.*")
+        assert module_allowed("my_project")
+        assert module_allowed("my_project.models.bert")
+        assert module_allowed("__main__")
+        assert not module_allowed("my_project2")
+        assert not module_allowed("torch.nn")
+        assert not module_allowed("numpy")
+
+    def test_exclude_takes_precedence(self):
+        from probing.inspect.trace import module_allowed
+
+        self.configure("my_project.*", "my_project.vendored.*")
+        assert module_allowed("my_project.train")
+        assert not module_allowed("my_project.vendored.six")
+
+    def test_reconfigure(self):
+        from probing.inspect.trace import module_allowed
+
+        self.configure(exclude="torch.*")
+        assert not module_allowed("torch.nn")
+        self.configure()
+        assert module_allowed("torch.nn")
@@ -0,0 +1,101 @@
+"""Tests for watch expressions."""
+
+import math
+from types import SimpleNamespace
+
+import pytest
+
+
+class TestWatchUpdate:
+    """Test change detection of Watch.update."""
+
+    def make_watch(self, threshold=0.0):
+        from probing.inspect.watch import Watch
+
+        return Watch("x", interval=1.0, threshold=threshold, namespace={})
+
+    def test_first_sample_is_not_a_change(self):
+        watch = self.make_watch()
+        assert not watch.update("1.0", 1.0)
+        assert watch.value == "1.0"
+
+    def test_numeric_threshold(self):
+        watch = self.make_watch(threshold=0.5)
+        watch.update("1.0", 1.0)
+        assert not watch.update("1.3", 1.3)
+        # small steps add up against the last reported value
+        assert watch.update("1.6", 1.6)
+        assert watch.previous == "1.0"
+
+    def test_nan_is_a_change(self):
+        watch = self.make_watch(threshold=10.0)
+        watch.update("1.0", 1.0)
+        assert watch.update("nan", math.nan)
+        assert not watch.update("nan", math.nan)
+
+    def test_text_change(self):
+        watch = self.make_watch()
+        watch.update("'warmup'", None)
+        assert not watch.update("'warmup'", None)
+        assert watch.update("'decay'", None)
+
+
+def test_evaluate_in_namespace():
+    from probing.inspect.watch import Watch
+
+    namespace = {"lr": [0.1, 0.2]}
+    watch = Watch("lr[0] * 10", interval=1.0, namespace=namespace)
+    assert watch.evaluate() == ("1.0", 1.0)
+
+    text, numeric = Watch("missing", interval=1.0, namespace={}).evaluate()
+    assert text.startswith("<error: NameError")
+    assert numeric is None
+
+
+def test_evaluate_redacts_values(monkeypatch):
+    import probing
+    from probing.inspect.watch import Watch
+
+    def redact(text):
+        return text.replace("alice@example.com", "[REDACTED]")
+
+    def redact_value(name, text):
+        return "[REDACTED]" if "email" in name else redact(text)
+
+    core = SimpleNamespace(_redact=redact, _redact_value=redact_value)
+    monkeypatch.setattr(probing, "_core", core, raising=False)
+
+    namespace = {"email": "alice@example.com", "batch": {"user": "alice@example.com"}}
+    assert Watch("email", interval=1.0, namespace=namespace).evaluate() == (
+        "[REDACTED]",
+        None,
+    )
+    text, _ = Watch("batch", interval=1.0, namespace=namespace).evaluate()
+    assert text == "{'user': '[REDACTED]'}"
+    text, _ = Watch("batch['alice@example.com']", 1.0, namespace=namespace).evaluate()
+    assert "alice" not in text
+
+
+def test_sample_due_records_samples():
+    from probing.inspect.watch import Watch, WatchSample, _Sampler
+
+    namespace = {"step": 1}
+    sampler = _Sampler()
+    sampler.watches["step"] = Watch("step", interval=5.0, namespace=namespace)
+
+    before = len(WatchSample.take(100000))
+    assert sampler.sample_due(now=0.0) == 5.0
+    namespace["step"] = 2
+    # not due yet
+    sampler.sample_due(now=1.0)
+    sampler.sample_due(now=5.0)
+    assert len(WatchSample.take(100000)) == before + 2
+
+
+def test_invalid_interval():
+    from probing.inspect.watch import parse_interval
+
+    with pytest.raises(ValueError):
+        parse_interval("soon")
+    with pytest.raises(ValueError):
+        parse_interval(0)
@@ -0,0 +1 @@
+# Tests for probing.kernel module
@@ -0,0 +1,72 @@
+"""Tests for the Jupyter kernel bridge."""
+
+import json
+import os
+import sys
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+# Add python/ to path explicitly
+sys.path.insert(0, os.path.join(os.path.dirname(__file__), "..", "..", "python"))
+
+import pytest
+
+from probing.kernel import is_complete
+from probing.kernel.client import TargetClient
+
+
+class FakeProbe(BaseHTTPRequestHandler):
+    requests = []
+
+    def do_GET(self):
+        self.reply({"name": "jupyter-1", "attached": 0})
+
+    def do_POST(self):
+        body = self.rfile.read(int(self.headers["Content-Length"])).decode()
+        self.reply({"status": "ok", "output": f"ran {body.strip()}", "traceback": []})
+
+    def reply(self, payload):
+        FakeProbe.requests.append((self.path, self.headers.get("Authorization")))
+        data = json.dumps(payload).encode()
+        self.send_response(200)
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def log_message(self, *args):
+        pass
+
+
+@pytest.fixture
+def probe(monkeypatch):
+    monkeypatch.setenv("PROBING_AUTH_TOKEN", "secret")
+    server = HTTPServer(("127.0.0.1", 0), FakeProbe)
+    thread = threading.Thread(target=server.serve_forever, daemon=True)
+    thread.start()
+    FakeProbe.requests.clear()
+    yield TargetClient(f"127.0.0.1:{server.server_port}")
+    server.shutdown()
+
+
+def test_eval_runs_in_session(probe):
+    result = probe.eval("x = 1\n", session="jupyter-1")
+    assert result["output"] == "ran x = 1"
+
+    probe.close_session("jupyter-1")
+    assert FakeProbe.requests == [
+        ("/apis/pythonext/eval?session=jupyter-1", "Bearer secret"),
+        ("/apis/pythonext/repl/sessions/close?name=jupyter-1", "Bearer secret"),
+    ]
+
+
+def test_target_parsing():
+    assert TargetClient("42").pid == 42
+    assert TargetClient("node1:9700").address == "node1:9700"
+    with pytest.raises(ValueError):
+        TargetClient("")
+
+
+def test_is_complete():
+    assert is_complete("def f():\n    return 1\n") == "complete"
+    assert is_complete("def f():") == "incomplete"
+    assert is_complete("1 +* 2") == "invalid"
@@ -0,0 +1,65 @@
+"""Tests for running the code of eval jobs."""
+
+import io
+import os
+import sys
+import threading
+
+# Add python/ to path explicitly
+sys.path.insert(0, os.path.join(os.path.dirname(__file__), "..", "..", "python"))
+
+import pytest
+
+from probing.repl import jobs
+
+
+class Console:
+    def __init__(self):
+        self.user_ns = {"model": "resnet"}
+
+
+def test_run_returns_trailing_expression():
+    console = Console()
+    out = io.StringIO()
+    assert jobs.run("x = 6\nx * 7", console, out) == "42"
+    assert console.user_ns["x"] == 6
+    assert jobs.run("model", console, out) == "'resnet'"
+    assert jobs.run("y = 1", console, out) is None
+    assert out.getvalue() == ""
+
+
+def test_run_captures_output_of_its_thread_only():
+    console = Console()
+    out = io.StringIO()
+    started, release = threading.Event(), threading.Event()
+
+    def job():
+        jobs.run(
+            "import sys\nprint('hello')\nstarted.set()\nrelease.wait()\n"
+            "print('oops', file=sys.stderr)",
+            console,
+            out,
+        )
+
+    console.user_ns.update(started=started, release=release)
+    thread = threading.Thread(target=job)
+    thread.start()
+    started.wait()
+    other = io.StringIO()
+    saved, sys.stdout.stream = sys.stdout.stream, other
+    try:
+        print("main thread")
+    finally:
+        sys.stdout.stream = saved
+    release.set()
+    thread.join()
+
+    assert out.getvalue() == "hello\noops\n"
+    assert other.getvalue() == "main thread\n"
+
+
+def test_run_raises_errors_of_the_code():
+    with pytest.raises(ZeroDivisionError):
+        jobs.run("1 / 0", Console(), io.StringIO())
+    # the thread is no longer routed once the job ends
+    assert threading.get_ident() not in jobs._writers
@@ -0,0 +1,59 @@
+"""Tests for named REPL sessions."""
+
+import json
+import os
+import sys
+from types import SimpleNamespace
+
+# Add python/ to path explicitly
+sys.path.insert(0, os.path.join(os.path.dirname(__file__), "..", "..", "python"))
+
+import pytest
+
+from probing.repl import ExecutionResult, SessionConsole
+
+
+class Module:
+    pass
+
+
+class FakeExecutor:
+    """Runs code in whatever namespace the shared shell points at."""
+
+    def __init__(self):
+        shell = SimpleNamespace(user_ns={"model": "resnet"})
+        shell.user_module = Module()
+        shell.user_module.__dict__ = shell.user_ns
+        self.km = SimpleNamespace(kernel=SimpleNamespace(shell=shell))
+
+    def execute(self, source):
+        shell = self.km.kernel.shell
+        try:
+            value = eval(source, shell.user_module.__dict__, shell.user_ns)
+            return ExecutionResult(status="ok", output=str(value))
+        except SyntaxError:
+            exec(source, shell.user_module.__dict__, shell.user_ns)
+            return ExecutionResult(status="ok")
+
+
+@pytest.fixture
+def parent():
+    return SimpleNamespace(code_executor=FakeExecutor())
+
+
+def output(console, source):
+    return json.loads(console.push(source))["output"]
+
+
+def test_sessions_keep_private_namespaces(parent):
+    first = SessionConsole(parent)
+    second = SessionConsole(parent)
+
+    first.push("step = 10")
+    second.push("step = 20")
+
+    assert output(first, "step") == "10"
+    assert output(second, "step") == "20"
+    # globals of the program stay reachable, session variables do not leak
+    assert output(first, "model") == "resnet"
+    assert "step" not in parent.code_executor.km.kernel.shell.user_ns
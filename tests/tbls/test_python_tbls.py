@@ -40,3 +40,15 @@ def test_probing_python_dict_list():
     assert df["float"][1] == "2.0"
     assert df["str"][0] == "str"
     assert df["str"][1] == "str2"
+
+
+def test_probing_python_threads():
+    import threading
+
+    import probing
+
+    df = probing.query(
+        "select name, state, is_main from python.threads where is_main = true"
+    )
+    assert len(df) == 1
+    assert df["name"][0] == threading.main_thread().name
@@ -6,10 +6,44 @@ use probing_proto::prelude::*;
 impl ApiClient {
     /// Execute SQL query
     pub async fn execute_query(&self, query: &str) -> Result<DataFrame> {
-        let request = Message::new(Query {
-            expr: query.to_string(),
-            ..Default::default()
-        });
+        let payload = self
+            .send_query(Query {
+                expr: query.to_string(),
+                ..Default::default()
+            })
+            .await?;
+
+        match payload {
+            QueryDataFormat::DataFrame(dataframe) => Ok(dataframe),
+            QueryDataFormat::Error(err) => Err(AppError::Api(err.to_string())),
+            _ => Err(AppError::Api("Bad Response: DataFrame is Expected.".to_string()))
+        }
+    }
+
+    /// Execute SQL query and return the first `page_size` rows, the rest is fetched with `fetch_page`.
+    /// `force` runs a query the server's query guard refuses as too heavy.
+    pub async fn execute_query_paged(&self, query: &str, page_size: usize, force: bool) -> Result<QueryPage> {
+        let payload = self
+            .send_query(Query {
+                expr: query.to_string(),
+                opts: Some(QueryOptions {
+                    page_size: Some(page_size),
+                    force,
+                    ..Default::default()
+                }),
+            })
+            .await?;
+        Self::expect_page(payload)
+    }
+
+    /// Fetch the page of a paginated result that `cursor` points to
+    pub async fn fetch_page(&self, cursor: &str) -> Result<QueryPage> {
+        let payload = self.send_query(Query::next_page(cursor.to_string())).await?;
+        Self::expect_page(payload)
+    }
+
+    async fn send_query(&self, query: Query) -> Result<QueryDataFormat> {
+        let request = Message::new(query);
 
         let request_body = serde_json::to_string(&request)
             .map_err(|e| AppError::Api(format!("Failed to serialize request: {}", e)))?;
@@ -17,10 +51,16 @@ impl ApiClient {
         let response = self.post_request_with_body("/query", request_body).await?;
 
         let msg: Message<QueryDataFormat> = Self::parse_json(&response)?;
+        Ok(msg.payload)
+    }
 
-        match msg.payload {
-            QueryDataFormat::DataFrame(dataframe) => Ok(dataframe),
-            _ => Err(AppError::Api("Bad Response: DataFrame is Expected.".to_string()))
+    fn expect_page(payload: QueryDataFormat) -> Result<QueryPage> {
+        match payload {
+            QueryDataFormat::Page(page) => Ok(page),
+            // statements without a result, e.g. SET
+            QueryDataFormat::Nil => Ok(QueryPage::default()),
+            QueryDataFormat::Error(err) => Err(AppError::Api(err.to_string())),
+            _ => Err(AppError::Api("Bad Response: Page is Expected.".to_string()))
         }
     }
 
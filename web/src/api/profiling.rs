@@ -28,8 +28,34 @@ impl ApiClient {
         Ok(result)
     }
 
-    /// Get flamegraph data
-    pub async fn get_flamegraph(&self, profiler_type: &str) -> Result<String> {
-        self.get_request(&format!("/apis/flamegraph/{}", profiler_type)).await
+    /// Get the flamegraph node at `path` with `depth` levels of callees
+    pub async fn get_flamegraph_tree(&self, profiler_type: &str, path: &[String], depth: usize) -> Result<FlameNode> {
+        let path = urlencoding::encode(&path.join(";")).into_owned();
+        let response = self
+            .get_request(&format!("/apis/flamegraph/{}/tree?path={}&depth={}", profiler_type, path, depth))
+            .await?;
+        Self::parse_json(&response)
+    }
+
+    /// Search flamegraph frames by name
+    pub async fn search_flamegraph(&self, profiler_type: &str, pattern: &str) -> Result<Vec<FlameMatch>> {
+        let pattern = urlencoding::encode(pattern).into_owned();
+        let response = self
+            .get_request(&format!("/apis/flamegraph/{}/search?q={}", profiler_type, pattern))
+            .await?;
+        Self::parse_json(&response)
+    }
+
+    /// Get the step-time waterfall of steps `from..=to`, the last `limit` of them
+    pub async fn get_waterfall(&self, from: Option<i64>, to: Option<i64>, limit: usize) -> Result<Vec<StepWaterfall>> {
+        let mut path = format!("/apis/waterfall?limit={}", limit);
+        if let Some(from) = from {
+            path.push_str(&format!("&from={}", from));
+        }
+        if let Some(to) = to {
+            path.push_str(&format!("&to={}", to));
+        }
+        let response = self.get_request(&path).await?;
+        Self::parse_json(&response)
     }
 }
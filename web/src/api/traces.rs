@@ -26,6 +26,12 @@ pub struct SpanInfo {
     pub name: String,
     pub start_timestamp: i64,
     pub end_timestamp: Option<i64>,
+    /// Nanoseconds, `None` while the span runs
+    #[serde(default)]
+    pub duration: Option<i64>,
+    /// Duration minus the time covered by children on the same thread
+    #[serde(default)]
+    pub self_time: Option<i64>,
     pub thread_id: i64,
     pub kind: Option<String>,
     pub location: Option<String>,
@@ -154,128 +160,12 @@ impl ApiClient {
         Ok(events)
     }
 
-    /// Build span tree structure, supports limiting count
+    /// Span trees of the latest trace events, built by the server with the
+    /// self time of each span
     pub async fn get_span_tree(&self, limit: Option<usize>) -> Result<Vec<SpanInfo>> {
-        let events = self.get_trace_events(limit).await?;
-
-        // Build span map from span_start events
-        let mut span_map: std::collections::HashMap<i64, SpanInfo> = std::collections::HashMap::new();
-        let mut root_spans: Vec<i64> = Vec::new();
-
-        for event in &events {
-            if event.record_type == "span_start" {
-                let span = SpanInfo {
-                    span_id: event.span_id,
-                    trace_id: event.trace_id,
-                    parent_id: event.parent_id,
-                    name: event.name.clone(),
-                    start_timestamp: event.timestamp,
-                    end_timestamp: None,
-                    thread_id: event.thread_id,
-                    kind: event.kind.clone(),
-                    location: event.location.clone(),
-                    attributes: event.attributes.clone(),
-                    children: Vec::new(),
-                    events: Vec::new(),
-                };
-
-                if event.parent_id.is_none() || event.parent_id == Some(-1) {
-                    root_spans.push(event.span_id);
-                }
-
-                span_map.insert(event.span_id, span);
-            } else if event.record_type == "span_end" {
-                if let Some(span) = span_map.get_mut(&event.span_id) {
-                    span.end_timestamp = Some(event.timestamp);
-                }
-            } else if event.record_type == "event" {
-                if let Some(span) = span_map.get_mut(&event.span_id) {
-                    span.events.push(EventInfo {
-                        name: event.name.clone(),
-                        timestamp: event.timestamp,
-                        attributes: event.event_attributes.clone(),
-                    });
-                }
-            }
-        }
-
-        // Build tree structure - process from deepest to shallowest
-        // Calculate depth for each span using iterative approach
-        let mut depth_map: std::collections::HashMap<i64, usize> = std::collections::HashMap::new();
-
-        // Initialize all root spans to depth 0
-        for root_id in &root_spans {
-            depth_map.insert(*root_id, 0);
-        }
-
-        // Iteratively calculate depths until no changes
-        let mut changed = true;
-        while changed {
-            changed = false;
-            for (span_id, span) in span_map.iter() {
-                if depth_map.contains_key(span_id) {
-                    continue; // Already calculated
-                }
-
-                if let Some(parent_id) = span.parent_id {
-                    if parent_id != -1 && depth_map.contains_key(&parent_id) {
-                        let parent_depth = depth_map[&parent_id];
-                        depth_map.insert(*span_id, parent_depth + 1);
-                        changed = true;
-                    }
-                } else {
-                    // Root span (should have been added already, but handle it)
-                    depth_map.insert(*span_id, 0);
-                    changed = true;
-                }
-            }
-        }
-
-        // Sort spans by depth (deepest first) so we process children before parents
-        let mut spans_to_process: Vec<(i64, usize)> = span_map.keys()
-            .map(|&id| (id, depth_map.get(&id).copied().unwrap_or(0)))
-            .collect();
-        spans_to_process.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by depth descending
-
-        // Process spans from deepest to shallowest
-        // This ensures that when we add a child to its parent, the child's children
-        // have already been added to the child
-        for (span_id, _depth) in spans_to_process {
-            let parent_id = span_map.get(&span_id)
-                .and_then(|span| span.parent_id)
-                .filter(|&pid| pid != -1);
-
-            if let Some(parent_id) = parent_id {
-                // Remove child from map and add to parent
-                if let Some(child) = span_map.remove(&span_id) {
-                    if let Some(parent) = span_map.get_mut(&parent_id) {
-                        parent.children.push(child);
-                    } else {
-                        // Parent not found (shouldn't happen if depth calculation is correct)
-                        // Put child back as orphan
-                        span_map.insert(span_id, child);
-                    }
-                }
-            }
-        }
-
-        // Collect root spans
-        let mut result = Vec::new();
-        for root_id in root_spans {
-            if let Some(span) = span_map.remove(&root_id) {
-                result.push(span);
-            }
-        }
-
-        // Add any remaining spans (orphans)
-        for (_, span) in span_map {
-            result.push(span);
-        }
-
-        // Sort by start timestamp
-        result.sort_by_key(|s| s.start_timestamp);
-
-        Ok(result)
+        let path = format!("/apis/pythonext/trace/span-tree?limit={}", limit.unwrap_or(0));
+        let response = self.get_request(&path).await?;
+        Self::parse_json(&response)
     }
 
     /// Get JSON data in Chrome tracing format
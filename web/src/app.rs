@@ -4,7 +4,7 @@ use dioxus_router::{Routable, Router};
 use crate::components::layout::AppLayout;
 use crate::pages::{
     analytics::Analytics, chrome_tracing::ChromeTracing, cluster::Cluster, dashboard::Dashboard,
-    profiling::Profiling, python::Python, stack::Stack, traces::Traces,
+    profiling::Profiling, python::Python, stack::Stack, traces::Traces, waterfall::Waterfall,
 };
 
 #[derive(Routable, Clone, PartialEq)]
@@ -24,6 +24,8 @@ pub enum Route {
     PythonPage {},
     #[route("/traces")]
     TracesPage {},
+    #[route("/waterfall")]
+    WaterfallPage {},
     #[route("/chrome-tracing")]
     ChromeTracingPage {},
 }
@@ -63,6 +65,11 @@ pub fn TracesPage() -> Element {
     rsx! { AppLayout { Traces {} } }
 }
 
+#[component]
+pub fn WaterfallPage() -> Element {
+    rsx! { AppLayout { Waterfall {} } }
+}
+
 #[component]
 pub fn ChromeTracingPage() -> Element {
     rsx! { AppLayout { ChromeTracing {} } }
@@ -1,5 +1,5 @@
 use dioxus::prelude::*;
-use probing_proto::prelude::CallFrame;
+use probing_proto::prelude::{CallFrame, SymbolStatus};
 use crate::components::value_list::ValueList;
 use crate::components::collapsible_card::CollapsibleCardWithIcon;
 use crate::components::icon::Icon;
@@ -7,8 +7,11 @@ use crate::components::icon::Icon;
 #[component]
 pub fn CallStackView(callstack: CallFrame) -> Element {
     match callstack {
-        CallFrame::CFrame { ip, file, func, lineno } => {
-            let key = format!("{ip}: {func} @ {file}: {lineno}");
+        CallFrame::CFrame { ip, file, func, lineno, status } => {
+            let key = match status {
+                SymbolStatus::Resolved => format!("{ip}: {func} @ {file}: {lineno}"),
+                _ => format!("{ip}: {func} @ {file}: {lineno} ({status})"),
+            };
             rsx! {
                 CollapsibleCardWithIcon {
                     title: key,
@@ -3,7 +3,15 @@ use probing_proto::prelude::{DataFrame, Ele};
 use crate::components::table_view::TableView;
 
 #[component]
-pub fn DataFrameView(df: DataFrame, #[props(optional)] on_row_click: Option<EventHandler<usize>>) -> Element {
+pub fn DataFrameView(
+    df: DataFrame,
+    #[props(optional)] on_row_click: Option<EventHandler<usize>>,
+    /// Rows of the whole result when `df` holds only the pages loaded so far
+    #[props(optional)] total: Option<usize>,
+    /// Load the next page, shown as a button while rows are missing
+    #[props(optional)] on_load_more: Option<EventHandler<()>>,
+) -> Element {
+    let loaded = df.len();
     let headers = use_memo(move || df.names.clone());
 
     let data = use_memo(move || {
@@ -30,5 +38,18 @@ pub fn DataFrameView(df: DataFrame, #[props(optional)] on_row_click: Option<Even
             .collect::<Vec<Vec<String>>>()
     });
 
-    rsx! { TableView { headers: headers.read().clone(), data: data.read().clone(), on_row_click } }
+    rsx! {
+        TableView { headers: headers.read().clone(), data: data.read().clone(), on_row_click }
+        if let Some(total) = total {
+            div { class: "flex items-center justify-between mt-2 text-sm text-gray-500",
+                span { "Showing {loaded} of {total} rows" }
+                if let (true, Some(on_load_more)) = (loaded < total, on_load_more) {
+                    button { class: "px-3 py-1 rounded bg-gray-100 hover:bg-gray-200 text-gray-700",
+                        onclick: move |_| on_load_more.call(()),
+                        "Load more"
+                    }
+                }
+            }
+        }
+    }
 }
@@ -140,6 +140,12 @@ pub fn Sidebar() -> Element {
                             label: "Traces",
                             is_active: route == Route::TracesPage {},
                         }
+                        SidebarNavItem {
+                            to: Route::WaterfallPage {},
+                            icon: &icondata::AiBarsOutlined,
+                            label: "Waterfall",
+                            is_active: route == Route::WaterfallPage {},
+                        }
                     }
 
                     div {
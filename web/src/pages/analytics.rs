@@ -5,7 +5,7 @@ use crate::components::page::{PageContainer, PageTitle};
 use crate::components::common::{LoadingState, ErrorState};
 use crate::hooks::{use_api, use_api_simple};
 use crate::api::ApiClient;
-use probing_proto::prelude::{DataFrame, Ele};
+use probing_proto::prelude::{DataFrame, Ele, QueryPage};
 
 #[component]
 pub fn Analytics() -> Element {
@@ -103,32 +103,62 @@ pub fn Analytics() -> Element {
     }
 }
 
+/// Rows fetched per request, further pages are loaded on demand
+const PAGE_SIZE: usize = 1000;
+
 #[component]
 fn SqlQueryPanel() -> Element {
     let mut sql = use_signal(|| String::new());
-    let query_state = use_api_simple::<DataFrame>();
+    let query_state = use_api_simple::<QueryPage>();
     let mut is_executing = use_signal(|| false);
+    let mut load_error = use_signal(|| None::<String>);
 
-    let execute_query = move |_| {
+    let mut run_query = move |force: bool| {
         let query = sql.read().clone();
         if query.trim().is_empty() {
             return;
         }
 
         *is_executing.write() = true;
+        *load_error.write() = None;
         let mut loading = query_state.loading;
         let mut data = query_state.data;
         let query_clone = query.clone();
         spawn(async move {
             *loading.write() = true;
             let client = ApiClient::new();
-            let result = client.execute_query(&query_clone).await;
+            let result = client.execute_query_paged(&query_clone, PAGE_SIZE, force).await;
             *data.write() = Some(result);
             *loading.write() = false;
             *is_executing.write() = false;
         });
     };
 
+    let load_more = EventHandler::new(move |_| {
+        let mut data = query_state.data;
+        let cursor = match data.read().as_ref() {
+            Some(Ok(page)) => page.next_cursor.clone(),
+            _ => None,
+        };
+        let Some(cursor) = cursor else { return };
+        *is_executing.write() = true;
+        spawn(async move {
+            let client = ApiClient::new();
+            match client.fetch_page(&cursor).await {
+                Ok(next) => {
+                    if let Some(Ok(page)) = data.write().as_mut() {
+                        if let Err(err) = page.df.extend(next.df) {
+                            *load_error.write() = Some(err.to_string());
+                        }
+                        page.next_cursor = next.next_cursor;
+                    }
+                }
+                Err(err) => *load_error.write() = Some(format!("{:?}", err)),
+            }
+            *is_executing.write() = false;
+        });
+    });
+
     rsx! {
         div {
             class: "space-y-4",
@@ -144,16 +174,32 @@ fn SqlQueryPanel() -> Element {
             button {
                 class: format!("px-6 py-2 bg-indigo-600 text-white rounded-md font-medium hover:bg-indigo-700 transition-colors shadow-sm {}", if *is_executing.read() { "opacity-50 cursor-not-allowed" } else { "" }),
                 disabled: *is_executing.read(),
-                onclick: execute_query,
+                onclick: move |_| run_query(false),
                 if *is_executing.read() { "Running..." } else { "Run Query" }
             }
 
             if query_state.is_loading() {
                 LoadingState { message: Some("Running query...".to_string()) }
-            } else if let Some(Ok(df)) = query_state.data.read().as_ref() {
-                DataFrameView { df: df.clone(), on_row_click: None }
+            } else if let Some(Ok(page)) = query_state.data.read().as_ref() {
+                DataFrameView {
+                    df: page.df.clone(),
+                    on_row_click: None,
+                    total: page.total,
+                    on_load_more: (!*is_executing.read()).then_some(load_more),
+                }
             } else if let Some(Err(err)) = query_state.data.read().as_ref() {
                 ErrorState { error: format!("{:?}", err), title: None }
+                if format!("{:?}", err).contains("query guard") {
+                    button {
+                        class: "px-4 py-2 border border-red-300 text-red-700 rounded-md text-sm hover:bg-red-50",
+                        onclick: move |_| run_query(true),
+                        "Run anyway"
+                    }
+                }
+            }
+
+            if let Some(err) = load_error.read().as_ref() {
+                ErrorState { error: err.clone(), title: Some("Failed to load more rows".to_string()) }
             }
         }
     }
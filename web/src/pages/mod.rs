@@ -6,3 +6,4 @@ pub mod profiling;
 pub mod python;
 pub mod stack;
 pub mod traces;
+pub mod waterfall;
@@ -6,6 +6,13 @@ use crate::api::{ApiClient, ProfileResponse};
 use crate::app::{PROFILING_VIEW, PROFILING_PPROF_FREQ, PROFILING_TORCH_ENABLED,
     PROFILING_CHROME_LIMIT, PROFILING_PYTORCH_TIMELINE_RELOAD, PROFILING_RAY_TIMELINE_RELOAD};
 use crate::pages::chrome_tracing::get_tracing_viewer_html;
+use probing_proto::prelude::{FlameMatch, FlameNode};
+
+/// Levels of the flamegraph fetched per request; deeper frames load on click
+const FLAME_DEPTH: usize = 8;
+
+/// Frames narrower than this fraction of the view are not drawn
+const FLAME_MIN_WIDTH: f64 = 0.002;
 
 fn apply_config(config: &[(String, String)]) {
     *PROFILING_PPROF_FREQ.write() = 0;
@@ -34,7 +41,9 @@ pub fn Profiling() -> Element {
     let chrome_iframe_key = use_signal(|| 0);
 
     let config_state = use_api_simple::<Vec<(String, String)>>();
-    let flamegraph_state = use_api_simple::<String>();
+    let flamegraph_state = use_api_simple::<FlameNode>();
+    let flame_search_state = use_api_simple::<Vec<FlameMatch>>();
+    let mut flame_path = use_signal(Vec::<String>::new);
     let chrome_tracing_state = use_api_simple::<String>();
     let pytorch_profile_state = use_api_simple::<ProfileResponse>();
     let ray_timeline_state = use_api_simple::<String>(); // Changed to String for Chrome format JSON
@@ -68,10 +77,17 @@ pub fn Profiling() -> Element {
         });
     });
 
+    // Switching profilers starts again from the root frame
+    use_effect(move || {
+        let _ = PROFILING_VIEW.read();
+        flame_path.write().clear();
+    });
+
     use_effect(move || {
         let view = PROFILING_VIEW.read().clone();
         let pprof_on = *PROFILING_PPROF_FREQ.read() > 0;
         let torch = *PROFILING_TORCH_ENABLED.read();
+        let path = flame_path.read().clone();
 
         let active_profiler = match view.as_str() {
             "pprof" if pprof_on => "pprof",
@@ -84,7 +100,7 @@ pub fn Profiling() -> Element {
         spawn(async move {
             *loading.write() = true;
             let client = ApiClient::new();
-            let result = client.get_flamegraph(active_profiler).await;
+            let result = client.get_flamegraph_tree(active_profiler, &path, FLAME_DEPTH).await;
             *data.write() = Some(result);
             *loading.write() = false;
         });
@@ -210,6 +226,8 @@ pub fn Profiling() -> Element {
                         rsx! {
                             FlamegraphView {
                                 flamegraph_state: flamegraph_state.clone(),
+                                search_state: flame_search_state.clone(),
+                                flame_path: flame_path.clone(),
                             }
                         }
                     } else if current_view == "trace-timeline" || current_view == "pytorch-timeline" {
@@ -238,8 +256,11 @@ pub fn Profiling() -> Element {
 
 #[component]
 fn FlamegraphView(
-    #[props] flamegraph_state: crate::hooks::ApiState<String>
+    #[props] flamegraph_state: crate::hooks::ApiState<FlameNode>,
+    #[props] search_state: crate::hooks::ApiState<Vec<FlameMatch>>,
+    #[props] flame_path: Signal<Vec<String>>,
 ) -> Element {
+    let search = use_signal(String::new);
     let pprof_enabled = *PROFILING_PPROF_FREQ.read() > 0;
     let torch_enabled = *PROFILING_TORCH_ENABLED.read();
     let current_view = PROFILING_VIEW.read().clone();
@@ -268,13 +289,28 @@ fn FlamegraphView(
         };
     }
 
-    if let Some(Ok(flamegraph)) = flamegraph_state.data.read().as_ref() {
+    if let Some(Ok(root)) = flamegraph_state.data.read().as_ref() {
+        if root.total == 0 {
+            return rsx! {
+                EmptyState { message: "No samples collected yet.".to_string() }
+            };
+        }
         return rsx! {
             div {
-                class: "absolute inset-0 w-full h-full",
+                class: "absolute inset-0 w-full h-full flex flex-col p-4 gap-3",
+                FlameToolbar {
+                    profiler: profiler_name.to_string(),
+                    search,
+                    search_state,
+                    flame_path,
+                }
                 div {
-                    class: "w-full h-full",
-                    dangerous_inner_html: "{flamegraph}"
+                    class: "flex-1 overflow-auto",
+                    FlameLevels {
+                        root: root.clone(),
+                        highlight: search.read().to_lowercase(),
+                        flame_path,
+                    }
                 }
             }
         };
@@ -292,6 +328,212 @@ fn FlamegraphView(
     rsx! { div {} }
 }
 
+/// Breadcrumbs of the zoomed frame and frame search
+#[component]
+fn FlameToolbar(
+    profiler: String,
+    mut search: Signal<String>,
+    search_state: crate::hooks::ApiState<Vec<FlameMatch>>,
+    mut flame_path: Signal<Vec<String>>,
+) -> Element {
+    let path = flame_path.read().clone();
+    let results = search_state.data;
+    let run_search = {
+        let profiler = profiler.clone();
+        move || {
+            let pattern = search.read().trim().to_string();
+            let mut loading = search_state.loading;
+            let mut data = search_state.data;
+            if pattern.is_empty() {
+                *data.write() = None;
+                return;
+            }
+            let profiler = profiler.clone();
+            spawn(async move {
+                *loading.write() = true;
+                let client = ApiClient::new();
+                let result = client.search_flamegraph(&profiler, &pattern).await;
+                *data.write() = Some(result);
+                *loading.write() = false;
+            });
+        }
+    };
+    let on_enter = run_search.clone();
+    let on_click = run_search;
+
+    rsx! {
+        div {
+            class: "flex flex-wrap items-center gap-2 text-sm",
+            button {
+                class: "text-blue-600 hover:underline",
+                onclick: move |_| flame_path.write().clear(),
+                "all"
+            }
+            for (idx, name) in path.iter().enumerate() {
+                span { key: "{idx}", class: "text-gray-400", "›" }
+                button {
+                    class: "text-blue-600 hover:underline font-mono truncate max-w-xs",
+                    title: "{name}",
+                    onclick: move |_| flame_path.write().truncate(idx + 1),
+                    "{name}"
+                }
+            }
+            div {
+                class: "ml-auto flex items-center gap-2",
+                input {
+                    class: "px-2 py-1 border border-gray-300 rounded",
+                    r#type: "text",
+                    placeholder: "Search frames",
+                    value: "{search}",
+                    oninput: move |e| *search.write() = e.value(),
+                    onkeydown: move |e| {
+                        if e.key() == Key::Enter {
+                            on_enter();
+                        }
+                    },
+                }
+                button {
+                    class: "px-3 py-1 bg-blue-600 text-white rounded hover:bg-blue-700",
+                    onclick: move |_| on_click(),
+                    "Search"
+                }
+                a {
+                    class: "px-3 py-1 border border-gray-300 rounded hover:bg-gray-50",
+                    href: "/apis/flamegraph/{profiler}",
+                    "SVG"
+                }
+            }
+        }
+        {
+            match results.read().as_ref() {
+                Some(Ok(matches)) if matches.is_empty() => rsx! {
+                    div { class: "text-sm text-gray-500", "No matching frames" }
+                },
+                Some(Ok(matches)) => rsx! {
+                    div {
+                        class: "max-h-40 overflow-auto border border-gray-200 rounded text-sm",
+                        for (idx, m) in matches.iter().enumerate() {
+                            {
+                                let target = m.path.clone();
+                                let label = m.path.join(" › ");
+                                let total = m.total;
+                                rsx! {
+                                    button {
+                                        key: "{idx}",
+                                        class: "block w-full text-left px-2 py-1 hover:bg-yellow-50 font-mono truncate",
+                                        title: "{label}",
+                                        onclick: move |_| *flame_path.write() = target.clone(),
+                                        "{total} · {label}"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                },
+                Some(Err(err)) => rsx! {
+                    div { class: "text-sm text-red-600", "Search failed: {err:?}" }
+                },
+                None => rsx! {},
+            }
+        }
+    }
+}
+
+/// Frame drawn in the flamegraph, positioned as fractions of the view width
+#[derive(Clone, PartialEq)]
+struct FlameBox {
+    /// Frames from the zoomed frame's child down to this frame
+    path: Vec<String>,
+    name: String,
+    total: u64,
+    self_value: u64,
+    truncated: bool,
+    offset: f64,
+    width: f64,
+}
+
+/// Lay out the callees of `root`, one row per call depth
+fn flame_levels(root: &FlameNode) -> Vec<Vec<FlameBox>> {
+    fn visit(node: &FlameNode, path: &mut Vec<String>, depth: usize, offset: f64, scale: f64, levels: &mut Vec<Vec<FlameBox>>) {
+        let mut offset = offset;
+        for child in &node.children {
+            let width = child.total as f64 * scale;
+            if width >= FLAME_MIN_WIDTH {
+                path.push(child.name.clone());
+                if levels.len() <= depth {
+                    levels.push(vec![]);
+                }
+                levels[depth].push(FlameBox {
+                    path: path.clone(),
+                    name: child.name.clone(),
+                    total: child.total,
+                    self_value: child.self_value,
+                    truncated: child.truncated,
+                    offset,
+                    width,
+                });
+                visit(child, path, depth + 1, offset, scale, levels);
+                path.pop();
+            }
+            offset += width;
+        }
+    }
+
+    let mut levels = vec![];
+    if root.total > 0 {
+        visit(root, &mut vec![], 0, 0.0, 1.0 / root.total as f64, &mut levels);
+    }
+    levels
+}
+
+/// Warm color derived from the frame name, so frames keep their color across zooms
+fn flame_color(name: &str) -> String {
+    let hash = name.bytes().fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32));
+    format!("hsl({}, 80%, {}%)", hash % 50, 55 + (hash / 50) % 15)
+}
+
+/// Icicle rendering of the zoomed frame; clicking a frame zooms into it
+#[component]
+fn FlameLevels(root: FlameNode, highlight: String, mut flame_path: Signal<Vec<String>>) -> Element {
+    let levels = flame_levels(&root);
+    let total = root.total;
+
+    rsx! {
+        div {
+            class: "relative w-full h-6 mb-px rounded-sm bg-orange-300 text-xs font-mono px-1 leading-6 truncate",
+            title: "{root.name}: {total}",
+            "{root.name} ({total})"
+        }
+        for (depth, level) in levels.into_iter().enumerate() {
+            div {
+                key: "{depth}",
+                class: "relative w-full h-6 mb-px",
+                for (idx, frame) in level.into_iter().enumerate() {
+                    {
+                        let matched = !highlight.is_empty() && frame.name.to_lowercase().contains(&highlight);
+                        let background = if matched { "#facc15".to_string() } else { flame_color(&frame.name) };
+                        let marker = if frame.truncated { " ▸" } else { "" };
+                        let percent = frame.total as f64 * 100.0 / total as f64;
+                        let left = frame.offset * 100.0;
+                        let width = frame.width * 100.0;
+                        let FlameBox { path: target, name, total: frame_total, self_value, .. } = frame;
+                        rsx! {
+                            div {
+                                key: "{idx}",
+                                class: "absolute h-6 rounded-sm text-xs font-mono px-1 leading-6 truncate cursor-pointer border-r border-white hover:brightness-110",
+                                style: "left: {left}%; width: {width}%; background: {background};",
+                                title: "{name}\ntotal: {frame_total} ({percent:.2}%)\nself: {self_value}",
+                                onclick: move |_| flame_path.write().extend(target.iter().cloned()),
+                                "{name}{marker}"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 fn ChromeTracingView(
     #[props] chrome_tracing_state: crate::hooks::ApiState<String>,
@@ -8,6 +8,7 @@ use crate::api::{ApiClient, SpanInfo, EventInfo};
 #[component]
 pub fn Traces() -> Element {
     let limit = use_signal(|| 400usize);
+    let mut by_self_time = use_signal(|| false);
     let state = use_api_simple::<Vec<SpanInfo>>();
 
     // Create dependency, recalculate when limit changes
@@ -82,6 +83,15 @@ pub fn Traces() -> Element {
 
             Card {
                 title: "Span Tree",
+                label {
+                    class: "flex items-center gap-2 text-sm text-gray-600 mb-2",
+                    input {
+                        r#type: "checkbox",
+                        checked: *by_self_time.read(),
+                        onchange: move |ev| *by_self_time.write() = ev.checked(),
+                    }
+                    "Sort by self time"
+                }
                 if state.is_loading() {
                     LoadingState { message: Some("Loading trace data...".to_string()) }
                 } else if let Some(Ok(spans)) = state.data.read().as_ref() {
@@ -93,8 +103,8 @@ pub fn Traces() -> Element {
                     } else {
                         div {
                             class: "space-y-4",
-                            for span in spans.iter() {
-                                SpanView { span: span.clone(), depth: 0 }
+                            for span in sorted_spans(spans, *by_self_time.read()) {
+                                SpanView { span, depth: 0 }
                             }
                         }
                     }
@@ -106,12 +116,23 @@ pub fn Traces() -> Element {
     }
 }
 
+/// Spans in start order, or with the largest self time first at every level
+fn sorted_spans(spans: &[SpanInfo], by_self_time: bool) -> Vec<SpanInfo> {
+    let mut spans = spans.to_vec();
+    if by_self_time {
+        spans.sort_by_key(|s| std::cmp::Reverse(s.self_time.unwrap_or(0)));
+        for span in spans.iter_mut() {
+            span.children = sorted_spans(&span.children, true);
+        }
+    }
+    spans
+}
+
 #[component]
 fn SpanView(span: SpanInfo, depth: usize) -> Element {
     let indent = depth * 24;
-    let duration = span.end_timestamp
-        .map(|end| (end - span.start_timestamp) as f64 / 1_000_000_000.0)
-        .unwrap_or(0.0);
+    let duration = span.duration.unwrap_or(0) as f64 / 1_000_000_000.0;
+    let self_time = span.self_time.map(|t| t as f64 / 1_000_000_000.0);
 
     let mut expanded = use_signal(|| depth < 2); // Auto-expand first 2 levels
 
@@ -170,6 +191,12 @@ fn SpanView(span: SpanInfo, depth: usize) -> Element {
                     class: "text-sm font-mono text-green-600",
                     "{duration:.3}s"
                 }
+                if let Some(self_time) = self_time {
+                    span {
+                        class: "text-sm font-mono text-gray-500",
+                        "self {self_time:.3}s"
+                    }
+                }
             }
 
             if *expanded.read() {
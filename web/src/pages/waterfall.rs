@@ -0,0 +1,157 @@
+use dioxus::prelude::*;
+use crate::components::card::Card;
+use crate::components::page::{PageContainer, PageTitle};
+use crate::components::common::{LoadingState, ErrorState};
+use crate::hooks::use_api_simple;
+use crate::api::ApiClient;
+use probing_proto::prelude::{StepPhase, StepWaterfall};
+
+/// Steps fetched when no range is given
+const DEFAULT_STEPS: usize = 20;
+
+#[component]
+pub fn Waterfall() -> Element {
+    let mut from = use_signal(|| String::new());
+    let mut to = use_signal(|| String::new());
+    let state = use_api_simple::<Vec<StepWaterfall>>();
+
+    let mut load = move || {
+        let from = from.peek().trim().parse::<i64>().ok();
+        let to = to.peek().trim().parse::<i64>().ok();
+        let mut loading = state.loading;
+        let mut data = state.data;
+        spawn(async move {
+            *loading.write() = true;
+            let client = ApiClient::new();
+            let result = client.get_waterfall(from, to, DEFAULT_STEPS).await;
+            *data.write() = Some(result);
+            *loading.write() = false;
+        });
+    };
+
+    use_effect(move || load());
+
+    rsx! {
+        PageContainer {
+            PageTitle {
+                title: "Step Waterfall".to_string(),
+                subtitle: Some("Where the time of every training step goes".to_string()),
+                icon: Some(&icondata::AiBarsOutlined),
+            }
+            Card {
+                title: "Steps",
+                div {
+                    class: "flex items-center gap-3",
+                    input {
+                        class: "w-32 px-3 py-2 rounded border border-gray-300 text-sm",
+                        placeholder: "from step",
+                        value: "{from}",
+                        oninput: move |ev| *from.write() = ev.value(),
+                    }
+                    input {
+                        class: "w-32 px-3 py-2 rounded border border-gray-300 text-sm",
+                        placeholder: "to step",
+                        value: "{to}",
+                        oninput: move |ev| *to.write() = ev.value(),
+                    }
+                    button {
+                        class: "px-4 py-2 bg-indigo-600 text-white rounded-md text-sm font-medium hover:bg-indigo-700",
+                        onclick: move |_| load(),
+                        "Load"
+                    }
+                    span { class: "text-xs text-gray-500", "Without a range, the last {DEFAULT_STEPS} steps" }
+                }
+            }
+            Card {
+                title: "Waterfall",
+                if state.is_loading() {
+                    LoadingState { message: Some("Loading steps...".to_string()) }
+                } else if let Some(Ok(steps)) = state.data.read().as_ref() {
+                    if steps.is_empty() {
+                        div {
+                            class: "text-center py-8 text-gray-500",
+                            "No steps recorded. Enable torch.profiling, the trainer integration or a torch.profiler"
+                        }
+                    } else {
+                        StepChart { steps: steps.clone() }
+                    }
+                } else if let Some(Err(err)) = state.data.read().as_ref() {
+                    ErrorState { error: format!("{:?}", err), title: None }
+                }
+            }
+        }
+    }
+}
+
+/// Tailwind background of the bars of `phase`
+fn phase_color(phase: StepPhase) -> &'static str {
+    match phase {
+        StepPhase::DataLoad => "bg-amber-400",
+        StepPhase::H2d => "bg-orange-500",
+        StepPhase::Forward => "bg-blue-500",
+        StepPhase::Backward => "bg-indigo-500",
+        StepPhase::Optimizer => "bg-emerald-500",
+        StepPhase::Comm => "bg-rose-500",
+    }
+}
+
+fn millis(nanos: i64) -> String {
+    format!("{:.3} ms", nanos as f64 / 1e6)
+}
+
+/// Steps on a common time axis, one lane per phase
+#[component]
+fn StepChart(steps: Vec<StepWaterfall>) -> Element {
+    let range = steps.iter().map(|s| s.duration).max().unwrap_or(0).max(1) as f64;
+
+    rsx! {
+        div {
+            class: "space-y-4",
+            div {
+                class: "flex flex-wrap gap-4 text-xs text-gray-600",
+                for phase in StepPhase::ALL {
+                    span {
+                        class: "flex items-center gap-1",
+                        span { class: "inline-block w-3 h-3 rounded {phase_color(phase)}" }
+                        "{phase.as_str()}"
+                    }
+                }
+            }
+            for step in steps {
+                div {
+                    key: "{step.step}",
+                    class: "border-t border-gray-100 pt-2",
+                    div {
+                        class: "flex justify-between text-sm mb-1",
+                        span { class: "font-medium text-gray-800", "step {step.step}" }
+                        span {
+                            class: "font-mono text-gray-500",
+                            "{millis(step.duration)}"
+                            if step.comm_overlap > 0 {
+                                " • comm overlapped {millis(step.comm_overlap)}"
+                            }
+                        }
+                    }
+                    for segment in step.segments.iter() {
+                        div {
+                            class: "flex items-center gap-2",
+                            span { class: "w-20 text-xs text-gray-500", "{segment.phase.as_str()}" }
+                            div {
+                                class: "relative flex-1 h-3 bg-gray-50",
+                                div {
+                                    class: "absolute h-3 rounded {phase_color(segment.phase)}",
+                                    style: format!(
+                                        "left:{:.3}%;width:{:.3}%",
+                                        segment.start as f64 * 100.0 / range,
+                                        ((segment.end - segment.start) as f64 * 100.0 / range).max(0.2),
+                                    ),
+                                    title: "{segment.phase.as_str()}: {millis(segment.busy)} busy from {millis(segment.start)} to {millis(segment.end)}",
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}